@@ -0,0 +1,26 @@
+use hourglass::hourglass::clickhouse_api::{datatype::clickhouse_trade_data::MarketTrade, queries_operations::ClickHouseClient};
+use std::time::Instant;
+
+#[tokio::main]
+async fn main()
+{
+    let client = ClickHouseClient::new();
+    let database = "synthetic_futures_trades";
+    let table = "synthetic_futures_trades_2024_05_05_ETHUSDT";
+
+    // 生成一段模拟的成交流
+    let trades: Vec<MarketTrade> = (0..10_000).map(|i| MarketTrade { exchange: "synthetic".to_string(),
+                                                                      symbol: "ETHUSDT".to_string(),
+                                                                      side: if i % 2 == 0 { "buy".to_string() } else { "sell".to_string() },
+                                                                      price: 3000.0 + (i % 100) as f64 * 0.1,
+                                                                      timestamp: 1714867200000 + i * 100,
+                                                                      amount: 0.01 * (1 + i % 10) as f64 })
+                                              .collect();
+
+    println!("Inserting {} trades into {}.{}...", trades.len(), database, table);
+    let start_time = Instant::now();
+    match client.insert_trades_batched(database, table, &trades, 2_000, true).await {
+        | Ok(written) => println!("{} trades inserted in: {:?}", written, start_time.elapsed()),
+        | Err(err) => eprintln!("Error inserting trades in {:?}: {:?}", start_time.elapsed(), err),
+    }
+}
@@ -83,7 +83,7 @@ use hourglass::{
     common::{
         account_positions::{exited_positions::AccountExitedPositions, AccountPositions, PositionDirectionMode, PositionMarginMode},
         balance::Balance,
-        instrument::{kind::InstrumentKind, Instrument},
+        instrument::{alias::InstrumentAliasRegistry, kind::InstrumentKind, Instrument},
         order::{
             identification::{client_order_id::ClientOrderId, OrderId},
             order_instructions::OrderInstruction,
@@ -96,7 +96,8 @@ use hourglass::{
     },
     hourglass::{
         account::{
-            account_config::{AccountConfig, CommissionLevel, HourglassMode, MarginMode},
+            account_commission::FeesBookCommission,
+            account_config::{AccountConfig, CommissionLevel, CostBasisMethod, FillPricePolicy, FilterViolationHandling, HourglassMode, InvariantCheckMode, MakerFillTrigger, MarginMode, NetModeOppositeBehavior, RoundingConfig, SlippageModel, SpreadLegRisk, UnsizedTradeHandling},
             account_latency::{AccountLatency, FluctuationMode},
             account_orders::AccountOrders,
             HourglassAccount,
@@ -224,7 +225,38 @@ async fn main()
                                                    execution_mode: HourglassMode::Backtest,
                                                    max_price_deviation: 0.1,
                                                    lazy_account_positions: false,
-                                                   liquidation_threshold: 0.9 };
+                                                   liquidation_threshold: 0.9,
+                                                   fill_price_policy: FillPricePolicy::RestingLimit,
+                                                   warmup_until_ts: None,
+                                                   order_to_trade_limit: None,
+                                                   max_open_orders_per_instrument: None,
+                                                   instrument_aliases: InstrumentAliasRegistry::default(),
+                                                   leveraged_token_daily_fee_rate: None,
+                                                   rounding: RoundingConfig::default(),
+                                                   invariant_check: InvariantCheckMode::Off,
+                                                   maker_fill_trigger: MakerFillTrigger::OnTradeThrough,
+                                                   contract_multipliers: Vec::new(),
+                                                   machine_id: None,
+                                                   seed: None,
+                                                   cost_basis_method: CostBasisMethod::Average,
+                                                   cancel_latency_ms: None,
+                                                   synthetic_depth: None,
+                                                   own_fills_on_tape: false,
+                                                   margin_update: None,
+                                                   matching_algorithms: Vec::new(),
+                                                   initial_margin_rates: Vec::new(),
+                                                   net_mode_opposite_behavior: NetModeOppositeBehavior::Reject,
+                                                   withdrawal_rules: Vec::new(),
+                                                   stale_order_policy: None,
+                                                   unsized_trade_handling: UnsizedTradeHandling::TouchOnly,
+                                                   spread_leg_risk: SpreadLegRisk::Reject,
+                                                   slippage_model: SlippageModel::None,
+                                                   funding_interval_ms: None,
+                                                   volume_tiers: Vec::new(),
+                                                   maintenance_margin_rate: None,
+                                                   instrument_filters: Vec::new(),
+                                                   filter_violation: FilterViolationHandling::Reject,
+                                                   trade_history_limit: None };
 
     // initialise the tokens possibly to be traded
     let mut instruments: Vec<Instrument> = vec![];
@@ -245,16 +277,23 @@ async fn main()
                                                              client_trade_counter: 0.into(),
                                                              exchange_timestamp: AtomicI64::new(0),
                                                              config: hourglass_account_config,
-                                                             account_open_book: Arc::new(RwLock::new(AccountOrders::new(0, instruments, AccountLatency { fluctuation_mode: FluctuationMode::Sine,
-                                                                                                                                                         maximum: 100,
-                                                                                                                                                         minimum: 2,
-                                                                                                                                                         current_value: 0 }).await)),
+                                                             account_open_book: Arc::new(RwLock::new(AccountOrders::new(0, instruments, AccountLatency::new(FluctuationMode::Sine, 100, 2, 0)).await)),
                                                              single_level_order_book: Arc::new(Mutex::new(single_level_order_books)),
                                                              balances: token_balances,
                                                              positions,
                                                              exited_positions: closed_positions,
                                                              account_event_tx,
-                                                             account_margin: Arc::new(Default::default()) }));
+                                                             account_margin: Arc::new(Default::default()),
+                                                             order_flow: Default::default(),
+                                                             traded_volume: Default::default(),
+                                                             spot_cost_basis: DashMap::new(),
+                                                             depth_order_books: Arc::new(Mutex::new(HashMap::new())),
+                                                             own_fill_prints: Vec::new(),
+                                                             trade_history: HashMap::new(),
+                                                             last_margin_update: None,
+                                                             last_funding_ts: None,
+                                                             commission_provider: Arc::new(FeesBookCommission),
+                                                             matching_engines: HashMap::new() }));
 
     // Sample cursor building
     let clickhouse_client = ClickHouseClient::new();
@@ -344,7 +383,8 @@ pub async fn order_parser(client: &HourglassClient, trade: &MarketTrade, order_i
                                         side: monk_order.side,                                                         // 买卖方向
                                         state: RequestOpen { reduce_only: false,
                                                              price: monk_order.price,
-                                                             size: monk_order.size } };
+                                                             size: monk_order.size,
+                                                             tag: None } };
 
                     let new_orders = client.open_orders(vec![order]).await;
                     info!("The new orders are : {:?}", &new_orders);
@@ -25,6 +25,8 @@ pub struct PositionExit
     pub realised_pnl: f64,                 // 退出后实现的盈亏。
     pub liquidation_price: f64,            // 退出平仓时的价格
     pub exit_isolated_margin: Option<f64>, // 平仓时的保证金
+    #[serde(default)]
+    pub funding_pnl: f64, // 持仓期间累计的资金费，与 realised_pnl 分开归因
 }
 
 #[allow(dead_code)]
@@ -44,9 +46,13 @@ impl PositionExit
     {
         // 计算退出时的总价值（不考虑费用）
         let exit_quantity = position_meta.current_size;
-        let exit_value_gross = exit_quantity * position_meta.current_symbol_price;
-        // 计算实现盈亏 (realised_pnl)
-        let realised_pnl = (position_meta.current_symbol_price - position_meta.current_avg_price) * exit_quantity;
+        let exit_value_gross = exit_quantity * position_meta.current_symbol_price * position_meta.contract_multiplier;
+        // 计算实现盈亏 (realised_pnl)，空头在价格下跌时盈利
+        let direction = match position_meta.side {
+            | Side::Buy => 1.0,
+            | Side::Sell => -1.0,
+        };
+        let realised_pnl = (position_meta.current_symbol_price - position_meta.current_avg_price) * exit_quantity * position_meta.contract_multiplier * direction;
 
         // 创建 `PositionExit`
         PositionExit { exchange: position_meta.exchange.clone(),       // 从 PositionMeta 获取静态数据
@@ -61,6 +67,7 @@ impl PositionExit
                        exit_value_gross,                                            // 平仓时的总价值
                        realised_pnl,
                        liquidation_price: position_meta.current_symbol_price,
-                       exit_isolated_margin }
+                       exit_isolated_margin,
+                       funding_pnl: position_meta.funding_pnl }
     }
 }
@@ -150,7 +150,9 @@ mod tests
                                                                  current_symbol_price: 61_000.0,
                                                                  current_avg_price: 50_000.0,
                                                                  unrealised_pnl: 11_000.0,
-                                                                 realised_pnl: 0.0 },
+                                                                 realised_pnl: 0.0,
+                                                                 contract_multiplier: 1.0,
+                                                                 funding_pnl: 0.0 },
                                             pos_config: FuturePositionConfig { pos_margin_mode: PositionMarginMode::Cross,
                                                                                leverage: 1.0,
                                                                                position_direction_mode: PositionDirectionMode::LongShort },
@@ -5,10 +5,45 @@ use crate::{
     hourglass::config_request::ConfigurationRequest,
 };
 
+/// 一天的毫秒数，杠杆代币管理费按天计提。
+pub const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// 管理费的计提周期。账户按距上次计提经过的整数个周期计提，不足一个周期的时间留到下次，
+/// 因此计提结果只取决于经过的时间，与行情的频率无关。
+pub const MANAGEMENT_FEE_TICK_MS: i64 = 3_600_000;
+
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct LeveragedTokenPosition
 {
     pub meta: PositionMeta,
+    /// 上一次计提管理费的时间戳，尚未计提时为 `None`，此时从 `meta.enter_ts` 开始计算。
+    #[serde(default)]
+    pub last_fee_accrual_ts: Option<i64>,
+}
+
+impl LeveragedTokenPosition
+{
+    /// 按每日费率计提杠杆代币的管理费，返回本次计提的费用（以计价货币计）。
+    ///
+    /// 真实的杠杆代币每天收取管理/再平衡费用，持仓价值随时间按复利衰减：
+    /// 经过 `days` 天后价值变为 `value * (1 - daily_rate)^days`，差额即为管理费。
+    /// 费用累加到 `current_fees_total`，并从 `realised_pnl` 中扣除；余额的扣减由账户层负责。
+    pub fn accrue_management_fee(&mut self, now_ts: i64, daily_rate: f64) -> f64
+    {
+        let last_ts = self.last_fee_accrual_ts.unwrap_or(self.meta.enter_ts);
+        self.last_fee_accrual_ts = Some(now_ts.max(last_ts));
+        if now_ts <= last_ts || daily_rate <= 0.0 {
+            return 0.0;
+        }
+
+        let days = (now_ts - last_ts) as f64 / MILLIS_PER_DAY as f64;
+        let value = self.meta.current_size * self.meta.current_symbol_price * self.meta.contract_multiplier;
+        let fee = value * (1.0 - (1.0 - daily_rate).powf(days));
+
+        self.meta.current_fees_total += fee;
+        self.meta.realised_pnl -= fee;
+        fee
+    }
 }
 
 #[allow(dead_code)]
@@ -29,3 +64,66 @@ impl From<ConfigurationRequest> for LeveragedTokenPositionConfig
                                        position_mode: config_request.position_direction_mode.unwrap()  /* 提供默认值或根据需求处理 None */ }
     }
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            account_positions::position_id::PositionId,
+            instrument::{kind::InstrumentKind, Instrument},
+            Side,
+        },
+        Exchange,
+    };
+
+    fn create_test_leveraged_token_position(size: f64, price: f64) -> LeveragedTokenPosition
+    {
+        LeveragedTokenPosition { meta: PositionMeta { position_id: PositionId(1),
+                                                      enter_ts: 0,
+                                                      update_ts: 0,
+                                                      exchange: Exchange::Hourglass,
+                                                      instrument: Instrument::new("BTC3L", "USDT", InstrumentKind::CryptoLeveragedToken),
+                                                      side: Side::Buy,
+                                                      current_size: size,
+                                                      current_fees_total: 0.0,
+                                                      current_avg_price_gross: price,
+                                                      current_symbol_price: price,
+                                                      current_avg_price: price,
+                                                      unrealised_pnl: 0.0,
+                                                      realised_pnl: 0.0,
+                                                      contract_multiplier: 1.0,
+                                                      funding_pnl: 0.0 },
+                                 last_fee_accrual_ts: None }
+    }
+
+    #[test]
+    fn test_management_fee_decays_value_over_multi_day_hold()
+    {
+        let mut position = create_test_leveraged_token_position(100.0, 10.0);
+        let daily_rate = 0.01;
+
+        // 持有三天，每天计提一次
+        let fees: Vec<f64> = (1..=3).map(|day| position.accrue_management_fee(day * MILLIS_PER_DAY, daily_rate)).collect();
+        assert!(fees.iter().all(|fee| (fee - 10.0).abs() < 1e-9));
+
+        // 一次性计提三天的结果应等于按复利衰减后的价值差
+        let mut once = create_test_leveraged_token_position(100.0, 10.0);
+        let fee = once.accrue_management_fee(3 * MILLIS_PER_DAY, daily_rate);
+        let expected = 1000.0 * (1.0 - 0.99_f64.powi(3));
+        assert!((fee - expected).abs() < 1e-9);
+        assert!((once.meta.realised_pnl + expected).abs() < 1e-9);
+        assert!((once.meta.current_fees_total - expected).abs() < 1e-9);
+        assert_eq!(once.last_fee_accrual_ts, Some(3 * MILLIS_PER_DAY));
+    }
+
+    #[test]
+    fn test_management_fee_is_zero_without_elapsed_time()
+    {
+        let mut position = create_test_leveraged_token_position(100.0, 10.0);
+        assert_eq!(position.accrue_management_fee(0, 0.01), 0.0);
+        assert_eq!(position.accrue_management_fee(MILLIS_PER_DAY, 0.0), 0.0);
+        assert_eq!(position.accrue_management_fee(MILLIS_PER_DAY, 0.01), 0.0);
+    }
+}
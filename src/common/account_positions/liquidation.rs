@@ -0,0 +1,17 @@
+use crate::common::{account_positions::PositionMarginMode, instrument::Instrument, Side};
+use serde::{Deserialize, Serialize};
+
+/// 一次强平：标记价格越过仓位的强平价格后，整个仓位按标记价格被强制平掉。
+///
+/// 逐仓仓位只损失该仓位的保证金；全仓仓位按标记价格计算的亏损从报价货币余额中扣除。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Liquidation
+{
+    pub instrument: Instrument,
+    pub side: Side, // 被强平仓位的方向，多头为 `Side::Buy`
+    pub size: f64,
+    pub mark_price: f64, // 触发强平的标记价格
+    pub liquidation_price: f64,
+    pub margin_mode: PositionMarginMode,
+    pub loss: f64, // 从报价货币余额中扣除的损失
+}
@@ -0,0 +1,61 @@
+use crate::common::{account_positions::PositionMarginMode, instrument::Instrument, Side};
+use serde::{Deserialize, Serialize};
+
+/// 账户保证金使用情况的快照，供实时监控保证金健康度。
+///
+/// - `equity`: 保证金余额，即计价币种余额总额加上所有仓位按最新价计算的未实现盈亏；
+/// - `used_margin`: 所有仓位占用的初始保证金；
+/// - `available_margin`: `equity - used_margin`，可能为负；
+/// - `maintenance_margin`: 所有仓位的维持保证金之和；
+/// - `margin_ratio`: `maintenance_margin / equity`，达到 1 时账户进入强平区间；权益不为正且持有仓位时固定为 1。
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct MarginUpdate
+{
+    pub equity: f64,
+    pub used_margin: f64,
+    pub available_margin: f64,
+    pub maintenance_margin: f64,
+    pub margin_ratio: f64,
+    pub positions: Vec<PositionMargin>, // 每个仓位的保证金明细，全仓模式下据此查看各仓位的维持保证金
+}
+
+/// 单个仓位的保证金明细。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PositionMargin
+{
+    pub instrument: Instrument,
+    pub side: Side,
+    pub margin_mode: PositionMarginMode,
+    pub mark_price: f64,
+    pub notional: f64,
+    pub unrealised_pnl: f64,
+    pub used_margin: f64,        // 全仓为 `notional / leverage`，逐仓为仓位的逐仓保证金
+    pub maintenance_margin: f64, // 配置了 `maintenance_margin_rate` 时为 `notional * maintenance_margin_rate`，否则为 `used_margin * (1 - liquidation_threshold)`
+}
+
+impl MarginUpdate
+{
+    /// 由各仓位明细与计价币种余额汇总出账户级别的保证金快照。
+    pub fn from_positions(quote_balance: f64, positions: Vec<PositionMargin>) -> Self
+    {
+        let equity = quote_balance + positions.iter().map(|position| position.unrealised_pnl).sum::<f64>();
+        let used_margin = positions.iter().map(|position| position.used_margin).sum::<f64>();
+        let maintenance_margin = positions.iter().map(|position| position.maintenance_margin).sum::<f64>();
+        let margin_ratio = if equity > 0.0 {
+            maintenance_margin / equity
+        }
+        else if positions.is_empty() {
+            0.0
+        }
+        else {
+            1.0
+        };
+
+        Self { equity,
+               used_margin,
+               available_margin: equity - used_margin,
+               maintenance_margin,
+               margin_ratio,
+               positions }
+    }
+}
@@ -3,26 +3,36 @@ use crate::{
         account_positions::{
             future::{FuturePosition, FuturePositionConfig},
             leveraged_token::{LeveragedTokenPosition, LeveragedTokenPositionConfig},
-            option::{OptionPosition, OptionPositionConfig},
+            option::{OptionPosition, OptionPositionConfig, OptionType},
             perpetual::{PerpetualPosition, PerpetualPositionConfig},
+            pnl_breakdown::PnlBreakdown,
+            positions_snapshot::AccountPositionsSnapshot,
         },
+        account_positions::position_meta::PositionMeta,
         instrument::{kind::InstrumentKind, Instrument},
+        token::Token,
+        Side,
     },
     hourglass::config_request::ConfigurationRequest,
 };
 use serde::{ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
 use std::{collections::HashMap, hash::Hash, sync::Arc};
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, RwLockReadGuard};
 
 pub(crate) mod exited_position;
 pub mod exited_positions;
 pub mod future;
 pub(crate) mod leveraged_token;
+pub mod liquidation;
+pub mod margin_update;
 pub(crate) mod option;
 pub(crate) mod perpetual;
+pub mod pnl_breakdown;
 mod position_delta;
 pub(crate) mod position_id;
+pub mod positions_snapshot;
 pub mod position_meta;
+pub mod realised_pnl;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum Position
@@ -81,6 +91,7 @@ impl From<ConfigurationRequest> for PositionConfig
     }
 }
 
+/// 序列化时以 `blocking_read` 读取各仓位表，在异步运行时的工作线程中调用会 panic；异步代码请先用 [`AccountPositions::snapshot`] 取得快照再序列化。
 impl Serialize for AccountPositions
 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -223,6 +234,194 @@ impl AccountPositions
                option_pos_short_call_config: Arc::new(RwLock::new(HashMap::new())),
                option_pos_short_put_config: Arc::new(RwLock::new(HashMap::new())) }
     }
+
+    /// 异步地读取所有仓位表，生成不含锁的快照。快照反映同一时刻的仓位，见 [`Self::read_all`]。
+    pub async fn snapshot(&self) -> AccountPositionsSnapshot
+    {
+        let tables = self.read_all().await;
+        AccountPositionsSnapshot { margin_pos_long: tables.margin_pos_long.clone(),
+                                   margin_pos_short: tables.margin_pos_short.clone(),
+                                   perpetual_pos_long: tables.perpetual_pos_long.clone(),
+                                   perpetual_pos_short: tables.perpetual_pos_short.clone(),
+                                   futures_pos_long: tables.futures_pos_long.clone(),
+                                   futures_pos_short: tables.futures_pos_short.clone(),
+                                   option_pos_long_call: tables.option_pos_long_call.clone(),
+                                   option_pos_long_put: tables.option_pos_long_put.clone(),
+                                   option_pos_short_call: tables.option_pos_short_call.clone(),
+                                   option_pos_short_put: tables.option_pos_short_put.clone() }
+    }
+
+    /// 同时持有所有仓位表的读锁，返回同一时刻的一致视图，供 [`Self::snapshot`] 与按类别汇总盈亏共用。
+    ///
+    /// 读锁通过 `tokio::join!` 并发获取，任一仓位表的写锁都要等到返回的视图被释放后才能拿到。
+    /// [`Self::update_position`] 等写入方每次只持有一个仓位表的写锁，不会在持有写锁的同时等待其他仓位表，因此不会与这里形成死锁；
+    /// 需要同时持有多个仓位表写锁的代码必须按字段声明的顺序加锁，并且不能在持有写锁时调用本方法。
+    async fn read_all(&self) -> PositionTablesReadGuard<'_>
+    {
+        let (margin_long, margin_short, perpetual_long, perpetual_short, futures_long, futures_short, option_long_call, option_long_put, option_short_call, option_short_put) =
+            tokio::join!(self.margin_pos_long.read(),
+                         self.margin_pos_short.read(),
+                         self.perpetual_pos_long.read(),
+                         self.perpetual_pos_short.read(),
+                         self.futures_pos_long.read(),
+                         self.futures_pos_short.read(),
+                         self.option_pos_long_call.read(),
+                         self.option_pos_long_put.read(),
+                         self.option_pos_short_call.read(),
+                         self.option_pos_short_put.read());
+
+        PositionTablesReadGuard { margin_pos_long: margin_long,
+                                  margin_pos_short: margin_short,
+                                  perpetual_pos_long: perpetual_long,
+                                  perpetual_pos_short: perpetual_short,
+                                  futures_pos_long: futures_long,
+                                  futures_pos_short: futures_short,
+                                  option_pos_long_call: option_long_call,
+                                  option_pos_long_put: option_long_put,
+                                  option_pos_short_call: option_short_call,
+                                  option_pos_short_put: option_short_put }
+    }
+
+    /// 按仓位的金融工具插入仓位，已存在同一金融工具的仓位时用新的仓位替换。
+    ///
+    /// 仓位表由 `meta.side` 选择：买入为多头、卖出为空头；期权仓位再按 `option_type` 区分看涨与看跌。
+    pub async fn update_position(&self, position: Position)
+    {
+        match position {
+            | Position::Perpetual(p) => {
+                let positions = match p.meta.side {
+                    | Side::Buy => &self.perpetual_pos_long,
+                    | Side::Sell => &self.perpetual_pos_short,
+                };
+                positions.write().await.insert(p.meta.instrument.clone(), p);
+            }
+            | Position::LeveragedToken(p) => {
+                let positions = match p.meta.side {
+                    | Side::Buy => &self.margin_pos_long,
+                    | Side::Sell => &self.margin_pos_short,
+                };
+                positions.write().await.insert(p.meta.instrument.clone(), p);
+            }
+            | Position::Future(p) => {
+                let positions = match p.meta.side {
+                    | Side::Buy => &self.futures_pos_long,
+                    | Side::Sell => &self.futures_pos_short,
+                };
+                positions.write().await.insert(p.meta.instrument.clone(), p);
+            }
+            | Position::Option(p) => {
+                let positions = match (p.meta.side, p.option_type) {
+                    | (Side::Buy, OptionType::Call) => &self.option_pos_long_call,
+                    | (Side::Buy, OptionType::Put) => &self.option_pos_long_put,
+                    | (Side::Sell, OptionType::Call) => &self.option_pos_short_call,
+                    | (Side::Sell, OptionType::Put) => &self.option_pos_short_put,
+                };
+                positions.write().await.insert(p.meta.instrument.clone(), p);
+            }
+        }
+    }
+
+    /// 按基础货币汇总所有仓位的带符号名义敞口，多头为正，空头为负。
+    ///
+    /// 同一基础货币的永续、交割合约与杠杆代币仓位会相互抵消，反映真实的方向性风险。名义敞口按
+    /// `current_size * current_symbol_price * contract_multiplier` 计算，尚未被标记价格时使用开仓均价。
+    /// 期权的方向敞口取决于 delta，无法由名义价值简单相加，因此不计入；现货目前只以余额形式存在，也不计入。
+    pub async fn net_exposure_by_base(&self) -> HashMap<Token, f64>
+    {
+        let mut exposures = HashMap::new();
+        accumulate_exposure(&self.margin_pos_long, |p| &p.meta, Side::Buy, &mut exposures).await;
+        accumulate_exposure(&self.margin_pos_short, |p| &p.meta, Side::Sell, &mut exposures).await;
+        accumulate_exposure(&self.perpetual_pos_long, |p| &p.meta, Side::Buy, &mut exposures).await;
+        accumulate_exposure(&self.perpetual_pos_short, |p| &p.meta, Side::Sell, &mut exposures).await;
+        accumulate_exposure(&self.futures_pos_long, |p| &p.meta, Side::Buy, &mut exposures).await;
+        accumulate_exposure(&self.futures_pos_short, |p| &p.meta, Side::Sell, &mut exposures).await;
+        exposures
+    }
+
+    /// 所有持仓按 `current_symbol_price` 重新计算的未实现盈亏，按金融工具类别汇总。
+    ///
+    /// 多头按 `(current_symbol_price - current_avg_price)`、空头按 `(current_avg_price - current_symbol_price)` 乘以数量与合约乘数计算；
+    /// 尚未被标记价格的仓位沿用 `meta.unrealised_pnl`。只读取仓位，不修改仓位中记录的未实现盈亏。
+    pub async fn unrealised_pnl_by_kind(&self) -> PnlBreakdown
+    {
+        self.pnl_by_kind(|meta| {
+                if meta.current_symbol_price <= 0.0 {
+                    return meta.unrealised_pnl;
+                }
+                let price_change = match meta.side {
+                    | Side::Buy => meta.current_symbol_price - meta.current_avg_price,
+                    | Side::Sell => meta.current_avg_price - meta.current_symbol_price,
+                };
+                price_change * meta.current_size * meta.contract_multiplier
+            })
+            .await
+    }
+
+    /// 所有持仓的 `meta.realised_pnl`（部分平仓等已实现的盈亏），按金融工具类别汇总。已完全平仓的仓位不在仓位表中，不计入。
+    pub async fn realised_pnl_by_kind(&self) -> PnlBreakdown
+    {
+        self.pnl_by_kind(|meta| meta.realised_pnl).await
+    }
+
+    /// 在 [`Self::read_all`] 得到的一致视图上逐个仓位累加 `pnl`。
+    async fn pnl_by_kind(&self, pnl: fn(&PositionMeta) -> f64) -> PnlBreakdown
+    {
+        let tables = self.read_all().await;
+        let mut breakdown = PnlBreakdown::default();
+        for meta in tables.metas() {
+            breakdown.add(meta.instrument.kind, pnl(meta));
+        }
+        breakdown
+    }
+}
+
+/// 同时持有所有仓位表读锁的视图，由 [`AccountPositions::read_all`] 创建，释放时一并释放所有读锁。
+struct PositionTablesReadGuard<'a>
+{
+    margin_pos_long: RwLockReadGuard<'a, HashMap<Instrument, LeveragedTokenPosition>>,
+    margin_pos_short: RwLockReadGuard<'a, HashMap<Instrument, LeveragedTokenPosition>>,
+    perpetual_pos_long: RwLockReadGuard<'a, HashMap<Instrument, PerpetualPosition>>,
+    perpetual_pos_short: RwLockReadGuard<'a, HashMap<Instrument, PerpetualPosition>>,
+    futures_pos_long: RwLockReadGuard<'a, HashMap<Instrument, FuturePosition>>,
+    futures_pos_short: RwLockReadGuard<'a, HashMap<Instrument, FuturePosition>>,
+    option_pos_long_call: RwLockReadGuard<'a, HashMap<Instrument, OptionPosition>>,
+    option_pos_long_put: RwLockReadGuard<'a, HashMap<Instrument, OptionPosition>>,
+    option_pos_short_call: RwLockReadGuard<'a, HashMap<Instrument, OptionPosition>>,
+    option_pos_short_put: RwLockReadGuard<'a, HashMap<Instrument, OptionPosition>>,
+}
+
+impl PositionTablesReadGuard<'_>
+{
+    /// 依次遍历所有仓位表中每个仓位的 [`PositionMeta`]。
+    fn metas(&self) -> impl Iterator<Item = &PositionMeta>
+    {
+        self.margin_pos_long
+            .values()
+            .chain(self.margin_pos_short.values())
+            .map(|p| &p.meta)
+            .chain(self.perpetual_pos_long.values().chain(self.perpetual_pos_short.values()).map(|p| &p.meta))
+            .chain(self.futures_pos_long.values().chain(self.futures_pos_short.values()).map(|p| &p.meta))
+            .chain(self.option_pos_long_call
+                       .values()
+                       .chain(self.option_pos_long_put.values())
+                       .chain(self.option_pos_short_call.values())
+                       .chain(self.option_pos_short_put.values())
+                       .map(|p| &p.meta))
+    }
+}
+
+async fn accumulate_exposure<T>(positions: &RwLock<HashMap<Instrument, T>>, meta: fn(&T) -> &PositionMeta, side: Side, exposures: &mut HashMap<Token, f64>)
+{
+    for (instrument, position) in positions.read().await.iter() {
+        let meta = meta(position);
+        let price = if meta.current_symbol_price > 0.0 { meta.current_symbol_price } else { meta.current_avg_price };
+        let notional = meta.current_size * price * meta.contract_multiplier;
+        let signed_notional = match side {
+            | Side::Buy => notional,
+            | Side::Sell => -notional,
+        };
+        *exposures.entry(instrument.base.clone()).or_insert(0.0) += signed_notional;
+    }
 }
 
 #[derive(Clone, PartialOrd, Debug, PartialEq, Deserialize, Serialize)]
@@ -238,3 +437,132 @@ pub enum PositionMarginMode
     Cross,
     Isolated,
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_utils::{create_test_future_position_with_side, create_test_perpetual_position};
+
+    #[tokio::test]
+    async fn test_net_exposure_by_base_nets_correlated_instruments()
+    {
+        let positions = AccountPositions::init();
+
+        // BTC 永续多头 2 张 @ 30000，BTC 交割合约空头 1.5 张 @ 30100
+        let btc_perp = Instrument::new("BTC", "USDT", InstrumentKind::Perpetual);
+        let mut perp = create_test_perpetual_position(btc_perp.clone());
+        perp.meta.current_size = 2.0;
+        perp.meta.current_symbol_price = 30000.0;
+        positions.perpetual_pos_long.write().await.insert(btc_perp, perp);
+
+        let btc_future = Instrument::new("BTC", "USDT", InstrumentKind::Future);
+        let mut future = create_test_future_position_with_side(btc_future.clone(), Side::Sell);
+        future.meta.current_size = 1.5;
+        future.meta.current_symbol_price = 30100.0;
+        positions.futures_pos_short.write().await.insert(btc_future, future);
+
+        // ETH 永续空头尚未标记价格，按开仓均价计算
+        let eth_perp = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        let mut eth = create_test_perpetual_position(eth_perp.clone());
+        eth.meta.side = Side::Sell;
+        eth.meta.current_size = 3.0;
+        eth.meta.current_avg_price = 2000.0;
+        positions.perpetual_pos_short.write().await.insert(eth_perp, eth);
+
+        let exposures = positions.net_exposure_by_base().await;
+        assert_eq!(exposures.len(), 2);
+        assert_eq!(exposures[&Token::from("BTC")], 2.0 * 30000.0 - 1.5 * 30100.0);
+        assert_eq!(exposures[&Token::from("ETH")], -6000.0);
+    }
+
+    #[tokio::test]
+    async fn test_update_position_routes_options_by_side_and_type()
+    {
+        let positions = AccountPositions::init();
+        let instrument = Instrument::new("BTC", "USDT", InstrumentKind::CryptoOption);
+        let option_position = |side, option_type, size| {
+            let mut meta = create_test_perpetual_position(instrument.clone()).meta;
+            meta.side = side;
+            meta.current_size = size;
+            OptionPosition { meta, option_type }
+        };
+
+        positions.update_position(Position::Option(option_position(Side::Buy, OptionType::Call, 1.0))).await;
+        positions.update_position(Position::Option(option_position(Side::Sell, OptionType::Put, 2.0))).await;
+        assert_eq!(positions.option_pos_long_call.read().await[&instrument].meta.current_size, 1.0);
+        assert_eq!(positions.option_pos_short_put.read().await[&instrument].meta.current_size, 2.0);
+        assert!(positions.option_pos_long_put.read().await.is_empty());
+        assert!(positions.option_pos_short_call.read().await.is_empty());
+
+        // 同一金融工具的仓位被替换而不是重复插入
+        positions.update_position(Position::Option(option_position(Side::Buy, OptionType::Call, 3.0))).await;
+        assert_eq!(positions.option_pos_long_call.read().await.len(), 1);
+        assert_eq!(positions.option_pos_long_call.read().await[&instrument].meta.current_size, 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_pnl_by_kind_recomputes_unrealised_against_latest_price()
+    {
+        let positions = AccountPositions::init();
+
+        // 永续多头 2 张，均价 100，最新价 110；永续空头 1 张，均价 50，最新价 40
+        let long_perp = Instrument::new("BTC", "USDT", InstrumentKind::Perpetual);
+        let mut long = create_test_perpetual_position(long_perp.clone());
+        long.meta.current_size = 2.0;
+        long.meta.current_avg_price = 100.0;
+        long.meta.current_symbol_price = 110.0;
+        long.meta.unrealised_pnl = 5.0; // 过时的记录值，应按最新价重算
+        long.meta.realised_pnl = 3.0;
+        positions.perpetual_pos_long.write().await.insert(long_perp, long);
+        let short_perp = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        let mut short = create_test_perpetual_position(short_perp.clone());
+        short.meta.side = Side::Sell;
+        short.meta.current_avg_price = 50.0;
+        short.meta.current_symbol_price = 40.0;
+        positions.perpetual_pos_short.write().await.insert(short_perp, short);
+
+        // 交割合约空头，合约乘数 10，均价 200，最新价 205
+        let btc_future = Instrument::new("BTC", "USDT", InstrumentKind::Future);
+        let mut future = create_test_future_position_with_side(btc_future.clone(), Side::Sell);
+        future.meta.current_size = 1.0;
+        future.meta.contract_multiplier = 10.0;
+        future.meta.current_avg_price = 200.0;
+        future.meta.current_symbol_price = 205.0;
+        future.meta.realised_pnl = -4.0;
+        positions.futures_pos_short.write().await.insert(btc_future, future);
+
+        let unrealised = positions.unrealised_pnl_by_kind().await;
+        assert_eq!(unrealised.get(InstrumentKind::Perpetual), 20.0 + 10.0);
+        assert_eq!(unrealised.get(InstrumentKind::Future), -50.0);
+        assert_eq!(unrealised.get(InstrumentKind::CryptoOption), 0.0);
+        assert_eq!(unrealised.total(), -20.0);
+
+        let realised = positions.realised_pnl_by_kind().await;
+        assert_eq!(realised.get(InstrumentKind::Perpetual), 3.0);
+        assert_eq!(realised.total(), -1.0);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_serializes_inside_async_runtime()
+    {
+        let positions = AccountPositions::init();
+        let instrument = Instrument::new("BTC", "USDT", InstrumentKind::Perpetual);
+        positions.perpetual_pos_long.write().await.insert(instrument.clone(), create_test_perpetual_position(instrument.clone()));
+
+        let snapshot = positions.snapshot().await;
+        let serialized = serde_json::to_string(&snapshot).unwrap();
+        let deserialized: AccountPositionsSnapshot = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, snapshot);
+
+        // 快照不随之后的仓位变化而改变
+        positions.perpetual_pos_long.write().await.clear();
+        assert_eq!(snapshot.perpetual_pos_long[&instrument].meta.current_size, 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_net_exposure_by_base_is_empty_without_positions()
+    {
+        assert!(AccountPositions::init().net_exposure_by_base().await.is_empty());
+    }
+}
@@ -5,10 +5,19 @@ use crate::{
     hourglass::config_request::ConfigurationRequest,
 };
 
+/// 期权的类型：看涨或看跌。
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum OptionType
+{
+    Call,
+    Put,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct OptionPosition
 {
     pub meta: PositionMeta,
+    pub option_type: OptionType, // 与 meta.side 一起决定仓位存放在四个期权仓位表中的哪一个
 }
 
 #[allow(dead_code)]
@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    common::account_positions::{position_meta::PositionMeta, PositionDirectionMode, PositionMarginMode},
+    common::{
+        account_positions::{position_meta::PositionMeta, PositionDirectionMode, PositionMarginMode},
+        Side,
+    },
     hourglass::config_request::ConfigurationRequest,
 };
 
@@ -34,6 +37,27 @@ impl PerpetualPosition
     {
         self.meta = new_meta;
     }
+
+    /// 以 `exit_price` 减仓 `qty`（不超过当前仓位），返回本次实现的盈亏（不含手续费）。
+    ///
+    /// 按平仓比例把以 `exit_price` 计价的未实现盈亏转入 `realised_pnl`；减仓不改变持仓均价，
+    /// 剩余仓位的未实现盈亏按 `exit_price` 重新计算。
+    pub fn close_partial(&mut self, qty: f64, exit_price: f64) -> f64
+    {
+        let meta = &mut self.meta;
+        let closed_size = qty.min(meta.current_size);
+        let direction = match meta.side {
+            | Side::Buy => 1.0,
+            | Side::Sell => -1.0,
+        };
+        let realised = (exit_price - meta.current_avg_price) * closed_size * meta.contract_multiplier * direction;
+
+        meta.realised_pnl += realised;
+        meta.current_size -= closed_size;
+        meta.current_symbol_price = exit_price;
+        meta.update_unrealised_pnl();
+        realised
+    }
 }
 
 #[allow(dead_code)]
@@ -119,11 +143,78 @@ mod tests
         common::{
             account_positions::{position_id::PositionId, PositionDirectionMode, PositionMarginMode},
             instrument::{kind::InstrumentKind, Instrument},
-            Side,
         },
         Exchange,
     };
 
+    fn long_position(size: f64, avg_price: f64) -> PerpetualPosition
+    {
+        PerpetualPosition { meta: PositionMeta { position_id: PositionId(1),
+                                                 enter_ts: 1625247600,
+                                                 update_ts: 1625247600,
+                                                 exchange: Exchange::Hourglass,
+                                                 instrument: Instrument::new("BTC", "USDT", InstrumentKind::Perpetual),
+                                                 side: Side::Buy,
+                                                 current_size: size,
+                                                 current_fees_total: 0.0,
+                                                 current_avg_price_gross: avg_price,
+                                                 current_symbol_price: avg_price,
+                                                 current_avg_price: avg_price,
+                                                 unrealised_pnl: 0.0,
+                                                 realised_pnl: 0.0,
+                                                 contract_multiplier: 1.0,
+                                                 funding_pnl: 0.0 },
+                            pos_config: PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                                                  leverage: 1.0,
+                                                                  position_direction_mode: PositionDirectionMode::Net },
+                            isolated_margin: None,
+                            liquidation_price: 0.0 }
+    }
+
+    #[test]
+    fn close_partial_should_realise_profit_on_reduced_long()
+    {
+        let mut position = long_position(4.0, 100.0);
+
+        let realised = position.close_partial(1.0, 110.0);
+
+        assert_eq!(realised, 10.0);
+        assert_eq!(position.meta.realised_pnl, 10.0);
+        assert_eq!(position.meta.current_size, 3.0);
+        assert_eq!(position.meta.current_avg_price, 100.0); // 减仓不改变均价
+        assert_eq!(position.meta.unrealised_pnl, 30.0);
+    }
+
+    #[test]
+    fn close_partial_should_realise_loss_on_reduced_long()
+    {
+        let mut position = long_position(4.0, 100.0);
+        position.meta.realised_pnl = 5.0;
+
+        let realised = position.close_partial(3.0, 95.0);
+
+        assert_eq!(realised, -15.0);
+        assert_eq!(position.meta.realised_pnl, -10.0);
+        assert_eq!(position.meta.current_size, 1.0);
+        assert_eq!(position.meta.current_avg_price, 100.0);
+        assert_eq!(position.meta.unrealised_pnl, -5.0);
+    }
+
+    #[test]
+    fn close_partial_should_realise_profit_on_reduced_short()
+    {
+        let mut position = long_position(10.0, 100.0);
+        position.meta.side = Side::Sell;
+
+        let realised = position.close_partial(4.0, 90.0);
+
+        assert_eq!(realised, 40.0);
+        assert_eq!(position.meta.realised_pnl, 40.0);
+        assert_eq!(position.meta.current_size, 6.0);
+        assert_eq!(position.meta.current_avg_price, 100.0);
+        assert_eq!(position.meta.unrealised_pnl, 60.0); // 空头在价格下跌时的浮盈为正
+    }
+
     #[test]
     fn perpetual_position_should_update_liquidation_price()
     {
@@ -140,7 +231,9 @@ mod tests
                                                                     current_symbol_price: 61_000.0,
                                                                     current_avg_price: 50_000.0,
                                                                     unrealised_pnl: 11_000.0,
-                                                                    realised_pnl: 0.0 },
+                                                                    realised_pnl: 0.0,
+                                                                    contract_multiplier: 1.0,
+                                                                    funding_pnl: 0.0 },
                                                pos_config: PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
                                                                                      leverage: 1.0,
                                                                                      position_direction_mode: PositionDirectionMode::LongShort },
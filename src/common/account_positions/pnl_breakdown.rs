@@ -0,0 +1,32 @@
+use crate::common::instrument::kind::InstrumentKind;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 按金融工具类别汇总的仓位盈亏。
+///
+/// 各仓位的盈亏以其报价货币计，直接相加时假设这些报价货币等值，适用于单一报价货币的账户。
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct PnlBreakdown
+{
+    pub by_kind: HashMap<InstrumentKind, f64>,
+}
+
+impl PnlBreakdown
+{
+    pub fn add(&mut self, kind: InstrumentKind, pnl: f64)
+    {
+        *self.by_kind.entry(kind).or_insert(0.0) += pnl;
+    }
+
+    /// 某一类别的盈亏，没有该类别的仓位时为 0。
+    pub fn get(&self, kind: InstrumentKind) -> f64
+    {
+        self.by_kind.get(&kind).copied().unwrap_or(0.0)
+    }
+
+    /// 所有类别的盈亏之和。
+    pub fn total(&self) -> f64
+    {
+        self.by_kind.values().sum()
+    }
+}
@@ -6,7 +6,7 @@ use std::{
 };
 
 /// PositionId 结构体，存储为 `u64`
-#[derive(Clone, PartialEq, PartialOrd, Debug, Deserialize, Serialize, Hash, Eq)]
+#[derive(Clone, PartialEq, PartialOrd, Ord, Debug, Deserialize, Serialize, Hash, Eq)]
 pub struct PositionId(pub u64);
 
 impl PositionId
@@ -22,6 +22,15 @@ pub struct PositionMeta
     pub current_avg_price: f64,       // 实时更新
     pub unrealised_pnl: f64,          // 实时更新
     pub realised_pnl: f64,            // 静态更新（平仓时更新）
+    #[serde(default = "default_contract_multiplier")]
+    pub contract_multiplier: f64, // 静态数据，合约乘数，1 张合约对应的基础货币数量
+    #[serde(default)]
+    pub funding_pnl: f64, // 实时更新，持仓期间累计收取（正）或支付（负）的资金费，已计入 realised_pnl 与 current_fees_total
+}
+
+fn default_contract_multiplier() -> f64
+{
+    1.0
 }
 
 impl PositionMeta
@@ -73,7 +82,9 @@ impl PositionMeta
                        current_symbol_price: trade.price,
                        current_avg_price: trade.price,
                        unrealised_pnl: 0.0,
-                       realised_pnl: 0.0 }
+                       realised_pnl: 0.0,
+                       contract_multiplier: 1.0,
+                       funding_pnl: 0.0 }
     }
 
     pub fn create_from_trade_with_remaining(trade: &ClientTrade, remaining_quantity: f64) -> Self
@@ -91,7 +102,9 @@ impl PositionMeta
                        current_symbol_price: trade.price,
                        current_avg_price: trade.price,
                        unrealised_pnl: 0.0,
-                       realised_pnl: 0.0 }
+                       realised_pnl: 0.0,
+                       contract_multiplier: 1.0,
+                       funding_pnl: 0.0 }
     }
 }
 
@@ -128,18 +141,38 @@ impl PositionMeta
         self.current_avg_price = self.current_avg_price_gross;
     }
 
-    /// 更新 unrealised_pnl
-    /// FIXME 在更新未实现盈亏时，现在使用 self.current_size 来计算，但是在反向仓位或部分平仓的情况下，会不会有问题，
-    /// FIXME 因为仓位大小已经发生变化。建议确保每次在更新未实现盈亏时，考虑实际持仓方向和剩余仓位大小。
+    /// 记录一次资金费结算，`amount` 为正表示收取，为负表示支付。
+    ///
+    /// 与杠杆代币的管理费一致，资金费计入 `realised_pnl`，支付的资金费累加到 `current_fees_total`（收取时抵减）；
+    /// 同时单独累计到 `funding_pnl`，用于从交易盈亏中拆分出资金费。
+    pub fn record_funding(&mut self, amount: f64, timestamp: i64)
+    {
+        self.funding_pnl += amount;
+        self.realised_pnl += amount;
+        self.current_fees_total -= amount;
+        self.update_ts = timestamp;
+    }
+
+    /// 交易盈亏（已实现与未实现之和），扣除计入 `realised_pnl` 的资金费。
+    pub fn trading_pnl(&self) -> f64
+    {
+        self.realised_pnl + self.unrealised_pnl - self.funding_pnl
+    }
+
+    /// 按持仓方向和剩余仓位大小更新 unrealised_pnl：多头在价格上涨时盈利，空头在价格下跌时盈利。
     pub fn update_unrealised_pnl(&mut self)
     {
-        self.unrealised_pnl = (self.current_symbol_price - self.current_avg_price) * self.current_size;
+        let price_change = match self.side {
+            | Side::Buy => self.current_symbol_price - self.current_avg_price,
+            | Side::Sell => self.current_avg_price - self.current_symbol_price,
+        };
+        self.unrealised_pnl = price_change * self.current_size * self.contract_multiplier;
     }
 
     /// 更新 realised_pnl 并清空持仓
     pub fn update_realised_pnl(&mut self, closing_price: f64)
     {
-        self.realised_pnl = (closing_price - self.current_avg_price) * self.current_size;
+        self.realised_pnl = (closing_price - self.current_avg_price) * self.current_size * self.contract_multiplier;
         // 清空当前持仓
         self.current_size = 0.0;
         self.current_avg_price = 0.0;
@@ -164,6 +197,8 @@ pub struct PositionMetaBuilder
     current_avg_price: Option<f64>,
     unrealised_pnl: Option<f64>,
     realised_pnl: Option<f64>,
+    contract_multiplier: Option<f64>,
+    funding_pnl: Option<f64>,
 }
 
 #[allow(dead_code)]
@@ -184,7 +219,9 @@ impl PositionMetaBuilder
                current_symbol_price: None,
                current_avg_price: None,
                unrealised_pnl: None,
-               realised_pnl: None }
+               realised_pnl: None,
+               contract_multiplier: None,
+               funding_pnl: None }
     }
 
     pub fn position_id(mut self, position_id: PositionId) -> Self
@@ -271,6 +308,18 @@ impl PositionMetaBuilder
         self
     }
 
+    pub fn contract_multiplier(mut self, contract_multiplier: f64) -> Self
+    {
+        self.contract_multiplier = Some(contract_multiplier);
+        self
+    }
+
+    pub fn funding_pnl(mut self, funding_pnl: f64) -> Self
+    {
+        self.funding_pnl = Some(funding_pnl);
+        self
+    }
+
     pub fn build(self) -> Result<PositionMeta, &'static str>
     {
         Ok(PositionMeta { position_id: self.position_id.ok_or("position_id is required")?,
@@ -286,7 +335,9 @@ impl PositionMetaBuilder
                           current_symbol_price: self.current_symbol_price.ok_or("current_symbol_price is required")?,
                           current_avg_price: self.current_avg_price.ok_or("current_avg_price is required")?,
                           unrealised_pnl: self.unrealised_pnl.ok_or("unrealised_pnl is required")?,
-                          realised_pnl: self.realised_pnl.ok_or("realised_pnl is required")? })
+                          realised_pnl: self.realised_pnl.ok_or("realised_pnl is required")?,
+                          contract_multiplier: self.contract_multiplier.unwrap_or(1.0),
+                          funding_pnl: self.funding_pnl.unwrap_or(0.0) })
     }
 }
 
@@ -321,7 +372,8 @@ mod tests
                       side: Side::Buy,
                       price: 50_000.0,
                       size: 1.0,
-                      fees: 2.0 }
+                      fees: 2.0,
+                      tag: None }
     }
 
     #[test]
@@ -371,7 +423,8 @@ mod tests
                                       side: Side::Buy,
                                       price: 60_000.0,
                                       size: 1.0,
-                                      fees: 2.0 };
+                                      fees: 2.0,
+                                      tag: None };
 
         meta.update_from_trade(&new_trade);
 
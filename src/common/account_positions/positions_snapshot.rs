@@ -0,0 +1,92 @@
+use crate::common::{
+    account_positions::{future::FuturePosition, leveraged_token::LeveragedTokenPosition, option::OptionPosition, perpetual::PerpetualPosition},
+    instrument::Instrument,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+
+/// [`AccountPositions`](super::AccountPositions) 中所有仓位表在同一时刻的副本，不含锁，可以在异步代码中安全地序列化与比较。
+///
+/// 由 [`AccountPositions::snapshot`](super::AccountPositions::snapshot) 生成，之后对账户仓位的修改不会反映到快照中。
+/// `Instrument` 不能作为 JSON 对象的键，因此每个仓位表序列化为仓位列表，反序列化时由仓位的 `meta.instrument` 重建键。
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct AccountPositionsSnapshot
+{
+    #[serde(with = "positions_as_list")]
+    pub margin_pos_long: HashMap<Instrument, LeveragedTokenPosition>,
+    #[serde(with = "positions_as_list")]
+    pub margin_pos_short: HashMap<Instrument, LeveragedTokenPosition>,
+    #[serde(with = "positions_as_list")]
+    pub perpetual_pos_long: HashMap<Instrument, PerpetualPosition>,
+    #[serde(with = "positions_as_list")]
+    pub perpetual_pos_short: HashMap<Instrument, PerpetualPosition>,
+    #[serde(with = "positions_as_list")]
+    pub futures_pos_long: HashMap<Instrument, FuturePosition>,
+    #[serde(with = "positions_as_list")]
+    pub futures_pos_short: HashMap<Instrument, FuturePosition>,
+    #[serde(with = "positions_as_list")]
+    pub option_pos_long_call: HashMap<Instrument, OptionPosition>,
+    #[serde(with = "positions_as_list")]
+    pub option_pos_long_put: HashMap<Instrument, OptionPosition>,
+    #[serde(with = "positions_as_list")]
+    pub option_pos_short_call: HashMap<Instrument, OptionPosition>,
+    #[serde(with = "positions_as_list")]
+    pub option_pos_short_put: HashMap<Instrument, OptionPosition>,
+}
+
+/// 能从自身取得所属金融工具的仓位，用于反序列化时重建仓位表的键。
+trait KeyedPosition
+{
+    fn instrument(&self) -> &Instrument;
+}
+
+impl KeyedPosition for LeveragedTokenPosition
+{
+    fn instrument(&self) -> &Instrument
+    {
+        &self.meta.instrument
+    }
+}
+
+impl KeyedPosition for PerpetualPosition
+{
+    fn instrument(&self) -> &Instrument
+    {
+        &self.meta.instrument
+    }
+}
+
+impl KeyedPosition for FuturePosition
+{
+    fn instrument(&self) -> &Instrument
+    {
+        &self.meta.instrument
+    }
+}
+
+impl KeyedPosition for OptionPosition
+{
+    fn instrument(&self) -> &Instrument
+    {
+        &self.meta.instrument
+    }
+}
+
+mod positions_as_list
+{
+    use super::*;
+
+    pub fn serialize<S, T>(positions: &HashMap<Instrument, T>, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer,
+              T: Serialize
+    {
+        serializer.collect_seq(positions.values())
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<HashMap<Instrument, T>, D::Error>
+        where D: Deserializer<'de>,
+              T: Deserialize<'de> + KeyedPosition
+    {
+        Ok(Vec::<T>::deserialize(deserializer)?.into_iter().map(|position| (position.instrument().clone(), position)).collect())
+    }
+}
@@ -0,0 +1,14 @@
+use crate::common::{instrument::Instrument, trade::ClientTradeId};
+use serde::{Deserialize, Serialize};
+
+/// 一笔成交减仓或平仓时实现的盈亏。
+///
+/// 只计入被平掉的那部分数量：部分平仓按平仓数量计算，平仓后反向开仓时只计入平掉原仓位的部分，手续费也按同样比例分摊。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RealisedPnl
+{
+    pub instrument: Instrument,
+    pub trade_id: ClientTradeId, // 触发减仓或平仓的成交
+    pub amount: f64,             // 扣除手续费后的实现盈亏，以报价货币计
+    pub fees: f64,               // 分摊到平仓部分的手续费
+}
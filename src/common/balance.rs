@@ -1,6 +1,7 @@
 use crate::common::token::Token;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::{iter::Sum, ops::Add};
 
 /// 与[`Token`]相关联的[`Balance`]。
 #[derive(Clone, PartialEq, PartialOrd, Debug, Deserialize, Serialize)]
@@ -18,6 +19,15 @@ impl TokenBalance
     }
 }
 
+/// 一次提现的结果：提现数量、额外扣除的手续费与提现后的余额。
+#[derive(Clone, PartialEq, PartialOrd, Debug, Deserialize, Serialize)]
+pub struct Withdrawal
+{
+    pub amount: f64,           // 转出账户的数量，不含手续费
+    pub fee: f64,              // 额外扣除的网络手续费
+    pub balance: TokenBalance, // 提现后的余额
+}
+
 /// 总余额和可用余额。
 #[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Deserialize, Serialize)]
 pub struct Balance
@@ -42,7 +52,7 @@ impl Balance
         self.total - self.available
     }
 
-    /// 对这个[`Balance`]应用一个[`BalanceDelta`]。
+    /// 对这个[`Balance`]应用一个[`BalanceDelta`]。`time` 不会改变，由调用方按自己的时钟（例如回测的交易所时间）更新。
     pub fn apply(&mut self, delta: BalanceDelta) -> Result<(), &'static str>
     {
         // 确保应用 BalanceDelta 后不会使 total 或 available 余额为负数。
@@ -51,9 +61,17 @@ impl Balance
         }
         self.total += delta.total;
         self.available += delta.available;
-        self.time = Utc::now(); // NOTE not sure about this timestamp, could err.
         Ok(())
     }
+
+    /// 把一组 [`BalanceDelta`] 作为一笔净额交易应用。
+    ///
+    /// 只校验全部增量合并后的最终余额，不会因为应用顺序（例如先扣手续费、后入账成交所得）造成的中间负值而拒绝。
+    /// 最终余额为负时整组增量都不会应用。
+    pub fn apply_net(&mut self, deltas: &[BalanceDelta]) -> Result<(), &'static str>
+    {
+        self.apply(deltas.iter().copied().sum())
+    }
 }
 
 /// 可应用于[`Balance`]的增量变更；
@@ -74,6 +92,25 @@ impl BalanceDelta
     }
 }
 
+impl Add for BalanceDelta
+{
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self
+    {
+        Self { total: self.total + other.total,
+               available: self.available + other.available }
+    }
+}
+
+impl Sum for BalanceDelta
+{
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self
+    {
+        iter.fold(Self::new(0.0, 0.0), Add::add)
+    }
+}
+
 #[cfg(test)]
 mod tests
 {
@@ -114,6 +151,25 @@ mod tests
         assert_eq!(balance.available, 55.0);
     }
 
+    #[test]
+    fn balance_apply_net_should_only_validate_final_state()
+    {
+        // 先扣手续费、后入账成交所得：逐笔应用时手续费会被拒绝，作为净额交易应用则成功
+        let fee = BalanceDelta::new(-1.0, -1.0);
+        let proceeds = BalanceDelta::new(100.0, 100.0);
+
+        let mut sequential = Balance::new(0.0, 0.0);
+        assert!(sequential.apply(fee).is_err());
+
+        let mut net = Balance::new(0.0, 0.0);
+        assert!(net.apply_net(&[fee, proceeds]).is_ok());
+        assert_eq!((net.total, net.available), (99.0, 99.0));
+
+        // 最终余额为负时整组增量都不应用
+        assert!(net.apply_net(&[BalanceDelta::new(-200.0, -200.0), proceeds]).is_err());
+        assert_eq!((net.total, net.available), (99.0, 99.0));
+    }
+
     #[test]
     fn balance_delta_new_should_create_balance_delta()
     {
@@ -0,0 +1,210 @@
+use crate::{
+    common::datafeed::market_event::MarketEvent,
+    error::ExchangeError,
+    hourglass::{clickhouse_api::datatype::clickhouse_trade_data::MarketTrade, ws_trade::parse_base_and_quote},
+};
+use flate2::read::MultiGzDecoder;
+use serde::Deserialize;
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+use tokio::sync::mpsc::{self, Receiver};
+
+/// CSV 文件中的一行成交。`exchange` 列可以省略，缺省为空字符串。
+#[derive(Debug, Deserialize)]
+struct CsvTradeRow
+{
+    #[serde(default)]
+    exchange: String,
+    symbol: String,
+    side: String,
+    price: f64,
+    timestamp: i64,
+    amount: f64,
+}
+
+impl From<CsvTradeRow> for MarketTrade
+{
+    fn from(row: CsvTradeRow) -> Self
+    {
+        MarketTrade { exchange: row.exchange,
+                      symbol: row.symbol,
+                      side: row.side,
+                      price: row.price,
+                      timestamp: row.timestamp,
+                      amount: row.amount }
+    }
+}
+
+/// 从 CSV 文件逐行读取成交的数据源，不依赖 ClickHouse。
+///
+/// 第一行为表头，必须包含 `symbol,side,price,timestamp,amount` 列（顺序不限），可选的 `exchange` 列见 [`MarketTrade::exchange`]。
+/// 以 `.gz` 结尾的文件边解压边读取。文件按行流式读取，不会整体读入内存。
+///
+/// 可以通过 [`DataSource::Csv`](crate::hourglass::DataSource::Csv) 直接交给交易所回放，
+/// 也可以用 [`CsvTradeSource::into_stream`] 转为与实时行情相同的 `MarketEvent<MarketTrade>` 通道。
+pub struct CsvTradeSource
+{
+    records: csv::DeserializeRecordsIntoIter<Box<dyn Read + Send>, CsvTradeRow>,
+    finished: bool,
+}
+
+impl CsvTradeSource
+{
+    /// 打开 CSV 文件，`.gz` 结尾的文件按 gzip 格式解压。
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ExchangeError>
+    {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| ExchangeError::DataSourceError(format!("Failed to open {}: {}", path.display(), err)))?;
+        if path.extension().is_some_and(|extension| extension == "gz") {
+            Ok(Self::from_reader(MultiGzDecoder::new(BufReader::new(file))))
+        }
+        else {
+            Ok(Self::from_reader(file))
+        }
+    }
+
+    /// 从任意未压缩的 CSV 输入读取成交。
+    pub fn from_reader(reader: impl Read + Send + 'static) -> Self
+    {
+        let reader: Box<dyn Read + Send> = Box::new(reader);
+        Self { records: csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader).into_deserialize(),
+               finished: false }
+    }
+
+    /// 读取下一笔成交，读完时返回 `None`。遇到无法解析的行或损坏的压缩数据时返回错误，之后不再返回成交。
+    pub fn next_trade(&mut self) -> Result<Option<MarketTrade>, ExchangeError>
+    {
+        if self.finished {
+            return Ok(None);
+        }
+        match self.records.next() {
+            | Some(Ok(row)) => Ok(Some(row.into())),
+            | Some(Err(err)) => {
+                self.finished = true;
+                Err(ExchangeError::DataSourceError(format!("Invalid CSV trade row: {}", err)))
+            }
+            | None => {
+                self.finished = true;
+                Ok(None)
+            }
+        }
+    }
+
+    /// 在后台线程中读取成交，转为 `MarketEvent<MarketTrade>` 按文件顺序发送到返回的通道，读完时关闭通道。
+    ///
+    /// 通道最多积压 `capacity` 笔成交，消费跟不上时读取线程等待，不会把整个文件读入内存。
+    /// 读取出错时把错误发送到通道后关闭通道。
+    pub fn into_stream(self, capacity: usize) -> Receiver<Result<MarketEvent<MarketTrade>, ExchangeError>>
+    {
+        let (event_tx, event_rx) = mpsc::channel(capacity);
+        std::thread::spawn(move || {
+            for trade in self {
+                let event = trade.map(|trade| {
+                                     let (base, quote) = parse_base_and_quote(&trade.symbol);
+                                     MarketEvent::from_swap_trade_clickhouse(trade, base, quote)
+                                 });
+                if event_tx.blocking_send(event).is_err() {
+                    break;
+                }
+            }
+        });
+        event_rx
+    }
+}
+
+impl Iterator for CsvTradeSource
+{
+    type Item = Result<MarketTrade, ExchangeError>;
+
+    /// 取出下一笔成交。读取出错时返回一次错误，之后结束。
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        self.next_trade().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    const CSV: &str = "symbol,side,price,timestamp,amount\nETHUSDT,buy,16400.5,1000,0.2\nBTCUSDT,sell,30000,1001,1.5\n";
+
+    #[test]
+    fn test_csv_rows_become_market_trades()
+    {
+        let trades: Vec<MarketTrade> = CsvTradeSource::from_reader(CSV.as_bytes()).collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].symbol, "ETHUSDT");
+        assert_eq!(trades[0].side, "buy");
+        assert_eq!(trades[0].price, 16400.5);
+        assert_eq!(trades[1].timestamp, 1001);
+        assert_eq!(trades[1].amount, 1.5);
+    }
+
+    #[test]
+    fn test_invalid_row_is_an_error()
+    {
+        let mut source = CsvTradeSource::from_reader("symbol,side,price,timestamp,amount\nETHUSDT,buy,abc,1000,0.2\n".as_bytes());
+
+        assert!(matches!(source.next_trade(), Err(ExchangeError::DataSourceError(_))));
+        assert!(matches!(source.next_trade(), Ok(None)));
+
+        // 迭代器把错误交给调用方，而不是当作数据结束
+        let mut source = CsvTradeSource::from_reader("symbol,side,price,timestamp,amount\nETHUSDT,buy,16400,1000,0.2\nETHUSDT,buy,abc,1001,0.2\n".as_bytes());
+        assert!(matches!(source.next(), Some(Ok(_))));
+        assert!(matches!(source.next(), Some(Err(ExchangeError::DataSourceError(_)))));
+        assert!(source.next().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_gzip_file_streams_market_events()
+    {
+        let dir = tempfile::tempdir().unwrap();
+        let mut encoder = GzEncoder::new(File::create(dir.path().join("trades.csv.gz")).unwrap(), Compression::default());
+        encoder.write_all(CSV.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let mut event_rx = CsvTradeSource::open(dir.path().join("trades.csv.gz")).unwrap().into_stream(1);
+
+        let first = event_rx.recv().await.unwrap().unwrap();
+        assert_eq!(first.exchange_ts, 1000);
+        assert_eq!((first.instrument.base.to_string(), first.instrument.quote.to_string()), ("ETH".to_string(), "USDT".to_string()));
+        assert_eq!(event_rx.recv().await.unwrap().unwrap().kind.symbol, "BTCUSDT");
+        assert!(event_rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_is_bounded_and_forwards_errors()
+    {
+        let mut csv = String::from("symbol,side,price,timestamp,amount\n");
+        for timestamp in 0..100 {
+            csv.push_str(&format!("ETHUSDT,buy,16400,{},0.1\n", timestamp));
+        }
+        csv.push_str("ETHUSDT,buy,abc,100,0.1\n");
+        let mut event_rx = CsvTradeSource::from_reader(std::io::Cursor::new(csv.into_bytes())).into_stream(4);
+
+        // 消费之前，通道中最多积压 4 笔
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(event_rx.len() <= 4);
+
+        let mut trades = 0;
+        while let Some(event) = event_rx.recv().await {
+            match event {
+                | Ok(_) => trades += 1,
+                | Err(err) => {
+                    assert!(matches!(err, ExchangeError::DataSourceError(_)));
+                    break;
+                }
+            }
+        }
+        assert_eq!(trades, 100);
+        assert!(event_rx.recv().await.is_none());
+    }
+}
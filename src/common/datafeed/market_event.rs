@@ -2,7 +2,11 @@ use std::fmt::Debug;
 
 use serde::{Deserialize, Serialize};
 
-use crate::{common::instrument::Instrument, Exchange};
+use crate::{
+    common::instrument::Instrument,
+    hourglass::account::account_latency::AccountLatency,
+    Exchange,
+};
 
 // 定义一个泛型结构体 MarketEvent，包含各种交易市场事件信息
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Deserialize, Serialize)]
@@ -14,3 +18,61 @@ pub struct MarketEvent<Data>
     pub instrument: Instrument, // 交易工具信息
     pub kind: Data,             // 事件的具体类型 `WsTrade` / `MarketTrade` / `ClientTrade` 等
 }
+
+impl<Data> MarketEvent<Data>
+{
+    /// 为行情事件加上模拟的网络延迟：`received_ts = exchange_ts + 延迟`，`exchange_ts` 保持为事件的真实发生时间。
+    ///
+    /// 延迟由 `latency` 按其 [`FluctuationMode`](crate::hourglass::account::account_latency::FluctuationMode) 抽样，
+    /// 按时间变化的模式以 `exchange_ts` 为相位，随机模式使用 `latency` 自身的随机数生成器，
+    /// 因此同一个种子（见 [`AccountConfig::network_latency`](crate::hourglass::account::account_config::AccountConfig::network_latency)）
+    /// 与同样的行情顺序得到同样的接收时间。策略只应在 `received_ts` 之后才能看到并响应该事件，
+    /// 撮合也以 `received_ts` 判断挂单是否已经可见，见 [`TradeHandler::handle_market_event`](crate::hourglass::account::account_handlers::trade_handler::TradeHandler::handle_market_event)。
+    pub fn with_network_latency(mut self, latency: &mut AccountLatency) -> Self
+    {
+        self.received_ts = latency.receive_time(self.exchange_ts);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::instrument::kind::InstrumentKind,
+        hourglass::{account::account_latency::FluctuationMode, clickhouse_api::datatype::clickhouse_trade_data::MarketTrade},
+    };
+
+    fn create_test_event(timestamp: i64) -> MarketEvent<MarketTrade>
+    {
+        MarketEvent::from_swap_trade_clickhouse(MarketTrade { exchange: "binance-futures".to_string(),
+                                                              symbol: "ETHUSDT".to_string(),
+                                                              side: "buy".to_string(),
+                                                              price: 16300.0,
+                                                              timestamp,
+                                                              amount: 1.0 },
+                                                "ETH".to_string(),
+                                                "USDT".to_string())
+    }
+
+    #[test]
+    fn test_network_latency_delays_receive_time_reproducibly()
+    {
+        let event = create_test_event(1625247600000);
+        assert_eq!(event.received_ts, event.exchange_ts);
+        assert_eq!(event.instrument.kind, InstrumentKind::Perpetual);
+
+        let receive_times = |seed: u64| {
+            let mut latency = AccountLatency::new(FluctuationMode::Uniform { min: 5, max: 50 }, 50, 5, seed);
+            (0..20).map(|offset| create_test_event(1625247600000 + offset).with_network_latency(&mut latency))
+                   .map(|event| {
+                       assert!((5..=50).contains(&(event.received_ts - event.exchange_ts)));
+                       event.received_ts
+                   })
+                   .collect::<Vec<_>>()
+        };
+        assert_eq!(receive_times(3), receive_times(3));
+        assert_ne!(receive_times(3), receive_times(4));
+    }
+}
@@ -0,0 +1,48 @@
+use crate::{error::ExchangeError, hourglass::clickhouse_api::datatype::clickhouse_trade_data::MarketTrade};
+use std::collections::VecDeque;
+
+/// 内存中的成交数据源，按给定顺序逐条回放，不依赖 ClickHouse。
+///
+/// 用于集成测试与示例：把一份固定的成交夹具通过 [`DataSource::Mock`](crate::hourglass::DataSource::Mock) 交给交易所，
+/// 即可完整地跑通 加载 -> 撮合 -> 事件 的流程，结果完全可复现。
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MockDataSource
+{
+    trades: VecDeque<MarketTrade>,
+}
+
+impl MockDataSource
+{
+    pub fn new(trades: Vec<MarketTrade>) -> Self
+    {
+        Self { trades: trades.into() }
+    }
+
+    /// 从 JSON 数组形式的成交夹具构建，字段与 [`MarketTrade`] 一致。
+    pub fn from_json(json: &str) -> Result<Self, ExchangeError>
+    {
+        serde_json::from_str(json).map(Self::new).map_err(|err| ExchangeError::ConfigParseError(format!("Invalid trade fixture: {err}")))
+    }
+
+    /// 剩余未回放的成交数量。
+    pub fn len(&self) -> usize
+    {
+        self.trades.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.trades.is_empty()
+    }
+}
+
+impl Iterator for MockDataSource
+{
+    type Item = MarketTrade;
+
+    /// 取出下一笔成交，回放完毕时返回 `None`。
+    fn next(&mut self) -> Option<MarketTrade>
+    {
+        self.trades.pop_front()
+    }
+}
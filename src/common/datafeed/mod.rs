@@ -1 +1,10 @@
+pub mod csv_trade_source;
 pub mod market_event;
+pub mod mock_data_source;
+pub mod multi_symbol_feed;
+pub mod parquet_trade_source;
+pub mod price_jitter;
+pub mod replay_clock;
+pub mod simulated_event;
+pub mod timestamp_normalizer;
+pub mod timestamp_sequencer;
@@ -0,0 +1,158 @@
+use crate::{
+    common::datafeed::market_event::MarketEvent,
+    hourglass::{account::account_latency::AccountLatency, clickhouse_api::datatype::clickhouse_trade_data::MarketTrade},
+};
+use clickhouse::{error::Result, query::RowCursor};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+};
+
+/// 单个品种的成交来源：ClickHouse 游标，或已经加载到内存中的成交。
+enum TradeSource
+{
+    Cursor(RowCursor<MarketTrade>),
+    Rows(VecDeque<MarketTrade>),
+}
+
+impl TradeSource
+{
+    async fn next(&mut self) -> Result<Option<MarketTrade>>
+    {
+        match self {
+            | TradeSource::Cursor(cursor) => cursor.next().await,
+            | TradeSource::Rows(rows) => Ok(rows.pop_front()),
+        }
+    }
+}
+
+/// 一个品种的成交流及其基础货币与报价货币。
+struct SymbolStream
+{
+    base: String,
+    quote: String,
+    source: TradeSource,
+}
+
+/// 把多个品种各自按时间戳升序的成交流 k 路归并为一条全局按时间戳不减的行情流。
+///
+/// 每个品种只在内存中保留一条尚未交出的成交（最小堆中的队首），不会缓存整天的数据。
+/// 时间戳相同的成交按 `symbol` 字典序交出，`symbol` 也相同时按品种加入的顺序，与
+/// [`TimestampSequencer`](crate::common::datafeed::timestamp_sequencer::TimestampSequencer) 的规则一致，
+/// 因此每次回放的顺序都相同。
+pub struct MultiSymbolFeed
+{
+    streams: Vec<SymbolStream>,
+    heads: Vec<Option<MarketTrade>>,
+    heap: BinaryHeap<Reverse<(i64, String, usize)>>,
+    primed: bool,
+    network_latency: Option<AccountLatency>, // 为交出的行情加上模拟网络延迟，默认不加
+}
+
+impl MultiSymbolFeed
+{
+    /// 由每个品种的 (基础货币, 报价货币, 游标) 构建，游标需按时间戳升序，见
+    /// [`ClickHouseClient::cursor_multi_symbol`](crate::hourglass::clickhouse_api::queries_operations::ClickHouseClient::cursor_multi_symbol)。
+    pub fn from_cursors(cursors: Vec<(String, String, RowCursor<MarketTrade>)>) -> Self
+    {
+        Self::new(cursors.into_iter().map(|(base, quote, cursor)| SymbolStream { base, quote, source: TradeSource::Cursor(cursor) }).collect())
+    }
+
+    /// 归并已经加载到内存中的各品种成交，每个品种的 `rows` 需按时间戳升序。
+    pub fn from_rows(rows: Vec<(String, String, Vec<MarketTrade>)>) -> Self
+    {
+        Self::new(rows.into_iter().map(|(base, quote, rows)| SymbolStream { base, quote, source: TradeSource::Rows(rows.into()) }).collect())
+    }
+
+    fn new(streams: Vec<SymbolStream>) -> Self
+    {
+        let heads = streams.iter().map(|_| None).collect();
+        Self { streams,
+               heads,
+               heap: BinaryHeap::new(),
+               primed: false,
+               network_latency: None }
+    }
+
+    /// 按归并后的顺序为每条行情加上模拟网络延迟，见 [`MarketEvent::with_network_latency`]。
+    /// 行情仍按 `exchange_ts` 交出，延迟不同的相邻行情的 `received_ts` 可能不是单调的。
+    pub fn with_network_latency(mut self, latency: AccountLatency) -> Self
+    {
+        self.network_latency = Some(latency);
+        self
+    }
+
+    /// 从第 `index` 个品种读取下一条成交放入堆中，该品种读完时不再放入。
+    async fn advance(&mut self, index: usize) -> Result<()>
+    {
+        if let Some(trade) = self.streams[index].source.next().await? {
+            self.heap.push(Reverse((trade.timestamp, trade.symbol.clone(), index)));
+            self.heads[index] = Some(trade);
+        }
+        Ok(())
+    }
+
+    /// 取出全局时间戳最小的下一条成交，所有品种都读完时返回 `None`。读取某个游标出错时返回该错误。
+    pub async fn next(&mut self) -> Result<Option<MarketEvent<MarketTrade>>>
+    {
+        if !self.primed {
+            for index in 0..self.streams.len() {
+                self.advance(index).await?;
+            }
+            self.primed = true;
+        }
+
+        let Some(Reverse((_, _, index))) = self.heap.pop()
+        else {
+            return Ok(None);
+        };
+        let trade = self.heads[index].take().expect("heap entry without a buffered trade");
+        self.advance(index).await?;
+
+        let stream = &self.streams[index];
+        let event = MarketEvent::from_swap_trade_clickhouse(trade, stream.base.clone(), stream.quote.clone());
+        Ok(Some(match &mut self.network_latency {
+            | Some(latency) => event.with_network_latency(latency),
+            | None => event,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::common::token::Token;
+
+    fn create_test_trade(symbol: &str, timestamp: i64, price: f64) -> MarketTrade
+    {
+        MarketTrade { exchange: "binance-futures".to_string(),
+                      symbol: symbol.to_string(),
+                      side: "buy".to_string(),
+                      price,
+                      timestamp,
+                      amount: 1.0 }
+    }
+
+    #[tokio::test]
+    async fn test_symbols_merged_in_timestamp_order_with_ties_by_symbol()
+    {
+        let mut feed = MultiSymbolFeed::from_rows(vec![("ETH".to_string(),
+                                                        "USDT".to_string(),
+                                                        vec![create_test_trade("ETHUSDT", 1, 1.0), create_test_trade("ETHUSDT", 3, 2.0), create_test_trade("ETHUSDT", 3, 3.0)]),
+                                                       ("BTC".to_string(), "USDT".to_string(), vec![create_test_trade("BTCUSDT", 2, 4.0), create_test_trade("BTCUSDT", 3, 5.0)]),
+                                                       ("SOL".to_string(), "USDT".to_string(), Vec::new())]);
+
+        let mut merged = Vec::new();
+        while let Some(event) = feed.next().await.unwrap() {
+            merged.push((event.instrument.base.clone(), event.exchange_ts, event.kind.price));
+        }
+        assert_eq!(merged,
+                   vec![(Token::from("ETH"), 1, 1.0),
+                        (Token::from("BTC"), 2, 4.0),
+                        (Token::from("BTC"), 3, 5.0),
+                        (Token::from("ETH"), 3, 2.0),
+                        (Token::from("ETH"), 3, 3.0)]);
+        assert!(feed.next().await.unwrap().is_none());
+    }
+}
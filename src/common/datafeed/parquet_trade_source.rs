@@ -0,0 +1,370 @@
+use crate::{
+    common::datafeed::market_event::MarketEvent,
+    error::ExchangeError,
+    hourglass::{clickhouse_api::datatype::clickhouse_trade_data::MarketTrade, ws_trade::parse_base_and_quote},
+};
+use parquet::{
+    file::reader::{FileReader, SerializedFileReader},
+    record::{Field, Row},
+    schema::types::Type,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fs::File, path::Path};
+use tokio::sync::mpsc::{self, Receiver};
+
+/// [`MarketTrade`] 各字段在 Parquet 文件中的列名。不同来源的 schema 列名不同，按需覆盖默认值。
+///
+/// `exchange` 为 `None` 时不读取该列，成交的 `exchange` 为空字符串。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ParquetColumns
+{
+    pub exchange: Option<String>,
+    pub symbol: String,
+    pub side: String,
+    pub price: String,
+    pub timestamp: String,
+    pub amount: String,
+}
+
+impl Default for ParquetColumns
+{
+    fn default() -> Self
+    {
+        Self { exchange: None,
+               symbol: "symbol".to_string(),
+               side: "side".to_string(),
+               price: "price".to_string(),
+               timestamp: "timestamp".to_string(),
+               amount: "amount".to_string() }
+    }
+}
+
+impl ParquetColumns
+{
+    fn names(&self) -> Vec<&str>
+    {
+        let mut names = vec![self.symbol.as_str(), self.side.as_str(), self.price.as_str(), self.timestamp.as_str(), self.amount.as_str()];
+        names.extend(self.exchange.as_deref());
+        names
+    }
+}
+
+/// 从 Parquet 文件按行组读取成交的数据源，不依赖 ClickHouse。
+///
+/// 每次只解码一个行组，且只读取 [`ParquetColumns`] 中列出的列，内存占用与行组大小相关，与文件大小无关。
+/// 时间戳列可以是整数或 `TIMESTAMP_MILLIS`/`TIMESTAMP_MICROS` 类型，后两者统一换算为毫秒；价格与数量列须为浮点数或整数。
+///
+/// 可以通过 [`DataSource::Parquet`](crate::hourglass::DataSource::Parquet) 直接交给交易所回放，
+/// 也可以用 [`ParquetTradeSource::into_stream`] 按行组批量取得 `MarketEvent<MarketTrade>`。
+pub struct ParquetTradeSource
+{
+    reader: SerializedFileReader<File>,
+    projection: Type, // 只包含所需列的 schema
+    columns: ParquetColumns,
+    next_row_group: usize,
+    buffered: VecDeque<MarketTrade>, // 当前行组中尚未取出的成交
+    finished: bool,
+}
+
+impl ParquetTradeSource
+{
+    /// 打开 Parquet 文件并确认 `columns` 中的列都存在。此时只读取文件尾部的元数据。
+    pub fn open(path: impl AsRef<Path>, columns: ParquetColumns) -> Result<Self, ExchangeError>
+    {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| ExchangeError::DataSourceError(format!("Failed to open {}: {}", path.display(), err)))?;
+        let reader = SerializedFileReader::new(file).map_err(|err| ExchangeError::DataSourceError(format!("Invalid parquet file {}: {}", path.display(), err)))?;
+
+        let schema = reader.metadata().file_metadata().schema();
+        let fields = columns.names()
+                            .into_iter()
+                            .map(|name| {
+                                schema.get_fields()
+                                      .iter()
+                                      .find(|field| field.name() == name)
+                                      .cloned()
+                                      .ok_or_else(|| ExchangeError::DataSourceError(format!("Column {} not found in {}", name, path.display())))
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+        let projection = Type::group_type_builder(schema.name()).with_fields(fields)
+                                                                 .build()
+                                                                 .map_err(|err| ExchangeError::DataSourceError(err.to_string()))?;
+
+        Ok(Self { reader,
+                  projection,
+                  columns,
+                  next_row_group: 0,
+                  buffered: VecDeque::new(),
+                  finished: false })
+    }
+
+    /// 文件中的行组数量。
+    pub fn num_row_groups(&self) -> usize
+    {
+        self.reader.num_row_groups()
+    }
+
+    /// 解码下一个行组中的全部成交，所有行组读完时返回 `None`。
+    pub fn next_batch(&mut self) -> Result<Option<Vec<MarketTrade>>, ExchangeError>
+    {
+        if self.next_row_group >= self.reader.num_row_groups() {
+            return Ok(None);
+        }
+        let row_group = self.reader.get_row_group(self.next_row_group).map_err(|err| ExchangeError::DataSourceError(err.to_string()))?;
+        self.next_row_group += 1;
+        row_group.get_row_iter(Some(self.projection.clone()))
+                 .map_err(|err| ExchangeError::DataSourceError(err.to_string()))?
+                 .map(|row| {
+                     let row = row.map_err(|err| ExchangeError::DataSourceError(err.to_string()))?;
+                     self.trade_from_row(&row)
+                 })
+                 .collect::<Result<Vec<_>, _>>()
+                 .map(Some)
+    }
+
+    /// 读取下一笔成交，读完时返回 `None`。遇到无法解析的行组时返回错误，之后不再返回成交。
+    pub fn next_trade(&mut self) -> Result<Option<MarketTrade>, ExchangeError>
+    {
+        while self.buffered.is_empty() && !self.finished {
+            match self.next_batch() {
+                | Ok(Some(batch)) => self.buffered.extend(batch),
+                | Ok(None) => self.finished = true,
+                | Err(err) => {
+                    self.finished = true;
+                    return Err(err);
+                }
+            }
+        }
+        Ok(self.buffered.pop_front())
+    }
+
+    /// 在后台线程中逐个行组读取，每个行组转为一批 `MarketEvent<MarketTrade>` 发送到返回的通道，读完时关闭通道。
+    ///
+    /// 通道最多积压 `capacity` 个行组，消费跟不上时读取线程等待，不会把整个文件读入内存。
+    /// 读取出错时把错误发送到通道后关闭通道。
+    pub fn into_stream(mut self, capacity: usize) -> Receiver<Result<Vec<MarketEvent<MarketTrade>>, ExchangeError>>
+    {
+        let (batch_tx, batch_rx) = mpsc::channel(capacity);
+        std::thread::spawn(move || loop {
+            let batch = match self.next_batch() {
+                | Ok(Some(batch)) => Ok(batch),
+                | Ok(None) => break,
+                | Err(err) => Err(err),
+            };
+            let failed = batch.is_err();
+            let events = batch.map(|batch| {
+                                  batch.into_iter()
+                                       .map(|trade| {
+                                           let (base, quote) = parse_base_and_quote(&trade.symbol);
+                                           MarketEvent::from_swap_trade_clickhouse(trade, base, quote)
+                                       })
+                                       .collect()
+                              });
+            if batch_tx.blocking_send(events).is_err() || failed {
+                break;
+            }
+        });
+        batch_rx
+    }
+
+    fn trade_from_row(&self, row: &Row) -> Result<MarketTrade, ExchangeError>
+    {
+        let field = |name: &str| {
+            row.get_column_iter()
+               .find(|(column, _)| column.as_str() == name)
+               .map(|(_, field)| field)
+               .ok_or_else(|| ExchangeError::DataSourceError(format!("Column {} missing from row", name)))
+        };
+        let exchange = match &self.columns.exchange {
+            | Some(name) => field_to_string(name, field(name)?)?,
+            | None => String::new(),
+        };
+        Ok(MarketTrade { exchange,
+                         symbol: field_to_string(&self.columns.symbol, field(&self.columns.symbol)?)?,
+                         side: field_to_string(&self.columns.side, field(&self.columns.side)?)?,
+                         price: field_to_f64(&self.columns.price, field(&self.columns.price)?)?,
+                         timestamp: field_to_millis(&self.columns.timestamp, field(&self.columns.timestamp)?)?,
+                         amount: field_to_f64(&self.columns.amount, field(&self.columns.amount)?)? })
+    }
+}
+
+fn unsupported(name: &str, field: &Field) -> ExchangeError
+{
+    ExchangeError::DataSourceError(format!("Column {} has unsupported value {:?}", name, field))
+}
+
+fn field_to_string(name: &str, field: &Field) -> Result<String, ExchangeError>
+{
+    match field {
+        | Field::Str(value) => Ok(value.clone()),
+        | Field::Bytes(bytes) => bytes.as_utf8().map(str::to_string).map_err(|_| unsupported(name, field)),
+        | _ => Err(unsupported(name, field)),
+    }
+}
+
+fn field_to_f64(name: &str, field: &Field) -> Result<f64, ExchangeError>
+{
+    match field {
+        | Field::Double(value) => Ok(*value),
+        | Field::Float(value) => Ok(*value as f64),
+        | Field::Long(value) => Ok(*value as f64),
+        | Field::Int(value) => Ok(*value as f64),
+        | _ => Err(unsupported(name, field)),
+    }
+}
+
+fn field_to_millis(name: &str, field: &Field) -> Result<i64, ExchangeError>
+{
+    match field {
+        | Field::Long(value) | Field::TimestampMillis(value) => Ok(*value),
+        | Field::Int(value) => Ok(*value as i64),
+        | Field::TimestampMicros(value) => Ok(value / 1000),
+        | _ => Err(unsupported(name, field)),
+    }
+}
+
+impl Iterator for ParquetTradeSource
+{
+    type Item = Result<MarketTrade, ExchangeError>;
+
+    /// 取出下一笔成交。读取出错时返回一次错误，之后结束。
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        self.next_trade().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use parquet::{
+        data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type},
+        file::{properties::WriterProperties, writer::SerializedFileWriter},
+        schema::parser::parse_message_type,
+    };
+    use std::sync::Arc;
+
+    const SCHEMA: &str = "message trades {
+        REQUIRED BYTE_ARRAY instrument (UTF8);
+        REQUIRED BYTE_ARRAY taker_side (UTF8);
+        REQUIRED DOUBLE px;
+        REQUIRED INT64 ts (TIMESTAMP(MICROS, true));
+        REQUIRED DOUBLE qty;
+        REQUIRED DOUBLE unused;
+    }";
+
+    /// 测试成交：(交易对, 方向, 价格, 时间戳, 数量)
+    type TestTrade<'a> = (&'a str, &'a str, f64, i64, f64);
+
+    /// 写入一个每个行组各有若干笔成交的测试文件，列名与默认值不同。
+    fn write_trades(path: &Path, row_groups: &[&[TestTrade]])
+    {
+        let schema = Arc::new(parse_message_type(SCHEMA).unwrap());
+        let mut writer = SerializedFileWriter::new(File::create(path).unwrap(), schema, Arc::new(WriterProperties::builder().build())).unwrap();
+        for trades in row_groups {
+            let symbols: Vec<ByteArray> = trades.iter().map(|trade| ByteArray::from(trade.0)).collect();
+            let sides: Vec<ByteArray> = trades.iter().map(|trade| ByteArray::from(trade.1)).collect();
+            let prices: Vec<f64> = trades.iter().map(|trade| trade.2).collect();
+            let timestamps: Vec<i64> = trades.iter().map(|trade| trade.3).collect();
+            let amounts: Vec<f64> = trades.iter().map(|trade| trade.4).collect();
+
+            let mut row_group = writer.next_row_group().unwrap();
+            for values in [&symbols, &sides] {
+                let mut column = row_group.next_column().unwrap().unwrap();
+                column.typed::<ByteArrayType>().write_batch(values, None, None).unwrap();
+                column.close().unwrap();
+            }
+            let mut column = row_group.next_column().unwrap().unwrap();
+            column.typed::<DoubleType>().write_batch(&prices, None, None).unwrap();
+            column.close().unwrap();
+            let mut column = row_group.next_column().unwrap().unwrap();
+            column.typed::<Int64Type>().write_batch(&timestamps, None, None).unwrap();
+            column.close().unwrap();
+            for values in [&amounts, &vec![0.0; trades.len()]] {
+                let mut column = row_group.next_column().unwrap().unwrap();
+                column.typed::<DoubleType>().write_batch(values, None, None).unwrap();
+                column.close().unwrap();
+            }
+            row_group.close().unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    fn remapped_columns() -> ParquetColumns
+    {
+        ParquetColumns { symbol: "instrument".to_string(),
+                         side: "taker_side".to_string(),
+                         price: "px".to_string(),
+                         timestamp: "ts".to_string(),
+                         amount: "qty".to_string(),
+                         ..Default::default() }
+    }
+
+    #[test]
+    fn test_remapped_columns_read_row_group_at_a_time()
+    {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trades.parquet");
+        write_trades(&path, &[&[("ETHUSDT", "buy", 16400.5, 1_000_000, 0.2), ("ETHUSDT", "sell", 16401.0, 1_001_000, 0.3)], &[("BTCUSDT", "sell", 30000.0, 1_002_000, 1.5)]]);
+
+        let mut source = ParquetTradeSource::open(&path, remapped_columns()).unwrap();
+        assert_eq!(source.num_row_groups(), 2);
+
+        let first = source.next_batch().unwrap().unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[0].symbol, "ETHUSDT");
+        assert_eq!(first[0].price, 16400.5);
+        // TIMESTAMP(MICROS) 换算为毫秒
+        assert_eq!(first[1].timestamp, 1001);
+
+        let trades: Vec<MarketTrade> = source.collect::<Result<_, _>>().unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!((trades[0].side.as_str(), trades[0].amount), ("sell", 1.5));
+    }
+
+    #[test]
+    fn test_missing_column_is_rejected_on_open()
+    {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trades.parquet");
+        write_trades(&path, &[&[("ETHUSDT", "buy", 16400.5, 1_000_000, 0.2)]]);
+
+        assert!(matches!(ParquetTradeSource::open(&path, ParquetColumns::default()), Err(ExchangeError::DataSourceError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_unparsable_row_is_returned_as_error_once()
+    {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trades.parquet");
+        write_trades(&path, &[&[("ETHUSDT", "buy", 16400.5, 1_000_000, 0.2)]]);
+        // 价格列指向字符串列，行组可以读取但无法转换为成交
+        let columns = ParquetColumns { price: "instrument".to_string(),
+                                       ..remapped_columns() };
+
+        let mut source = ParquetTradeSource::open(&path, columns.clone()).unwrap();
+        assert!(matches!(source.next(), Some(Err(ExchangeError::DataSourceError(_)))));
+        assert!(source.next().is_none());
+
+        let mut batch_rx = ParquetTradeSource::open(&path, columns).unwrap().into_stream(1);
+        assert!(matches!(batch_rx.recv().await, Some(Err(ExchangeError::DataSourceError(_)))));
+        assert!(batch_rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_yields_one_batch_per_row_group()
+    {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trades.parquet");
+        write_trades(&path, &[&[("ETHUSDT", "buy", 16400.5, 1_000_000, 0.2)], &[("BTCUSDT", "sell", 30000.0, 1_002_000, 1.5)]]);
+
+        let mut batch_rx = ParquetTradeSource::open(&path, remapped_columns()).unwrap().into_stream(1);
+
+        let first = batch_rx.recv().await.unwrap().unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!((first[0].instrument.base.to_string(), first[0].exchange_ts), ("ETH".to_string(), 1000));
+        assert_eq!(batch_rx.recv().await.unwrap().unwrap()[0].kind.symbol, "BTCUSDT");
+        assert!(batch_rx.recv().await.is_none());
+    }
+}
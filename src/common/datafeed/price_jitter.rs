@@ -0,0 +1,94 @@
+use crate::hourglass::clickhouse_api::datatype::clickhouse_trade_data::MarketTrade;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// 价格扰动配置，用于回测稳健性测试。
+///
+/// 在 `band_bps` 的基点区间内对每一笔市场成交价施加均匀分布的随机扰动，
+/// 相同的 `seed` 会产生完全相同的扰动序列，便于做可复现的蒙特卡洛检验。默认关闭。
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct PriceJitterConfig
+{
+    pub enabled: bool, // 是否启用扰动
+    pub band_bps: f64, // 扰动区间（基点），成交价会落在 price * (1 ± band_bps / 10_000) 之内
+    pub seed: u64,     // 随机数种子
+}
+
+/// 包裹市场数据流的价格扰动器，在 [`MarketTrade`] 进入撮合之前对价格进行扰动。
+#[derive(Debug)]
+pub struct PriceJitter
+{
+    config: PriceJitterConfig,
+    rng: StdRng,
+}
+
+impl PriceJitter
+{
+    pub fn new(config: PriceJitterConfig) -> Self
+    {
+        let rng = StdRng::seed_from_u64(config.seed);
+        Self { config, rng }
+    }
+
+    pub fn is_enabled(&self) -> bool
+    {
+        self.config.enabled && self.config.band_bps > 0.0
+    }
+
+    /// 对单笔 [`MarketTrade`] 的价格施加扰动。未启用时原样返回。
+    pub fn apply(&mut self, mut trade: MarketTrade) -> MarketTrade
+    {
+        if self.is_enabled() {
+            let band = self.config.band_bps / 10_000.0;
+            let offset: f64 = self.rng.gen_range(-band..=band);
+            trade.price *= 1.0 + offset;
+        }
+        trade
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn create_test_trade(price: f64) -> MarketTrade
+    {
+        MarketTrade { exchange: "binance-futures".to_string(),
+                      symbol: "BTC_USDT".to_string(),
+                      side: "buy".to_string(),
+                      price,
+                      timestamp: 1625247600000,
+                      amount: 1.0 }
+    }
+
+    fn jittered_prices(config: PriceJitterConfig) -> Vec<f64>
+    {
+        let mut jitter = PriceJitter::new(config);
+        (0..100).map(|_| jitter.apply(create_test_trade(100.0)).price).collect()
+    }
+
+    #[test]
+    fn test_disabled_jitter_keeps_price()
+    {
+        let prices = jittered_prices(PriceJitterConfig { enabled: false, band_bps: 10.0, seed: 7 });
+        assert!(prices.iter().all(|p| *p == 100.0));
+    }
+
+    #[test]
+    fn test_jitter_stays_within_band()
+    {
+        let prices = jittered_prices(PriceJitterConfig { enabled: true, band_bps: 5.0, seed: 7 });
+        assert!(prices.iter().all(|p| (*p - 100.0).abs() <= 100.0 * 5.0 / 10_000.0 + 1e-9));
+        assert!(prices.iter().any(|p| *p != 100.0));
+    }
+
+    #[test]
+    fn test_jitter_is_reproducible_per_seed()
+    {
+        let config = PriceJitterConfig { enabled: true, band_bps: 5.0, seed: 42 };
+        assert_eq!(jittered_prices(config.clone()), jittered_prices(config));
+        assert_ne!(jittered_prices(PriceJitterConfig { enabled: true, band_bps: 5.0, seed: 42 }),
+                   jittered_prices(PriceJitterConfig { enabled: true, band_bps: 5.0, seed: 43 }));
+    }
+}
@@ -0,0 +1,93 @@
+use tokio::time::{self, Duration, Instant};
+
+/// 按墙上时钟控制历史行情的回放速度。
+///
+/// `speed` 为回放速度相对真实时间的倍数：`1.0` 按真实节奏回放，`10.0` 以十倍速回放。
+/// `speed` 为 `0`、负数、无穷大或 `NaN` 时不做任何等待，即尽可能快地回放，适合批量回测。
+///
+/// 相邻两条行情之间等待的时间与它们交易所时间戳的差值成正比（差值除以 `speed`）。等待的目标时刻以第一条行情为锚点计算，
+/// 处理行情本身耗费的时间会从下一次等待中扣除，长时间回放不会累积漂移；时间戳倒退的行情不等待。
+#[derive(Clone, Debug)]
+pub struct ReplayClock
+{
+    speed: f64,
+    anchor: Option<(i64, Instant)>, // (第一条行情的交易所时间戳, 交出它时的墙上时刻)
+}
+
+impl ReplayClock
+{
+    pub fn new(speed: f64) -> Self
+    {
+        Self { speed, anchor: None }
+    }
+
+    /// 尽可能快地回放，不做任何等待。
+    pub fn as_fast_as_possible() -> Self
+    {
+        Self::new(0.0)
+    }
+
+    /// 是否需要按墙上时钟等待。
+    pub fn is_paced(&self) -> bool
+    {
+        self.speed.is_finite() && self.speed > 0.0
+    }
+
+    /// 在 `now` 时刻交出交易所时间戳为 `exchange_ts` 的行情之前需要等待的时长。第一条行情只记录锚点，不等待。
+    pub fn delay_at(&mut self, exchange_ts: i64, now: Instant) -> Duration
+    {
+        if !self.is_paced() {
+            return Duration::ZERO;
+        }
+        let Some((anchor_ts, anchor_instant)) = self.anchor
+        else {
+            self.anchor = Some((exchange_ts, now));
+            return Duration::ZERO;
+        };
+
+        let elapsed_ms = (exchange_ts - anchor_ts).max(0) as f64;
+        let target = anchor_instant + Duration::from_secs_f64(elapsed_ms / 1000.0 / self.speed);
+        target.saturating_duration_since(now)
+    }
+
+    /// 等待到交易所时间戳为 `exchange_ts` 的行情按回放速度应当交出的时刻。
+    pub async fn pace(&mut self, exchange_ts: i64)
+    {
+        let delay = self.delay_at(exchange_ts, Instant::now());
+        if !delay.is_zero() {
+            time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_delay_scales_with_exchange_time_gap_and_speed()
+    {
+        let start = Instant::now();
+        let mut clock = ReplayClock::new(10.0);
+        assert_eq!(clock.delay_at(1_000, start), Duration::ZERO);
+        // 交易所时间过去 500 毫秒，十倍速下需要在锚点之后 50 毫秒交出
+        assert_eq!(clock.delay_at(1_500, start), Duration::from_millis(50));
+        // 处理耗费的 20 毫秒从等待中扣除
+        assert_eq!(clock.delay_at(1_500, start + Duration::from_millis(20)), Duration::from_millis(30));
+        // 时间戳倒退时不等待
+        assert_eq!(clock.delay_at(900, start + Duration::from_millis(20)), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_unpaced_speeds_never_sleep()
+    {
+        let start = Instant::now();
+        for speed in [0.0, -1.0, f64::INFINITY, f64::NAN] {
+            let mut clock = ReplayClock::new(speed);
+            assert!(!clock.is_paced());
+            assert_eq!(clock.delay_at(1_000, start), Duration::ZERO);
+            assert_eq!(clock.delay_at(60_000, start), Duration::ZERO);
+        }
+    }
+}
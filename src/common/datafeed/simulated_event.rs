@@ -0,0 +1,241 @@
+use crate::{
+    common::datafeed::timestamp_normalizer::TimestampNormalizer,
+    hourglass::clickhouse_api::datatype::{clickhouse_liquidation_data::MarketLiquidation, clickhouse_trade_data::MarketTrade, order_book_25::OrderBook25},
+    hourglass_log::warn,
+};
+use clickhouse::query::RowCursor;
+use std::collections::VecDeque;
+
+/// 回放中按时间戳顺序交给交易所处理的一条外部事件。
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulatedEvent
+{
+    MarketTrade(MarketTrade),               // 历史成交
+    ExternalLiquidation(MarketLiquidation), // 历史强平单，以主动成交的形式冲击挂单与行情流
+    DepthSnapshot(Box<OrderBook25>),        // 历史深度快照，覆盖本地深度订单簿并成交与之交叉的挂单
+}
+
+impl SimulatedEvent
+{
+    pub fn timestamp(&self) -> i64
+    {
+        match self {
+            | SimulatedEvent::MarketTrade(trade) => trade.timestamp,
+            | SimulatedEvent::ExternalLiquidation(liquidation) => liquidation.timestamp,
+            | SimulatedEvent::DepthSnapshot(snapshot) => snapshot.timestamp,
+        }
+    }
+}
+
+/// 可选的强平事件流，与成交流按时间戳合并回放。
+///
+/// 强平事件需按时间戳升序提供。与成交时间戳相同的强平事件先于这些成交处理，模拟强平单引发的价格冲击。
+///
+/// 若成交数据本身已经包含强平单的成交（例如 Binance 的强平单也会出现在 `aggTrade` 中），
+/// 再注入强平事件会重复计入这部分成交量，此时只应在需要额外压力测试时启用。
+///
+/// 设置了 [`TimestampNormalizer`] 时，强平事件的时间戳与成交一样先按所属数据流规范化为毫秒，再与成交流合并。
+pub struct LiquidationFeed
+{
+    cursor: Option<RowCursor<MarketLiquidation>>,
+    pending: VecDeque<MarketLiquidation>,
+    timestamp_normalizer: Option<TimestampNormalizer>,
+}
+
+impl LiquidationFeed
+{
+    /// 从 [`ClickHouseClient::query_liquidations`](crate::hourglass::clickhouse_api::queries_operations::ClickHouseClient::query_liquidations) 的游标读取强平事件。
+    pub fn from_cursor(cursor: RowCursor<MarketLiquidation>) -> Self
+    {
+        Self { cursor: Some(cursor),
+               pending: VecDeque::new(),
+               timestamp_normalizer: None }
+    }
+
+    /// 回放已经加载到内存中的强平事件，`rows` 需按时间戳升序。
+    pub fn from_rows(rows: Vec<MarketLiquidation>) -> Self
+    {
+        Self { cursor: None,
+               pending: rows.into(),
+               timestamp_normalizer: None }
+    }
+
+    /// 按 `normalizer` 规范化强平事件的时间戳。通过 [`ExchangeBuilder`](crate::hourglass::ExchangeBuilder) 配置时，会自动使用交易所的规范化配置。
+    pub fn with_timestamp_normalizer(self, normalizer: TimestampNormalizer) -> Self
+    {
+        Self { timestamp_normalizer: Some(normalizer),
+               ..self }
+    }
+
+    /// 取出下一条时间戳不晚于 `until` 的强平事件；`until` 为 `None` 表示成交流已经结束，依次取出剩余的全部事件。
+    /// `until` 与返回的时间戳都是规范化之后的毫秒。
+    pub async fn pop_due(&mut self, until: Option<i64>) -> Option<MarketLiquidation>
+    {
+        if self.pending.is_empty() {
+            if let Some(cursor) = &mut self.cursor {
+                match cursor.next().await {
+                    | Ok(Some(row)) => self.pending.push_back(row),
+                    | Ok(None) => self.cursor = None,
+                    | Err(e) => {
+                        warn!("Failed to read liquidation row, dropping the liquidation stream: {:?}", e);
+                        self.cursor = None;
+                    }
+                }
+            }
+        }
+
+        let next = self.pending.front()?;
+        let timestamp = match &self.timestamp_normalizer {
+            | Some(normalizer) => normalizer.normalize(&next.exchange, next.timestamp),
+            | None => next.timestamp,
+        };
+        if until.is_some_and(|until| timestamp > until) {
+            return None;
+        }
+        let mut liquidation = self.pending.pop_front()?;
+        liquidation.timestamp = timestamp;
+        Some(liquidation)
+    }
+}
+
+/// 可选的深度快照流，与成交流按时间戳合并回放。
+///
+/// 快照需按时间戳升序提供。与成交时间戳相同的快照先于这些成交处理，成交时看到的是该时刻的深度；
+/// 每条快照通过 [`HourglassAccount::apply_depth_snapshot`](crate::hourglass::account::HourglassAccount::apply_depth_snapshot) 覆盖本地深度，
+/// 之后的市价单与可立即成交的限价单按快照的档位成交。
+pub struct DepthFeed
+{
+    cursor: Option<RowCursor<OrderBook25>>,
+    pending: VecDeque<OrderBook25>,
+}
+
+impl DepthFeed
+{
+    /// 从 ClickHouse 查询 [`OrderBook25`] 得到的游标读取深度快照。
+    pub fn from_cursor(cursor: RowCursor<OrderBook25>) -> Self
+    {
+        Self { cursor: Some(cursor),
+               pending: VecDeque::new() }
+    }
+
+    /// 回放已经加载到内存中的深度快照，`rows` 需按时间戳升序。
+    pub fn from_rows(rows: Vec<OrderBook25>) -> Self
+    {
+        Self { cursor: None,
+               pending: rows.into() }
+    }
+
+    /// 取出下一条时间戳不晚于 `until` 的深度快照；`until` 为 `None` 表示成交流已经结束，依次取出剩余的全部快照。
+    pub async fn pop_due(&mut self, until: Option<i64>) -> Option<OrderBook25>
+    {
+        if self.pending.is_empty() {
+            if let Some(cursor) = &mut self.cursor {
+                match cursor.next().await {
+                    | Ok(Some(row)) => self.pending.push_back(row),
+                    | Ok(None) => self.cursor = None,
+                    | Err(e) => {
+                        warn!("Failed to read depth snapshot row, dropping the depth stream: {:?}", e);
+                        self.cursor = None;
+                    }
+                }
+            }
+        }
+
+        let next = self.pending.front()?;
+        if until.is_some_and(|until| next.timestamp > until) {
+            return None;
+        }
+        self.pending.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            datafeed::timestamp_normalizer::{TimestampNormalization, TimestampUnit},
+            event::AccountEventKind,
+            instrument::{kind::InstrumentKind, Instrument},
+            order::{identification::client_order_id::ClientOrderId, order_instructions::OrderInstruction, states::request_open::RequestOpen, Order},
+            Side,
+        },
+        hourglass::account::account_handlers::trade_handler::TradeHandler,
+        test_utils::create_test_account,
+        Exchange,
+    };
+    use tokio::sync::mpsc;
+
+    fn create_test_liquidation(side: &str, price: f64, timestamp: i64) -> MarketLiquidation
+    {
+        MarketLiquidation { exchange: "binance-futures".to_string(),
+                            symbol: "ETHUSDT".to_string(),
+                            side: side.to_string(),
+                            price,
+                            timestamp,
+                            amount: 2.0 }
+    }
+
+    #[tokio::test]
+    async fn test_liquidations_merge_before_trades_at_or_after_their_timestamp()
+    {
+        let mut feed = LiquidationFeed::from_rows(vec![create_test_liquidation("sell", 16300.0, 5), create_test_liquidation("sell", 16200.0, 10), create_test_liquidation("buy", 16500.0, 20)]);
+
+        let mut merged = Vec::new();
+        for trade_timestamp in [1, 10, 15] {
+            while let Some(liquidation) = feed.pop_due(Some(trade_timestamp)).await {
+                merged.push(SimulatedEvent::ExternalLiquidation(liquidation).timestamp());
+            }
+            merged.push(trade_timestamp);
+        }
+        // 成交流结束后补齐剩余的强平事件
+        while let Some(liquidation) = feed.pop_due(None).await {
+            merged.push(liquidation.timestamp);
+        }
+        assert_eq!(merged, vec![1, 5, 10, 10, 15, 20]);
+    }
+
+    #[tokio::test]
+    async fn test_liquidation_timestamps_normalized_before_merging()
+    {
+        let normalizer = TimestampNormalizer::default().stream("binance-futures", TimestampNormalization::new(TimestampUnit::Microseconds, -1));
+        let mut feed = LiquidationFeed::from_rows(vec![create_test_liquidation("sell", 16300.0, 5_000), create_test_liquidation("buy", 16500.0, 20_000)]).with_timestamp_normalizer(normalizer);
+
+        // 微秒时间戳 5_000 规范化为 4 毫秒，先于 10 毫秒的成交；20_000 规范化为 19 毫秒，晚于该成交
+        assert_eq!(feed.pop_due(Some(10)).await.map(|liquidation| liquidation.timestamp), Some(4));
+        assert!(feed.pop_due(Some(10)).await.is_none());
+        assert_eq!(feed.pop_due(None).await.map(|liquidation| liquidation.timestamp), Some(19));
+    }
+
+    #[tokio::test]
+    async fn test_external_liquidation_sweeps_resting_orders()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                    exchange: Exchange::Hourglass,
+                                    instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                                    timestamp: 1234567,
+                                    cid: Some(ClientOrderId("liquidation".into())),
+                                    side: Side::Buy,
+                                    state: RequestOpen { price: 16400.0,
+                                                         size: 0.25,
+                                                         reduce_only: false,
+                                                         tag: None } })
+               .await
+               .unwrap();
+
+        // 多头强平单以卖方主动成交的形式打穿买单
+        account.handle_trade_data(&create_test_liquidation("sell", 16300.0, 1234568).to_market_trade().unwrap()).await.unwrap();
+
+        let mut filled = 0.0;
+        while let Ok(event) = account_event_rx.try_recv() {
+            if let AccountEventKind::Trade(trade) = event.kind {
+                filled += trade.size;
+            }
+        }
+        assert_eq!(filled, 0.25);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::hourglass::clickhouse_api::datatype::clickhouse_trade_data::MarketTrade;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 数据源原始时间戳的单位。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TimestampUnit
+{
+    Seconds,
+    #[default]
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+}
+
+impl TimestampUnit
+{
+    /// 把该单位的时间戳换算为毫秒，更细的单位向下取整。
+    pub fn to_millis(self, timestamp: i64) -> i64
+    {
+        match self {
+            | TimestampUnit::Seconds => timestamp.saturating_mul(1_000),
+            | TimestampUnit::Milliseconds => timestamp,
+            | TimestampUnit::Microseconds => timestamp.div_euclid(1_000),
+            | TimestampUnit::Nanoseconds => timestamp.div_euclid(1_000_000),
+        }
+    }
+}
+
+/// 单个数据流的时间戳规范化方式：先按 `unit` 换算为毫秒，再加上固定的 `skew_correction_ms`。
+///
+/// `skew_correction_ms` 用于校正交易所时钟的系统性偏差，例如某交易所时钟比参考时钟快 15 毫秒时设为 `-15`。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TimestampNormalization
+{
+    #[serde(default)]
+    pub unit: TimestampUnit,
+    #[serde(default)]
+    pub skew_correction_ms: i64,
+}
+
+impl TimestampNormalization
+{
+    pub fn new(unit: TimestampUnit, skew_correction_ms: i64) -> Self
+    {
+        Self { unit, skew_correction_ms }
+    }
+
+    pub fn normalize(&self, timestamp: i64) -> i64
+    {
+        self.unit.to_millis(timestamp).saturating_add(self.skew_correction_ms)
+    }
+}
+
+/// 在成交进入排序与撮合之前，把各数据流的时间戳统一为毫秒并校正时钟偏差。
+///
+/// 数据流按 [`MarketTrade`] 的 `exchange` 字段区分，未单独配置的数据流使用 `default`（默认为不做任何转换的毫秒）。
+/// 混合回放时间戳单位不同的数据源时，不做规范化会使不同数据源的成交顺序错乱。
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct TimestampNormalizer
+{
+    #[serde(default)]
+    pub default: TimestampNormalization,
+    #[serde(default)]
+    pub streams: HashMap<String, TimestampNormalization>,
+}
+
+impl TimestampNormalizer
+{
+    /// 为 `exchange` 字段等于 `stream` 的数据流单独设置规范化方式。
+    pub fn stream(mut self, stream: impl Into<String>, normalization: TimestampNormalization) -> Self
+    {
+        self.streams.insert(stream.into(), normalization);
+        self
+    }
+
+    /// 按数据流 `stream` 的配置把时间戳规范化为毫秒。
+    pub fn normalize(&self, stream: &str, timestamp: i64) -> i64
+    {
+        self.streams.get(stream).unwrap_or(&self.default).normalize(timestamp)
+    }
+
+    /// 把一笔成交的时间戳规范化为毫秒。
+    pub fn apply(&self, mut trade: MarketTrade) -> MarketTrade
+    {
+        trade.timestamp = self.normalize(&trade.exchange, trade.timestamp);
+        trade
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn create_test_trade(exchange: &str, timestamp: i64) -> MarketTrade
+    {
+        MarketTrade { exchange: exchange.to_string(),
+                      symbol: "BTCUSDT".to_string(),
+                      side: "buy".to_string(),
+                      price: 100.0,
+                      timestamp,
+                      amount: 1.0 }
+    }
+
+    #[test]
+    fn test_micro_and_nano_timestamps_normalized_to_millis()
+    {
+        let normalizer = TimestampNormalizer::default().stream("okex", TimestampNormalization::new(TimestampUnit::Microseconds, 0))
+                                                       .stream("bybit", TimestampNormalization::new(TimestampUnit::Nanoseconds, 0));
+
+        assert_eq!(normalizer.apply(create_test_trade("okex", 1_714_867_200_123_456)).timestamp, 1_714_867_200_123);
+        assert_eq!(normalizer.apply(create_test_trade("bybit", 1_714_867_200_123_456_789)).timestamp, 1_714_867_200_123);
+        // 未单独配置的数据流按默认的毫秒处理
+        assert_eq!(normalizer.apply(create_test_trade("binance", 1_714_867_200_123)).timestamp, 1_714_867_200_123);
+    }
+
+    #[test]
+    fn test_skew_correction_applied_after_unit_conversion()
+    {
+        let normalizer = TimestampNormalizer::default().stream("okex", TimestampNormalization::new(TimestampUnit::Microseconds, -15));
+        assert_eq!(normalizer.apply(create_test_trade("okex", 1_714_867_200_123_999)).timestamp, 1_714_867_200_108);
+        assert_eq!(TimestampNormalization::new(TimestampUnit::Seconds, 5).normalize(1_714_867_200), 1_714_867_200_005);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::hourglass::clickhouse_api::datatype::clickhouse_trade_data::MarketTrade;
+use std::collections::VecDeque;
+
+/// 为同一时间戳内的多品种 [`MarketTrade`] 提供确定性的处理顺序。
+///
+/// # 排序规则
+///
+/// 1. 按 `timestamp` 升序（数据源本身已保证）。
+/// 2. 同一 `timestamp` 内，按 `symbol` 字典序，再按 `exchange` 字典序排序。
+/// 3. `timestamp`、`symbol`、`exchange` 都相同的成交保持其在数据流中的到达顺序（稳定排序）。
+///
+/// 这样多品种回测在同一时间戳上的撮合顺序不再依赖数据流的到达顺序，消除每次运行之间的差异。
+#[derive(Debug, Default)]
+pub struct TimestampSequencer
+{
+    batch: Vec<MarketTrade>,
+    ready: VecDeque<MarketTrade>,
+}
+
+impl TimestampSequencer
+{
+    /// 推入一条成交。当时间戳发生变化时，上一时间戳的成交会被排序并转入就绪队列。
+    pub fn push(&mut self, trade: MarketTrade)
+    {
+        if self.batch.first().is_some_and(|first| first.timestamp != trade.timestamp) {
+            self.flush();
+        }
+        self.batch.push(trade);
+    }
+
+    /// 数据流结束时调用，将尚未排序的最后一批成交转入就绪队列。返回是否有成交被转入。
+    pub fn flush(&mut self) -> bool
+    {
+        if self.batch.is_empty() {
+            return false;
+        }
+        // sort_by 为稳定排序，相同 key 的成交保持到达顺序
+        self.batch.sort_by(|a, b| a.symbol.cmp(&b.symbol).then_with(|| a.exchange.cmp(&b.exchange)));
+        self.ready.extend(self.batch.drain(..));
+        true
+    }
+
+    /// 下一条已经确定顺序的成交的时间戳。
+    pub fn peek_timestamp(&self) -> Option<i64>
+    {
+        self.ready.front().map(|trade| trade.timestamp)
+    }
+
+    /// 取出下一条已经确定顺序的成交。
+    pub fn pop(&mut self) -> Option<MarketTrade>
+    {
+        self.ready.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn create_test_trade(symbol: &str, timestamp: i64, price: f64) -> MarketTrade
+    {
+        MarketTrade { exchange: "binance-futures".to_string(),
+                      symbol: symbol.to_string(),
+                      side: "buy".to_string(),
+                      price,
+                      timestamp,
+                      amount: 1.0 }
+    }
+
+    fn drain(trades: Vec<MarketTrade>) -> Vec<(String, i64, f64)>
+    {
+        let mut sequencer = TimestampSequencer::default();
+        let mut out = Vec::new();
+        for trade in trades {
+            sequencer.push(trade);
+            while let Some(trade) = sequencer.pop() {
+                out.push((trade.symbol, trade.timestamp, trade.price));
+            }
+        }
+        sequencer.flush();
+        while let Some(trade) = sequencer.pop() {
+            out.push((trade.symbol, trade.timestamp, trade.price));
+        }
+        out
+    }
+
+    #[test]
+    fn test_same_timestamp_trades_are_ordered_by_symbol_then_sequence()
+    {
+        let arrival_a = vec![create_test_trade("ETH_USDT", 1, 1.0),
+                             create_test_trade("BTC_USDT", 1, 2.0),
+                             create_test_trade("ETH_USDT", 1, 3.0),
+                             create_test_trade("BTC_USDT", 2, 4.0),];
+        let arrival_b = vec![create_test_trade("BTC_USDT", 1, 2.0),
+                             create_test_trade("ETH_USDT", 1, 1.0),
+                             create_test_trade("ETH_USDT", 1, 3.0),
+                             create_test_trade("BTC_USDT", 2, 4.0),];
+
+        let expected = vec![("BTC_USDT".to_string(), 1, 2.0),
+                            ("ETH_USDT".to_string(), 1, 1.0),
+                            ("ETH_USDT".to_string(), 1, 3.0),
+                            ("BTC_USDT".to_string(), 2, 4.0),];
+        assert_eq!(drain(arrival_a), expected);
+        assert_eq!(drain(arrival_b), expected);
+    }
+
+    #[test]
+    fn test_flush_on_empty_sequencer()
+    {
+        let mut sequencer = TimestampSequencer::default();
+        assert!(!sequencer.flush());
+        assert!(sequencer.pop().is_none());
+    }
+}
@@ -2,8 +2,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     common::{
-        account_positions::AccountPositions,
-        balance::TokenBalance,
+        account_positions::{liquidation::Liquidation, margin_update::MarginUpdate, positions_snapshot::AccountPositionsSnapshot, realised_pnl::RealisedPnl},
+        balance::{TokenBalance, Withdrawal},
         order::{
             states::{
                 cancelled::Cancelled,
@@ -12,7 +12,7 @@ use crate::{
             },
             Order,
         },
-        trade::ClientTrade,
+        trade::{ClientTrade, ClientTradeId},
     },
     hourglass::account::account_config::AccountConfig,
     Exchange,
@@ -36,17 +36,22 @@ pub enum AccountEventKind
     OrdersCancelled(Vec<Order<Cancelled>>),
     OrdersFilled(Vec<Order<FullyFill>>),
     OrdersPartiallyFilled(Vec<Order<PartialFill>>),
+    OrdersAmended(Vec<Order<Open>>), // 挂单被原地修改后的最新状态
     Balance(TokenBalance),
     Trade(ClientTrade),
     Balances(Vec<TokenBalance>),
-    Positions(AccountPositions),
-    AccountConfig(AccountConfig),
+    Positions(Box<AccountPositionsSnapshot>), // 发送时刻的仓位快照，之后的仓位变化不会反映到已发送的事件中
+    AccountConfig(Box<AccountConfig>), // 与 Positions 一样装箱，避免账户配置（含撮合器表）撑大整个事件枚举
+    WarmUpCompleted(i64), // 预热结束，参数为预热截止时间戳，仅发送一次
+    SyntheticFills(Vec<ClientTradeId>), // 由合成流动性产生的成交，分析结果时可据此打折扣
+    MarginUpdate(MarginUpdate), // 保证金使用情况快照，按 `margin_update` 配置定期或在保证金率明显变化时发送
+    Withdrawal(Withdrawal), // 提现后的余额，附带提现数量与扣除的手续费
+    RealisedPnl(RealisedPnl), // 成交减仓或平仓时实现的盈亏，紧随该成交的 Positions 事件发送
+    Liquidation(Liquidation), // 仓位被强平，随后发送扣除损失后的 Balance 事件
     // OrderBookUpdate(OrderBookUpdate),
     // MarketStatus(MarketStatus),
-    // MarginUpdate(MarginUpdate),
     // Transfer(Transfer),
     // Deposit(Deposit),
-    // Withdrawal(Withdrawal),
 }
 
 #[cfg(test)]
@@ -93,8 +98,9 @@ mod tests
                          AccountEventKind::Balance(TokenBalance::new(Token::from("BTC"), Balance::new(100.0, 50.0))),
                          // AccountEventKind::Trade(ClientTrade::default()),
                          AccountEventKind::Balances(vec![]),
-                         /* AccountEventKind::Positions(AccountPositions::default()),
-                          * AccountEventKind::AccountConfig(AccountConfig::default()), */];
+                         AccountEventKind::MarginUpdate(MarginUpdate::default()),
+                         AccountEventKind::Positions(Box::default()),
+                         /* AccountEventKind::AccountConfig(AccountConfig::default()), */];
         for kind in kinds {
             let serialized = serde_json::to_string(&kind).unwrap();
             let deserialized: AccountEventKind = serde_json::from_str(&serialized).unwrap();
@@ -0,0 +1,137 @@
+use crate::{
+    common::{
+        instrument::{kind::InstrumentKind, Instrument},
+        token::Token,
+    },
+    error::ExchangeError,
+    hourglass::ws_trade::parse_base_and_quote,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 交易对符号中常见的分隔符，例如 `BTC-USDT`、`BTC/USDT`、`BTC_USDT`、`BTC:USDT`。
+const SYMBOL_SEPARATORS: [char; 5] = ['-', '/', '_', ':', ' '];
+
+/// 金融工具别名表。
+///
+/// 不同数据源对同一金融工具的命名不同（`BTCUSDT`、`BTC-USDT`、`BTC/USDT`），
+/// 别名表把这些写法统一解析为规范的 `(base, quote)`，避免把同一个金融工具当成两个不同的工具交易。
+///
+/// 解析顺序：
+/// 1. 先把原始符号规范化（转大写并去掉分隔符），在别名表中查找；
+/// 2. 未登记时，若 `strict` 为 `true` 则返回错误；
+/// 3. 否则按分隔符拆分，没有分隔符时交给 [`parse_base_and_quote`] 按报价货币后缀拆分。
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct InstrumentAliasRegistry
+{
+    #[serde(default)]
+    pub strict: bool,
+    #[serde(default)]
+    pub aliases: HashMap<String, (Token, Token)>,
+}
+
+impl InstrumentAliasRegistry
+{
+    pub fn new(strict: bool) -> Self
+    {
+        Self { strict,
+               aliases: HashMap::new() }
+    }
+
+    /// 把原始符号规范化为别名表的查找键：转大写并去掉所有分隔符。
+    pub fn normalize_symbol(raw: &str) -> String
+    {
+        raw.trim().chars().filter(|c| !SYMBOL_SEPARATORS.contains(c)).collect::<String>().to_uppercase()
+    }
+
+    /// 登记一个别名，`alias` 会先经过 [`normalize_symbol`](Self::normalize_symbol)。
+    pub fn register<S>(&mut self, alias: &str, base: S, quote: S)
+        where S: Into<Token>
+    {
+        self.aliases.insert(Self::normalize_symbol(alias), (base.into(), quote.into()));
+    }
+
+    /// 将原始符号解析为规范的 `(base, quote)`。
+    pub fn resolve(&self, raw: &str) -> Result<(Token, Token), ExchangeError>
+    {
+        if let Some((base, quote)) = self.aliases.get(&Self::normalize_symbol(raw)) {
+            return Ok((base.clone(), quote.clone()));
+        }
+        if self.strict {
+            return Err(ExchangeError::InvalidInstrument(format!("Unknown symbol in strict mode: {}", raw)));
+        }
+
+        let trimmed = raw.trim();
+        let (base, quote) = match trimmed.split_once(|c| SYMBOL_SEPARATORS.contains(&c)) {
+            | Some((base, quote)) => (base.to_uppercase(), quote.to_uppercase()),
+            | None => parse_base_and_quote(&trimmed.to_uppercase()),
+        };
+        if base.is_empty() || quote.is_empty() {
+            return Err(ExchangeError::InvalidInstrument(format!("Unable to parse symbol: {}", raw)));
+        }
+        Ok((Token::from(base), Token::from(quote)))
+    }
+
+    /// 将已拆分好的 [`Instrument`] 规范化，用于客户端提交的订单。
+    pub fn canonicalize(&self, instrument: &Instrument) -> Result<Instrument, ExchangeError>
+    {
+        let (base, quote) = self.resolve(&format!("{}-{}", instrument.base, instrument.quote))?;
+        Ok(Instrument { base,
+                        quote,
+                        kind: instrument.kind })
+    }
+}
+
+impl Instrument
+{
+    /// 通过别名表把原始符号解析为规范的 [`Instrument`]。
+    pub fn normalize(raw: &str, kind: InstrumentKind, registry: &InstrumentAliasRegistry) -> Result<Instrument, ExchangeError>
+    {
+        let (base, quote) = registry.resolve(raw)?;
+        Ok(Instrument { base, quote, kind })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_different_spellings_resolve_to_the_same_instrument()
+    {
+        let registry = InstrumentAliasRegistry::default();
+        let expected = Instrument::new("BTC", "USDT", InstrumentKind::Perpetual);
+        for raw in ["BTCUSDT", "BTC-USDT", "BTC/USDT", "btc_usdt"] {
+            assert_eq!(Instrument::normalize(raw, InstrumentKind::Perpetual, &registry).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_registered_alias_takes_precedence()
+    {
+        let mut registry = InstrumentAliasRegistry::default();
+        registry.register("XBT-USD", "BTC", "USD");
+        let instrument = Instrument::normalize("XBTUSD", InstrumentKind::Perpetual, &registry).unwrap();
+        assert_eq!(instrument, Instrument::new("BTC", "USD", InstrumentKind::Perpetual));
+
+        let canonical = registry.canonicalize(&Instrument::new("XBT", "USD", InstrumentKind::Perpetual)).unwrap();
+        assert_eq!(canonical, instrument);
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_unknown_symbols()
+    {
+        let mut registry = InstrumentAliasRegistry::new(true);
+        registry.register("BTCUSDT", "BTC", "USDT");
+        assert!(Instrument::normalize("BTC/USDT", InstrumentKind::Spot, &registry).is_ok());
+        assert!(matches!(Instrument::normalize("ETHUSDT", InstrumentKind::Spot, &registry), Err(ExchangeError::InvalidInstrument(_))));
+    }
+
+    #[test]
+    fn test_unparseable_symbol_is_an_error()
+    {
+        let registry = InstrumentAliasRegistry::default();
+        assert!(Instrument::normalize("XRP", InstrumentKind::Spot, &registry).is_err());
+    }
+}
@@ -1,4 +1,7 @@
-use std::fmt::{Display, Formatter};
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -50,6 +53,26 @@ impl Display for InstrumentKind
     }
 }
 
+/// 不区分大小写地解析 [`Display`] 输出的名称，同时接受变体全名（如 `crypto_option`）与 `swap` 等常见别名。
+impl FromStr for InstrumentKind
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        match s.trim().to_lowercase().as_str() {
+            | "spot" => Ok(InstrumentKind::Spot),
+            | "perpetual" | "swap" => Ok(InstrumentKind::Perpetual),
+            | "future" => Ok(InstrumentKind::Future),
+            | "option" | "crypto_option" => Ok(InstrumentKind::CryptoOption),
+            | "margin" | "crypto_leveraged_token" => Ok(InstrumentKind::CryptoLeveragedToken),
+            | "commodity_future" => Ok(InstrumentKind::CommodityFuture),
+            | "commodity_option" => Ok(InstrumentKind::CommodityOption),
+            | _ => Err(format!("Unknown instrument kind: {}", s)),
+        }
+    }
+}
+
 impl TryFrom<String> for InstrumentKind
 {
     type Error = String;
@@ -100,6 +123,24 @@ mod tests
         assert_eq!(InstrumentKind::try_from("Margin".to_string()), Ok(InstrumentKind::CryptoLeveragedToken));
     }
 
+    #[test]
+    fn instrument_kind_from_str_should_round_trip_display()
+    {
+        for kind in [InstrumentKind::Spot,
+                     InstrumentKind::Perpetual,
+                     InstrumentKind::Future,
+                     InstrumentKind::CryptoOption,
+                     InstrumentKind::CryptoLeveragedToken,
+                     InstrumentKind::CommodityFuture,
+                     InstrumentKind::CommodityOption]
+        {
+            assert_eq!(kind.to_string().parse::<InstrumentKind>(), Ok(kind));
+            assert_eq!(kind.to_string().to_uppercase().parse::<InstrumentKind>(), Ok(kind));
+        }
+        assert_eq!("SWAP".parse::<InstrumentKind>(), Ok(InstrumentKind::Perpetual));
+        assert!("Unknown".parse::<InstrumentKind>().is_err());
+    }
+
     #[test]
     fn instrument_kind_from_string_should_return_err_on_unknown_kind()
     {
@@ -1,9 +1,17 @@
-use std::fmt::{Display, Formatter};
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
 
 use serde::{Deserialize, Serialize};
 
-use crate::common::{instrument::kind::InstrumentKind, token::Token};
+use crate::{
+    common::{instrument::kind::InstrumentKind, token::Token},
+    error::ExchangeError,
+    hourglass::ws_trade::parse_base_and_quote,
+};
 
+pub mod alias;
 pub mod kind;
 
 // 定义Instrument结构体，用于表示金融工具。
@@ -17,12 +25,40 @@ pub struct Instrument
     pub kind: InstrumentKind, // 金融工具的类型
 }
 
-// 为Instrument实现Display trait，方便打印显示。
+// 为Instrument实现Display trait，方便打印显示。标准字符串形式为 `BASE-QUOTE-KIND`，例如 `BTC-USDT-PERPETUAL`。
 impl Display for Instrument
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
     {
-        write!(f, "({}_{}, {})", self.base, self.quote, self.kind)
+        write!(f, "{}-{}-{}", self.base, self.quote, self.kind.to_string().to_uppercase())
+    }
+}
+
+/// 解析 [`Display`] 输出的 `BASE-QUOTE-KIND` 形式，使配置中可以用字符串引用金融工具。
+///
+/// 也接受不带分隔的交易对加类型（如 `BTCUSDT-PERPETUAL`），此时用 [`parse_base_and_quote`] 拆分基础货币与报价货币。
+/// 类型名不区分大小写，可选值见 [`InstrumentKind`] 的 `FromStr`。
+impl FromStr for Instrument
+{
+    type Err = ExchangeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let invalid = || ExchangeError::InvalidInstrument(format!("Cannot parse instrument from '{}', expected BASE-QUOTE-KIND", s));
+        let parts = s.trim().split('-').collect::<Vec<_>>();
+        let (base, quote, kind) = match parts.as_slice() {
+            | [base, quote, kind] => (base.to_string(), quote.to_string(), kind),
+            | [symbol, kind] => {
+                let (base, quote) = parse_base_and_quote(symbol);
+                (base, quote, kind)
+            }
+            | _ => return Err(invalid()),
+        };
+        if base.is_empty() || quote.is_empty() {
+            return Err(invalid());
+        }
+        let kind = kind.parse::<InstrumentKind>().map_err(ExchangeError::InvalidInstrument)?;
+        Ok(Instrument::new(base, quote, kind))
     }
 }
 
@@ -29,13 +29,16 @@
 /// - `random_component` 被放在最右边的最低位。
 pub mod client_order_id;
 pub mod machine_id;
+pub mod oco_group_id;
 pub mod request_id;
 
+use crate::error::ExchangeError;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::{
     fmt,
     fmt::{Display, Formatter},
+    str::FromStr,
 };
 
 /// **OrderID**
@@ -53,6 +56,56 @@ impl Display for OrderId
     }
 }
 
+/// 解析十进制字符串形式的 `OrderId`，与 [`Display`] 的输出互逆。
+///
+/// 实盘交易所通常以字符串下发订单号（例如 `"8389765542102425601"`），借此与本地雪花算法生成的数值 ID 互通。
+/// 前后的空白会被忽略；非数字或超出 `u64` 范围的字符串返回 `ExchangeError::InvalidID`。
+impl FromStr for OrderId
+{
+    type Err = ExchangeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        s.trim().parse::<u64>().map(OrderId).map_err(|_| ExchangeError::InvalidID)
+    }
+}
+
+impl TryFrom<&str> for OrderId
+{
+    type Error = ExchangeError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error>
+    {
+        value.parse()
+    }
+}
+
+impl TryFrom<String> for OrderId
+{
+    type Error = ExchangeError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error>
+    {
+        value.parse()
+    }
+}
+
+impl From<u64> for OrderId
+{
+    fn from(value: u64) -> Self
+    {
+        OrderId(value)
+    }
+}
+
+impl From<OrderId> for u64
+{
+    fn from(id: OrderId) -> Self
+    {
+        id.0
+    }
+}
+
 impl OrderId
 {
     /// 生成一个具有更高安全性要求的 `OrderId`。
@@ -68,7 +121,12 @@ impl OrderId
     {
         // 将随机组件降低到更低的位置
         let random_component: u64 = rand::thread_rng().gen_range(0..8192);
+        Self::with_random_component(timestamp, machine_id, counter, random_component)
+    }
 
+    /// 与 [`OrderId::new`] 相同，随机部分由调用方给出（只使用最低 3 位），用于由种子决定的可复现订单ID。
+    pub fn with_random_component(timestamp: u64, machine_id: u64, counter: u64, random_component: u64) -> Self
+    {
         // 生成唯一的OrderId
         let id = ((timestamp & 0x1FFFFFFFFFF) << 23) | ((machine_id & 0x3FF) << 13) | ((counter & 0x3FF) << 3) | (random_component & 0x7);
 
@@ -130,4 +188,36 @@ mod tests
             assert!(ids.insert(id.clone()), "Duplicate OrderId generated: {}", id);
         }
     }
+
+    #[test]
+    fn test_order_id_string_round_trip()
+    {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).expect("时间出现倒退").as_millis() as u64;
+        let id = OrderId::new(timestamp, 1, 42);
+
+        // Display 与 FromStr 互逆
+        assert_eq!(id.to_string().parse::<OrderId>().unwrap(), id);
+        assert_eq!(OrderId::try_from(id.to_string()).unwrap(), id);
+
+        // 实盘交易所下发的字符串订单号
+        assert_eq!(OrderId::try_from(" 8389765542102425601 ").unwrap(), OrderId(8389765542102425601));
+        assert_eq!(u64::MAX.to_string().parse::<OrderId>().unwrap(), OrderId(u64::MAX));
+    }
+
+    #[test]
+    fn test_order_id_numeric_round_trip()
+    {
+        let id = OrderId::from(123u64);
+        assert_eq!(id, OrderId(123));
+        assert_eq!(u64::from(id.clone()), 123);
+        assert_eq!(serde_json::from_str::<OrderId>(&serde_json::to_string(&id).unwrap()).unwrap(), id);
+    }
+
+    #[test]
+    fn test_order_id_rejects_invalid_strings()
+    {
+        for invalid in ["", "abc", "-1", "1.5", "18446744073709551616"] {
+            assert!(matches!(invalid.parse::<OrderId>(), Err(ExchangeError::InvalidID)), "{} should be rejected", invalid);
+        }
+    }
 }
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// 一组 OCO（一笔成交即撤销另一笔）关联订单的标识符，在账户内唯一。
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize, Serialize, PartialOrd, Ord)]
+pub struct OcoGroupId(pub u64);
+
+impl Display for OcoGroupId
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "{}", self.0)
+    }
+}
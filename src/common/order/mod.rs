@@ -1,6 +1,7 @@
 pub mod identification;
 pub mod order_instructions;
 pub mod states;
+pub mod stop_order;
 
 use crate::{
     common::{
@@ -47,6 +48,7 @@ mod tests
         assert_eq!(format!("{}", OrderInstruction::ImmediateOrCancel), "immediate_or_cancel");
         assert_eq!(format!("{}", OrderInstruction::FillOrKill), "fill_or_kill");
         assert_eq!(format!("{}", OrderInstruction::GoodTilCancelled), "good_til_cancelled");
+        assert_eq!(format!("{}", OrderInstruction::GoodTilTime { expire_ts: 1 }), "good_til_time");
     }
 
     #[test]
@@ -54,13 +56,30 @@ mod tests
     {
         let req1 = RequestOpen { reduce_only: true,
                                  price: 50.0,
-                                 size: 1.0 };
+                                 size: 1.0,
+                                 tag: None };
         let req2 = RequestOpen { reduce_only: false,
                                  price: 60.0,
-                                 size: 2.0 };
+                                 size: 2.0,
+                                 tag: None };
         assert!(req1 < req2);
     }
 
+    #[test]
+    fn request_open_tag_should_round_trip_through_serde()
+    {
+        let request = RequestOpen { reduce_only: false,
+                                    price: 50.0,
+                                    size: 1.0,
+                                    tag: Some("grid_leg_1".to_string()) };
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(serde_json::from_str::<RequestOpen>(&json).unwrap(), request);
+
+        // 旧格式中没有 tag 字段时默认为 None
+        let legacy: RequestOpen = serde_json::from_str(r#"{"reduce_only":false,"price":50.0,"size":1.0}"#).unwrap();
+        assert_eq!(legacy.tag, None);
+    }
+
     #[test]
     fn request_cancel_should_create_from_order_id()
     {
@@ -11,9 +11,26 @@ pub enum OrderInstruction
     ImmediateOrCancel,
     FillOrKill,
     GoodTilCancelled,
+    /// 在 `expire_ts`（交易所时间，毫秒）到达时自动撤销的限价单。
+    GoodTilTime
+    {
+        expire_ts: i64,
+    },
     Cancel,
 }
 
+impl OrderInstruction
+{
+    /// GTT 订单在交易所时间 `now` 是否已经到期，其他订单指令永不到期。
+    pub fn is_expired(&self, now: i64) -> bool
+    {
+        match self {
+            | OrderInstruction::GoodTilTime { expire_ts } => now >= *expire_ts,
+            | _ => false,
+        }
+    }
+}
+
 impl Display for OrderInstruction
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
@@ -24,6 +41,7 @@ impl Display for OrderInstruction
             | OrderInstruction::ImmediateOrCancel => "immediate_or_cancel",
             | OrderInstruction::FillOrKill => "fill_or_kill",
             | OrderInstruction::GoodTilCancelled => "good_til_cancelled",
+            | OrderInstruction::GoodTilTime { .. } => "good_til_time",
             | OrderInstruction::PostOnlyLimit => "post_only",
             | OrderInstruction::Cancel => "cancel_request",
         })
@@ -27,6 +27,9 @@ pub struct FullyFill
     pub price: f64,
     /// 完全成交的订单数量。
     pub size: f64,
+    /// 原订单附带的策略标签。
+    #[serde(default)]
+    pub tag: Option<String>,
 }
 
 /// `PartialFill` 结构体表示订单部分成交的状态。
@@ -41,4 +44,7 @@ pub struct PartialFill
     pub price: f64,
     /// 部分成交的订单数量。
     pub size: f64,
+    /// 原订单附带的策略标签。
+    #[serde(default)]
+    pub tag: Option<String>,
 }
@@ -2,5 +2,6 @@ pub mod cancelled;
 pub mod fills;
 pub mod open;
 // pub mod pending;
+pub mod request_amend;
 pub mod request_cancel;
 pub mod request_open;
@@ -10,7 +10,40 @@ pub struct Open
     pub price: f64,
     pub size: f64,
     pub filled_quantity: f64,
+    /// 已成交部分的成交量加权平均价（VWAP），尚未成交时为 0.0。
+    #[serde(default)]
+    pub avg_fill_price: f64,
+    /// 是否为只减仓订单，仓位完全平掉时这类挂单会被自动撤销。
+    #[serde(default)]
+    pub reduce_only: bool,
     pub order_role: OrderRole,
+    /// 下单时附带的策略标签，成交时原样写入 `ClientTrade`。
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// 冰山单的显示部分，普通订单为 `None`。订单总量仍为 `size`，已成交数量仍为 `filled_quantity`。
+    #[serde(default)]
+    pub iceberg: Option<Iceberg>,
+}
+
+/// 冰山单在订单簿中对外显示的部分。
+///
+/// 每次只有不超过 `display_size` 的数量参与撮合，显示部分被吃完后从隐藏的剩余数量中补充，
+/// 并以新的到达时间排到同价位队尾。
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize, Serialize)]
+pub struct Iceberg
+{
+    pub display_size: f64,       // 每次显示的数量
+    pub displayed_quantity: f64, // 当前显示部分尚未成交的数量
+}
+
+impl Iceberg
+{
+    /// 为总量为 `size` 的订单创建冰山显示部分，首次显示 `display_size` 与 `size` 中较小者。
+    pub fn new(display_size: f64, size: f64) -> Self
+    {
+        Self { display_size,
+               displayed_quantity: display_size.min(size) }
+    }
 }
 
 impl Open
@@ -23,6 +56,53 @@ impl Open
     {
         self.size - self.filled_quantity
     }
+
+    /// 当前参与撮合的数量：普通订单为剩余数量，冰山单为显示部分尚未成交的数量。
+    pub fn visible_quantity(&self) -> f64
+    {
+        match &self.iceberg {
+            | Some(iceberg) => iceberg.displayed_quantity.min(self.remaining_quantity()),
+            | None => self.remaining_quantity(),
+        }
+    }
+
+    /// 冰山单的显示部分已被吃完且仍有隐藏数量时，按 `display_size` 补充显示部分并返回 `true`。
+    ///
+    /// 返回 `true` 时调用方需要把订单移到同价位队尾，补充后的显示部分失去原有的时间优先级。
+    pub fn replenish(&mut self) -> bool
+    {
+        let remaining_quantity = self.remaining_quantity();
+        match &mut self.iceberg {
+            | Some(iceberg) if iceberg.displayed_quantity <= 0.0 && remaining_quantity > 0.0 => {
+                iceberg.displayed_quantity = iceberg.display_size.min(remaining_quantity);
+                true
+            }
+            | _ => false,
+        }
+    }
+
+    /// 返回订单已成交部分的成交量加权平均价。
+    pub fn avg_fill_price(&self) -> f64
+    {
+        self.avg_fill_price
+    }
+
+    /// 记录一笔成交，同时更新 `filled_quantity` 与 `avg_fill_price`。
+    ///
+    /// # 参数
+    /// - `price`: 本次成交价。
+    /// - `quantity`: 本次成交数量。
+    pub fn record_fill(&mut self, price: f64, quantity: f64)
+    {
+        let total_filled = self.filled_quantity + quantity;
+        if total_filled > 0.0 {
+            self.avg_fill_price = (self.avg_fill_price * self.filled_quantity + price * quantity) / total_filled;
+        }
+        self.filled_quantity = total_filled;
+        if let Some(iceberg) = &mut self.iceberg {
+            iceberg.displayed_quantity = (iceberg.displayed_quantity - quantity).max(0.0);
+        }
+    }
 }
 
 impl Ord for Order<Open>
@@ -63,3 +143,65 @@ impl PartialOrd for Order<Open>
 
 /// 为 `Order<Open>` 实现 `Eq` trait，以支持完全相等的比较。
 impl Eq for Order<Open> {}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn create_open(size: f64) -> Open
+    {
+        Open { id: OrderId(1),
+               price: 100.0,
+               size,
+               filled_quantity: 0.0,
+               avg_fill_price: 0.0,
+               reduce_only: false,
+               order_role: OrderRole::Maker,
+               tag: None,
+               iceberg: None }
+    }
+
+    #[test]
+    fn test_avg_fill_price_equals_vwap_of_fills()
+    {
+        let mut open = create_open(6.0);
+        let fills = [(100.0, 1.0), (101.0, 2.0), (99.5, 3.0)];
+        for (price, quantity) in fills {
+            open.record_fill(price, quantity);
+        }
+
+        let vwap = fills.iter().map(|(p, q)| p * q).sum::<f64>() / fills.iter().map(|(_, q)| q).sum::<f64>();
+        assert!((open.avg_fill_price() - vwap).abs() < 1e-9);
+        assert_eq!(open.filled_quantity, 6.0);
+        assert_eq!(open.remaining_quantity(), 0.0);
+    }
+
+    #[test]
+    fn test_iceberg_replenishes_only_after_display_exhausted()
+    {
+        let mut open = create_open(2.5);
+        open.iceberg = Some(Iceberg::new(1.0, open.size));
+        assert_eq!(open.visible_quantity(), 1.0);
+
+        open.record_fill(100.0, 0.4);
+        assert!(!open.replenish());
+        assert!((open.visible_quantity() - 0.6).abs() < 1e-9);
+
+        open.record_fill(100.0, 0.6);
+        assert!(open.replenish());
+        assert_eq!(open.visible_quantity(), 1.0);
+
+        open.record_fill(100.0, 1.0);
+        assert!(open.replenish());
+        assert!((open.visible_quantity() - 0.5).abs() < 1e-9);
+        assert_eq!(open.filled_quantity, 2.0);
+    }
+
+    #[test]
+    fn test_avg_fill_price_is_zero_before_any_fill()
+    {
+        let open = create_open(1.0);
+        assert_eq!(open.avg_fill_price(), 0.0);
+    }
+}
@@ -0,0 +1,13 @@
+use crate::common::order::identification::OrderId;
+use serde::{Deserialize, Serialize};
+
+/// `RequestAmend` 结构体表示一个原地修改挂单的请求。
+///
+/// `new_price` 与 `new_size` 为 `None` 时保持原值不变；`new_size` 是修改后的订单总量（包括已成交的部分），而不是剩余数量。
+#[derive(Clone, PartialEq, PartialOrd, Debug, Deserialize, Serialize)]
+pub struct RequestAmend
+{
+    pub id: OrderId,            // 要修改的订单的唯一标识符
+    pub new_price: Option<f64>, // 新的价格
+    pub new_size: Option<f64>,  // 新的订单总量
+}
@@ -6,13 +6,15 @@ use std::{cmp::Ordering, fmt};
 
 /// 订单初始状态。发送到client进行操作
 ///
-/// `RequestOpen` 用于表示一个初始订单状态。这个状态包含了订单的价格、大小，以及是否为 `reduce_only` 订单。
+/// `RequestOpen` 用于表示一个初始订单状态。这个状态包含了订单的价格、大小，是否为 `reduce_only` 订单，以及可选的策略标签。
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
 pub struct RequestOpen
 {
     pub reduce_only: bool,
     pub price: f64,
     pub size: f64,
+    #[serde(default)]
+    pub tag: Option<String>, // 策略自定义标签，随订单传递到成交与事件中，不参与撮合
     // pub leverage: Option<f64>,
     // pub margin_mode: Option<PositionMarginMode>,
     // pub position_direction_mode: Option<PositionDirectionMode>
@@ -0,0 +1,203 @@
+use crate::common::{
+    order::{
+        identification::OrderId,
+        order_instructions::OrderInstruction,
+        states::{cancelled::Cancelled, request_open::RequestOpen},
+        Order,
+    },
+    Side,
+};
+use serde::{Deserialize, Serialize};
+
+/// 条件单的触发方式。
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum StopOrderKind
+{
+    /// 触发后以市价单成交。
+    Stop
+    {
+        trigger_price: f64,
+    },
+    /// 触发后以 `limit_price` 挂出限价单。
+    StopLimit
+    {
+        trigger_price: f64,
+        limit_price: f64,
+    },
+    /// 触发价跟随最优价格移动，价格从最优价格回撤 `callback_rate`（例如 0.01 表示 1%）时以市价单成交。
+    TrailingStop
+    {
+        callback_rate: f64,
+    },
+}
+
+impl StopOrderKind
+{
+    /// 固定的触发价，跟踪止损的触发价随行情变化，返回 `None`。
+    pub fn trigger_price(&self) -> Option<f64>
+    {
+        match self {
+            | StopOrderKind::Stop { trigger_price } | StopOrderKind::StopLimit { trigger_price, .. } => Some(*trigger_price),
+            | StopOrderKind::TrailingStop { .. } => None,
+        }
+    }
+}
+
+/// 等待触发的止损/止盈条件单。
+///
+/// 买入条件单在成交价不低于触发价时触发，卖出条件单在成交价不高于触发价时触发。
+/// 触发前不锁定余额，也不进入挂单簿；触发后 `order` 按 `kind` 转换为市价单或限价单，
+/// 其原有的 `instruction` 与 `price` 会被覆盖。
+///
+/// 跟踪止损的 `watermark` 记录提交以来的最优价格：卖出时为最高价，买入时为最低价，
+/// 触发价为 `watermark` 向不利方向偏移 `callback_rate`。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct StopOrder
+{
+    pub order: Order<RequestOpen>,
+    pub kind: StopOrderKind,
+    #[serde(default)]
+    pub watermark: Option<f64>, // 仅用于跟踪止损，提交时取当时的最新成交价，没有行情时由第一笔成交初始化
+    #[serde(default)]
+    pub id: Option<OrderId>, // 登记时由模拟交易所分配，撤销条件单时可按它指定
+}
+
+impl StopOrder
+{
+    pub fn new(order: Order<RequestOpen>, kind: StopOrderKind) -> Self
+    {
+        Self { order,
+               kind,
+               watermark: None,
+               id: None }
+    }
+
+    /// 撤销后的订单，尚未分配订单ID（未经登记）的条件单返回 `None`。
+    pub fn into_cancelled(self) -> Option<Order<Cancelled>>
+    {
+        let id = self.id?;
+        Some(Order { instruction: self.order.instruction,
+                     exchange: self.order.exchange,
+                     instrument: self.order.instrument,
+                     timestamp: self.order.timestamp,
+                     cid: self.order.cid,
+                     side: self.order.side,
+                     state: Cancelled { id } })
+    }
+
+    /// 当前的触发价，尚未观察到任何价格的跟踪止损返回 `None`。
+    pub fn trigger_price(&self) -> Option<f64>
+    {
+        match self.kind {
+            | StopOrderKind::TrailingStop { callback_rate } => self.watermark.map(|watermark| match self.order.side {
+                                                                                    | Side::Buy => watermark * (1.0 + callback_rate),
+                                                                                    | Side::Sell => watermark * (1.0 - callback_rate),
+                                                                                }),
+            | kind => kind.trigger_price(),
+        }
+    }
+
+    /// 用最新成交价更新跟踪止损的最优价格，其他条件单不受影响。
+    pub fn update_watermark(&mut self, price: f64)
+    {
+        if let StopOrderKind::TrailingStop { .. } = self.kind {
+            self.watermark = Some(match (self.watermark, self.order.side) {
+                | (None, _) => price,
+                | (Some(watermark), Side::Buy) => watermark.min(price),
+                | (Some(watermark), Side::Sell) => watermark.max(price),
+            });
+        }
+    }
+
+    /// 给定的成交价是否触发该条件单。
+    pub fn is_triggered(&self, price: f64) -> bool
+    {
+        self.trigger_price().is_some_and(|trigger_price| match self.order.side {
+                                            | Side::Buy => price >= trigger_price,
+                                            | Side::Sell => price <= trigger_price,
+                                        })
+    }
+
+    /// 转换为触发后实际提交的开单请求。市价单的 `price` 取触发价，仅用于估算所需的保证金。
+    pub fn into_request(self) -> Order<RequestOpen>
+    {
+        let (instruction, price) = match self.kind {
+            | StopOrderKind::StopLimit { limit_price, .. } => (OrderInstruction::Limit, limit_price),
+            | StopOrderKind::Stop { .. } | StopOrderKind::TrailingStop { .. } => (OrderInstruction::Market, self.trigger_price().unwrap_or(self.order.state.price)),
+        };
+        Order { instruction,
+                state: RequestOpen { price, ..self.order.state },
+                ..self.order }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::instrument::{kind::InstrumentKind, Instrument},
+        Exchange,
+    };
+
+    fn stop_order(side: Side, kind: StopOrderKind) -> StopOrder
+    {
+        StopOrder::new(Order { instruction: OrderInstruction::Limit,
+                               exchange: Exchange::Hourglass,
+                               instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                               timestamp: 1234567,
+                               cid: None,
+                               side,
+                               state: RequestOpen { reduce_only: true,
+                                                    price: 0.0,
+                                                    size: 1.0,
+                                                    tag: None } },
+                       kind)
+    }
+
+    #[test]
+    fn stop_order_should_trigger_by_side()
+    {
+        let buy_stop = stop_order(Side::Buy, StopOrderKind::Stop { trigger_price: 100.0 });
+        assert!(!buy_stop.is_triggered(99.9));
+        assert!(buy_stop.is_triggered(100.0));
+        assert!(buy_stop.is_triggered(101.0));
+
+        let sell_stop = stop_order(Side::Sell, StopOrderKind::Stop { trigger_price: 100.0 });
+        assert!(!sell_stop.is_triggered(100.1));
+        assert!(sell_stop.is_triggered(100.0));
+        assert!(sell_stop.is_triggered(99.0));
+    }
+
+    #[test]
+    fn stop_order_should_convert_to_market_or_limit_request()
+    {
+        let market = stop_order(Side::Sell, StopOrderKind::Stop { trigger_price: 95.0 }).into_request();
+        assert_eq!((market.instruction, market.state.price), (OrderInstruction::Market, 95.0));
+
+        let limit = stop_order(Side::Sell, StopOrderKind::StopLimit { trigger_price: 95.0, limit_price: 94.5 }).into_request();
+        assert_eq!((limit.instruction, limit.state.price), (OrderInstruction::Limit, 94.5));
+        assert!(limit.state.reduce_only);
+    }
+
+    #[test]
+    fn trailing_stop_trigger_should_follow_best_price()
+    {
+        let mut sell_trailing = stop_order(Side::Sell, StopOrderKind::TrailingStop { callback_rate: 0.01 });
+        assert_eq!(sell_trailing.trigger_price(), None);
+        assert!(!sell_trailing.is_triggered(1.0));
+
+        sell_trailing.update_watermark(100.0);
+        sell_trailing.update_watermark(120.0);
+        sell_trailing.update_watermark(110.0);
+        assert_eq!(sell_trailing.watermark, Some(120.0));
+        assert!(!sell_trailing.is_triggered(118.9));
+        assert!(sell_trailing.is_triggered(118.7));
+
+        let mut buy_trailing = stop_order(Side::Buy, StopOrderKind::TrailingStop { callback_rate: 0.01 });
+        buy_trailing.update_watermark(100.0);
+        buy_trailing.update_watermark(90.0);
+        assert!((buy_trailing.trigger_price().unwrap() - 90.9).abs() < 1e-9);
+        assert_eq!(buy_trailing.into_request().instruction, OrderInstruction::Market);
+    }
+}
@@ -22,6 +22,8 @@ pub struct ClientTrade
     pub price: f64,
     pub size: f64,
     pub fees: f64,
+    #[serde(default)]
+    pub tag: Option<String>, // 来源订单的策略标签，用于按子策略归因成交
 }
 
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
@@ -48,7 +50,33 @@ mod tests
     fn instrument_display_should_format_correctly()
     {
         let instrument = Instrument::new(Token::new("BTC"), Token::new("USDT"), InstrumentKind::Spot);
-        assert_eq!(format!("{}", instrument), "(BTC_USDT, spot)");
+        assert_eq!(format!("{}", instrument), "BTC-USDT-SPOT");
+    }
+
+    #[test]
+    fn instrument_display_should_round_trip_through_from_str()
+    {
+        for kind in [InstrumentKind::Spot,
+                     InstrumentKind::Perpetual,
+                     InstrumentKind::Future,
+                     InstrumentKind::CryptoOption,
+                     InstrumentKind::CryptoLeveragedToken,
+                     InstrumentKind::CommodityFuture,
+                     InstrumentKind::CommodityOption]
+        {
+            let instrument = Instrument::new(Token::new("BTC"), Token::new("USDT"), kind);
+            assert_eq!(instrument.to_string().parse::<Instrument>(), Ok(instrument));
+        }
+        assert_eq!("BTC-USDT-PERPETUAL".parse::<Instrument>(), Ok(Instrument::new(Token::new("BTC"), Token::new("USDT"), InstrumentKind::Perpetual)));
+    }
+
+    #[test]
+    fn instrument_from_str_should_split_concatenated_symbol()
+    {
+        assert_eq!("ETHUSDT-perpetual".parse::<Instrument>(), Ok(Instrument::new(Token::new("ETH"), Token::new("USDT"), InstrumentKind::Perpetual)));
+        assert!("BTC-USDT".parse::<Instrument>().is_err());
+        assert!("BTC-USDT-UNKNOWN".parse::<Instrument>().is_err());
+        assert!("BTC-USDT-SPOT-EXTRA".parse::<Instrument>().is_err());
     }
 
     #[test]
@@ -63,7 +91,7 @@ mod tests
     fn instrument_from_tuple_should_work()
     {
         let instrument = Instrument::from((Token::new("BTC"), Token::new("USDT"), InstrumentKind::Spot));
-        assert_eq!(format!("{}", instrument), "(BTC_USDT, spot)");
+        assert_eq!(format!("{}", instrument), "BTC-USDT-SPOT");
     }
 
     #[test]
@@ -71,7 +99,7 @@ mod tests
     {
         let initiator = InstrumentBuilder::new().base(Token::new("BTC")).quote(Token::new("USDT")).kind(InstrumentKind::Spot);
         let instrument = initiator.initiate().expect("Failed to create instrument");
-        assert_eq!(format!("{}", instrument), "(BTC_USDT, spot)");
+        assert_eq!(format!("{}", instrument), "BTC-USDT-SPOT");
     }
 
     #[test]
@@ -21,9 +21,14 @@ pub enum ExchangeError
     #[error("Hourglass error: {0}")]
     Hourglass(String),
 
-    /// 余额不足，无法开单。
-    #[error("Insufficient balance for symbol {0}, unable to place order")]
-    InsufficientBalance(Token),
+    /// 可用余额不足，无法开单或提现。
+    #[error("Insufficient {token} balance: {required} required, {available} available")]
+    InsufficientBalance
+    {
+        token: Token,   // 不足的币种
+        required: f64,  // 所需的可用余额
+        available: f64, // 当前的可用余额
+    },
 
     /// 找不到特定客户端订单ID的订单。
     #[error("Order with ClientOrderId not found: {0}")]
@@ -73,6 +78,10 @@ pub enum ExchangeError
     #[error("Invalid order direction")]
     InvalidDirection,
 
+    /// `reduce_only` 订单没有可减少的仓位，或会增加净敞口。
+    #[error("Reduce-only order rejected: {0}")]
+    ReduceOnlyRejected(String),
+
     /// 无效的ID。
     #[error("Invalid ID")]
     InvalidID,
@@ -128,6 +137,10 @@ pub enum ExchangeError
     #[error("Invalid RequestOpen: {0}")]
     InvalidRequestOpen(String),
 
+    /// 订单的数量或价格非法（为零、负数或非有限值）。
+    #[error("Invalid order: {0}")]
+    InvalidOrder(String),
+
     #[error("Invalid RequestCancel: {0}")]
     InvalidRequestCancel(String),
 
@@ -140,11 +153,46 @@ pub enum ExchangeError
     #[error("InvalidLeverage")]
     InvalidLeverage(String),
 
-    #[error("PostOnlyViolation")]
-    PostOnlyViolation(String),
+    /// PostOnly 订单的限价与对手方最优价格交叉，挂出后会立即作为 Taker 成交，因此被拒绝。
+    #[error("PostOnly order at {price} would cross the best opposing price {best_opposing_price}")]
+    PostOnlyWouldCross
+    {
+        price: f64,
+        best_opposing_price: f64,
+    },
+
+    /// 订单名义价值低于配置的最小名义价值。
+    #[error("Order notional {got} is below the minimum of {min}")]
+    BelowMinNotional
+    {
+        min: f64,
+        got: f64,
+    },
+
+    /// 订单数量不是金融工具 `lot_size` 的整数倍。
+    #[error("Order size {size} is not a multiple of the lot size {lot_size}")]
+    SizeNotAligned
+    {
+        size: f64,
+        lot_size: f64,
+    },
+
+    /// 限价不是金融工具 `tick_size` 的整数倍。
+    #[error("Order price {price} is not a multiple of the tick size {tick_size}")]
+    PriceNotAligned
+    {
+        price: f64,
+        tick_size: f64,
+    },
 
-    #[error("ReduceOnlyViolation")]
-    ReduceOnlyViolation,
+    /// 挂单价格超出按 `max_price_deviation` 计算的允许价格区间 `[lower, upper]`。
+    #[error("Order price {price} is outside the allowed band [{lower}, {upper}]")]
+    PriceOutOfBand
+    {
+        price: f64,
+        lower: f64,
+        upper: f64,
+    },
 
     #[error("UnsupportedInstrumentKind")]
     UnsupportedInstrumentKind,
@@ -187,4 +235,40 @@ pub enum ExchangeError
 
     #[error("PasswordHashError.")]
     PasswordHashError,
+
+    /// 预热期内拒绝开单，参数为预热截止时间戳。
+    #[error("Warm-up in progress, orders are accepted from timestamp {0}")]
+    WarmUpInProgress(i64),
+
+    /// 报单成交比超过配置上限，拒绝开单，参数为当前比值。
+    #[error("Order-to-trade ratio exceeded: {0}")]
+    OrderToTradeRatioExceeded(f64),
+
+    /// 该金融工具的挂单数量已达上限，参数为配置的上限。
+    #[error("Maximum open orders per instrument reached: {0}")]
+    MaxOpenOrdersExceeded(usize),
+
+    /// 提现数量低于该币种的最小提现数量，参数为币种、提现数量与最小提现数量。
+    #[error("Withdrawal of {1} {0} is below the minimum of {2}")]
+    BelowMinimumWithdrawal(Token, f64, f64),
+
+    /// 价差订单两腿按当前深度成交的价差未达到目标价差，参数为可成交价差与目标价差。
+    #[error("Executable spread {0} does not meet the target spread {1}")]
+    SpreadTargetNotMet(f64, f64),
+
+    /// 价差订单某一腿的深度不足以完全成交，且未允许单腿成交，参数为该腿的金融工具。
+    #[error("Insufficient liquidity to fill spread leg {0}")]
+    SpreadLegUnfillable(String),
+
+    /// 回放检查点读写失败。
+    #[error("Replay checkpoint error: {0}")]
+    CheckpointError(String),
+
+    /// 打开或读取行情数据文件失败。
+    #[error("Market data source error: {0}")]
+    DataSourceError(String),
+
+    /// 开单后全仓保证金率将低于维持保证金要求，参数为开单后的保证金率。
+    #[error("Opening would push the cross margin ratio to {0}, below the maintenance requirement")]
+    MarginRatioBelowMaintenance(f64),
 }
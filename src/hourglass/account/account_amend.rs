@@ -0,0 +1,374 @@
+use crate::{
+    common::{
+        balance::{BalanceDelta, TokenBalance},
+        event::{AccountEvent, AccountEventKind},
+        order::{
+            states::{open::Open, request_amend::RequestAmend, request_open::RequestOpen},
+            Order, OrderRole,
+        },
+        Side,
+    },
+    error::ExchangeError,
+    hourglass::account::{
+        account_handlers::balance_handler::BalanceHandler,
+        account_orders::OrderRoleClassifier,
+        HourglassAccount,
+    },
+    hourglass_log::info,
+    Exchange,
+};
+use std::sync::atomic::Ordering;
+use tokio::sync::oneshot::Sender;
+
+impl HourglassAccount
+{
+    pub async fn amend_orders(&mut self, amend_requests: Vec<Order<RequestAmend>>, response_tx: Sender<Vec<Result<Order<Open>, ExchangeError>>>)
+    {
+        let mut results = Vec::with_capacity(amend_requests.len());
+
+        for request in amend_requests {
+            let result = self.atomic_amend(request).await;
+            results.push(result);
+        }
+
+        self.assert_invariants(&"AmendOrders").await;
+        response_tx.send(results).unwrap_or(());
+    }
+
+    /// 原地修改一笔挂单的价格和/或数量，返回修改后的 `Order<Open>`。
+    ///
+    /// # 队列优先级
+    ///
+    /// * 只减少数量时订单保留原有的排队位置。
+    /// * 把价格改离盘口（买单降价、卖单加价）且不增加数量时，订单保留原有的时间优先级，按原时间戳插入新价位。
+    /// * 增加数量或把价格改向盘口时订单以当前交易所时间重新计时，排到新价位的队尾。
+    ///
+    /// # 校验
+    ///
+    /// * 新价格必须是有限正数，且不能使订单立即成交：`PostOnlyLimit` 挂单返回 `PostOnlyWouldCross`，其余返回 `InvalidOrder`。
+    /// * 新价格与新数量按金融工具过滤器对齐（见 `apply_instrument_filters`），修改后的订单须满足最小名义价值。
+    /// * 新数量必须是有限数，且大于已成交数量。
+    /// * 增加数量时，增加的部分按开仓订单做全仓保证金检查（见 `check_cross_margin_for_order`）。
+    /// * 冻结额按 [`BalanceHandler::reserved_balance`] 重新计算，增加的部分须有足够的可用余额。
+    ///
+    /// 任一校验失败时挂单保持不变。成功时依次发送 `Balance`（冻结额有变化时）与 `OrdersAmended` 事件。
+    pub async fn atomic_amend(&mut self, request: Order<RequestAmend>) -> Result<Order<Open>, ExchangeError>
+    {
+        info!("Attempting to amend order: {:?}", request);
+        let RequestAmend { id, new_price, new_size } = request.state.clone();
+        if new_price.is_none() && new_size.is_none() {
+            return Err(ExchangeError::InvalidOrder(format!("Amend request for order {:?} changes nothing", id)));
+        }
+
+        let current = {
+            let orders_guard = self.account_open_book.read().await;
+            let orders = orders_guard.get_ins_orders_mut(&request.instrument)?;
+            let side_orders = match request.side {
+                | Side::Buy => &orders.bids,
+                | Side::Sell => &orders.asks,
+            };
+            side_orders.iter()
+                       .find(|order| order.state.id == id)
+                       .cloned()
+                       .ok_or_else(|| ExchangeError::OrderNotFound { client_order_id: request.cid.clone(),
+                                                                     order_id: Some(id.clone()) })?
+        };
+
+        let price = new_price.unwrap_or(current.state.price);
+        let size = new_size.unwrap_or(current.state.size);
+        if !price.is_finite() || price <= 0.0 {
+            return Err(ExchangeError::InvalidOrder(format!("Amended price must be a positive finite number, got {}", price)));
+        }
+        if !size.is_finite() {
+            return Err(ExchangeError::InvalidOrder(format!("Amended size must be a finite number, got {}", size)));
+        }
+
+        // 与开单相同，按过滤器对齐价格与数量，并检查最小名义价值
+        let aligned = self.apply_instrument_filters(Self::as_open_request(&current, price, size))?;
+        let (price, size) = (aligned.state.price, aligned.state.size);
+        if size <= current.state.filled_quantity {
+            return Err(ExchangeError::InvalidOrder(format!("Amended size {} must exceed the filled quantity {}", size, current.state.filled_quantity)));
+        }
+        {
+            let order_books = self.single_level_order_book.lock().await;
+            let order_book = order_books.get(&aligned.instrument)
+                                        .ok_or_else(|| ExchangeError::InvalidInstrument(format!("No market data for {}", aligned.instrument)))?;
+            self.check_min_notional(&aligned, order_book)?;
+        }
+        if size > current.state.size {
+            self.check_cross_margin_for_order(&Self::as_open_request(&current, price, size - current.state.size)).await?;
+        }
+
+        let mut amended = current.clone();
+        amended.state.price = price;
+        amended.state.size = size;
+        let remaining_quantity = amended.state.remaining_quantity();
+        if let Some(iceberg) = amended.state.iceberg.as_mut() {
+            iceberg.displayed_quantity = iceberg.displayed_quantity.min(remaining_quantity);
+        }
+
+        let price_changed = price != current.state.price;
+        if price_changed {
+            self.ensure_amend_rests(&amended).await?;
+        }
+
+        let (token, old_lock) = self.reserved_balance(&current);
+        let (_, new_lock) = self.reserved_balance(&amended);
+        let lock_delta = new_lock - old_lock;
+        if lock_delta > 0.0 {
+            self.has_sufficient_available_balance(&token, lock_delta)?;
+        }
+
+        let moves_toward_touch = match current.side {
+            | Side::Buy => price > current.state.price,
+            | Side::Sell => price < current.state.price,
+        };
+        let keeps_priority = !moves_toward_touch && size <= current.state.size;
+        if !keeps_priority {
+            amended.timestamp = amended.timestamp.max(self.exchange_timestamp.load(Ordering::SeqCst));
+        }
+        {
+            let orders_guard = self.account_open_book.write().await;
+            let mut orders = orders_guard.get_ins_orders_mut(&request.instrument)?;
+            let side_orders = match request.side {
+                | Side::Buy => &mut orders.bids,
+                | Side::Sell => &mut orders.asks,
+            };
+            let index = side_orders.iter().position(|order| order.state.id == id).expect("Order existence checked before amending");
+            if keeps_priority && !price_changed {
+                side_orders[index] = amended.clone();
+            }
+            else if keeps_priority {
+                side_orders.remove(index);
+                orders.add_order_open_by_time(amended.clone());
+            }
+            else {
+                side_orders.remove(index);
+                orders.add_order_open(amended.clone());
+            }
+        }
+
+        let exchange_timestamp = self.exchange_timestamp.load(Ordering::SeqCst);
+        if lock_delta != 0.0 {
+            let updated_balance = self.apply_balance_delta(&token, BalanceDelta { total: 0.0,
+                                                                                  available: -lock_delta });
+            self.send_account_event(AccountEvent { exchange_timestamp,
+                                                   exchange: Exchange::Hourglass,
+                                                   kind: AccountEventKind::Balance(TokenBalance::new(token, updated_balance)) })?;
+        }
+        self.send_account_event(AccountEvent { exchange_timestamp,
+                                               exchange: Exchange::Hourglass,
+                                               kind: AccountEventKind::OrdersAmended(vec![amended.clone()]) })?;
+
+        Ok(amended)
+    }
+
+    /// 确认改价后的订单仍是不会立即成交的挂单。
+    async fn ensure_amend_rests(&self, amended: &Order<Open>) -> Result<(), ExchangeError>
+    {
+        let order_books = self.single_level_order_book.lock().await;
+        let Some(order_book) = order_books.get(&amended.instrument)
+        else {
+            return Ok(());
+        };
+        let as_request = Self::as_open_request(amended, amended.state.price, amended.state.remaining_quantity());
+        if self.account_open_book.read().await.determine_maker_taker(&as_request, order_book)? == OrderRole::Taker {
+            return Err(ExchangeError::InvalidOrder(format!("Amended price {} would execute order {:?} immediately", amended.state.price, amended.state.id)));
+        }
+        Ok(())
+    }
+
+    /// 以 `price` 和 `size` 把挂单表示为开单请求，供开单时的各项校验复用。
+    fn as_open_request(order: &Order<Open>, price: f64, size: f64) -> Order<RequestOpen>
+    {
+        Order { instruction: order.instruction,
+                exchange: order.exchange,
+                instrument: order.instrument.clone(),
+                timestamp: order.timestamp,
+                cid: order.cid.clone(),
+                side: order.side,
+                state: RequestOpen { reduce_only: order.state.reduce_only,
+                                     price,
+                                     size,
+                                     tag: order.state.tag.clone() } }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            instrument::{kind::InstrumentKind, Instrument},
+            order::{identification::client_order_id::ClientOrderId, order_instructions::OrderInstruction},
+            token::Token,
+        },
+        hourglass::account::account_config::{FilterViolationHandling, InstrumentFilters},
+        test_utils::create_test_account,
+    };
+    use tokio::sync::mpsc;
+
+    fn limit_order(cid: &str, price: f64, size: f64) -> Order<RequestOpen>
+    {
+        Order { instruction: OrderInstruction::Limit,
+                exchange: Exchange::Hourglass,
+                instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                timestamp: 1234567,
+                cid: Some(ClientOrderId(cid.into())),
+                side: Side::Buy,
+                state: RequestOpen { reduce_only: false,
+                                     price,
+                                     size,
+                                     tag: None } }
+    }
+
+    fn amend(open: &Order<Open>, new_price: Option<f64>, new_size: Option<f64>) -> Order<RequestAmend>
+    {
+        Order { instruction: open.instruction,
+                exchange: Exchange::Hourglass,
+                instrument: open.instrument.clone(),
+                timestamp: 1234568,
+                cid: open.cid.clone(),
+                side: open.side,
+                state: RequestAmend { id: open.state.id.clone(),
+                                      new_price,
+                                      new_size } }
+    }
+
+    async fn bid_cids(account: &HourglassAccount) -> Vec<String>
+    {
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        let orders_guard = account.account_open_book.read().await;
+        let orders = orders_guard.get_ins_orders_mut(&instrument).unwrap();
+        orders.bids.iter().map(|order| order.cid.clone().unwrap().0).collect()
+    }
+
+    #[tokio::test]
+    async fn test_amend_size_decrease_keeps_priority_and_releases_lock()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        let first = account.atomic_open(limit_order("first", 16300.0, 0.2)).await.unwrap();
+        account.atomic_open(limit_order("second", 16300.0, 0.2)).await.unwrap();
+        let available_before = account.get_balance(&Token::from("USDT")).unwrap().available;
+        while account_event_rx.try_recv().is_ok() {}
+
+        let amended = account.atomic_amend(amend(&first, None, Some(0.1))).await.unwrap();
+
+        assert_eq!(amended.state.size, 0.1);
+        // 同价位按从队尾撮合的顺序排列，"first" 仍排在 "second" 之前
+        assert_eq!(bid_cids(&account).await, vec!["second", "first"]);
+        let available_after = account.get_balance(&Token::from("USDT")).unwrap().available;
+        assert!((available_after - available_before - 16300.0 * 0.1).abs() < 1e-6);
+
+        let kinds: Vec<_> = std::iter::from_fn(|| account_event_rx.try_recv().ok()).map(|event| event.kind).collect();
+        assert!(matches!(kinds[0], AccountEventKind::Balance(_)));
+        assert!(matches!(&kinds[1], AccountEventKind::OrdersAmended(orders) if orders[0].state.size == 0.1));
+        assert!(account.check_invariants().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_amend_size_increase_loses_priority()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        let first = account.atomic_open(limit_order("first", 16300.0, 0.2)).await.unwrap();
+        account.atomic_open(limit_order("second", 16300.0, 0.2)).await.unwrap();
+
+        account.atomic_amend(amend(&first, None, Some(0.3))).await.unwrap();
+
+        assert_eq!(bid_cids(&account).await, vec!["first", "second"]);
+        assert!(account.check_invariants().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_amend_price_moves_order_to_back_of_new_level()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        let moving = account.atomic_open(limit_order("moving", 16200.0, 0.2)).await.unwrap();
+        account.atomic_open(limit_order("resting", 16300.0, 0.2)).await.unwrap();
+
+        let amended = account.atomic_amend(amend(&moving, Some(16300.0), None)).await.unwrap();
+
+        assert_eq!(amended.state.price, 16300.0);
+        assert_eq!(bid_cids(&account).await, vec!["moving", "resting"]);
+        assert!(account.check_invariants().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_amend_price_away_from_touch_keeps_time_priority()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        let moving = account.atomic_open(limit_order("moving", 16300.0, 0.2)).await.unwrap();
+        account.exchange_timestamp.store(1234570, Ordering::SeqCst);
+        account.atomic_open(Order { timestamp: 1234570,
+                                    ..limit_order("resting", 16200.0, 0.2) })
+               .await
+               .unwrap();
+
+        let amended = account.atomic_amend(amend(&moving, Some(16200.0), None)).await.unwrap();
+
+        // 降价离开盘口不重新计时，"moving" 比 "resting" 更早到达，同价位中先成交（排在队尾）
+        assert_eq!(amended.timestamp, 1234567);
+        assert_eq!(bid_cids(&account).await, vec!["resting", "moving"]);
+        assert!(account.check_invariants().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_amend_leaves_order_unchanged()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        let open = account.atomic_open(limit_order("open", 16300.0, 0.2)).await.unwrap();
+
+        // 改价后会立即成交
+        assert!(matches!(account.atomic_amend(amend(&open, Some(16600.0), None)).await, Err(ExchangeError::InvalidOrder(_))));
+        // 可用余额不足以覆盖增加的冻结额
+        assert!(matches!(account.atomic_amend(amend(&open, None, Some(10.0))).await, Err(ExchangeError::InsufficientBalance { .. })));
+        // 未知的订单
+        let mut unknown = amend(&open, None, Some(0.1));
+        unknown.state.id = crate::common::order::identification::OrderId(u64::MAX);
+        assert!(matches!(account.atomic_amend(unknown).await, Err(ExchangeError::OrderNotFound { .. })));
+
+        let resting = account.account_open_book.read().await.fetch_all();
+        assert_eq!(resting, vec![open]);
+    }
+
+    #[tokio::test]
+    async fn test_amend_applies_instrument_filters_and_min_notional()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let open = account.atomic_open(limit_order("open", 16300.0, 0.2)).await.unwrap();
+        account.config.instrument_filters = vec![InstrumentFilters { instrument: open.instrument.clone(),
+                                                                     min_notional: 1000.0,
+                                                                     lot_size: 0.01,
+                                                                     tick_size: 0.5 }];
+
+        // 默认拒绝未对齐的价格与数量，以及低于最小名义价值的订单
+        assert!(matches!(account.atomic_amend(amend(&open, Some(16300.2), None)).await, Err(ExchangeError::PriceNotAligned { .. })));
+        assert!(matches!(account.atomic_amend(amend(&open, None, Some(0.205))).await, Err(ExchangeError::SizeNotAligned { .. })));
+        assert!(matches!(account.atomic_amend(amend(&open, None, Some(0.05))).await, Err(ExchangeError::BelowMinNotional { .. })));
+        assert_eq!(account.account_open_book.read().await.fetch_all(), vec![open.clone()]);
+
+        // 按取整处理时改单后的价格与数量对齐到步长
+        account.config.filter_violation = FilterViolationHandling::Round;
+        let amended = account.atomic_amend(amend(&open, Some(16300.2), Some(0.205))).await.unwrap();
+        assert_eq!(amended.state.price, 16300.0);
+        assert!((amended.state.size - 0.2).abs() < 1e-9);
+    }
+}
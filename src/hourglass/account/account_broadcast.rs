@@ -0,0 +1,233 @@
+use crate::{
+    common::event::{AccountEvent, AccountEventKind},
+    error::ExchangeError,
+    hourglass::account::HourglassAccount,
+    hourglass_log::{info, warn},
+};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, net::SocketAddr};
+use tokio::sync::{broadcast, mpsc};
+use warp::{
+    ws::{Message, WebSocket, Ws},
+    Filter,
+};
+
+/// 广播通道的容量，订阅者落后超过该数量的事件时丢弃最早的事件。
+const BROADCAST_CAPACITY: usize = 1024;
+
+/// WebSocket 订阅者可以选择的事件类别。
+///
+/// - `Orders`: 挂单状态变化与成交，即 `Orders*`、`Trade` 与 `SyntheticFills`。
+/// - `Balances`: 余额变化，即 `Balance`、`Balances` 与 `Withdrawal`。
+/// - `Positions`: 仓位与保证金变化，即 `Positions`、`MarginUpdate`、`RealisedPnl` 与 `Liquidation`。
+///
+/// `AccountConfig` 与 `WarmUpCompleted` 不属于任何类别，只推送给未发送订阅消息的客户端。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventTopic
+{
+    Orders,
+    Balances,
+    Positions,
+}
+
+impl EventTopic
+{
+    /// 返回事件所属的类别。
+    pub fn of(kind: &AccountEventKind) -> Option<Self>
+    {
+        match kind {
+            | AccountEventKind::OrdersOpen(_)
+            | AccountEventKind::OrdersCancelled(_)
+            | AccountEventKind::OrdersFilled(_)
+            | AccountEventKind::OrdersPartiallyFilled(_)
+            | AccountEventKind::OrdersAmended(_)
+            | AccountEventKind::Trade(_)
+            | AccountEventKind::SyntheticFills(_) => Some(EventTopic::Orders),
+            | AccountEventKind::Balance(_) | AccountEventKind::Balances(_) | AccountEventKind::Withdrawal(_) => Some(EventTopic::Balances),
+            | AccountEventKind::Positions(_) | AccountEventKind::MarginUpdate(_) | AccountEventKind::RealisedPnl(_) | AccountEventKind::Liquidation(_) => Some(EventTopic::Positions),
+            | AccountEventKind::AccountConfig(_) | AccountEventKind::WarmUpCompleted(_) => None,
+        }
+    }
+}
+
+/// 客户端发送的订阅消息，例如 `{"subscribe": ["orders", "balances"]}`。每条订阅消息都会替换之前的订阅。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Subscribe
+{
+    pub subscribe: Vec<EventTopic>,
+}
+
+/// 服务端对 [`Subscribe`] 的确认，例如 `{"subscribed": ["orders"]}`。
+///
+/// 确认之后推送的事件都已按新的订阅过滤，客户端需要确定订阅生效的时刻时应等待该消息。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Subscribed
+{
+    pub subscribed: Vec<EventTopic>,
+}
+
+impl HourglassAccount
+{
+    /// 在 `addr` 上启动 WebSocket 服务器，把之后发送的每个 [`AccountEvent`] 序列化为 JSON 推送给所有连接的客户端，返回实际绑定的地址。
+    ///
+    /// 原有的 `account_event_tx` 仍然按原顺序收到全部事件，但改为经由一个转发任务送达，因此是异步到达的。
+    /// 应在账户构建后、开始回放前调用：调用前已经克隆出去的 `account_event_tx` 发送的事件不会被广播。
+    ///
+    /// 客户端连接后默认收到全部事件，发送 [`Subscribe`] 消息并收到 [`Subscribed`] 确认后只收到所选 [`EventTopic`] 的事件。
+    /// 消费过慢的客户端会跳过落后的事件，不会阻塞撮合。
+    pub fn with_event_broadcast(&mut self, addr: impl Into<SocketAddr>) -> Result<SocketAddr, ExchangeError>
+    {
+        let (broadcast_tx, _) = broadcast::channel::<AccountEvent>(BROADCAST_CAPACITY);
+        let subscriber_tx = broadcast_tx.clone();
+        let route = warp::ws().map(move |ws: Ws| {
+                                  let events_rx = subscriber_tx.subscribe();
+                                  ws.on_upgrade(move |socket| serve_subscriber(socket, events_rx))
+                              });
+        let (bound_addr, server) = warp::serve(route).try_bind_ephemeral(addr.into())
+                                                     .map_err(|e| ExchangeError::NetworkError(format!("Failed to bind event broadcast server: {}", e)))?;
+        tokio::spawn(server);
+        info!("Broadcasting account events on ws://{}", bound_addr);
+
+        let (tee_tx, mut tee_rx) = mpsc::unbounded_channel::<AccountEvent>();
+        let client_tx = std::mem::replace(&mut self.account_event_tx, tee_tx);
+        tokio::spawn(async move {
+            while let Some(event) = tee_rx.recv().await {
+                // 没有 WebSocket 订阅者时发送失败，属于正常情况
+                let _ = broadcast_tx.send(event.clone());
+                if let Err(err) = client_tx.send(event) {
+                    warn!("Client offline - Failed to forward AccountEvent: {:?}", err);
+                }
+            }
+        });
+
+        Ok(bound_addr)
+    }
+}
+
+/// 向单个 WebSocket 客户端推送事件，同时处理其订阅消息，直到连接关闭。
+async fn serve_subscriber(socket: WebSocket, mut events_rx: broadcast::Receiver<AccountEvent>)
+{
+    let (mut ws_tx, mut ws_rx) = socket.split();
+    let mut topics: Option<HashSet<EventTopic>> = None;
+
+    loop {
+        tokio::select! {
+            message = ws_rx.next() => match message {
+                | Some(Ok(message)) if message.is_close() => break,
+                | Some(Ok(message)) => {
+                    let Ok(text) = message.to_str()
+                    else {
+                        continue;
+                    };
+                    let subscribe = match serde_json::from_str::<Subscribe>(text) {
+                        | Ok(subscribe) => subscribe,
+                        | Err(err) => {
+                            warn!("Ignoring invalid subscribe message {:?}: {}", text, err);
+                            continue;
+                        }
+                    };
+                    let ack = Subscribed { subscribed: subscribe.subscribe.clone() };
+                    topics = Some(subscribe.subscribe.into_iter().collect());
+                    let json = serde_json::to_string(&ack).expect("Subscribed serialises to JSON");
+                    if ws_tx.send(Message::text(json)).await.is_err() {
+                        break;
+                    }
+                }
+                | Some(Err(_)) | None => break,
+            },
+            event = events_rx.recv() => match event {
+                | Ok(event) => {
+                    let wanted = match &topics {
+                        | None => true,
+                        | Some(topics) => EventTopic::of(&event.kind).is_some_and(|topic| topics.contains(&topic)),
+                    };
+                    if !wanted {
+                        continue;
+                    }
+                    let json = match serde_json::to_string(&event) {
+                        | Ok(json) => json,
+                        | Err(err) => {
+                            warn!("Failed to serialise AccountEvent for broadcast: {}", err);
+                            continue;
+                        }
+                    };
+                    if ws_tx.send(Message::text(json)).await.is_err() {
+                        break;
+                    }
+                }
+                | Err(broadcast::error::RecvError::Lagged(skipped)) => warn!("Event broadcast subscriber lagged, skipped {} events", skipped),
+                | Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            balance::{Balance, TokenBalance},
+            token::Token,
+        },
+        test_utils::create_test_account,
+        Exchange,
+    };
+    use tokio_tungstenite::{connect_async, tungstenite};
+
+    fn balance_event() -> AccountEvent
+    {
+        AccountEvent { exchange_timestamp: 1,
+                       exchange: Exchange::Hourglass,
+                       kind: AccountEventKind::Balance(TokenBalance::new(Token::from("USDT"), Balance::new(100.0, 100.0))) }
+    }
+
+    fn orders_event() -> AccountEvent
+    {
+        AccountEvent { exchange_timestamp: 2,
+                       exchange: Exchange::Hourglass,
+                       kind: AccountEventKind::OrdersOpen(vec![]) }
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_forwards_events_and_filters_by_topic()
+    {
+        let mut account = create_test_account().await;
+        let (client_tx, mut client_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = client_tx;
+        let addr = account.with_event_broadcast(([127, 0, 0, 1], 0)).unwrap();
+
+        let (mut ws, _) = connect_async(format!("ws://{}", addr)).await.unwrap();
+        ws.send(tungstenite::Message::text(r#"{"subscribe": ["orders"]}"#)).await.unwrap();
+        // 收到确认后订阅已经生效，再发送事件
+        let ack = ws.next().await.unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Subscribed>(ack.to_text().unwrap()).unwrap(),
+                   Subscribed { subscribed: vec![EventTopic::Orders] });
+
+        let (balance, orders) = (balance_event(), orders_event());
+        account.send_account_event(balance.clone()).unwrap();
+        account.send_account_event(orders.clone()).unwrap();
+
+        // 订阅了 orders 的客户端只收到挂单事件
+        let received = ws.next().await.unwrap().unwrap();
+        let event: AccountEvent = serde_json::from_str(received.to_text().unwrap()).unwrap();
+        assert_eq!(event, orders);
+
+        // 原有的事件接收方仍按顺序收到全部事件
+        assert_eq!(client_rx.recv().await.unwrap(), balance);
+        assert_eq!(client_rx.recv().await.unwrap(), orders);
+    }
+
+    #[test]
+    fn test_event_topic_classification()
+    {
+        assert_eq!(EventTopic::of(&balance_event().kind), Some(EventTopic::Balances));
+        assert_eq!(EventTopic::of(&orders_event().kind), Some(EventTopic::Orders));
+        assert_eq!(EventTopic::of(&AccountEventKind::WarmUpCompleted(0)), None);
+        assert_eq!(serde_json::from_str::<Subscribe>(r#"{"subscribe": ["positions"]}"#).unwrap(),
+                   Subscribe { subscribe: vec![EventTopic::Positions] });
+    }
+}
@@ -0,0 +1,379 @@
+use crate::{
+    common::{
+        account_positions::{
+            exited_position::PositionExit,
+            exited_positions::AccountExitedPositions,
+            future::{FuturePosition, FuturePositionConfig},
+            leveraged_token::{LeveragedTokenPosition, LeveragedTokenPositionConfig},
+            option::{OptionPosition, OptionPositionConfig},
+            perpetual::{PerpetualPosition, PerpetualPositionConfig},
+            position_id::PositionId,
+            AccountPositions,
+        },
+        balance::Balance,
+        instrument::Instrument,
+        order::{
+            identification::{oco_group_id::OcoGroupId, OrderId},
+            stop_order::StopOrder,
+        },
+        token::Token,
+        trade::ClientTrade,
+    },
+    hourglass::{
+        account::{
+            account_config::AccountConfig,
+            account_orders::{OrderIdRngState, PendingCancel},
+            account_spot::SpotCostBasis,
+            account_volume_tiers::RollingVolumeTracker,
+            HourglassAccount,
+        },
+        clickhouse_api::datatype::single_level_order_book::SingleLevelOrderBook,
+        open_orders_book::OpenOrdersBook,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+
+/// 把 map 的条目按 key 排序，使检查点的内容与 `HashMap`/`DashMap` 的遍历顺序无关。
+fn sorted_entries<K: Ord, V>(entries: impl Iterator<Item = (K, V)>) -> Vec<(K, V)>
+{
+    let mut entries: Vec<_> = entries.collect();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    entries
+}
+
+/// 为 `Arc<RwLock<HashMap<K, V>>>` 组成的持仓集合生成可序列化的检查点结构。
+///
+/// 以按 key 排序的 `Vec<(K, V)>` 形式保存，避免 `Instrument` 等非字符串 key 在 JSON 中无法作为 map key 的问题，
+/// 同一账户状态总是生成相同的检查点；读写都走异步锁，可以在运行时内安全调用。
+macro_rules! position_maps_checkpoint {
+    ($name:ident, $source:ty, $key:ty, { $($field:ident: $value:ty),* $(,)? }) => {
+        #[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+        pub struct $name
+        {
+            $(pub $field: Vec<($key, $value)>,)*
+        }
+
+        impl $name
+        {
+            pub async fn capture(source: &$source) -> Self
+            {
+                Self { $($field: sorted_entries(source.$field.read().await.iter().map(|(key, value)| (key.clone(), value.clone()))),)* }
+            }
+
+            pub async fn restore(self, target: &$source)
+            {
+                $(*target.$field.write().await = self.$field.into_iter().collect();)*
+            }
+        }
+    };
+}
+
+position_maps_checkpoint!(PositionsCheckpoint, AccountPositions, Instrument, {
+    margin_pos_long: LeveragedTokenPosition,
+    margin_pos_short: LeveragedTokenPosition,
+    perpetual_pos_long: PerpetualPosition,
+    perpetual_pos_short: PerpetualPosition,
+    futures_pos_long: FuturePosition,
+    futures_pos_short: FuturePosition,
+    option_pos_long_call: OptionPosition,
+    option_pos_long_put: OptionPosition,
+    option_pos_short_call: OptionPosition,
+    option_pos_short_put: OptionPosition,
+    margin_pos_long_config: LeveragedTokenPositionConfig,
+    margin_pos_short_config: LeveragedTokenPositionConfig,
+    perpetual_pos_long_config: PerpetualPositionConfig,
+    perpetual_pos_short_config: PerpetualPositionConfig,
+    futures_pos_long_config: FuturePositionConfig,
+    futures_pos_short_config: FuturePositionConfig,
+    option_pos_long_call_config: OptionPositionConfig,
+    option_pos_long_put_config: OptionPositionConfig,
+    option_pos_short_call_config: OptionPositionConfig,
+    option_pos_short_put_config: OptionPositionConfig,
+});
+
+position_maps_checkpoint!(ExitedPositionsCheckpoint, AccountExitedPositions, PositionId, {
+    margin_pos_long: PositionExit,
+    margin_pos_short: PositionExit,
+    perpetual_pos_long: PositionExit,
+    perpetual_pos_short: PositionExit,
+    futures_pos_long: PositionExit,
+    futures_pos_short: PositionExit,
+    option_pos_long_call: PositionExit,
+    option_pos_long_put: PositionExit,
+    option_pos_short_call: PositionExit,
+    option_pos_short_put: PositionExit,
+});
+
+/// 账户状态检查点：余额、持仓、挂单、待生效撤单、条件单、OCO 组、最新价格、现货成本基础、账户配置、
+/// 成交历史、阶梯费率的滚动成交额、资金费结算时点、订单ID随机数状态以及各类计数器。
+///
+/// 检查点是账户状态的深拷贝，生成之后对账户的修改不会反映到检查点中。
+/// 旧版本写入的检查点没有 `config` 时，恢复后沿用当前账户的配置；没有 `order_id_rng` 时保留当前的随机数状态。
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct AccountCheckpoint
+{
+    pub exchange_timestamp: i64,
+    pub client_trade_counter: i64,
+    pub request_counter: u64,
+    pub order_counter: u64,
+    pub balances: Vec<(Token, Balance)>,
+    pub positions: PositionsCheckpoint,
+    pub exited_positions: ExitedPositionsCheckpoint,
+    pub open_orders: Vec<(Instrument, OpenOrdersBook)>,
+    pub latest_prices: Vec<(Instrument, SingleLevelOrderBook)>,
+    #[serde(default)]
+    pub spot_cost_basis: Vec<(Instrument, SpotCostBasis)>,
+    #[serde(default)]
+    pub config: Option<AccountConfig>,
+    #[serde(default)]
+    pub trade_history: Vec<(Instrument, Vec<ClientTrade>)>,
+    #[serde(default)]
+    pub pending_cancels: Vec<PendingCancel>,
+    #[serde(default)]
+    pub pending_stops: Vec<StopOrder>,
+    #[serde(default)]
+    pub oco_groups: Vec<(OcoGroupId, (OrderId, OrderId))>,
+    #[serde(default)]
+    pub oco_group_counter: u64,
+    #[serde(default)]
+    pub order_id_rng: Option<OrderIdRngState>,
+    #[serde(default)]
+    pub traded_volume: RollingVolumeTracker,
+    #[serde(default)]
+    pub last_funding_ts: Option<i64>,
+}
+
+impl HourglassAccount
+{
+    /// 生成当前账户状态的检查点。
+    pub async fn checkpoint(&self) -> AccountCheckpoint
+    {
+        // 挂单相关的状态在同一把读锁内一次取出，避免持锁等待其他锁
+        let orders_checkpoint = {
+            let account_open_book = self.account_open_book.read().await;
+            AccountCheckpoint { request_counter: account_open_book.request_counter.load(Ordering::SeqCst),
+                                order_counter: account_open_book.order_counter.load(Ordering::SeqCst),
+                                open_orders: sorted_entries(account_open_book.instrument_orders_map.iter().map(|entry| (entry.key().clone(), entry.value().clone()))),
+                                pending_cancels: account_open_book.pending_cancels.clone(),
+                                pending_stops: account_open_book.pending_stops.clone(),
+                                oco_groups: sorted_entries(account_open_book.oco_groups.iter().map(|(group, legs)| (*group, legs.clone()))),
+                                oco_group_counter: account_open_book.oco_group_counter,
+                                order_id_rng: Some(account_open_book.order_id_rng_state()),
+                                ..Default::default() }
+        };
+
+        let latest_prices = sorted_entries(self.single_level_order_book.lock().await.iter().map(|(instrument, book)| (instrument.clone(), book.clone())));
+
+        AccountCheckpoint { exchange_timestamp: self.exchange_timestamp.load(Ordering::SeqCst),
+                            client_trade_counter: self.client_trade_counter.load(Ordering::SeqCst),
+                            balances: sorted_entries(self.balances.iter().map(|entry| (entry.key().clone(), *entry.value()))),
+                            positions: PositionsCheckpoint::capture(&self.positions).await,
+                            exited_positions: ExitedPositionsCheckpoint::capture(&self.exited_positions).await,
+                            latest_prices,
+                            spot_cost_basis: sorted_entries(self.spot_cost_basis.iter().map(|entry| (entry.key().clone(), entry.value().clone()))),
+                            config: Some(self.config.clone()),
+                            trade_history: sorted_entries(self.trade_history.iter().map(|(instrument, trades)| (instrument.clone(), trades.clone()))),
+                            traded_volume: self.traded_volume.clone(),
+                            last_funding_ts: self.last_funding_ts,
+                            ..orders_checkpoint }
+    }
+
+    /// 用检查点覆盖当前账户状态。原有的余额、持仓、挂单与成交历史会被清空。
+    pub async fn restore_checkpoint(&mut self, checkpoint: AccountCheckpoint)
+    {
+        self.exchange_timestamp.store(checkpoint.exchange_timestamp, Ordering::SeqCst);
+        self.client_trade_counter.store(checkpoint.client_trade_counter, Ordering::SeqCst);
+
+        self.balances.clear();
+        for (token, balance) in checkpoint.balances {
+            self.balances.insert(token, balance);
+        }
+
+        checkpoint.positions.restore(&self.positions).await;
+        checkpoint.exited_positions.restore(&self.exited_positions).await;
+
+        {
+            let mut account_open_book = self.account_open_book.write().await;
+            account_open_book.request_counter.store(checkpoint.request_counter, Ordering::SeqCst);
+            account_open_book.order_counter.store(checkpoint.order_counter, Ordering::SeqCst);
+            account_open_book.instrument_orders_map.clear();
+            for (instrument, mut book) in checkpoint.open_orders {
+                book.rebuild_expiry_index();
+                account_open_book.instrument_orders_map.insert(instrument, book);
+            }
+            account_open_book.pending_cancels = checkpoint.pending_cancels;
+            account_open_book.pending_stops = checkpoint.pending_stops;
+            account_open_book.oco_groups = checkpoint.oco_groups.into_iter().collect();
+            account_open_book.oco_group_counter = checkpoint.oco_group_counter;
+            if let Some(state) = &checkpoint.order_id_rng {
+                account_open_book.restore_order_id_rng(state);
+            }
+        }
+
+        *self.single_level_order_book.lock().await = checkpoint.latest_prices.into_iter().collect();
+
+        self.spot_cost_basis.clear();
+        for (instrument, cost_basis) in checkpoint.spot_cost_basis {
+            self.spot_cost_basis.insert(instrument, cost_basis);
+        }
+
+        if let Some(config) = checkpoint.config {
+            self.config = config;
+        }
+        self.trade_history = checkpoint.trade_history.into_iter().collect();
+        self.traded_volume = checkpoint.traded_volume;
+        self.last_funding_ts = checkpoint.last_funding_ts;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            account_positions::{PositionDirectionMode, PositionMarginMode},
+            instrument::kind::InstrumentKind,
+            order::{
+                identification::client_order_id::ClientOrderId,
+                order_instructions::OrderInstruction,
+                states::{request_cancel::RequestCancel, request_open::RequestOpen},
+                stop_order::StopOrderKind,
+                Order,
+            },
+            Side,
+        },
+        hourglass::{account::account_handlers::trade_handler::TradeHandler, clickhouse_api::datatype::clickhouse_trade_data::MarketTrade},
+        test_utils::{create_test_account, create_test_order_open, create_test_perpetual_position},
+        Exchange,
+    };
+    use tokio::sync::mpsc;
+
+    /// 挂出一笔买单并用一笔市场卖单使其完全成交。
+    async fn open_and_fill(account: &mut HourglassAccount, cid: &str, timestamp: i64)
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                    exchange: Exchange::Hourglass,
+                                    instrument,
+                                    timestamp,
+                                    cid: Some(ClientOrderId(cid.into())),
+                                    side: Side::Buy,
+                                    state: RequestOpen { price: 16400.0,
+                                                         size: 0.1,
+                                                         reduce_only: false,
+                                                         tag: None } })
+               .await
+               .unwrap();
+        account.handle_trade_data(&MarketTrade { exchange: "binance-futures".to_string(),
+                                                 symbol: "ETHUSDT".to_string(),
+                                                 side: "sell".to_string(),
+                                                 price: 16300.0,
+                                                 timestamp: timestamp + 1,
+                                                 amount: 0.1 })
+               .await
+               .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_round_trip_through_json()
+    {
+        let mut account = create_test_account().await;
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        account.positions.perpetual_pos_long.write().await.insert(instrument.clone(), create_test_perpetual_position(instrument.clone()));
+        account.balances.get_mut(&Token::from("USDT")).unwrap().available = 1234.0;
+        account.account_open_book
+               .read()
+               .await
+               .get_ins_orders_mut(&instrument)
+               .unwrap()
+               .add_order_open(create_test_order_open(Side::Buy, 16000.0, 1.0));
+        {
+            let mut account_open_book = account.account_open_book.write().await;
+            let stop_request = Order { instruction: OrderInstruction::Market,
+                                       exchange: Exchange::Hourglass,
+                                       instrument: instrument.clone(),
+                                       timestamp: 1234567,
+                                       cid: Some(ClientOrderId("stop".into())),
+                                       side: Side::Sell,
+                                       state: RequestOpen { price: 0.0,
+                                                            size: 0.1,
+                                                            reduce_only: true,
+                                                            tag: None } };
+            account_open_book.pending_stops.push(StopOrder::new(stop_request, StopOrderKind::Stop { trigger_price: 15000.0 }));
+            account_open_book.schedule_cancel(Order { instruction: OrderInstruction::Limit,
+                                                      exchange: Exchange::Hourglass,
+                                                      instrument: instrument.clone(),
+                                                      timestamp: 1234567,
+                                                      cid: None,
+                                                      side: Side::Buy,
+                                                      state: RequestCancel { id: Some(OrderId(1)) } },
+                                              1234600);
+            account_open_book.oco_groups.insert(OcoGroupId(1), (OrderId(1), OrderId(2)));
+            account_open_book.oco_group_counter = 1;
+            account_open_book.reseed(7);
+            account_open_book.order_id(1234567);
+        }
+        account.traded_volume.record(1234000, InstrumentKind::Perpetual, 5000.0, 1_000_000);
+        account.last_funding_ts = Some(1200000);
+
+        let json = serde_json::to_string(&account.checkpoint().await).unwrap();
+        let checkpoint: AccountCheckpoint = serde_json::from_str(&json).unwrap();
+
+        let mut restored = create_test_account().await;
+        restored.positions.perpetual_pos_long.write().await.clear();
+        restored.exchange_timestamp.store(0, Ordering::SeqCst);
+        restored.restore_checkpoint(checkpoint).await;
+
+        assert_eq!(restored.exchange_timestamp.load(Ordering::SeqCst), 1234567);
+        assert_eq!(restored.balances.get(&Token::from("USDT")).unwrap().available, 1234.0);
+        assert!(restored.positions.perpetual_pos_long.read().await.contains_key(&instrument));
+        assert_eq!(restored.account_open_book.read().await.fetch_all().len(), 1);
+        assert_eq!(restored.single_level_order_book.lock().await.get(&instrument).unwrap().latest_bid, 16305.0);
+        assert_eq!(restored.traded_volume.volume(InstrumentKind::Perpetual), 5000.0);
+        assert_eq!(restored.last_funding_ts, Some(1200000));
+        assert_eq!(restored.checkpoint().await, account.checkpoint().await);
+
+        // 订单ID的随机序列从保存时的位置继续
+        let mut original_orders = account.account_open_book.write().await;
+        let mut restored_orders = restored.account_open_book.write().await;
+        assert_eq!(restored_orders.pending_stops.len(), 1);
+        assert_eq!(restored_orders.pending_cancels[0].effective_ts, 1234600);
+        assert_eq!(restored_orders.oco_groups[&OcoGroupId(1)], (OrderId(1), OrderId(2)));
+        assert_eq!(restored_orders.order_id(1234568), original_orders.order_id(1234568));
+    }
+
+    #[tokio::test]
+    async fn test_restore_after_further_fills_reproduces_checkpoint()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(),
+                                                                         PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                                                                                   leverage: 1.0,
+                                                                                                   position_direction_mode: PositionDirectionMode::Net });
+        open_and_fill(&mut account, "first", 1234568).await;
+        account.account_open_book
+               .read()
+               .await
+               .get_ins_orders_mut(&instrument)
+               .unwrap()
+               .add_order_open(create_test_order_open(Side::Buy, 16000.0, 1.0));
+        let checkpoint = account.checkpoint().await;
+
+        // 检查点之后继续成交并修改配置，检查点保持不变
+        open_and_fill(&mut account, "second", 1234570).await;
+        account.config.maintenance_margin_rate = Some(0.01);
+        assert_ne!(account.checkpoint().await, checkpoint);
+
+        account.restore_checkpoint(checkpoint.clone()).await;
+
+        assert_eq!(account.checkpoint().await, checkpoint);
+        assert_eq!(account.fetch_trades(&instrument).len(), 1);
+        assert!((account.positions.perpetual_pos_long.read().await[&instrument].meta.current_size - 0.1).abs() < 1e-9);
+        assert_eq!(account.config.maintenance_margin_rate, None);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{
+    common::{order::OrderRole, trade::ClientTrade},
+    error::ExchangeError,
+    hourglass::account::{account_config::CommissionRates, HourglassAccount},
+};
+use std::fmt::Debug;
+
+/// 计算一笔成交手续费时可用的信息。
+#[derive(Clone, Copy, Debug)]
+pub struct FillContext<'a>
+{
+    pub trade: &'a ClientTrade,             // 待计费的成交，`fees` 字段尚未确定
+    pub role: OrderRole,                    // 本账户在这笔成交中的角色
+    pub contract_multiplier: f64,           // 该金融工具的合约乘数
+    pub rates: Option<&'a CommissionRates>, // 该合约类型当前适用的费率：阶梯费率的当前档位，否则为 `AccountConfig.fees_book` 中的费率，都未配置时为 `None`
+}
+
+/// 一笔成交的手续费，以报价货币计，为负时表示返佣。
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SymbolFees
+{
+    pub fees: f64,
+}
+
+/// 可插拔的手续费计算。
+///
+/// 实现该 trait 即可接入阶梯费率、按张收费、返佣、推荐折扣等自定义费率表，而无需修改撮合逻辑。
+/// 撮合产生的每笔成交都会调用 [`fee`](Self::fee)，结果再按 `AccountConfig.rounding` 舍入后计入成交。
+/// 返回错误时本次撮合以该错误结束，不会按零手续费结算。
+pub trait CommissionProvider: Debug + Send + Sync
+{
+    fn fee(&self, fill: &FillContext) -> Result<SymbolFees, ExchangeError>;
+}
+
+/// 默认的手续费计算，按 `AccountConfig.fees_book` 中的费率与计费口径收取。
+///
+/// 未配置该合约类型的费率时返回 `ExchangeError::Hourglass`，与 `fees_percent` 的错误一致。
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FeesBookCommission;
+
+impl CommissionProvider for FeesBookCommission
+{
+    fn fee(&self, fill: &FillContext) -> Result<SymbolFees, ExchangeError>
+    {
+        let rates = fill.rates.ok_or_else(|| ExchangeError::Hourglass(format!("Fee rates for {:?} not found", fill.trade.instrument.kind)))?;
+        let rate = match fill.role {
+            | OrderRole::Maker => rates.maker_fees,
+            | OrderRole::Taker => rates.taker_fees,
+        };
+        let percent_fee = rate * fill.trade.price * fill.trade.size;
+        Ok(SymbolFees { fees: rates.fee_basis.fee(percent_fee, fill.trade.size, fill.contract_multiplier) })
+    }
+}
+
+impl HourglassAccount
+{
+    /// 用账户的 [`CommissionProvider`] 计算一笔撮合成交的手续费，并按配置的舍入方式舍入。撮合器对每笔成交调用。
+    pub(crate) fn commission(&self, trade: &ClientTrade, role: OrderRole) -> Result<f64, ExchangeError>
+    {
+        // 配置了阶梯费率时按 30 天滚动成交额所在档位的费率计费
+        let tier_rates = self.volume_tier_rates(trade.instrument.kind);
+        let fill = FillContext { trade,
+                                 role,
+                                 contract_multiplier: self.config.contract_multiplier(&trade.instrument),
+                                 rates: tier_rates.as_ref().or_else(|| self.config.fees_book.get(&trade.instrument.kind)) };
+        Ok(self.config.rounding.round_fee(self.commission_provider.fee(&fill)?.fees))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            event::AccountEventKind,
+            instrument::{kind::InstrumentKind, Instrument},
+            order::{identification::client_order_id::ClientOrderId, order_instructions::OrderInstruction, states::request_open::RequestOpen, Order},
+            Side,
+        },
+        hourglass::{account::account_handlers::trade_handler::TradeHandler, clickhouse_api::datatype::clickhouse_trade_data::MarketTrade},
+        test_utils::create_test_account,
+        Exchange,
+    };
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    /// 示例：Maker 返佣、Taker 按张收费，带策略标签 `referral` 的成交再打八折。
+    #[derive(Debug)]
+    struct RebateWithReferralDiscount
+    {
+        maker_rebate: f64,
+        taker_fee_per_contract: f64,
+    }
+
+    impl CommissionProvider for RebateWithReferralDiscount
+    {
+        fn fee(&self, fill: &FillContext) -> Result<SymbolFees, ExchangeError>
+        {
+            let fees = match fill.role {
+                | OrderRole::Maker => -self.maker_rebate * fill.trade.price * fill.trade.size * fill.contract_multiplier,
+                | OrderRole::Taker => self.taker_fee_per_contract * fill.trade.size,
+            };
+            let discount = if fill.trade.tag.as_deref() == Some("referral") { 0.8 } else { 1.0 };
+            Ok(SymbolFees { fees: fees * discount })
+        }
+    }
+
+    async fn fill_resting_buy(account: &mut HourglassAccount, tag: Option<String>) -> f64
+    {
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                    exchange: Exchange::Hourglass,
+                                    instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                                    timestamp: 1234567,
+                                    cid: Some(ClientOrderId("commission".into())),
+                                    side: Side::Buy,
+                                    state: RequestOpen { price: 16400.0,
+                                                         size: 0.25,
+                                                         reduce_only: false,
+                                                         tag } })
+               .await
+               .unwrap();
+        account.handle_trade_data(&MarketTrade { exchange: "binance-futures".to_string(),
+                                                 symbol: "ETHUSDT".to_string(),
+                                                 side: "sell".to_string(),
+                                                 price: 16300.0,
+                                                 timestamp: 1234568,
+                                                 amount: 1.0 })
+               .await
+               .unwrap();
+
+        let mut fees = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            if let AccountEventKind::Trade(trade) = event.kind {
+                fees.push(trade.fees);
+            }
+        }
+        assert_eq!(fees.len(), 1);
+        fees[0]
+    }
+
+    #[tokio::test]
+    async fn test_default_provider_uses_fees_book()
+    {
+        let mut account = create_test_account().await;
+        // 按挂单价成交，Maker 费率 0.001
+        let fees = fill_resting_buy(&mut account, None).await;
+        assert!((fees - 16400.0 * 0.25 * 0.001).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_default_provider_errors_without_fees_book_entry()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                    exchange: Exchange::Hourglass,
+                                    instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                                    timestamp: 1234567,
+                                    cid: Some(ClientOrderId("commission".into())),
+                                    side: Side::Buy,
+                                    state: RequestOpen { price: 16400.0,
+                                                         size: 0.25,
+                                                         reduce_only: false,
+                                                         tag: None } })
+               .await
+               .unwrap();
+        account.config.fees_book.clear();
+
+        // 缺少费率时撮合以错误结束，而不是按零手续费成交
+        let result = account.handle_trade_data(&MarketTrade { exchange: "binance-futures".to_string(),
+                                                              symbol: "ETHUSDT".to_string(),
+                                                              side: "sell".to_string(),
+                                                              price: 16300.0,
+                                                              timestamp: 1234568,
+                                                              amount: 1.0 })
+                            .await;
+        assert_eq!(result, Err(ExchangeError::Hourglass("Fee rates for Perpetual not found".to_string())));
+        while let Ok(event) = account_event_rx.try_recv() {
+            assert!(!matches!(event.kind, AccountEventKind::Trade(_)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_provider_replaces_fee_schedule()
+    {
+        let mut fees = Vec::new();
+        for tag in [None, Some("referral".to_string())] {
+            let mut account = create_test_account().await;
+            account.commission_provider = Arc::new(RebateWithReferralDiscount { maker_rebate: 0.0002,
+                                                                                taker_fee_per_contract: 1.5 });
+            // 不再依赖 fees_book
+            account.config.fees_book.clear();
+            fees.push(fill_resting_buy(&mut account, tag).await);
+        }
+
+        // Maker 成交得到返佣，推荐折扣作用于返佣金额
+        assert!((fees[0] + 16400.0 * 0.25 * 0.0002).abs() < 1e-9);
+        assert!((fees[1] - fees[0] * 0.8).abs() < 1e-9);
+    }
+}
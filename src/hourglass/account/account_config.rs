@@ -1,10 +1,18 @@
 use crate::{
     common::{
         account_positions::{PositionDirectionMode, PositionMarginMode},
-        instrument::kind::InstrumentKind,
+        datafeed::price_jitter::PriceJitterConfig,
+        instrument::{alias::InstrumentAliasRegistry, kind::InstrumentKind, Instrument},
+        order::identification::machine_id::generate_machine_id,
+        token::Token,
+        Side,
     },
     error::ExchangeError,
-    hourglass::utils::config_parser::read_config_file,
+    hourglass::{
+        account::account_latency::{AccountLatency, FluctuationMode},
+        matching_engine::ProRataMatcher,
+        utils::config_parser::read_config_file,
+    },
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -23,6 +31,68 @@ pub struct AccountConfig
     pub max_price_deviation: f64,                              // 最大价格偏差，用于限制订单价格与市场价格的偏离范围
     pub lazy_account_positions: bool,                          // 是否惰性更新以节约性能
     pub liquidation_threshold: f64,                            // 平仓的门槛，通常为一个0.9~1的系数
+    #[serde(default)]
+    pub fill_price_policy: FillPricePolicy, // 挂单被外部 MarketTrade 触发成交时采用的成交价规则
+    #[serde(default)]
+    pub warmup_until_ts: Option<i64>, // 预热截止时间戳，在此之前仅推送行情，拒绝一切开单请求
+    #[serde(default)]
+    pub order_to_trade_limit: Option<OrderToTradeLimit>, // 报单成交比限制，未配置时只统计不限制
+    #[serde(default)]
+    pub max_open_orders_per_instrument: Option<usize>, // 每个金融工具允许的最大挂单数量，未配置时不限制
+    #[serde(default)]
+    pub instrument_aliases: InstrumentAliasRegistry, // 金融工具别名表，把不同数据源的符号写法统一为规范的 Instrument
+    #[serde(default)]
+    pub leveraged_token_daily_fee_rate: Option<f64>, // 杠杆代币每日管理费率，未配置时不计提
+    #[serde(default)]
+    pub rounding: RoundingConfig, // 手续费、保证金与清算价格的舍入方式，默认不舍入
+    #[serde(default)]
+    pub invariant_check: InvariantCheckMode, // 每个事件处理后是否检查账户不变量，默认关闭
+    #[serde(default)]
+    pub maker_fill_trigger: MakerFillTrigger, // 挂单在外部成交价恰好等于挂单价时是否成交，默认要求穿价
+    #[serde(default)]
+    pub contract_multipliers: Vec<ContractMultiplier>, // 各金融工具的合约乘数，未配置的金融工具乘数为 1.0
+    #[serde(default)]
+    pub machine_id: Option<u64>, // 固定的机器ID，使订单ID在不同机器与CI上可复现；未配置时由MAC地址自动推导
+    #[serde(default)]
+    pub seed: Option<u64>, // 随机数种子，配置后延迟抽样与订单ID的随机部分都由它决定，相同种子与相同行情得到相同的账户事件序列
+    #[serde(default)]
+    pub cost_basis_method: CostBasisMethod, // 现货卖出时计算已实现盈亏所用的成本法，默认平均成本法
+    #[serde(default)]
+    pub cancel_latency_ms: Option<i64>, // 撤单生效延迟（毫秒），延迟期间到来的成交仍可成交该挂单；未配置时撤单立即生效
+    #[serde(default)]
+    pub synthetic_depth: Option<SyntheticDepthConfig>, // 没有深度数据时围绕最新成交价铺设的合成流动性，未配置时不启用
+    #[serde(default)]
+    pub own_fills_on_tape: bool, // 本账户的成交是否作为合成成交记录推送到行情流，默认关闭，见 `HourglassAccount::take_own_fill_prints`
+    #[serde(default)]
+    pub margin_update: Option<MarginUpdatePolicy>, // 发送 `AccountEventKind::MarginUpdate` 的节奏，未配置时不发送
+    #[serde(default)]
+    pub matching_algorithms: Vec<InstrumentMatchingAlgorithm>, // 各金融工具的撮合算法，未配置的金融工具按价格优先、时间优先撮合
+    #[serde(default)]
+    pub initial_margin_rates: Vec<InitialMarginRate>, // 各金融工具的初始保证金率，未配置的金融工具按 `1 / global_leverage_rate` 计算
+    #[serde(default)]
+    pub net_mode_opposite_behavior: NetModeOppositeBehavior, // Net 模式下与持仓方向相反且非 reduce_only 的新订单如何处理，默认拒绝
+    #[serde(default)]
+    pub withdrawal_rules: Vec<WithdrawalRule>, // 各币种的提现手续费与最小提现数量，未配置的币种不收费也不设下限
+    #[serde(default)]
+    pub stale_order_policy: Option<StaleOrderPolicy>, // 自动撤销久未成交且远离市场的挂单，未配置时不启用
+    #[serde(default)]
+    pub unsized_trade_handling: UnsizedTradeHandling, // 外部成交缺少成交量（为 0 或无效）时如何撮合挂单，默认只触价不成交
+    #[serde(default)]
+    pub spread_leg_risk: SpreadLegRisk, // 价差订单只有一腿有足够流动性时是否允许单腿成交，默认拒绝
+    #[serde(default)]
+    pub slippage_model: SlippageModel, // 主动成交的挂单被外部成交撮合时的滑点模型，默认不产生滑点
+    #[serde(default)]
+    pub funding_interval_ms: Option<i64>, // 永续合约资金费的结算周期（毫秒），例如 8 小时为 28_800_000，未配置时不自动结算
+    #[serde(default)]
+    pub volume_tiers: Vec<VolumeTier>, // 按 30 天滚动成交额分级的费率，门槛升序排列，为空时按 `fees_book` 收取
+    #[serde(default)]
+    pub maintenance_margin_rate: Option<f64>, // 维持保证金率（占名义价值的比例），配置后全仓开单会检查开单后的保证金率；未配置时按 `liquidation_threshold` 推算且不检查
+    #[serde(default)]
+    pub instrument_filters: Vec<InstrumentFilters>, // 各金融工具的最小名义价值、数量步长与价格步长，未配置的金融工具不做限制
+    #[serde(default)]
+    pub filter_violation: FilterViolationHandling, // 订单数量或价格不是步长整数倍时的处理方式，默认拒绝
+    #[serde(default)]
+    pub trade_history_limit: Option<usize>, // 每个金融工具保留的最近成交数量，超出时丢弃最早的成交，未配置时全部保留
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -31,11 +101,589 @@ pub enum HourglassMode
     Backtest,
     Online,
 }
+
+/// 挂单被外部 [`MarketTrade`](crate::hourglass::clickhouse_api::datatype::clickhouse_trade_data::MarketTrade) 触发成交时的成交价规则。
+///
+/// - `RestingLimit`: 按挂单自身的限价成交，被动成交时最贴近真实撮合，为默认值。
+/// - `TradePrice`: 按触发成交的市场成交价成交。
+/// - `Midpoint`: 取挂单限价与市场成交价的中间价成交。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum FillPricePolicy
+{
+    #[default]
+    RestingLimit,
+    TradePrice,
+    Midpoint,
+}
+
+impl FillPricePolicy
+{
+    /// 根据挂单价格与市场成交价计算实际成交价。
+    pub fn fill_price(&self, resting_price: f64, trade_price: f64) -> f64
+    {
+        match self {
+            | FillPricePolicy::RestingLimit => resting_price,
+            | FillPricePolicy::TradePrice => trade_price,
+            | FillPricePolicy::Midpoint => (resting_price + trade_price) / 2.0,
+        }
+    }
+}
+
+/// 主动成交（市价单与交叉的限价单）相对于撮合成交价的滑点模型，用于模拟市场冲击。
+///
+/// 滑点总是朝不利方向：买单的成交价上移，卖单的成交价下移。只作用于被外部成交撮合的订单；
+/// 用本地深度订单簿立即成交的主动单按档位逐级成交，已经体现了市场冲击，不再叠加滑点。
+///
+/// - `None`: 不产生滑点，为默认值。
+/// - `FixedBps(bps)`: 每笔成交固定滑点 `bps` 个基点（1 基点为 0.01%）。
+/// - `VolumeProportional { bps_per_unit }`: 滑点与本次成交数量成正比，每单位数量滑点 `bps_per_unit` 个基点。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum SlippageModel
+{
+    #[default]
+    None,
+    FixedBps(f64),
+    VolumeProportional
+    {
+        bps_per_unit: f64,
+    },
+}
+
+impl SlippageModel
+{
+    /// 计算 `side` 方向成交 `quantity` 时加上滑点后的成交价。
+    pub fn slipped_price(&self, side: Side, price: f64, quantity: f64) -> f64
+    {
+        let bps = match self {
+            | SlippageModel::None => return price,
+            | SlippageModel::FixedBps(bps) => *bps,
+            | SlippageModel::VolumeProportional { bps_per_unit } => bps_per_unit * quantity,
+        };
+        match side {
+            | Side::Buy => price * (1.0 + bps / 10_000.0),
+            | Side::Sell => price * (1.0 - bps / 10_000.0),
+        }
+    }
+}
+
+/// 金融工具的合约乘数：1 张合约对应 `multiplier` 个基础货币单位。
+///
+/// 订单与仓位的数量以合约张数计，名义价值、盈亏、保证金与手续费都按 `price * size * multiplier` 计算。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ContractMultiplier
+{
+    pub instrument: Instrument,
+    pub multiplier: f64,
+}
+
+/// 金融工具的初始保证金率：开仓挂单时按名义价值的 `rate` 倍锁定保证金，覆盖由账户杠杆率推导的 `1 / global_leverage_rate`。
+///
+/// 用于模拟保证金要求比账户杠杆率更严格（或更宽松）的金融工具。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct InitialMarginRate
+{
+    pub instrument: Instrument,
+    pub rate: f64,
+}
+
+/// 币种的提现规则：每次提现额外扣除固定的网络手续费 `withdrawal_fee`，提现数量不得低于 `min_withdrawal`。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct WithdrawalRule
+{
+    pub token: Token,
+    pub withdrawal_fee: f64,
+    pub min_withdrawal: f64,
+}
+
+/// 金融工具的下单过滤规则：订单名义价值不得低于 `min_notional`，数量必须是 `lot_size` 的整数倍，限价必须是 `tick_size` 的整数倍。
+///
+/// 步长为 0 时不限制对应的数量或价格。不对齐时按 [`FilterViolationHandling`] 拒绝或取整。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct InstrumentFilters
+{
+    pub instrument: Instrument,
+    pub min_notional: f64,
+    pub lot_size: f64,
+    pub tick_size: f64,
+}
+
+impl InstrumentFilters
+{
+    /// 按 `lot_size` 对齐订单数量，取整时向下取整，避免超出可用余额。
+    pub fn align_size(&self, size: f64, handling: FilterViolationHandling) -> Result<f64, ExchangeError>
+    {
+        match align_to_step(size, self.lot_size, handling, false) {
+            | Some(aligned) if aligned > 0.0 => Ok(aligned),
+            | _ => Err(ExchangeError::SizeNotAligned { size, lot_size: self.lot_size }),
+        }
+    }
+
+    /// 按 `tick_size` 对齐限价，取整时买单向下、卖单向上取整，不会让订单变得更激进。
+    pub fn align_price(&self, side: Side, price: f64, handling: FilterViolationHandling) -> Result<f64, ExchangeError>
+    {
+        match align_to_step(price, self.tick_size, handling, side == Side::Sell) {
+            | Some(aligned) if aligned > 0.0 => Ok(aligned),
+            | _ => Err(ExchangeError::PriceNotAligned { price, tick_size: self.tick_size }),
+        }
+    }
+}
+
+/// 把 `value` 对齐到 `step` 的整数倍：已对齐（容忍浮点误差）或 `step` 为 0 时原样返回，否则按 `handling` 拒绝（返回 `None`）或取整。
+fn align_to_step(value: f64, step: f64, handling: FilterViolationHandling, round_up: bool) -> Option<f64>
+{
+    if step <= 0.0 {
+        return Some(value);
+    }
+    let steps = value / step;
+    if (steps - steps.round()).abs() <= 1e-9 * steps.abs().max(1.0) {
+        return Some(steps.round() * step);
+    }
+    match handling {
+        | FilterViolationHandling::Reject => None,
+        | FilterViolationHandling::Round if round_up => Some(steps.ceil() * step),
+        | FilterViolationHandling::Round => Some(steps.floor() * step),
+    }
+}
+
+/// 某个金融工具使用的内置撮合算法，见 [`MatchingAlgorithm`]。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct InstrumentMatchingAlgorithm
+{
+    pub instrument: Instrument,
+    pub algorithm: MatchingAlgorithm,
+}
+
+/// 挂单被外部成交触发后，外部成交量在多笔挂单之间的分配方式。
+///
+/// - `Fifo`: 价格优先、时间优先，同价位先到的挂单先成交，为默认值，见 [`FifoMatcher`](crate::hourglass::matching_engine::FifoMatcher)。
+/// - `ProRata`: 价格优先，同价位的挂单按剩余数量比例分配成交量，取整、最小分配量与余量规则见 [`ProRataMatcher`]。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum MatchingAlgorithm
+{
+    #[default]
+    Fifo,
+    ProRata(ProRataMatcher),
+}
+
+impl AccountConfig
+{
+    /// 返回金融工具的撮合算法，未配置时为 [`MatchingAlgorithm::Fifo`]。
+    pub fn matching_algorithm(&self, instrument: &Instrument) -> MatchingAlgorithm
+    {
+        self.matching_algorithms
+            .iter()
+            .find(|entry| &entry.instrument == instrument)
+            .map(|entry| entry.algorithm)
+            .unwrap_or_default()
+    }
+
+    /// 返回金融工具的初始保证金率，未配置时由账户杠杆率推导为 `1 / global_leverage_rate`。
+    pub fn initial_margin_rate(&self, instrument: &Instrument) -> f64
+    {
+        self.initial_margin_rates
+            .iter()
+            .find(|entry| &entry.instrument == instrument)
+            .map(|entry| entry.rate)
+            .unwrap_or(1.0 / self.global_leverage_rate)
+    }
+
+    /// 返回金融工具的下单过滤规则，未配置时为 `None`。
+    pub fn instrument_filters(&self, instrument: &Instrument) -> Option<&InstrumentFilters>
+    {
+        self.instrument_filters.iter().find(|entry| &entry.instrument == instrument)
+    }
+
+    /// 返回金融工具在 `instrument_filters` 中配置的最小名义价值，未配置时为 `None`。
+    pub fn min_notional(&self, instrument: &Instrument) -> Option<f64>
+    {
+        self.instrument_filters(instrument).map(|filters| filters.min_notional)
+    }
+
+    /// 返回币种的提现规则，未配置时为 `None`。
+    pub fn withdrawal_rule(&self, token: &Token) -> Option<&WithdrawalRule>
+    {
+        self.withdrawal_rules.iter().find(|rule| &rule.token == token)
+    }
+
+    /// 返回金融工具的合约乘数，未配置时为 1.0。
+    pub fn contract_multiplier(&self, instrument: &Instrument) -> f64
+    {
+        self.contract_multipliers
+            .iter()
+            .find(|entry| &entry.instrument == instrument)
+            .map(|entry| entry.multiplier)
+            .unwrap_or(1.0)
+    }
+
+    /// 返回账户使用的机器ID：配置了 `machine_id` 时直接使用，否则由本机MAC地址推导。
+    pub fn resolve_machine_id(&self) -> Result<u64, String>
+    {
+        match self.machine_id {
+            | Some(machine_id) => Ok(machine_id),
+            | None => generate_machine_id(),
+        }
+    }
+
+    /// 创建行情网络延迟的生成器，见 [`MarketEvent::with_network_latency`](crate::common::datafeed::market_event::MarketEvent::with_network_latency)。
+    ///
+    /// 配置了 `seed` 时由它派生随机数种子（与订单延迟使用不同的序列），同样的种子与行情得到同样的接收时间；否则使用随机种子。
+    pub fn network_latency(&self, mode: FluctuationMode, maximum: i64, minimum: i64) -> AccountLatency
+    {
+        let seed = self.seed.map_or_else(rand::random, |seed| seed.wrapping_add(1));
+        AccountLatency::new(mode, maximum, minimum, seed)
+    }
+
+    /// 创建启用状态的价格扰动配置，种子同样由账户 `seed` 派生（与订单延迟、行情延迟使用不同的序列），
+    /// 使扰动与账户的其它随机过程一起可复现；未配置 `seed` 时使用随机种子。
+    pub fn price_jitter(&self, band_bps: f64) -> PriceJitterConfig
+    {
+        let seed = self.seed.map_or_else(rand::random, |seed| seed.wrapping_add(2));
+        PriceJitterConfig { enabled: true, band_bps, seed }
+    }
+
+    /// 返回合约类型的手续费计费口径，未配置手续费时按名义价值比例收取。
+    pub fn fee_basis(&self, instrument_kind: &InstrumentKind) -> FeeBasis
+    {
+        self.fees_book.get(instrument_kind).map(|rates| rates.fee_basis).unwrap_or_default()
+    }
+}
+
+/// `PositionDirectionMode::Net` 下，与现有持仓方向相反且未标记 `reduce_only` 的新订单的处理方式。
+///
+/// - `Reject`: 以 `ExchangeError::InvalidDirection` 拒绝该订单，需要减仓时必须显式使用 `reduce_only`，为默认值。
+/// - `Reduce`: 接受该订单，成交时与现有持仓轧差：先减少持仓，超出持仓的部分反向开仓，不会同时持有多空两个方向的仓位。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum NetModeOppositeBehavior
+{
+    #[default]
+    Reject,
+    Reduce,
+}
+
+/// 外部成交缺少成交量时（`amount` 为 0 或无效，例如由不带成交量的 `WsTrade` 转换而来）挂单的撮合方式。
+///
+/// 有成交量的外部成交按其成交量分配给挂单，被成交的挂单数量之和不超过该成交量。缺少成交量时无从得知实际成交了多少：
+///
+/// - `TouchOnly`: 只视为价格触及，更新最新价与订单簿，但不消耗任何数量，挂单不会成交，为默认值。偏保守，挂单成交率会被低估。
+/// - `FillTouched`: 视为流动性无限，所有满足成交价格条件的挂单全部成交。偏乐观，挂单成交率会被高估。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum UnsizedTradeHandling
+{
+    #[default]
+    TouchOnly,
+    FillTouched,
+}
+
+/// 订单数量或限价不是 [`InstrumentFilters`] 步长整数倍时的处理方式。
+///
+/// - `Reject`: 拒绝订单，返回 `SizeNotAligned` 或 `PriceNotAligned`，为默认值。
+/// - `Round`: 数量向下取整到 `lot_size`；限价向不利于成交的方向取整到 `tick_size`（买单向下、卖单向上）。取整后为 0 时仍然拒绝。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum FilterViolationHandling
+{
+    #[default]
+    Reject,
+    Round,
+}
+
+/// 价差订单（[`SpreadOrder`](crate::hourglass::account::account_spread::SpreadOrder)）某一腿深度不足以完全成交时的处理方式。
+///
+/// - `Reject`: 拒绝整笔价差订单，两腿都不成交，避免留下单腿敞口，为默认值。
+/// - `AllowLegging`: 允许承担单腿风险，各腿按各自可成交的数量成交，其中一腿可能完全不成交。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum SpreadLegRisk
+{
+    #[default]
+    Reject,
+    AllowLegging,
+}
+
+/// 挂单被外部 [`MarketTrade`](crate::hourglass::clickhouse_api::datatype::clickhouse_trade_data::MarketTrade) 触发成交的价格条件。
+///
+/// - `OnTouch`: 外部成交价触及挂单价即成交（买单 `trade_price <= P`，卖单 `trade_price >= P`），偏乐观。
+/// - `OnTradeThrough`: 外部成交价必须穿过挂单价才成交（买单 `trade_price < P`，卖单 `trade_price > P`），
+///   恰好在挂单价的成交视为被排在前面的挂单吃掉，偏保守，为默认值。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum MakerFillTrigger
+{
+    OnTouch,
+    #[default]
+    OnTradeThrough,
+}
+
+impl MakerFillTrigger
+{
+    /// 价格为 `resting_price` 的挂单在外部成交价为 `trade_price` 时能否成交。
+    pub fn is_triggered(&self, side: Side, resting_price: f64, trade_price: f64) -> bool
+    {
+        match (self, side) {
+            | (MakerFillTrigger::OnTouch, Side::Buy) => trade_price <= resting_price,
+            | (MakerFillTrigger::OnTouch, Side::Sell) => trade_price >= resting_price,
+            | (MakerFillTrigger::OnTradeThrough, Side::Buy) => trade_price < resting_price,
+            | (MakerFillTrigger::OnTradeThrough, Side::Sell) => trade_price > resting_price,
+        }
+    }
+}
+
+/// 只有成交数据时用于冷启动订单簿的合成流动性。
+///
+/// 每笔外部成交后围绕成交价在两侧各铺设 `levels` 档、每档 `level_size` 的虚拟挂单：
+/// 最优档距成交价 `half_spread_bps`，之后每档再远离 `level_step_bps`（均以基点计）。
+/// 由合成流动性产生的成交会通过 `AccountEventKind::SyntheticFills` 标记，便于在分析结果时打折扣。
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SyntheticDepthConfig
+{
+    pub half_spread_bps: f64,
+    pub level_step_bps: f64,
+    pub levels: usize,
+    pub level_size: f64,
+}
+
+impl SyntheticDepthConfig
+{
+    /// 围绕 `price` 生成买方的 `(价格, 数量)` 档位，由高到低排列。
+    pub fn bid_levels(&self, price: f64) -> Vec<(f64, f64)>
+    {
+        (0..self.levels).map(|level| (price * (1.0 - self.offset(level)), self.level_size)).collect()
+    }
+
+    /// 围绕 `price` 生成卖方的 `(价格, 数量)` 档位，由低到高排列。
+    pub fn ask_levels(&self, price: f64) -> Vec<(f64, f64)>
+    {
+        (0..self.levels).map(|level| (price * (1.0 + self.offset(level)), self.level_size)).collect()
+    }
+
+    /// 第 `level` 档相对成交价的偏移比例。
+    fn offset(&self, level: usize) -> f64
+    {
+        (self.half_spread_bps + self.level_step_bps * level as f64) / 10_000.0
+    }
+}
+
+/// 每个行情事件处理后检查是否需要发送 `AccountEventKind::MarginUpdate`。
+///
+/// 首次检查时总会发送一次；之后距上次发送已满 `interval_ms` 毫秒，或保证金率相对上次发送的变化不小于
+/// `min_ratio_change` 时再次发送。两者都未配置时只发送首次快照。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct MarginUpdatePolicy
+{
+    #[serde(default)]
+    pub interval_ms: Option<i64>,
+    #[serde(default)]
+    pub min_ratio_change: Option<f64>,
+}
+
+impl MarginUpdatePolicy
+{
+    /// 根据上次发送时的时间戳与保证金率判断当前是否需要发送。
+    pub fn is_due(&self, last: Option<(i64, f64)>, now: i64, margin_ratio: f64) -> bool
+    {
+        match last {
+            | None => true,
+            | Some((last_ts, last_ratio)) => {
+                self.interval_ms.is_some_and(|interval_ms| now - last_ts >= interval_ms) || self.min_ratio_change.is_some_and(|min_change| (margin_ratio - last_ratio).abs() >= min_change)
+            }
+        }
+    }
+}
+
+/// 自动清理明显无法成交的挂单：挂单时长超过 `max_age_ms` 毫秒，且挂单价偏离该金融工具最新成交价超过 `max_distance_pct`%，
+/// 两个条件同时满足时撤销，模拟交易所清理远离市场的挂单。
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct StaleOrderPolicy
+{
+    pub max_age_ms: i64,       // 挂单时长上限，按交易所时间戳与订单时间戳之差计算
+    pub max_distance_pct: f64, // 挂单价与最新成交价的偏离上限，5.0 表示 5%
+}
+
+impl StaleOrderPolicy
+{
+    /// 判断一笔挂单在 `now` 时是否应被清理。
+    pub fn is_stale(&self, order_timestamp: i64, order_price: f64, now: i64, last_price: f64) -> bool
+    {
+        if last_price <= 0.0 {
+            return false;
+        }
+        let distance_pct = (order_price - last_price).abs() / last_price * 100.0;
+        now - order_timestamp > self.max_age_ms && distance_pct > self.max_distance_pct
+    }
+}
+
+/// 现货卖出时计算已实现盈亏所用的成本法。
+///
+/// - `Fifo`: 按买入批次先进先出，卖出先消耗最早的批次，适合按批次核算的税务报表。
+/// - `Average`: 所有持仓按加权平均成本计算，为默认值。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum CostBasisMethod
+{
+    Fifo,
+    #[default]
+    Average,
+}
+
+/// 手续费、保证金与清算价格计算时采用的舍入方式，舍入精度由 [`RoundingConfig::decimals`] 决定。
+///
+/// 各模式对三类计算的影响：
+///
+/// | 模式            | 手续费     | 占用保证金 | 释放保证金 | 清算价格                         |
+/// |-----------------|------------|------------|------------|----------------------------------|
+/// | `Exact`         | 不舍入     | 不舍入     | 不舍入     | 不舍入                           |
+/// | `HalfEven`      | 银行家舍入 | 银行家舍入 | 银行家舍入 | 银行家舍入                       |
+/// | `HalfUp`        | 四舍五入   | 四舍五入   | 四舍五入   | 四舍五入                         |
+/// | `AgainstTrader` | 向上取整   | 向上取整   | 向下取整   | 多头向上、空头向下（更早触发强平） |
+///
+/// - 手续费：撮合生成 `ClientTrade` 后的 `fees`，余额变化随之使用舍入后的手续费。
+/// - 占用保证金：永续合约开仓、加仓时按成交计算的全仓保证金增量与逐仓保证金。
+/// - 释放保证金：永续合约减仓、平仓与强平时按成交计算的全仓保证金减量与逐仓保证金减量。
+/// - 清算价格：永续合约开仓与加仓时重新计算的 `liquidation_price`。
+///
+/// `AgainstTrader` 对应大多数交易所的惯例：舍入方向总是对交易者不利。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum RoundingMode
+{
+    #[default]
+    Exact,
+    HalfEven,
+    HalfUp,
+    AgainstTrader,
+}
+
+/// 舍入配置：舍入方式与保留的小数位数。
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RoundingConfig
+{
+    pub mode: RoundingMode,
+    pub decimals: u32,
+}
+
+impl Default for RoundingConfig
+{
+    fn default() -> Self
+    {
+        Self { mode: RoundingMode::Exact,
+               decimals: 8 }
+    }
+}
+
+impl RoundingConfig
+{
+    /// 浮点误差容忍度，避免 `0.1 + 0.2` 之类的表示误差被向上取整放大。
+    const EPSILON: f64 = 1e-9;
+
+    /// 舍入手续费，`AgainstTrader` 时向上取整。
+    pub fn round_fee(&self, fee: f64) -> f64
+    {
+        self.round(fee, true)
+    }
+
+    /// 舍入开仓、加仓占用的保证金，`AgainstTrader` 时向上取整。
+    pub fn round_margin(&self, margin: f64) -> f64
+    {
+        self.round(margin, true)
+    }
+
+    /// 舍入减仓、平仓释放的保证金，`AgainstTrader` 时向下取整，交易者取回的保证金不会多于占用的部分。
+    pub fn round_margin_release(&self, margin: f64) -> f64
+    {
+        self.round(margin, false)
+    }
+
+    /// 舍入清算价格，`AgainstTrader` 时多头向上、空头向下取整。
+    pub fn round_liquidation_price(&self, price: f64, side: Side) -> f64
+    {
+        self.round(price, side == Side::Buy)
+    }
+
+    fn round(&self, value: f64, against_trader_up: bool) -> f64
+    {
+        let factor = 10f64.powi(self.decimals as i32);
+        let scaled = value * factor;
+        let rounded = match self.mode {
+            | RoundingMode::Exact => return value,
+            | RoundingMode::HalfUp => scaled.round(),
+            | RoundingMode::HalfEven => {
+                let floor = scaled.floor();
+                let diff = scaled - floor;
+                if (diff - 0.5).abs() < Self::EPSILON {
+                    if floor % 2.0 == 0.0 {
+                        floor
+                    }
+                    else {
+                        floor + 1.0
+                    }
+                }
+                else {
+                    scaled.round()
+                }
+            }
+            | RoundingMode::AgainstTrader => {
+                if against_trader_up {
+                    (scaled - Self::EPSILON).ceil()
+                }
+                else {
+                    (scaled + Self::EPSILON).floor()
+                }
+            }
+        };
+        rounded / factor
+    }
+}
+
+/// 账户不变量检查模式，用于调试记账错误，详见 `HourglassAccount::check_invariants`。
+///
+/// - `Off`: 不检查，生产环境的默认值，没有额外开销。
+/// - `Log`: 每个事件处理后检查，发现违反时记录事件与违反项。
+/// - `Panic`: 同 `Log`，记录后立即 panic，便于在测试中尽早暴露问题。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum InvariantCheckMode
+{
+    #[default]
+    Off,
+    Log,
+    Panic,
+}
+
+/// 报单成交比限制：在 `window_ms` 毫秒窗口内，(报单数 + 撤单数) / 成交数 超过 `max_ratio` 时拒绝新的开单请求。
+///
+/// 报单数只统计通过校验与余额检查、被交易所接受的开单请求，被拒绝的请求不计入。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct OrderToTradeLimit
+{
+    pub window_ms: i64,
+    pub max_ratio: f64,
+}
+
+/// 手续费的计费口径。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum FeeBasis
+{
+    /// 按成交名义价值的比例收取，比例为 `maker_fees` / `taker_fees`。
+    #[default]
+    PercentNotional,
+    /// 按成交合约张数收取固定金额（以报价货币计），与成交价格和 maker/taker 角色无关，常见于股指和商品期货。
+    PerContract(f64),
+}
+
+impl FeeBasis
+{
+    /// 计算一笔成交的手续费。`percent_fee` 为按比例口径算出的 `rate * price * size`，
+    /// 按比例收取时再乘以合约乘数折算为名义价值；按张收取时只与成交张数 `size` 有关。
+    pub fn fee(&self, percent_fee: f64, size: f64, contract_multiplier: f64) -> f64
+    {
+        match self {
+            | FeeBasis::PercentNotional => percent_fee * contract_multiplier,
+            | FeeBasis::PerContract(fee_per_contract) => fee_per_contract * size,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct CommissionRates
 {
     pub maker_fees: f64,
     pub taker_fees: f64,
+    #[serde(default)]
+    pub fee_basis: FeeBasis,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -43,6 +691,7 @@ pub struct CommissionRatesBuilder
 {
     pub maker_fees: Option<f64>,
     pub taker_fees: Option<f64>,
+    pub fee_basis: Option<FeeBasis>,
 }
 
 impl CommissionRates
@@ -65,7 +714,9 @@ impl CommissionRatesBuilder
 {
     pub fn new() -> Self
     {
-        Self { maker_fees: None, taker_fees: None }
+        Self { maker_fees: None,
+               taker_fees: None,
+               fee_basis: None }
     }
 
     pub fn maker(mut self, rate: f64) -> Self
@@ -80,10 +731,17 @@ impl CommissionRatesBuilder
         self
     }
 
+    pub fn fee_basis(mut self, fee_basis: FeeBasis) -> Self
+    {
+        self.fee_basis = Some(fee_basis);
+        self
+    }
+
     pub fn build(self) -> Result<CommissionRates, &'static str>
     {
         Ok(CommissionRates { maker_fees: self.maker_fees.ok_or("Spot maker rate is missing")?,
-                             taker_fees: self.taker_fees.ok_or("Spot taker rate is missing")? })
+                             taker_fees: self.taker_fees.ok_or("Spot taker rate is missing")?,
+                             fee_basis: self.fee_basis.unwrap_or_default() })
     }
 }
 
@@ -145,6 +803,35 @@ pub enum CommissionLevel
     Lv5,
 }
 
+impl CommissionLevel
+{
+    /// `volume_tiers` 中第 `index` 档（从 0 开始）对应的手续费等级。
+    pub fn from_tier_index(index: usize) -> Self
+    {
+        match index {
+            | 0 => CommissionLevel::Lv1,
+            | 1 => CommissionLevel::Lv2,
+            | 2 => CommissionLevel::Lv3,
+            | 3 => CommissionLevel::Lv4,
+            | _ => CommissionLevel::Lv5,
+        }
+    }
+}
+
+/// 阶梯费率中的一档：某类金融工具 30 天滚动成交额（名义价值）达到 `threshold_notional` 时适用的 Maker/Taker 费率。
+///
+/// 依次对应 `CommissionLevel::Lv1` 到 `Lv5`，计费口径沿用 `fees_book` 中该类金融工具的 `fee_basis`。
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct VolumeTier
+{
+    pub threshold_notional: f64,
+    pub maker: f64,
+    pub taker: f64,
+}
+
+/// 阶梯费率的最大档数，与 [`CommissionLevel`] 的等级数一致。
+pub const MAX_VOLUME_TIERS: usize = 5;
+
 pub struct AccountConfigBuilder
 {
     margin_mode: Option<MarginMode>,
@@ -157,6 +844,37 @@ pub struct AccountConfigBuilder
     max_price_deviation: Option<f64>,
     lazy_account_positions: Option<bool>,
     liquidation_threshold: Option<f64>,
+    fill_price_policy: Option<FillPricePolicy>,
+    warmup_until_ts: Option<i64>,
+    order_to_trade_limit: Option<OrderToTradeLimit>,
+    max_open_orders_per_instrument: Option<usize>,
+    instrument_aliases: Option<InstrumentAliasRegistry>,
+    leveraged_token_daily_fee_rate: Option<f64>,
+    rounding: Option<RoundingConfig>,
+    invariant_check: Option<InvariantCheckMode>,
+    maker_fill_trigger: Option<MakerFillTrigger>,
+    contract_multipliers: Vec<ContractMultiplier>,
+    machine_id: Option<u64>,
+    seed: Option<u64>,
+    cost_basis_method: Option<CostBasisMethod>,
+    cancel_latency_ms: Option<i64>,
+    synthetic_depth: Option<SyntheticDepthConfig>,
+    own_fills_on_tape: Option<bool>,
+    margin_update: Option<MarginUpdatePolicy>,
+    matching_algorithms: Vec<InstrumentMatchingAlgorithm>,
+    initial_margin_rates: Vec<InitialMarginRate>,
+    net_mode_opposite_behavior: Option<NetModeOppositeBehavior>,
+    withdrawal_rules: Vec<WithdrawalRule>,
+    stale_order_policy: Option<StaleOrderPolicy>,
+    unsized_trade_handling: Option<UnsizedTradeHandling>,
+    spread_leg_risk: Option<SpreadLegRisk>,
+    slippage_model: Option<SlippageModel>,
+    funding_interval_ms: Option<i64>,
+    volume_tiers: Vec<VolumeTier>,
+    maintenance_margin_rate: Option<f64>,
+    instrument_filters: Vec<InstrumentFilters>,
+    filter_violation: Option<FilterViolationHandling>,
+    trade_history_limit: Option<usize>,
 }
 
 impl Default for AccountConfigBuilder
@@ -180,7 +898,38 @@ impl AccountConfigBuilder
                execution_mode: None,
                max_price_deviation: None,
                lazy_account_positions: None,
-               liquidation_threshold: None }
+               liquidation_threshold: None,
+               fill_price_policy: None,
+               warmup_until_ts: None,
+               order_to_trade_limit: None,
+               max_open_orders_per_instrument: None,
+               instrument_aliases: None,
+               leveraged_token_daily_fee_rate: None,
+               rounding: None,
+               invariant_check: None,
+               maker_fill_trigger: None,
+               contract_multipliers: Vec::new(),
+               machine_id: None,
+               seed: None,
+               cost_basis_method: None,
+               cancel_latency_ms: None,
+               synthetic_depth: None,
+               own_fills_on_tape: None,
+               margin_update: None,
+               matching_algorithms: Vec::new(),
+               initial_margin_rates: Vec::new(),
+               net_mode_opposite_behavior: None,
+               withdrawal_rules: Vec::new(),
+               stale_order_policy: None,
+               unsized_trade_handling: None,
+               spread_leg_risk: None,
+               slippage_model: None,
+               funding_interval_ms: None,
+               volume_tiers: Vec::new(),
+               maintenance_margin_rate: None,
+               instrument_filters: Vec::new(),
+               filter_violation: None,
+               trade_history_limit: None }
     }
 
     pub fn margin_mode(mut self, margin_mode: MarginMode) -> Self
@@ -260,6 +1009,233 @@ impl AccountConfigBuilder
         }
     }
 
+    pub fn fill_price_policy(mut self, fill_price_policy: FillPricePolicy) -> Self
+    {
+        self.fill_price_policy = Some(fill_price_policy);
+        self
+    }
+
+    pub fn warmup_until_ts(mut self, warmup_until_ts: i64) -> Self
+    {
+        self.warmup_until_ts = Some(warmup_until_ts);
+        self
+    }
+
+    pub fn order_to_trade_limit(mut self, order_to_trade_limit: OrderToTradeLimit) -> Self
+    {
+        self.order_to_trade_limit = Some(order_to_trade_limit);
+        self
+    }
+
+    pub fn max_open_orders_per_instrument(mut self, max_open_orders_per_instrument: usize) -> Self
+    {
+        self.max_open_orders_per_instrument = Some(max_open_orders_per_instrument);
+        self
+    }
+
+    pub fn instrument_aliases(mut self, instrument_aliases: InstrumentAliasRegistry) -> Self
+    {
+        self.instrument_aliases = Some(instrument_aliases);
+        self
+    }
+
+    pub fn leveraged_token_daily_fee_rate(mut self, leveraged_token_daily_fee_rate: f64) -> Self
+    {
+        self.leveraged_token_daily_fee_rate = Some(leveraged_token_daily_fee_rate);
+        self
+    }
+
+    pub fn rounding(mut self, rounding: RoundingConfig) -> Self
+    {
+        self.rounding = Some(rounding);
+        self
+    }
+
+    pub fn invariant_check(mut self, invariant_check: InvariantCheckMode) -> Self
+    {
+        self.invariant_check = Some(invariant_check);
+        self
+    }
+
+    pub fn maker_fill_trigger(mut self, maker_fill_trigger: MakerFillTrigger) -> Self
+    {
+        self.maker_fill_trigger = Some(maker_fill_trigger);
+        self
+    }
+
+    pub fn contract_multiplier(mut self, instrument: Instrument, multiplier: f64) -> Self
+    {
+        self.contract_multipliers.retain(|entry| entry.instrument != instrument);
+        self.contract_multipliers.push(ContractMultiplier { instrument, multiplier });
+        self
+    }
+
+    pub fn machine_id(mut self, machine_id: u64) -> Self
+    {
+        self.machine_id = Some(machine_id);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self
+    {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn cost_basis_method(mut self, cost_basis_method: CostBasisMethod) -> Self
+    {
+        self.cost_basis_method = Some(cost_basis_method);
+        self
+    }
+
+    pub fn cancel_latency_ms(mut self, cancel_latency_ms: i64) -> Self
+    {
+        self.cancel_latency_ms = Some(cancel_latency_ms);
+        self
+    }
+
+    pub fn synthetic_depth(mut self, synthetic_depth: SyntheticDepthConfig) -> Self
+    {
+        self.synthetic_depth = Some(synthetic_depth);
+        self
+    }
+
+    pub fn own_fills_on_tape(mut self, own_fills_on_tape: bool) -> Self
+    {
+        self.own_fills_on_tape = Some(own_fills_on_tape);
+        self
+    }
+
+    pub fn margin_update(mut self, margin_update: MarginUpdatePolicy) -> Self
+    {
+        self.margin_update = Some(margin_update);
+        self
+    }
+
+    pub fn matching_algorithm(mut self, instrument: Instrument, algorithm: MatchingAlgorithm) -> Self
+    {
+        self.matching_algorithms.retain(|entry| entry.instrument != instrument);
+        self.matching_algorithms.push(InstrumentMatchingAlgorithm { instrument, algorithm });
+        self
+    }
+
+    pub fn initial_margin_rate(mut self, instrument: Instrument, rate: f64) -> Result<Self, ExchangeError>
+    {
+        // 初始保证金率必须在 (0, 1] 之间
+        if rate > 0.0 && rate <= 1.0 {
+            self.initial_margin_rates.retain(|entry| entry.instrument != instrument);
+            self.initial_margin_rates.push(InitialMarginRate { instrument, rate });
+            Ok(self)
+        }
+        else {
+            Err(ExchangeError::Hourglass("Invalid initial margin rate".into()))
+        }
+    }
+
+    pub fn net_mode_opposite_behavior(mut self, net_mode_opposite_behavior: NetModeOppositeBehavior) -> Self
+    {
+        self.net_mode_opposite_behavior = Some(net_mode_opposite_behavior);
+        self
+    }
+
+    pub fn withdrawal_rule(mut self, token: Token, withdrawal_fee: f64, min_withdrawal: f64) -> Result<Self, ExchangeError>
+    {
+        // 手续费与最小提现数量都不能为负
+        if withdrawal_fee >= 0.0 && min_withdrawal >= 0.0 {
+            self.withdrawal_rules.retain(|rule| rule.token != token);
+            self.withdrawal_rules.push(WithdrawalRule { token,
+                                                        withdrawal_fee,
+                                                        min_withdrawal });
+            Ok(self)
+        }
+        else {
+            Err(ExchangeError::Hourglass("Invalid withdrawal rule".into()))
+        }
+    }
+
+    pub fn stale_order_policy(mut self, stale_order_policy: StaleOrderPolicy) -> Self
+    {
+        self.stale_order_policy = Some(stale_order_policy);
+        self
+    }
+
+    pub fn unsized_trade_handling(mut self, unsized_trade_handling: UnsizedTradeHandling) -> Self
+    {
+        self.unsized_trade_handling = Some(unsized_trade_handling);
+        self
+    }
+
+    pub fn spread_leg_risk(mut self, spread_leg_risk: SpreadLegRisk) -> Self
+    {
+        self.spread_leg_risk = Some(spread_leg_risk);
+        self
+    }
+
+    pub fn slippage_model(mut self, slippage_model: SlippageModel) -> Self
+    {
+        self.slippage_model = Some(slippage_model);
+        self
+    }
+
+    pub fn funding_interval_ms(mut self, funding_interval_ms: i64) -> Self
+    {
+        self.funding_interval_ms = Some(funding_interval_ms);
+        self
+    }
+
+    /// 设置阶梯费率，门槛必须为有限的非负数并严格递增，最多 [`MAX_VOLUME_TIERS`] 档。
+    pub fn volume_tiers(mut self, volume_tiers: Vec<VolumeTier>) -> Result<Self, ExchangeError>
+    {
+        if volume_tiers.len() > MAX_VOLUME_TIERS {
+            return Err(ExchangeError::Hourglass(format!("At most {} volume tiers are supported, got {}", MAX_VOLUME_TIERS, volume_tiers.len())));
+        }
+        let thresholds_valid = volume_tiers.iter().all(|tier| tier.threshold_notional.is_finite() && tier.threshold_notional >= 0.0)
+                               && volume_tiers.windows(2).all(|pair| pair[0].threshold_notional < pair[1].threshold_notional);
+        if !thresholds_valid {
+            return Err(ExchangeError::Hourglass("Volume tier thresholds must be non-negative and strictly increasing".into()));
+        }
+        self.volume_tiers = volume_tiers;
+        Ok(self)
+    }
+
+    /// 设置维持保证金率，必须在 (0, 1) 之间。
+    pub fn maintenance_margin_rate(mut self, maintenance_margin_rate: f64) -> Result<Self, ExchangeError>
+    {
+        if maintenance_margin_rate > 0.0 && maintenance_margin_rate < 1.0 {
+            self.maintenance_margin_rate = Some(maintenance_margin_rate);
+            Ok(self)
+        }
+        else {
+            Err(ExchangeError::Hourglass(format!("Maintenance margin rate must be within (0, 1), got {}", maintenance_margin_rate)))
+        }
+    }
+
+    /// 设置金融工具的下单过滤规则，最小名义价值与步长都必须是非负的有限数。
+    pub fn instrument_filters(mut self, filters: InstrumentFilters) -> Result<Self, ExchangeError>
+    {
+        if [filters.min_notional, filters.lot_size, filters.tick_size].iter().all(|value| value.is_finite() && *value >= 0.0) {
+            self.instrument_filters.retain(|entry| entry.instrument != filters.instrument);
+            self.instrument_filters.push(filters);
+            Ok(self)
+        }
+        else {
+            Err(ExchangeError::Hourglass(format!("Invalid instrument filters: {:?}", filters)))
+        }
+    }
+
+    pub fn filter_violation(mut self, filter_violation: FilterViolationHandling) -> Self
+    {
+        self.filter_violation = Some(filter_violation);
+        self
+    }
+
+    /// 设置每个金融工具保留的最近成交数量。
+    pub fn trade_history_limit(mut self, trade_history_limit: usize) -> Self
+    {
+        self.trade_history_limit = Some(trade_history_limit);
+        self
+    }
+
     pub fn initiate(self) -> Result<AccountConfig, &'static str>
     {
         Ok(AccountConfig { margin_mode: self.margin_mode.ok_or("margin_mode is required")?,
@@ -272,6 +1248,111 @@ impl AccountConfigBuilder
                            execution_mode: HourglassMode::Backtest,
                            max_price_deviation: self.max_price_deviation.ok_or("max price deviation is required")?,
                            lazy_account_positions: self.lazy_account_positions.ok_or("lazy_account_positions switch is required")?,
-                           liquidation_threshold: self.liquidation_threshold.ok_or("liquidation threshold is required")? })
+                           liquidation_threshold: self.liquidation_threshold.ok_or("liquidation threshold is required")?,
+                           fill_price_policy: self.fill_price_policy.unwrap_or_default(),
+                           warmup_until_ts: self.warmup_until_ts,
+                           order_to_trade_limit: self.order_to_trade_limit,
+                           max_open_orders_per_instrument: self.max_open_orders_per_instrument,
+                           instrument_aliases: self.instrument_aliases.unwrap_or_default(),
+                           leveraged_token_daily_fee_rate: self.leveraged_token_daily_fee_rate,
+                           rounding: self.rounding.unwrap_or_default(),
+                           invariant_check: self.invariant_check.unwrap_or_default(),
+                           maker_fill_trigger: self.maker_fill_trigger.unwrap_or_default(),
+                           contract_multipliers: self.contract_multipliers,
+                           machine_id: self.machine_id,
+                           seed: self.seed,
+                           cost_basis_method: self.cost_basis_method.unwrap_or_default(),
+                           cancel_latency_ms: self.cancel_latency_ms,
+                           synthetic_depth: self.synthetic_depth,
+                           own_fills_on_tape: self.own_fills_on_tape.unwrap_or(false),
+                           margin_update: self.margin_update,
+                           matching_algorithms: self.matching_algorithms,
+                           initial_margin_rates: self.initial_margin_rates,
+                           net_mode_opposite_behavior: self.net_mode_opposite_behavior.unwrap_or_default(),
+                           withdrawal_rules: self.withdrawal_rules,
+                           stale_order_policy: self.stale_order_policy,
+                           unsized_trade_handling: self.unsized_trade_handling.unwrap_or_default(),
+                           spread_leg_risk: self.spread_leg_risk.unwrap_or_default(),
+                           slippage_model: self.slippage_model.unwrap_or_default(),
+                           funding_interval_ms: self.funding_interval_ms,
+                           volume_tiers: self.volume_tiers,
+                           maintenance_margin_rate: self.maintenance_margin_rate,
+                           instrument_filters: self.instrument_filters,
+                           filter_violation: self.filter_violation.unwrap_or_default(),
+                           trade_history_limit: self.trade_history_limit })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn rounding(mode: RoundingMode) -> RoundingConfig
+    {
+        RoundingConfig { mode, decimals: 2 }
+    }
+
+    #[test]
+    fn test_price_jitter_seed_is_derived_from_account_seed()
+    {
+        let config = AccountConfig { seed: Some(7), ..crate::test_utils::create_test_account_configuration() };
+        let jitter = config.price_jitter(2.0);
+        assert_eq!(jitter, PriceJitterConfig { enabled: true, band_bps: 2.0, seed: 9 });
+        assert_eq!(config.price_jitter(2.0), jitter);
+    }
+
+    #[test]
+    fn test_exact_rounding_keeps_value()
+    {
+        assert_eq!(rounding(RoundingMode::Exact).round_fee(0.123456), 0.123456);
+    }
+
+    #[test]
+    fn test_half_even_differs_from_half_up_on_ties()
+    {
+        assert_eq!(rounding(RoundingMode::HalfEven).round_fee(0.125), 0.12);
+        assert_eq!(rounding(RoundingMode::HalfUp).round_fee(0.125), 0.13);
+        assert_eq!(rounding(RoundingMode::HalfEven).round_fee(0.135), 0.14);
+    }
+
+    #[test]
+    fn test_against_trader_rounds_charges_up_and_releases_down()
+    {
+        let config = rounding(RoundingMode::AgainstTrader);
+        assert_eq!(config.round_fee(0.121), 0.13);
+        assert_eq!(config.round_margin(10.001), 10.01);
+        assert_eq!(config.round_margin_release(10.009), 10.0);
+        // 返佣（负手续费）向零取整，返还的金额更少
+        assert_eq!(config.round_fee(-0.129), -0.12);
+        // 其他模式下占用与释放的舍入方式相同
+        assert_eq!(rounding(RoundingMode::HalfUp).round_margin_release(10.005), rounding(RoundingMode::HalfUp).round_margin(10.005));
+        // 浮点表示误差不应被向上取整放大
+        assert_eq!(config.round_fee(0.1 + 0.2), 0.3);
+    }
+
+    #[test]
+    fn test_against_trader_liquidation_price_depends_on_side()
+    {
+        let config = rounding(RoundingMode::AgainstTrader);
+        assert_eq!(config.round_liquidation_price(100.001, Side::Buy), 100.01);
+        assert_eq!(config.round_liquidation_price(100.009, Side::Sell), 100.0);
+    }
+
+    #[test]
+    fn test_slippage_model_moves_price_against_the_order()
+    {
+        assert_eq!(SlippageModel::None.slipped_price(Side::Buy, 100.0, 5.0), 100.0);
+
+        // 固定 50 个基点，与成交数量无关
+        let fixed = SlippageModel::FixedBps(50.0);
+        assert!((fixed.slipped_price(Side::Buy, 100.0, 1.0) - 100.5).abs() < 1e-9);
+        assert!((fixed.slipped_price(Side::Sell, 100.0, 3.0) - 99.5).abs() < 1e-9);
+
+        // 每单位数量 10 个基点，成交 3 个单位滑点 30 个基点
+        let proportional = SlippageModel::VolumeProportional { bps_per_unit: 10.0 };
+        assert!((proportional.slipped_price(Side::Buy, 100.0, 3.0) - 100.3).abs() < 1e-9);
+        assert!((proportional.slipped_price(Side::Sell, 100.0, 3.0) - 99.7).abs() < 1e-9);
+        assert!((proportional.slipped_price(Side::Sell, 100.0, 0.5) - 99.95).abs() < 1e-9);
     }
 }
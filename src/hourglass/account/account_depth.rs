@@ -0,0 +1,806 @@
+use crate::{
+    common::{
+        event::{AccountEvent, AccountEventKind},
+        instrument::Instrument,
+        order::{
+            order_instructions::OrderInstruction,
+            states::{
+                fills::{FullyFill, PartialFill},
+                open::Open,
+                request_open::RequestOpen,
+            },
+            Order, OrderRole,
+        },
+        trade::ClientTrade,
+        Side,
+    },
+    error::ExchangeError,
+    hourglass::{
+        account::{
+            account_handlers::{balance_handler::BalanceHandler, trade_handler::TradeHandler},
+            HourglassAccount,
+        },
+        clickhouse_api::datatype::{
+            clickhouse_trade_data::{parse_instrument_kind, MarketTrade},
+            depth_order_book::{DepthLevel, DepthOrderBook},
+            order_book_25::OrderBook25,
+        },
+        open_orders_book::OpenOrdersBook,
+    },
+    hourglass_log::warn,
+    Exchange,
+};
+use std::sync::atomic::Ordering;
+
+impl HourglassAccount
+{
+    /// 用外部回放的深度快照刷新本地深度订单簿，再用新的深度成交与之交叉的挂单，见 [`Self::fill_resting_orders_from_depth`]。
+    ///
+    /// 快照会整体覆盖本地档位，之前被本地市价单吃掉的流动性随之恢复。
+    pub async fn apply_depth_snapshot(&mut self, instrument: &Instrument, snapshot: &OrderBook25) -> Result<(), ExchangeError>
+    {
+        self.depth_order_books.lock().await.entry(instrument.clone()).or_default().apply_snapshot(snapshot);
+        self.fill_resting_orders_from_depth(instrument).await
+    }
+
+    /// 按快照的交易所标识与符号解析出规范的金融工具，规则与成交相同，见 [`Self::resolve_market_instrument`]。
+    pub fn resolve_depth_instrument(&self, snapshot: &OrderBook25) -> Result<Instrument, ExchangeError>
+    {
+        Instrument::normalize(&snapshot.symbol, parse_instrument_kind(&snapshot.exchange), &self.config.instrument_aliases)
+    }
+
+    /// 用给定的 `(价格, 数量)` 档位覆盖本地深度订单簿，语义与 [`Self::apply_depth_snapshot`] 相同。
+    pub async fn replace_depth_levels(&mut self, instrument: &Instrument, timestamp: i64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> Result<(), ExchangeError>
+    {
+        self.depth_order_books.lock().await.entry(instrument.clone()).or_default().replace_levels(timestamp, bids, asks);
+        self.fill_resting_orders_from_depth(instrument).await
+    }
+
+    /// 用本地深度中与挂单交叉的档位成交挂单：买单吃价格不高于限价的卖方档位，卖单吃价格不低于限价的买方档位。
+    ///
+    /// 挂单是被新到达的流动性成交的，因此按 Maker 计费。挂单按价格优先、时间优先依次成交，快照时间之后才到达交易所的挂单不参与；
+    /// 每笔挂单成交后发送 `OrdersPartiallyFilled`，完全成交时发送 `OrdersFilled`。
+    pub(crate) async fn fill_resting_orders_from_depth(&mut self, instrument: &Instrument) -> Result<(), ExchangeError>
+    {
+        let crossing_orders = {
+            let depth_order_books = self.depth_order_books.lock().await;
+            let Some(book) = depth_order_books.get(instrument)
+            else {
+                return Ok(());
+            };
+            let orders_guard = self.account_open_book.read().await;
+            let Ok(orders) = orders_guard.get_ins_orders_mut(instrument)
+            else {
+                return Ok(());
+            };
+            // 订单簿中越靠后的挂单越优先
+            let crossing_bids = orders.bids.iter().rev().filter(|order| book.best_ask().is_some_and(|best_ask| order.state.price >= best_ask));
+            let crossing_asks = orders.asks.iter().rev().filter(|order| book.best_bid().is_some_and(|best_bid| order.state.price <= best_bid));
+            crossing_bids.chain(crossing_asks).filter(|order| order.timestamp <= book.timestamp).cloned().collect::<Vec<_>>()
+        };
+
+        for order in crossing_orders {
+            if let Some(filled_order) = self.fill_order_from_depth(&order, Some(order.state.price), OrderRole::Maker).await? {
+                self.send_account_event(AccountEvent { exchange_timestamp: self.exchange_timestamp.load(Ordering::SeqCst),
+                                                       exchange: Exchange::Hourglass,
+                                                       kind: Self::fill_state_event_kind(&filled_order) })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 按 `synthetic_depth` 配置围绕最新成交价铺设合成流动性，为只有成交数据的回测提供对手盘。
+    ///
+    /// 只在该金融工具没有真实深度时生效：一旦加载过真实快照，合成流动性不再覆盖它。未配置时不做任何事。
+    pub(crate) async fn seed_synthetic_depth(&self, trade: &MarketTrade)
+    {
+        let Some(synthetic_depth) = self.config.synthetic_depth
+        else {
+            return;
+        };
+        let instrument = match self.resolve_market_instrument(trade) {
+            | Ok(instrument) => instrument,
+            | Err(err) => {
+                warn!("Failed to resolve instrument for synthetic depth {}: {:?}", trade.symbol, err);
+                return;
+            }
+        };
+
+        let mut depth_order_books = self.depth_order_books.lock().await;
+        let book = depth_order_books.entry(instrument).or_insert_with(|| DepthOrderBook { synthetic: true, ..Default::default() });
+        if book.synthetic {
+            book.replace_with_synthetic_levels(trade.timestamp, &synthetic_depth.bid_levels(trade.price), &synthetic_depth.ask_levels(trade.price));
+        }
+    }
+
+    /// 返回某个金融工具当前的本地深度订单簿。
+    pub async fn depth_order_book(&self, instrument: &Instrument) -> Option<DepthOrderBook>
+    {
+        self.depth_order_books.lock().await.get(instrument).cloned()
+    }
+
+    /// 本地深度订单簿的最优买价，没有该金融工具的深度或没有买方档位时返回 `None`。
+    pub async fn best_bid(&self, instrument: &Instrument) -> Option<f64>
+    {
+        self.depth_order_books.lock().await.get(instrument).and_then(DepthOrderBook::best_bid)
+    }
+
+    /// 本地深度订单簿的最优卖价，没有该金融工具的深度或没有卖方档位时返回 `None`。
+    pub async fn best_ask(&self, instrument: &Instrument) -> Option<f64>
+    {
+        self.depth_order_books.lock().await.get(instrument).and_then(DepthOrderBook::best_ask)
+    }
+
+    /// 本地深度订单簿的中间价，任一方没有档位时返回 `None`。
+    pub async fn mid_price(&self, instrument: &Instrument) -> Option<f64>
+    {
+        self.depth_order_books.lock().await.get(instrument).and_then(DepthOrderBook::mid_price)
+    }
+
+    /// 用本地深度订单簿成交一笔订单：刚挂出的主动单（市价单，或与对手方深度交叉的限价单），或与新到达的深度交叉的挂单。
+    ///
+    /// 主动单按价位由优到劣吃掉对手方档位，每一档生成一笔成交，被吃掉的数量从本地深度中扣除，
+    /// 因此同一快照内连续的主动单会得到逐渐变差的成交价。`limit_price` 为 `Some` 时只吃价格不劣于限价的档位。
+    /// 深度不足或剩余档位超过限价时，剩余部分继续挂在订单簿中等待后续行情撮合；限价单的剩余部分此后作为 Maker 挂单。
+    ///
+    /// 深度为合成流动性时，额外发送 `AccountEventKind::SyntheticFills` 标记这些成交。
+    ///
+    /// `order_role` 决定成交的计费方式：主动吃掉深度的订单为 Taker，被新到达的深度成交的挂单为 Maker。
+    ///
+    /// 没有加载该金融工具的深度、或没有可成交的档位时返回 `None`，保持原有的撮合方式；否则返回更新成交量后的订单。
+    ///
+    /// 先在挂单簿中找到该订单再扫单，扫单数量以挂单簿中该订单的剩余数量（即冻结余额对应的数量）为上限；
+    /// 订单不存在时返回 `ExchangeError::OrderNotFound`，深度保持不变。
+    pub(crate) async fn fill_order_from_depth(&mut self, order: &Order<Open>, limit_price: Option<f64>, order_role: OrderRole) -> Result<Option<Order<Open>>, ExchangeError>
+    {
+        let exchange_timestamp = self.exchange_timestamp.load(Ordering::SeqCst);
+        let (filled_order, trades, synthetic) = {
+            let mut depth_order_books = self.depth_order_books.lock().await;
+            let Some(book) = depth_order_books.get_mut(&order.instrument)
+            else {
+                return Ok(None);
+            };
+            let orders_guard = self.account_open_book.read().await;
+            let mut instrument_orders = orders_guard.get_ins_orders_mut(&order.instrument)?;
+            let index = Self::position_in_book(&instrument_orders, order)?;
+            let reserved_quantity = match order.side {
+                | Side::Buy => instrument_orders.bids[index].state.remaining_quantity(),
+                | Side::Sell => instrument_orders.asks[index].state.remaining_quantity(),
+            };
+            let sweep = book.sweep_within(order.side, reserved_quantity, limit_price);
+            if sweep.fills.is_empty() {
+                return Ok(None);
+            }
+            let (filled_order, trades) = self.apply_level_fills(&mut instrument_orders, order.side, index, &sweep.fills, limit_price, order_role)?;
+            (filled_order, trades, book.synthetic)
+        };
+
+        self.settle_depth_fills(trades, order_role, synthetic, exchange_timestamp).await?;
+        Ok(Some(filled_order))
+    }
+
+    /// 判断限价类订单（含 IOC、FOK）的限价是否与本地深度（含合成档位）的最优对手价交叉。
+    ///
+    /// 单档订单簿只反映最新成交，只有成交数据时合成档位可能比它更靠近成交价，因此可立即成交的限价单也按深度判断。
+    /// PostOnly 与市价单不受影响；没有加载深度时返回 `false`。
+    pub(crate) async fn limit_crosses_depth(&self, order: &Order<RequestOpen>) -> bool
+    {
+        if !matches!(order.instruction,
+                     OrderInstruction::Limit
+                     | OrderInstruction::GoodTilCancelled
+                     | OrderInstruction::GoodTilTime { .. }
+                     | OrderInstruction::ImmediateOrCancel
+                     | OrderInstruction::FillOrKill)
+        {
+            return false;
+        }
+        let depth_order_books = self.depth_order_books.lock().await;
+        let Some(book) = depth_order_books.get(&order.instrument)
+        else {
+            return false;
+        };
+        match order.side {
+            | Side::Buy => book.best_ask().is_some_and(|best_ask| order.state.price >= best_ask),
+            | Side::Sell => book.best_bid().is_some_and(|best_bid| order.state.price <= best_bid),
+        }
+    }
+
+    /// 没有加载深度时，按单档订单簿的最优对手价以 Taker 成交与之交叉的限价单的全部剩余数量。
+    ///
+    /// 单档订单簿不记录挂单量，最优价位视为流动性充足。没有行情或对手价不与限价交叉时返回 `None`。
+    pub(crate) async fn fill_order_at_top_of_book(&mut self, order: &Order<Open>) -> Result<Option<Order<Open>>, ExchangeError>
+    {
+        let top_of_book = {
+            let order_books_lock = self.single_level_order_book.lock().await;
+            order_books_lock.get(&order.instrument).map(|book| match order.side {
+                                                       | Side::Buy => book.latest_ask,
+                                                       | Side::Sell => book.latest_bid,
+                                                   })
+        };
+        let Some(price) = top_of_book.filter(|price| {
+                                         *price > 0.0
+                                         && match order.side {
+                                             | Side::Buy => *price <= order.state.price,
+                                             | Side::Sell => *price >= order.state.price,
+                                         }
+                                     })
+        else {
+            return Ok(None);
+        };
+
+        let exchange_timestamp = self.exchange_timestamp.load(Ordering::SeqCst);
+        let (filled_order, trades) = {
+            let orders_guard = self.account_open_book.read().await;
+            let mut instrument_orders = orders_guard.get_ins_orders_mut(&order.instrument)?;
+            let index = Self::position_in_book(&instrument_orders, order)?;
+            let amount = match order.side {
+                | Side::Buy => instrument_orders.bids[index].state.remaining_quantity(),
+                | Side::Sell => instrument_orders.asks[index].state.remaining_quantity(),
+            };
+            self.apply_level_fills(&mut instrument_orders, order.side, index, &[DepthLevel { price, amount }], Some(order.state.price), OrderRole::Taker)?
+        };
+
+        self.settle_depth_fills(trades, OrderRole::Taker, false, exchange_timestamp).await?;
+        Ok(Some(filled_order))
+    }
+
+    /// 在挂单簿中查找 `order` 所在的下标，不存在时返回 `ExchangeError::OrderNotFound`。
+    fn position_in_book(instrument_orders: &OpenOrdersBook, order: &Order<Open>) -> Result<usize, ExchangeError>
+    {
+        let side_orders = match order.side {
+            | Side::Buy => &instrument_orders.bids,
+            | Side::Sell => &instrument_orders.asks,
+        };
+        side_orders.iter()
+                   .position(|open| open.state.id == order.state.id)
+                   .ok_or_else(|| ExchangeError::OrderNotFound { client_order_id: order.cid.clone(),
+                                                                 order_id: Some(order.state.id.clone()) })
+    }
+
+    /// 把各档成交记录到挂单簿中下标为 `index` 的订单上：完全成交时移出挂单簿，否则以剩余数量留在原位。
+    fn apply_level_fills(&self, instrument_orders: &mut OpenOrdersBook, side: Side, index: usize, fills: &[DepthLevel], limit_price: Option<f64>, order_role: OrderRole) -> Result<(Order<Open>, Vec<ClientTrade>), ExchangeError>
+    {
+        let exchange_timestamp = self.exchange_timestamp.load(Ordering::SeqCst);
+        let mut filled_order = match side {
+            | Side::Buy => instrument_orders.bids[index].clone(),
+            | Side::Sell => instrument_orders.asks[index].clone(),
+        };
+        let trades = self.record_depth_fills(instrument_orders, &mut filled_order, fills, order_role, exchange_timestamp)?;
+
+        let side_orders = match side {
+            | Side::Buy => &mut instrument_orders.bids,
+            | Side::Sell => &mut instrument_orders.asks,
+        };
+        if filled_order.state.remaining_quantity() <= 0.0 {
+            side_orders.remove(index);
+        }
+        else {
+            // 限价单吃完限价以内的流动性后，剩余部分作为 Maker 挂单
+            if limit_price.is_some() {
+                filled_order.state.order_role = OrderRole::Maker;
+            }
+            // 冰山挂单的显示部分被深度吃完时从隐藏部分补充
+            filled_order.state.replenish();
+            side_orders[index] = filled_order.clone();
+        }
+        Ok((filled_order, trades))
+    }
+
+    /// 把深度扫单的各档成交记录到 `order` 上，按 `order_role` 计费，生成对应的 [`ClientTrade`]。
+    fn record_depth_fills(&self, instrument_orders: &OpenOrdersBook, order: &mut Order<Open>, fills: &[DepthLevel], order_role: OrderRole, exchange_timestamp: i64) -> Result<Vec<ClientTrade>, ExchangeError>
+    {
+        let commission = |trade: &ClientTrade, _: OrderRole| self.commission(trade, order_role);
+        let mut trades = Vec::with_capacity(fills.len());
+        for fill in fills {
+            self.client_trade_counter.fetch_add(1, Ordering::SeqCst);
+            order.state.record_fill(fill.price, fill.amount);
+            trades.push(instrument_orders.generate_client_trade_event(exchange_timestamp, order, fill.price, fill.amount, &commission, &self.client_trade_counter)?);
+        }
+        Ok(trades)
+    }
+
+    /// 结算深度成交：更新余额与仓位并发送成交事件；对手盘来自合成流动性时额外发送 `SyntheticFills`。
+    async fn settle_depth_fills(&mut self, trades: Vec<ClientTrade>, order_role: OrderRole, synthetic: bool, exchange_timestamp: i64) -> Result<(), ExchangeError>
+    {
+        let trade_ids = trades.iter().map(|trade| trade.trade_id).collect();
+        self.record_own_fill_prints(&trades, order_role);
+        self.process_trades(trades).await;
+
+        if synthetic {
+            self.send_account_event(AccountEvent { exchange_timestamp,
+                                                   exchange: Exchange::Hourglass,
+                                                   kind: AccountEventKind::SyntheticFills(trade_ids) })?;
+        }
+        Ok(())
+    }
+
+    /// 在开单时执行已到达交易所的 IOC 或 FOK 订单。订单只在本次调用中执行，不会进入挂单簿。
+    ///
+    /// 只有以 Taker 身份挂出且加载了深度时才有可成交的流动性：IOC 吃掉价格不劣于限价的可用深度，未成交部分随即撤销；
+    /// FOK 只在这些深度足以成交全部数量时才执行，否则直接拒绝，不冻结余额。
+    /// 有成交时依次发送余额、成交事件与 `OrdersPartiallyFilled`（完全成交时为 `OrdersFilled`），
+    /// 撤销剩余部分时释放冻结余额并发送 `OrdersCancelled`；没有可成交的流动性时 IOC 不冻结余额，直接撤销。
+    pub(crate) async fn execute_immediate_order(&mut self, order: Order<Open>, required_balance: f64) -> Result<Order<Open>, ExchangeError>
+    {
+        let fill_or_kill = order.instruction == OrderInstruction::FillOrKill;
+        let sweep = {
+            let mut depth_order_books = self.depth_order_books.lock().await;
+            match (order.state.order_role, depth_order_books.get_mut(&order.instrument)) {
+                | (OrderRole::Taker, Some(book)) if !fill_or_kill || book.liquidity_within(order.side, Some(order.state.price)) >= order.state.remaining_quantity() => {
+                    Some((book.sweep_within(order.side, order.state.remaining_quantity(), Some(order.state.price)), book.synthetic))
+                }
+                | _ => None,
+            }
+        };
+
+        let exchange_timestamp = self.exchange_timestamp.load(Ordering::SeqCst);
+        let Some((sweep, synthetic)) = sweep.filter(|(sweep, _)| !sweep.fills.is_empty())
+        else {
+            if fill_or_kill {
+                return Err(ExchangeError::InvalidOrder(format!("FillOrKill order {:?} cannot be filled in full within limit {}", order.cid, order.state.price)));
+            }
+            self.send_account_event(AccountEvent { exchange_timestamp,
+                                                   exchange: Exchange::Hourglass,
+                                                   kind: AccountEventKind::OrdersCancelled(vec![Order::from(order.clone())]) })?;
+            return Ok(order);
+        };
+
+        let balance_event = self.apply_open_order_changes(&order, required_balance).await?;
+        self.send_account_event(balance_event)?;
+
+        let mut executed_order = order;
+        let trades = {
+            let orders_guard = self.account_open_book.read().await;
+            let instrument_orders = orders_guard.get_ins_orders_mut(&executed_order.instrument)?;
+            self.record_depth_fills(&instrument_orders, &mut executed_order, &sweep.fills, OrderRole::Taker, exchange_timestamp)?
+        };
+        self.settle_depth_fills(trades, OrderRole::Taker, synthetic, exchange_timestamp).await?;
+        self.send_account_event(AccountEvent { exchange_timestamp,
+                                               exchange: Exchange::Hourglass,
+                                               kind: Self::fill_state_event_kind(&executed_order) })?;
+
+        if executed_order.state.remaining_quantity() > 0.0 {
+            self.release_removed_orders(vec![executed_order.clone()]).await?;
+        }
+        Ok(executed_order)
+    }
+
+    /// 为与对手方深度交叉、已立即成交部分数量的限价单发送订单事件：
+    /// 完全成交时发送 `OrdersFilled`；否则发送 `OrdersPartiallyFilled`，再以 `OrdersOpen` 发送继续挂单的剩余部分。
+    pub(crate) fn send_crossing_fill_events(&self, order: &Order<Open>) -> Result<(), ExchangeError>
+    {
+        let exchange_timestamp = self.exchange_timestamp.load(Ordering::SeqCst);
+        let fill_kind = Self::fill_state_event_kind(order);
+
+        self.send_account_event(AccountEvent { exchange_timestamp,
+                                               exchange: Exchange::Hourglass,
+                                               kind: fill_kind })?;
+
+        if order.state.remaining_quantity() > 0.0 {
+            self.send_account_event(AccountEvent { exchange_timestamp,
+                                                   exchange: Exchange::Hourglass,
+                                                   kind: AccountEventKind::OrdersOpen(vec![order.clone()]) })?;
+        }
+        Ok(())
+    }
+
+    /// 按订单的剩余数量生成成交状态事件：完全成交为 `OrdersFilled`，否则为 `OrdersPartiallyFilled`，
+    /// 价格为至今的成交均价，数量为累计成交数量。
+    pub(crate) fn fill_state_event_kind(order: &Order<Open>) -> AccountEventKind
+    {
+        if order.state.remaining_quantity() <= 0.0 {
+            AccountEventKind::OrdersFilled(vec![Order { instruction: order.instruction,
+                                                        exchange: order.exchange,
+                                                        instrument: order.instrument.clone(),
+                                                        timestamp: order.timestamp,
+                                                        cid: order.cid.clone(),
+                                                        side: order.side,
+                                                        state: FullyFill { id: order.state.id.clone(),
+                                                                           price: order.state.avg_fill_price(),
+                                                                           size: order.state.filled_quantity,
+                                                                           tag: order.state.tag.clone() } }])
+        }
+        else {
+            AccountEventKind::OrdersPartiallyFilled(vec![Order { instruction: order.instruction,
+                                                                 exchange: order.exchange,
+                                                                 instrument: order.instrument.clone(),
+                                                                 timestamp: order.timestamp,
+                                                                 cid: order.cid.clone(),
+                                                                 side: order.side,
+                                                                 state: PartialFill { id: order.state.id.clone(),
+                                                                                      price: order.state.avg_fill_price(),
+                                                                                      size: order.state.filled_quantity,
+                                                                                      tag: order.state.tag.clone() } }])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            instrument::kind::InstrumentKind,
+            order::{identification::{client_order_id::ClientOrderId, OrderId}, order_instructions::OrderInstruction, states::request_open::RequestOpen},
+            token::Token,
+        },
+        hourglass::account::{account_config::SyntheticDepthConfig, account_handlers::trade_handler::TradeHandler},
+        test_utils::create_test_account,
+    };
+    use tokio::sync::mpsc;
+
+    fn market_buy(size: f64) -> Order<RequestOpen>
+    {
+        Order { instruction: OrderInstruction::Market,
+                exchange: Exchange::Hourglass,
+                instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                timestamp: 1234567,
+                cid: Some(ClientOrderId("depth".into())),
+                side: Side::Buy,
+                state: RequestOpen { price: 16499.0,
+                                     size,
+                                     reduce_only: false,
+                                     tag: None } }
+    }
+
+    #[tokio::test]
+    async fn test_market_orders_consume_depth_until_next_snapshot()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        account.replace_depth_levels(&instrument, 1, &[(16305.0, 1.0)], &[(16500.0, 0.2), (16600.0, 1.0)]).await.unwrap();
+
+        // 吃掉第一档后继续吃第二档，成交均价反映冲击成本
+        let filled = account.atomic_open(market_buy(0.5)).await.unwrap();
+        assert_eq!(filled.state.filled_quantity, 0.5);
+        assert!((filled.state.avg_fill_price() - 16560.0).abs() < 1e-9);
+        assert_eq!(account.account_open_book.read().await.get_ins_orders_mut(&instrument).unwrap().num_orders(), 0);
+
+        // 同一快照内的下一笔市价单只能拿到剩余的流动性
+        let filled = account.atomic_open(market_buy(0.1)).await.unwrap();
+        assert_eq!(filled.state.avg_fill_price(), 16600.0);
+        assert!((account.depth_order_book(&instrument).await.unwrap().asks[0].amount - 0.6).abs() < 1e-9);
+
+        // 外部快照到来后流动性恢复
+        account.replace_depth_levels(&instrument, 2, &[(16305.0, 1.0)], &[(16500.0, 0.2), (16600.0, 1.0)]).await.unwrap();
+        assert_eq!(account.depth_order_book(&instrument).await.unwrap().best_ask(), Some(16500.0));
+    }
+
+    fn immediate_buy(instruction: OrderInstruction, size: f64) -> Order<RequestOpen>
+    {
+        let mut order = market_buy(size);
+        order.instruction = instruction;
+        order.state.price = 16600.0;
+        order
+    }
+
+    #[tokio::test]
+    async fn test_fill_or_kill_rejected_when_depth_insufficient()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        account.replace_depth_levels(&instrument, 1, &[(16305.0, 1.0)], &[(16500.0, 0.2), (16600.0, 0.2), (16700.0, 1.0)]).await.unwrap();
+        let usdt_before = account.balances.get(&Token::from("USDT")).unwrap().available;
+
+        // 限价以内只有 0.4，不足以成交 0.5：直接拒绝，不冻结余额，不发送任何事件，深度保持不变
+        assert!(matches!(account.atomic_open(immediate_buy(OrderInstruction::FillOrKill, 0.5)).await, Err(ExchangeError::InvalidOrder(_))));
+        assert!(account_event_rx.try_recv().is_err());
+        assert_eq!(account.balances.get(&Token::from("USDT")).unwrap().available, usdt_before);
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+        assert_eq!(account.depth_order_book(&instrument).await.unwrap().asks[0].amount, 0.2);
+
+        // 数量不超过可用深度时完全成交
+        let filled = account.atomic_open(immediate_buy(OrderInstruction::FillOrKill, 0.3)).await.unwrap();
+        assert_eq!(filled.state.filled_quantity, 0.3);
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_immediate_orders_never_rest_without_fillable_depth()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let usdt_before = account.balances.get(&Token::from("USDT")).unwrap().available;
+
+        // 没有加载深度：IOC 整笔撤销，FOK 直接拒绝
+        let cancelled = account.atomic_open(immediate_buy(OrderInstruction::ImmediateOrCancel, 0.5)).await.unwrap();
+        assert_eq!(cancelled.state.filled_quantity, 0.0);
+        assert!(matches!(account.atomic_open(immediate_buy(OrderInstruction::FillOrKill, 0.5)).await, Err(ExchangeError::InvalidOrder(_))));
+
+        // 加载了深度但限价低于最优卖价，不可立即成交：同样不会挂单
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        account.replace_depth_levels(&instrument, 1, &[(16305.0, 1.0)], &[(16500.0, 1.0)]).await.unwrap();
+        let mut passive = immediate_buy(OrderInstruction::ImmediateOrCancel, 0.5);
+        passive.state.price = 16400.0;
+        assert_eq!(account.atomic_open(passive).await.unwrap().state.filled_quantity, 0.0);
+
+        let mut kinds = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            match event.kind {
+                | AccountEventKind::OrdersCancelled(_) => kinds.push("cancelled"),
+                | AccountEventKind::OrdersOpen(_) => kinds.push("open"),
+                | AccountEventKind::Trade(_) => kinds.push("trade"),
+                | _ => {}
+            }
+        }
+        assert_eq!(kinds, vec!["cancelled", "cancelled"]);
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+        assert_eq!(account.balances.get(&Token::from("USDT")).unwrap().available, usdt_before);
+    }
+
+    #[tokio::test]
+    async fn test_immediate_or_cancel_fills_depth_and_cancels_remainder()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        account.replace_depth_levels(&instrument, 1, &[(16305.0, 1.0)], &[(16500.0, 0.2), (16600.0, 0.2), (16700.0, 1.0)]).await.unwrap();
+
+        let executed = account.atomic_open(immediate_buy(OrderInstruction::ImmediateOrCancel, 0.5)).await.unwrap();
+        assert!((executed.state.filled_quantity - 0.4).abs() < 1e-9);
+
+        let mut events = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            match event.kind {
+                | AccountEventKind::Trade(trade) => events.push(format!("trade {}", trade.price)),
+                | AccountEventKind::OrdersPartiallyFilled(orders) => events.push(format!("partial {:.1}", orders[0].state.size)),
+                | AccountEventKind::OrdersCancelled(orders) => events.push(format!("cancelled {:?}", orders[0].cid)),
+                | AccountEventKind::OrdersOpen(_) => events.push("open".to_string()),
+                | _ => {}
+            }
+        }
+        assert_eq!(events, vec!["trade 16500".to_string(), "trade 16600".to_string(), "partial 0.4".to_string(), format!("cancelled {:?}", Some(ClientOrderId("depth".into())))]);
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_fills_crossing_resting_order_as_maker()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+
+        // 没有深度时 16400 的买单低于最优卖价，作为 Maker 挂单
+        let mut order = market_buy(0.5);
+        order.instruction = OrderInstruction::Limit;
+        order.state.price = 16400.0;
+        let open = account.atomic_open(order).await.unwrap();
+        assert_eq!(open.state.order_role, OrderRole::Maker);
+        while account_event_rx.try_recv().is_ok() {}
+
+        // 新快照中有两档卖单不高于挂单价，挂单吃掉这两档后剩余部分继续挂单
+        account.replace_depth_levels(&instrument, 1234568, &[(16300.0, 1.0)], &[(16380.0, 0.2), (16390.0, 0.1), (16450.0, 1.0)]).await.unwrap();
+        let mut trades = Vec::new();
+        let mut partial_sizes = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            match event.kind {
+                | AccountEventKind::Trade(trade) => trades.push(trade),
+                | AccountEventKind::OrdersPartiallyFilled(orders) => partial_sizes.push(orders[0].state.size),
+                | _ => {}
+            }
+        }
+        assert_eq!(trades.iter().map(|trade| trade.price).collect::<Vec<_>>(), vec![16380.0, 16390.0]);
+        assert!(trades.iter().all(|trade| (trade.fees - trade.price * trade.size * 0.001).abs() < 1e-9));
+        assert_eq!(partial_sizes.len(), 1);
+        assert!((partial_sizes[0] - 0.3).abs() < 1e-9);
+        let resting = account.account_open_book.read().await.fetch_all();
+        assert!((resting[0].state.remaining_quantity() - 0.2).abs() < 1e-9);
+
+        assert_eq!(account.best_bid(&instrument).await, Some(16300.0));
+        assert_eq!(account.best_ask(&instrument).await, Some(16450.0));
+        assert_eq!(account.mid_price(&instrument).await, Some(16375.0));
+    }
+
+    #[tokio::test]
+    async fn test_depth_fill_looks_up_order_first_and_caps_at_reserved_quantity()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+
+        let mut order = market_buy(0.3);
+        order.instruction = OrderInstruction::Limit;
+        order.state.price = 16400.0;
+        let open = account.atomic_open(order).await.unwrap();
+        account.replace_depth_levels(&instrument, 1234568, &[(16300.0, 1.0)], &[(16500.0, 1.0)]).await.unwrap();
+
+        // 不在挂单簿中的订单：返回 OrderNotFound，深度不被消耗
+        let mut missing = open.clone();
+        missing.state.id = OrderId(u64::MAX);
+        assert!(matches!(account.fill_order_from_depth(&missing, Some(16600.0), OrderRole::Taker).await, Err(ExchangeError::OrderNotFound { .. })));
+        assert_eq!(account.depth_order_book(&instrument).await.unwrap().asks[0].amount, 1.0);
+
+        // 调用方持有的订单数量大于挂单簿中冻结的数量时，只按冻结的 0.3 扫单
+        let mut stale = open.clone();
+        stale.state.size = 1.0;
+        let filled = account.fill_order_from_depth(&stale, Some(16600.0), OrderRole::Taker).await.unwrap().unwrap();
+        assert_eq!(filled.state.filled_quantity, 0.3);
+        assert!((account.depth_order_book(&instrument).await.unwrap().asks[0].amount - 0.7).abs() < 1e-9);
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_market_order_rests_without_depth()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let open = account.atomic_open(market_buy(0.1)).await.unwrap();
+        assert_eq!(open.state.filled_quantity, 0.0);
+        assert_eq!(account.account_open_book.read().await.get_ins_orders_mut(&open.instrument).unwrap().num_orders(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_synthetic_depth_seeds_trade_only_books_and_flags_fills()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.config.synthetic_depth = Some(SyntheticDepthConfig { half_spread_bps: 10.0,
+                                                                     level_step_bps: 10.0,
+                                                                     levels: 3,
+                                                                     level_size: 0.1 });
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        let market_trade = |price: f64, timestamp: i64| MarketTrade { exchange: "binance-futures".to_string(),
+                                                                      symbol: "ETHUSDT".to_string(),
+                                                                      side: "buy".to_string(),
+                                                                      price,
+                                                                      timestamp,
+                                                                      amount: 1.0 };
+
+        // 只有成交数据时围绕成交价铺设合成档位
+        account.handle_trade_data(&market_trade(16000.0, 1234567)).await.unwrap();
+        let book = account.depth_order_book(&instrument).await.unwrap();
+        assert!(book.synthetic);
+        assert_eq!(book.asks.len(), 3);
+        assert!((book.best_ask().unwrap() - 16016.0).abs() < 1e-9);
+        assert!((book.best_bid().unwrap() - 15984.0).abs() < 1e-9);
+
+        // 市价单吃掉合成档位，且这些成交被单独标记
+        let filled = account.atomic_open(market_buy(0.15)).await.unwrap();
+        assert_eq!(filled.state.filled_quantity, 0.15);
+        let mut trade_ids = Vec::new();
+        let mut synthetic_ids = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            match event.kind {
+                | AccountEventKind::Trade(trade) => trade_ids.push(trade.trade_id),
+                | AccountEventKind::SyntheticFills(ids) => synthetic_ids.extend(ids),
+                | _ => {}
+            }
+        }
+        assert_eq!(trade_ids.len(), 2);
+        assert_eq!(synthetic_ids, trade_ids);
+
+        // 加载真实深度后不再被合成流动性覆盖
+        account.replace_depth_levels(&instrument, 1234568, &[(16305.0, 1.0)], &[(16499.0, 1.0)]).await.unwrap();
+        account.handle_trade_data(&market_trade(16100.0, 1234569)).await.unwrap();
+        let book = account.depth_order_book(&instrument).await.unwrap();
+        assert!(!book.synthetic);
+        assert_eq!(book.best_ask(), Some(16499.0));
+    }
+
+    #[tokio::test]
+    async fn test_marketable_limit_walks_synthetic_depth()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.config.synthetic_depth = Some(SyntheticDepthConfig { half_spread_bps: 10.0,
+                                                                     level_step_bps: 10.0,
+                                                                     levels: 3,
+                                                                     level_size: 0.1 });
+        account.handle_trade_data(&MarketTrade { exchange: "binance-futures".to_string(),
+                                                 symbol: "ETHUSDT".to_string(),
+                                                 side: "buy".to_string(),
+                                                 price: 16000.0,
+                                                 timestamp: 1234567,
+                                                 amount: 1.0 })
+               .await
+               .unwrap();
+        while account_event_rx.try_recv().is_ok() {}
+
+        // 单档订单簿的最优卖价仍为 16499，但限价 16020 与合成的 16016 一档交叉：吃掉该档，剩余部分作为 Maker 挂单
+        let mut order = market_buy(0.15);
+        order.instruction = OrderInstruction::Limit;
+        order.state.price = 16020.0;
+        let open = account.atomic_open(order).await.unwrap();
+        assert!((open.state.filled_quantity - 0.1).abs() < 1e-9);
+        assert!((open.state.avg_fill_price() - 16016.0).abs() < 1e-9);
+        assert_eq!(open.state.order_role, OrderRole::Maker);
+        let resting = account.account_open_book.read().await.fetch_all();
+        assert!((resting[0].state.remaining_quantity() - 0.05).abs() < 1e-9);
+
+        let mut trade_ids = Vec::new();
+        let mut synthetic_ids = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            match event.kind {
+                | AccountEventKind::Trade(trade) => trade_ids.push(trade.trade_id),
+                | AccountEventKind::SyntheticFills(ids) => synthetic_ids.extend(ids),
+                | _ => {}
+            }
+        }
+        assert_eq!(trade_ids.len(), 1);
+        assert_eq!(synthetic_ids, trade_ids);
+    }
+
+    #[tokio::test]
+    async fn test_crossing_limit_matches_levels_up_to_limit_and_rests_remainder()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        account.replace_depth_levels(&instrument, 1, &[(16305.0, 1.0)], &[(16499.0, 0.1), (16520.0, 0.1), (16540.0, 0.1), (16600.0, 1.0)]).await.unwrap();
+
+        // 限价 16550 穿过三档卖单，16600 一档超过限价
+        let mut order = market_buy(0.5);
+        order.instruction = OrderInstruction::Limit;
+        order.state.price = 16550.0;
+        let open = account.atomic_open(order).await.unwrap();
+        assert!((open.state.filled_quantity - 0.3).abs() < 1e-9);
+        let expected_avg = (16499.0 + 16520.0 + 16540.0) / 3.0;
+        assert!((open.state.avg_fill_price() - expected_avg).abs() < 1e-9);
+        assert_eq!(open.state.order_role, OrderRole::Maker);
+        assert_eq!(account.depth_order_book(&instrument).await.unwrap().best_ask(), Some(16600.0));
+
+        // 剩余部分按限价继续挂单
+        let resting = account.account_open_book.read().await.fetch_all();
+        assert_eq!(resting.len(), 1);
+        assert_eq!(resting[0].state.price, 16550.0);
+        assert!((resting[0].state.remaining_quantity() - 0.2).abs() < 1e-9);
+
+        let mut trades = 0;
+        let mut order_events = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            match event.kind {
+                | AccountEventKind::Trade(_) => trades += 1,
+                | AccountEventKind::OrdersPartiallyFilled(orders) => order_events.push(("partial", orders[0].state.size)),
+                | AccountEventKind::OrdersOpen(orders) => order_events.push(("open", orders[0].state.remaining_quantity())),
+                | _ => {}
+            }
+        }
+        assert_eq!(trades, 3);
+        assert_eq!(order_events.len(), 2);
+        assert_eq!(order_events[0].0, "partial");
+        assert!((order_events[0].1 - 0.3).abs() < 1e-9);
+        assert_eq!(order_events[1].0, "open");
+        assert!((order_events[1].1 - 0.2).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_crossing_limit_without_depth_fills_at_top_of_book()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        // 没有加载深度，限价 16550 高于单档订单簿的最优卖价 16499：按 16499 以 Taker 成交全部数量，不进入挂单簿
+        let mut order = market_buy(0.5);
+        order.instruction = OrderInstruction::Limit;
+        order.state.price = 16550.0;
+        let filled = account.atomic_open(order).await.unwrap();
+        assert_eq!(filled.state.filled_quantity, 0.5);
+        assert_eq!(filled.state.avg_fill_price(), 16499.0);
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+
+        let mut kinds = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            match event.kind {
+                | AccountEventKind::Trade(trade) => kinds.push(format!("trade {} {}", trade.price, trade.size)),
+                | AccountEventKind::OrdersFilled(_) => kinds.push("filled".to_string()),
+                | AccountEventKind::OrdersOpen(_) => kinds.push("open".to_string()),
+                | _ => {}
+            }
+        }
+        assert_eq!(kinds, vec!["trade 16499 0.5".to_string(), "filled".to_string()]);
+    }
+}
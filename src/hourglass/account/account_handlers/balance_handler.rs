@@ -35,15 +35,27 @@ pub trait BalanceHandler
     /// [`Balance`]的变化取决于[`Order<Open>`]是[`Side::Buy`]还是[`Side::Sell`]。
     async fn apply_open_order_changes(&mut self, open: &Order<Open>, required_balance: f64) -> Result<AccountEvent, ExchangeError>;
     /// 当client取消[`Order<Open>`]时，更新相关的[`Token`] [`Balance`]。
-    /// [`Balance`]的变化取决于[`Order<Open>`]是[`Side::Buy`]还是[`Side::Sell`]。
-    fn apply_cancel_order_changes(&mut self, cancelled: &Order<Open>) -> Result<AccountEvent, ExchangeError>;
+    /// [`Balance`]的变化取决于[`Order<Open>`]是[`Side::Buy`]还是[`Side::Sell`]。被撤销的挂单所在的 OCO 订单组同时解除。
+    async fn apply_cancel_order_changes(&mut self, cancelled: &Order<Open>) -> Result<AccountEvent, ExchangeError>;
     /// 从交易中更新余额并返回 [`AccountEvent`]
     async fn apply_trade_changes(&mut self, trade: &ClientTrade) -> Result<AccountEvent, ExchangeError>;
+    /// 与 [`Self::apply_trade_changes`] 相同，但把 `quote_settlement`（例如平仓实现的盈亏）与手续费等报价货币变动作为一笔净额交易应用，
+    /// 只校验结算后的最终余额，先扣手续费不会因报价货币余额暂时为负而被拒绝。
+    async fn settle_trade(&mut self, trade: &ClientTrade, quote_settlement: &[BalanceDelta]) -> Result<AccountEvent, ExchangeError>;
     /// 将 [`BalanceDelta`] 应用于指定 [`Token`] 的 [`Balance`]，并返回更新后的 [`Balance`] 。
     fn apply_balance_delta(&mut self, token: &Token, delta: BalanceDelta) -> Balance;
+    /// 将一组 [`BalanceDelta`] 作为一笔净额交易应用于指定 [`Token`] 的 [`Balance`]，只校验最终余额，见 [`Balance::apply_net`]。
+    ///
+    /// 未配置该 [`Token`] 或最终余额为负时返回错误，余额保持不变。
+    fn apply_balance_deltas(&mut self, token: &Token, deltas: &[BalanceDelta]) -> Result<Balance, ExchangeError>;
     async fn required_available_balance<'a>(&'a self, order: &'a Order<RequestOpen>, order_role: OrderRole) -> Result<(&'a Token, f64), ExchangeError>;
     /// 判断client是否有足够的可用[`Balance`]来执行[`Order<RequestOpen>`]。
     fn has_sufficient_available_balance(&self, token: &Token, required_balance: f64) -> Result<(), ExchangeError>;
+    /// 挂单[`Order<Open>`]剩余部分冻结的[`Token`]与数量，撤单时按此释放。
+    ///
+    /// 现货买单冻结 quote（`price * remaining_quantity`），现货卖单冻结 base（`remaining_quantity`），
+    /// 衍生品冻结 quote 保证金（`price * remaining_quantity * contract_multiplier * initial_margin_rate`）。
+    fn reserved_balance(&self, order: &Order<Open>) -> (Token, f64);
 }
 
 #[async_trait]
@@ -62,12 +74,15 @@ impl BalanceHandler for HourglassAccount
             .ok_or_else(|| ExchangeError::Hourglass(format!("HourglassExchange is not configured for Token: {:?}", token)))
     }
 
-    /// 返回指定[`Token`]的[`Balance`]的可变引用。
+    /// 返回指定[`Token`]的[`Balance`]的可变引用，并把余额时间更新为当前的交易所时间。
     fn get_balance_mut(&mut self, token: &Token) -> Result<DashMapRefMut<'_, Token, Balance>, ExchangeError>
     {
-        self.balances
-            .get_mut(token)
-            .ok_or_else(|| ExchangeError::Hourglass(format!("HourglassExchange is not configured for Token: {:?}", token)))
+        let time = self.exchange_time();
+        let mut balance = self.balances
+                              .get_mut(token)
+                              .ok_or_else(|| ExchangeError::Hourglass(format!("HourglassExchange is not configured for Token: {:?}", token)))?;
+        balance.time = time;
+        Ok(balance)
     }
 
     async fn fetch_token_balances_and_respond(&self, response_tx: Sender<Result<Vec<TokenBalance>, ExchangeError>>)
@@ -89,55 +104,39 @@ impl BalanceHandler for HourglassAccount
     {
         info!("[apply_open_order_changes] : applying open order: {:?}, subtracting required_balance: {:?}", open, required_balance);
 
-        // 根据 PositionMarginMode 处理余额更新 注意 : 暂时不支持spot的仓位逻辑
-        match open.instrument.kind {
-            | InstrumentKind::Perpetual | InstrumentKind::Future | InstrumentKind::CryptoLeveragedToken => {
-                let delta = BalanceDelta { total: 0.0,
-                                           available: -required_balance };
-                self.apply_balance_delta(&open.instrument.quote, delta)
-            }
+        // 现货买单锁定 quote、卖单锁定 base；合约类订单都以 quote 作为保证金
+        let token = match open.instrument.kind {
+            | InstrumentKind::Spot => match open.side {
+                | Side::Buy => open.instrument.quote.clone(),
+                | Side::Sell => open.instrument.base.clone(),
+            },
+            | InstrumentKind::Perpetual | InstrumentKind::Future | InstrumentKind::CryptoLeveragedToken => open.instrument.quote.clone(),
             | _ => {
                 return Err(ExchangeError::Hourglass(format!("Unsupported InstrumentKind or PositionMarginMode for open order: {:?}", open.instrument.kind)));
             }
         };
-
-        // 更新后的余额
-        let updated_balance = match open.side {
-            | Side::Buy => *self.get_balance(&open.instrument.quote)?,
-            | Side::Sell => *self.get_balance(&open.instrument.base)?,
-        };
+        let delta = BalanceDelta { total: 0.0,
+                                   available: -required_balance };
+        let updated_balance = self.apply_balance_delta(&token, delta);
 
         Ok(AccountEvent { exchange_timestamp: self.exchange_timestamp.load(Ordering::SeqCst),
                           exchange: Exchange::Hourglass,
-                          kind: AccountEventKind::Balance(TokenBalance::new(open.instrument.quote.clone(), updated_balance)) })
+                          kind: AccountEventKind::Balance(TokenBalance::new(token, updated_balance)) })
     }
 
     /// 当client取消[`Order<Open>`]时，更新相关的[`Token`] [`Balance`]。
-    /// [`Balance`]的变化取决于[`Order<Open>`]是[`Side::Buy`]还是[`Side::Sell`]。
-    fn apply_cancel_order_changes(&mut self, cancelled: &Order<Open>) -> Result<AccountEvent, ExchangeError>
+    /// [`Balance`]的变化取决于[`Order<Open>`]是[`Side::Buy`]还是[`Side::Sell`]。被撤销的挂单所在的 OCO 订单组同时解除。
+    async fn apply_cancel_order_changes(&mut self, cancelled: &Order<Open>) -> Result<AccountEvent, ExchangeError>
     {
-        let updated_balance = match cancelled.side {
-            | Side::Buy => {
-                info!("[apply_cancel_order_changes] : applying cancelled balance");
-                let mut balance = self.get_balance_mut(&cancelled.instrument.quote).expect("Balance existence checked when opening Order");
-                info!("[apply_cancel_order_changes] : balance before application of change: {:?}", *balance);
-                info!("[apply_cancel_order_changes] : cancelled order's price is : {:?}", cancelled.state.price);
-                balance.available += cancelled.state.price * cancelled.state.remaining_quantity();
-                info!("[apply_cancel_order_changes] : balance after application of change: {:?}", *balance);
-                *balance
-            }
-            | Side::Sell => {
-                let mut balance = self.get_balance_mut(&cancelled.instrument.base).expect("Balance existence checked when opening Order");
-                balance.available += cancelled.state.price * cancelled.state.remaining_quantity();
-                *balance
-            }
-        };
+        // 被撤销的挂单不再属于任何 OCO 订单组，解除关联，避免留下指向已撤销挂单的订单组
+        self.account_open_book.write().await.take_oco_sibling(&cancelled.state.id);
 
-        // 根据 `Side` 确定使用 `base` 或 `quote` 作为 `Token`
-        let token = match cancelled.side {
-            | Side::Buy => cancelled.instrument.quote.clone(),
-            | Side::Sell => cancelled.instrument.base.clone(),
-        };
+        // 释放的数量与开单时冻结的口径一致，衍生品卖单同样释放 quote 保证金
+        let (token, released) = self.reserved_balance(cancelled);
+        info!("[apply_cancel_order_changes] : releasing {:?} of {:?} for cancelled order {:?}", released, token, cancelled.state.id);
+        let delta = BalanceDelta { total: 0.0,
+                                   available: released };
+        let updated_balance = self.apply_balance_delta(&token, delta);
 
         Ok(AccountEvent { exchange_timestamp: self.exchange_timestamp.load(Ordering::SeqCst),
                           exchange: Exchange::Hourglass,
@@ -146,6 +145,11 @@ impl BalanceHandler for HourglassAccount
 
     /// 从交易中更新余额并返回 [`AccountEvent`]
     async fn apply_trade_changes(&mut self, trade: &ClientTrade) -> Result<AccountEvent, ExchangeError>
+    {
+        self.settle_trade(trade, &[]).await
+    }
+
+    async fn settle_trade(&mut self, trade: &ClientTrade, quote_settlement: &[BalanceDelta]) -> Result<AccountEvent, ExchangeError>
     {
         info!("[apply_trade_changes] : applying trade: {:?}", trade);
         let Instrument { quote, kind, .. } = &trade.instrument;
@@ -157,36 +161,40 @@ impl BalanceHandler for HourglassAccount
         match kind {
             | InstrumentKind::Spot => {
                 let base = &trade.instrument.base;
-                let (base_delta, quote_delta) = match side {
+                // 手续费与成交金额分开记账，作为一笔净额交易应用，先扣手续费不会因可用余额暂时为负被拒绝
+                let fee_delta = BalanceDelta { total: -fee, available: -fee };
+                let (base_delta, notional_delta) = match side {
                     | Side::Buy => {
                         let base_increase = trade.size;
                         // Note: available was already decreased by the opening of the Side::Buy order
                         let base_delta = BalanceDelta { total: base_increase,
                                                         available: base_increase };
-                        let quote_delta = BalanceDelta { total: -trade.size * trade.price - fee,
-                                                         available: -fee };
-                        (base_delta, quote_delta)
+                        let notional_delta = BalanceDelta { total: -trade.size * trade.price,
+                                                            available: 0.0 };
+                        (base_delta, notional_delta)
                     }
                     | Side::Sell => {
                         // Note: available was already decreased by the opening of the Side::Sell order
                         let base_delta = BalanceDelta { total: -trade.size, available: 0.0 };
-                        let quote_increase = (trade.size * trade.price) - fee;
-                        let quote_delta = BalanceDelta { total: quote_increase,
-                                                         available: quote_increase };
-                        (base_delta, quote_delta)
+                        let quote_increase = trade.size * trade.price;
+                        let notional_delta = BalanceDelta { total: quote_increase,
+                                                            available: quote_increase };
+                        (base_delta, notional_delta)
                     }
                 };
 
+                // 先结算可能被拒绝的 quote，失败时 base 保持不变
+                let quote_deltas: Vec<BalanceDelta> = [fee_delta, notional_delta].into_iter().chain(quote_settlement.iter().copied()).collect();
+                let quote_balance = self.apply_balance_deltas(quote, &quote_deltas)?;
                 let base_balance = self.apply_balance_delta(base, base_delta);
-                let quote_balance = self.apply_balance_delta(quote, quote_delta);
+                self.update_spot_cost_basis(trade);
 
                 Ok(AccountEvent { exchange_timestamp: self.get_exchange_ts().expect("Failed to get exchange timestamp"),
                                   exchange: Exchange::Hourglass,
                                   kind: AccountEventKind::Balances(vec![TokenBalance::new(base.clone(), base_balance), TokenBalance::new(quote.clone(), quote_balance),]) })
             }
-            | InstrumentKind::CryptoOption => {
-                todo!("Option handling is not implemented yet");
-            }
+            // 期权的 `Instrument` 不含看涨/看跌与行权价，无法结算权利金或确定仓位表
+            | InstrumentKind::CryptoOption => Err(ExchangeError::UnsupportedInstrumentKind),
             | InstrumentKind::CommodityOption => {
                 todo!("CommodityOption handling is not implemented yet")
             }
@@ -209,8 +217,9 @@ impl BalanceHandler for HourglassAccount
                 };
 
                 info!("[apply_trade_changes] : quote_delta: {:?}", quote_delta);
-                // 应用 quote 的余额变动
-                let quote_balance = self.apply_balance_delta(quote, quote_delta);
+                // 应用 quote 的余额变动，手续费与平仓盈亏等结算一起按净额应用
+                let quote_deltas: Vec<BalanceDelta> = std::iter::once(quote_delta).chain(quote_settlement.iter().copied()).collect();
+                let quote_balance = self.apply_balance_deltas(quote, &quote_deltas)?;
 
                 // 生成账户事件，只涉及 quote
                 Ok(AccountEvent { exchange_timestamp: self.get_exchange_ts().expect("Failed to get exchange timestamp"),
@@ -230,6 +239,20 @@ impl BalanceHandler for HourglassAccount
         *base_balance
     }
 
+    fn apply_balance_deltas(&mut self, token: &Token, deltas: &[BalanceDelta]) -> Result<Balance, ExchangeError>
+    {
+        let mut balance = self.get_balance_mut(token)?;
+
+        if balance.apply_net(deltas).is_err() {
+            let net: BalanceDelta = deltas.iter().copied().sum();
+            return Err(ExchangeError::InsufficientBalance { token: token.clone(),
+                                                            required: -net.available,
+                                                            available: balance.available });
+        }
+
+        Ok(*balance)
+    }
+
     // NOTE 此处计算required_available_balance要分离出maker的处理规则
     async fn required_available_balance<'a>(&'a self, order: &'a Order<RequestOpen>, order_role: OrderRole) -> Result<(&'a Token, f64), ExchangeError>
     {
@@ -251,12 +274,7 @@ impl BalanceHandler for HourglassAccount
                     // 处理买单（Side::Buy）
                     | (Side::Buy, OrderRole::Maker) => {
                         // 确保买单价格在合理范围内
-                        if order.state.price < latest_ask * (1.0 - max_price_deviation) {
-                            return Err(ExchangeError::OrderRejected("Buy order price is too low compared to the market".into()));
-                        }
-                        if order.state.price > latest_bid * (1.0 + max_price_deviation) {
-                            return Err(ExchangeError::OrderRejected("Buy order price is too high compared to the market".into()));
-                        }
+                        ensure_price_within_band(order.state.price, latest_bid, latest_ask, max_price_deviation)?;
                         // 计算所需的余额 (挂单价格 * 数量)
                         let required_balance = order.state.price * order.state.size;
                         Ok((&order.instrument.quote, required_balance))
@@ -270,20 +288,13 @@ impl BalanceHandler for HourglassAccount
                     // 处理卖单（Side::Sell）
                     | (Side::Sell, OrderRole::Maker) => {
                         // 确保卖单价格在合理范围内
-                        if order.state.price > latest_bid * (1.0 + max_price_deviation) {
-                            return Err(ExchangeError::OrderRejected("Sell order price is too high compared to the market".into()));
-                        }
-                        if order.state.price < latest_ask * (1.0 - max_price_deviation) {
-                            return Err(ExchangeError::OrderRejected("Sell order price is too low compared to the market".into()));
-                        }
-                        // 计算所需的余额 (挂单价格 * 数量)
-                        let required_balance = order.state.price * order.state.size;
-                        Ok((&order.instrument.base, required_balance))
+                        ensure_price_within_band(order.state.price, latest_bid, latest_ask, max_price_deviation)?;
+                        // 卖出锁定的是 base 数量
+                        Ok((&order.instrument.base, order.state.size))
                     }
                     | (Side::Sell, OrderRole::Taker) => {
-                        // taker 必须以最新的买单价成交
-                        let required_balance = latest_bid * order.state.size;
-                        Ok((&order.instrument.base, required_balance))
+                        // taker 以最新的买单价成交，锁定的同样是 base 数量
+                        Ok((&order.instrument.base, order.state.size))
                     }
                 }
             }
@@ -291,6 +302,8 @@ impl BalanceHandler for HourglassAccount
             | InstrumentKind::Perpetual | InstrumentKind::Future => {
                 let latest_ask = order_book.latest_ask;
                 let latest_bid = order_book.latest_bid;
+                let multiplier = self.config.contract_multiplier(&order.instrument);
+                let initial_margin_rate = self.config.initial_margin_rate(&order.instrument);
                 info!("[required_available_balance] : latest_ask is {:?}", latest_ask);
                 info!("[required_available_balance] : latest_bid is {:?}", latest_bid);
 
@@ -298,45 +311,33 @@ impl BalanceHandler for HourglassAccount
                     // Buy 订单处理
                     | (Side::Buy, OrderRole::Maker) => {
                         // maker 买单，检查价格是否合理，使用指定的价格
-                        if order.state.price < latest_ask * (1.0 - max_price_deviation) {
-                            return Err(ExchangeError::OrderRejected("Buy order price is too low compared to the market".into()));
-                        }
-                        if order.state.price > latest_bid * (1.0 + max_price_deviation) {
-                            return Err(ExchangeError::OrderRejected("Buy order price is too high compared to the market".into()));
-                        }
+                        ensure_price_within_band(order.state.price, latest_bid, latest_ask, max_price_deviation)?;
                         // maker 挂单时需要按照 order.state.price 计算保证金
-                        let required_balance = order.state.price * order.state.size / self.config.global_leverage_rate;
+                        let required_balance = order.state.price * order.state.size * multiplier * initial_margin_rate;
                         Ok((&order.instrument.quote, required_balance))
                     }
                     | (Side::Buy, OrderRole::Taker) => {
                         // taker 买单，以市场卖价成交
-                        let required_balance = latest_ask * order.state.size / self.config.global_leverage_rate;
+                        let required_balance = latest_ask * order.state.size * multiplier * initial_margin_rate;
                         Ok((&order.instrument.quote, required_balance))
                     }
                     // Sell 订单处理
                     | (Side::Sell, OrderRole::Maker) => {
                         // maker 卖单，检查价格是否合理
-                        if order.state.price > latest_bid * (1.0 + max_price_deviation) {
-                            return Err(ExchangeError::OrderRejected("Sell order price is too high compared to the market".into()));
-                        }
-                        if order.state.price < latest_ask * (1.0 - max_price_deviation) {
-                            return Err(ExchangeError::OrderRejected("Sell order price is too low compared to the market".into()));
-                        }
+                        ensure_price_within_band(order.state.price, latest_bid, latest_ask, max_price_deviation)?;
                         // maker 卖单按照 order.state.price 计算
-                        let required_balance = order.state.price * order.state.size / self.config.global_leverage_rate;
+                        let required_balance = order.state.price * order.state.size * multiplier * initial_margin_rate;
                         Ok((&order.instrument.quote, required_balance))
                     }
                     | (Side::Sell, OrderRole::Taker) => {
                         // taker 卖单，以市场买价成交
-                        let required_balance = latest_bid * order.state.size / self.config.global_leverage_rate;
+                        let required_balance = latest_bid * order.state.size * multiplier * initial_margin_rate;
                         Ok((&order.instrument.quote, required_balance))
                     }
                 }
             }
             // 其他类型待实现
-            | InstrumentKind::CryptoOption => {
-                todo!("CryptoOption is not supported yet")
-            }
+            | InstrumentKind::CryptoOption => Err(ExchangeError::UnsupportedInstrumentKind),
             | InstrumentKind::CryptoLeveragedToken => {
                 todo!("CryptoLeveragedToken is not supported yet")
             }
@@ -358,9 +359,36 @@ impl BalanceHandler for HourglassAccount
             Ok(())
         }
         else {
-            Err(ExchangeError::InsufficientBalance(token.clone()))
+            Err(ExchangeError::InsufficientBalance { token: token.clone(),
+                                                     required: required_balance,
+                                                     available })
         }
     }
+
+    fn reserved_balance(&self, order: &Order<Open>) -> (Token, f64)
+    {
+        let remaining_quantity = order.state.remaining_quantity();
+        match (order.instrument.kind, order.side) {
+            | (InstrumentKind::Spot, Side::Buy) => (order.instrument.quote.clone(), order.state.price * remaining_quantity),
+            | (InstrumentKind::Spot, Side::Sell) => (order.instrument.base.clone(), remaining_quantity),
+            | _ => {
+                let multiplier = self.config.contract_multiplier(&order.instrument);
+                let initial_margin_rate = self.config.initial_margin_rate(&order.instrument);
+                (order.instrument.quote.clone(), order.state.price * remaining_quantity * multiplier * initial_margin_rate)
+            }
+        }
+    }
+}
+
+/// 挂单价格必须落在 `[latest_ask * (1 - max_price_deviation), latest_bid * (1 + max_price_deviation)]` 之内。
+fn ensure_price_within_band(price: f64, latest_bid: f64, latest_ask: f64, max_price_deviation: f64) -> Result<(), ExchangeError>
+{
+    let lower = latest_ask * (1.0 - max_price_deviation);
+    let upper = latest_bid * (1.0 + max_price_deviation);
+    if price < lower || price > upper {
+        return Err(ExchangeError::PriceOutOfBand { price, lower, upper });
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -374,7 +402,10 @@ mod tests
             states::request_open::RequestOpen,
             OrderRole,
         },
-        hourglass::account::account_handlers::{position_handler::PositionHandler, trade_handler::TradeHandler},
+        hourglass::account::{
+            account_config::InitialMarginRate,
+            account_handlers::{position_handler::PositionHandler, trade_handler::TradeHandler},
+        },
         test_utils::create_test_account,
     };
 
@@ -423,10 +454,14 @@ mod tests
                                           price: 100.0,
                                           size: 2.0,
                                           filled_quantity: 0.0,
-                                          order_role: OrderRole::Maker } };
+                                          avg_fill_price: 0.0,
+                                          reduce_only: false,
+                                          order_role: OrderRole::Maker,
+                                          tag: None,
+                                          iceberg: None } };
 
         let balance_before = account.get_balance(&Token::from("USDT")).unwrap().available;
-        let account_event = account.apply_cancel_order_changes(&order).unwrap();
+        let account_event = account.apply_cancel_order_changes(&order).await.unwrap();
 
         // 从 AccountEvent 提取 TokenBalance
         if let AccountEventKind::Balance(token_balance) = account_event.kind {
@@ -473,7 +508,8 @@ mod tests
                             side: Side::Buy,
                             state: RequestOpen { price: 100.0, // 设置一个低于市场价格的买单
                                                  size: 2.0,
-                                                 reduce_only: false } };
+                                                 reduce_only: false,
+                                                 tag: None } };
 
         match account.required_available_balance(&order, OrderRole::Maker).await {
             | Ok((_token, _required_balance)) => {
@@ -482,7 +518,7 @@ mod tests
             }
             | Err(e) => {
                 // 订单应该因价格过低而被拒绝
-                assert_eq!(e.to_string(), "Order rejected: Buy order price is too low compared to the market");
+                assert!(matches!(e, ExchangeError::PriceOutOfBand { price, .. } if price == 100.0));
             }
         }
     }
@@ -500,7 +536,8 @@ mod tests
                             side: Side::Buy,
                             state: RequestOpen { price: 16499.0,
                                                  size: 2.0,
-                                                 reduce_only: false } };
+                                                 reduce_only: false,
+                                                 tag: None } };
 
         match account.required_available_balance(&order, OrderRole::Maker).await {
             | Ok((token, required_balance)) => {
@@ -514,6 +551,103 @@ mod tests
         }
     }
 
+    #[tokio::test]
+    async fn test_initial_margin_rate_overrides_leverage_derived_margin()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let mut locked = Vec::new();
+        for initial_margin_rate in [None, Some(0.25)] {
+            let mut account = create_test_account().await;
+            let (account_event_tx, _account_event_rx) = tokio::sync::mpsc::unbounded_channel();
+            account.account_event_tx = account_event_tx;
+            account.config.global_leverage_rate = 10.0;
+            if let Some(rate) = initial_margin_rate {
+                account.config.initial_margin_rates.push(InitialMarginRate { instrument: instrument.clone(), rate });
+            }
+
+            account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                        exchange: Exchange::Hourglass,
+                                        instrument: instrument.clone(),
+                                        timestamp: 1234567,
+                                        cid: Some(ClientOrderId("initial_margin".into())),
+                                        side: Side::Buy,
+                                        state: RequestOpen { price: 16400.0,
+                                                             size: 0.5,
+                                                             reduce_only: false,
+                                                             tag: None } })
+                   .await
+                   .unwrap();
+            locked.push(10_000.0 - account.get_balance(&Token::from("USDT")).unwrap().available);
+        }
+
+        // 未配置时按 1 / 10 倍杠杆锁定，配置后按 25% 锁定
+        assert!((locked[0] - 16400.0 * 0.5 * 0.1).abs() < 1e-9);
+        assert!((locked[1] - 16400.0 * 0.5 * 0.25).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_releases_leveraged_margin_reserved_at_open()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.config.global_leverage_rate = 10.0;
+
+        let mut opened = Vec::new();
+        for (cid, side, price) in [("buy", Side::Buy, 16300.0), ("sell", Side::Sell, 16500.0)] {
+            let open = account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                                   exchange: Exchange::Hourglass,
+                                                   instrument: Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual)),
+                                                   timestamp: 1234567,
+                                                   cid: Some(ClientOrderId(cid.into())),
+                                                   side,
+                                                   state: RequestOpen { price,
+                                                                        size: 0.5,
+                                                                        reduce_only: false,
+                                                                        tag: None } })
+                              .await
+                              .unwrap();
+            opened.push(open);
+        }
+        // 买卖两侧都按 1 / 10 倍杠杆冻结 quote 保证金
+        let locked = 10_000.0 - account.get_balance(&Token::from("USDT")).unwrap().available;
+        assert!((locked - (16300.0 + 16500.0) * 0.5 * 0.1).abs() < 1e-9);
+        assert!(account.check_invariants().await.is_empty());
+
+        for open in &opened {
+            account.apply_cancel_order_changes(open).await.unwrap();
+        }
+
+        // 撤单按同一口径释放，卖单不会把保证金释放到 base 上
+        assert!((account.get_balance(&Token::from("USDT")).unwrap().available - 10_000.0).abs() < 1e-9);
+        assert_eq!(account.get_balance(&Token::from("ETH")).unwrap().available, 10.0);
+    }
+
+    #[tokio::test]
+    async fn test_open_exceeding_available_balance_is_rejected()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        // 1 倍杠杆下 16300 * 1.0 超过 10000 USDT 的可用余额
+        let result = account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                                 exchange: Exchange::Hourglass,
+                                                 instrument: Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual)),
+                                                 timestamp: 1234567,
+                                                 cid: Some(ClientOrderId("too_large".into())),
+                                                 side: Side::Buy,
+                                                 state: RequestOpen { price: 16300.0,
+                                                                      size: 1.0,
+                                                                      reduce_only: false,
+                                                                      tag: None } })
+                            .await;
+
+        assert!(matches!(result, Err(ExchangeError::InsufficientBalance { .. })));
+        assert_eq!(account.get_balance(&Token::from("USDT")).unwrap().available, 10_000.0);
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+    }
+
     #[tokio::test]
     async fn test_has_sufficient_available_balance()
     {
@@ -541,6 +675,23 @@ mod tests
         assert_eq!(balance.available, 0.0);
     }
 
+    #[tokio::test]
+    async fn test_apply_balance_deltas_rejects_unknown_token_and_negative_result()
+    {
+        let mut account = create_test_account().await;
+
+        let token = Token::from("ETH");
+        let result = account.apply_balance_deltas(&token, &[BalanceDelta::new(-4.0, -4.0), BalanceDelta::new(-7.0, -7.0)]);
+        assert!(matches!(result, Err(ExchangeError::InsufficientBalance { required, available, .. }) if required == 11.0 && available == 10.0));
+        assert_eq!(account.get_balance(&token).unwrap().total, 10.0); // 被拒绝的增量不会应用
+
+        let result = account.apply_balance_deltas(&Token::from("BTC"), &[BalanceDelta::new(1.0, 1.0)]);
+        assert!(matches!(result, Err(ExchangeError::Hourglass(_))));
+
+        let balance = account.apply_balance_deltas(&token, &[BalanceDelta::new(-12.0, -12.0), BalanceDelta::new(5.0, 5.0)]).unwrap();
+        assert_eq!(balance.available, 3.0);
+    }
+
     #[tokio::test]
     async fn test_apply_open_order_changes_buy()
     {
@@ -555,7 +706,8 @@ mod tests
                                          side: Side::Buy,
                                          state: RequestOpen { price: 1.0,
                                                               size: 2.0,
-                                                              reduce_only: false } };
+                                                              reduce_only: false,
+                                                              tag: None } };
 
         // 将订单状态从 RequestOpen 转换为 Open
         let open_order = Order { instruction: open_order_request.instruction,
@@ -569,7 +721,11 @@ mod tests
                                                price: open_order_request.state.price,
                                                size: open_order_request.state.size,
                                                filled_quantity: 0.0,
-                                               order_role: OrderRole::Maker } };
+                                               avg_fill_price: 0.0,
+                                               reduce_only: false,
+                                               order_role: OrderRole::Maker,
+                                               tag: None,
+                                               iceberg: None } };
 
         let required_balance = 2.0; // 模拟需要的余额
 
@@ -594,7 +750,8 @@ mod tests
                                          side: Side::Sell,
                                          state: RequestOpen { price: 1.0,
                                                               size: 2.0,
-                                                              reduce_only: false } };
+                                                              reduce_only: false,
+                                                              tag: None } };
 
         // 将订单状态从 RequestOpen 转换为 Open
         let open_order = Order { instruction: open_order_request.instruction,
@@ -608,7 +765,11 @@ mod tests
                                                price: open_order_request.state.price,
                                                size: open_order_request.state.size,
                                                filled_quantity: 0.0,
-                                               order_role: OrderRole::Maker } };
+                                               avg_fill_price: 0.0,
+                                               reduce_only: false,
+                                               order_role: OrderRole::Maker,
+                                               tag: None,
+                                               iceberg: None } };
 
         let required_balance = 2.0; // 模拟需要的余额
 
@@ -6,7 +6,6 @@ use crate::{
     error::ExchangeError,
     hourglass::{config_request::ConfigurationRequest, hourglass_client_local_mode::ConfigureInstrumentsResults},
     hourglass_log::{info, warn},
-    Exchange,
 };
 use async_trait::async_trait;
 use std::sync::atomic::Ordering;
@@ -20,15 +19,16 @@ use crate::{
             option::OptionPosition,
             perpetual::{PerpetualPosition, PerpetualPositionConfig},
             position_meta::PositionMeta,
+            realised_pnl::RealisedPnl,
             AccountPositions, PositionDirectionMode, PositionMarginMode,
         },
         instrument::kind::InstrumentKind,
-        trade::{ClientTrade, ClientTradeId},
+        trade::ClientTrade,
         Side,
     },
     hourglass::{
         account::{
-            account_handlers::{position_handler::PositionHandling::CloseCompleteAndReverse, trade_handler::TradeHandler},
+            account_handlers::position_handler::PositionHandling::CloseCompleteAndReverse,
             respond, HourglassAccount,
         },
         clickhouse_api::datatype::clickhouse_trade_data::MarketTrade,
@@ -36,6 +36,9 @@ use crate::{
 };
 use tokio::sync::oneshot::Sender;
 
+/// 判断反向成交是否恰好平掉仓位时允许的数量误差。
+pub const POSITION_SIZE_EPSILON: f64 = 1e-9;
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum PositionHandling
 {
@@ -83,6 +86,8 @@ pub trait PositionHandler
     async fn determine_handling_type(&self, trade: ClientTrade) -> Result<PositionHandling, ExchangeError>;
 
     async fn update_position_from_client_trade(&mut self, trade: ClientTrade) -> Result<(), ExchangeError>;
+    /// 计算一笔成交在更新仓位前将会实现的盈亏；该成交不减仓或平仓时返回 `None`。
+    async fn realised_pnl_from_trade(&self, trade: &ClientTrade) -> Result<Option<RealisedPnl>, ExchangeError>;
 
     async fn remove_position(&self, instrument: Instrument, side: Side) -> Option<Position>;
 
@@ -101,8 +106,8 @@ pub trait PositionHandler
     async fn get_position_short_config(&self, instrument: &Instrument) -> Result<Option<PerpetualPositionConfig>, ExchangeError>;
     // 更新已有仓位
     async fn update_existing_position(&mut self, trade: ClientTrade) -> Result<(), ExchangeError>;
-    // 关闭仓位
-    async fn close_position(&mut self, instrument: Instrument, side: Side) -> Result<(), ExchangeError>;
+    // 以成交完全平掉与其方向相反的仓位
+    async fn close_position(&mut self, trade: &ClientTrade) -> Result<(), ExchangeError>;
     // 关闭并反向开仓
 
     async fn check_and_handle_liquidation(&mut self, trade: &MarketTrade) -> Result<(), ExchangeError>;
@@ -251,7 +256,8 @@ impl PositionHandler for HourglassAccount
 
         match instrument.kind {
             | InstrumentKind::Spot => {
-                return Err(ExchangeError::InvalidInstrument(format!("Spots do not support positions: {:?}", instrument)));
+                // 现货成交只调整 base 与 quote 余额，不持有仓位
+                return Ok(None);
             }
             | InstrumentKind::Perpetual => {
                 let perpetual_positions = &positions.perpetual_pos_long;
@@ -268,7 +274,7 @@ impl PositionHandler for HourglassAccount
                 todo!()
             }
             | InstrumentKind::CryptoOption => {
-                todo!()
+                return Err(ExchangeError::UnsupportedInstrumentKind);
             }
             | InstrumentKind::CryptoLeveragedToken => {
                 todo!()
@@ -288,7 +294,8 @@ impl PositionHandler for HourglassAccount
 
         match instrument.kind {
             | InstrumentKind::Spot => {
-                return Err(ExchangeError::InvalidInstrument(format!("Spots do not support positions: {:?}", instrument)));
+                // 现货成交只调整 base 与 quote 余额，不持有仓位
+                return Ok(None);
             }
             | InstrumentKind::Perpetual => {
                 let perpetual_positions = &positions.perpetual_pos_short;
@@ -323,7 +330,7 @@ impl PositionHandler for HourglassAccount
         let positions = &self.positions; // 获取锁
 
         match instrument.kind {
-            | InstrumentKind::Spot => Err(ExchangeError::InvalidInstrument(format!("Spots do not support positions: {:?}", instrument))),
+            | InstrumentKind::Spot => Ok((None, None)), // 现货不持有仓位
             | InstrumentKind::Perpetual => {
                 // 获取读锁
                 let long_pos_lock = positions.perpetual_pos_long.read().await;
@@ -338,9 +345,7 @@ impl PositionHandler for HourglassAccount
             | InstrumentKind::Future => {
                 todo!()
             }
-            | InstrumentKind::CryptoOption => {
-                todo!()
-            }
+            | InstrumentKind::CryptoOption => Err(ExchangeError::UnsupportedInstrumentKind),
             | InstrumentKind::CryptoLeveragedToken => {
                 todo!()
             }
@@ -393,7 +398,8 @@ impl PositionHandler for HourglassAccount
 
         match instrument.kind {
             | InstrumentKind::Spot => {
-                return Err(ExchangeError::NotImplemented("Spot account_positions conflict check not implemented".into()));
+                // 现货不持有仓位，买卖都只是两种币之间的兑换，不存在方向冲突
+                return Ok(());
             }
             | InstrumentKind::CommodityOption | InstrumentKind::CommodityFuture => {
                 return Err(ExchangeError::NotImplemented("Commodity account_positions conflict check not implemented".into()));
@@ -463,21 +469,27 @@ impl PositionHandler for HourglassAccount
         let perpetual_config = self.handle_config_inheritance(&trade).await?;
 
         // 创建 PositionMeta 和新的 PerpetualPosition
-        let meta = match handle_type {
+        let mut meta = match handle_type {
             | PositionHandling::OpenBrandNewPosition => PositionMeta::create_from_trade(&trade),
             | CloseCompleteAndReverse { remaining_size: reverse_size } => PositionMeta::create_from_trade_with_remaining(&trade, reverse_size),
             | _ => return Err(ExchangeError::Hourglass("Not supposed to create any position here.".into())),
         };
+        meta.contract_multiplier = self.config.contract_multiplier(&trade.instrument);
 
+        // 多头的强平价格低于开仓价，空头的强平价格高于开仓价
+        let liquidation_factor = match trade.side {
+            | Side::Buy => 1.0 - liquidation_threshold / perpetual_config.leverage,
+            | Side::Sell => 1.0 + liquidation_threshold / perpetual_config.leverage,
+        };
         let (isolated_margin, liquidation_price) = match perpetual_config.pos_margin_mode {
             // Cross Mode: Use account-wide margin, no isolated margin.
             | PositionMarginMode::Cross => {
                 // Calculate margin to add to the global margin (account_margin).
-                let margin_to_add = trade.size * trade.price / perpetual_config.leverage;
+                let margin_to_add = self.config.rounding.round_margin(self.trade_notional(&trade) / perpetual_config.leverage);
                 self.account_margin.fetch_add(margin_to_add, Ordering::SeqCst);
 
                 // Calculate liquidation price in Cross Mode (it depends on account-wide margin and liquidation threshold).
-                let liquidation_price = trade.price * (1.0 - liquidation_threshold / perpetual_config.leverage);
+                let liquidation_price = self.config.rounding.round_liquidation_price(trade.price * liquidation_factor, trade.side);
 
                 // No isolated margin in Cross mode.
                 (None, liquidation_price)
@@ -486,10 +498,10 @@ impl PositionHandler for HourglassAccount
             // Isolated Mode: Calculate isolated margin and liquidation price separately.
             | PositionMarginMode::Isolated => {
                 // Calculate isolated margin.
-                let isolated_margin = Some(trade.price / perpetual_config.leverage * trade.size);
+                let isolated_margin = Some(self.config.rounding.round_margin(self.trade_notional(&trade) / perpetual_config.leverage));
 
                 // Calculate liquidation price for isolated positions.
-                let liquidation_price = trade.price * (1.0 - liquidation_threshold / perpetual_config.leverage);
+                let liquidation_price = self.config.rounding.round_liquidation_price(trade.price * liquidation_factor, trade.side);
 
                 (isolated_margin, liquidation_price)
             }
@@ -521,7 +533,8 @@ impl PositionHandler for HourglassAccount
     /// 更新 OptionPosition 的方法（占位符）
     async fn create_option_position(&mut self, _trade: ClientTrade) -> Result<OptionPosition, ExchangeError>
     {
-        todo!("Updating Option positions is not yet implemented")
+        // 成交的 `Instrument` 不含看涨/看跌，无法选择期权仓位表，见 `AccountPositions::update_position`
+        Err(ExchangeError::UnsupportedInstrumentKind)
     }
 
     #[allow(dead_code)]
@@ -614,7 +627,8 @@ impl PositionHandler for HourglassAccount
                 // 在 Net 模式下，仓位方向与交易方向相同，或者需要关闭反向仓位
                 if position_side != trade.side {
                     // 如果方向不同，可能是反向操作
-                    if current_size == trade.size {
+                    // 数量在浮点误差内相等时视为恰好平仓，避免留下数量接近零的仓位
+                    if (current_size - trade.size).abs() <= POSITION_SIZE_EPSILON {
                         Ok(PositionHandling::CloseComplete)
                     }
                     else if current_size < trade.size {
@@ -665,7 +679,9 @@ impl PositionHandler for HourglassAccount
             }
             | PositionHandling::CloseComplete => {
                 info!("executing PositionHandling::CloseComplete");
-                self.close_position(trade.instrument.clone(), trade.side).await?;
+                self.close_position(&trade).await?;
+                // 仓位已平，撤销剩余的 reduce-only 挂单
+                self.cancel_reduce_only_orders_if_flat(&trade.instrument).await?;
             }
             | PositionHandling::CloseCompleteAndReverse { remaining_size: reverse_size } => {
                 info!("executing PositionHandling::CloseCompleteAndReverse");
@@ -680,6 +696,38 @@ impl PositionHandler for HourglassAccount
         Ok(())
     }
 
+    async fn realised_pnl_from_trade(&self, trade: &ClientTrade) -> Result<Option<RealisedPnl>, ExchangeError>
+    {
+        let handling_type = self.determine_handling_type(trade.clone()).await?;
+        if !matches!(handling_type, PositionHandling::ClosePartial | PositionHandling::CloseComplete | CloseCompleteAndReverse { .. }) {
+            return Ok(None);
+        }
+
+        // 被减仓的是与成交方向相反的仓位
+        let position = match trade.side {
+            | Side::Buy => self.get_position_short(&trade.instrument).await?,
+            | Side::Sell => self.get_position_long(&trade.instrument).await?,
+        };
+        let Some(Position::Perpetual(position)) = position
+        else {
+            return Ok(None);
+        };
+
+        // 反向开仓的部分不计入实现盈亏
+        let closed_size = trade.size.min(position.meta.current_size);
+        let direction = match position.meta.side {
+            | Side::Buy => 1.0,
+            | Side::Sell => -1.0,
+        };
+        let gross_pnl = (trade.price - position.meta.current_avg_price) * closed_size * position.meta.contract_multiplier * direction;
+        let fees = if trade.size > 0.0 { trade.fees * closed_size / trade.size } else { 0.0 };
+
+        Ok(Some(RealisedPnl { instrument: trade.instrument.clone(),
+                              trade_id: trade.trade_id,
+                              amount: gross_pnl - fees,
+                              fees }))
+    }
+
     async fn remove_position(&self, instrument: Instrument, side: Side) -> Option<Position>
     {
         match instrument.kind {
@@ -806,18 +854,18 @@ impl PositionHandler for HourglassAccount
                     match position.pos_config.pos_margin_mode {
                         | PositionMarginMode::Cross => {
                             // 更新 Cross 模式下的保证金
-                            let margin_to_add = trade.size * trade.price / position.pos_config.leverage;
+                            let margin_to_add = self.config.rounding.round_margin(self.trade_notional(&trade) / position.pos_config.leverage);
                             self.account_margin.fetch_add(margin_to_add, Ordering::SeqCst);
 
                             // 更新清算价格
-                            position.liquidation_price = trade.price * (1.0 - self.config.liquidation_threshold / position.pos_config.leverage);
+                            position.liquidation_price = self.config.rounding.round_liquidation_price(trade.price * (1.0 - self.config.liquidation_threshold / position.pos_config.leverage), Side::Buy);
                         }
                         | PositionMarginMode::Isolated => {
                             // 更新 Isolated 模式下的保证金
                             self.update_isolated_margin(&mut position, &trade).await;
 
                             // 更新清算价格
-                            position.liquidation_price = trade.price * (1.0 - self.config.liquidation_threshold / position.pos_config.leverage);
+                            position.liquidation_price = self.config.rounding.round_liquidation_price(trade.price * (1.0 - self.config.liquidation_threshold / position.pos_config.leverage), Side::Buy);
                         }
                     }
 
@@ -838,17 +886,17 @@ impl PositionHandler for HourglassAccount
                     // 根据仓位模式更新保证金和清算价格
                     match position.pos_config.pos_margin_mode {
                         | PositionMarginMode::Cross => {
-                            let margin_to_add = trade.size * trade.price / position.pos_config.leverage;
+                            let margin_to_add = self.config.rounding.round_margin(self.trade_notional(&trade) / position.pos_config.leverage);
                             self.account_margin.fetch_add(margin_to_add, Ordering::SeqCst);
 
                             // 更新清算价格
-                            position.liquidation_price = trade.price * (1.0 + self.config.liquidation_threshold / position.pos_config.leverage);
+                            position.liquidation_price = self.config.rounding.round_liquidation_price(trade.price * (1.0 + self.config.liquidation_threshold / position.pos_config.leverage), Side::Sell);
                         }
                         | PositionMarginMode::Isolated => {
                             self.update_isolated_margin(&mut position, &trade).await;
 
                             // 更新清算价格
-                            position.liquidation_price = trade.price * (1.0 + self.config.liquidation_threshold / position.pos_config.leverage);
+                            position.liquidation_price = self.config.rounding.round_liquidation_price(trade.price * (1.0 + self.config.liquidation_threshold / position.pos_config.leverage), Side::Sell);
                         }
                     }
 
@@ -862,128 +910,66 @@ impl PositionHandler for HourglassAccount
     }
 
     /// FIXME 支持的金融工具太少了。
-    /// 关闭仓位
-    async fn close_position(&mut self, instrument: Instrument, side: Side) -> Result<(), ExchangeError>
+    /// 以成交 `trade` 完全平掉与其方向相反的永续合约仓位。
+    ///
+    /// 平仓记录按成交价实现盈亏，并只分摊平掉的数量对应的手续费；仓位随后从仓位表中移除，不会留下数量为零的仓位。
+    async fn close_position(&mut self, trade: &ClientTrade) -> Result<(), ExchangeError>
     {
-        match side {
-            | Side::Buy => {
-                // 处理空头仓位关闭
-                let position = self.get_position_short(&instrument).await?;
-                if let Some(Position::Perpetual(position)) = position {
-                    match position.pos_config.pos_margin_mode {
-                        | PositionMarginMode::Cross => {
-                            // 减去对应的保证金
-                            let margin_to_subtract = position.meta.current_size / position.pos_config.leverage;
-                            self.account_margin.fetch_sub(margin_to_subtract, Ordering::SeqCst);
-                            self.register_exit_position(&position.meta, side, None).await?;
-                        }
-                        | PositionMarginMode::Isolated => {
-                            // 并不清空 isolated 保证金，只需要 dump
-                            self.register_exit_position(&position.meta, side, position.isolated_margin).await?;
-                        }
-                    };
-                }
-                else {
-                    // 返回不支持的仓位类型错误
-                    return Err(ExchangeError::UnsupportedInstrumentKind);
-                }
+        if trade.instrument.kind != InstrumentKind::Perpetual {
+            return Err(ExchangeError::UnsupportedInstrumentKind);
+        }
 
-                // 使用 `ok_or` 将 `Option` 转换为 `Result`
-                self.remove_position(instrument, Side::Sell).await.ok_or(ExchangeError::AttemptToRemoveNonExistingPosition)?;
-            }
-            | Side::Sell => {
-                // 处理多头仓位关闭
-                let position = self.get_position_long(&instrument).await?;
-                if let Some(Position::Perpetual(mut position)) = position {
-                    match position.pos_config.pos_margin_mode {
-                        | PositionMarginMode::Cross => {
-                            // 减去对应的保证金
-                            let margin_to_subtract = position.meta.current_size / position.pos_config.leverage;
-                            self.account_margin.fetch_sub(margin_to_subtract, Ordering::SeqCst);
-                        }
-                        | PositionMarginMode::Isolated => {
-                            // 清空 isolated 保证金
-                            position.isolated_margin = Some(0.0);
-                        }
-                    }
-                }
-                else {
-                    // 返回不支持的仓位类型错误
-                    return Err(ExchangeError::UnsupportedInstrumentKind);
-                }
+        // 被平掉的是与成交方向相反的仓位
+        let position_side = match trade.side {
+            | Side::Buy => Side::Sell,
+            | Side::Sell => Side::Buy,
+        };
+        let Some(mut position) = self.remove_perpetual_position(trade.instrument.clone(), position_side).await
+        else {
+            return Err(ExchangeError::AttemptToRemoveNonExistingPosition);
+        };
 
-                self.remove_position(instrument, Side::Buy).await.ok_or(ExchangeError::AttemptToRemoveNonExistingPosition)?;
+        let closed_size = position.meta.current_size;
+        position.meta.update_ts = trade.timestamp;
+        position.meta.current_symbol_price = trade.price;
+        position.meta.current_fees_total += if trade.size > 0.0 { trade.fees * closed_size.min(trade.size) / trade.size } else { 0.0 };
+
+        let exit_margin = match position.pos_config.pos_margin_mode {
+            | PositionMarginMode::Cross => {
+                // 按开仓均价释放该仓位占用的保证金，与开仓时按名义价值计入的口径一致
+                let notional = position.meta.current_avg_price * closed_size * position.meta.contract_multiplier;
+                let margin_to_subtract = self.config.rounding.round_margin_release(notional / position.pos_config.leverage);
+                self.account_margin.fetch_sub(margin_to_subtract, Ordering::SeqCst);
+                None
             }
-        }
-        Ok(())
+            // 并不清空 isolated 保证金，只需要 dump
+            | PositionMarginMode::Isolated => position.isolated_margin,
+        };
+        self.register_exit_position(&position.meta, position_side, exit_margin).await
     }
 
+    /// 以本笔成交价作为标记价格，强平该金融工具上越过强平价格的仓位，见 [`HourglassAccount::liquidate_crossed_positions`]。
     async fn check_and_handle_liquidation(&mut self, trade: &MarketTrade) -> Result<(), ExchangeError>
     {
-        // 解析金融工具
-        let instrument = trade.parse_instrument().ok_or_else(|| ExchangeError::InvalidInstrument("Instrument parsing failed".to_string()))?;
-
-        // 获取多头和空头仓位
-        let (long_position, short_position) = self.get_position_both_ways(&instrument).await?;
-        // info!("Long Position: {:?}", long_position);
-        // info!("Short Position: {:?}", short_position);
-
-        // 生成新的交易 ID
-        let trade_id_value = self.client_trade_counter.fetch_add(1, Ordering::SeqCst);
-        let trade_id = ClientTradeId(trade_id_value);
-        // 检查并处理多头仓位
-        if let Some(Position::Perpetual(long_pos)) = long_position {
-            if trade.price <= long_pos.liquidation_price && trade.parse_side() == Side::Sell {
-                // 生成平仓的 `ClientTrade`
-                let liquidation_trade = ClientTrade { exchange: Exchange::Hourglass,
-                                                      timestamp: trade.timestamp,
-                                                      trade_id,
-                                                      order_id: None,
-                                                      cid: None,
-                                                      instrument: instrument.clone(),
-                                                      side: Side::Sell,
-                                                      price: trade.price,
-                                                      size: long_pos.meta.current_size,
-                                                      fees: 0.0 };
-
-                // 处理平仓
-                self.liquidate_position_by_trade(&mut Position::Perpetual(long_pos), Side::Buy).await?;
-                self.process_trade(liquidation_trade).await?;
-                return Ok(());
-            }
+        let instrument = self.resolve_market_instrument(trade)?;
+        let liquidations = self.liquidate_crossed_positions(&instrument, trade.price).await?;
+        if !liquidations.is_empty() {
+            self.cancel_reduce_only_orders_if_flat(&instrument).await?;
         }
-
-        // 检查并处理空头仓位
-        if let Some(Position::Perpetual(short_pos)) = short_position {
-            if trade.price >= short_pos.liquidation_price && trade.parse_side() == Side::Buy {
-                // 生成平仓的 `ClientTrade`
-                let liquidation_trade = ClientTrade { exchange: Exchange::Hourglass,
-                                                      timestamp: trade.timestamp,
-                                                      trade_id,
-                                                      order_id: None,
-                                                      cid: None,
-                                                      instrument: instrument.clone(),
-                                                      side: Side::Buy,
-                                                      price: trade.price,
-                                                      size: short_pos.meta.current_size,
-                                                      fees: 0.0 };
-
-                // 处理平仓
-                self.liquidate_position_by_trade(&mut Position::Perpetual(short_pos), Side::Sell).await?;
-                self.process_trade(liquidation_trade).await?;
-                return Ok(());
-            }
-        }
-
         Ok(())
     }
 
     // 关闭并反向开仓
     async fn close_and_reverse_position(&mut self, trade: ClientTrade, remaining: f64) -> Result<(), ExchangeError>
     {
-        self.close_position(trade.instrument.clone(), trade.side).await?;
+        self.close_position(&trade).await?;
+
+        // 剩余数量以成交价在另一侧开立新仓位，保证金与手续费只按剩余数量计算
+        let mut reverse_trade = trade;
+        reverse_trade.fees = if reverse_trade.size > 0.0 { reverse_trade.fees * remaining / reverse_trade.size } else { 0.0 };
+        reverse_trade.size = remaining;
         // Ignore the returned `PerpetualPosition`
-        let _ = self.create_perpetual_position(trade.clone(), CloseCompleteAndReverse { remaining_size: remaining }).await?;
+        let _ = self.create_perpetual_position(reverse_trade, CloseCompleteAndReverse { remaining_size: remaining }).await?;
         Ok(())
     }
 
@@ -1048,19 +1034,25 @@ impl PositionHandler for HourglassAccount
                     if trade.size > position.meta.current_size {
                         return Err(ExchangeError::InvalidTradeSize);
                     }
-                    position.meta.update_from_trade(&trade); // 更新 PositionMeta
-                                                             // 根据保证金模式调整保证金
+                    // 减仓并按比例实现盈亏，均价保持不变
+                    position.meta.update_ts = trade.timestamp;
+                    position.meta.current_fees_total += trade.fees;
+                    position.close_partial(trade.size, trade.price);
+                    // 按开仓均价释放平掉部分占用的保证金，与完全平仓的口径一致
+                    let closed_notional = position.meta.current_avg_price * trade.size * position.meta.contract_multiplier;
+
+                    // 根据保证金模式调整保证金
                     match position.pos_config.pos_margin_mode {
                         | PositionMarginMode::Cross => {
                             // 减去对应的 Cross 保证金
-                            let margin_to_subtract = trade.size * trade.price / position.pos_config.leverage;
+                            let margin_to_subtract = self.config.rounding.round_margin_release(closed_notional / position.pos_config.leverage);
                             self.account_margin.fetch_sub(margin_to_subtract, Ordering::SeqCst);
                         }
                         | PositionMarginMode::Isolated => {
                             // 根据平仓比例减少 Isolated 保证金
                             if let Some(isolated_margin) = position.isolated_margin {
                                 info!("isolated_margin: {}", isolated_margin);
-                                let margin_to_subtract = trade.price * trade.size / position.pos_config.leverage;
+                                let margin_to_subtract = self.config.rounding.round_margin_release(closed_notional / position.pos_config.leverage);
                                 info!("margin to subtract: {}", margin_to_subtract);
                                 position.isolated_margin = Some(isolated_margin - margin_to_subtract);
                             }
@@ -1080,19 +1072,24 @@ impl PositionHandler for HourglassAccount
                     if trade.size > position.meta.current_size {
                         return Err(ExchangeError::InvalidTradeSize);
                     }
-                    position.meta.update_from_trade(&trade); // 更新 PositionMeta
+                    // 减仓并按比例实现盈亏，均价保持不变
+                    position.meta.update_ts = trade.timestamp;
+                    position.meta.current_fees_total += trade.fees;
+                    position.close_partial(trade.size, trade.price);
+                    // 按开仓均价释放平掉部分占用的保证金，与完全平仓的口径一致
+                    let closed_notional = position.meta.current_avg_price * trade.size * position.meta.contract_multiplier;
 
                     // 根据保证金模式调整保证金
                     match position.pos_config.pos_margin_mode {
                         | PositionMarginMode::Cross => {
                             // 减去对应的 Cross 保证金
-                            let margin_to_subtract = trade.size * trade.price / position.pos_config.leverage;
+                            let margin_to_subtract = self.config.rounding.round_margin_release(closed_notional / position.pos_config.leverage);
                             self.account_margin.fetch_sub(margin_to_subtract, Ordering::SeqCst);
                         }
                         | PositionMarginMode::Isolated => {
                             // 根据平仓比例减少 Isolated 保证金
                             if let Some(isolated_margin) = position.isolated_margin {
-                                let margin_to_subtract = isolated_margin * (trade.size / position.meta.current_size);
+                                let margin_to_subtract = self.config.rounding.round_margin_release(closed_notional / position.pos_config.leverage);
                                 position.isolated_margin = Some(isolated_margin - margin_to_subtract);
                             }
                         }
@@ -1110,11 +1107,12 @@ impl PositionHandler for HourglassAccount
     async fn update_isolated_margin(&mut self, position: &mut PerpetualPosition, trade: &ClientTrade)
     {
         if let PositionMarginMode::Isolated = position.pos_config.pos_margin_mode {
+            let margin_to_add = self.config.rounding.round_margin(self.trade_notional(trade) / position.pos_config.leverage);
             if let Some(ref mut margin) = position.isolated_margin {
-                *margin += trade.price * trade.size / position.pos_config.leverage;
+                *margin += margin_to_add;
             }
             else {
-                position.isolated_margin = Some(trade.price * trade.size / position.pos_config.leverage);
+                position.isolated_margin = Some(margin_to_add);
             }
         }
     }
@@ -1125,7 +1123,12 @@ mod tests
 {
     use super::*;
     use crate::{
-        common::{order::identification::OrderId, token::Token, trade::ClientTradeId},
+        common::{
+            event::AccountEventKind,
+            order::{identification::OrderId, order_instructions::OrderInstruction, states::open::Open, Order, OrderRole},
+            token::Token,
+            trade::ClientTradeId,
+        },
         test_utils::create_test_account,
         Exchange,
     };
@@ -1168,7 +1171,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 16999.0,
                                   size: 1.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         // 插入预先配置的多头仓位 PerpetualPositionConfig
         let instrument = trade.instrument.clone();
@@ -1208,7 +1212,8 @@ mod tests
                                   side: Side::Sell,
                                   price: 100.0,
                                   size: 5.0,
-                                  fees: 0.05 };
+                                  fees: 0.05,
+                                  tag: None };
 
         // 使用与 `trade` 相同的 `instrument` 进行插入配置
         let instrument = trade.instrument.clone();
@@ -1248,7 +1253,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         // 插入多头仓位配置
         let instrument = trade.instrument.clone();
@@ -1272,7 +1278,8 @@ mod tests
                                              side: Side::Buy,
                                              price: 100.0,
                                              size: 5.0,
-                                             fees: 0.05 };
+                                             fees: 0.05,
+                                             tag: None };
 
         // 更新现有仓位
         account.update_position_from_client_trade(additional_trade.clone()).await.unwrap();
@@ -1299,7 +1306,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         // 插入多头仓位配置
         let instrument = trade.instrument.clone();
@@ -1323,7 +1331,8 @@ mod tests
                                              side: Side::Buy,
                                              price: 100.0,
                                              size: 5.0,
-                                             fees: 0.05 };
+                                             fees: 0.05,
+                                             tag: None };
 
         // 更新现有仓位
         account.update_position_from_client_trade(additional_trade.clone()).await.unwrap();
@@ -1350,7 +1359,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         // 插入多头仓位配置
         let instrument = trade.instrument.clone();
@@ -1374,7 +1384,8 @@ mod tests
                                              side: Side::Buy,
                                              price: 100.0,
                                              size: 5.0,
-                                             fees: 0.05 };
+                                             fees: 0.05,
+                                             tag: None };
 
         // 更新现有仓位
         account.update_position_from_client_trade(additional_trade.clone()).await.unwrap();
@@ -1401,7 +1412,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         // 插入多头仓位配置
         let instrument = trade.instrument.clone();
@@ -1425,7 +1437,8 @@ mod tests
                                              side: Side::Buy,
                                              price: 100.0,
                                              size: 5.0,
-                                             fees: 0.05 };
+                                             fees: 0.05,
+                                             tag: None };
 
         // 更新现有仓位
         account.update_position_from_client_trade(additional_trade.clone()).await.unwrap();
@@ -1452,7 +1465,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         // 插入多头仓位配置
         let instrument = trade.instrument.clone();
@@ -1477,7 +1491,8 @@ mod tests
                                           side: Side::Sell,
                                           price: 100.0,
                                           size: 5.0,
-                                          fees: 0.05 };
+                                          fees: 0.05,
+                                          tag: None };
 
         account.update_position_from_client_trade(closing_trade.clone()).await.unwrap();
         // // 检查仓位是否部分平仓
@@ -1502,7 +1517,8 @@ mod tests
                                   side: Side::Sell,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         // 插入多头仓位配置
         let instrument = trade.instrument.clone();
@@ -1525,7 +1541,8 @@ mod tests
                                           side: Side::Buy,
                                           price: 100.0,
                                           size: 5.0,
-                                          fees: 0.05 };
+                                          fees: 0.05,
+                                          tag: None };
 
         account.update_position_from_client_trade(closing_trade.clone()).await.unwrap();
         // // 检查仓位是否部分平仓
@@ -1550,7 +1567,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         // 插入多头仓位配置
         let instrument = trade.instrument.clone();
@@ -1574,7 +1592,8 @@ mod tests
                                           side: Side::Sell,
                                           price: 100.0,
                                           size: 5.0,
-                                          fees: 0.05 };
+                                          fees: 0.05,
+                                          tag: None };
 
         account.update_position_from_client_trade(closing_trade.clone()).await.unwrap();
         // 检查仓位是否部分平仓
@@ -1599,7 +1618,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         // 插入多头仓位配置
         let instrument = trade.instrument.clone();
@@ -1621,7 +1641,8 @@ mod tests
                                           side: Side::Sell,
                                           price: 100.0,
                                           size: 10.0,
-                                          fees: 0.1 };
+                                          fees: 0.1,
+                                          tag: None };
 
         account.update_position_from_client_trade(closing_trade.clone()).await.unwrap();
 
@@ -1646,7 +1667,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         // 插入多头仓位配置
         let instrument = trade.instrument.clone();
@@ -1668,7 +1690,8 @@ mod tests
                                           side: Side::Sell,
                                           price: 100.0,
                                           size: 15.0, // 卖出 15.0 超过当前的多头仓位
-                                          fees: 0.15 };
+                                          fees: 0.15,
+                                          tag: None };
 
         account.update_position_from_client_trade(reverse_trade.clone()).await.unwrap();
 
@@ -1684,6 +1707,102 @@ mod tests
         assert_eq!(short_position.meta.side, Side::Sell); // 检查持仓方向是否为 Sell
     }
 
+    #[tokio::test]
+    async fn test_flip_long_to_short_realises_closed_leg_and_opens_fresh_short()
+    {
+        let mut account = create_test_account().await;
+        let instrument = Instrument::new("BTC", "USDT", InstrumentKind::Perpetual);
+        let preconfig = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                                  leverage: 1.0,
+                                                  position_direction_mode: PositionDirectionMode::Net };
+        account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(), preconfig);
+
+        let open_trade = ClientTrade { exchange: Exchange::Hourglass,
+                                       timestamp: 1690000000,
+                                       trade_id: ClientTradeId(1),
+                                       order_id: Some(OrderId(1)),
+                                       cid: None,
+                                       instrument: instrument.clone(),
+                                       side: Side::Buy,
+                                       price: 100.0,
+                                       size: 10.0,
+                                       fees: 0.0,
+                                       tag: None };
+        account.update_position_from_client_trade(open_trade).await.unwrap();
+        let long_id = account.positions.perpetual_pos_long.read().await.get(&instrument).unwrap().meta.position_id.clone();
+
+        // 以 110 卖出 15：平掉 10 的多头并反手开出 5 的空头
+        let flip_trade = ClientTrade { exchange: Exchange::Hourglass,
+                                       timestamp: 1690000100,
+                                       trade_id: ClientTradeId(2),
+                                       order_id: Some(OrderId(2)),
+                                       cid: None,
+                                       instrument: instrument.clone(),
+                                       side: Side::Sell,
+                                       price: 110.0,
+                                       size: 15.0,
+                                       fees: 0.3,
+                                       tag: None };
+        let margin_before = account.account_margin.load(Ordering::SeqCst);
+        account.update_position_from_client_trade(flip_trade).await.unwrap();
+
+        assert!(!account.positions.perpetual_pos_long.read().await.contains_key(&instrument));
+        let exited = account.exited_positions.perpetual_pos_long.read().await;
+        let exit = exited.get(&long_id).unwrap();
+        assert_eq!(exit.realised_pnl, 100.0);
+        assert!((exit.exit_fees_total - 0.2).abs() < 1e-9);
+
+        let shorts = account.positions.perpetual_pos_short.read().await;
+        let short = shorts.get(&instrument).unwrap();
+        assert_eq!(short.meta.side, Side::Sell);
+        assert_eq!(short.meta.current_size, 5.0);
+        assert_eq!(short.meta.current_avg_price, 110.0);
+        assert_eq!(short.meta.enter_ts, 1690000100);
+        assert_ne!(short.meta.position_id, long_id);
+        assert!((short.meta.current_fees_total - 0.1).abs() < 1e-9);
+        // 保证金：释放多头占用的 1000，新空头只按剩余 5 张、110 的名义价值计入 550
+        assert!((account.account_margin.load(Ordering::SeqCst) - margin_before - (550.0 - 1000.0)).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_fill_equal_to_position_size_leaves_no_ghost_position()
+    {
+        let mut account = create_test_account().await;
+        let instrument = Instrument::new("BTC", "USDT", InstrumentKind::Perpetual);
+        let preconfig = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                                  leverage: 1.0,
+                                                  position_direction_mode: PositionDirectionMode::Net };
+        account.positions.perpetual_pos_short_config.write().await.insert(instrument.clone(), preconfig);
+
+        let mut trade = ClientTrade { exchange: Exchange::Hourglass,
+                                      timestamp: 1690000000,
+                                      trade_id: ClientTradeId(1),
+                                      order_id: Some(OrderId(1)),
+                                      cid: None,
+                                      instrument: instrument.clone(),
+                                      side: Side::Sell,
+                                      price: 100.0,
+                                      size: 0.1,
+                                      fees: 0.0,
+                                      tag: None };
+        account.update_position_from_client_trade(trade.clone()).await.unwrap();
+        trade.size = 0.2;
+        account.update_position_from_client_trade(trade.clone()).await.unwrap();
+
+        // 0.1 + 0.2 在浮点下不等于 0.3，仍应视为恰好平仓
+        trade.side = Side::Buy;
+        trade.price = 90.0;
+        trade.size = 0.3;
+        trade.timestamp = 1690000100;
+        account.update_position_from_client_trade(trade).await.unwrap();
+
+        assert!(account.positions.perpetual_pos_short.read().await.is_empty());
+        assert!(account.positions.perpetual_pos_long.read().await.is_empty());
+        let exited = account.exited_positions.perpetual_pos_short.read().await;
+        let exit = exited.values().next().unwrap();
+        assert!((exit.realised_pnl - 3.0).abs() < 1e-9);
+    }
+
     #[tokio::test]
     async fn test_reverse_position_after_closing_long_isolated_net()
     {
@@ -1700,7 +1819,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         // 插入多头仓位配置
         let instrument = trade.instrument.clone();
@@ -1722,7 +1842,8 @@ mod tests
                                           side: Side::Sell,
                                           price: 100.0,
                                           size: 15.0, // 卖出 15.0 超过当前的多头仓位
-                                          fees: 0.15 };
+                                          fees: 0.15,
+                                          tag: None };
 
         let _ = account.update_position_from_client_trade(reverse_trade.clone()).await;
 
@@ -1754,7 +1875,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         // 插入多头仓位配置
         let instrument = trade.instrument.clone();
@@ -1776,7 +1898,8 @@ mod tests
                                           side: Side::Sell,
                                           price: 100.0,
                                           size: 15.0, // 卖出 15.0 超过当前的多头仓位
-                                          fees: 0.15 };
+                                          fees: 0.15,
+                                          tag: None };
 
         let result = account.update_position_from_client_trade(reverse_trade.clone()).await;
         assert!(matches!(result, Err(ExchangeError::ConfigInheritanceNotAllowed)), "Unexpected error: {:?}", result);
@@ -1798,7 +1921,8 @@ mod tests
                                   side: Side::Sell,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         // 执行管理仓位逻辑，应该返回错误
         let result = account.update_position_from_client_trade(trade.clone()).await;
@@ -1821,7 +1945,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         let instrument = trade.instrument.clone();
         let preconfig = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
@@ -1852,7 +1977,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         let instrument = trade.instrument.clone();
         let preconfig = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Isolated,
@@ -1883,7 +2009,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         let instrument = trade.instrument.clone();
         let preconfig = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Isolated,
@@ -1929,7 +2056,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         let instrument = trade.instrument.clone();
         let preconfig = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Isolated,
@@ -1952,7 +2080,8 @@ mod tests
                                           side: Side::Sell,
                                           price: 100.0,
                                           size: 5.0,
-                                          fees: 0.05 };
+                                          fees: 0.05,
+                                          tag: None };
 
         account.update_position_from_client_trade(closing_trade.clone()).await.unwrap();
 
@@ -1980,7 +2109,8 @@ mod tests
                                   side: Side::Sell,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         let instrument = trade.instrument.clone();
         let preconfig = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Isolated,
@@ -2000,7 +2130,8 @@ mod tests
                                           side: Side::Buy,
                                           price: 100.0,
                                           size: 5.0,
-                                          fees: 0.05 };
+                                          fees: 0.05,
+                                          tag: None };
 
         account.update_position_from_client_trade(closing_trade.clone()).await.unwrap();
 
@@ -2010,6 +2141,46 @@ mod tests
         assert_eq!(pos.meta.current_size, 5.0); // 剩余仓位为5
     }
 
+    #[tokio::test]
+    async fn test_partial_then_full_close_releases_all_cross_margin()
+    {
+        let instrument = Instrument::from(("BTC", "USDT", InstrumentKind::Perpetual));
+        for position_side in [Side::Buy, Side::Sell] {
+            let mut account = create_test_account().await;
+            let (closing_side, config_table) = match position_side {
+                | Side::Buy => (Side::Sell, &account.positions.perpetual_pos_long_config),
+                | Side::Sell => (Side::Buy, &account.positions.perpetual_pos_short_config),
+            };
+            config_table.write().await.insert(instrument.clone(),
+                                              PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                                                        leverage: 5.0,
+                                                                        position_direction_mode: PositionDirectionMode::Net });
+            let trade = |trade_id: i64, side: Side, price: f64, size: f64| ClientTrade { exchange: Exchange::Hourglass,
+                                                                                         timestamp: 1690000000 + trade_id,
+                                                                                         trade_id: ClientTradeId(trade_id),
+                                                                                         order_id: Some(OrderId(trade_id as u64)),
+                                                                                         cid: None,
+                                                                                         instrument: instrument.clone(),
+                                                                                         side,
+                                                                                         price,
+                                                                                         size,
+                                                                                         fees: 0.0,
+                                                                                         tag: None };
+            let margin_before = account.account_margin.load(Ordering::SeqCst);
+
+            // 以 100 开仓 10 张，再以不同于开仓价的价格先平 4 张、后平剩余 6 张
+            account.update_position_from_client_trade(trade(1, position_side, 100.0, 10.0)).await.unwrap();
+            assert_eq!(account.account_margin.load(Ordering::SeqCst) - margin_before, 200.0);
+            account.update_position_from_client_trade(trade(2, closing_side, 120.0, 4.0)).await.unwrap();
+            assert_eq!(account.account_margin.load(Ordering::SeqCst) - margin_before, 120.0); // 按均价释放 4 * 100 / 5
+            account.update_position_from_client_trade(trade(3, closing_side, 90.0, 6.0)).await.unwrap();
+
+            let (long, short) = account.get_position_both_ways(&instrument).await.unwrap();
+            assert!(long.is_none() && short.is_none());
+            assert_eq!(account.account_margin.load(Ordering::SeqCst), margin_before);
+        }
+    }
+
     #[tokio::test]
     async fn test_close_short_position_completely()
     {
@@ -2027,7 +2198,8 @@ mod tests
                                   side: Side::Sell,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         let instrument = trade.instrument.clone();
         let preconfig = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Isolated,
@@ -2047,7 +2219,8 @@ mod tests
                                           side: Side::Buy,
                                           price: 100.0,
                                           size: 10.0,
-                                          fees: 0.1 };
+                                          fees: 0.1,
+                                          tag: None };
 
         account.update_position_from_client_trade(closing_trade.clone()).await.unwrap();
 
@@ -2072,7 +2245,8 @@ mod tests
                                   side: Side::Buy,
                                   price: 100.0,
                                   size: 10.0,
-                                  fees: 0.1 };
+                                  fees: 0.1,
+                                  tag: None };
 
         let instrument = trade.instrument.clone();
         let preconfig = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
@@ -2102,4 +2276,131 @@ mod tests
         let positions = account.positions.perpetual_pos_long.read().await;
         assert!(!positions.contains_key(&trade.instrument));
     }
+
+    fn create_reduce_only_order(instrument: &Instrument, side: Side, reduce_only: bool) -> Order<Open>
+    {
+        Order { instruction: OrderInstruction::Limit,
+                exchange: Exchange::Hourglass,
+                instrument: instrument.clone(),
+                timestamp: 1690000000,
+                cid: None,
+                side,
+                state: Open { id: OrderId(77),
+                              price: 120.0,
+                              size: 10.0,
+                              filled_quantity: 0.0,
+                              avg_fill_price: 0.0,
+                              reduce_only,
+                              order_role: OrderRole::Maker,
+                              tag: None,
+                              iceberg: None } }
+    }
+
+    #[tokio::test]
+    async fn test_reduce_only_orders_cancelled_when_position_closed_by_opposing_fill()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let preconfig = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                                  leverage: 1.0,
+                                                  position_direction_mode: PositionDirectionMode::Net };
+        account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(), preconfig);
+
+        let opening_trade = ClientTrade { exchange: Exchange::Hourglass,
+                                          timestamp: 1690000000,
+                                          trade_id: ClientTradeId(1),
+                                          order_id: Some(OrderId(1)),
+                                          cid: None,
+                                          instrument: instrument.clone(),
+                                          side: Side::Buy,
+                                          price: 100.0,
+                                          size: 10.0,
+                                          fees: 0.1,
+                                          tag: None };
+        account.create_perpetual_position(opening_trade, PositionHandling::OpenBrandNewPosition).await.unwrap();
+
+        // 挂一个 reduce-only 的卖单和一个普通买单
+        {
+            let orders_guard = account.account_open_book.read().await;
+            let mut orders = orders_guard.get_ins_orders_mut(&instrument).unwrap();
+            orders.add_order_open(create_reduce_only_order(&instrument, Side::Sell, true));
+            orders.add_order_open(create_reduce_only_order(&instrument, Side::Buy, false));
+        }
+
+        // 反向成交完全平仓
+        let closing_trade = ClientTrade { exchange: Exchange::Hourglass,
+                                          timestamp: 1690000100,
+                                          trade_id: ClientTradeId(2),
+                                          order_id: Some(OrderId(2)),
+                                          cid: None,
+                                          instrument: instrument.clone(),
+                                          side: Side::Sell,
+                                          price: 110.0,
+                                          size: 10.0,
+                                          fees: 0.1,
+                                          tag: None };
+        account.update_position_from_client_trade(closing_trade).await.unwrap();
+
+        let orders = account.account_open_book.read().await.fetch_all();
+        assert_eq!(orders.len(), 1);
+        assert!(!orders[0].state.reduce_only);
+
+        let mut cancelled = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            if let AccountEventKind::OrdersCancelled(orders) = event.kind {
+                cancelled.extend(orders);
+            }
+        }
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].side, Side::Sell);
+    }
+
+    #[tokio::test]
+    async fn test_reduce_only_orders_cancelled_when_position_liquidated()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let preconfig = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Isolated,
+                                                  leverage: 5.0,
+                                                  position_direction_mode: PositionDirectionMode::Net };
+        account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(), preconfig);
+
+        let opening_trade = ClientTrade { exchange: Exchange::Hourglass,
+                                          timestamp: 1690000000,
+                                          trade_id: ClientTradeId(1),
+                                          order_id: Some(OrderId(1)),
+                                          cid: None,
+                                          instrument: instrument.clone(),
+                                          side: Side::Buy,
+                                          price: 100.0,
+                                          size: 10.0,
+                                          fees: 0.1,
+                                          tag: None };
+        account.create_perpetual_position(opening_trade, PositionHandling::OpenBrandNewPosition).await.unwrap();
+        account.account_open_book
+               .read()
+               .await
+               .get_ins_orders_mut(&instrument)
+               .unwrap()
+               .add_order_open(create_reduce_only_order(&instrument, Side::Sell, true));
+
+        let liquidation_triggering_trade = MarketTrade { timestamp: 1690000100,
+                                                         price: 11.0,
+                                                         exchange: "binance-futures".to_string(),
+                                                         symbol: "ETHUSDT".to_string(),
+                                                         amount: 10.0,
+                                                         side: "Sell".to_string() };
+        account.check_and_handle_liquidation(&liquidation_triggering_trade).await.unwrap();
+
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+        let cancelled_events = std::iter::from_fn(|| event_rx.try_recv().ok()).filter(|event| matches!(event.kind, AccountEventKind::OrdersCancelled(_)))
+                                                                              .count();
+        assert_eq!(cancelled_events, 1);
+    }
 }
@@ -1,23 +1,25 @@
 use crate::{
     common::{
+        balance::BalanceDelta,
+        datafeed::market_event::MarketEvent,
         event::{AccountEvent, AccountEventKind},
-        instrument::{kind::InstrumentKind, Instrument},
+        instrument::kind::InstrumentKind,
         order::OrderRole,
-        token::Token,
         trade::ClientTrade,
-        Side,
     },
     error::ExchangeError,
     hourglass::{
         account::{
-            account_config::{FeesQuerier, HourglassMode},
+            account_config::{FeesQuerier, HourglassMode, UnsizedTradeHandling},
             account_handlers::{balance_handler::BalanceHandler, position_handler::PositionHandler},
+            account_order_flow::OrderFlowMessage,
             HourglassAccount,
         },
         clickhouse_api::datatype::{
             clickhouse_trade_data::MarketTrade,
             single_level_order_book::{OrderBookUpdater, SingleLevelOrderBook},
         },
+        matching_engine::MatchingRules,
     },
     Exchange,
 };
@@ -34,16 +36,34 @@ pub trait TradeHandler
     async fn create_or_update_single_level_orderbook_from_market_trade(&mut self, trade: &MarketTrade);
     async fn handle_trade_data(&mut self, trade: &MarketTrade) -> Result<(), ExchangeError>;
 
+    /// 处理带有接收时间的行情事件：`exchange_ts` 作为成交的发生时间推进账户时钟，
+    /// `received_ts` 决定哪些挂单已经可见并参与本笔成交的撮合，见 [`Self::handle_received_trade_data`]。
+    async fn handle_market_event(&mut self, event: &MarketEvent<MarketTrade>) -> Result<(), ExchangeError>;
+
+    /// 与 [`Self::handle_trade_data`] 相同，但以 `received_ts` 判断挂单的可见性：
+    /// 时间戳不晚于 `received_ts` 的挂单参与撮合，到期时间不晚于 `received_ts` 的延迟撤单先于撮合生效。
+    /// `handle_trade_data` 等价于 `received_ts` 等于成交时间戳。
+    async fn handle_received_trade_data(&mut self, trade: &MarketTrade, received_ts: i64) -> Result<(), ExchangeError>;
+
     async fn match_orders(&mut self, market_trade: &MarketTrade) -> Result<Vec<ClientTrade>, ExchangeError>;
 
+    /// 与 [`Self::match_orders`] 相同，时间戳不晚于 `received_ts` 的挂单才参与撮合。
+    async fn match_orders_received_at(&mut self, market_trade: &MarketTrade, received_ts: i64) -> Result<Vec<ClientTrade>, ExchangeError>;
+
     async fn fees_percent(&self, instrument_kind: &InstrumentKind, role: OrderRole) -> Result<f64, ExchangeError>;
     /// 处理客户端交易列表并更新账户余额及交易事件。
     ///
     /// 该方法接收多个 `ClientTrade` 实例，并依次处理每笔交易：
     ///
-    /// 1. 更新账户的相关余额信息。
+    /// 1. 更新账户的相关余额信息，已配置仓位的永续合约成交同时更新仓位，减仓或平仓实现的盈亏与手续费作为一笔净额交易计入报价货币余额。
     /// 2. 发送交易事件 `AccountEventKind::Trade`。
     /// 3. 发送余额更新事件 `AccountEventKind::Balance`。
+    /// 4. 仓位有更新时发送仓位快照 `AccountEventKind::Positions`。
+    ///
+    /// # 事件顺序
+    ///
+    /// 同一笔成交产生的事件使用同一个交易所时间戳，并严格按 Trade、Balance、Positions 的顺序连续发送，
+    /// 不会与其他成交的事件交错，客户端可以据此在收到 Positions 时认为该笔成交的余额变化已经到达。
     ///
     /// # 参数
     ///
@@ -52,6 +72,7 @@ pub trait TradeHandler
     /// # 错误处理
     ///
     /// * 如果在应用交易变化时发生错误，会记录警告日志并继续处理下一笔交易。
+    /// * 余额已结算但仓位更新失败时，照常发送 Trade 与 Balance 事件后返回该错误；未配置仓位的金融工具不视为错误。
     /// * 如果发送交易事件或余额事件失败，也会记录警告日志。
     ///
     /// # 注意事项
@@ -82,7 +103,13 @@ impl TradeHandler for HourglassAccount
     /// - 该函数异步锁定了 `single_level_order_book`，并且通过 `.await` 实现对共享数据的安全访问。
     async fn create_or_update_single_level_orderbook_from_market_trade(&mut self, trade: &MarketTrade)
     {
-        let instrument = trade.parse_instrument().unwrap();
+        let instrument = match self.resolve_market_instrument(trade) {
+            | Ok(instrument) => instrument,
+            | Err(err) => {
+                warn!("Failed to resolve instrument for market trade {}: {:?}", trade.symbol, err);
+                return;
+            }
+        };
         let mut orderbook = self.single_level_order_book.lock().await;
 
         orderbook.entry(instrument)
@@ -93,13 +120,60 @@ impl TradeHandler for HourglassAccount
     /// 处理交易数据的方法
     async fn handle_trade_data(&mut self, trade: &MarketTrade) -> Result<(), ExchangeError>
     {
-        // 更新时间戳
+        self.handle_received_trade_data(trade, trade.timestamp).await
+    }
+
+    async fn handle_market_event(&mut self, event: &MarketEvent<MarketTrade>) -> Result<(), ExchangeError>
+    {
+        let trade = MarketTrade { timestamp: event.exchange_ts,
+                                  ..event.kind.clone() };
+        self.handle_received_trade_data(&trade, event.received_ts).await
+    }
+
+    async fn handle_received_trade_data(&mut self, trade: &MarketTrade, received_ts: i64) -> Result<(), ExchangeError>
+    {
+        // 更新时间戳，若本次更新跨过了预热截止时间，则发送一次性的预热结束事件
+        let was_warming_up = self.is_warming_up();
         self.update_exchange_ts(trade.timestamp);
+        if was_warming_up && !self.is_warming_up() {
+            if let Some(warmup_until_ts) = self.config.warmup_until_ts {
+                if let Err(err) = self.account_event_tx.send(AccountEvent { exchange_timestamp: self.exchange_timestamp.load(Ordering::SeqCst),
+                                                                            exchange: Exchange::Hourglass,
+                                                                            kind: AccountEventKind::WarmUpCompleted(warmup_until_ts) })
+                {
+                    warn!("Client offline - Failed to send AccountEvent::WarmUpCompleted: {:?}", err);
+                }
+            }
+        }
+        // 维护 tick：滚动成交额的窗口随时间戳滑动，阶梯费率档位随之降级
+        self.prune_traded_volume();
+        // 维护 tick：计提杠杆代币持仓的管理费
+        self.accrue_leveraged_token_management_fees().await;
+        // 维护 tick：跨过资金费结算时点时结算永续合约持仓的资金费
+        self.apply_funding(trade.timestamp).await;
         // 更新单层OrderBook，注意 这个做法仅仅适用于回测。
         self.create_or_update_single_level_orderbook_from_market_trade(trade).await;
+        // 没有深度数据时按配置围绕成交价铺设合成流动性
+        self.seed_synthetic_depth(trade).await;
         // 用交易所记录的用户的挂单去匹配 market_rade 以实现模拟的目的
         self.check_and_handle_liquidation(trade).await?;
-        self.match_orders(&trade).await?;
+        // 先执行在本次成交之前已生效的延迟撤单，尚未生效的撤单不影响撮合
+        self.process_due_cancels(received_ts).await;
+        // 时间戳推进后撤销已经到期的 GTT 挂单
+        self.cancel_expired_orders().await?;
+        // 被本笔成交触发的条件单先转为普通订单，再一起参与撮合
+        self.trigger_stop_orders(trade).await?;
+        // 一组 OCO 订单的两笔都会被本笔成交撮合时，只保留离成交价较近的一笔
+        self.resolve_oco_races(trade).await?;
+        self.match_orders_received_at(trade, received_ts).await?;
+        // 撮合后按配置清理久未成交且远离最新成交价的挂单
+        if self.config.stale_order_policy.is_some() {
+            let instrument = self.resolve_market_instrument(trade)?;
+            self.prune_stale_orders(&instrument, trade.price).await?;
+        }
+        // 按配置的节奏推送保证金快照
+        self.emit_margin_update_if_due().await;
+        self.assert_invariants(trade).await;
         Ok(())
     }
 
@@ -121,55 +195,69 @@ impl TradeHandler for HourglassAccount
     /// 1. 从市场交易事件中解析出基础货币和报价货币，并确定金融工具种类。
     /// 2. 查找与该金融工具相关的挂单（`InstrumentOrders`）。
     /// 3. 根据市场事件的方向（买或卖）尝试匹配相应的挂单（买单匹配卖单，卖单匹配买单）。
-    /// 4. 使用订单的 `OrderRole` 来计算手续费，并生成交易记录。
+    /// 4. 使用订单的 `OrderRole` 来计算手续费，并生成交易记录：开单时已挂在订单簿上的挂单按 Maker 费率，
+    ///    到达时即可与对手方成交的订单按 Taker 费率；Taker 订单部分成交后，剩余部分之后的成交按 Maker 费率。
     /// 5. 处理并返回生成的交易记录。
+    /// 6. 为每笔被成交的挂单发送 `OrdersPartiallyFilled`（完全成交时为 `OrdersFilled`），数量为累计成交数量。
+    ///
+    /// 部分成交的挂单以剩余数量留在订单簿中，由之后的外部成交继续撮合；外部成交量超过挂单剩余数量时，
+    /// 多出的成交量继续分配给同价位及次优价位的其他挂单。
     ///
     /// # 注意
     /// 该函数假设市场交易事件的符号格式为 `base_quote`，并从中解析出基础货币和报价货币。
     /// 如果找不到与市场事件相关的挂单，函数会记录警告并返回一个空的交易向量。
     async fn match_orders(&mut self, market_trade: &MarketTrade) -> Result<Vec<ClientTrade>, ExchangeError>
+    {
+        self.match_orders_received_at(market_trade, market_trade.timestamp).await
+    }
+
+    async fn match_orders_received_at(&mut self, market_trade: &MarketTrade, received_ts: i64) -> Result<Vec<ClientTrade>, ExchangeError>
     {
         // println!("[match_orders]: market_trade: {:?}", market_trade);
         let mut trades = Vec::new();
+        let mut roles = Vec::new();
+        let mut filled_orders = Vec::new();
 
-        // 从市场交易事件的符号中解析基础货币和报价货币，并确定金融工具种类
-        let base = Token::from(market_trade.parse_base().ok_or_else(|| ExchangeError::Hourglass("Unknown base.".to_string()))?);
-        let quote = Token::from(market_trade.parse_quote().ok_or_else(|| ExchangeError::Hourglass("Unknown quote.".to_string()))?);
-        let kind = market_trade.parse_kind();
-        // println!("[match_orders]: kind is {}", kind);
-        let instrument = Instrument { base, quote, kind };
+        // 通过别名表从市场交易事件的符号中解析出规范的金融工具
+        let instrument = self.resolve_market_instrument(market_trade)?;
         // println!("[match_orders]: instrument is {}", instrument);
 
         // 查找与指定金融工具相关的挂单
         if let Ok(mut instrument_orders) = self.account_open_book.read().await.get_ins_orders_mut(&instrument) {
-            // 确定市场事件匹配的挂单方向（买或卖）
-            if let Some(matching_side) = instrument_orders.determine_matching_side(market_trade) {
-                // println!("[match_orders]: matching side is {}, will look up in corresponding open orders", matching_side);
-                match matching_side {
-                    | Side::Buy => {
-                        // 从最佳买单中提取 `OrderRole` 以获取正确的手续费比例
-                        if let Some(best_bid) = instrument_orders.bids.last() {
-                            let order_role = best_bid.state.order_role;
-                            // println!("[match_orders]: order_role: {:?}", order_role);
-                            let fees_percent = self.fees_percent(&kind, order_role).await.map_err(|_| ExchangeError::Hourglass("Missing fees.".to_string()))?;
-
-                            // 使用计算出的手续费比例匹配买单
-                            trades.append(&mut instrument_orders.match_bids(market_trade, fees_percent, &self.client_trade_counter));
-                        }
-                    }
-                    | Side::Sell => {
-                        // 从最佳卖单中提取 `OrderRole` 以获取正确的手续费比例
-                        if let Some(best_ask) = instrument_orders.asks.last() {
-                            let order_role = best_ask.state.order_role;
-                            // println!("[match_orders]: order_role: {:?}", order_role);
-                            let fees_percent = self.fees_percent(&kind, order_role).await.map_err(|_| ExchangeError::Hourglass("Missing fees.".to_string()))?;
-
-                            // 使用计算出的手续费比例匹配卖单
-                            trades.append(&mut instrument_orders.match_asks(market_trade, fees_percent, &self.client_trade_counter));
-                        }
-                    }
+            // 由该金融工具的撮合器分配外部成交量：优先使用自定义撮合器，否则按配置的内置算法
+            let rules = MatchingRules { fill_price_policy: self.config.fill_price_policy,
+                                        fill_trigger: self.config.maker_fill_trigger,
+                                        slippage_model: self.config.slippage_model };
+            // 撮合器按成交的时间戳判断挂单是否已到达，这里换成接收时间
+            let visible_trade = MarketTrade { timestamp: received_ts,
+                                              ..market_trade.clone() };
+            // 成交量未知的外部成交按配置处理：只触价时不撮合，视为流动性无限时以足以成交全部挂单的数量撮合
+            let sized_trade = match (market_trade.has_size(), self.config.unsized_trade_handling) {
+                | (true, _) => Some(visible_trade),
+                | (false, UnsizedTradeHandling::TouchOnly) => None,
+                | (false, UnsizedTradeHandling::FillTouched) => {
+                    let resting_quantity = instrument_orders.bids.iter().chain(instrument_orders.asks.iter()).map(|order| order.state.remaining_quantity()).sum();
+                    Some(MarketTrade { amount: resting_quantity,
+                                       ..visible_trade })
+                }
+            };
+            let fills = match (sized_trade, self.matching_engines.get_mut(&instrument)) {
+                | (None, _) => Vec::new(),
+                | (Some(trade), Some(engine)) => engine.match_trade(&mut instrument_orders, &trade, &rules),
+                | (Some(trade), None) => self.config.matching_algorithm(&instrument).match_trade(&mut instrument_orders, &trade, &rules),
+            };
+            // 每笔成交按对应挂单的 `OrderRole` 由 CommissionProvider 计费
+            let commission = |trade: &ClientTrade, role: OrderRole| self.commission(trade, role);
+            trades = instrument_orders.client_trades_from_fills(market_trade.timestamp, &fills, &commission, &self.client_trade_counter)?;
+            roles = fills.iter().map(|fill| fill.order.state.order_role).collect();
+            // 到达时可立即成交的 Taker 挂单在本次成交后仍有剩余时，剩余部分已经挂在订单簿上，之后的成交按 Maker 计费
+            let orders = &mut *instrument_orders;
+            for order in orders.bids.iter_mut().chain(orders.asks.iter_mut()) {
+                if order.state.order_role == OrderRole::Taker && fills.iter().any(|fill| fill.order.state.id == order.state.id) {
+                    order.state.order_role = OrderRole::Maker;
                 }
             }
+            filled_orders = fills.into_iter().map(|fill| fill.order).collect();
         }
         else {
             // 记录日志并继续，不返回错误
@@ -177,7 +265,25 @@ impl TradeHandler for HourglassAccount
         }
 
         // println!("[match_orders]: generated client trades are: {:?}", trades);
+        // Maker 挂单由外部成交触发，主动方是外部对手方；到达时可立即成交的 Taker 挂单本身是主动方
+        for (trade, role) in trades.iter().zip(roles) {
+            self.record_own_fill_prints(std::slice::from_ref(trade), role);
+        }
         self.process_trades(trades.clone()).await;
+        // 每笔成交之后按挂单的剩余数量发送 `OrdersPartiallyFilled`，最后一笔使挂单完全成交时发送 `OrdersFilled`
+        for order in &filled_orders {
+            if let Err(err) = self.send_account_event(AccountEvent { exchange_timestamp: self.exchange_timestamp.load(Ordering::SeqCst),
+                                                                     exchange: Exchange::Hourglass,
+                                                                     kind: Self::fill_state_event_kind(order) })
+            {
+                warn!("Client offline - Failed to send fill state event: {:?}", err);
+            }
+        }
+        // 成交的挂单若属于 OCO 订单组，撤销同组的另一笔
+        self.cancel_oco_siblings(&trades).await?;
+
+        // 本次撮合是已到达的 IOC 挂单唯一的成交机会，撮合后撤销其未成交的剩余部分
+        self.cancel_unfilled_immediate_or_cancel_orders(&instrument, received_ts).await?;
 
         Ok(trades)
     }
@@ -218,9 +324,15 @@ impl TradeHandler for HourglassAccount
     ///
     /// 该方法接收多个 `ClientTrade` 实例，并依次处理每笔交易：
     ///
-    /// 1. 更新账户的相关余额信息。
+    /// 1. 更新账户的相关余额信息，已配置仓位的永续合约成交同时更新仓位，减仓或平仓实现的盈亏与手续费作为一笔净额交易计入报价货币余额。
     /// 2. 发送交易事件 `AccountEventKind::Trade`。
     /// 3. 发送余额更新事件 `AccountEventKind::Balance`。
+    /// 4. 仓位有更新时发送仓位快照 `AccountEventKind::Positions`。
+    ///
+    /// # 事件顺序
+    ///
+    /// 同一笔成交产生的事件使用同一个交易所时间戳，并严格按 Trade、Balance、Positions 的顺序连续发送，
+    /// 不会与其他成交的事件交错，客户端可以据此在收到 Positions 时认为该笔成交的余额变化已经到达。
     ///
     /// # 参数
     ///
@@ -229,6 +341,7 @@ impl TradeHandler for HourglassAccount
     /// # 错误处理
     ///
     /// * 如果在应用交易变化时发生错误，会记录警告日志并继续处理下一笔交易。
+    /// * 余额已结算但仓位更新失败时，照常发送 Trade 与 Balance 事件后返回该错误；未配置仓位的金融工具不视为错误。
     /// * 如果发送交易事件或余额事件失败，也会记录警告日志。
     ///
     /// # 注意事项
@@ -238,8 +351,27 @@ impl TradeHandler for HourglassAccount
     {
         let exchange_timestamp = self.exchange_timestamp.load(Ordering::SeqCst);
 
-        // 直接调用 `self.apply_trade_changes` 来处理余额更新
-        let balance_event = match self.apply_trade_changes(&trade).await {
+        // 已配置仓位的永续合约成交同步更新仓位，未配置仓位的金融工具不跟踪仓位；减仓或平仓时先按更新前的仓位计算实现盈亏
+        let mut realised_pnl = None;
+        let position_update = match trade.instrument.kind {
+            | InstrumentKind::Perpetual => match self.realised_pnl_from_trade(&trade).await {
+                | Ok(pnl) => {
+                    realised_pnl = pnl;
+                    Some(self.update_position_from_client_trade(trade.clone()).await)
+                }
+                | Err(err) => Some(Err(err)),
+            },
+            | _ => None,
+        };
+        let positions_updated = matches!(position_update, Some(Ok(())));
+
+        // 减仓或平仓实现的盈亏（不含手续费）与手续费作为一笔净额交易计入报价货币，
+        // 手续费先于盈亏结算时不会因余额暂时为负被拒绝，Balance 事件携带结算后的余额
+        let quote_settlement: Vec<BalanceDelta> = match (positions_updated, &realised_pnl) {
+            | (true, Some(pnl)) => vec![BalanceDelta::new(pnl.amount + pnl.fees, pnl.amount + pnl.fees)],
+            | _ => Vec::new(),
+        };
+        let balance_event = match self.settle_trade(&trade, &quote_settlement).await {
             | Ok(event) => event,
             | Err(err) => {
                 warn!("Failed to update balance: {:?}", err);
@@ -247,20 +379,35 @@ impl TradeHandler for HourglassAccount
             }
         };
 
-        // 发送交易事件
-        if let Err(err) = self.account_event_tx.send(AccountEvent { exchange_timestamp,
-                                                                    exchange: Exchange::Hourglass,
-                                                                    kind: AccountEventKind::Trade(trade.clone()) /* 发送交易事件 */ })
-        {
-            warn!("Client offline - Failed to send AccountEvent::Trade: {:?}", err);
-        }
+        self.record_order_flow(OrderFlowMessage::Fill);
+        self.record_traded_volume(&trade);
+        self.record_trade_history(&trade);
 
-        // 发送余额更新事件
-        if let Err(err) = self.account_event_tx.send(balance_event) {
-            warn!("Client offline - Failed to send AccountEvent::Balance: {:?}", err);
+        // 按 Trade、Balance、Positions、RealisedPnl 的顺序以同一个时间戳发送
+        let mut kinds = vec![AccountEventKind::Trade(trade), balance_event.kind];
+        if positions_updated {
+            kinds.push(AccountEventKind::Positions(Box::new(self.positions.snapshot().await)));
+            if let Some(realised_pnl) = realised_pnl {
+                kinds.push(AccountEventKind::RealisedPnl(realised_pnl));
+            }
+        }
+        for kind in kinds {
+            if let Err(err) = self.account_event_tx.send(AccountEvent { exchange_timestamp,
+                                                                        exchange: Exchange::Hourglass,
+                                                                        kind })
+            {
+                warn!("Client offline - Failed to send AccountEvent: {:?}", err);
+            }
         }
 
-        Ok(())
+        // 余额已经结算，仓位更新失败时在发送事件后返回错误
+        match position_update {
+            | Some(Err(ExchangeError::ConfigMissing)) | Some(Ok(())) | None => Ok(()),
+            | Some(Err(err)) => {
+                warn!("Failed to update position: {:?}", err);
+                Err(err)
+            }
+        }
     }
 
     async fn process_trades(&mut self, client_trades: Vec<ClientTrade>)
@@ -290,14 +437,25 @@ mod tests
 {
     use super::*;
     use crate::{
-        common::order::{
-            identification::{client_order_id::ClientOrderId, OrderId},
-            order_instructions::OrderInstruction,
-            states::{open::Open, request_cancel::RequestCancel, request_open::RequestOpen},
-            Order,
+        common::{
+            instrument::Instrument,
+            order::{
+                identification::{client_order_id::ClientOrderId, OrderId},
+                order_instructions::OrderInstruction,
+                states::{open::Open, request_cancel::RequestCancel, request_open::RequestOpen},
+                Order,
+            },
+            account_positions::{perpetual::PerpetualPositionConfig, PositionDirectionMode, PositionMarginMode},
+            token::Token,
+            trade::ClientTradeId,
+            Side,
         },
-        hourglass::account::account_handlers::trade_handler::TradeHandler,
-        test_utils::create_test_account,
+        hourglass::account::{
+            account_config::{FillPricePolicy, MakerFillTrigger, SlippageModel},
+            account_handlers::trade_handler::TradeHandler,
+            account_latency::{AccountLatency, FluctuationMode},
+        },
+        test_utils::{create_test_account, create_test_order_open},
     };
 
     #[tokio::test]
@@ -345,7 +503,8 @@ mod tests
                                  side: Side::Sell,
                                  state: RequestOpen { reduce_only: false,
                                                       price: 16406.0,
-                                                      size: 2.0 } };
+                                                      size: 2.0,
+                                                      tag: None } };
 
         // 将订单添加到账户
         let result = account.atomic_open(open_order.clone()).await;
@@ -393,7 +552,8 @@ mod tests
                                  side: Side::Sell,
                                  state: RequestOpen { reduce_only: false,
                                                       price: 16406.0,
-                                                      size: 2.0 } };
+                                                      size: 2.0,
+                                                      tag: None } };
 
         // 将订单添加到账户
         let result = account.atomic_open(open_order.clone()).await;
@@ -421,10 +581,60 @@ mod tests
         assert_eq!(quote_balance.total, 60000.); // NOTE this is correct remaining total
     }
 
+    #[tokio::test]
+    async fn test_orders_rejected_during_warmup_and_allowed_after()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        account.config.warmup_until_ts = Some(1625247600000);
+        account.exchange_timestamp.store(0, Ordering::SeqCst);
+
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let open_order = Order { instruction: OrderInstruction::Limit,
+                                 exchange: Exchange::Hourglass,
+                                 instrument: instrument.clone(),
+                                 timestamp: 1625247600000,
+                                 cid: Some(ClientOrderId("validCID789".into())),
+                                 side: Side::Buy,
+                                 state: RequestOpen { reduce_only: false,
+                                                      price: 16000.0,
+                                                      size: 0.1,
+                                                      tag: None } };
+
+        // 预热期内开单应被拒绝
+        let result = account.atomic_open(open_order.clone()).await;
+        assert_eq!(result.unwrap_err(), ExchangeError::WarmUpInProgress(1625247600000));
+
+        // 行情推进到预热截止时间，应发送一次预热结束事件
+        let market_event = MarketTrade { exchange: "binance-futures".to_string(),
+                                         symbol: "ETH_USDT".to_string(),
+                                         timestamp: 1625247600000,
+                                         price: 16400.0,
+                                         side: Side::Buy.to_string(),
+                                         amount: 1.0 };
+        account.handle_trade_data(&market_event).await.unwrap();
+        account.handle_trade_data(&market_event).await.unwrap();
+
+        let mut warmup_events = 0;
+        while let Ok(event) = event_rx.try_recv() {
+            if let AccountEventKind::WarmUpCompleted(ts) = event.kind {
+                assert_eq!(ts, 1625247600000);
+                warmup_events += 1;
+            }
+        }
+        assert_eq!(warmup_events, 1);
+
+        // 预热结束后开单应被接受
+        assert!(account.atomic_open(open_order).await.is_ok());
+    }
+
     #[tokio::test]
     async fn test_get_open_orders_should_be_empty_after_matching()
     {
         let mut account = create_test_account().await;
+        // 市场成交价恰好等于挂单价，需要按触价成交
+        account.config.maker_fill_trigger = MakerFillTrigger::OnTouch;
 
         let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
 
@@ -440,7 +650,11 @@ mod tests
                                                price: 100.0,
                                                size: 2.0,
                                                filled_quantity: 0.0,
-                                               order_role: OrderRole::Maker } };
+                                               avg_fill_price: 0.0,
+                                               reduce_only: false,
+                                               order_role: OrderRole::Maker,
+                                               tag: None,
+                                               iceberg: None } };
         account.account_open_book.write().await.get_ins_orders_mut(&instrument).unwrap().add_order_open(open_order.clone());
 
         // 匹配一个完全匹配的市场事件
@@ -457,6 +671,46 @@ mod tests
         assert!(orders.is_empty(), "Expected no open orders after full match, but found some.");
     }
 
+    #[tokio::test]
+    async fn test_unsized_trade_touches_only_unless_configured_to_fill()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let mut remaining = Vec::new();
+        for handling in [UnsizedTradeHandling::TouchOnly, UnsizedTradeHandling::FillTouched] {
+            let mut account = create_test_account().await;
+            account.config.unsized_trade_handling = handling;
+            let open_order = Order { instruction: OrderInstruction::Limit,
+                                     exchange: Exchange::Hourglass,
+                                     instrument: instrument.clone(),
+                                     timestamp: 1625247600000,
+                                     cid: Some(ClientOrderId("validCID123".into())),
+                                     side: Side::Buy,
+                                     state: Open { id: OrderId::new(0, 0, 0),
+                                                   price: 100.0,
+                                                   size: 2.0,
+                                                   filled_quantity: 0.0,
+                                                   avg_fill_price: 0.0,
+                                                   reduce_only: false,
+                                                   order_role: OrderRole::Maker,
+                                                   tag: None,
+                                                   iceberg: None } };
+            account.account_open_book.write().await.get_ins_orders_mut(&instrument).unwrap().add_order_open(open_order);
+
+            // 成交价穿过挂单价，但成交量未知
+            let market_event = MarketTrade { exchange: "binance-futures".to_string(),
+                                             symbol: "ETHUSDT".to_string(),
+                                             timestamp: 1625247600000,
+                                             price: 99.0,
+                                             side: Side::Sell.to_string(),
+                                             amount: 0.0 };
+            let trades = account.match_orders(&market_event).await.unwrap();
+            assert_eq!(trades.len(), if handling == UnsizedTradeHandling::TouchOnly { 0 } else { 1 });
+            remaining.push(account.account_open_book.read().await.fetch_all().len());
+        }
+
+        assert_eq!(remaining, vec![1, 0]);
+    }
+
     #[tokio::test]
     async fn test_fail_to_open_limit_order_due_to_insufficient_funds()
     {
@@ -479,13 +733,17 @@ mod tests
                                          side: Side::Buy,
                                          state: RequestOpen { price: 16499.0,
                                                               size: 5.0,
-                                                              reduce_only: false } };
+                                                              reduce_only: false,
+                                                              tag: None } };
 
         let result = account.atomic_open(open_order_request).await;
 
         // 断言开单失败，且返回的错误是余额不足
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), ExchangeError::InsufficientBalance(instrument.quote));
+        assert_eq!(result.unwrap_err(),
+                   ExchangeError::InsufficientBalance { token: instrument.quote,
+                                                        required: 16499.0 * 5.0,
+                                                        available: 1.0 });
     }
 
     #[tokio::test]
@@ -507,4 +765,367 @@ mod tests
         // 验证时间戳是否已更新
         assert_eq!(account.get_exchange_ts().unwrap(), 1625247600000);
     }
+
+    #[tokio::test]
+    async fn test_fill_events_share_timestamp_in_trade_balance_positions_order()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(),
+                                                                         PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                                                                                   leverage: 1.0,
+                                                                                                   position_direction_mode: PositionDirectionMode::Net });
+
+        let mut resting = create_test_order_open(Side::Buy, 16400.0, 0.5);
+        resting.timestamp = 1625247600000;
+        account.account_open_book.read().await.get_ins_orders_mut(&instrument).unwrap().add_order_open(resting);
+        let market_trade = MarketTrade { exchange: "binance-futures".to_string(),
+                                         symbol: "ETHUSDT".to_string(),
+                                         side: "sell".to_string(),
+                                         price: 16300.0,
+                                         timestamp: 1625247600500,
+                                         amount: 1.0 };
+        account.handle_trade_data(&market_trade).await.unwrap();
+
+        let events: Vec<AccountEvent> = std::iter::from_fn(|| account_event_rx.try_recv().ok()).collect();
+        let kinds: Vec<&AccountEventKind> = events.iter().map(|event| &event.kind).collect();
+        assert!(matches!(kinds.as_slice(), [AccountEventKind::Trade(_), AccountEventKind::Balances(_), AccountEventKind::Positions(_), AccountEventKind::OrdersFilled(_)]), "{:?}", kinds);
+        assert!(events.iter().all(|event| event.exchange_timestamp == 1625247600500));
+
+        // Positions 快照已包含该笔成交
+        if let AccountEventKind::Positions(positions) = &events[2].kind {
+            assert_eq!(positions.perpetual_pos_long.get(&instrument).unwrap().meta.current_size, 0.5);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_position_update_error_propagates_after_balance_events()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        // 只有 LongShort 模式的多仓配置，卖出成交不能继承它
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(),
+                                                                         PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                                                                                   leverage: 1.0,
+                                                                                                   position_direction_mode: PositionDirectionMode::LongShort });
+
+        let trade = ClientTrade { exchange: Exchange::Hourglass,
+                                  timestamp: 1625247600000,
+                                  trade_id: ClientTradeId(1),
+                                  order_id: None,
+                                  cid: None,
+                                  instrument: instrument.clone(),
+                                  side: Side::Sell,
+                                  price: 16400.0,
+                                  size: 0.5,
+                                  fees: 0.1,
+                                  tag: None };
+        assert_eq!(account.process_trade(trade).await, Err(ExchangeError::ConfigInheritanceNotAllowed));
+
+        // 余额已经结算，Trade 与 Balance 事件照常发送，没有仓位快照
+        let events: Vec<AccountEvent> = std::iter::from_fn(|| account_event_rx.try_recv().ok()).collect();
+        let kinds: Vec<&AccountEventKind> = events.iter().map(|event| &event.kind).collect();
+        assert!(matches!(kinds.as_slice(), [AccountEventKind::Trade(_), AccountEventKind::Balances(_)]), "{:?}", kinds);
+        assert!(account.positions.perpetual_pos_short.read().await.get(&instrument).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resting_order_filled_incrementally_across_trades()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+
+        // 同价位先挂 10 再挂 5
+        for (id, size) in [(1, 10.0), (2, 5.0)] {
+            let mut resting = create_test_order_open(Side::Buy, 100.0, size);
+            resting.state.id = OrderId(id);
+            resting.state.order_role = OrderRole::Maker;
+            resting.timestamp = 1625247600000;
+            account.account_open_book.read().await.get_ins_orders_mut(&instrument).unwrap().add_order_open(resting);
+        }
+
+        let mut fill_states = Vec::new();
+        for (timestamp, amount) in [(1625247600100, 3.0), (1625247600200, 3.0), (1625247600300, 6.0)] {
+            account.match_orders(&MarketTrade { exchange: "binance-futures".to_string(),
+                                                symbol: "ETHUSDT".to_string(),
+                                                side: "sell".to_string(),
+                                                price: 99.0,
+                                                timestamp,
+                                                amount })
+                   .await
+                   .unwrap();
+            while let Ok(event) = account_event_rx.try_recv() {
+                match event.kind {
+                    | AccountEventKind::OrdersPartiallyFilled(orders) => fill_states.push(("partial", orders[0].state.id.0, orders[0].state.size)),
+                    | AccountEventKind::OrdersFilled(orders) => fill_states.push(("filled", orders[0].state.id.0, orders[0].state.size)),
+                    | _ => {}
+                }
+            }
+        }
+
+        // 最后一笔 6 先成交第一笔挂单剩余的 4，多出的 2 继续成交同价位的第二笔挂单
+        assert_eq!(fill_states, vec![("partial", 1, 3.0), ("partial", 1, 6.0), ("filled", 1, 10.0), ("partial", 2, 2.0)]);
+        let remaining = account.account_open_book.read().await.fetch_all();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].state.id, OrderId(2));
+        assert_eq!(remaining[0].state.remaining_quantity(), 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_network_latency_decides_which_trades_a_resting_order_sees()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let trade = MarketTrade { exchange: "binance-futures".to_string(),
+                                  symbol: "ETHUSDT".to_string(),
+                                  side: "sell".to_string(),
+                                  price: 99.0,
+                                  timestamp: 1625247600000,
+                                  amount: 1.0 };
+
+        // 挂单在成交发生 50 毫秒之后才到达，按成交时间撮合时它还不可见
+        let mut fills = Vec::new();
+        for latency_ms in [0, 100] {
+            let mut account = create_test_account().await;
+            let mut resting = create_test_order_open(Side::Buy, 100.0, 1.0);
+            resting.state.order_role = OrderRole::Maker;
+            resting.timestamp = 1625247600050;
+            account.account_open_book.read().await.get_ins_orders_mut(&instrument).unwrap().add_order_open(resting);
+
+            let mut latency = AccountLatency::new(FluctuationMode::Constant, latency_ms, latency_ms, 0);
+            let event = MarketEvent::from_swap_trade_clickhouse(trade.clone(), "ETH".to_string(), "USDT".to_string()).with_network_latency(&mut latency);
+            account.handle_market_event(&event).await.unwrap();
+
+            // 账户时钟仍按成交的发生时间推进
+            assert_eq!(account.exchange_timestamp.load(Ordering::SeqCst), trade.timestamp);
+            fills.push(account.account_open_book.read().await.fetch_all().is_empty());
+        }
+
+        // 接收时间晚于挂单到达时间时，同一笔成交可以成交该挂单
+        assert_eq!(fills, vec![false, true]);
+    }
+
+    #[tokio::test]
+    async fn test_realised_pnl_emitted_on_partial_reduce_and_full_close()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let config = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                               leverage: 1.0,
+                                               position_direction_mode: PositionDirectionMode::Net };
+        account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(), config.clone());
+        account.positions.perpetual_pos_short_config.write().await.insert(instrument.clone(), config);
+
+        let trade = |trade_id: i64, side: Side, price: f64, size: f64, fees: f64| ClientTrade { exchange: Exchange::Hourglass,
+                                                                                               timestamp: 1625247600000 + trade_id,
+                                                                                               trade_id: ClientTradeId(trade_id),
+                                                                                               order_id: None,
+                                                                                               cid: None,
+                                                                                               instrument: instrument.clone(),
+                                                                                               side,
+                                                                                               price,
+                                                                                               size,
+                                                                                               fees,
+                                                                                               tag: None };
+
+        // 开多 10 @ 100，再分两次平仓：4 @ 110 与 6 @ 90
+        account.process_trade(trade(1, Side::Buy, 100.0, 10.0, 1.0)).await.unwrap();
+        account.process_trade(trade(2, Side::Sell, 110.0, 4.0, 0.4)).await.unwrap();
+        account.process_trade(trade(3, Side::Sell, 90.0, 6.0, 0.6)).await.unwrap();
+
+        let mut realised = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            if let AccountEventKind::RealisedPnl(pnl) = event.kind {
+                realised.push(pnl);
+            }
+        }
+        assert_eq!(realised.len(), 2);
+        assert_eq!(realised.iter().map(|pnl| pnl.trade_id).collect::<Vec<_>>(), vec![ClientTradeId(2), ClientTradeId(3)]);
+        assert!(realised.iter().all(|pnl| pnl.instrument == instrument));
+        // 部分平仓只计入平掉的 4 张：(110 - 100) * 4 - 0.4
+        assert!((realised[0].amount - 39.6).abs() < 1e-9);
+        assert!((realised[0].fees - 0.4).abs() < 1e-9);
+        // 完全平仓计入剩余的 6 张：(90 - 100) * 6 - 0.6
+        assert!((realised[1].amount + 60.6).abs() < 1e-9);
+        assert!(account.positions.perpetual_pos_long.read().await.get(&instrument).is_none());
+
+        // 实现盈亏 40 - 60 计入 USDT，三笔手续费共 2.0 另行扣除
+        let usdt = account.get_balance(&Token::from("USDT")).unwrap();
+        assert!((usdt.total - (10_000.0 - 20.0 - 2.0)).abs() < 1e-9);
+        assert!((usdt.available - (10_000.0 - 20.0 - 2.0)).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_close_fee_settled_together_with_realised_pnl()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let config = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                               leverage: 1.0,
+                                               position_direction_mode: PositionDirectionMode::Net };
+        account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(), config.clone());
+        account.positions.perpetual_pos_short_config.write().await.insert(instrument.clone(), config);
+
+        let trade = |trade_id: i64, side: Side, price: f64, size: f64, fees: f64| ClientTrade { exchange: Exchange::Hourglass,
+                                                                                               timestamp: 1625247600000 + trade_id,
+                                                                                               trade_id: ClientTradeId(trade_id),
+                                                                                               order_id: None,
+                                                                                               cid: None,
+                                                                                               instrument: instrument.clone(),
+                                                                                               side,
+                                                                                               price,
+                                                                                               size,
+                                                                                               fees,
+                                                                                               tag: None };
+
+        account.process_trade(trade(1, Side::Buy, 100.0, 10.0, 0.0)).await.unwrap();
+        // 可用余额耗尽：单独先扣 0.4 的平仓手续费会被拒绝，与 40 的实现盈亏按净额结算则成功
+        account.get_balance_mut(&Token::from("USDT")).unwrap().available = 0.0;
+        account.process_trade(trade(2, Side::Sell, 110.0, 4.0, 0.4)).await.unwrap();
+
+        let usdt = account.get_balance(&Token::from("USDT")).unwrap();
+        assert!((usdt.available - 39.6).abs() < 1e-9);
+        assert!((usdt.total - (10_000.0 + 39.6)).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_fill_that_cannot_be_settled_returns_insufficient_balance()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.get_balance_mut(&Token::from("USDT")).unwrap().available = 1.0;
+
+        // 5 的手续费超过 1 的可用余额：结算被拒绝并返回错误，而不是静默跳过
+        let trade = ClientTrade { exchange: Exchange::Hourglass,
+                                  timestamp: 1625247600000,
+                                  trade_id: ClientTradeId(1),
+                                  order_id: None,
+                                  cid: None,
+                                  instrument: Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual)),
+                                  side: Side::Buy,
+                                  price: 100.0,
+                                  size: 1.0,
+                                  fees: 5.0,
+                                  tag: None };
+        let result = account.process_trade(trade).await;
+
+        assert!(matches!(result, Err(ExchangeError::InsufficientBalance { .. })));
+        let usdt = account.get_balance(&Token::from("USDT")).unwrap();
+        assert_eq!(usdt.available, 1.0);
+        assert_eq!(usdt.total, 10_000.0);
+    }
+
+    #[tokio::test]
+    async fn test_slippage_reflected_in_client_trade_and_position_price()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.config.slippage_model = SlippageModel::VolumeProportional { bps_per_unit: 20.0 };
+        account.config.fill_price_policy = FillPricePolicy::TradePrice;
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let config = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                               leverage: 1.0,
+                                               position_direction_mode: PositionDirectionMode::Net };
+        account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(), config);
+
+        // 买单价格高于最优卖价 16499，以 Taker 身份挂出；下单时尚未到达交易所，到达后被外部卖单成交
+        let order = Order { instruction: OrderInstruction::Limit,
+                            exchange: Exchange::Hourglass,
+                            instrument: instrument.clone(),
+                            timestamp: 1234568,
+                            cid: Some(ClientOrderId("taker_bid".into())),
+                            side: Side::Buy,
+                            state: RequestOpen { reduce_only: false,
+                                                 price: 16600.0,
+                                                 size: 0.5,
+                                                 tag: None } };
+        assert_eq!(account.atomic_open(order).await.unwrap().state.order_role, OrderRole::Taker);
+        account.handle_trade_data(&MarketTrade { exchange: "binance-futures".to_string(),
+                                                 symbol: "ETHUSDT".to_string(),
+                                                 side: "sell".to_string(),
+                                                 price: 16550.0,
+                                                 timestamp: 1234568,
+                                                 amount: 1.0 })
+               .await
+               .unwrap();
+
+        // 按成交价 16550 成交 0.5 个单位，滑点 10 个基点，买单向上滑，仍低于限价 16600
+        let expected_price = 16550.0 * (1.0 + 10.0 / 10_000.0);
+        let mut trade_prices = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            if let AccountEventKind::Trade(trade) = event.kind {
+                trade_prices.push(trade.price);
+            }
+        }
+        assert_eq!(trade_prices.len(), 1);
+        assert!((trade_prices[0] - expected_price).abs() < 1e-9);
+        let long_positions = account.positions.perpetual_pos_long.read().await;
+        assert!((long_positions[&instrument].meta.current_avg_price - expected_price).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_same_limit_price_maker_or_taker_depending_on_arrival()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let bid = |timestamp: i64| Order { instruction: OrderInstruction::Limit,
+                                          exchange: Exchange::Hourglass,
+                                          instrument: instrument.clone(),
+                                          timestamp,
+                                          cid: Some(ClientOrderId("arrival".into())),
+                                          side: Side::Buy,
+                                          state: RequestOpen { reduce_only: false,
+                                                               price: 16400.0,
+                                                               size: 0.5,
+                                                               tag: None } };
+        let market_trade = |price: f64, timestamp: i64, amount: f64| MarketTrade { exchange: "binance-futures".to_string(),
+                                                                                  symbol: "ETHUSDT".to_string(),
+                                                                                  side: "sell".to_string(),
+                                                                                  price,
+                                                                                  timestamp,
+                                                                                  amount };
+        let fees = |account_event_rx: &mut tokio::sync::mpsc::UnboundedReceiver<AccountEvent>| {
+            std::iter::from_fn(|| account_event_rx.try_recv().ok()).filter_map(|event| match event.kind {
+                                                                       | AccountEventKind::Trade(trade) => Some(trade.fees),
+                                                                       | _ => None,
+                                                                   })
+                                                                   .collect::<Vec<_>>()
+        };
+
+        // 先挂单：16400 低于最优卖价 16499，挂在订单簿上，被之后的外部卖单成交时按 Maker 费率 0.001
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        assert_eq!(account.atomic_open(bid(1625247600000)).await.unwrap().state.order_role, OrderRole::Maker);
+        account.handle_trade_data(&market_trade(16300.0, 1625247600100, 1.0)).await.unwrap();
+        let maker_fees = fees(&mut account_event_rx);
+        assert_eq!(maker_fees.len(), 1);
+        assert!((maker_fees[0] - 16400.0 * 0.5 * 0.001).abs() < 1e-9);
+
+        // 后挂单：外部卖单先把最优卖价压到 16300，同样价格的买单到达时即可成交，按 Taker 费率 0.002
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = tokio::sync::mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.handle_trade_data(&market_trade(16300.0, 1625247600000, 1.0)).await.unwrap();
+        assert_eq!(account.atomic_open(bid(1625247600100)).await.unwrap().state.order_role, OrderRole::Taker);
+        account.handle_trade_data(&market_trade(16350.0, 1625247600200, 0.2)).await.unwrap();
+        // 部分成交后剩余的 0.3 挂在订单簿上，之后的成交按 Maker 费率
+        account.handle_trade_data(&market_trade(16350.0, 1625247600300, 1.0)).await.unwrap();
+        let taker_fees = fees(&mut account_event_rx);
+        assert_eq!(taker_fees.len(), 2);
+        assert!((taker_fees[0] - 16400.0 * 0.2 * 0.002).abs() < 1e-9);
+        assert!((taker_fees[1] - 16400.0 * 0.3 * 0.001).abs() < 1e-9);
+    }
 }
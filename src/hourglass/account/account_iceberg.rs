@@ -0,0 +1,164 @@
+use crate::{
+    common::order::{
+        order_instructions::OrderInstruction,
+        states::{
+            open::{Iceberg, Open},
+            request_open::RequestOpen,
+        },
+        Order, OrderRole,
+    },
+    error::ExchangeError,
+    hourglass::account::{account_orders::OrderRoleClassifier, HourglassAccount},
+};
+
+impl HourglassAccount
+{
+    /// 挂出一笔冰山单：订单总量为 `order.state.size`，每次只有不超过 `display_size` 的数量参与撮合。
+    ///
+    /// 显示部分被吃完后从隐藏的剩余数量中补充，按同一价格排到同价位队尾，失去原有的时间优先级；
+    /// 每笔成交的 `ClientTrade` 与成交状态事件都按实际成交数量计算。撤单时整笔订单（包括隐藏部分）一并撤销。
+    ///
+    /// 冰山单只能是提交时不会立即成交的限价挂单（`Limit`、`GoodTilCancelled`、`GoodTilTime` 或 `PostOnlyLimit`），
+    /// `display_size` 必须是有限正数，否则拒绝且不挂出订单。
+    pub async fn submit_iceberg(&mut self, order: Order<RequestOpen>, display_size: f64) -> Result<Order<Open>, ExchangeError>
+    {
+        if !display_size.is_finite() || display_size <= 0.0 {
+            return Err(ExchangeError::InvalidOrder(format!("Iceberg display size must be a positive finite number, got {}", display_size)));
+        }
+        if !matches!(order.instruction, OrderInstruction::Limit | OrderInstruction::GoodTilCancelled | OrderInstruction::GoodTilTime { .. } | OrderInstruction::PostOnlyLimit) {
+            return Err(ExchangeError::InvalidOrder(format!("Iceberg order must be a resting limit order, got {}", order.instruction)));
+        }
+        Self::validate_order_size_and_price(&order)?;
+        {
+            let order_books = self.single_level_order_book.lock().await;
+            let order_book = order_books.get(&order.instrument).ok_or_else(|| ExchangeError::InvalidOrder(format!("No market data for iceberg instrument {}", order.instrument)))?;
+            if self.account_open_book.read().await.determine_maker_taker(&order, order_book)? == OrderRole::Taker {
+                return Err(ExchangeError::InvalidOrder(format!("Iceberg order {:?} would execute immediately", order.cid)));
+            }
+        }
+
+        let iceberg = Iceberg::new(display_size, order.state.size);
+        self.atomic_open_with_iceberg(order, Some(iceberg)).await
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            event::AccountEventKind,
+            instrument::{kind::InstrumentKind, Instrument},
+            order::{identification::client_order_id::ClientOrderId, states::request_cancel::RequestCancel},
+            Side,
+        },
+        hourglass::{account::account_handlers::trade_handler::TradeHandler, clickhouse_api::datatype::clickhouse_trade_data::MarketTrade},
+        test_utils::create_test_account,
+        Exchange,
+    };
+    use tokio::sync::mpsc;
+
+    fn limit_order(cid: &str, size: f64) -> Order<RequestOpen>
+    {
+        Order { instruction: OrderInstruction::Limit,
+                exchange: Exchange::Hourglass,
+                instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                timestamp: 1234567,
+                cid: Some(ClientOrderId(cid.into())),
+                side: Side::Buy,
+                state: RequestOpen { reduce_only: false,
+                                     price: 16300.0,
+                                     size,
+                                     tag: None } }
+    }
+
+    fn sell_trade(amount: f64) -> MarketTrade
+    {
+        MarketTrade { exchange: "binance-futures".to_string(),
+                      symbol: "ETHUSDT".to_string(),
+                      side: "sell".to_string(),
+                      price: 16290.0,
+                      timestamp: 1234568,
+                      amount }
+    }
+
+    #[tokio::test]
+    async fn test_iceberg_refills_behind_order_at_same_price()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        account.submit_iceberg(limit_order("iceberg", 0.5), 0.2).await.unwrap();
+        account.atomic_open(limit_order("plain", 0.1)).await.unwrap();
+
+        // 冰山单先成交显示的 0.2，补充后排到普通挂单之后；普通挂单成交 0.1，剩余 0.1 再成交冰山单的新显示部分
+        account.handle_trade_data(&sell_trade(0.4)).await.unwrap();
+
+        let mut trades = Vec::new();
+        let mut iceberg_filled = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            match event.kind {
+                | AccountEventKind::Trade(trade) => trades.push((trade.cid.unwrap().0, trade.size)),
+                | AccountEventKind::OrdersPartiallyFilled(orders) if orders[0].cid == Some(ClientOrderId("iceberg".into())) => iceberg_filled.push(orders[0].state.size),
+                | _ => {}
+            }
+        }
+        let expected = [("iceberg", 0.2), ("plain", 0.1), ("iceberg", 0.1)];
+        assert_eq!(trades.len(), expected.len());
+        for ((cid, size), (expected_cid, expected_size)) in trades.iter().zip(expected) {
+            assert_eq!(cid, expected_cid);
+            assert!((size - expected_size).abs() < 1e-9);
+        }
+        // 成交状态事件报告的是累计的实际成交数量，而不是显示数量
+        assert_eq!(iceberg_filled.len(), 2);
+        assert!((iceberg_filled[1] - 0.3).abs() < 1e-9);
+
+        let resting = account.account_open_book.read().await.fetch_all();
+        assert_eq!(resting.len(), 1);
+        assert!((resting[0].state.remaining_quantity() - 0.2).abs() < 1e-9);
+        assert!((resting[0].state.visible_quantity() - 0.1).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_iceberg_cancels_hidden_remainder()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        let open = account.submit_iceberg(limit_order("iceberg", 0.5), 0.2).await.unwrap();
+        account.handle_trade_data(&sell_trade(0.2)).await.unwrap();
+        let resting = account.account_open_book.read().await.fetch_all();
+        assert!((resting[0].state.remaining_quantity() - 0.3).abs() < 1e-9);
+
+        let cancelled = account.execute_cancel(Order { instruction: open.instruction,
+                                                       exchange: Exchange::Hourglass,
+                                                       instrument: open.instrument.clone(),
+                                                       timestamp: 1234569,
+                                                       cid: open.cid.clone(),
+                                                       side: open.side,
+                                                       state: RequestCancel { id: Some(open.state.id.clone()) } })
+                                 .await
+                                 .unwrap();
+
+        assert_eq!(cancelled.state.id, open.state.id);
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_iceberg_rejected_without_opening()
+    {
+        let mut account = create_test_account().await;
+        for display_size in [0.0, -0.1, f64::NAN] {
+            let result = account.submit_iceberg(limit_order("iceberg", 0.5), display_size).await;
+            assert!(matches!(result, Err(ExchangeError::InvalidOrder(_))));
+        }
+
+        let mut crossing = limit_order("iceberg", 0.5);
+        crossing.state.price = 16600.0;
+        assert!(matches!(account.submit_iceberg(crossing, 0.2).await, Err(ExchangeError::InvalidOrder(_))));
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+    }
+}
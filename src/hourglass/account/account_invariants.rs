@@ -0,0 +1,213 @@
+use crate::{
+    common::{
+        account_positions::position_meta::PositionMeta,
+        instrument::Instrument,
+        token::Token,
+        Side,
+    },
+    hourglass::account::{account_config::InvariantCheckMode, account_handlers::balance_handler::BalanceHandler, HourglassAccount},
+    hourglass_log::error,
+};
+use std::{collections::HashMap, fmt};
+use tokio::sync::RwLock;
+
+/// 不变量检查的浮点容忍度。
+pub const INVARIANT_EPSILON: f64 = 1e-9;
+
+/// 账户状态不变量被破坏时的具体情况。
+#[derive(Clone, Debug, PartialEq)]
+pub enum InvariantViolation
+{
+    /// 余额总额小于可用额。
+    TotalBelowAvailable
+    {
+        token: Token,
+        total: f64,
+        available: f64,
+    },
+    /// 余额（总额或可用额）为负。
+    NegativeBalance
+    {
+        token: Token,
+        total: f64,
+        available: f64,
+    },
+    /// 仓位数量为负。
+    NegativePositionSize
+    {
+        instrument: Instrument,
+        side: Side,
+        size: f64,
+    },
+    /// 冻结额（总额 - 可用额）不足以覆盖挂单所需。
+    LockedBelowOpenOrders
+    {
+        token: Token,
+        locked: f64,
+        required: f64,
+    },
+}
+
+impl fmt::Display for InvariantViolation
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self {
+            | InvariantViolation::TotalBelowAvailable { token, total, available } => write!(f, "{} total {} is below available {}", token, total, available),
+            | InvariantViolation::NegativeBalance { token, total, available } => write!(f, "{} balance is negative: total {}, available {}", token, total, available),
+            | InvariantViolation::NegativePositionSize { instrument, side, size } => write!(f, "{} {:?} position size is negative: {}", instrument, side, size),
+            | InvariantViolation::LockedBelowOpenOrders { token, locked, required } => write!(f, "{} locked {} does not cover open orders requiring {}", token, locked, required),
+        }
+    }
+}
+
+impl HourglassAccount
+{
+    /// 检查账户状态的不变量，返回所有被破坏的不变量。
+    ///
+    /// - 每个余额满足 `total >= available`；
+    /// - 余额不为负（允许 [`INVARIANT_EPSILON`] 的误差）；
+    /// - 所有仓位数量不为负；
+    /// - 每个币种的冻结额（`total - available`）不小于挂单所需。挂单所需与撤单时释放的金额口径一致，
+    ///   见 [`BalanceHandler::reserved_balance`]。
+    ///   由于衍生品成交后当前不会释放冻结额，这里只检查冻结额足以覆盖挂单，而不要求二者相等。
+    pub async fn check_invariants(&self) -> Vec<InvariantViolation>
+    {
+        let mut violations = Vec::new();
+
+        let mut required: HashMap<Token, f64> = HashMap::new();
+        for order in self.account_open_book.read().await.fetch_all() {
+            let (token, reserved) = self.reserved_balance(&order);
+            *required.entry(token).or_insert(0.0) += reserved;
+        }
+
+        for entry in self.balances.iter() {
+            let (token, balance) = (entry.key(), entry.value());
+            if balance.total + INVARIANT_EPSILON < balance.available {
+                violations.push(InvariantViolation::TotalBelowAvailable { token: token.clone(),
+                                                                          total: balance.total,
+                                                                          available: balance.available });
+            }
+            if balance.total < -INVARIANT_EPSILON || balance.available < -INVARIANT_EPSILON {
+                violations.push(InvariantViolation::NegativeBalance { token: token.clone(),
+                                                                      total: balance.total,
+                                                                      available: balance.available });
+            }
+            let locked = balance.total - balance.available;
+            let required = required.get(token).copied().unwrap_or(0.0);
+            if locked + INVARIANT_EPSILON < required {
+                violations.push(InvariantViolation::LockedBelowOpenOrders { token: token.clone(), locked, required });
+            }
+        }
+
+        let positions = &self.positions;
+        check_position_sizes(&positions.margin_pos_long, |p| &p.meta, Side::Buy, &mut violations).await;
+        check_position_sizes(&positions.margin_pos_short, |p| &p.meta, Side::Sell, &mut violations).await;
+        check_position_sizes(&positions.perpetual_pos_long, |p| &p.meta, Side::Buy, &mut violations).await;
+        check_position_sizes(&positions.perpetual_pos_short, |p| &p.meta, Side::Sell, &mut violations).await;
+        check_position_sizes(&positions.futures_pos_long, |p| &p.meta, Side::Buy, &mut violations).await;
+        check_position_sizes(&positions.futures_pos_short, |p| &p.meta, Side::Sell, &mut violations).await;
+        check_position_sizes(&positions.option_pos_long_call, |p| &p.meta, Side::Buy, &mut violations).await;
+        check_position_sizes(&positions.option_pos_long_put, |p| &p.meta, Side::Buy, &mut violations).await;
+        check_position_sizes(&positions.option_pos_short_call, |p| &p.meta, Side::Sell, &mut violations).await;
+        check_position_sizes(&positions.option_pos_short_put, |p| &p.meta, Side::Sell, &mut violations).await;
+
+        violations
+    }
+
+    /// 按 `invariant_check` 配置在事件处理后检查不变量。
+    ///
+    /// `Off` 时直接返回，不产生任何开销；`Log` 时记录触发的事件与被破坏的不变量；`Panic` 时记录后 panic。
+    pub(crate) async fn assert_invariants(&self, event: &(dyn fmt::Debug + Sync))
+    {
+        let mode = self.config.invariant_check;
+        if mode == InvariantCheckMode::Off {
+            return;
+        }
+
+        let violations = self.check_invariants().await;
+        if violations.is_empty() {
+            return;
+        }
+        for violation in &violations {
+            error!("Invariant violated after event {:?}: {}", event, violation);
+        }
+        if mode == InvariantCheckMode::Panic {
+            panic!("{} account invariant(s) violated after event {:?}", violations.len(), event);
+        }
+    }
+}
+
+async fn check_position_sizes<T>(positions: &RwLock<HashMap<Instrument, T>>, meta: fn(&T) -> &PositionMeta, side: Side, violations: &mut Vec<InvariantViolation>)
+{
+    for (instrument, position) in positions.read().await.iter() {
+        let size = meta(position).current_size;
+        if size < -INVARIANT_EPSILON {
+            violations.push(InvariantViolation::NegativePositionSize { instrument: instrument.clone(), side, size });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::instrument::kind::InstrumentKind,
+        test_utils::{create_test_account, create_test_order_open, create_test_perpetual_position},
+    };
+
+    #[tokio::test]
+    async fn test_fresh_account_has_no_violations()
+    {
+        let account = create_test_account().await;
+        assert!(account.check_invariants().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_detects_balance_and_position_violations()
+    {
+        let account = create_test_account().await;
+        {
+            let mut usdt = account.balances.get_mut(&Token::from("USDT")).unwrap();
+            usdt.total = -1.0;
+            usdt.available = 5.0;
+        }
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        let mut position = create_test_perpetual_position(instrument.clone());
+        position.meta.current_size = -0.5;
+        account.positions.perpetual_pos_long.write().await.insert(instrument, position);
+
+        let violations = account.check_invariants().await;
+        assert!(violations.iter().any(|v| matches!(v, InvariantViolation::TotalBelowAvailable { .. })));
+        assert!(violations.iter().any(|v| matches!(v, InvariantViolation::NegativeBalance { .. })));
+        assert!(violations.iter().any(|v| matches!(v, InvariantViolation::NegativePositionSize { size, .. } if *size == -0.5)));
+    }
+
+    #[tokio::test]
+    async fn test_detects_unlocked_open_order_requirement()
+    {
+        let account = create_test_account().await;
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        // 直接放入挂单簿而不冻结余额，模拟记账遗漏
+        account.account_open_book
+               .read()
+               .await
+               .get_ins_orders_mut(&instrument)
+               .unwrap()
+               .add_order_open(create_test_order_open(Side::Buy, 16000.0, 1.0));
+
+        let violations = account.check_invariants().await;
+        assert!(violations.iter().any(|v| matches!(v, InvariantViolation::LockedBelowOpenOrders { required, .. } if *required == 16000.0)));
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "invariant(s) violated")]
+    async fn test_panic_mode_panics_on_violation()
+    {
+        let mut account = create_test_account().await;
+        account.config.invariant_check = InvariantCheckMode::Panic;
+        account.balances.get_mut(&Token::from("USDT")).unwrap().available = -1.0;
+        account.assert_invariants(&"test event").await;
+    }
+}
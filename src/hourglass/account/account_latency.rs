@@ -1,10 +1,18 @@
-use rand::Rng;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 // 引入随机数生成器
 use rand_distr::{Distribution, Normal};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::f64::consts::TAU;
+
+/// 按时间变化的模式（`Sine`、`Cosine`、`Step`）下延迟完成一次完整变化的周期（毫秒）。
+pub const LATENCY_CYCLE_MS: i64 = 60_000;
+/// [`AccountLatency::next_latency`] 逐次抽样时，按时间变化的模式每个周期的样本数。
+pub const LATENCY_SAMPLES_PER_CYCLE: i64 = 20;
 
 // 引入随机分布库，包括常态分布
 
+/// 订单延迟生成器。所有模式产生的延迟都限制在 `minimum..=maximum` 之内。
+///
+/// 随机模式使用由 `seed` 初始化的随机数生成器，相同的 `seed` 产生相同的延迟序列，回测可以复现。
 #[derive(Clone, Debug)] // 派生Clone和Debug特性
 pub struct AccountLatency
 {
@@ -12,51 +20,124 @@ pub struct AccountLatency
     pub maximum: i64,
     pub minimum: i64,
     pub current_value: i64,
+    rng: StdRng,
+    samples: i64, // `next_latency` 已抽取的样本数
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum FluctuationMode
 {
+    /// 在 `minimum` 与 `maximum` 之间按正弦曲线随时间振荡。
     Sine,
+    /// 在 `minimum` 与 `maximum` 之间按余弦曲线随时间振荡。
     Cosine,
-    NormalDistribution,
-    Uniform,
+    /// 每个周期的前半段为 `minimum`，后半段为 `maximum`。
+    Step,
+    /// 固定为 `minimum`。
+    Constant,
+    /// 在 `min..=max` 内均匀抽样。
+    Uniform
+    {
+        min: i64,
+        max: i64,
+    },
+    /// 按均值 `mean`、标准差 `stddev` 的正态分布抽样，结果不小于 0。
+    Normal
+    {
+        mean: f64,
+        stddev: f64,
+    },
 }
+
 impl AccountLatency
 {
-    /// 创建一个新的 `AccountLatency` 实例。
-    pub fn new(fluctuation_mode: FluctuationMode, maximum: i64, minimum: i64) -> Self
+    /// 创建一个新的 `AccountLatency` 实例，`seed` 用于初始化随机模式的随机数生成器。
+    pub fn new(fluctuation_mode: FluctuationMode, maximum: i64, minimum: i64, seed: u64) -> Self
     {
         Self { fluctuation_mode,
                maximum,
                minimum,
-               current_value: minimum }
+               current_value: minimum,
+               rng: StdRng::seed_from_u64(seed),
+               samples: 0 }
+    }
+
+    /// 用新的种子重置随机数生成器与抽样进度。
+    pub fn reseed(&mut self, seed: u64)
+    {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.samples = 0;
+    }
+
+    /// 抽取下一个延迟样本并更新 `current_value`。
+    ///
+    /// 按时间变化的模式每次调用前进 1 / [`LATENCY_SAMPLES_PER_CYCLE`] 个周期，随机模式每次调用抽样一次。
+    pub fn next_latency(&mut self) -> i64
+    {
+        let timestamp = self.samples * (LATENCY_CYCLE_MS / LATENCY_SAMPLES_PER_CYCLE);
+        self.samples += 1;
+        fluctuate_latency(self, timestamp);
+        self.current_value
+    }
+
+    /// 用同一个随机数生成器在 `0..len` 中均匀抽取一个下标，`len` 必须大于 0。
+    pub fn sample_index(&mut self, len: usize) -> usize
+    {
+        self.rng.gen_range(0..len)
+    }
+
+    /// 按 `exchange_ts` 抽样一次延迟，返回行情到达账户的接收时间 `exchange_ts + 延迟`，负延迟按 0 计。
+    pub fn receive_time(&mut self, exchange_ts: i64) -> i64
+    {
+        fluctuate_latency(self, exchange_ts);
+        exchange_ts + self.current_value.max(0)
+    }
+
+    /// 把延迟限制在 `minimum..=maximum` 之内。
+    fn bounded(&self, value: i64) -> i64
+    {
+        value.clamp(self.minimum.min(self.maximum), self.maximum.max(self.minimum))
     }
 }
 
+/// 按 `seed` 更新延迟的当前值。
+///
+/// `Sine`、`Cosine` 与 `Step` 把 `seed` 视为时间戳（毫秒），延迟随时间以 [`LATENCY_CYCLE_MS`] 为周期在 `minimum` 与 `maximum` 之间变化，
+/// 同一时间戳总是得到同一延迟；`Uniform` 与 `Normal` 与 `seed` 无关，由 `AccountLatency` 自身的随机数生成器抽样。
 pub fn fluctuate_latency(latency: &mut AccountLatency, seed: i64)
 {
     let range = (latency.maximum - latency.minimum) as f64;
-    let dynamic_seed = seed + (SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64 % 1000);
+    let phase = seed.rem_euclid(LATENCY_CYCLE_MS) as f64 / LATENCY_CYCLE_MS as f64 * TAU;
 
-    match latency.fluctuation_mode {
+    let value = match latency.fluctuation_mode {
         | FluctuationMode::Sine => {
-            let adjusted_seed = ((dynamic_seed as f64 / 100.0).sin() + 1.0) / 2.0; // 0到1之间的值
-            latency.current_value = (range * adjusted_seed) as i64 + latency.minimum;
+            let adjusted_seed = (phase.sin() + 1.0) / 2.0; // 0到1之间的值
+            (range * adjusted_seed).round() as i64 + latency.minimum
         }
         | FluctuationMode::Cosine => {
-            let adjusted_seed = ((dynamic_seed as f64 / 100.0).cos() + 1.0) / 2.0; // 0到1之间的值
-            latency.current_value = (range * adjusted_seed) as i64 + latency.minimum;
+            let adjusted_seed = (phase.cos() + 1.0) / 2.0; // 0到1之间的值
+            (range * adjusted_seed).round() as i64 + latency.minimum
         }
-        | FluctuationMode::NormalDistribution => {
-            let normal = Normal::new((latency.maximum + latency.minimum) as f64 / 2.0, range / 4.0).unwrap();
-            let value = normal.sample(&mut rand::thread_rng()) as i64;
-            latency.current_value = value.clamp(latency.minimum, latency.maximum);
+        | FluctuationMode::Step => {
+            if seed.rem_euclid(LATENCY_CYCLE_MS) < LATENCY_CYCLE_MS / 2 {
+                latency.minimum
+            }
+            else {
+                latency.maximum
+            }
         }
-        | FluctuationMode::Uniform => {
-            latency.current_value = rand::thread_rng().gen_range(latency.minimum..=latency.maximum);
+        | FluctuationMode::Constant => latency.minimum,
+        | FluctuationMode::Uniform { min, max } => latency.rng.gen_range(min.min(max)..=max.max(min)),
+        | FluctuationMode::Normal { mean, stddev } => {
+            // 标准差非法（负数或非有限值）时退化为均值
+            let sample = Normal::new(mean, stddev).map(|normal| normal.sample(&mut latency.rng)).unwrap_or(mean);
+            (sample.round() as i64).max(0)
         }
-    }
+    };
+    latency.current_value = match latency.fluctuation_mode {
+        | FluctuationMode::Normal { .. } => latency.bounded(value).max(0),
+        | _ => latency.bounded(value),
+    };
 }
 
 #[cfg(test)]
@@ -69,35 +150,76 @@ mod tests
     fn test_fluctuate_latency_sine()
     {
         let machine_id = generate_machine_id().unwrap();
-        let mut latency = AccountLatency::new(FluctuationMode::Sine, 100, 0);
+        let mut latency = AccountLatency::new(FluctuationMode::Sine, 100, 0, 0);
         fluctuate_latency(&mut latency, machine_id as i64);
         assert!(latency.current_value >= latency.minimum && latency.current_value <= latency.maximum);
     }
 
+    #[test]
+    fn test_sine_latency_oscillates_over_time()
+    {
+        let mut latency = AccountLatency::new(FluctuationMode::Sine, 100, 10, 0);
+        let mut value_at = |timestamp: i64| {
+            fluctuate_latency(&mut latency, timestamp);
+            latency.current_value
+        };
+        // 周期起点处于区间中点，四分之一周期达到最大值，四分之三周期达到最小值
+        assert_eq!(value_at(0), 55);
+        assert_eq!(value_at(LATENCY_CYCLE_MS / 4), 100);
+        assert_eq!(value_at(LATENCY_CYCLE_MS * 3 / 4), 10);
+        assert_eq!(value_at(LATENCY_CYCLE_MS + LATENCY_CYCLE_MS / 4), 100);
+    }
+
     #[test]
     fn test_fluctuate_latency_cosine()
     {
         let machine_id = generate_machine_id().unwrap();
-        let mut latency = AccountLatency::new(FluctuationMode::Cosine, 100, 0);
+        let mut latency = AccountLatency::new(FluctuationMode::Cosine, 100, 0, 0);
         fluctuate_latency(&mut latency, machine_id as i64);
         assert!(latency.current_value >= latency.minimum && latency.current_value <= latency.maximum);
     }
 
     #[test]
-    fn test_fluctuate_latency_normal_distribution()
+    fn test_step_and_constant_latency()
     {
-        let machine_id = generate_machine_id().unwrap();
-        let mut latency = AccountLatency::new(FluctuationMode::NormalDistribution, 100, 0);
-        fluctuate_latency(&mut latency, machine_id as i64);
-        assert!(latency.current_value >= latency.minimum && latency.current_value <= latency.maximum);
+        let mut step = AccountLatency::new(FluctuationMode::Step, 100, 10, 0);
+        let samples = (0..LATENCY_SAMPLES_PER_CYCLE).map(|_| step.next_latency()).collect::<Vec<_>>();
+        assert!(samples[..10].iter().all(|latency| *latency == 10));
+        assert!(samples[10..].iter().all(|latency| *latency == 100));
+
+        let mut constant = AccountLatency::new(FluctuationMode::Constant, 100, 10, 0);
+        assert!((0..5).all(|_| constant.next_latency() == 10));
     }
 
     #[test]
-    fn test_fluctuate_latency_uniform()
+    fn test_uniform_latency_stays_within_range()
     {
-        let machine_id = generate_machine_id().unwrap();
-        let mut latency = AccountLatency::new(FluctuationMode::Uniform, 100, 0);
-        fluctuate_latency(&mut latency, machine_id as i64);
-        assert!(latency.current_value >= latency.minimum && latency.current_value <= latency.maximum);
+        let mut latency = AccountLatency::new(FluctuationMode::Uniform { min: 20, max: 40 }, 100, 10, 7);
+        for _ in 0..100 {
+            assert!((20..=40).contains(&latency.next_latency()));
+        }
+    }
+
+    #[test]
+    fn test_normal_latency_clamped_to_bounds_and_non_negative()
+    {
+        let mut latency = AccountLatency::new(FluctuationMode::Normal { mean: 50.0, stddev: 200.0 }, 120, 5, 7);
+        for _ in 0..200 {
+            assert!((5..=120).contains(&latency.next_latency()));
+        }
+
+        let mut latency = AccountLatency::new(FluctuationMode::Normal { mean: 0.0, stddev: 50.0 }, 100, -100, 7);
+        assert!((0..200).all(|_| latency.next_latency() >= 0));
+    }
+
+    #[test]
+    fn test_same_seed_reproduces_latency_sequence()
+    {
+        let sample = |seed: u64| {
+            let mut latency = AccountLatency::new(FluctuationMode::Normal { mean: 50.0, stddev: 20.0 }, 100, 0, seed);
+            (0..20).map(|_| latency.next_latency()).collect::<Vec<_>>()
+        };
+        assert_eq!(sample(42), sample(42));
+        assert_ne!(sample(42), sample(43));
     }
 }
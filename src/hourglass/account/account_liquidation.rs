@@ -0,0 +1,234 @@
+use crate::{
+    common::{
+        account_positions::{liquidation::Liquidation, position_meta::PositionMeta, PositionMarginMode},
+        balance::{BalanceDelta, TokenBalance},
+        event::{AccountEvent, AccountEventKind},
+        instrument::{kind::InstrumentKind, Instrument},
+        Side,
+    },
+    error::ExchangeError,
+    hourglass::account::{
+        account_handlers::{balance_handler::BalanceHandler, position_handler::PositionHandler},
+        account_margin::MarginedPosition,
+        HourglassAccount,
+    },
+    hourglass_log::warn,
+    Exchange,
+};
+use std::{collections::HashMap, sync::atomic::Ordering};
+use tokio::sync::RwLock;
+
+impl HourglassAccount
+{
+    /// 强平某个金融工具上标记价格越过强平价格的永续合约与交割合约仓位，按处理顺序返回强平记录。
+    ///
+    /// 多头在标记价格不高于强平价格时、空头在标记价格不低于强平价格时被强平，强平价格不为正的仓位视为尚未计算强平价格。
+    /// 同一标记价格可能同时越过多个仓位的强平价格（例如双向持仓的多空两侧），此时按多头、空头的顺序逐个强平，
+    /// 每个仓位各发送一次 `Liquidation` 事件以及随后扣除损失后的 `Balance` 事件。
+    ///
+    /// 逐仓仓位只损失该仓位的保证金；全仓仓位按标记价格计算的亏损从报价货币余额中扣除，并释放其占用的全仓保证金。
+    pub async fn liquidate_crossed_positions(&mut self, instrument: &Instrument, mark_price: f64) -> Result<Vec<Liquidation>, ExchangeError>
+    {
+        let crossed = match instrument.kind {
+            | InstrumentKind::Perpetual => [take_crossed_position(&self.positions.perpetual_pos_long, Side::Buy, instrument, mark_price).await,
+                                            take_crossed_position(&self.positions.perpetual_pos_short, Side::Sell, instrument, mark_price).await],
+            | InstrumentKind::Future => [take_crossed_position(&self.positions.futures_pos_long, Side::Buy, instrument, mark_price).await,
+                                         take_crossed_position(&self.positions.futures_pos_short, Side::Sell, instrument, mark_price).await],
+            | _ => return Ok(Vec::new()),
+        };
+
+        let exchange_timestamp = self.exchange_timestamp.load(Ordering::SeqCst);
+        let mut liquidations = Vec::new();
+        for (liquidation, mut meta, released_margin) in crossed.into_iter().flatten() {
+            if released_margin > 0.0 {
+                self.account_margin.fetch_sub(self.config.rounding.round_margin_release(released_margin), Ordering::SeqCst);
+            }
+            meta.current_symbol_price = mark_price;
+            meta.realised_pnl -= liquidation.loss;
+            if instrument.kind == InstrumentKind::Perpetual {
+                self.register_exit_position(&meta, liquidation.side, None).await?;
+            }
+
+            let token = instrument.quote.clone();
+            let balance = self.apply_balance_deltas(&token, &[BalanceDelta::new(-liquidation.loss, -liquidation.loss)])?;
+            for kind in [AccountEventKind::Liquidation(liquidation.clone()), AccountEventKind::Balance(TokenBalance::new(token, balance))] {
+                if let Err(err) = self.account_event_tx.send(AccountEvent { exchange_timestamp,
+                                                                            exchange: Exchange::Hourglass,
+                                                                            kind })
+                {
+                    warn!("Client offline - Failed to send AccountEvent after liquidation: {:?}", err);
+                }
+            }
+            liquidations.push(liquidation);
+        }
+        Ok(liquidations)
+    }
+}
+
+/// 从仓位表中取出标记价格越过强平价格的仓位，返回强平记录、仓位元数据以及需要释放的全仓保证金。
+async fn take_crossed_position<T>(positions: &RwLock<HashMap<Instrument, T>>, side: Side, instrument: &Instrument, mark_price: f64) -> Option<(Liquidation, PositionMeta, f64)>
+    where T: MarginedPosition
+{
+    let mut positions = positions.write().await;
+    let crossed = positions.get(instrument).is_some_and(|position| {
+                                               let liquidation_price = position.liquidation_price();
+                                               position.meta().current_size > 0.0
+                                               && liquidation_price > 0.0
+                                               && match side {
+                                                   | Side::Buy => mark_price <= liquidation_price,
+                                                   | Side::Sell => mark_price >= liquidation_price,
+                                               }
+                                           });
+    if !crossed {
+        return None;
+    }
+
+    let position = positions.remove(instrument)?;
+    let meta = position.meta();
+    let price_change = match side {
+        | Side::Buy => mark_price - meta.current_avg_price,
+        | Side::Sell => meta.current_avg_price - mark_price,
+    };
+    let initial_margin = meta.current_avg_price * meta.current_size * meta.contract_multiplier / position.leverage();
+    let (loss, released_margin) = match position.margin_mode() {
+        | PositionMarginMode::Isolated => (position.isolated_margin().unwrap_or(initial_margin), 0.0),
+        | PositionMarginMode::Cross => ((-price_change * meta.current_size * meta.contract_multiplier).max(0.0), initial_margin),
+    };
+
+    Some((Liquidation { instrument: instrument.clone(),
+                        side,
+                        size: meta.current_size,
+                        mark_price,
+                        liquidation_price: position.liquidation_price(),
+                        margin_mode: position.margin_mode().clone(),
+                        loss },
+          meta.clone(),
+          released_margin))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            account_positions::{perpetual::PerpetualPositionConfig, PositionDirectionMode},
+            token::Token,
+            trade::{ClientTrade, ClientTradeId},
+        },
+        hourglass::{account::account_handlers::trade_handler::TradeHandler, clickhouse_api::datatype::clickhouse_trade_data::MarketTrade},
+        test_utils::{create_test_account, create_test_perpetual_position},
+    };
+    use tokio::sync::mpsc;
+
+    fn position_config(pos_margin_mode: PositionMarginMode) -> PerpetualPositionConfig
+    {
+        PerpetualPositionConfig { pos_margin_mode,
+                                  leverage: 5.0,
+                                  position_direction_mode: PositionDirectionMode::LongShort }
+    }
+
+    fn market_trade(price: f64) -> MarketTrade
+    {
+        MarketTrade { exchange: "binance-futures".to_string(),
+                      symbol: "ETHUSDT".to_string(),
+                      side: "sell".to_string(),
+                      price,
+                      timestamp: 1690000100,
+                      amount: 1.0 }
+    }
+
+    #[tokio::test]
+    async fn test_single_trade_liquidates_both_sides_of_hedged_position()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+
+        // 逐仓多头：保证金 2 * 100 / 5 = 40，强平价格 85
+        let mut long = create_test_perpetual_position(instrument.clone());
+        long.meta.current_size = 2.0;
+        long.meta.current_avg_price = 100.0;
+        long.pos_config = position_config(PositionMarginMode::Isolated);
+        long.isolated_margin = Some(40.0);
+        long.liquidation_price = 85.0;
+        account.positions.perpetual_pos_long.write().await.insert(instrument.clone(), long);
+        // 全仓空头：开仓均价 60，强平价格 70，在 80 强平时亏损 (80 - 60) * 3 = 60
+        let mut short = create_test_perpetual_position(instrument.clone());
+        short.meta.current_size = 3.0;
+        short.meta.current_avg_price = 60.0;
+        short.pos_config = position_config(PositionMarginMode::Cross);
+        short.liquidation_price = 70.0;
+        account.positions.perpetual_pos_short.write().await.insert(instrument.clone(), short);
+
+        account.check_and_handle_liquidation(&market_trade(80.0)).await.unwrap();
+
+        assert!(account.positions.perpetual_pos_long.read().await.is_empty());
+        assert!(account.positions.perpetual_pos_short.read().await.is_empty());
+        let liquidations: Vec<_> = std::iter::from_fn(|| account_event_rx.try_recv().ok()).filter_map(|event| match event.kind {
+                                                                                              | AccountEventKind::Liquidation(liquidation) => Some((liquidation.side, liquidation.margin_mode, liquidation.loss)),
+                                                                                              | _ => None,
+                                                                                          })
+                                                                                          .collect();
+        assert_eq!(liquidations, vec![(Side::Buy, PositionMarginMode::Isolated, 40.0), (Side::Sell, PositionMarginMode::Cross, 60.0)]);
+        assert_eq!(account.balances.get(&Token::from("USDT")).unwrap().total, 10_000.0 - 100.0);
+        assert_eq!(account.exited_positions.perpetual_pos_long.read().await.len(), 1);
+        assert_eq!(account.exited_positions.perpetual_pos_short.read().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_position_without_crossed_liquidation_price_untouched()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+
+        let mut long = create_test_perpetual_position(instrument.clone());
+        long.liquidation_price = 85.0;
+        account.positions.perpetual_pos_long.write().await.insert(instrument.clone(), long);
+        // 尚未计算强平价格的空头不会被任何价格强平
+        account.positions.perpetual_pos_short.write().await.insert(instrument.clone(), create_test_perpetual_position(instrument.clone()));
+
+        let liquidations = account.liquidate_crossed_positions(&instrument, 90.0).await.unwrap();
+        assert!(liquidations.is_empty());
+        assert_eq!(account.positions.perpetual_pos_long.read().await.len(), 1);
+        assert_eq!(account.positions.perpetual_pos_short.read().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fresh_short_survives_trade_at_entry_price()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        account.positions.perpetual_pos_short_config.write().await.insert(instrument.clone(), position_config(PositionMarginMode::Cross));
+
+        account.process_trade(ClientTrade { exchange: Exchange::Hourglass,
+                                            timestamp: 1690000000,
+                                            trade_id: ClientTradeId(1),
+                                            order_id: None,
+                                            cid: None,
+                                            instrument: instrument.clone(),
+                                            side: Side::Sell,
+                                            price: 100.0,
+                                            size: 1.0,
+                                            fees: 0.0,
+                                            tag: None })
+               .await
+               .unwrap();
+
+        // 空头的强平价格高于开仓价，开仓价附近的成交不会触发强平
+        let liquidation_price = account.positions.perpetual_pos_short.read().await.get(&instrument).unwrap().liquidation_price;
+        assert!(liquidation_price > 100.0);
+        for price in [99.0, 100.0, 101.0] {
+            account.check_and_handle_liquidation(&market_trade(price)).await.unwrap();
+        }
+        assert_eq!(account.positions.perpetual_pos_short.read().await.len(), 1);
+
+        account.check_and_handle_liquidation(&market_trade(liquidation_price)).await.unwrap();
+        assert!(account.positions.perpetual_pos_short.read().await.is_empty());
+    }
+}
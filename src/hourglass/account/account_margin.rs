@@ -0,0 +1,433 @@
+use crate::{
+    common::{
+        account_positions::{
+            future::FuturePosition,
+            margin_update::{MarginUpdate, PositionMargin},
+            perpetual::PerpetualPosition,
+            position_meta::PositionMeta,
+            PositionDirectionMode,
+            PositionMarginMode,
+        },
+        event::{AccountEvent, AccountEventKind},
+        instrument::{kind::InstrumentKind, Instrument},
+        order::{states::request_open::RequestOpen, Order},
+        token::Token,
+        Side,
+    },
+    error::ExchangeError,
+    hourglass::account::HourglassAccount,
+    hourglass_log::warn,
+    Exchange,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::Ordering,
+};
+use tokio::sync::RwLock;
+
+/// 参与保证金计算与强平的仓位。
+pub(crate) trait MarginedPosition
+{
+    fn meta(&self) -> &PositionMeta;
+    fn margin_mode(&self) -> &PositionMarginMode;
+    fn leverage(&self) -> f64;
+    fn isolated_margin(&self) -> Option<f64>;
+    fn liquidation_price(&self) -> f64;
+}
+
+impl MarginedPosition for PerpetualPosition
+{
+    fn meta(&self) -> &PositionMeta
+    {
+        &self.meta
+    }
+
+    fn margin_mode(&self) -> &PositionMarginMode
+    {
+        &self.pos_config.pos_margin_mode
+    }
+
+    fn leverage(&self) -> f64
+    {
+        self.pos_config.leverage
+    }
+
+    fn isolated_margin(&self) -> Option<f64>
+    {
+        self.isolated_margin
+    }
+
+    fn liquidation_price(&self) -> f64
+    {
+        self.liquidation_price
+    }
+}
+
+impl MarginedPosition for FuturePosition
+{
+    fn meta(&self) -> &PositionMeta
+    {
+        &self.meta
+    }
+
+    fn margin_mode(&self) -> &PositionMarginMode
+    {
+        &self.pos_config.pos_margin_mode
+    }
+
+    fn leverage(&self) -> f64
+    {
+        self.pos_config.leverage
+    }
+
+    fn isolated_margin(&self) -> Option<f64>
+    {
+        self.isolated_margin
+    }
+
+    fn liquidation_price(&self) -> f64
+    {
+        self.liquidation_price
+    }
+}
+
+impl HourglassAccount
+{
+    /// 由当前的永续与交割合约仓位和余额计算保证金快照。
+    ///
+    /// 仓位按各金融工具的最新成交价标记，尚无成交价时依次退回仓位记录的最新价与开仓均价。
+    /// 权益只计入衍生品计价币种（已交易的衍生品与持仓的 quote）的余额总额，假设这些计价币种等值，适用于单币种保证金模式。
+    pub async fn margin_update(&self) -> MarginUpdate
+    {
+        let mut quote_tokens: HashSet<Token> = HashSet::new();
+        let mut latest_prices = HashMap::new();
+        for (instrument, book) in self.single_level_order_book.lock().await.iter() {
+            if matches!(instrument.kind, InstrumentKind::Perpetual | InstrumentKind::Future) {
+                quote_tokens.insert(instrument.quote.clone());
+            }
+            if book.latest_price > 0.0 {
+                latest_prices.insert(instrument.clone(), book.latest_price);
+            }
+        }
+
+        let mut positions = Vec::new();
+        collect_position_margins(&self.positions.perpetual_pos_long, Side::Buy, &latest_prices, self.maintenance_margin_model(), &mut positions).await;
+        collect_position_margins(&self.positions.perpetual_pos_short, Side::Sell, &latest_prices, self.maintenance_margin_model(), &mut positions).await;
+        collect_position_margins(&self.positions.futures_pos_long, Side::Buy, &latest_prices, self.maintenance_margin_model(), &mut positions).await;
+        collect_position_margins(&self.positions.futures_pos_short, Side::Sell, &latest_prices, self.maintenance_margin_model(), &mut positions).await;
+        quote_tokens.extend(positions.iter().map(|position| position.instrument.quote.clone()));
+
+        let quote_balance = quote_tokens.iter().filter_map(|token| self.balances.get(token).map(|balance| balance.total)).sum();
+        MarginUpdate::from_positions(quote_balance, positions)
+    }
+
+    /// 全仓保证金率：(钱包余额 + 全仓仓位的未实现盈亏) / 全仓仓位的维持保证金之和。
+    ///
+    /// 数值越大越安全，低于 1 时账户进入强平区间；没有全仓仓位时为 `f64::INFINITY`。
+    /// 注意与 [`MarginUpdate::margin_ratio`]（维持保证金 / 权益）的口径互为倒数。钱包余额与权益的口径见 [`Self::margin_update`]。
+    pub async fn margin_ratio(&self) -> f64
+    {
+        CrossMargin::from_update(&self.margin_update().await).margin_ratio(0.0)
+    }
+
+    /// 可用于新订单的保证金：钱包余额加上全仓仓位的未实现盈亏，减去所有仓位占用的初始保证金，可能为负。
+    pub async fn available_margin(&self) -> f64
+    {
+        let cross = CrossMargin::from_update(&self.margin_update().await);
+        cross.wallet_balance + cross.unrealised_pnl - cross.used_margin
+    }
+
+    /// 配置了 `maintenance_margin_rate` 时，检查全仓模式下的开单是否会使保证金率低于 1。
+    ///
+    /// 按订单价格与全部数量估算新增的维持保证金，只按开仓数量计算：Net 模式下与现有反向仓位抵消的部分、
+    /// 以及 reduce_only 订单都不会增加维持保证金。未配置维持保证金率、逐仓模式或非衍生品订单时不做检查。
+    pub(crate) async fn check_cross_margin_for_order(&self, order: &Order<RequestOpen>) -> Result<(), ExchangeError>
+    {
+        let Some(maintenance_margin_rate) = self.config.maintenance_margin_rate
+        else {
+            return Ok(());
+        };
+        if order.state.reduce_only || !matches!(order.instrument.kind, InstrumentKind::Perpetual | InstrumentKind::Future) {
+            return Ok(());
+        }
+        if self.order_margin_mode(order).await != PositionMarginMode::Cross {
+            return Ok(());
+        }
+
+        let opposite_size = self.opposite_position_size(order).await;
+        let opening_size = (order.state.size - opposite_size).max(0.0);
+        if opening_size <= 0.0 {
+            return Ok(());
+        }
+
+        let added_maintenance = order.state.price * opening_size * self.config.contract_multiplier(&order.instrument) * maintenance_margin_rate;
+        let margin_ratio = CrossMargin::from_update(&self.margin_update().await).margin_ratio(added_maintenance);
+        if margin_ratio < 1.0 {
+            return Err(ExchangeError::MarginRatioBelowMaintenance(margin_ratio));
+        }
+        Ok(())
+    }
+
+    /// 订单开仓后所属仓位的保证金模式：优先使用该金融工具与方向上的仓位配置，否则使用账户的全局配置。
+    async fn order_margin_mode(&self, order: &Order<RequestOpen>) -> PositionMarginMode
+    {
+        let configured = match (order.instrument.kind, order.side) {
+            | (InstrumentKind::Perpetual, Side::Buy) => self.positions.perpetual_pos_long_config.read().await.get(&order.instrument).map(|config| config.pos_margin_mode.clone()),
+            | (InstrumentKind::Perpetual, Side::Sell) => self.positions.perpetual_pos_short_config.read().await.get(&order.instrument).map(|config| config.pos_margin_mode.clone()),
+            | (InstrumentKind::Future, Side::Buy) => self.positions.futures_pos_long_config.read().await.get(&order.instrument).map(|config| config.pos_margin_mode.clone()),
+            | (InstrumentKind::Future, Side::Sell) => self.positions.futures_pos_short_config.read().await.get(&order.instrument).map(|config| config.pos_margin_mode.clone()),
+            | _ => None,
+        };
+        configured.unwrap_or_else(|| self.config.global_position_margin_mode.clone())
+    }
+
+    /// 与订单方向相反的现有仓位数量，Net 模式下订单先与其抵消。
+    async fn opposite_position_size(&self, order: &Order<RequestOpen>) -> f64
+    {
+        if self.config.global_position_direction_mode != PositionDirectionMode::Net {
+            return 0.0;
+        }
+        let size = match (order.instrument.kind, order.side) {
+            | (InstrumentKind::Perpetual, Side::Buy) => self.positions.perpetual_pos_short.read().await.get(&order.instrument).map(|position| position.meta.current_size),
+            | (InstrumentKind::Perpetual, Side::Sell) => self.positions.perpetual_pos_long.read().await.get(&order.instrument).map(|position| position.meta.current_size),
+            | (InstrumentKind::Future, Side::Buy) => self.positions.futures_pos_short.read().await.get(&order.instrument).map(|position| position.meta.current_size),
+            | (InstrumentKind::Future, Side::Sell) => self.positions.futures_pos_long.read().await.get(&order.instrument).map(|position| position.meta.current_size),
+            | _ => None,
+        };
+        size.unwrap_or(0.0)
+    }
+
+    /// 维持保证金的计算方式：配置了 `maintenance_margin_rate` 时按名义价值的比例，否则按占用保证金与 `liquidation_threshold` 推算。
+    fn maintenance_margin_model(&self) -> MaintenanceMargin
+    {
+        match self.config.maintenance_margin_rate {
+            | Some(rate) => MaintenanceMargin::NotionalRate(rate),
+            | None => MaintenanceMargin::LiquidationThreshold(self.config.liquidation_threshold),
+        }
+    }
+
+    /// 按 `margin_update` 配置在行情事件处理后发送 `AccountEventKind::MarginUpdate`。
+    ///
+    /// 未配置时直接返回，不做任何计算；节奏见 [`MarginUpdatePolicy`](crate::hourglass::account::account_config::MarginUpdatePolicy)。
+    pub(crate) async fn emit_margin_update_if_due(&mut self)
+    {
+        let Some(policy) = self.config.margin_update
+        else {
+            return;
+        };
+
+        let update = self.margin_update().await;
+        let exchange_timestamp = self.exchange_timestamp.load(Ordering::SeqCst);
+        if !policy.is_due(self.last_margin_update, exchange_timestamp, update.margin_ratio) {
+            return;
+        }
+
+        self.last_margin_update = Some((exchange_timestamp, update.margin_ratio));
+        if let Err(err) = self.account_event_tx.send(AccountEvent { exchange_timestamp,
+                                                                    exchange: Exchange::Hourglass,
+                                                                    kind: AccountEventKind::MarginUpdate(update) })
+        {
+            warn!("Client offline - Failed to send AccountEvent::MarginUpdate: {:?}", err);
+        }
+    }
+}
+
+/// 单个仓位维持保证金的计算方式。
+#[derive(Clone, Copy, Debug)]
+enum MaintenanceMargin
+{
+    NotionalRate(f64),         // `notional * rate`
+    LiquidationThreshold(f64), // `used_margin * (1 - liquidation_threshold)`，与清算价格的计算口径一致
+}
+
+/// 从保证金快照中拆出的全仓部分。
+struct CrossMargin
+{
+    wallet_balance: f64,     // 计价币种余额总额
+    unrealised_pnl: f64,     // 全仓仓位的未实现盈亏
+    maintenance_margin: f64, // 全仓仓位的维持保证金
+    used_margin: f64,        // 所有仓位（包括逐仓）占用的初始保证金
+}
+
+impl CrossMargin
+{
+    fn from_update(update: &MarginUpdate) -> Self
+    {
+        let cross = || update.positions.iter().filter(|position| position.margin_mode == PositionMarginMode::Cross);
+        Self { wallet_balance: update.equity - update.positions.iter().map(|position| position.unrealised_pnl).sum::<f64>(),
+               unrealised_pnl: cross().map(|position| position.unrealised_pnl).sum(),
+               maintenance_margin: cross().map(|position| position.maintenance_margin).sum(),
+               used_margin: update.used_margin }
+    }
+
+    /// 额外增加 `added_maintenance` 的维持保证金后的全仓保证金率。
+    fn margin_ratio(&self, added_maintenance: f64) -> f64
+    {
+        let maintenance_margin = self.maintenance_margin + added_maintenance;
+        if maintenance_margin <= 0.0 {
+            return f64::INFINITY;
+        }
+        (self.wallet_balance + self.unrealised_pnl) / maintenance_margin
+    }
+}
+
+async fn collect_position_margins<T>(positions: &RwLock<HashMap<Instrument, T>>, side: Side, latest_prices: &HashMap<Instrument, f64>, maintenance: MaintenanceMargin, margins: &mut Vec<PositionMargin>)
+    where T: MarginedPosition
+{
+    for (instrument, position) in positions.read().await.iter() {
+        let meta = position.meta();
+        if meta.current_size <= 0.0 {
+            continue;
+        }
+
+        let mark_price = match latest_prices.get(instrument) {
+            | Some(price) => *price,
+            | None if meta.current_symbol_price > 0.0 => meta.current_symbol_price,
+            | None => meta.current_avg_price,
+        };
+        let notional = meta.current_size * mark_price * meta.contract_multiplier;
+        let price_change = match side {
+            | Side::Buy => mark_price - meta.current_avg_price,
+            | Side::Sell => meta.current_avg_price - mark_price,
+        };
+        let used_margin = match position.margin_mode() {
+            | PositionMarginMode::Isolated => position.isolated_margin().unwrap_or(notional / position.leverage()),
+            | PositionMarginMode::Cross => notional / position.leverage(),
+        };
+
+        margins.push(PositionMargin { instrument: instrument.clone(),
+                                      side,
+                                      margin_mode: position.margin_mode().clone(),
+                                      mark_price,
+                                      notional,
+                                      unrealised_pnl: price_change * meta.current_size * meta.contract_multiplier,
+                                      used_margin,
+                                      maintenance_margin: match maintenance {
+                                          | MaintenanceMargin::NotionalRate(rate) => notional * rate,
+                                          | MaintenanceMargin::LiquidationThreshold(liquidation_threshold) => used_margin * (1.0 - liquidation_threshold),
+                                      } });
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::order::{identification::client_order_id::ClientOrderId, order_instructions::OrderInstruction},
+        hourglass::{
+            account::{account_config::MarginUpdatePolicy, account_handlers::trade_handler::TradeHandler},
+            clickhouse_api::datatype::clickhouse_trade_data::MarketTrade,
+        },
+        test_utils::{create_test_account, create_test_perpetual_position},
+    };
+    use tokio::sync::mpsc;
+
+    async fn account_with_long_position() -> HourglassAccount
+    {
+        let account = create_test_account().await;
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        let mut position = create_test_perpetual_position(instrument.clone());
+        position.meta.current_avg_price = 16000.0;
+        position.meta.current_symbol_price = 16100.0;
+        position.pos_config.leverage = 10.0;
+        account.positions.perpetual_pos_long.write().await.insert(instrument, position);
+        account
+    }
+
+    #[tokio::test]
+    async fn test_margin_update_reports_cross_position_maintenance_margin()
+    {
+        let account = account_with_long_position().await;
+        let update = account.margin_update().await;
+
+        assert_eq!(update.positions.len(), 1);
+        let position = &update.positions[0];
+        assert_eq!(position.margin_mode, PositionMarginMode::Cross);
+        assert_eq!(position.notional, 16100.0);
+        assert_eq!(position.unrealised_pnl, 100.0);
+        assert!((position.used_margin - 1610.0).abs() < 1e-9);
+        assert!((position.maintenance_margin - 161.0).abs() < 1e-9);
+
+        // 权益只计入计价币种 USDT，不包括 ETH 余额
+        assert_eq!(update.equity, 10_100.0);
+        assert!((update.available_margin - 8490.0).abs() < 1e-9);
+        assert!((update.margin_ratio - 161.0 / 10_100.0).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn test_margin_update_emitted_on_cadence_and_material_change()
+    {
+        let mut account = account_with_long_position().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.config.margin_update = Some(MarginUpdatePolicy { interval_ms: Some(1_000),
+                                                                 min_ratio_change: Some(0.01) });
+        let market_trade = |price: f64, timestamp: i64| MarketTrade { exchange: "binance-futures".to_string(),
+                                                                      symbol: "ETHUSDT".to_string(),
+                                                                      side: "buy".to_string(),
+                                                                      price,
+                                                                      timestamp,
+                                                                      amount: 0.01 };
+
+        // 首次总会发送；之后价格小幅波动且未满间隔时不发送；满间隔后发送；价格大跌使保证金率明显上升时立即发送
+        for (price, timestamp) in [(16100.0, 1_000_000), (16110.0, 1_000_500), (16110.0, 1_001_000), (8_000.0, 1_001_100)] {
+            account.handle_trade_data(&market_trade(price, timestamp)).await.unwrap();
+        }
+
+        let mut updates = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            if let AccountEventKind::MarginUpdate(update) = event.kind {
+                updates.push((event.exchange_timestamp, update));
+            }
+        }
+        assert_eq!(updates.iter().map(|(timestamp, _)| *timestamp).collect::<Vec<_>>(), vec![1_000_000, 1_001_000, 1_001_100]);
+        assert!(updates[2].1.margin_ratio > updates[1].1.margin_ratio + 0.01);
+        assert_eq!(updates[2].1.positions[0].mark_price, 8_000.0);
+    }
+
+    #[tokio::test]
+    async fn test_cross_margin_ratio_and_available_margin()
+    {
+        let mut account = account_with_long_position().await;
+        assert!((account.margin_ratio().await - 10_100.0 / 161.0).abs() < 1e-9);
+
+        account.config.maintenance_margin_rate = Some(0.05);
+        // (10000 + 100) / (16100 * 0.05)
+        assert!((account.margin_ratio().await - 10_100.0 / 805.0).abs() < 1e-9);
+        assert!((account.available_margin().await - 8490.0).abs() < 1e-9);
+
+        account.positions.perpetual_pos_long.write().await.clear();
+        assert_eq!(account.margin_ratio().await, f64::INFINITY);
+    }
+
+    #[tokio::test]
+    async fn test_open_rejected_when_cross_margin_ratio_would_drop_below_maintenance()
+    {
+        let mut account = account_with_long_position().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.config.maintenance_margin_rate = Some(0.5);
+        let buy = |size: f64| Order { instruction: OrderInstruction::Limit,
+                                      exchange: Exchange::Hourglass,
+                                      instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                                      timestamp: 1234567,
+                                      cid: Some(ClientOrderId("margin".into())),
+                                      side: Side::Buy,
+                                      state: RequestOpen { reduce_only: false,
+                                                           price: 16300.0,
+                                                           size,
+                                                           tag: None } };
+
+        // 现有维持保证金 8050，开 0.1 后为 8865，保证金率 10100 / 8865 仍高于 1
+        account.atomic_open(buy(0.1)).await.unwrap();
+
+        // 再开 0.3 后维持保证金为 8865 + 2445，保证金率低于 1
+        let result = account.atomic_open(buy(0.3)).await;
+        match result {
+            | Err(ExchangeError::MarginRatioBelowMaintenance(ratio)) => assert!((ratio - 10_100.0 / 10_495.0).abs() < 1e-9),
+            | other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(account.account_open_book.read().await.fetch_all().len(), 1);
+    }
+}
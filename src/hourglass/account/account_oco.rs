@@ -0,0 +1,251 @@
+use crate::{
+    common::{
+        order::{
+            identification::oco_group_id::OcoGroupId,
+            states::{request_cancel::RequestCancel, request_open::RequestOpen},
+            Order, OrderRole,
+        },
+        trade::ClientTrade,
+        Side,
+    },
+    error::ExchangeError,
+    hourglass::{
+        account::{account_orders::OrderRoleClassifier, HourglassAccount},
+        clickhouse_api::datatype::clickhouse_trade_data::MarketTrade,
+    },
+    Exchange,
+};
+use std::sync::atomic::Ordering;
+
+impl HourglassAccount
+{
+    /// 挂出两笔关联的 OCO 订单（例如止盈与止损两个平仓腿），返回关联组的标识符。
+    ///
+    /// 两笔订单必须是同一金融工具上提交时不会立即成交的挂单，否则整组拒绝、不挂出任何一笔。
+    /// 之后任意一笔被撮合成交（包括部分成交）时，另一笔被自动撤销并发送 `OrdersCancelled`。
+    /// 第二笔开单失败时撤销已挂出的第一笔并返回该错误。
+    pub async fn submit_oco(&mut self, leg_a: Order<RequestOpen>, leg_b: Order<RequestOpen>) -> Result<OcoGroupId, ExchangeError>
+    {
+        if leg_a.instrument != leg_b.instrument {
+            return Err(ExchangeError::InvalidOrder(format!("OCO legs must share an instrument, got {} and {}", leg_a.instrument, leg_b.instrument)));
+        }
+        {
+            let order_books = self.single_level_order_book.lock().await;
+            let order_book = order_books.get(&leg_a.instrument).ok_or_else(|| ExchangeError::InvalidOrder(format!("No market data for OCO instrument {}", leg_a.instrument)))?;
+            let orders_guard = self.account_open_book.read().await;
+            for leg in [&leg_a, &leg_b] {
+                if orders_guard.determine_maker_taker(leg, order_book)? == OrderRole::Taker {
+                    return Err(ExchangeError::InvalidOrder(format!("OCO leg {:?} would execute immediately", leg.cid)));
+                }
+            }
+        }
+
+        let open_a = self.atomic_open(leg_a).await?;
+        let open_b = match self.atomic_open(leg_b).await {
+            | Ok(open_b) => open_b,
+            | Err(err) => {
+                self.execute_cancel(Order { instruction: open_a.instruction,
+                                            exchange: Exchange::Hourglass,
+                                            instrument: open_a.instrument.clone(),
+                                            timestamp: self.exchange_timestamp.load(Ordering::SeqCst),
+                                            cid: open_a.cid.clone(),
+                                            side: open_a.side,
+                                            state: RequestCancel { id: Some(open_a.state.id.clone()) } })
+                    .await?;
+                return Err(err);
+            }
+        };
+        Ok(self.account_open_book.write().await.link_oco(open_a.state.id, open_b.state.id))
+    }
+
+    /// 同一笔市场成交同时满足一组 OCO 订单两笔挂单的价格时，在撮合之前撤销离成交价较远的一笔，只让较近的一笔参与撮合；
+    /// 距离相同时保留先提交的一笔。
+    ///
+    /// 只考虑能被该笔成交撮合的一侧挂单：主动买入只撮合卖单，主动卖出只撮合买单；主动方未知时不做处理。
+    pub(crate) async fn resolve_oco_races(&mut self, trade: &MarketTrade) -> Result<(), ExchangeError>
+    {
+        if self.account_open_book.read().await.oco_groups.is_empty() {
+            return Ok(());
+        }
+        let Some(aggressor_side) = trade.aggressor_side()
+        else {
+            return Ok(());
+        };
+
+        let instrument = self.resolve_market_instrument(trade)?;
+        let farther_legs = {
+            let orders_guard = self.account_open_book.read().await;
+            let Ok(orders) = orders_guard.get_ins_orders_mut(&instrument)
+            else {
+                return Ok(());
+            };
+            // 能被本笔成交撮合的挂单价格
+            let touched_price = |order_id| {
+                orders.bids
+                      .iter()
+                      .chain(orders.asks.iter())
+                      .find(|order| &order.state.id == order_id && order.side == aggressor_side.toggle())
+                      .map(|order| order.state.price)
+                      .filter(|price| match aggressor_side {
+                          | Side::Buy => *price <= trade.price,
+                          | Side::Sell => *price >= trade.price,
+                      })
+            };
+            orders_guard.oco_groups
+                        .values()
+                        .filter_map(|(leg_a, leg_b)| match (touched_price(leg_a), touched_price(leg_b)) {
+                            | (Some(price_a), Some(price_b)) => Some(if (price_b - trade.price).abs() < (price_a - trade.price).abs() { leg_a.clone() } else { leg_b.clone() }),
+                            | _ => None,
+                        })
+                        .collect::<Vec<_>>()
+        };
+
+        for order_id in farther_legs {
+            self.account_open_book.write().await.take_oco_sibling(&order_id);
+            self.cancel_open_orders_where(&instrument, |order| order.state.id == order_id).await?;
+        }
+        Ok(())
+    }
+
+    /// 撤销本次撮合中成交（包括部分成交）的挂单所关联的另一笔 OCO 订单。
+    pub(crate) async fn cancel_oco_siblings(&mut self, trades: &[ClientTrade]) -> Result<(), ExchangeError>
+    {
+        for trade in trades {
+            let Some(order_id) = &trade.order_id
+            else {
+                continue;
+            };
+            let sibling = self.account_open_book.write().await.take_oco_sibling(order_id);
+            if let Some(sibling) = sibling {
+                self.cancel_open_orders_where(&trade.instrument, |order| order.state.id == sibling).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            event::{AccountEvent, AccountEventKind},
+            instrument::{kind::InstrumentKind, Instrument},
+            order::{identification::client_order_id::ClientOrderId, order_instructions::OrderInstruction},
+        },
+        hourglass::account::account_handlers::trade_handler::TradeHandler,
+        test_utils::create_test_account,
+    };
+    use tokio::sync::mpsc;
+
+    fn limit_order(cid: &str, side: Side, price: f64) -> Order<RequestOpen>
+    {
+        Order { instruction: OrderInstruction::Limit,
+                exchange: Exchange::Hourglass,
+                instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                timestamp: 1234567,
+                cid: Some(ClientOrderId(cid.into())),
+                side,
+                state: RequestOpen { reduce_only: false,
+                                     price,
+                                     size: 0.1,
+                                     tag: None } }
+    }
+
+    fn market_trade(side: &str, price: f64, amount: f64) -> MarketTrade
+    {
+        MarketTrade { exchange: "binance-futures".to_string(),
+                      symbol: "ETHUSDT".to_string(),
+                      side: side.to_string(),
+                      price,
+                      timestamp: 1234568,
+                      amount }
+    }
+
+    fn cancelled_cids(account_event_rx: &mut mpsc::UnboundedReceiver<AccountEvent>) -> Vec<String>
+    {
+        let mut cids = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            if let AccountEventKind::OrdersCancelled(orders) = event.kind {
+                cids.extend(orders.into_iter().filter_map(|order| order.cid.map(|cid| cid.0)));
+            }
+        }
+        cids
+    }
+
+    #[tokio::test]
+    async fn test_partial_fill_of_one_leg_cancels_sibling()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        account.submit_oco(limit_order("oco_bid", Side::Buy, 16300.0), limit_order("oco_ask", Side::Sell, 16500.0)).await.unwrap();
+        assert_eq!(account.account_open_book.read().await.oco_groups.len(), 1);
+
+        // 卖单只成交一部分，买单随即被撤销
+        account.handle_trade_data(&market_trade("buy", 16600.0, 0.05)).await.unwrap();
+        let remaining = account.account_open_book.read().await.fetch_all();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].cid, Some(ClientOrderId("oco_ask".into())));
+        assert!(account.account_open_book.read().await.oco_groups.is_empty());
+        assert_eq!(cancelled_cids(&mut account_event_rx), vec!["oco_bid".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_leg_closer_to_trade_price_wins_when_both_would_fill()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        account.submit_oco(limit_order("oco_far", Side::Sell, 16500.0), limit_order("oco_near", Side::Sell, 16550.0)).await.unwrap();
+        account.handle_trade_data(&market_trade("buy", 16600.0, 1.0)).await.unwrap();
+
+        assert_eq!(cancelled_cids(&mut account_event_rx), vec!["oco_far".to_string()]);
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+        assert!(account.account_open_book.read().await.oco_groups.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_oco_with_crossing_leg_rejected_without_opening()
+    {
+        let mut account = create_test_account().await;
+        let result = account.submit_oco(limit_order("oco_bid", Side::Buy, 16300.0), limit_order("oco_cross", Side::Sell, 16000.0)).await;
+        assert!(matches!(result, Err(ExchangeError::InvalidOrder(_))));
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cancelling_a_leg_removes_its_group()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        // 手动撤销一笔，另一笔保留为普通挂单，订单组随之解除
+        account.submit_oco(limit_order("oco_bid", Side::Buy, 16300.0), limit_order("oco_ask", Side::Sell, 16500.0)).await.unwrap();
+        let bid = account.account_open_book.read().await.fetch_all().into_iter().find(|order| order.side == Side::Buy).unwrap();
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+        account.cancel_orders(vec![Order { instruction: bid.instruction,
+                                           exchange: Exchange::Hourglass,
+                                           instrument: bid.instrument.clone(),
+                                           timestamp: 1234567,
+                                           cid: bid.cid.clone(),
+                                           side: bid.side,
+                                           state: RequestCancel { id: Some(bid.state.id.clone()) } }],
+                              response_tx)
+               .await;
+        assert!(response_rx.await.unwrap()[0].is_ok());
+        assert!(account.account_open_book.read().await.oco_groups.is_empty());
+        assert_eq!(account.account_open_book.read().await.fetch_all().len(), 1);
+
+        // 全部撤单同样不会留下订单组
+        account.submit_oco(limit_order("oco_bid_2", Side::Buy, 16200.0), limit_order("oco_ask_2", Side::Sell, 16600.0)).await.unwrap();
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+        account.cancel_orders_all(response_tx).await;
+        assert_eq!(response_rx.await.unwrap().unwrap().len(), 3);
+        assert!(account.account_open_book.read().await.oco_groups.is_empty());
+    }
+}
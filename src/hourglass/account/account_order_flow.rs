@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+
+/// 未配置限制时默认的统计窗口（毫秒）。
+pub const DEFAULT_ORDER_FLOW_WINDOW_MS: i64 = 60_000;
+
+/// 计入报单流量统计的消息类型。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrderFlowMessage
+{
+    Submission,
+    Cancel,
+    Fill,
+}
+
+/// 按滑动时间窗口统计报单、撤单和成交次数，用于计算报单成交比（order-to-trade ratio）。
+///
+/// 交易所通常会对相对成交而言过多的报单消息进行限制，做市类回测需要遵守这一点。
+#[derive(Clone, Debug, Default)]
+pub struct OrderFlowTracker
+{
+    messages: VecDeque<(i64, OrderFlowMessage)>,
+    submissions: u64,
+    cancels: u64,
+    fills: u64,
+}
+
+impl OrderFlowTracker
+{
+    /// 记录一条消息，并移除 `window_ms` 窗口之外的旧消息。
+    pub fn record(&mut self, timestamp: i64, message: OrderFlowMessage, window_ms: i64)
+    {
+        self.messages.push_back((timestamp, message));
+        match message {
+            | OrderFlowMessage::Submission => self.submissions += 1,
+            | OrderFlowMessage::Cancel => self.cancels += 1,
+            | OrderFlowMessage::Fill => self.fills += 1,
+        }
+        self.prune(timestamp, window_ms);
+    }
+
+    /// 移除早于 `now - window_ms` 的消息。
+    pub fn prune(&mut self, now: i64, window_ms: i64)
+    {
+        let window_start = now - window_ms;
+        while let Some((timestamp, message)) = self.messages.front().copied() {
+            if timestamp >= window_start {
+                break;
+            }
+            self.messages.pop_front();
+            match message {
+                | OrderFlowMessage::Submission => self.submissions -= 1,
+                | OrderFlowMessage::Cancel => self.cancels -= 1,
+                | OrderFlowMessage::Fill => self.fills -= 1,
+            }
+        }
+    }
+
+    pub fn submissions(&self) -> u64
+    {
+        self.submissions
+    }
+
+    pub fn cancels(&self) -> u64
+    {
+        self.cancels
+    }
+
+    pub fn fills(&self) -> u64
+    {
+        self.fills
+    }
+
+    /// 当前窗口内的报单成交比：(报单数 + 撤单数) / 成交数。没有成交时按 1 笔计算，避免除零。
+    pub fn order_to_trade_ratio(&self) -> f64
+    {
+        (self.submissions + self.cancels) as f64 / self.fills.max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_order_to_trade_ratio_counts_messages_in_window()
+    {
+        let mut tracker = OrderFlowTracker::default();
+        tracker.record(0, OrderFlowMessage::Submission, 1_000);
+        tracker.record(100, OrderFlowMessage::Submission, 1_000);
+        tracker.record(200, OrderFlowMessage::Cancel, 1_000);
+        tracker.record(300, OrderFlowMessage::Fill, 1_000);
+
+        assert_eq!(tracker.submissions(), 2);
+        assert_eq!(tracker.cancels(), 1);
+        assert_eq!(tracker.fills(), 1);
+        assert_eq!(tracker.order_to_trade_ratio(), 3.0);
+    }
+
+    #[test]
+    fn test_messages_outside_window_are_pruned()
+    {
+        let mut tracker = OrderFlowTracker::default();
+        tracker.record(0, OrderFlowMessage::Submission, 1_000);
+        tracker.record(500, OrderFlowMessage::Fill, 1_000);
+        tracker.record(1_600, OrderFlowMessage::Submission, 1_000);
+
+        assert_eq!(tracker.submissions(), 1);
+        assert_eq!(tracker.fills(), 0);
+        assert_eq!(tracker.order_to_trade_ratio(), 1.0);
+    }
+}
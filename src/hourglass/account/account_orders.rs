@@ -2,9 +2,10 @@ use crate::{
     common::{
         instrument::Instrument,
         order::{
-            identification::{machine_id::generate_machine_id, OrderId},
+            identification::{oco_group_id::OcoGroupId, OrderId},
             order_instructions::OrderInstruction,
-            states::{open::Open, request_open::RequestOpen},
+            states::{open::Open, request_cancel::RequestCancel, request_open::RequestOpen},
+            stop_order::StopOrder,
             Order, OrderRole,
         },
         Side,
@@ -18,10 +19,12 @@ use crate::{
 };
 use async_trait::async_trait;
 use dashmap::{mapref::one::RefMut, DashMap};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     sync::atomic::{AtomicU64, Ordering},
-    time::{SystemTime, UNIX_EPOCH},
 };
 
 #[derive(Debug)]
@@ -33,6 +36,28 @@ pub struct AccountOrders
     pub request_counter: AtomicU64,
     pub order_counter: AtomicU64,
     pub instrument_orders_map: DashMap<Instrument, OpenOrdersBook>,
+    pub pending_cancels: Vec<PendingCancel>, // 已提交但尚未生效的撤单请求，按提交顺序排列
+    pub pending_stops: Vec<StopOrder>,       // 尚未触发的条件单，按提交顺序排列
+    pub oco_groups: HashMap<OcoGroupId, (OrderId, OrderId)>, // OCO 关联订单组，其中一笔成交时撤销另一笔
+    pub oco_group_counter: u64,
+    pub order_id_rng: ChaCha12Rng, // 订单ID随机部分的随机数生成器，未设置种子时由系统熵初始化
+}
+
+/// `order_id_rng` 的完整内部状态，恢复后生成的随机序列与保存时完全一致。
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct OrderIdRngState
+{
+    pub seed: [u8; 32],
+    pub stream: u64,
+    pub word_pos: u128,
+}
+
+/// 已提交但尚未到达交易所的撤单请求，在 `effective_ts` 时才真正移除挂单。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PendingCancel
+{
+    pub effective_ts: i64,
+    pub request: Order<RequestCancel>,
 }
 
 impl AccountOrders
@@ -68,7 +93,7 @@ impl AccountOrders
     /// async fn main()
     /// {
     ///     let instruments = vec![Instrument::new("BTC", "USD", InstrumentKind::Spot)];
-    ///     let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10);
+    ///     let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10, 0);
     ///     let account_orders = AccountOrders::new(123124124124, instruments, account_latency).await;
     ///     println!("新建的 AccountOrders 实例: {:?}", account_orders);
     /// }
@@ -82,7 +107,37 @@ impl AccountOrders
                request_counter: AtomicU64::new(0),
                instrument_orders_map: instruments.into_iter().map(|instrument| (instrument, OpenOrdersBook::default())).collect(),
                latency_generator: account_latency,
-               selectable_latencies }
+               selectable_latencies,
+               pending_cancels: Vec::new(),
+               pending_stops: Vec::new(),
+               oco_groups: HashMap::new(),
+               oco_group_counter: 0,
+               order_id_rng: ChaCha12Rng::from_entropy() }
+    }
+
+    /// 用一个种子重置延迟生成器与订单ID的随机数生成器，使之后的延迟与订单ID可复现。
+    pub fn reseed(&mut self, seed: u64)
+    {
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        self.latency_generator.reseed(rng.gen());
+        self.order_id_rng = rng;
+    }
+
+    /// 导出 `order_id_rng` 的内部状态，用于检查点。
+    pub fn order_id_rng_state(&self) -> OrderIdRngState
+    {
+        OrderIdRngState { seed: self.order_id_rng.get_seed(),
+                          stream: self.order_id_rng.get_stream(),
+                          word_pos: self.order_id_rng.get_word_pos() }
+    }
+
+    /// 从检查点恢复 `order_id_rng` 的内部状态。
+    pub fn restore_order_id_rng(&mut self, state: &OrderIdRngState)
+    {
+        let mut rng = ChaCha12Rng::from_seed(state.seed);
+        rng.set_stream(state.stream);
+        rng.set_word_pos(state.word_pos);
+        self.order_id_rng = rng;
     }
 
     /// 返回指定 [`Instrument`] 的 [`OpenOrdersBook`] 的可变引用。
@@ -118,6 +173,22 @@ impl AccountOrders
             .collect()
     }
 
+    /// 从所有金融工具的挂单簿中取出在 `now` 已经到期的 GTT 挂单，同时返回查找过程中检查过的挂单数量。
+    ///
+    /// 每个挂单簿只访问其到期索引中已经到期的条目，开销与到期的挂单数量相关，与挂单总数无关，
+    /// 见 [`OpenOrdersBook::take_expired_orders`]。
+    pub fn take_expired_orders(&self, now: i64) -> (Vec<Order<Open>>, usize)
+    {
+        let mut expired = Vec::new();
+        let mut inspected = 0;
+        for mut orders in self.instrument_orders_map.iter_mut() {
+            let (book_expired, book_inspected) = orders.take_expired_orders(now);
+            expired.extend(book_expired);
+            inspected += book_inspected;
+        }
+        (expired, inspected)
+    }
+
     /// 从提供的 [`Order<RequestOpen>`] 构建一个 [`Order<Open>`]。请求计数器递增，
     /// 在 increment_request_counter 方法中，使用 Ordering::Relaxed 进行递增。
     pub async fn build_order_open(&mut self, request: Order<RequestOpen>, role: OrderRole) -> Order<Open>
@@ -131,11 +202,15 @@ impl AccountOrders
                 cid: request.cid,
                 timestamp: request.timestamp,
                 side: request.side,
-                state: Open { id: self.order_id(),
+                state: Open { id: self.order_id(request.timestamp),
                               price: request.state.price,
                               size: request.state.size,
                               filled_quantity: 0.0,
-                              order_role: role } }
+                              avg_fill_price: 0.0,
+                              reduce_only: request.state.reduce_only,
+                              order_role: role,
+                              tag: request.state.tag,
+                              iceberg: None } }
     }
 
     /// 增加请求计数器的值。
@@ -155,22 +230,87 @@ impl AccountOrders
 
     /// 生成一个新的 [OrderId]。
     ///
-    /// 该函数根据当前的系统时间戳和订单计数器生成一个唯一的 [OrderId]。
-    /// 系统时间戳以毫秒为单位计算，并结合机器 ID 和计数器来确保生成的 ID 唯一。
+    /// 该函数根据订单的时间戳（毫秒）和订单计数器生成一个唯一的 [OrderId]，并结合机器 ID 和计数器来确保生成的 ID 唯一。
+    /// 随机部分取自 `order_id_rng`，因此不依赖系统时钟，设置种子后同样的订单流得到同样的订单ID。
     /// 由于计数器使用 [Ordering::SeqCst] 进行递增，确保了在多线程环境下的顺序一致性和原子性。
     ///
     /// # 返回值
     ///
-    /// 返回一个唯一的 [OrderId]，该 ID 是基于当前的时间戳、机器 ID 和计数器生成的。
-    ///
-    /// # 错误处理
-    ///
-    /// 如果系统时间出现倒退，将导致程序崩溃并输出错误信息 "时间出现倒退"。
-    pub fn order_id(&self) -> OrderId
+    /// 返回一个唯一的 [OrderId]，该 ID 是基于订单时间戳、机器 ID 和计数器生成的。
+    pub fn order_id(&mut self, timestamp: i64) -> OrderId
     {
-        let now_ts = SystemTime::now().duration_since(UNIX_EPOCH).expect("时间出现倒退").as_millis() as u64;
         let counter = self.order_counter.fetch_add(1, Ordering::SeqCst);
-        OrderId::new(now_ts, self.machine_id, counter)
+        OrderId::with_random_component(timestamp.max(0) as u64, self.machine_id, counter, self.order_id_rng.gen())
+    }
+
+    /// 登记一个将在 `effective_ts` 生效的撤单请求。
+    pub fn schedule_cancel(&mut self, request: Order<RequestCancel>, effective_ts: i64)
+    {
+        self.pending_cancels.push(PendingCancel { effective_ts, request });
+    }
+
+    /// 取出所有在 `now` 及之前生效的撤单请求，按提交顺序返回，尚未生效的继续保留。
+    pub fn take_due_cancels(&mut self, now: i64) -> Vec<Order<RequestCancel>>
+    {
+        let (due, pending) = std::mem::take(&mut self.pending_cancels).into_iter().partition::<Vec<_>, _>(|pending| pending.effective_ts <= now);
+        self.pending_cancels = pending;
+        due.into_iter().map(|pending| pending.request).collect()
+    }
+
+    /// 登记一笔等待触发的条件单。
+    pub fn add_pending_stop(&mut self, stop: StopOrder)
+    {
+        self.pending_stops.push(stop);
+    }
+
+    /// 取出撤单请求指向的条件单：同一金融工具上订单ID或客户端订单ID与请求一致的第一笔，没有时返回 `None`。
+    pub fn take_pending_stop(&mut self, request: &Order<RequestCancel>) -> Option<StopOrder>
+    {
+        let index = self.pending_stops.iter().position(|stop| {
+                                                 let id_match = request.state.id.is_some() && stop.id == request.state.id;
+                                                 let cid_match = request.cid.is_some() && stop.order.cid == request.cid;
+                                                 stop.order.instrument == request.instrument && (id_match || cid_match)
+                                             })?;
+        Some(self.pending_stops.remove(index))
+    }
+
+    /// 取出 `instrument` 上被成交价 `price` 触发的条件单，按提交顺序返回，未触发的继续保留。
+    ///
+    /// 判断触发前先用 `price` 更新该金融工具上跟踪止损的最优价格。
+    pub fn take_triggered_stops(&mut self, instrument: &Instrument, price: f64) -> Vec<StopOrder>
+    {
+        self.pending_stops.iter_mut().filter(|stop| &stop.order.instrument == instrument).for_each(|stop| stop.update_watermark(price));
+        let (triggered, pending) = std::mem::take(&mut self.pending_stops).into_iter()
+                                                                          .partition::<Vec<_>, _>(|stop| &stop.order.instrument == instrument && stop.is_triggered(price));
+        self.pending_stops = pending;
+        triggered
+    }
+
+    /// 把两笔挂单关联为一组 OCO 订单，返回新的组标识符。
+    pub fn link_oco(&mut self, leg_a: OrderId, leg_b: OrderId) -> OcoGroupId
+    {
+        self.oco_group_counter += 1;
+        let group_id = OcoGroupId(self.oco_group_counter);
+        self.oco_groups.insert(group_id, (leg_a, leg_b));
+        group_id
+    }
+
+    /// 解除 `order_id` 所在的 OCO 关联并返回另一笔订单的 [`OrderId`]，不属于任何组时返回 `None`。
+    pub fn take_oco_sibling(&mut self, order_id: &OrderId) -> Option<OrderId>
+    {
+        let (group_id, sibling) = self.oco_groups.iter().find_map(|(group_id, (leg_a, leg_b))| {
+                                                            if leg_a == order_id {
+                                                                Some((*group_id, leg_b.clone()))
+                                                            }
+                                                            else if leg_b == order_id {
+                                                                Some((*group_id, leg_a.clone()))
+                                                            }
+                                                            else {
+                                                                None
+                                                            }
+                                                        })?;
+        self.oco_groups.remove(&group_id);
+        Some(sibling)
     }
 }
 #[async_trait]
@@ -227,6 +367,8 @@ impl OrderRoleClassifier for AccountOrders
 
             | OrderInstruction::GoodTilCancelled => self.determine_limit_order_role(order, current_price), // GTC订单与限价订单处理类似
 
+            | OrderInstruction::GoodTilTime { .. } => self.determine_limit_order_role(order, current_price), // GTT订单到期前与限价订单处理相同
+
             | OrderInstruction::Cancel => {
                 todo!() // 取消订单逻辑
             }
@@ -238,22 +380,15 @@ impl OrderRoleClassifier for AccountOrders
     /// # 参数
     ///
     /// - `order`: 待处理的限价订单 (`Order<RequestOpen>`)。
-    /// - `current_price`: 当前市场价格。
+    /// - `current_price`: 对手方最优价格，买单为最新卖价，卖单为最新买价。
     ///
     /// # 返回值
     ///
-    /// - `Ok(OrderRole::Maker)`: 如果订单价格与当前市场价格相比，具有优势（买单价格高于或等于市场价格，或卖单价格低于或等于市场价格），则订单作为 Maker 角色。
-    /// - `Ok(OrderRole::Taker)`: 如果订单价格与当前市场价格相比，处于劣势（买单价格低于市场价格，或卖单价格高于市场价格），则订单作为 Taker 角色。
-    ///
-    /// # 逻辑
-    ///
-    /// - 对于买单 (`Side::Buy`):
-    ///   - 如果订单价格 (`order.state.price`) 大于或等于当前市场价格 (`current_price`)，则返回 `OrderRole::Maker`。
-    ///   - 否则，返回 `OrderRole::Taker`。
+    /// - `Ok(OrderRole::Maker)`: 订单不与对手方最优价格交叉（买单价格低于最新卖价，或卖单价格高于最新买价），挂入挂单簿等待成交。
+    /// - `Ok(OrderRole::Taker)`: 订单与对手方最优价格交叉（买单价格高于或等于最新卖价，或卖单价格低于或等于最新买价），立即成交。
     ///
-    /// - 对于卖单 (`Side::Sell`):
-    ///   - 如果订单价格 (`order.state.price`) 小于或等于当前市场价格 (`current_price`)，则返回 `OrderRole::Maker`。
-    ///   - 否则，返回 `OrderRole::Taker`。
+    /// 普通限价订单交叉时按 Taker 成交，不会被拒绝；只有 `PostOnlyLimit` 订单在交叉时由 `determine_post_only_order_role`
+    /// 返回 `ExchangeError::PostOnlyWouldCross`。
     fn determine_limit_order_role(&self, order: &Order<RequestOpen>, current_price: f64) -> Result<OrderRole, ExchangeError>
     {
         match order.side {
@@ -311,7 +446,8 @@ impl OrderRoleClassifier for AccountOrders
                     Ok(OrderRole::Maker)
                 }
                 else {
-                    Err(ExchangeError::PostOnlyViolation("PostOnly order should be rejected".into()))
+                    Err(ExchangeError::PostOnlyWouldCross { price: order.state.price,
+                                                            best_opposing_price: current_price })
                 }
             }
             | Side::Sell => {
@@ -319,8 +455,9 @@ impl OrderRoleClassifier for AccountOrders
                     Ok(OrderRole::Maker)
                 }
                 else {
-                    Err(ExchangeError::PostOnlyViolation("PostOnly order should be rejected".into()))
                     // 返回需要拒绝的错误，但不立即执行拒绝操作
+                    Err(ExchangeError::PostOnlyWouldCross { price: order.state.price,
+                                                            best_opposing_price: current_price })
                 }
             }
         }
@@ -354,50 +491,33 @@ pub trait OrderRoleClassifier
     /// # 参数
     ///
     /// - `order`: 待处理的限价订单 (`Order<RequestOpen>`)。
-    /// - `current_price`: 当前市场价格。
+    /// - `current_price`: 对手方最优价格，买单为最新卖价，卖单为最新买价。
     ///
     /// # 返回值
     ///
-    /// - `Ok(OrderRole::Maker)`: 如果订单价格与当前市场价格相比，具有优势（买单价格高于或等于市场价格，或卖单价格低于或等于市场价格），则订单作为 Maker 角色。
-    /// - `Ok(OrderRole::Taker)`: 如果订单价格与当前市场价格相比，处于劣势（买单价格低于市场价格，或卖单价格高于市场价格），则订单作为 Taker 角色。
-    ///
-    /// # 逻辑
-    ///
-    /// - 对于买单 (`Side::Buy`):
-    ///   - 如果订单价格 (`order.state.price`) 大于或等于当前市场价格 (`current_price`)，则返回 `OrderRole::Maker`。
-    ///   - 否则，返回 `OrderRole::Taker`。
+    /// - `Ok(OrderRole::Maker)`: 订单不与对手方最优价格交叉（买单价格低于最新卖价，或卖单价格高于最新买价），挂入挂单簿等待成交。
+    /// - `Ok(OrderRole::Taker)`: 订单与对手方最优价格交叉（买单价格高于或等于最新卖价，或卖单价格低于或等于最新买价），立即成交。
     ///
-    /// - 对于卖单 (`Side::Sell`):
-    ///   - 如果订单价格 (`order.state.price`) 小于或等于当前市场价格 (`current_price`)，则返回 `OrderRole::Maker`。
-    ///   - 否则，返回 `OrderRole::Taker`。
+    /// 普通限价订单交叉时按 Taker 成交，不会被拒绝；只有 `PostOnlyLimit` 订单在交叉时由 `determine_post_only_order_role`
+    /// 返回 `ExchangeError::PostOnlyWouldCross`。
     fn determine_limit_order_role(&self, order: &Order<RequestOpen>, current_price: f64) -> Result<OrderRole, ExchangeError>;
     /// FIXME 这个逻辑提前到了 open_orders 中。所以可能产生重复。
     /// 判断 PostOnly 订单是否符合条件，并确定其是 Maker 还是被拒绝。
     ///
-    /// 如果订单不符合 PostOnly 的条件（即买单价格低于当前市场价格，或卖单价格高于当前市场价格），
-    /// 则会拒绝该订单，并将其从待处理订单中删除。
+    /// 如果订单的限价与对手方最优价格交叉（即买单价格不低于当前最优卖价，或卖单价格不高于当前最优买价），
+    /// 挂出后会立即吃掉流动性，因此拒绝该订单。对手方最优价格取自由最新外部成交更新的单层订单簿。
     ///
     /// # 参数
     ///
     /// - `order`: 待处理的 PostOnly 订单 (`Order<RequestOpen>`)。
-    /// - `current_price`: 当前市场价格。
+    /// - `current_price`: 对手方最优价格，买单为最新卖价，卖单为最新买价。
     ///
     /// # 返回值
     ///
-    /// - `Ok(OrderRole::Maker)`: 如果订单价格符合 PostOnly 的条件，
-    ///   即买单价格高于或等于市场价格，或者卖单价格低于或等于市场价格，则订单作为 Maker 角色。
-    /// - `Err(ExecutionError::OrderRejected)`: 如果订单价格不符合 PostOnly 的条件，
-    ///   即买单价格低于市场价格，或者卖单价格高于市场价格，则订单会被拒绝并从待处理订单中删除。
-    ///
-    /// # 逻辑
-    ///
-    /// - 对于买单 (`Side::Buy`):
-    ///   - 如果订单价格 (`order.state.price`) 大于或等于当前市场价格 (`current_price`)，则返回 `OrderRole::Maker`。
-    ///   - 否则，调用 `self.reject_post_only_order(order)` 拒绝订单，并返回错误。
-    ///
-    /// - 对于卖单 (`Side::Sell`):
-    ///   - 如果订单价格 (`order.state.price`) 小于或等于当前市场价格 (`current_price`)，则返回 `OrderRole::Maker`。
-    ///   - 否则，调用 `self.reject_post_only_order(order)` 拒绝订单，并返回错误。
+    /// - `Ok(OrderRole::Maker)`: 买单价格低于最优卖价，或卖单价格高于最优买价。PostOnly 订单不会得到其他角色，
+    ///   因此被接受的 PostOnly 订单之后的所有成交都按 Maker 费率计费。
+    /// - `Err(ExchangeError::PostOnlyWouldCross)`: 订单价格与对手方最优价格交叉。此时 `atomic_open` 在构建挂单之前返回，
+    ///   订单不会进入挂单簿，也不会锁定余额。
     fn determine_post_only_order_role(&self, order: &Order<RequestOpen>, current_price: f64) -> Result<OrderRole, ExchangeError>;
 }
 
@@ -405,7 +525,7 @@ pub trait OrderRoleClassifier
 pub trait LatencySimulator
 {
     async fn generate_latencies(latency_generator: &mut AccountLatency) -> [i64; 20];
-    fn get_random_latency(&self) -> i64;
+    fn get_random_latency(&mut self) -> i64;
     async fn process_backtest_requestopen_with_a_simulated_latency(&mut self, order: Order<RequestOpen>) -> Order<RequestOpen>;
     fn update_latency(&mut self, current_time: i64);
 }
@@ -434,7 +554,7 @@ impl LatencySimulator for AccountOrders
 
     /// 生成一组预定义的延迟值数组，用于模拟订单延迟。
     ///
-    /// 该函数通过调用 `fluctuate_latency` 函数来动态调整延迟值，并将结果存储在一个数组中。
+    /// 该函数通过调用 [`AccountLatency::next_latency`] 依次抽取延迟值，并将结果存储在一个数组中，相同种子的生成器得到相同的数组。
     ///
     /// # 参数
     ///
@@ -445,17 +565,9 @@ impl LatencySimulator for AccountOrders
     /// 返回一个包含 20 个延迟值的数组 `[i64; 20]`，每个延迟值是通过 `AccountLatency` 计算得到的。
     async fn generate_latencies(latency_generator: &mut AccountLatency) -> [i64; 20]
     {
-        let mut seed = generate_machine_id().unwrap();
-        let mut rng = rand::thread_rng();
         let mut latencies = [0; 20];
-
-        for (i, latency) in latencies.iter_mut().enumerate() {
-            // 增加种子的变化范围，确保不同种子之间有足够大的差异
-            seed += rng.gen_range(1..1000000) as u64 + (i as u64 * 9999);
-            fluctuate_latency(latency_generator, seed as i64);
-            *latency = latency_generator.current_value;
-            // 调试信息，输出种子和当前值
-            // println!("Iteration: {}, Seed: {}, Current Value: {}", i, seed, latency_generator.current_value);
+        for latency in latencies.iter_mut() {
+            *latency = latency_generator.next_latency();
         }
         latencies
     }
@@ -468,13 +580,17 @@ impl LatencySimulator for AccountOrders
     /// # 返回值
     ///
     /// 返回一个随机选择的延迟值 `i64`。
-    fn get_random_latency(&self) -> i64
+    fn get_random_latency(&mut self) -> i64
     {
-        let mut rng = rand::thread_rng();
-        let idx = rng.gen_range(0..self.selectable_latencies.len());
+        let idx = self.latency_generator.sample_index(self.selectable_latencies.len());
         self.selectable_latencies[idx]
     }
 
+    /// 把订单的时间戳改为其到达交易所的时间：提交时间加上 `latency_generator` 在提交时刻的延迟。
+    ///
+    /// 撮合只让到达时间不晚于外部成交时间的挂单参与，因此在提交时间 `T` 下单、延迟为 `L` 时，
+    /// 早于 `T + L` 的成交不会成交这笔订单。
+    ///
     /// # 参数
     ///
     /// - `order`: 要处理的订单请求 (`Order<RequestOpen>`)。
@@ -485,8 +601,9 @@ impl LatencySimulator for AccountOrders
     ///   注意 : 仅在回测场景下用这个方法！！！
     async fn process_backtest_requestopen_with_a_simulated_latency(&mut self, order: Order<RequestOpen>) -> Order<RequestOpen>
     {
-        // 从预定义的延迟值数组中选择一个延迟值
-        let latency = self.get_random_latency();
+        // 延迟随提交时间按 `FluctuationMode` 变化
+        self.update_latency(order.timestamp);
+        let latency = self.latency_generator.current_value;
         let adjusted_client_ts = order.timestamp + latency;
 
         // 创建并返回新的 RequestOpen 订单
@@ -498,7 +615,8 @@ impl LatencySimulator for AccountOrders
                 side: order.side,
                 state: RequestOpen { reduce_only: order.state.reduce_only,
                                      price: order.state.price,
-                                     size: order.state.size } }
+                                     size: order.state.size,
+                                     tag: order.state.tag } }
     }
 
     /// 更新账户的延迟值。
@@ -508,7 +626,7 @@ impl LatencySimulator for AccountOrders
     ///
     /// # 参数
     ///
-    /// - current_time: 当前时间，以微秒为单位，作为调整延迟值的参考点。
+    /// - current_time: 当前时间，以毫秒为单位，作为调整延迟值的参考点。
     fn update_latency(&mut self, current_time: i64)
     {
         fluctuate_latency(&mut self.latency_generator, current_time);
@@ -538,7 +656,7 @@ mod tests
     #[tokio::test]
     async fn test_generate_latencies()
     {
-        let account_latency = AccountLatency::new(FluctuationMode::Cosine, 100, 10);
+        let account_latency = AccountLatency::new(FluctuationMode::Cosine, 100, 10, 0);
 
         let latency_generator = Arc::new(RwLock::new(account_latency));
 
@@ -559,19 +677,32 @@ mod tests
     async fn test_get_random_latency()
     {
         let instruments = vec![Instrument::new("BTC", "USD", InstrumentKind::Spot)];
-        let account_latency = AccountLatency::new(FluctuationMode::Uniform, 100, 10);
+        let account_latency = AccountLatency::new(FluctuationMode::Uniform { min: 10, max: 100 }, 100, 10, 0);
 
-        let account_orders = AccountOrders::new(123, instruments, account_latency).await;
+        let mut account_orders = AccountOrders::new(123, instruments, account_latency).await;
 
         let latency = account_orders.get_random_latency();
         assert!(latency >= 10 && latency <= 100);
     }
 
+    #[tokio::test]
+    async fn test_get_random_latency_is_reproducible_with_same_seed()
+    {
+        let instruments = vec![Instrument::new("BTC", "USD", InstrumentKind::Spot)];
+        let mut picks = Vec::new();
+        for _ in 0..2 {
+            let account_latency = AccountLatency::new(FluctuationMode::Uniform { min: 10, max: 100 }, 100, 10, 42);
+            let mut account_orders = AccountOrders::new(123, instruments.clone(), account_latency).await;
+            picks.push((0..16).map(|_| account_orders.get_random_latency()).collect::<Vec<_>>());
+        }
+        assert_eq!(picks[0], picks[1]);
+    }
+
     #[tokio::test]
     async fn test_ins_orders_mut()
     {
         let instruments = vec![Instrument::new("BTC", "USD", InstrumentKind::Spot)];
-        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10);
+        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10, 0);
 
         let account_orders = AccountOrders::new(123124, instruments.clone(), account_latency).await;
 
@@ -590,7 +721,7 @@ mod tests
     async fn test_fetch_all()
     {
         let instruments = vec![Instrument::new("BTC", "USD", InstrumentKind::Spot)];
-        let account_latency = AccountLatency::new(FluctuationMode::Cosine, 100, 10);
+        let account_latency = AccountLatency::new(FluctuationMode::Cosine, 100, 10, 0);
 
         let account_orders = AccountOrders::new(1231, instruments, account_latency).await;
 
@@ -602,7 +733,7 @@ mod tests
     async fn test_increment_request_counter()
     {
         let instruments = vec![Instrument::new("BTC", "USD", InstrumentKind::Spot)];
-        let account_latency = AccountLatency::new(FluctuationMode::Cosine, 100, 10);
+        let account_latency = AccountLatency::new(FluctuationMode::Cosine, 100, 10, 0);
         let account_orders = AccountOrders::new(09890, instruments, account_latency).await;
 
         assert_eq!(account_orders.order_counter.load(Ordering::Acquire), 0);
@@ -614,13 +745,13 @@ mod tests
     async fn test_order_id()
     {
         let instruments = vec![Instrument::new("BTC", "USD", InstrumentKind::Spot)];
-        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10);
-        let account_orders = AccountOrders::new(123123, instruments, account_latency).await;
+        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10, 0);
+        let mut account_orders = AccountOrders::new(123123, instruments, account_latency).await;
 
-        let first_order_id = account_orders.order_id();
+        let first_order_id = account_orders.order_id(1234567);
         let second_order_id = {
             account_orders.increment_order_counter();
-            account_orders.order_id()
+            account_orders.order_id(1234567)
         };
 
         // 获取前 51 位 (即 [timestamp:41 bits] [machine_id:10 bits]) 的值
@@ -635,7 +766,7 @@ mod tests
     async fn test_update_latency()
     {
         let instruments = vec![Instrument::new("BTC", "USD", InstrumentKind::Spot)];
-        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10);
+        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10, 0);
         let mut account_orders = AccountOrders::new(123123, instruments, account_latency).await;
 
         account_orders.update_latency(1000);
@@ -648,7 +779,7 @@ mod tests
     async fn test_process_backtest_requestopen_with_a_simulated_latency()
     {
         let instruments = vec![Instrument::new("BTC", "USD", InstrumentKind::Spot)];
-        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10);
+        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10, 0);
         let mut account_orders = AccountOrders::new(123123, instruments, account_latency).await;
 
         let order = Order { instruction: OrderInstruction::Limit,
@@ -659,7 +790,8 @@ mod tests
                             side: Side::Buy,
                             state: RequestOpen { reduce_only: false,
                                                  price: 35000.0,
-                                                 size: 0.1 } };
+                                                 size: 0.1,
+                                                 tag: None } };
 
         let simulated_order = account_orders.process_backtest_requestopen_with_a_simulated_latency(order).await;
         assert!(simulated_order.timestamp >= 1625232523000 + 10); // Assuming latency is at least 10
@@ -670,7 +802,7 @@ mod tests
     async fn test_determine_maker_taker()
     {
         let instruments = vec![Instrument::new("BTC", "USD", InstrumentKind::Spot)];
-        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10);
+        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10, 0);
         let account_orders = AccountOrders::new(123123, instruments, account_latency).await;
 
         let order = Order { instruction: OrderInstruction::Limit,
@@ -681,7 +813,8 @@ mod tests
                             side: Side::Buy,
                             state: RequestOpen { reduce_only: false,
                                                  price: 35000.0,
-                                                 size: 0.1 } };
+                                                 size: 0.1,
+                                                 tag: None } };
 
         // 构建模拟的订单簿
         let order_book = SingleLevelOrderBook { latest_bid: 34900.0,
@@ -698,7 +831,7 @@ mod tests
     async fn test_determine_post_only_order_role()
     {
         let instruments = vec![Instrument::new("BTC", "USD", InstrumentKind::Spot)];
-        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10);
+        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10, 0);
         let account_orders = AccountOrders::new(123123, instruments, account_latency).await;
 
         let order = Order { instruction: OrderInstruction::PostOnlyLimit,
@@ -709,7 +842,8 @@ mod tests
                             side: Side::Buy,
                             state: RequestOpen { reduce_only: false,
                                                  price: 35000.0, // 买单价格
-                                                 size: 0.1 } };
+                                                 size: 0.1,
+                                                 tag: None } };
 
         // 成功场景：Post-Only 买单，挂单价格低于市场价格，成为 Maker
         let result = account_orders.determine_post_only_order_role(&order, 35001.0);
@@ -717,14 +851,16 @@ mod tests
 
         // 失败场景：Post-Only 买单，挂单价格高于市场价格，违反条件，应该返回错误
         let reject_result = account_orders.determine_post_only_order_role(&order, 34999.0);
-        assert_eq!(reject_result.unwrap_err().to_string(), "PostOnlyViolation");
+        assert_eq!(reject_result.unwrap_err(),
+                   ExchangeError::PostOnlyWouldCross { price: 35000.0,
+                                                       best_opposing_price: 34999.0 });
     }
 
     #[tokio::test]
     async fn test_build_order_open()
     {
         let instruments = vec![Instrument::new("BTC", "USD", InstrumentKind::Spot)];
-        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10);
+        let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10, 0);
         let mut account_orders = AccountOrders::new(123123, instruments, account_latency).await;
 
         let order = Order { instruction: OrderInstruction::Limit,
@@ -735,7 +871,8 @@ mod tests
                             side: Side::Buy,
                             state: RequestOpen { reduce_only: false,
                                                  price: 35000.0,
-                                                 size: 0.1 } };
+                                                 size: 0.1,
+                                                 tag: None } };
 
         let open_order = account_orders.build_order_open(order, OrderRole::Maker).await;
 
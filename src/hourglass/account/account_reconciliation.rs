@@ -0,0 +1,189 @@
+use crate::{
+    common::{account_positions::position_meta::PositionMeta, instrument::Instrument, trade::ClientTrade, Side},
+    hourglass::account::HourglassAccount,
+};
+use std::{collections::HashMap, fmt};
+use tokio::sync::RwLock;
+
+/// 成交与仓位对账的浮点容忍度。
+pub const RECONCILIATION_EPSILON: f64 = 1e-9;
+
+/// 某个金融工具的成交净量与仓位净量不一致。
+#[derive(Clone, Debug, PartialEq)]
+pub struct PositionMismatch
+{
+    pub instrument: Instrument,
+    /// 按成交记录累加出的带符号净量（买为正，卖为负）。
+    pub fills_net_size: f64,
+    /// 当前仓位的带符号净量（多头为正，空头为负）。
+    pub position_net_size: f64,
+}
+
+impl PositionMismatch
+{
+    /// 不一致的幅度，即 `position_net_size - fills_net_size`。
+    pub fn difference(&self) -> f64
+    {
+        self.position_net_size - self.fills_net_size
+    }
+}
+
+impl fmt::Display for PositionMismatch
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f,
+               "{} fills net {} but position net {} (mismatch {})",
+               self.instrument,
+               self.fills_net_size,
+               self.position_net_size,
+               self.difference())
+    }
+}
+
+/// 按金融工具累加成交的带符号数量，买为正，卖为负。
+pub fn net_fill_sizes(trades: &[ClientTrade]) -> HashMap<Instrument, f64>
+{
+    let mut net_sizes = HashMap::new();
+    for trade in trades {
+        let signed_size = match trade.side {
+            | Side::Buy => trade.size,
+            | Side::Sell => -trade.size,
+        };
+        *net_sizes.entry(trade.instrument.clone()).or_insert(0.0) += signed_size;
+    }
+    net_sizes
+}
+
+impl HourglassAccount
+{
+    /// 将成交记录与当前仓位对账，返回所有净量不一致的金融工具。
+    ///
+    /// `trades` 应为账户自开始以来的全部成交（例如客户端收集的 `AccountEvent::Trade`），
+    /// 对每个出现在成交记录或仓位中的金融工具，成交净量应等于多头仓位数量减去空头仓位数量。
+    /// 加仓、减仓与反手都只改变仓位的拆分方式，不应改变净量，因此这里适合在回测结束时作为一道检查。
+    pub async fn reconcile_fills_with_positions(&self, trades: &[ClientTrade]) -> Vec<PositionMismatch>
+    {
+        let fills = net_fill_sizes(trades);
+
+        let mut positions = HashMap::new();
+        let account_positions = &self.positions;
+        accumulate_net_sizes(&account_positions.margin_pos_long, |p| &p.meta, Side::Buy, &mut positions).await;
+        accumulate_net_sizes(&account_positions.margin_pos_short, |p| &p.meta, Side::Sell, &mut positions).await;
+        accumulate_net_sizes(&account_positions.perpetual_pos_long, |p| &p.meta, Side::Buy, &mut positions).await;
+        accumulate_net_sizes(&account_positions.perpetual_pos_short, |p| &p.meta, Side::Sell, &mut positions).await;
+        accumulate_net_sizes(&account_positions.futures_pos_long, |p| &p.meta, Side::Buy, &mut positions).await;
+        accumulate_net_sizes(&account_positions.futures_pos_short, |p| &p.meta, Side::Sell, &mut positions).await;
+        accumulate_net_sizes(&account_positions.option_pos_long_call, |p| &p.meta, Side::Buy, &mut positions).await;
+        accumulate_net_sizes(&account_positions.option_pos_long_put, |p| &p.meta, Side::Buy, &mut positions).await;
+        accumulate_net_sizes(&account_positions.option_pos_short_call, |p| &p.meta, Side::Sell, &mut positions).await;
+        accumulate_net_sizes(&account_positions.option_pos_short_put, |p| &p.meta, Side::Sell, &mut positions).await;
+
+        let mut instruments: Vec<&Instrument> = fills.keys().chain(positions.keys()).collect();
+        instruments.sort();
+        instruments.dedup();
+
+        instruments.into_iter()
+                   .filter_map(|instrument| {
+                       let fills_net_size = fills.get(instrument).copied().unwrap_or(0.0);
+                       let position_net_size = positions.get(instrument).copied().unwrap_or(0.0);
+                       ((fills_net_size - position_net_size).abs() > RECONCILIATION_EPSILON).then(|| PositionMismatch { instrument: instrument.clone(),
+                                                                                                                         fills_net_size,
+                                                                                                                         position_net_size })
+                   })
+                   .collect()
+    }
+}
+
+async fn accumulate_net_sizes<T>(positions: &RwLock<HashMap<Instrument, T>>, meta: fn(&T) -> &PositionMeta, side: Side, net_sizes: &mut HashMap<Instrument, f64>)
+{
+    for (instrument, position) in positions.read().await.iter() {
+        let size = meta(position).current_size;
+        let signed_size = match side {
+            | Side::Buy => size,
+            | Side::Sell => -size,
+        };
+        *net_sizes.entry(instrument.clone()).or_insert(0.0) += signed_size;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            account_positions::{perpetual::PerpetualPositionConfig, PositionDirectionMode, PositionMarginMode},
+            instrument::kind::InstrumentKind,
+            order::identification::OrderId,
+            trade::ClientTradeId,
+        },
+        hourglass::account::account_handlers::position_handler::PositionHandler,
+        test_utils::{create_test_account, create_test_perpetual_position},
+        Exchange,
+    };
+
+    fn trade(id: i64, instrument: &Instrument, side: Side, size: f64) -> ClientTrade
+    {
+        ClientTrade { exchange: Exchange::Hourglass,
+                      timestamp: 1690000000 + id,
+                      trade_id: ClientTradeId(id),
+                      order_id: Some(OrderId(id as u64)),
+                      cid: None,
+                      instrument: instrument.clone(),
+                      side,
+                      price: 100.0,
+                      size,
+                      fees: 0.0,
+                      tag: None }
+    }
+
+    #[tokio::test]
+    async fn test_fills_reconcile_through_adds_reductions_and_flip()
+    {
+        let mut account = create_test_account().await;
+        let instrument = Instrument::new("BTC", "USDT", InstrumentKind::Perpetual);
+        let config = PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                               leverage: 1.0,
+                                               position_direction_mode: PositionDirectionMode::Net };
+        account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(), config.clone());
+        account.positions.perpetual_pos_short_config.write().await.insert(instrument.clone(), config);
+
+        // 开多、加多、部分平仓，再反手成空头
+        let trades = vec![trade(1, &instrument, Side::Buy, 10.0),
+                          trade(2, &instrument, Side::Buy, 5.0),
+                          trade(3, &instrument, Side::Sell, 3.0),
+                          trade(4, &instrument, Side::Sell, 20.0)];
+        for trade in &trades {
+            account.update_position_from_client_trade(trade.clone()).await.unwrap();
+        }
+
+        assert_eq!(net_fill_sizes(&trades).get(&instrument).copied(), Some(-8.0));
+        assert!(account.reconcile_fills_with_positions(&trades).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reports_instrument_and_mismatch_magnitude()
+    {
+        let account = create_test_account().await;
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        let mut position = create_test_perpetual_position(instrument.clone());
+        position.meta.current_size = 1.5;
+        account.positions.perpetual_pos_long.write().await.insert(instrument.clone(), position);
+
+        // 成交记录只有 1.0 的买入，而仓位是 1.5
+        let mismatches = account.reconcile_fills_with_positions(&[trade(1, &instrument, Side::Buy, 1.0)]).await;
+        assert_eq!(mismatches,
+                   vec![PositionMismatch { instrument: instrument.clone(),
+                                           fills_net_size: 1.0,
+                                           position_net_size: 1.5 }]);
+        assert_eq!(mismatches[0].difference(), 0.5);
+
+        // 有成交却没有任何仓位同样算作不一致
+        let other = Instrument::new("BTC", "USDT", InstrumentKind::Perpetual);
+        let mismatches = account.reconcile_fills_with_positions(&[trade(1, &instrument, Side::Buy, 1.5), trade(2, &other, Side::Sell, 2.0)]).await;
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].instrument, other);
+        assert_eq!(mismatches[0].difference(), 2.0);
+    }
+}
@@ -0,0 +1,361 @@
+use crate::{
+    common::{
+        balance::Balance,
+        instrument::{kind::InstrumentKind, Instrument},
+        trade::ClientTrade,
+        Side,
+    },
+    error::ExchangeError,
+    hourglass::account::{account_config::CostBasisMethod, account_handlers::balance_handler::BalanceHandler, HourglassAccount},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// 一个买入批次：数量与含手续费的单位成本。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SpotLot
+{
+    pub quantity: f64,
+    pub unit_cost: f64,
+}
+
+/// 某个现货币种的成本基础，按 [`CostBasisMethod`] 计算卖出时释放的成本。
+///
+/// 成本以成交的报价货币计价，买入时的手续费计入成本，卖出时的手续费从卖出收入中扣除。
+/// 使用 `Fifo` 时 `lots` 按买入顺序保存未卖出的批次，`total_cost` 始终等于各批次成本之和；使用 `Average` 时不维护批次。
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct SpotCostBasis
+{
+    /// 有成本记录的持仓数量。
+    pub quantity: f64,
+    /// 持仓的总成本（含买入手续费）。
+    pub total_cost: f64,
+    /// 累计已实现盈亏（已扣除卖出手续费）。
+    pub realised_pnl: f64,
+    /// 未卖出的买入批次，最早的在队首，仅在 `Fifo` 下维护。
+    #[serde(default)]
+    pub lots: VecDeque<SpotLot>,
+}
+
+impl SpotCostBasis
+{
+    /// 每单位持仓的平均成本，没有持仓时为 0。
+    pub fn average_cost(&self) -> f64
+    {
+        if self.quantity > 0.0 {
+            self.total_cost / self.quantity
+        }
+        else {
+            0.0
+        }
+    }
+
+    /// 记录一笔买入，成本增加 `price * size + fee`。
+    pub fn apply_buy(&mut self, method: CostBasisMethod, price: f64, size: f64, fee: f64)
+    {
+        if size <= 0.0 {
+            return;
+        }
+
+        let cost = price * size + fee;
+        self.quantity += size;
+        self.total_cost += cost;
+        if method == CostBasisMethod::Fifo {
+            self.lots.push_back(SpotLot { quantity: size, unit_cost: cost / size });
+        }
+    }
+
+    /// 记录一笔卖出并返回本次实现的盈亏。
+    ///
+    /// 只有有成本记录的数量才会实现盈亏。超出部分（例如直接充值、没有买入记录的余额）没有成本可比较，
+    /// 视为按卖出价成交的无盈亏部分，其对应比例的手续费也不计入本次盈亏。
+    pub fn apply_sell(&mut self, method: CostBasisMethod, price: f64, size: f64, fee: f64) -> f64
+    {
+        if size <= 0.0 {
+            return 0.0;
+        }
+
+        let matched = size.min(self.quantity);
+        let released_cost = match method {
+            | CostBasisMethod::Average => self.average_cost() * matched,
+            | CostBasisMethod::Fifo => self.consume_lots(matched),
+        };
+        let pnl = price * matched - fee * matched / size - released_cost;
+
+        self.quantity -= matched;
+        self.total_cost -= released_cost;
+        if self.quantity <= 0.0 {
+            self.quantity = 0.0;
+            self.total_cost = 0.0;
+            self.lots.clear();
+        }
+        self.realised_pnl += pnl;
+        pnl
+    }
+
+    /// 从最早的批次开始消耗 `quantity`，返回被消耗部分的成本。
+    fn consume_lots(&mut self, mut quantity: f64) -> f64
+    {
+        let mut released_cost = 0.0;
+        while quantity > 0.0 {
+            let Some(lot) = self.lots.front_mut()
+            else {
+                break;
+            };
+            let consumed = quantity.min(lot.quantity);
+            released_cost += consumed * lot.unit_cost;
+            lot.quantity -= consumed;
+            quantity -= consumed;
+            if lot.quantity <= 0.0 {
+                self.lots.pop_front();
+            }
+        }
+        released_cost
+    }
+}
+
+impl HourglassAccount
+{
+    /// 现货金融工具两条腿的余额，返回 `(base, quote)`。
+    pub fn spot_holdings(&self, instrument: &Instrument) -> Result<(Balance, Balance), ExchangeError>
+    {
+        if instrument.kind != InstrumentKind::Spot {
+            return Err(ExchangeError::InvalidInstrument(format!("Spot holdings are only available for spot instruments: {:?}", instrument)));
+        }
+
+        let base = *self.get_balance(&instrument.base)?;
+        let quote = *self.get_balance(&instrument.quote)?;
+        Ok((base, quote))
+    }
+
+    /// 返回现货交易对当前的成本基础，没有任何买卖记录时为 `None`。
+    ///
+    /// 成本以交易对的计价货币计，同一 base 币种在不同计价货币下的交易对分别统计，例如 ETH/USDT 与 ETH/BTC。
+    pub fn spot_cost_basis(&self, instrument: &Instrument) -> Option<SpotCostBasis>
+    {
+        self.spot_cost_basis.get(instrument).map(|cost_basis| cost_basis.clone())
+    }
+
+    /// 按现货成交与配置的成本法更新该交易对的成本基础，返回本次实现的盈亏（买入为 0）。
+    pub(crate) fn update_spot_cost_basis(&self, trade: &ClientTrade) -> f64
+    {
+        let method = self.config.cost_basis_method;
+        let mut cost_basis = self.spot_cost_basis.entry(trade.instrument.clone()).or_default();
+        match trade.side {
+            | Side::Buy => {
+                cost_basis.apply_buy(method, trade.price, trade.size, trade.fees);
+                0.0
+            }
+            | Side::Sell => cost_basis.apply_sell(method, trade.price, trade.size, trade.fees),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            order::{
+                identification::{client_order_id::ClientOrderId, OrderId},
+                order_instructions::OrderInstruction,
+                states::request_open::RequestOpen,
+                Order,
+            },
+            token::Token,
+            trade::ClientTradeId,
+        },
+        hourglass::{
+            account::account_handlers::{position_handler::PositionHandler, trade_handler::TradeHandler},
+            clickhouse_api::datatype::clickhouse_trade_data::MarketTrade,
+            open_orders_book::OpenOrdersBook,
+        },
+        test_utils::create_test_account,
+        Exchange,
+    };
+    use tokio::sync::{mpsc, oneshot};
+
+    fn spot_trade(id: i64, side: Side, price: f64, size: f64, fees: f64) -> ClientTrade
+    {
+        ClientTrade { exchange: Exchange::Hourglass,
+                      timestamp: 1690000000 + id,
+                      trade_id: ClientTradeId(id),
+                      order_id: Some(OrderId(id as u64)),
+                      cid: None,
+                      instrument: Instrument::new("ETH", "USDT", InstrumentKind::Spot),
+                      side,
+                      price,
+                      size,
+                      fees,
+                      tag: None }
+    }
+
+    #[tokio::test]
+    async fn test_buy_then_sell_realises_pnl_against_average_cost()
+    {
+        let mut account = create_test_account().await;
+        account.apply_trade_changes(&spot_trade(1, Side::Buy, 100.0, 2.0, 0.2)).await.unwrap();
+        account.apply_trade_changes(&spot_trade(2, Side::Buy, 200.0, 2.0, 0.4)).await.unwrap();
+
+        let cost_basis = account.spot_cost_basis(&Instrument::new("ETH", "USDT", InstrumentKind::Spot)).unwrap();
+        assert_eq!(cost_basis.quantity, 4.0);
+        assert!((cost_basis.average_cost() - 150.15).abs() < 1e-9);
+
+        // 卖出 3 个：750 - 0.75 - 3 * 150.15 = 298.8
+        account.apply_trade_changes(&spot_trade(3, Side::Sell, 250.0, 3.0, 0.75)).await.unwrap();
+        let cost_basis = account.spot_cost_basis(&Instrument::new("ETH", "USDT", InstrumentKind::Spot)).unwrap();
+        assert!((cost_basis.realised_pnl - 298.8).abs() < 1e-9);
+        assert_eq!(cost_basis.quantity, 1.0);
+        assert!((cost_basis.average_cost() - 150.15).abs() < 1e-9);
+
+        let (base, quote) = account.spot_holdings(&Instrument::new("ETH", "USDT", InstrumentKind::Spot)).unwrap();
+        assert_eq!(base.total, 10.0 + 2.0 + 2.0 - 3.0);
+        assert!((quote.total - (10_000.0 - 200.2 - 400.4 + 749.25)).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_cost_basis_tracked_per_spot_pair()
+    {
+        let mut account = create_test_account().await;
+        account.balances.insert(Token::from("BTC"), Balance::new(10.0, 10.0));
+        let eth_btc = Instrument::new("ETH", "BTC", InstrumentKind::Spot);
+        account.apply_trade_changes(&spot_trade(1, Side::Buy, 100.0, 1.0, 0.0)).await.unwrap();
+        account.apply_trade_changes(&ClientTrade { instrument: eth_btc.clone(),
+                                                   price: 0.05,
+                                                   ..spot_trade(2, Side::Buy, 0.0, 1.0, 0.0) })
+               .await
+               .unwrap();
+
+        // 同一 base 币种在不同计价货币下的成本互不混合
+        let eth_usdt = account.spot_cost_basis(&Instrument::new("ETH", "USDT", InstrumentKind::Spot)).unwrap();
+        let eth_btc = account.spot_cost_basis(&eth_btc).unwrap();
+        assert_eq!((eth_usdt.quantity, eth_usdt.average_cost()), (1.0, 100.0));
+        assert_eq!((eth_btc.quantity, eth_btc.average_cost()), (1.0, 0.05));
+    }
+
+    #[test]
+    fn test_selling_untracked_balance_realises_no_pnl()
+    {
+        let mut cost_basis = SpotCostBasis::default();
+        cost_basis.apply_buy(CostBasisMethod::Fifo, 100.0, 1.0, 0.0);
+
+        // 只有 1 个有成本记录，另外 1 个按卖出价视为无盈亏
+        assert_eq!(cost_basis.apply_sell(CostBasisMethod::Fifo, 120.0, 2.0, 2.0), 20.0 - 1.0);
+        assert_eq!(cost_basis, SpotCostBasis { quantity: 0.0,
+                                               total_cost: 0.0,
+                                               realised_pnl: 19.0,
+                                               lots: VecDeque::new() });
+    }
+
+    #[tokio::test]
+    async fn test_fifo_and_average_realise_different_pnl_on_same_trades()
+    {
+        let trades = [spot_trade(1, Side::Buy, 100.0, 1.0, 0.0),
+                      spot_trade(2, Side::Buy, 200.0, 1.0, 0.0),
+                      spot_trade(3, Side::Sell, 250.0, 1.0, 0.0)];
+
+        let mut realised = Vec::new();
+        for method in [CostBasisMethod::Fifo, CostBasisMethod::Average] {
+            let mut account = create_test_account().await;
+            account.config.cost_basis_method = method;
+            for trade in &trades {
+                account.apply_trade_changes(trade).await.unwrap();
+            }
+            let cost_basis = account.spot_cost_basis(&Instrument::new("ETH", "USDT", InstrumentKind::Spot)).unwrap();
+            realised.push((cost_basis.realised_pnl, cost_basis.average_cost()));
+        }
+
+        // FIFO 卖出最早的 100 成本批次，剩余持仓成本为 200；平均成本法按 150 计算，剩余持仓成本仍为 150
+        assert_eq!(realised, vec![(150.0, 200.0), (100.0, 150.0)]);
+    }
+
+    #[test]
+    fn test_fifo_sell_spans_multiple_lots_and_round_trips_through_serde()
+    {
+        let mut cost_basis = SpotCostBasis::default();
+        cost_basis.apply_buy(CostBasisMethod::Fifo, 100.0, 1.0, 0.0);
+        cost_basis.apply_buy(CostBasisMethod::Fifo, 200.0, 2.0, 2.0);
+        cost_basis.apply_buy(CostBasisMethod::Fifo, 300.0, 1.0, 0.0);
+
+        // 卖出 2 个：消耗第一批 1 个（成本 100）和第二批 1 个（单位成本 201）
+        let pnl = cost_basis.apply_sell(CostBasisMethod::Fifo, 250.0, 2.0, 0.0);
+        assert!((pnl - (500.0 - 100.0 - 201.0)).abs() < 1e-9);
+        assert_eq!(cost_basis.lots,
+                   VecDeque::from([SpotLot { quantity: 1.0, unit_cost: 201.0 }, SpotLot { quantity: 1.0, unit_cost: 300.0 }]));
+        assert!((cost_basis.total_cost - 501.0).abs() < 1e-9);
+
+        let json = serde_json::to_string(&cost_basis).unwrap();
+        let restored: SpotCostBasis = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, cost_basis);
+
+        // 恢复后继续按批次计算
+        let mut restored = restored;
+        assert!((restored.apply_sell(CostBasisMethod::Fifo, 250.0, 1.0, 0.0) - 49.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_spot_holdings_rejects_derivatives()
+    {
+        let account = create_test_account().await;
+        assert!(account.spot_holdings(&Instrument::new("ETH", "USDT", InstrumentKind::Perpetual)).is_err());
+    }
+
+    fn spot_order(side: Side, price: f64, size: f64) -> Order<RequestOpen>
+    {
+        Order { instruction: OrderInstruction::Limit,
+                exchange: Exchange::Hourglass,
+                instrument: Instrument::new("ETH", "USDT", InstrumentKind::Spot),
+                timestamp: 1234567,
+                cid: Some(ClientOrderId(format!("spot_{}", side))),
+                side,
+                state: RequestOpen { reduce_only: false,
+                                     price,
+                                     size,
+                                     tag: None } }
+    }
+
+    fn spot_market_trade(side: &str, price: f64, timestamp: i64) -> MarketTrade
+    {
+        MarketTrade { exchange: "binance".to_string(),
+                      symbol: "ETHUSDT".to_string(),
+                      side: side.to_string(),
+                      price,
+                      timestamp,
+                      amount: 10.0 }
+    }
+
+    #[tokio::test]
+    async fn test_spot_orders_settle_into_balances_end_to_end()
+    {
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Spot);
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        // 测试账户只配置了永续合约的费率，现货沿用同一档费率
+        let commission_rates = account.config.fees_book[&InstrumentKind::Perpetual].clone();
+        account.config.fees_book.insert(InstrumentKind::Spot, commission_rates);
+        account.account_open_book.write().await.instrument_orders_map.insert(instrument.clone(), OpenOrdersBook::default());
+        account.handle_trade_data(&spot_market_trade("buy", 1000.0, 1234560)).await.unwrap();
+
+        // 买入 2 ETH 后再卖出 1 ETH，均作为 Maker 成交
+        let (tx, rx) = oneshot::channel();
+        account.open_orders(vec![spot_order(Side::Buy, 990.0, 2.0)], tx).await.unwrap();
+        assert!(rx.await.unwrap()[0].is_ok());
+        account.handle_trade_data(&spot_market_trade("sell", 980.0, 1300000)).await.unwrap();
+
+        let (tx, rx) = oneshot::channel();
+        account.open_orders(vec![spot_order(Side::Sell, 1010.0, 1.0)], tx).await.unwrap();
+        assert!(rx.await.unwrap()[0].is_ok());
+        account.handle_trade_data(&spot_market_trade("buy", 1020.0, 1400000)).await.unwrap();
+
+        // 买入扣除报价货币并加上扣除手续费前的 base，卖出反之；手续费按 Maker 费率从报价货币扣除
+        let (base, quote) = account.spot_holdings(&instrument).unwrap();
+        assert!((base.total - (10.0 + 2.0 - 1.0)).abs() < 1e-9);
+        assert!((quote.total - (10_000.0 - 990.0 * 2.0 * 1.001 + 1010.0 * 0.999)).abs() < 1e-9);
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+
+        // 现货不持有仓位对象
+        assert!(matches!(account.get_position_both_ways(&instrument).await, Ok((None, None))));
+    }
+}
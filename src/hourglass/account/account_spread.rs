@@ -0,0 +1,318 @@
+use crate::{
+    common::{
+        event::{AccountEvent, AccountEventKind},
+        account_positions::PositionDirectionMode,
+        order::{
+            order_instructions::OrderInstruction,
+            states::{fills::FullyFill, open::Open, request_open::RequestOpen},
+            Order, OrderRole,
+        },
+        token::Token,
+        Side,
+    },
+    error::ExchangeError,
+    hourglass::{
+        account::{account_config::SpreadLegRisk, account_handlers::balance_handler::BalanceHandler, account_order_flow::OrderFlowMessage, HourglassAccount},
+        clickhouse_api::datatype::depth_order_book::DepthSweep,
+    },
+    Exchange,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::atomic::Ordering};
+
+/// 两腿价差订单，用于跨期、期现基差等价差交易：两腿只在成交价差满足目标时一起成交。
+///
+/// 价差定义为 `leg_a` 的成交均价减去 `leg_b` 的成交均价。`leg_a` 为买入时成交价差不高于 `target_spread` 才成交，
+/// 为卖出时不低于 `target_spread` 才成交。非市价腿的 `price` 同时作为该腿的限价，吃单不会劣于它。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SpreadOrder
+{
+    pub leg_a: Order<RequestOpen>,
+    pub leg_b: Order<RequestOpen>,
+    pub target_spread: f64,
+}
+
+impl SpreadOrder
+{
+    /// 给定的成交价差是否满足目标价差。
+    pub fn is_spread_met(&self, spread: f64) -> bool
+    {
+        match self.leg_a.side {
+            | Side::Buy => spread <= self.target_spread,
+            | Side::Sell => spread >= self.target_spread,
+        }
+    }
+}
+
+impl HourglassAccount
+{
+    /// 按本地深度订单簿立即撮合一笔价差订单，成功时返回两腿成交后的订单。
+    ///
+    /// 先在深度的副本上试算两腿可成交的数量与均价：两腿都能完全成交且价差满足目标时，两腿一起按 Taker 吃掉深度，
+    /// 随后以一个 `OrdersFilled` 事件同时发送两腿的成交；价差不满足时返回 `ExchangeError::SpreadTargetNotMet`，不会成交任何一腿。
+    ///
+    /// 某一腿深度不足时按 `AccountConfig.spread_leg_risk` 处理：默认返回 `ExchangeError::SpreadLegUnfillable`；
+    /// 配置为 `AllowLegging` 时各腿按可成交的数量成交，只有一腿有流动性时该腿单独成交，不再检查价差。
+    /// 价差订单不会挂单，未成交的部分直接丢弃。
+    ///
+    /// 两腿必须是不同的金融工具，否则返回 `ExchangeError::InvalidOrder`。试算前每一腿都经过与 [`Self::atomic_open`] 相同的校验
+    /// （限频、数量与价格、reduce_only、方向冲突、过滤器、最小名义价值与全仓保证金），任一腿被拒绝时不会改动任何状态。
+    pub async fn atomic_open_spread(&mut self, mut spread: SpreadOrder) -> Result<Vec<Order<Open>>, ExchangeError>
+    {
+        spread.leg_a.instrument = self.config.instrument_aliases.canonicalize(&spread.leg_a.instrument)?;
+        spread.leg_b.instrument = self.config.instrument_aliases.canonicalize(&spread.leg_b.instrument)?;
+        if spread.leg_a.instrument == spread.leg_b.instrument {
+            return Err(ExchangeError::InvalidOrder(format!("Spread legs must trade different instruments, both legs trade {}", spread.leg_a.instrument)));
+        }
+
+        self.check_submission_allowed()?;
+        let is_netmode = self.config.global_position_direction_mode == PositionDirectionMode::Net;
+        for leg in [&mut spread.leg_a, &mut spread.leg_b] {
+            Self::validate_order_size_and_price(leg)?;
+            if leg.state.reduce_only {
+                self.enforce_reduce_only(leg).await?;
+            }
+            if is_netmode {
+                self.check_direction_conflict(leg).await?;
+            }
+            *leg = self.validate_open_request(leg.clone()).await?;
+            self.check_cross_margin_for_order(leg).await?;
+        }
+
+        // 在深度副本上试算，确认整笔订单可以成交前不改动任何状态
+        let sweeps = {
+            let depth_order_books = self.depth_order_books.lock().await;
+            [&spread.leg_a, &spread.leg_b].map(|leg| match depth_order_books.get(&leg.instrument) {
+                                                  | Some(book) => book.clone().sweep_within(leg.side, leg.state.size, Self::spread_leg_limit(leg)),
+                                                  | None => DepthSweep::default(),
+                                              })
+        };
+
+        let fully_fillable = [&spread.leg_a, &spread.leg_b].iter().zip(&sweeps).all(|(leg, sweep)| sweep.filled_size >= leg.state.size);
+        if !fully_fillable && self.config.spread_leg_risk == SpreadLegRisk::Reject {
+            let unfillable = if sweeps[0].filled_size < spread.leg_a.state.size { &spread.leg_a } else { &spread.leg_b };
+            return Err(ExchangeError::SpreadLegUnfillable(unfillable.instrument.to_string()));
+        }
+        match (sweeps[0].average_price(), sweeps[1].average_price()) {
+            | (Some(price_a), Some(price_b)) => {
+                if !spread.is_spread_met(price_a - price_b) {
+                    return Err(ExchangeError::SpreadTargetNotMet(price_a - price_b, spread.target_spread));
+                }
+            }
+            | (None, None) => return Err(ExchangeError::SpreadLegUnfillable(spread.leg_a.instrument.to_string())),
+            | _ => {} // 只有一腿有流动性，已允许单腿成交
+        }
+
+        // 各腿只下可成交的数量，先一起检查保证金，避免一腿成交后另一腿因余额不足失败
+        let legs = [spread.leg_a, spread.leg_b].into_iter()
+                                               .zip(&sweeps)
+                                               .filter(|(_, sweep)| sweep.filled_size > 0.0)
+                                               .map(|(mut leg, sweep)| {
+                                                   leg.state.size = sweep.filled_size;
+                                                   leg
+                                               })
+                                               .collect::<Vec<_>>();
+        let mut required_balances: HashMap<Token, f64> = HashMap::new();
+        for leg in &legs {
+            let (token, required_balance) = self.required_available_balance(leg, OrderRole::Taker).await?;
+            *required_balances.entry(token.clone()).or_default() += required_balance;
+        }
+        for (token, required_balance) in &required_balances {
+            self.has_sufficient_available_balance(token, *required_balance)?;
+        }
+        // 与单笔开单相同，只有被接受的腿计入报单成交比
+        for _ in &legs {
+            self.record_order_flow(OrderFlowMessage::Submission);
+        }
+
+        let mut filled_legs = Vec::with_capacity(legs.len());
+        for leg in legs {
+            let limit_price = Self::spread_leg_limit(&leg);
+            let (_, required_balance) = self.required_available_balance(&leg, OrderRole::Taker).await?;
+            let open_order = {
+                let mut orders_guard = self.account_open_book.write().await;
+                let open_order = orders_guard.build_order_open(leg, OrderRole::Taker).await;
+                orders_guard.get_ins_orders_mut(&open_order.instrument)?.add_order_open(open_order.clone());
+                open_order
+            };
+            let balance_event = self.apply_open_order_changes(&open_order, required_balance).await?;
+            self.send_account_event(balance_event)?;
+
+            let filled_order = self.fill_order_from_depth(&open_order, limit_price, OrderRole::Taker)
+                                   .await?
+                                   .ok_or_else(|| ExchangeError::SpreadLegUnfillable(open_order.instrument.to_string()))?;
+            filled_legs.push(filled_order);
+        }
+
+        // 两腿的成交在同一个事件中发送
+        let filled_orders = filled_legs.iter()
+                                       .map(|order| Order { instruction: order.instruction,
+                                                            exchange: order.exchange,
+                                                            instrument: order.instrument.clone(),
+                                                            timestamp: order.timestamp,
+                                                            cid: order.cid.clone(),
+                                                            side: order.side,
+                                                            state: FullyFill { id: order.state.id.clone(),
+                                                                               price: order.state.avg_fill_price(),
+                                                                               size: order.state.filled_quantity,
+                                                                               tag: order.state.tag.clone() } })
+                                       .collect();
+        self.send_account_event(AccountEvent { exchange_timestamp: self.exchange_timestamp.load(Ordering::SeqCst),
+                                               exchange: Exchange::Hourglass,
+                                               kind: AccountEventKind::OrdersFilled(filled_orders) })?;
+        Ok(filled_legs)
+    }
+
+    // 市价腿不限价，其余腿以 `price` 为限价
+    fn spread_leg_limit(leg: &Order<RequestOpen>) -> Option<f64>
+    {
+        (leg.instruction != OrderInstruction::Market).then_some(leg.state.price)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            instrument::{kind::InstrumentKind, Instrument},
+            order::identification::client_order_id::ClientOrderId,
+        },
+        hourglass::{clickhouse_api::datatype::single_level_order_book::SingleLevelOrderBook, open_orders_book::OpenOrdersBook},
+        test_utils::create_test_account,
+    };
+    use tokio::sync::mpsc;
+
+    fn perpetual() -> Instrument
+    {
+        Instrument::new("ETH", "USDT", InstrumentKind::Perpetual)
+    }
+
+    fn future() -> Instrument
+    {
+        Instrument::new("ETH", "USDT", InstrumentKind::Future)
+    }
+
+    fn leg(instrument: Instrument, side: Side, price: f64, size: f64) -> Order<RequestOpen>
+    {
+        Order { instruction: OrderInstruction::Limit,
+                exchange: Exchange::Hourglass,
+                instrument,
+                timestamp: 1234567,
+                cid: Some(ClientOrderId(format!("spread_{}", side))),
+                side,
+                state: RequestOpen { reduce_only: false,
+                                     price,
+                                     size,
+                                     tag: None } }
+    }
+
+    /// 买入永续、卖出交割的价差订单，永续卖一 16400，交割买一 16500。
+    async fn spread_account(future_bid_size: f64) -> HourglassAccount
+    {
+        let mut account = create_test_account().await;
+        // 交割合约沿用永续合约的费率
+        let rates = account.config.fees_book[&InstrumentKind::Perpetual].clone();
+        account.config.fees_book.insert(InstrumentKind::Future, rates);
+        account.account_open_book.write().await.instrument_orders_map.insert(future(), OpenOrdersBook::default());
+        account.single_level_order_book.lock().await.insert(future(),
+                                                            SingleLevelOrderBook { latest_bid: 16500.0,
+                                                                                   latest_ask: 16600.0,
+                                                                                   latest_price: 0.0 });
+        account.replace_depth_levels(&perpetual(), 1234567, &[(16300.0, 1.0)], &[(16400.0, 0.5)]).await.unwrap();
+        account.replace_depth_levels(&future(), 1234567, &[(16500.0, future_bid_size)], &[(16600.0, 1.0)]).await.unwrap();
+        account
+    }
+
+    fn buy_calendar_spread(target_spread: f64) -> SpreadOrder
+    {
+        SpreadOrder { leg_a: leg(perpetual(), Side::Buy, 16450.0, 0.2),
+                      leg_b: leg(future(), Side::Sell, 16450.0, 0.2),
+                      target_spread }
+    }
+
+    #[tokio::test]
+    async fn test_spread_fills_both_legs_together_when_target_met()
+    {
+        let mut account = spread_account(1.0).await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        // 成交价差 16400 - 16500 = -100，不高于目标 -50
+        let filled = account.atomic_open_spread(buy_calendar_spread(-50.0)).await.unwrap();
+        assert_eq!(filled.len(), 2);
+        assert!(filled.iter().all(|order| order.state.remaining_quantity() <= 0.0));
+
+        let mut filled_events = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            if let AccountEventKind::OrdersFilled(orders) = event.kind {
+                filled_events.push(orders);
+            }
+        }
+        assert_eq!(filled_events.len(), 1);
+        assert_eq!(filled_events[0].iter().map(|order| (order.instrument.clone(), order.state.price)).collect::<Vec<_>>(),
+                   vec![(perpetual(), 16400.0), (future(), 16500.0)]);
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_spread_rejected_without_fills_when_target_not_met()
+    {
+        let mut account = spread_account(1.0).await;
+        let result = account.atomic_open_spread(buy_calendar_spread(-150.0)).await;
+        assert_eq!(result, Err(ExchangeError::SpreadTargetNotMet(-100.0, -150.0)));
+        // 深度没有被消耗
+        assert_eq!(account.depth_order_book(&perpetual()).await.unwrap().asks[0].amount, 0.5);
+    }
+
+    #[tokio::test]
+    async fn test_spread_leg_risk_only_when_configured()
+    {
+        // 交割合约没有买盘，默认拒绝整笔订单
+        let mut account = spread_account(0.0).await;
+        let result = account.atomic_open_spread(buy_calendar_spread(-50.0)).await;
+        assert_eq!(result, Err(ExchangeError::SpreadLegUnfillable(future().to_string())));
+        assert_eq!(account.depth_order_book(&perpetual()).await.unwrap().asks[0].amount, 0.5);
+
+        // 允许单腿成交时只成交有流动性的永续腿
+        let mut account = spread_account(0.0).await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.config.spread_leg_risk = SpreadLegRisk::AllowLegging;
+        let filled = account.atomic_open_spread(buy_calendar_spread(-50.0)).await.unwrap();
+        assert_eq!(filled.len(), 1);
+        assert_eq!(filled[0].instrument, perpetual());
+        assert_eq!(filled[0].state.filled_quantity, 0.2);
+    }
+
+    #[tokio::test]
+    async fn test_spread_rejects_legs_on_the_same_instrument()
+    {
+        let mut account = spread_account(1.0).await;
+        let spread = SpreadOrder { leg_a: leg(perpetual(), Side::Buy, 16450.0, 0.2),
+                                   leg_b: leg(perpetual(), Side::Sell, 16250.0, 0.2),
+                                   target_spread: 0.0 };
+        let result = account.atomic_open_spread(spread).await;
+        assert!(matches!(result, Err(ExchangeError::InvalidOrder(_))));
+        assert_eq!(account.depth_order_book(&perpetual()).await.unwrap().asks[0].amount, 0.5);
+    }
+
+    #[tokio::test]
+    async fn test_spread_leg_failing_validation_changes_no_state()
+    {
+        let mut account = spread_account(1.0).await;
+        let available_before = account.get_balance(&Token::from("USDT")).unwrap().available;
+
+        // 交割腿为 reduce_only 但没有可减少的仓位，整笔订单在试算前被拒绝
+        let mut spread = buy_calendar_spread(-50.0);
+        spread.leg_b.state.reduce_only = true;
+        let result = account.atomic_open_spread(spread).await;
+        assert!(matches!(result, Err(ExchangeError::ReduceOnlyRejected(_))));
+
+        assert_eq!(account.depth_order_book(&perpetual()).await.unwrap().asks[0].amount, 0.5);
+        assert_eq!(account.depth_order_book(&future()).await.unwrap().bids[0].amount, 1.0);
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+        assert_eq!(account.get_balance(&Token::from("USDT")).unwrap().available, available_before);
+    }
+}
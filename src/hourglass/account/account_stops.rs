@@ -0,0 +1,296 @@
+use crate::{
+    common::order::{
+        states::request_cancel::RequestCancel,
+        stop_order::{StopOrder, StopOrderKind},
+        Order,
+    },
+    error::ExchangeError,
+    hourglass::{account::HourglassAccount, clickhouse_api::datatype::clickhouse_trade_data::MarketTrade},
+    hourglass_log::warn,
+};
+use std::sync::atomic::Ordering;
+use tokio::sync::oneshot::Sender;
+
+impl HourglassAccount
+{
+    /// 校验并登记一批条件单，结果按提交顺序返回，见 [`Self::submit_stop_order`]。
+    pub async fn open_stop_orders(&mut self, stops: Vec<StopOrder>, response_tx: Sender<Vec<Result<StopOrder, ExchangeError>>>) -> Result<(), ExchangeError>
+    {
+        let mut results = Vec::with_capacity(stops.len());
+        for stop in stops {
+            results.push(self.submit_stop_order(stop).await);
+        }
+
+        if let Err(e) = response_tx.send(results) {
+            return Err(ExchangeError::Hourglass(format!("Failed to send open stop order results: {:?}", e)));
+        }
+        Ok(())
+    }
+
+    /// 校验并登记一笔条件单，返回登记后的条件单。
+    ///
+    /// 登记时为条件单分配订单ID，之后可按该ID或客户端订单ID撤销，见 [`Self::cancel_stop_order`]。
+    /// 条件单在触发前不锁定余额，也不计入挂单数量上限；余额与方向冲突等检查在触发后按普通订单进行。
+    /// 跟踪止损的初始最优价格取提交时的最新成交价，尚无行情时由之后的第一笔成交初始化。
+    pub async fn submit_stop_order(&mut self, mut stop: StopOrder) -> Result<StopOrder, ExchangeError>
+    {
+        stop.order.instrument = self.config.instrument_aliases.canonicalize(&stop.order.instrument)?;
+
+        match stop.kind {
+            | StopOrderKind::TrailingStop { callback_rate } => {
+                if !callback_rate.is_finite() || callback_rate <= 0.0 || callback_rate >= 1.0 {
+                    return Err(ExchangeError::InvalidOrder(format!("Trailing stop callback rate must be between 0 and 1, got {}", callback_rate)));
+                }
+                // 以提交时的最新成交价作为初始最优价格，避免行情在提交前已经移动时按过时的价格计算触发价
+                stop.watermark = self.single_level_order_book.lock().await.get(&stop.order.instrument).map(|book| book.latest_price).filter(|price| *price > 0.0);
+            }
+            | kind => {
+                let trigger_price = kind.trigger_price().unwrap_or(f64::NAN);
+                if !trigger_price.is_finite() || trigger_price <= 0.0 {
+                    return Err(ExchangeError::InvalidOrder(format!("Stop order trigger price must be a positive finite number, got {}", trigger_price)));
+                }
+            }
+        }
+        Self::validate_order_size_and_price(&stop.clone().into_request())?;
+
+        let mut orders_guard = self.account_open_book.write().await;
+        stop.id = Some(orders_guard.order_id(self.exchange_timestamp.load(Ordering::SeqCst)));
+        orders_guard.add_pending_stop(stop.clone());
+        Ok(stop)
+    }
+
+    /// 撤销一批尚未触发的条件单，结果按请求顺序返回，见 [`Self::cancel_stop_order`]。
+    pub async fn cancel_stop_orders(&mut self, cancel_requests: Vec<Order<RequestCancel>>, response_tx: Sender<Vec<Result<StopOrder, ExchangeError>>>) -> Result<(), ExchangeError>
+    {
+        let mut results = Vec::with_capacity(cancel_requests.len());
+        for request in cancel_requests {
+            results.push(self.cancel_stop_order(request).await);
+        }
+
+        if let Err(e) = response_tx.send(results) {
+            return Err(ExchangeError::Hourglass(format!("Failed to send cancel stop order results: {:?}", e)));
+        }
+        Ok(())
+    }
+
+    /// 按订单ID或客户端订单ID撤销一笔尚未触发的条件单，返回被撤销的条件单。
+    ///
+    /// 条件单触发前不锁定余额，撤销时只从等待队列中移除；已经触发的条件单是普通挂单，需按普通订单撤销。
+    /// 找不到对应的条件单时返回 `ExchangeError::OrderNotFound`。
+    pub async fn cancel_stop_order(&mut self, mut request: Order<RequestCancel>) -> Result<StopOrder, ExchangeError>
+    {
+        request.instrument = self.config.instrument_aliases.canonicalize(&request.instrument)?;
+        self.account_open_book
+            .write()
+            .await
+            .take_pending_stop(&request)
+            .ok_or_else(|| ExchangeError::OrderNotFound { client_order_id: request.cid.clone(),
+                                                          order_id: request.state.id.clone() })
+    }
+
+    /// 把被本笔市场成交触发的条件单转换为市价单或限价单并开单。
+    ///
+    /// 在撮合本笔成交之前调用，因此触发后挂出的订单会参与本笔成交的撮合。开单成功时与普通订单一样发送 `OrdersOpen`；
+    /// 开单失败（例如余额不足）的条件单会被丢弃并记录警告。
+    pub(crate) async fn trigger_stop_orders(&mut self, trade: &MarketTrade) -> Result<(), ExchangeError>
+    {
+        if self.account_open_book.read().await.pending_stops.is_empty() {
+            return Ok(());
+        }
+
+        let instrument = self.resolve_market_instrument(trade)?;
+        let triggered = self.account_open_book.write().await.take_triggered_stops(&instrument, trade.price);
+        for stop in triggered {
+            let cid = stop.order.cid.clone();
+            if let Err(err) = self.atomic_open(stop.into_request()).await {
+                warn!("Failed to open triggered stop order {:?}: {:?}", cid, err);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            event::AccountEventKind,
+            instrument::{kind::InstrumentKind, Instrument},
+            order::{
+                identification::{client_order_id::ClientOrderId, OrderId},
+                order_instructions::OrderInstruction,
+                states::request_open::RequestOpen,
+            },
+            Side,
+        },
+        hourglass::account::account_handlers::trade_handler::TradeHandler,
+        test_utils::create_test_account,
+        Exchange,
+    };
+    use tokio::sync::mpsc;
+
+    fn stop_order(side: Side, size: f64, kind: StopOrderKind) -> StopOrder
+    {
+        StopOrder::new(Order { instruction: OrderInstruction::Limit,
+                               exchange: Exchange::Hourglass,
+                               instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                               timestamp: 1234567,
+                               cid: Some(ClientOrderId("stop".into())),
+                               side,
+                               state: RequestOpen { reduce_only: false,
+                                                    price: 0.0,
+                                                    size,
+                                                    tag: None } },
+                       kind)
+    }
+
+    fn market_trade(side: &str, price: f64, timestamp: i64) -> MarketTrade
+    {
+        MarketTrade { exchange: "binance-futures".to_string(),
+                      symbol: "ETHUSDT".to_string(),
+                      side: side.to_string(),
+                      price,
+                      timestamp,
+                      amount: 1.0 }
+    }
+
+    #[tokio::test]
+    async fn test_stop_limit_promoted_and_matched_by_triggering_trade()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        // 买入止损：价格涨到 16400 及以上时以 16450 挂买单
+        account.submit_stop_order(stop_order(Side::Buy, 0.1, StopOrderKind::StopLimit { trigger_price: 16400.0, limit_price: 16450.0 })).await.unwrap();
+
+        // 未达到触发价时不会挂单
+        account.handle_trade_data(&market_trade("sell", 16350.0, 1234568)).await.unwrap();
+        assert_eq!(account.account_open_book.read().await.pending_stops.len(), 1);
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+
+        // 触发后挂出的限价单与触发成交更新后的最优卖价 16420 交叉，按最优价立即成交
+        account.handle_trade_data(&market_trade("sell", 16420.0, 1234569)).await.unwrap();
+        assert!(account.account_open_book.read().await.pending_stops.is_empty());
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+
+        let mut kinds = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            match event.kind {
+                | AccountEventKind::OrdersOpen(orders) => kinds.push(format!("open {} {}", orders[0].instruction, orders[0].state.price)),
+                | AccountEventKind::Trade(trade) => kinds.push(format!("trade {} {}", trade.price, trade.size)),
+                | _ => {}
+            }
+        }
+        assert_eq!(kinds, vec!["trade 16420 0.1".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_sell_stop_triggers_only_when_price_falls_to_trigger()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        account.submit_stop_order(stop_order(Side::Sell, 0.1, StopOrderKind::Stop { trigger_price: 16300.0 })).await.unwrap();
+        account.handle_trade_data(&market_trade("buy", 16450.0, 1234568)).await.unwrap();
+        assert_eq!(account.account_open_book.read().await.pending_stops.len(), 1);
+
+        account.handle_trade_data(&market_trade("sell", 16290.0, 1234569)).await.unwrap();
+        assert!(account.account_open_book.read().await.pending_stops.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stop_order_with_invalid_trigger_rejected()
+    {
+        let mut account = create_test_account().await;
+        let result = account.submit_stop_order(stop_order(Side::Sell, 0.1, StopOrderKind::Stop { trigger_price: f64::NAN })).await;
+        assert!(matches!(result, Err(ExchangeError::InvalidOrder(_))));
+
+        let result = account.submit_stop_order(stop_order(Side::Sell, 0.1, StopOrderKind::StopLimit { trigger_price: 16300.0, limit_price: -1.0 })).await;
+        assert!(matches!(result, Err(ExchangeError::InvalidOrder(_))));
+        assert!(account.account_open_book.read().await.pending_stops.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_trailing_stop_starts_from_submission_price_and_fires_on_retrace()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+
+        // 提交前行情已经涨到 16450，初始最优价格取 16450 而不是 0
+        account.handle_trade_data(&market_trade("buy", 16450.0, 1234568)).await.unwrap();
+        let stop = account.submit_stop_order(stop_order(Side::Sell, 0.1, StopOrderKind::TrailingStop { callback_rate: 0.01 })).await.unwrap();
+        assert_eq!(stop.watermark, Some(16450.0));
+
+        // 最高价推到 16600 后，触发价随之上移到 16434
+        account.handle_trade_data(&market_trade("buy", 16600.0, 1234569)).await.unwrap();
+        account.handle_trade_data(&market_trade("sell", 16450.0, 1234570)).await.unwrap();
+        assert_eq!(account.account_open_book.read().await.pending_stops[0].watermark, Some(16600.0));
+
+        account.handle_trade_data(&market_trade("sell", 16430.0, 1234571)).await.unwrap();
+        assert!(account.account_open_book.read().await.pending_stops.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_trailing_stop_with_invalid_callback_rate_rejected()
+    {
+        let mut account = create_test_account().await;
+        for callback_rate in [0.0, 1.0, f64::NAN] {
+            let result = account.submit_stop_order(stop_order(Side::Sell, 0.1, StopOrderKind::TrailingStop { callback_rate })).await;
+            assert!(matches!(result, Err(ExchangeError::InvalidOrder(_))));
+        }
+    }
+
+    fn cancel_request(id: Option<OrderId>, cid: Option<&str>) -> Order<RequestCancel>
+    {
+        Order { instruction: OrderInstruction::Limit,
+                exchange: Exchange::Hourglass,
+                instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                timestamp: 1234567,
+                cid: cid.map(|cid| ClientOrderId(cid.into())),
+                side: Side::Sell,
+                state: RequestCancel { id } }
+    }
+
+    #[tokio::test]
+    async fn test_pending_stop_cancelled_by_id_or_cid()
+    {
+        let mut account = create_test_account().await;
+        let first = account.submit_stop_order(stop_order(Side::Sell, 0.1, StopOrderKind::Stop { trigger_price: 16300.0 })).await.unwrap();
+        let mut second = stop_order(Side::Sell, 0.2, StopOrderKind::Stop { trigger_price: 16200.0 });
+        second.order.cid = Some(ClientOrderId("second_stop".into()));
+        account.submit_stop_order(second).await.unwrap();
+
+        let cancelled = account.cancel_stop_order(cancel_request(first.id.clone(), None)).await.unwrap();
+        assert_eq!(cancelled.id, first.id);
+        let cancelled = account.cancel_stop_order(cancel_request(None, Some("second_stop"))).await.unwrap();
+        assert_eq!(cancelled.order.state.size, 0.2);
+        assert!(account.account_open_book.read().await.pending_stops.is_empty());
+
+        // 已撤销的条件单不能再次撤销
+        assert!(matches!(account.cancel_stop_order(cancel_request(first.id, None)).await, Err(ExchangeError::OrderNotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_all_includes_pending_stops()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let stop = account.submit_stop_order(stop_order(Side::Sell, 0.1, StopOrderKind::Stop { trigger_price: 16300.0 })).await.unwrap();
+
+        let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+        account.cancel_orders_all(response_tx).await;
+        let cancelled = response_rx.await.unwrap().unwrap();
+        assert_eq!(cancelled.iter().map(|order| order.state.id.clone()).collect::<Vec<_>>(), vec![stop.id.unwrap()]);
+        assert!(account.account_open_book.read().await.pending_stops.is_empty());
+
+        // 之后价格穿过触发价也不会开单
+        account.handle_trade_data(&market_trade("sell", 16290.0, 1234569)).await.unwrap();
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{
+    common::{order::OrderRole, trade::ClientTrade},
+    hourglass::{account::HourglassAccount, clickhouse_api::datatype::clickhouse_trade_data::MarketTrade},
+};
+
+impl HourglassAccount
+{
+    /// 按 `own_fills_on_tape` 配置，把本账户的成交记录为合成的行情成交，等待推送到行情流。
+    ///
+    /// `role` 为本账户在这些成交中的角色：作为 Taker 时主动方是本账户自己，作为 Maker 时主动方是对手方。
+    /// 未启用时不做任何事。
+    pub(crate) fn record_own_fill_prints(&mut self, trades: &[ClientTrade], role: OrderRole)
+    {
+        if !self.config.own_fills_on_tape {
+            return;
+        }
+        for trade in trades {
+            let aggressor_side = match role {
+                | OrderRole::Taker => trade.side,
+                | OrderRole::Maker => trade.side.toggle(),
+            };
+            self.own_fill_prints.push(MarketTrade::from_client_trade(trade, aggressor_side));
+        }
+    }
+
+    /// 取出所有等待推送的本账户成交记录，由交易所在处理完当前事件后推送到行情流，供客户端聚合 K 线、VWAP 等。
+    ///
+    /// # 前视偏差
+    ///
+    /// 只有启用 `own_fills_on_tape` 时才会产生记录，默认关闭：
+    ///
+    /// - 单账户回测回放的是历史成交，本账户的挂单成交所消耗的外部流动性已经体现在历史成交中，再追加一笔会重复计入成交量；
+    /// - 历史上的其他参与者从未对本账户的成交做出反应，若策略再用包含自身成交的 K 线、VWAP 生成信号，
+    ///   等于把自己的行为当作市场信息，回测结果会偏离真实表现。
+    ///
+    /// 因此只在多账户仿真等需要让各参与者看到彼此成交的场景下启用。无论是否启用，这些记录都不会回流到撮合，
+    /// 本账户的挂单不会与自己的成交记录成交。
+    pub fn take_own_fill_prints(&mut self) -> Vec<MarketTrade>
+    {
+        std::mem::take(&mut self.own_fill_prints)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            instrument::{kind::InstrumentKind, Instrument},
+            order::{identification::client_order_id::ClientOrderId, order_instructions::OrderInstruction, states::request_open::RequestOpen, Order},
+            Side,
+        },
+        hourglass::account::account_handlers::trade_handler::TradeHandler,
+        test_utils::create_test_account,
+        Exchange,
+    };
+    use tokio::sync::mpsc;
+
+    async fn fill_resting_buy(own_fills_on_tape: bool) -> HourglassAccount
+    {
+        let mut account = create_test_account().await;
+        account.config.own_fills_on_tape = own_fills_on_tape;
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                    exchange: Exchange::Hourglass,
+                                    instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                                    timestamp: 1234567,
+                                    cid: Some(ClientOrderId("tape".into())),
+                                    side: Side::Buy,
+                                    state: RequestOpen { price: 16400.0,
+                                                         size: 0.25,
+                                                         reduce_only: false,
+                                                         tag: None } })
+               .await
+               .unwrap();
+        account.handle_trade_data(&MarketTrade { exchange: "binance-futures".to_string(),
+                                                 symbol: "ETHUSDT".to_string(),
+                                                 side: "sell".to_string(),
+                                                 price: 16300.0,
+                                                 timestamp: 1234568,
+                                                 amount: 1.0 })
+               .await
+               .unwrap();
+        account
+    }
+
+    #[tokio::test]
+    async fn test_own_fills_stay_off_tape_by_default()
+    {
+        let mut account = fill_resting_buy(false).await;
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+        assert!(account.take_own_fill_prints().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_own_fills_become_tape_prints_when_enabled()
+    {
+        let mut account = fill_resting_buy(true).await;
+        let prints = account.take_own_fill_prints();
+        assert_eq!(prints.len(), 1);
+        assert_eq!(prints[0].amount, 0.25);
+        assert_eq!(prints[0].timestamp, 1234568);
+        // 挂买单被动成交，主动方是卖方
+        assert_eq!(prints[0].aggressor_side(), Some(Side::Sell));
+        assert_eq!(account.resolve_market_instrument(&prints[0]).unwrap(), Instrument::new("ETH", "USDT", InstrumentKind::Perpetual));
+        assert!(account.take_own_fill_prints().is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{
+    common::{instrument::kind::InstrumentKind, trade::ClientTrade},
+    hourglass::account::{
+        account_config::{CommissionLevel, CommissionRates, VolumeTier},
+        HourglassAccount,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::atomic::Ordering,
+};
+
+/// 阶梯费率统计成交额的滚动窗口：30 天（毫秒）。
+pub const VOLUME_TIER_WINDOW_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+
+/// 按金融工具类别统计滑动时间窗口内的成交额（名义价值），用于选择阶梯费率。
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct RollingVolumeTracker
+{
+    fills: VecDeque<(i64, InstrumentKind, f64)>,
+    volumes: HashMap<InstrumentKind, f64>,
+}
+
+impl RollingVolumeTracker
+{
+    /// 记录一笔成交额，并移除 `window_ms` 窗口之外的旧成交。
+    pub fn record(&mut self, timestamp: i64, kind: InstrumentKind, notional: f64, window_ms: i64)
+    {
+        self.fills.push_back((timestamp, kind, notional));
+        *self.volumes.entry(kind).or_insert(0.0) += notional;
+        self.prune(timestamp, window_ms);
+    }
+
+    /// 移除早于 `now - window_ms` 的成交。
+    pub fn prune(&mut self, now: i64, window_ms: i64)
+    {
+        let window_start = now - window_ms;
+        while let Some((timestamp, kind, notional)) = self.fills.front().copied() {
+            if timestamp >= window_start {
+                break;
+            }
+            self.fills.pop_front();
+            if let Some(volume) = self.volumes.get_mut(&kind) {
+                *volume = (*volume - notional).max(0.0);
+            }
+        }
+    }
+
+    /// 当前窗口内某类金融工具的成交额。
+    pub fn volume(&self, kind: InstrumentKind) -> f64
+    {
+        self.volumes.get(&kind).copied().unwrap_or(0.0)
+    }
+
+    /// 截至 `now` 的 `window_ms` 窗口内某类金融工具的成交额，结果与先调用 [`Self::prune`] 再查询相同。
+    pub fn volume_at(&self, kind: InstrumentKind, now: i64, window_ms: i64) -> f64
+    {
+        let window_start = now - window_ms;
+        let expired: f64 = self.fills
+                               .iter()
+                               .take_while(|(timestamp, ..)| *timestamp < window_start)
+                               .filter(|(_, fill_kind, _)| *fill_kind == kind)
+                               .map(|(.., notional)| notional)
+                               .sum();
+        (self.volume(kind) - expired).max(0.0)
+    }
+}
+
+impl HourglassAccount
+{
+    /// 把一笔成交的名义价值计入 30 天滚动成交额。
+    pub(crate) fn record_traded_volume(&mut self, trade: &ClientTrade)
+    {
+        let notional = self.trade_notional(trade);
+        self.traded_volume.record(trade.timestamp, trade.instrument.kind, notional, VOLUME_TIER_WINDOW_MS);
+    }
+
+    /// 时间戳推进后移除 30 天窗口之外的成交，没有新成交时档位也会随窗口滑过而降级。
+    pub(crate) fn prune_traded_volume(&mut self)
+    {
+        let now = self.exchange_timestamp.load(Ordering::SeqCst);
+        self.traded_volume.prune(now, VOLUME_TIER_WINDOW_MS);
+    }
+
+    /// 按 30 天滚动成交额选出的某类金融工具的阶梯费率档位（从 0 开始），未配置阶梯费率或成交额低于第一档门槛时为 `None`。
+    ///
+    /// 成交额按截至当前 `exchange_timestamp` 的窗口计算，已经滑出窗口的成交不再计入。
+    fn volume_tier(&self, kind: InstrumentKind) -> Option<(usize, &VolumeTier)>
+    {
+        let now = self.exchange_timestamp.load(Ordering::SeqCst);
+        let volume = self.traded_volume.volume_at(kind, now, VOLUME_TIER_WINDOW_MS);
+        self.config.volume_tiers.iter().enumerate().rev().find(|(_, tier)| volume >= tier.threshold_notional)
+    }
+
+    /// 某类金融工具当前的手续费等级：配置了阶梯费率时由 30 天滚动成交额决定，否则为 `AccountConfig::commission_level`。
+    pub fn commission_level(&self, kind: InstrumentKind) -> CommissionLevel
+    {
+        if self.config.volume_tiers.is_empty() {
+            return self.config.commission_level.clone();
+        }
+        self.volume_tier(kind).map_or(CommissionLevel::Lv1, |(index, _)| CommissionLevel::from_tier_index(index))
+    }
+
+    /// 当前档位的费率，计费口径沿用 `fees_book` 中的配置；没有适用的档位时为 `None`，此时按 `fees_book` 收取。
+    pub(crate) fn volume_tier_rates(&self, kind: InstrumentKind) -> Option<CommissionRates>
+    {
+        let (_, tier) = self.volume_tier(kind)?;
+        Some(CommissionRates { maker_fees: tier.maker,
+                               taker_fees: tier.taker,
+                               fee_basis: self.config.fees_book.get(&kind).map(|rates| rates.fee_basis).unwrap_or_default() })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            balance::Balance,
+            event::AccountEventKind,
+            instrument::Instrument,
+            order::{identification::client_order_id::ClientOrderId, order_instructions::OrderInstruction, states::request_open::RequestOpen, Order},
+            token::Token,
+            Side,
+        },
+        hourglass::{account::account_handlers::trade_handler::TradeHandler, clickhouse_api::datatype::clickhouse_trade_data::MarketTrade},
+        test_utils::create_test_account,
+        Exchange,
+    };
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_rolling_volume_drops_fills_outside_window()
+    {
+        let mut tracker = RollingVolumeTracker::default();
+        tracker.record(0, InstrumentKind::Perpetual, 100.0, 1_000);
+        tracker.record(500, InstrumentKind::Spot, 50.0, 1_000);
+        tracker.record(800, InstrumentKind::Perpetual, 30.0, 1_000);
+        assert_eq!(tracker.volume(InstrumentKind::Perpetual), 130.0);
+
+        tracker.record(1_200, InstrumentKind::Perpetual, 10.0, 1_000);
+        assert_eq!(tracker.volume(InstrumentKind::Perpetual), 40.0);
+        assert_eq!(tracker.volume(InstrumentKind::Spot), 50.0);
+        assert_eq!(tracker.volume(InstrumentKind::Future), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_maker_fee_drops_after_volume_crosses_tier_threshold()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.balances.insert(Token::from("USDT"), Balance::new(100_000.0, 100_000.0));
+        // 两笔买单都以 Maker 成交；第一笔成交额 16400 越过 10000 的门槛，第二笔按第二档费率计费
+        account.config.volume_tiers = vec![VolumeTier { threshold_notional: 0.0, maker: 0.001, taker: 0.002 },
+                                           VolumeTier { threshold_notional: 10_000.0, maker: 0.0005, taker: 0.001 }];
+        assert_eq!(account.commission_level(InstrumentKind::Perpetual), CommissionLevel::Lv1);
+
+        let mut fees = Vec::new();
+        for (index, (timestamp, price)) in [(1_000_000_i64, 16400.0), (2_000_000, 16200.0)].into_iter().enumerate() {
+            account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                        exchange: Exchange::Hourglass,
+                                        instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                                        timestamp,
+                                        cid: Some(ClientOrderId(format!("tier_{}", index))),
+                                        side: Side::Buy,
+                                        state: RequestOpen { price,
+                                                             size: 1.0,
+                                                             reduce_only: false,
+                                                             tag: None } })
+                   .await
+                   .unwrap();
+            account.handle_trade_data(&MarketTrade { exchange: "binance-futures".to_string(),
+                                                     symbol: "ETHUSDT".to_string(),
+                                                     side: "sell".to_string(),
+                                                     price: price - 100.0,
+                                                     timestamp: timestamp + 1_000,
+                                                     amount: 1.0 })
+                   .await
+                   .unwrap();
+            while let Ok(event) = account_event_rx.try_recv() {
+                if let AccountEventKind::Trade(trade) = event.kind {
+                    fees.push(trade.fees);
+                }
+            }
+        }
+
+        assert_eq!(fees.len(), 2);
+        assert!((fees[0] - 16400.0 * 0.001).abs() < 1e-9);
+        assert!((fees[1] - 16200.0 * 0.0005).abs() < 1e-9);
+        assert_eq!(account.commission_level(InstrumentKind::Perpetual), CommissionLevel::Lv2);
+        assert_eq!(account.commission_level(InstrumentKind::Spot), CommissionLevel::Lv1);
+
+        // 窗口滑过两笔成交之后，即使没有新的成交，档位也会降回第一档
+        let expired_ts = 2_001_000 + VOLUME_TIER_WINDOW_MS + 1;
+        account.handle_trade_data(&MarketTrade { exchange: "binance-futures".to_string(),
+                                                 symbol: "ETHUSDT".to_string(),
+                                                 side: "buy".to_string(),
+                                                 price: 16400.0,
+                                                 timestamp: expired_ts,
+                                                 amount: 1.0 })
+               .await
+               .unwrap();
+        assert_eq!(account.commission_level(InstrumentKind::Perpetual), CommissionLevel::Lv1);
+        assert_eq!(account.traded_volume.volume(InstrumentKind::Perpetual), 0.0);
+        assert_eq!(account.volume_tier_rates(InstrumentKind::Perpetual).unwrap().maker_fees, 0.001);
+    }
+
+    #[test]
+    fn test_windowed_volume_matches_pruned_volume()
+    {
+        let mut tracker = RollingVolumeTracker::default();
+        tracker.record(0, InstrumentKind::Perpetual, 100.0, 1_000);
+        tracker.record(500, InstrumentKind::Spot, 50.0, 1_000);
+        tracker.record(800, InstrumentKind::Perpetual, 30.0, 1_000);
+
+        // 查询时不修改窗口，结果与修剪后的成交额一致
+        assert_eq!(tracker.volume_at(InstrumentKind::Perpetual, 1_600, 1_000), 30.0);
+        assert_eq!(tracker.volume_at(InstrumentKind::Spot, 1_600, 1_000), 0.0);
+        tracker.prune(1_600, 1_000);
+        assert_eq!(tracker.volume(InstrumentKind::Perpetual), 30.0);
+        assert_eq!(tracker.volume(InstrumentKind::Spot), 0.0);
+    }
+}
@@ -1,34 +1,53 @@
 use crate::{
     common::{
-        account_positions::{exited_positions::AccountExitedPositions, AccountPositions, PositionDirectionMode},
-        balance::{Balance, BalanceDelta, TokenBalance},
+        account_positions::{
+            exited_positions::AccountExitedPositions, leveraged_token::MANAGEMENT_FEE_TICK_MS, pnl_breakdown::PnlBreakdown, position_meta::PositionMeta, positions_snapshot::AccountPositionsSnapshot, AccountPositions,
+            PositionDirectionMode,
+        },
+        balance::{Balance, BalanceDelta, TokenBalance, Withdrawal},
         event::{AccountEvent, AccountEventKind},
-        instrument::Instrument,
+        instrument::{kind::InstrumentKind, Instrument},
         order::{
-            identification::{client_order_id::ClientOrderId, machine_id::generate_machine_id},
+            identification::client_order_id::ClientOrderId,
             order_instructions::OrderInstruction,
-            states::{cancelled::Cancelled, open::Open, request_cancel::RequestCancel, request_open::RequestOpen},
-            Order,
+            states::{
+                cancelled::Cancelled,
+                open::{Iceberg, Open},
+                request_cancel::RequestCancel,
+                request_open::RequestOpen,
+            },
+            stop_order::StopOrder,
+            Order, OrderRole,
         },
         token::Token,
+        trade::ClientTrade,
         Side,
     },
     error::ExchangeError,
     hourglass::{
         account::{
-            account_config::{ConfigLoader, FeesQuerier, HourglassMode},
+            account_commission::{CommissionProvider, FeesBookCommission},
+            account_config::{ConfigLoader, FeesQuerier, HourglassMode, NetModeOppositeBehavior},
             account_handlers::{balance_handler::BalanceHandler, position_handler::PositionHandler, trade_handler::TradeHandler},
+            account_order_flow::{OrderFlowMessage, OrderFlowTracker, DEFAULT_ORDER_FLOW_WINDOW_MS},
             account_orders::{LatencySimulator, OrderRoleClassifier},
+            account_spot::SpotCostBasis,
+            account_volume_tiers::RollingVolumeTracker,
+        },
+        clickhouse_api::datatype::{
+            clickhouse_trade_data::MarketTrade,
+            depth_order_book::DepthOrderBook,
+            single_level_order_book::{OrderBookUpdater, SingleLevelOrderBook},
         },
-        clickhouse_api::datatype::single_level_order_book::{OrderBookUpdater, SingleLevelOrderBook},
+        matching_engine::MatchingEngine,
     },
-    hourglass_log::info,
+    hourglass_log::{info, warn},
     Exchange,
 };
 use account_config::AccountConfig;
 use account_orders::AccountOrders;
 use atomic_float::AtomicF64;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use dashmap::{mapref::one::RefMut as DashMapRefMut, DashMap};
 use mpsc::UnboundedSender;
 use oneshot::Sender;
@@ -45,11 +64,29 @@ use std::{
 use tokio::sync::{mpsc, oneshot, Mutex, RwLock};
 use uuid::Uuid;
 
+pub mod account_amend;
+#[cfg(feature = "event_broadcast")]
+pub mod account_broadcast;
+pub mod account_checkpoint;
+pub mod account_commission;
 pub mod account_config;
+pub mod account_depth;
 pub mod account_handlers;
+pub mod account_iceberg;
+pub mod account_invariants;
 pub mod account_latency;
+pub mod account_liquidation;
+pub mod account_margin;
 pub mod account_market_feed;
+pub mod account_oco;
+pub mod account_order_flow;
 pub mod account_orders;
+pub mod account_reconciliation;
+pub mod account_spot;
+pub mod account_spread;
+pub mod account_stops;
+pub mod account_tape;
+pub mod account_volume_tiers;
 
 #[derive(Debug)]
 pub struct HourglassAccount
@@ -70,6 +107,16 @@ pub struct HourglassAccount
     pub positions: AccountPositions,                                                    // 帐户持仓
     pub exited_positions: AccountExitedPositions,                                       // pub vault: Vault,
     pub account_margin: Arc<AtomicF64>,
+    pub order_flow: OrderFlowTracker, // 报单、撤单与成交的滑动窗口统计
+    pub traded_volume: RollingVolumeTracker, // 按金融工具类别统计的 30 天滚动成交额，用于选择阶梯费率
+    pub spot_cost_basis: DashMap<Instrument, SpotCostBasis>, // 现货持仓的成本基础，按现货交易对统计
+    pub depth_order_books: Arc<Mutex<HashMap<Instrument, DepthOrderBook>>>, // 回放加载的多档深度，市价单会吃掉其中的流动性
+    pub own_fill_prints: Vec<MarketTrade>, // 等待推送到行情流的本账户成交记录，仅在启用 `own_fills_on_tape` 时写入
+    pub trade_history: HashMap<Instrument, Vec<ClientTrade>>, // 本账户已结算的成交，按金融工具分组并按结算顺序记录，见 `fetch_trades`
+    pub last_margin_update: Option<(i64, f64)>, // 上次发送 `MarginUpdate` 时的时间戳与保证金率
+    pub last_funding_ts: Option<i64>, // 最近一次资金费结算时点，按 `funding_interval_ms` 对齐
+    pub commission_provider: Arc<dyn CommissionProvider>, // 成交手续费的计算方式，默认按 `fees_book` 收取
+    pub matching_engines: HashMap<Instrument, Box<dyn MatchingEngine>>, // 自定义撮合器，未设置的金融工具按 `matching_algorithms` 配置撮合
 }
 
 // 手动实现 Clone trait
@@ -88,7 +135,17 @@ impl Clone for HourglassAccount
                            balances: self.balances.clone(),
                            positions: self.positions.clone(),
                            exited_positions: self.exited_positions.clone(),
-                           account_margin: self.account_margin.clone() }
+                           account_margin: self.account_margin.clone(),
+                           order_flow: self.order_flow.clone(),
+                           traded_volume: self.traded_volume.clone(),
+                           spot_cost_basis: self.spot_cost_basis.clone(),
+                           depth_order_books: Arc::new(Mutex::new(HashMap::new())),
+                           own_fill_prints: self.own_fill_prints.clone(),
+                           trade_history: self.trade_history.clone(),
+                           last_margin_update: self.last_margin_update,
+                           last_funding_ts: self.last_funding_ts,
+                           commission_provider: Arc::clone(&self.commission_provider),
+                           matching_engines: self.matching_engines.clone() }
     }
 }
 #[derive(Debug)]
@@ -100,6 +157,8 @@ pub struct AccountBuilder
     balances: Option<DashMap<Token, Balance>>,
     positions: Option<AccountPositions>,
     closed_positions: Option<AccountExitedPositions>,
+    commission_provider: Option<Arc<dyn CommissionProvider>>,
+    matching_engines: HashMap<Instrument, Box<dyn MatchingEngine>>,
 }
 
 impl Default for AccountBuilder
@@ -119,7 +178,9 @@ impl AccountBuilder
                          orders: None,
                          balances: None,
                          positions: None,
-                         closed_positions: None }
+                         closed_positions: None,
+                         commission_provider: None,
+                         matching_engines: HashMap::new() }
     }
 
     pub fn account_event_tx(mut self, value: UnboundedSender<AccountEvent>) -> Self
@@ -152,20 +213,66 @@ impl AccountBuilder
         self
     }
 
+    pub fn closed_positions(mut self, value: AccountExitedPositions) -> Self
+    {
+        self.closed_positions = Some(value);
+        self
+    }
+
+    /// 自定义成交手续费的计算方式，未设置时使用 [`FeesBookCommission`]。
+    pub fn commission_provider(mut self, value: Arc<dyn CommissionProvider>) -> Self
+    {
+        self.commission_provider = Some(value);
+        self
+    }
+
+    /// 为某个金融工具指定自定义撮合器，优先于 `AccountConfig.matching_algorithms` 中的内置算法。
+    pub fn matching_engine(mut self, instrument: Instrument, engine: Box<dyn MatchingEngine>) -> Self
+    {
+        self.matching_engines.insert(instrument, engine);
+        self
+    }
+
+    /// 构建账户。配置了 `AccountConfig::machine_id` 时账户与订单集合都使用该固定ID，
+    /// 生成的订单ID因此不依赖运行的机器；否则由本机MAC地址推导，订单集合保持构造时传入的ID。
+    /// 配置了 `AccountConfig::seed` 时用它重置订单集合的延迟生成器与订单ID随机数生成器。
     pub fn build(self) -> Result<HourglassAccount, String>
     {
+        let config = self.config.ok_or("config is required")?;
+        let machine_id = config.resolve_machine_id()?;
+        let account_open_book = self.orders.ok_or("orders are required")?;
+        if config.machine_id.is_some() || config.seed.is_some() {
+            let mut orders = account_open_book.try_write().map_err(|_| "orders are locked while building the account")?;
+            if config.machine_id.is_some() {
+                orders.machine_id = machine_id;
+            }
+            if let Some(seed) = config.seed {
+                orders.reseed(seed);
+            }
+        }
+
         Ok(HourglassAccount { current_session: Uuid::new_v4(),
-                              machine_id: generate_machine_id()?,
+                              machine_id,
                               client_trade_counter: 0.into(),
                               exchange_timestamp: 0.into(),
                               account_event_tx: self.account_event_tx.ok_or("account_event_tx is required")?,
-                              config: self.config.ok_or("config is required")?,
-                              account_open_book: self.orders.ok_or("orders are required")?,
+                              config,
+                              account_open_book,
                               balances: self.balances.ok_or("balances are required")?,
                               positions: self.positions.ok_or("positions are required")?,
                               single_level_order_book: Arc::new(Mutex::new(HashMap::new())),
                               exited_positions: self.closed_positions.ok_or("closed_positions sink are required")?,
-                              account_margin: Arc::new(0.0.into()) })
+                              account_margin: Arc::new(0.0.into()),
+                              order_flow: OrderFlowTracker::default(),
+                              traded_volume: RollingVolumeTracker::default(),
+                              spot_cost_basis: DashMap::new(),
+                              depth_order_books: Arc::new(Mutex::new(HashMap::new())),
+                              own_fill_prints: Vec::new(),
+                              trade_history: HashMap::new(),
+                              last_margin_update: None,
+                              last_funding_ts: None,
+                              commission_provider: self.commission_provider.unwrap_or_else(|| Arc::new(FeesBookCommission)),
+                              matching_engines: self.matching_engines })
     }
 }
 
@@ -186,7 +293,8 @@ impl HourglassAccount
     {
         for token_str in tokens {
             let token = Token(token_str);
-            self.balances.entry(token.clone()).or_insert_with(|| Balance { time: Utc::now(),
+            let time = self.exchange_time();
+            self.balances.entry(token.clone()).or_insert_with(|| Balance { time,
                                                                            // current_price: Some(1.0), // 假设初始价格为 1.0，具体根据实际情况调整
                                                                            total: 0.0,
                                                                            available: 0.0 });
@@ -208,8 +316,9 @@ impl HourglassAccount
     /// 返回更新后的 `TokenBalance`。
     fn deposit_coin(&mut self, token: Token, amount: f64) -> Result<TokenBalance, ExchangeError>
     {
+        let time = self.exchange_time();
         let mut balance = self.balances.entry(token.clone()).or_insert_with(|| {
-                                                                Balance { time: Utc::now(),
+                                                                Balance { time,
                                                                           // current_price: Some(1.0), // 假设稳定币价格为1.0
                                                                           total: 0.0,
                                                                           available: 0.0 }
@@ -217,6 +326,7 @@ impl HourglassAccount
 
         balance.total += amount;
         balance.available += amount;
+        balance.time = time;
 
         Ok(TokenBalance::new(token, *balance))
     }
@@ -281,6 +391,89 @@ impl HourglassAccount
         self.deposit_coin(btc_token, amount)
     }
 
+    /// 从账户提现指定数量的 `Token`，并发送 `AccountEventKind::Withdrawal`。
+    ///
+    /// 按 `AccountConfig.withdrawal_rules` 中该币种的规则：提现数量低于 `min_withdrawal` 时拒绝，
+    /// 否则从可用余额中扣除 `amount + withdrawal_fee`。未配置规则的币种不收费也不设下限。
+    ///
+    /// # 参数
+    ///
+    /// * `token` - 需要提现的 `Token`。
+    /// * `amount` - 转出账户的数额，不含手续费。
+    ///
+    /// # 返回值
+    ///
+    /// 返回提现数量、手续费与更新后的余额。
+    pub fn withdraw(&mut self, token: Token, amount: f64) -> Result<Withdrawal, ExchangeError>
+    {
+        if amount <= 0.0 {
+            return Err(ExchangeError::Hourglass("Withdrawal amount must be positive".into()));
+        }
+        let (fee, min_withdrawal) = self.config
+                                        .withdrawal_rule(&token)
+                                        .map(|rule| (rule.withdrawal_fee, rule.min_withdrawal))
+                                        .unwrap_or((0.0, 0.0));
+        if amount < min_withdrawal {
+            return Err(ExchangeError::BelowMinimumWithdrawal(token, amount, min_withdrawal));
+        }
+
+        let balance = {
+            let required = amount + fee;
+            let insufficient = |available| ExchangeError::InsufficientBalance { token: token.clone(), required, available };
+            let mut balance = self.balances.get_mut(&token).ok_or_else(|| insufficient(0.0))?;
+            let available = balance.available;
+            balance.apply(BalanceDelta::new(-required, -required))
+                   .map_err(|_| insufficient(available))?;
+            balance.time = self.exchange_time();
+            *balance
+        };
+
+        let withdrawal = Withdrawal { amount,
+                                      fee,
+                                      balance: TokenBalance::new(token, balance) };
+        self.send_account_event(AccountEvent { exchange_timestamp: self.exchange_timestamp.load(Ordering::SeqCst),
+                                               exchange: Exchange::Hourglass,
+                                               kind: AccountEventKind::Withdrawal(withdrawal.clone()) })?;
+        Ok(withdrawal)
+    }
+
+    /// 把价值低于 `threshold` 的零散余额按 `prices` 折算为 `reference`，并从余额表中移除这些币种。
+    ///
+    /// `prices` 为各币种以 `reference` 计价的价格，价值按 `total * price` 计算并与 `threshold`（以 `reference` 计）比较。
+    /// 没有价格的币种、`reference` 本身以及仍有冻结余额（`total` 与 `available` 不相等）的币种不会被清扫。
+    ///
+    /// 有余额被清扫时发送一次 `AccountEventKind::Balances`，包含清零的各币种与更新后的 `reference` 余额。
+    ///
+    /// # 返回值
+    ///
+    /// 返回该事件中的余额列表，没有可清扫的余额时为空。
+    pub fn sweep_dust(&mut self, reference: &Token, prices: &HashMap<Token, f64>, threshold: f64) -> Result<Vec<TokenBalance>, ExchangeError>
+    {
+        let dust: Vec<(Token, f64)> = self.balances
+                                          .iter()
+                                          .filter(|entry| entry.key() != reference && entry.total == entry.available)
+                                          .filter_map(|entry| prices.get(entry.key()).map(|price| (entry.key().clone(), entry.total * price)))
+                                          .filter(|(_, value)| *value < threshold)
+                                          .collect();
+        if dust.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut swept_balances = Vec::with_capacity(dust.len() + 1);
+        let mut proceeds = 0.0;
+        for (token, value) in dust {
+            self.balances.remove(&token);
+            proceeds += value;
+            swept_balances.push(TokenBalance::new(token, Balance::new(0.0, 0.0)));
+        }
+        swept_balances.push(self.deposit_coin(reference.clone(), proceeds)?);
+
+        self.send_account_event(AccountEvent { exchange_timestamp: self.exchange_timestamp.load(Ordering::SeqCst),
+                                               exchange: Exchange::Hourglass,
+                                               kind: AccountEventKind::Balances(swept_balances.clone()) })?;
+        Ok(swept_balances)
+    }
+
     /// NOTE : BETA功能，待测试。
     /// 用 `u本位` (USDT) 买 `b本位` (BTC)。
     ///
@@ -323,10 +516,33 @@ impl HourglassAccount
         respond(response_tx, Ok(orders));
     }
 
+    /// 返回所有仓位表在当前时刻的快照，之后的仓位变化不会反映到快照中，与 `Positions` 事件的语义一致。
+    pub async fn fetch_positions(&self) -> AccountPositionsSnapshot
+    {
+        self.positions.snapshot().await
+    }
+
+    /// 按结算顺序返回 [`Instrument`] 上本账户保留的历史成交，数量上限见 `trade_history_limit`。
+    pub fn fetch_trades(&self, instrument: &Instrument) -> Vec<ClientTrade>
+    {
+        self.trade_history.get(instrument).cloned().unwrap_or_default()
+    }
+
+    /// 记录一笔已结算的成交，超出 `trade_history_limit` 时丢弃该金融工具最早的成交。
+    pub(crate) fn record_trade_history(&mut self, trade: &ClientTrade)
+    {
+        let history = self.trade_history.entry(trade.instrument.clone()).or_default();
+        history.push(trade.clone());
+        if let Some(limit) = self.config.trade_history_limit {
+            let excess = history.len().saturating_sub(limit);
+            history.drain(..excess);
+        }
+    }
+
     /// 处理多个开仓订单请求，并执行相应操作。
     ///
     /// 对于每个开仓请求，该函数根据配置的 `PositionDirectionMode` 来判断是否允许方向冲突。如果是 `NetMode`，则会检查订单方向与当前持仓的方向是否冲突。
-    /// 如果订单标记为 `reduce only`，则不会进行方向冲突检查，但仍需判断订单方向与现有持仓方向是否一致。如果 `reduce only` 订单的方向与现有持仓方向相同，将拒绝该订单。
+    /// 如果订单标记为 `reduce only`，则不会进行方向冲突检查，而是由 [`Self::enforce_reduce_only`] 确认订单只会减少已有仓位，超出仓位的数量截断到仓位大小。
     ///
     /// # 参数
     ///
@@ -336,15 +552,23 @@ impl HourglassAccount
     /// # 逻辑
     ///
     /// 1. 首先检查订单的 `reduce only` 状态：
-    ///    - 如果是 `reduce only`，则跳过方向冲突检查，但如果订单方向与当前持仓方向相同，则拒绝该订单。
-    /// 2. 如果是 `NetMode` 且订单不是 `reduce only`，则调用 `check_position_direction_conflict` 检查当前持仓方向是否与订单冲突。
-    /// 3. 计算订单的当前价格，并尝试原子性开仓操作。
+    ///    - 如果是 `reduce only`，则跳过方向冲突检查；没有可减少的仓位或会增加净敞口时拒绝该订单，否则把数量截断到可减少的仓位大小，
+    ///      返回的 `Order<Open>` 中是截断后的数量。
+    /// 2. 如果是 `NetMode` 且订单不是 `reduce only`，按 `net_mode_opposite_behavior` 处理与持仓方向相反的订单：
+    ///    `Reject` 时调用 `check_position_direction_conflict` 检查冲突并拒绝，`Reduce` 时接受订单，成交时与持仓轧差。
+    /// 3. 计算订单的当前价格，并尝试原子性开仓操作，见 [`Self::atomic_open`]。
     /// 4. 将每个订单的处理结果发送到 `response_tx`。
     ///
+    /// # 批次顺序
+    ///
+    /// 批次内的请求严格按提交顺序（在 `open_requests` 中的下标）逐个处理，结果按同样的顺序返回。
+    /// 订单按此顺序进入挂单簿，同价位的挂单先到先成交，因此同一批次中竞争同一笔外部成交流动性的订单
+    /// （例如两个同价位的 IOC）总是先提交的先成交，IOC 剩余部分在该笔成交撮合后撤销。
+    ///
     /// # 错误处理
     ///
-    /// - 如果 `reduce only` 订单的方向与现有持仓方向相同，则拒绝该订单，并继续处理下一个订单。
-    /// - 如果在 `NetMode` 下存在方向冲突，则跳过该订单并继续处理下一个订单。
+    /// - 如果 `reduce only` 订单没有可减少的仓位或会增加净敞口，则以 `ExchangeError::ReduceOnlyRejected` 拒绝该订单，并继续处理下一个订单。
+    /// - 如果在 `NetMode` 下存在方向冲突且配置为 `Reject`，则跳过该订单并继续处理下一个订单。
     pub async fn open_orders(&mut self, open_requests: Vec<Order<RequestOpen>>, response_tx: Sender<Vec<Result<Order<Open>, ExchangeError>>>) -> Result<(), ExchangeError>
     {
         let mut open_results = Vec::new();
@@ -352,7 +576,36 @@ impl HourglassAccount
         // 获取当前的 position_direction_mode 并提前判断是否需要进行方向冲突检查
         let is_netmode = self.config.global_position_direction_mode == PositionDirectionMode::Net;
 
-        for request in open_requests {
+        for mut request in open_requests {
+            // 通过别名表把订单的金融工具统一为规范写法，避免同一工具被当成两个不同的工具交易
+            match self.config.instrument_aliases.canonicalize(&request.instrument) {
+                | Ok(instrument) => request.instrument = instrument,
+                | Err(err) => {
+                    open_results.push(Err(err));
+                    continue;
+                }
+            }
+
+            // 拒绝数量或价格非法的订单
+            if let Err(err) = Self::validate_order_size_and_price(&request) {
+                open_results.push(Err(err));
+                continue;
+            }
+
+            // 检查该金融工具的挂单数量是否已达上限
+            if let Err(err) = self.check_open_orders_limit(&request.instrument).await {
+                open_results.push(Err(err));
+                continue;
+            }
+
+            // reduce_only 订单只能减少已有仓位，超出仓位的部分截断到仓位大小
+            if request.state.reduce_only {
+                if let Err(err) = self.enforce_reduce_only(&mut request).await {
+                    open_results.push(Err(err));
+                    continue;
+                }
+            }
+
             // 如果是 NetMode，检查方向冲突
             if is_netmode {
                 if let Err(err) = self.check_direction_conflict(&request).await {
@@ -372,6 +625,8 @@ impl HourglassAccount
             open_results.push(open_result);
         }
 
+        self.assert_invariants(&"OpenOrders").await;
+
         // 发送处理结果
         if let Err(e) = response_tx.send(open_results) {
             return Err(ExchangeError::Hourglass(format!("Failed to send open order results: {:?}", e)));
@@ -380,29 +635,99 @@ impl HourglassAccount
         Ok(())
     }
 
-    // 辅助函数，用于检查仓位方向冲突
-    async fn check_direction_conflict(&self, request: &Order<RequestOpen>) -> Result<(), ExchangeError>
+    // 辅助函数，按 `instrument_filters` 对齐订单的数量与限价，不对齐时按 `filter_violation` 拒绝或取整
+    fn apply_instrument_filters(&self, mut order: Order<RequestOpen>) -> Result<Order<RequestOpen>, ExchangeError>
     {
-        if request.state.reduce_only {
-            // 获取当前仓位
-            let (long_pos, short_pos) = self.get_position_both_ways(&request.instrument).await?;
+        if let Some(filters) = self.config.instrument_filters(&order.instrument) {
+            let handling = self.config.filter_violation;
+            order.state.size = filters.align_size(order.state.size, handling)?;
+            if order.instruction != OrderInstruction::Market {
+                order.state.price = filters.align_price(order.side, order.state.price, handling)?;
+            }
+        }
+        Ok(order)
+    }
 
-            // 检查 reduce_only 订单是否有相同方向的仓位
-            match request.side {
-                | Side::Buy => {
-                    if long_pos.is_some() {
-                        return Err(ExchangeError::InvalidDirection);
-                    }
-                }
-                | Side::Sell => {
-                    if short_pos.is_some() {
-                        return Err(ExchangeError::InvalidDirection);
-                    }
-                }
+    // 辅助函数，用于检查订单名义价值是否达到金融工具的最小名义价值，市价单按对手方最优价估算
+    fn check_min_notional(&self, order: &Order<RequestOpen>, order_book: &SingleLevelOrderBook) -> Result<(), ExchangeError>
+    {
+        if let Some(min_notional) = self.config.min_notional(&order.instrument) {
+            let price = match (order.instruction, order.side) {
+                | (OrderInstruction::Market, Side::Buy) => order_book.latest_ask,
+                | (OrderInstruction::Market, Side::Sell) => order_book.latest_bid,
+                | _ => order.state.price,
+            };
+            let notional = price * order.state.size * self.config.contract_multiplier(&order.instrument);
+            if notional < min_notional {
+                return Err(ExchangeError::BelowMinNotional { min: min_notional, got: notional });
             }
         }
-        else {
-            // 检查非 reduce_only 订单的方向冲突
+        Ok(())
+    }
+
+    // 辅助函数，用于检查金融工具的挂单数量是否已达到 `max_open_orders_per_instrument`
+    async fn check_open_orders_limit(&self, instrument: &Instrument) -> Result<(), ExchangeError>
+    {
+        if let Some(max_open_orders) = self.config.max_open_orders_per_instrument {
+            let open_orders_count = self.account_open_book.read().await.get_ins_orders_mut(instrument).map(|orders| orders.num_orders()).unwrap_or(0);
+            if open_orders_count >= max_open_orders {
+                return Err(ExchangeError::MaxOpenOrdersExceeded(max_open_orders));
+            }
+        }
+        Ok(())
+    }
+
+    /// 校验 `reduce_only` 订单，并把超出可减少仓位的数量截断到仓位大小。
+    ///
+    /// 买单只能减少空仓，卖单只能减少多仓，没有可减少的仓位时以 `ExchangeError::ReduceOnlyRejected` 拒绝。
+    /// `Net` 模式下同一金融工具只有一个净仓位，与订单同向的仓位意味着订单会增加净敞口，同样拒绝；
+    /// `LongShort` 模式下多空仓位相互独立，只看与订单方向相反的那一侧仓位，同向仓位不影响判断。
+    /// 同方向已挂出的 reduce_only 订单的剩余数量从可减少的仓位中扣除，已占满时同样拒绝。
+    async fn enforce_reduce_only(&self, request: &mut Order<RequestOpen>) -> Result<(), ExchangeError>
+    {
+        let (long_size, short_size) = match request.instrument.kind {
+            | InstrumentKind::Perpetual => (self.positions.perpetual_pos_long.read().await.get(&request.instrument).map(|pos| pos.meta.current_size),
+                                            self.positions.perpetual_pos_short.read().await.get(&request.instrument).map(|pos| pos.meta.current_size)),
+            | InstrumentKind::Future => (self.positions.futures_pos_long.read().await.get(&request.instrument).map(|pos| pos.meta.current_size),
+                                         self.positions.futures_pos_short.read().await.get(&request.instrument).map(|pos| pos.meta.current_size)),
+            | kind => return Err(ExchangeError::ReduceOnlyRejected(format!("{} instruments have no positions to reduce", kind))),
+        };
+        let (same_side_size, opposite_size) = match request.side {
+            | Side::Buy => (long_size.unwrap_or(0.0), short_size.unwrap_or(0.0)),
+            | Side::Sell => (short_size.unwrap_or(0.0), long_size.unwrap_or(0.0)),
+        };
+
+        if self.config.global_position_direction_mode == PositionDirectionMode::Net && same_side_size > 0.0 {
+            return Err(ExchangeError::ReduceOnlyRejected(format!("{} order would increase the net position in {}", request.side, request.instrument)));
+        }
+        if opposite_size <= 0.0 {
+            return Err(ExchangeError::ReduceOnlyRejected(format!("No opposing position in {} to reduce", request.instrument)));
+        }
+
+        // 同方向已挂出的 reduce_only 订单已经占用了一部分可减少的仓位
+        let outstanding_reduce_only: f64 = match self.account_open_book.read().await.get_ins_orders_mut(&request.instrument) {
+            | Ok(orders) => {
+                let same_side_orders = match request.side {
+                    | Side::Buy => &orders.bids,
+                    | Side::Sell => &orders.asks,
+                };
+                same_side_orders.iter().filter(|order| order.state.reduce_only).map(|order| order.state.remaining_quantity()).sum()
+            }
+            | Err(_) => 0.0,
+        };
+        let reducible_size = opposite_size - outstanding_reduce_only;
+        if reducible_size <= 0.0 {
+            return Err(ExchangeError::ReduceOnlyRejected(format!("Outstanding reduce-only orders already cover the position in {}", request.instrument)));
+        }
+        request.state.size = request.state.size.min(reducible_size);
+        Ok(())
+    }
+
+    // 辅助函数，用于检查仓位方向冲突；reduce_only 订单已由 `enforce_reduce_only` 校验
+    async fn check_direction_conflict(&self, request: &Order<RequestOpen>) -> Result<(), ExchangeError>
+    {
+        if !request.state.reduce_only && self.config.net_mode_opposite_behavior == NetModeOppositeBehavior::Reject {
+            // 检查非 reduce_only 订单的方向冲突；配置为 `Reduce` 时接受反向订单，成交时与持仓轧差
             self.check_position_direction_conflict(&request.instrument, request.side, request.state.reduce_only).await?;
         }
 
@@ -433,10 +758,33 @@ impl HourglassAccount
     //     }
     // }
 
+    /// 校验并挂出一笔订单，锁定所需的可用余额。
+    ///
+    /// # 交叉的限价单
+    ///
+    /// 价格与对手方交叉（买单不低于最优卖价、卖单不高于最优买价）的非 PostOnly 限价单不会交叉挂在订单簿中，
+    /// 最优价取单档订单簿与本地深度（含合成档位）中任一交叉者，见 [`Self::limit_crosses_depth`]：
+    /// 加载了深度时，先按 Taker 吃掉价格不劣于限价的所有档位，剩余部分再按限价作为 Maker 挂单。
+    /// 此时依次发送各笔成交的事件、`OrdersPartiallyFilled`（完全成交时为 `OrdersFilled`）与剩余部分的 `OrdersOpen`。
+    /// 没有加载深度时退回到单档订单簿，按最优对手价以 Taker 成交全部数量，见 [`Self::fill_order_at_top_of_book`]。
+    ///
+    /// # PostOnly 订单
+    ///
+    /// `PostOnlyLimit` 在判断订单角色时与对手方最优价格比较，交叉时返回 `ExchangeError::PostOnlyWouldCross`，
+    /// 订单不会进入挂单簿；被接受的 PostOnly 订单的 `order_role` 总是 `OrderRole::Maker`。
     pub async fn atomic_open(&mut self, order: Order<RequestOpen>) -> Result<Order<Open>, ExchangeError>
     {
-        // 验证订单的基本合法性
-        Self::validate_order_instruction(order.instruction)?;
+        self.atomic_open_with_iceberg(order, None).await
+    }
+
+    /// 与 [`Self::atomic_open`] 相同，`iceberg` 为 `Some` 时挂出的订单为冰山单，见 [`Self::submit_iceberg`]。
+    pub(crate) async fn atomic_open_with_iceberg(&mut self, order: Order<RequestOpen>, iceberg: Option<Iceberg>) -> Result<Order<Open>, ExchangeError>
+    {
+        self.check_submission_allowed()?;
+
+        // 取整可能减少数量，冰山单的显示部分按取整后的数量重新计算
+        let order = self.validate_open_request(order).await?;
+        let iceberg = iceberg.map(|iceberg| Iceberg::new(iceberg.display_size, order.state.size));
 
         info!("[attempt_atomic_open] : Successfully validated order instruction");
 
@@ -445,37 +793,310 @@ impl HourglassAccount
             let mut order_books_lock = self.single_level_order_book.lock().await;
             info!("[attempt_atomic_open] order_books_lock: {:?}", order_books_lock);
             info!("instrument is {:#?}", order.instrument);
-            let order_book = order_books_lock.get_mut(&order.instrument).unwrap(); // 引用的生命周期延长
+            let order_book = order_books_lock.get_mut(&order.instrument)
+                                             .ok_or_else(|| ExchangeError::InvalidInstrument(format!("No market data for {}", order.instrument)))?; // 引用的生命周期延长
             let orders_guard = self.account_open_book.read().await;
             // 将订单簿传递给 determine_maker_taker
             orders_guard.determine_maker_taker(&order, order_book)?
         };
+        // 与本地深度（含合成档位）交叉的限价单同样可以立即成交
+        let order_role = match order_role {
+            | OrderRole::Maker if self.limit_crosses_depth(&order).await => OrderRole::Taker,
+            | order_role => order_role,
+        };
 
         // 锁已经在此处释放，后续操作可以安全地借用 `self` NOTE 此处计算required_available_balance要分离出maker的处理规则
         let (token, required_balance) = self.required_available_balance(&order, order_role).await?;
         info!("[attempt_atomic_open] required balance is quoted in {}: {}", token, required_balance);
         self.has_sufficient_available_balance(token, required_balance)?;
+        self.check_cross_margin_for_order(&order).await?;
+        // 只有通过校验与余额检查、被交易所接受的报单才计入报单成交比
+        self.record_order_flow(OrderFlowMessage::Submission);
 
-        let open_order = {
-            let mut orders_guard = self.account_open_book.write().await;
-            let open_order = orders_guard.build_order_open(order, order_role).await;
-            orders_guard.get_ins_orders_mut(&open_order.instrument)?.add_order_open(open_order.clone());
-            open_order
-        };
+        let exchange_timestamp = self.exchange_timestamp.load(Ordering::SeqCst);
+        let mut open_order = self.account_open_book.write().await.build_order_open(order, order_role).await;
+        open_order.state.iceberg = iceberg;
+
+        // 已到达交易所的 IOC/FOK 在开单时即执行完毕：成交可用的深度，剩余部分撤销，FOK 不能完全成交时直接拒绝，从不进入挂单簿。
+        // 带模拟延迟、尚未到达交易所的订单不能立即成交，先挂单，到达后由之后的深度或成交撮合
+        let arrived = open_order.timestamp <= exchange_timestamp;
+        if arrived && matches!(open_order.instruction, OrderInstruction::ImmediateOrCancel | OrderInstruction::FillOrKill) {
+            return self.execute_immediate_order(open_order, required_balance).await;
+        }
+        self.account_open_book.read().await.get_ins_orders_mut(&open_order.instrument)?.add_order_open(open_order.clone());
 
         let balance_event = self.apply_open_order_changes(&open_order, required_balance).await?;
-        let exchange_timestamp = self.exchange_timestamp.load(Ordering::SeqCst);
 
         // 使用 `send_account_event` 发送余额和订单事件
         self.send_account_event(balance_event)?;
+
+        // 与对手方深度交叉的限价单先按限价吃掉可用流动性，剩余部分再挂单。
+        let crossing_limit = order_role == OrderRole::Taker && matches!(open_order.instruction, OrderInstruction::Limit | OrderInstruction::GoodTilCancelled | OrderInstruction::GoodTilTime { .. });
+        if crossing_limit && arrived {
+            let filled_order = match self.fill_order_from_depth(&open_order, Some(open_order.state.price), OrderRole::Taker).await? {
+                | Some(filled_order) => Some(filled_order),
+                | None if !self.depth_order_books.lock().await.contains_key(&open_order.instrument) => self.fill_order_at_top_of_book(&open_order).await?,
+                | None => None,
+            };
+            if let Some(filled_order) = filled_order {
+                self.send_crossing_fill_events(&filled_order)?;
+                return Ok(filled_order);
+            }
+        }
+
         let order_event = AccountEvent { exchange_timestamp,
                                          exchange: Exchange::Hourglass,
                                          kind: AccountEventKind::OrdersOpen(vec![open_order.clone()]) };
 
         self.send_account_event(order_event)?;
+
+        // 加载了深度时市价单立即吃掉对手方档位，而不是等待后续的市场成交
+        if open_order.instruction == OrderInstruction::Market && arrived {
+            if let Some(filled_order) = self.fill_order_from_depth(&open_order, None, OrderRole::Taker).await? {
+                return Ok(filled_order);
+            }
+        }
         Ok(open_order)
     }
 
+    // 辅助函数，预热期内或报单成交比超限时拒绝新的开单请求（模拟交易所的限频）
+    fn check_submission_allowed(&mut self) -> Result<(), ExchangeError>
+    {
+        if let Some(warmup_until_ts) = self.config.warmup_until_ts {
+            if self.is_warming_up() {
+                return Err(ExchangeError::WarmUpInProgress(warmup_until_ts));
+            }
+        }
+        if let Some(max_ratio) = self.config.order_to_trade_limit.as_ref().map(|limit| limit.max_ratio) {
+            let ratio = self.order_to_trade_ratio();
+            if ratio > max_ratio {
+                return Err(ExchangeError::OrderToTradeRatioExceeded(ratio));
+            }
+        }
+        Ok(())
+    }
+
+    // 辅助函数，校验开单请求的指令、有效期、过滤器和最小名义价值，返回按过滤器对齐后的订单，不改动任何状态
+    async fn validate_open_request(&self, order: Order<RequestOpen>) -> Result<Order<RequestOpen>, ExchangeError>
+    {
+        Self::validate_order_instruction(order.instruction)?;
+        if order.instruction.is_expired(self.exchange_timestamp.load(Ordering::SeqCst)) {
+            return Err(ExchangeError::InvalidOrder(format!("{:?} has already expired", order.instruction)));
+        }
+
+        let order = self.apply_instrument_filters(order)?;
+
+        let order_books_lock = self.single_level_order_book.lock().await;
+        let order_book = order_books_lock.get(&order.instrument)
+                                         .ok_or_else(|| ExchangeError::InvalidInstrument(format!("No market data for {}", order.instrument)))?;
+        self.check_min_notional(&order, order_book)?;
+        Ok(order)
+    }
+
+    /// 当前统计窗口内的报单成交比，即 (报单数 + 撤单数) / 成交数。
+    pub fn order_to_trade_ratio(&mut self) -> f64
+    {
+        let now = self.exchange_timestamp.load(Ordering::SeqCst);
+        let window_ms = self.order_flow_window_ms();
+        self.order_flow.prune(now, window_ms);
+        self.order_flow.order_to_trade_ratio()
+    }
+
+    /// 以当前交易所时间戳记录一条报单流量消息。
+    pub(crate) fn record_order_flow(&mut self, message: OrderFlowMessage)
+    {
+        let now = self.exchange_timestamp.load(Ordering::SeqCst);
+        let window_ms = self.order_flow_window_ms();
+        self.order_flow.record(now, message, window_ms);
+    }
+
+    fn order_flow_window_ms(&self) -> i64
+    {
+        self.config.order_to_trade_limit.as_ref().map_or(DEFAULT_ORDER_FLOW_WINDOW_MS, |limit| limit.window_ms)
+    }
+
+    /// 判断账户是否仍处于预热期，即当前交易所时间戳尚未到达 `warmup_until_ts`。
+    pub fn is_warming_up(&self) -> bool
+    {
+        match self.config.warmup_until_ts {
+            | Some(warmup_until_ts) => self.exchange_timestamp.load(Ordering::SeqCst) < warmup_until_ts,
+            | None => false,
+        }
+    }
+
+    /// 维护 tick：为杠杆代币持仓计提管理费，并从计价货币余额中扣除。
+    ///
+    /// 仅在配置了 `leveraged_token_daily_fee_rate` 时生效。每个持仓按距上次计提经过的整数个 [`MANAGEMENT_FEE_TICK_MS`] 周期计提，
+    /// 计提时间戳只前进这些整周期，余下不足一个周期的时间计入下一次。返回本次计提的费用总额。
+    pub async fn accrue_leveraged_token_management_fees(&mut self) -> f64
+    {
+        let Some(daily_rate) = self.config.leveraged_token_daily_fee_rate
+        else {
+            return 0.0;
+        };
+        let now = self.exchange_timestamp.load(Ordering::SeqCst);
+
+        let mut charges: Vec<(Token, f64)> = Vec::new();
+        for positions in [&self.positions.margin_pos_long, &self.positions.margin_pos_short] {
+            for (instrument, position) in positions.write().await.iter_mut() {
+                let last_ts = position.last_fee_accrual_ts.unwrap_or(position.meta.enter_ts);
+                let elapsed_periods = (now - last_ts).div_euclid(MANAGEMENT_FEE_TICK_MS);
+                if elapsed_periods <= 0 {
+                    continue;
+                }
+                let fee = position.accrue_management_fee(last_ts + elapsed_periods * MANAGEMENT_FEE_TICK_MS, daily_rate);
+                if fee > 0.0 {
+                    charges.push((instrument.quote.clone(), fee));
+                }
+            }
+        }
+
+        let mut total_fee = 0.0;
+        for (token, fee) in charges {
+            let balance = self.apply_balance_delta(&token, BalanceDelta::new(-fee, -fee));
+            total_fee += fee;
+            if let Err(err) = self.account_event_tx.send(AccountEvent { exchange_timestamp: now,
+                                                                        exchange: Exchange::Hourglass,
+                                                                        kind: AccountEventKind::Balance(TokenBalance::new(token, balance)) })
+            {
+                warn!("Client offline - Failed to send AccountEvent::Balance: {:?}", err);
+            }
+        }
+        total_fee
+    }
+
+    /// 回放结束时的账户收尾：
+    ///
+    /// 1. 执行所有尚未生效的延迟撤单；
+    /// 2. 按各金融工具的最新成交价重新标记永续与交割合约持仓的未实现盈亏；
+    /// 3. 发送最终的持仓与余额快照事件，使事件消费者拿到结束时的完整状态。
+    pub async fn finalize_run(&mut self)
+    {
+        self.process_due_cancels(i64::MAX).await;
+
+        let latest_prices: HashMap<Instrument, f64> = self.single_level_order_book
+                                                          .lock()
+                                                          .await
+                                                          .iter()
+                                                          .filter(|(_, book)| book.latest_price > 0.0)
+                                                          .map(|(instrument, book)| (instrument.clone(), book.latest_price))
+                                                          .collect();
+        mark_to_latest_prices(&self.positions.perpetual_pos_long, |p| &mut p.meta, &latest_prices).await;
+        mark_to_latest_prices(&self.positions.perpetual_pos_short, |p| &mut p.meta, &latest_prices).await;
+        mark_to_latest_prices(&self.positions.futures_pos_long, |p| &mut p.meta, &latest_prices).await;
+        mark_to_latest_prices(&self.positions.futures_pos_short, |p| &mut p.meta, &latest_prices).await;
+
+        let exchange_timestamp = self.exchange_timestamp.load(Ordering::SeqCst);
+        for kind in [AccountEventKind::Positions(Box::new(self.positions.snapshot().await)), AccountEventKind::Balances(self.get_balances().await)] {
+            if let Err(err) = self.account_event_tx.send(AccountEvent { exchange_timestamp,
+                                                                        exchange: Exchange::Hourglass,
+                                                                        kind })
+            {
+                warn!("Client offline - Failed to send final account snapshot: {:?}", err);
+            }
+        }
+    }
+
+    /// 对某个永续合约的持仓结算一次资金费（一个资金费周期）。
+    ///
+    /// 每个持仓的资金费为 `current_size * contract_multiplier * mark_price * funding_rate`，
+    /// 资金费率为正时多头支付、空头收取，为负时反之。资金费计入报价货币余额与持仓的 `realised_pnl`、`current_fees_total`，
+    /// 并单独累计到 `PositionMeta::funding_pnl`，见 [`PositionMeta::record_funding`]。返回账户本次净收取的资金费（支付为负）。
+    pub async fn settle_funding(&mut self, instrument: &Instrument, funding_rate: f64, mark_price: f64) -> f64
+    {
+        let now = self.exchange_timestamp.load(Ordering::SeqCst);
+        let mut net_funding = 0.0;
+        for (positions, sign) in [(&self.positions.perpetual_pos_long, -1.0), (&self.positions.perpetual_pos_short, 1.0)] {
+            if let Some(position) = positions.write().await.get_mut(instrument) {
+                let meta = &mut position.meta;
+                let amount = sign * meta.current_size * meta.contract_multiplier * mark_price * funding_rate;
+                meta.record_funding(amount, now);
+                net_funding += amount;
+            }
+        }
+
+        if net_funding != 0.0 {
+            let token = instrument.quote.clone();
+            let balance = self.apply_balance_delta(&token, BalanceDelta::new(net_funding, net_funding));
+            if let Err(err) = self.account_event_tx.send(AccountEvent { exchange_timestamp: now,
+                                                                        exchange: Exchange::Hourglass,
+                                                                        kind: AccountEventKind::Balance(TokenBalance::new(token, balance)) })
+            {
+                warn!("Client offline - Failed to send AccountEvent::Balance: {:?}", err);
+            }
+        }
+        net_funding
+    }
+
+    /// 按 `AccountConfig::funding_interval_ms` 对所有永续合约持仓结算资金费，费率取 `AccountConfig::funding_rate`，
+    /// 返回账户本次净收取的资金费（支付为负）。未配置结算周期时不做任何处理。
+    ///
+    /// 结算时点为周期的整数倍（8 小时周期即 UTC 0 点、8 点、16 点）。账户第一次看到的时间戳只用于确定所在的周期，
+    /// 之后每跨过一个结算时点按 [`Self::settle_funding`] 结算一次，行情间隔跨过多个时点时逐个补结。
+    /// 标记价格取最新成交价，尚无行情时取持仓的最新价格或开仓均价。
+    pub async fn apply_funding(&mut self, exchange_ts: i64) -> f64
+    {
+        let Some(interval) = self.config.funding_interval_ms.filter(|interval| *interval > 0)
+        else {
+            return 0.0;
+        };
+        let funding_ts = exchange_ts.div_euclid(interval) * interval;
+        let last_funding_ts = *self.last_funding_ts.get_or_insert(funding_ts);
+        if funding_ts <= last_funding_ts {
+            return 0.0;
+        }
+        self.last_funding_ts = Some(funding_ts);
+
+        let mut mark_prices = HashMap::new();
+        for positions in [&self.positions.perpetual_pos_long, &self.positions.perpetual_pos_short] {
+            for (instrument, position) in positions.read().await.iter() {
+                let meta = &position.meta;
+                let price = if meta.current_symbol_price > 0.0 { meta.current_symbol_price } else { meta.current_avg_price };
+                mark_prices.entry(instrument.clone()).or_insert(price);
+            }
+        }
+        {
+            let order_books = self.single_level_order_book.lock().await;
+            for (instrument, mark_price) in mark_prices.iter_mut() {
+                if let Some(order_book) = order_books.get(instrument).filter(|order_book| order_book.latest_price > 0.0) {
+                    *mark_price = order_book.latest_price;
+                }
+            }
+        }
+
+        let mut net_funding = 0.0;
+        for _ in 0..(funding_ts - last_funding_ts) / interval {
+            for (instrument, mark_price) in &mark_prices {
+                net_funding += self.settle_funding(instrument, self.config.funding_rate, *mark_price).await;
+            }
+        }
+        net_funding
+    }
+
+    /// 账户所有持仓按最新价格计算的未实现盈亏，按金融工具类别汇总，见 [`AccountPositions::unrealised_pnl_by_kind`]。
+    pub async fn total_unrealised_pnl(&self) -> PnlBreakdown
+    {
+        self.positions.unrealised_pnl_by_kind().await
+    }
+
+    /// 账户所有持仓的已实现盈亏，按金融工具类别汇总，见 [`AccountPositions::realised_pnl_by_kind`]。
+    pub async fn total_realised_pnl(&self) -> PnlBreakdown
+    {
+        self.positions.realised_pnl_by_kind().await
+    }
+
+    /// 成交的名义价值，即 `price * size * contract_multiplier`。
+    pub fn trade_notional(&self, trade: &ClientTrade) -> f64
+    {
+        trade.price * trade.size * self.config.contract_multiplier(&trade.instrument)
+    }
+
+    /// 通过配置中的别名表把市场成交的符号解析为规范的 [`Instrument`]。
+    pub fn resolve_market_instrument(&self, trade: &MarketTrade) -> Result<Instrument, ExchangeError>
+    {
+        Instrument::normalize(&trade.symbol, trade.parse_kind(), &self.config.instrument_aliases)
+    }
+
     /// NOTE 现货等一些金融工具是否不支持这些订单指令？？？？
     pub fn validate_order_instruction(kind: OrderInstruction) -> Result<(), ExchangeError>
     {
@@ -486,11 +1107,33 @@ impl HourglassAccount
             | OrderInstruction::FillOrKill
             | OrderInstruction::PostOnlyLimit
             | OrderInstruction::GoodTilCancelled
+            | OrderInstruction::GoodTilTime { .. }
             | OrderInstruction::Cancel => Ok(()), /* NOTE 不同交易所支持的订单种类不同，如有需要过滤的OrderKind变种，我们要在此处特殊设计
                                                    * | unsupported => Err(ExecutionError::UnsupportedOrderKind(unsupported)), */
         }
     }
 
+    /// 按订单指令检查数量与价格。
+    ///
+    /// 所有订单的数量都必须是有限正数；市价单按盘口成交，本身不带有效价格，因此不检查价格，
+    /// 其他指令的价格同样必须是有限正数。零、负数与 NaN 都会在进入撮合前被拒绝，避免后续的仓位和盈亏计算出现 NaN。
+    pub fn validate_order_size_and_price(order: &Order<RequestOpen>) -> Result<(), ExchangeError>
+    {
+        let size = order.state.size;
+        if !size.is_finite() || size <= 0.0 {
+            return Err(ExchangeError::InvalidOrder(format!("{} order size must be a positive finite number, got {}", order.instruction, size)));
+        }
+
+        if order.instruction != OrderInstruction::Market {
+            let price = order.state.price;
+            if !price.is_finite() || price <= 0.0 {
+                return Err(ExchangeError::InvalidOrder(format!("{} order price must be a positive finite number, got {}", order.instruction, price)));
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn validate_order_request_open(order: &Order<RequestOpen>) -> Result<(), ExchangeError>
     {
         // 检查是否提供了有效的 ClientOrderId
@@ -507,15 +1150,8 @@ impl HourglassAccount
         // 检查订单类型是否合法
         HourglassAccount::validate_order_instruction(order.instruction)?;
 
-        // 检查价格是否合法（应为正数）
-        if order.state.price <= 0.0 {
-            return Err(ExchangeError::InvalidRequestOpen(format!("Invalid price: {}", order.state.price)));
-        }
-
-        // 检查数量是否合法（应为正数）
-        if order.state.size <= 0.0 {
-            return Err(ExchangeError::InvalidRequestOpen(format!("Invalid size: {}", order.state.size)));
-        }
+        // 检查数量与价格是否合法
+        HourglassAccount::validate_order_size_and_price(order)?;
 
         // 检查基础货币和报价货币是否相同
         if order.instrument.base == order.instrument.quote {
@@ -564,6 +1200,7 @@ impl HourglassAccount
             results.push(result);
         }
 
+        self.assert_invariants(&"CancelOrders").await;
         response_tx.send(results).unwrap_or(());
     }
 
@@ -597,11 +1234,61 @@ impl HourglassAccount
     /// # 锁机制
     ///
     /// * 在查找和移除订单时，使用读锁以减少写锁的持有时间，避免阻塞其他操作。
+    ///
+    /// # 撤单延迟
+    ///
+    /// 配置了 `cancel_latency_ms` 时，撤单请求不会立即移除挂单，而是在 `exchange_ts + cancel_latency_ms` 才生效，
+    /// 期间到来的市场成交仍可成交该挂单。此时返回的 `Order<Cancelled>` 只表示撤单请求已受理，
+    /// 最终结果以事件为准：撤单生效时发送 `OrdersCancelled`；若挂单在生效前已完全成交（撤单输掉竞速），只会收到 `Trade` 事件。
     pub async fn atomic_cancel(&mut self, request: Order<RequestCancel>) -> Result<Order<Cancelled>, ExchangeError>
     {
         // 验证取消请求的合法性
         Self::validate_order_request_cancel(&request)?;
 
+        if let Some(cancel_latency_ms) = self.config.cancel_latency_ms.filter(|latency| *latency > 0) {
+            return self.schedule_cancel(request, cancel_latency_ms).await;
+        }
+        self.execute_cancel(request).await
+    }
+
+    /// 受理一个带延迟的撤单请求：确认挂单存在后登记到待生效队列，生效前挂单保持不变。
+    async fn schedule_cancel(&mut self, request: Order<RequestCancel>, cancel_latency_ms: i64) -> Result<Order<Cancelled>, ExchangeError>
+    {
+        let effective_ts = self.exchange_timestamp.load(Ordering::SeqCst) + cancel_latency_ms;
+        let mut orders_guard = self.account_open_book.write().await;
+        let acknowledged = {
+            let orders = orders_guard.get_ins_orders_mut(&request.instrument)?;
+            let side_orders = match request.side {
+                | Side::Buy => &orders.bids,
+                | Side::Sell => &orders.asks,
+            };
+            Order::from(side_orders[Self::find_matching_order(side_orders, &request)?].clone())
+        };
+        info!("Cancel request accepted, effective at {}: {:?}", effective_ts, request);
+        orders_guard.schedule_cancel(request, effective_ts);
+        Ok(acknowledged)
+    }
+
+    /// 执行所有在 `now` 及之前生效的延迟撤单。
+    ///
+    /// 挂单若已在撤单生效前完全成交，则撤单输掉竞速，不再发送撤单事件。
+    pub(crate) async fn process_due_cancels(&mut self, now: i64)
+    {
+        let due_cancels = self.account_open_book.write().await.take_due_cancels(now);
+        for request in due_cancels {
+            match self.execute_cancel(request).await {
+                | Ok(_) => {}
+                | Err(ExchangeError::OrderNotFound { client_order_id, order_id }) => {
+                    info!("Cancel lost the race to a fill: cid {:?}, order id {:?}", client_order_id, order_id);
+                }
+                | Err(err) => warn!("Failed to execute delayed cancel: {:?}", err),
+            }
+        }
+    }
+
+    /// 立即移除挂单并释放冻结的余额，发送撤单与余额事件。
+    async fn execute_cancel(&mut self, request: Order<RequestCancel>) -> Result<Order<Cancelled>, ExchangeError>
+    {
         info!("Attempting to cancel order: {:?}", request);
 
         // 使用写锁获取订单簿，以允许修改
@@ -628,7 +1315,7 @@ impl HourglassAccount
         };
 
         // 处理取消订单后的余额更新
-        let balance_event = match self.apply_cancel_order_changes(&removed_order) {
+        let balance_event = match self.apply_cancel_order_changes(&removed_order).await {
             | Ok(event) => event,
             | Err(e) => {
                 info!("Failed to apply balance changes: {:?}", e);
@@ -636,6 +1323,8 @@ impl HourglassAccount
             }
         };
 
+        self.record_order_flow(OrderFlowMessage::Cancel);
+
         // 将订单从 `Order<Open>` 转换为 `Order<Cancelled>`
         let cancelled_order = Order::from(removed_order);
 
@@ -661,6 +1350,7 @@ impl HourglassAccount
         Ok(cancelled_order)
     }
 
+    /// 撤销所有挂单与尚未触发的条件单，返回撤销后的订单；条件单按登记时分配的订单ID返回。
     pub async fn cancel_orders_all(&mut self, response_tx: Sender<Result<Vec<Order<Cancelled>>, ExchangeError>>)
     {
         // 获取所有打开的订单
@@ -684,10 +1374,14 @@ impl HourglassAccount
         let (tx, rx) = oneshot::channel();
         self.cancel_orders(cancel_requests, tx).await;
 
+        // 尚未触发的条件单一并撤销，它们不冻结余额，直接从等待队列中移除
+        let cancelled_stops = std::mem::take(&mut self.account_open_book.write().await.pending_stops);
+
         // 等待取消操作完成并返回结果
         match rx.await {
             | Ok(results) => {
-                let cancelled_orders: Vec<_> = results.into_iter().collect::<Result<Vec<_>, _>>().expect("Failed to collect cancel results");
+                let mut cancelled_orders: Vec<_> = results.into_iter().collect::<Result<Vec<_>, _>>().expect("Failed to collect cancel results");
+                cancelled_orders.extend(cancelled_stops.into_iter().filter_map(StopOrder::into_cancelled));
                 response_tx.send(Ok(cancelled_orders)).unwrap_or_else(|_| {
                                                           eprintln!("Failed to send cancel_orders_all response");
                                                       });
@@ -700,20 +1394,128 @@ impl HourglassAccount
         }
     }
 
-    /// [PART 3] - [Miscellaneous]
-
-    pub(crate) fn get_exchange_ts(&self) -> Result<i64, ExchangeError>
+    /// 当某个 [`Instrument`] 的仓位完全平掉后，撤销该 [`Instrument`] 上剩余的所有 reduce-only 挂单，
+    /// 并发送 `OrdersCancelled` 事件。这样可以避免只减仓的平仓单在之后意外开出新仓位。
+    ///
+    /// 如果该 [`Instrument`] 仍有任一方向的仓位，则不做任何处理。
+    pub async fn cancel_reduce_only_orders_if_flat(&mut self, instrument: &Instrument) -> Result<Vec<Order<Cancelled>>, ExchangeError>
     {
-        // 直接访问 account 的 exchange_timestamp 字段
-        let exchange_ts = self.exchange_timestamp.load(Ordering::SeqCst);
-        Ok(exchange_ts)
+        let (long_pos, short_pos) = self.get_position_both_ways(instrument).await?;
+        if long_pos.is_some() || short_pos.is_some() {
+            return Ok(Vec::new());
+        }
+
+        self.cancel_open_orders_where(instrument, |order| order.state.reduce_only).await
+    }
+
+    /// 撤销已到达交易所（`timestamp <= arrived_by_ts`）但未完全成交的 IOC 与 FOK 挂单，并发送 `OrdersCancelled` 事件。
+    ///
+    /// IOC 订单只参与到达后的第一次撮合，无论该笔外部成交能否与之成交、是否带有主动方方向，撮合结束后剩余部分立即撤销，即先成交后撤单。
+    /// 同一批次中的多个 IOC 按提交顺序到达，同价位时先提交的先消耗该笔成交的流动性，因此部分成交的结果是可复现的。
+    /// FOK 订单同样只参与这一笔成交，但撮合时不允许部分成交，该笔成交不足以让其完全成交时整笔撤销。
+    pub async fn cancel_unfilled_immediate_or_cancel_orders(&mut self, instrument: &Instrument, arrived_by_ts: i64) -> Result<Vec<Order<Cancelled>>, ExchangeError>
+    {
+        self.cancel_open_orders_where(instrument, |order| {
+                matches!(order.instruction, OrderInstruction::ImmediateOrCancel | OrderInstruction::FillOrKill) && order.timestamp <= arrived_by_ts
+            })
+            .await
+    }
+
+    /// 按 `stale_order_policy` 撤销 [`Instrument`] 上挂单过久且远离最新成交价 `last_price` 的挂单，并发送 `OrdersCancelled` 事件。
+    ///
+    /// 未配置时不做任何事。
+    pub async fn prune_stale_orders(&mut self, instrument: &Instrument, last_price: f64) -> Result<Vec<Order<Cancelled>>, ExchangeError>
+    {
+        let Some(policy) = self.config.stale_order_policy
+        else {
+            return Ok(Vec::new());
+        };
+        let now = self.exchange_timestamp.load(Ordering::SeqCst);
+        self.cancel_open_orders_where(instrument, |order| policy.is_stale(order.timestamp, order.state.price, now, last_price)).await
+    }
+
+    /// 撤销所有金融工具上已经到期的 GTT 挂单，并发送 `OrdersCancelled` 事件。
+    ///
+    /// 模拟交易所的时钟随外部成交的时间戳推进，因此到期按 `exchange_timestamp` 而不是系统时钟判断，
+    /// 每次时间戳推进后、撮合之前调用，到期的挂单不会再参与本笔成交的撮合。
+    /// 到期的挂单由各挂单簿的到期索引取出，没有挂单到期时不访问任何挂单。
+    pub async fn cancel_expired_orders(&mut self) -> Result<Vec<Order<Cancelled>>, ExchangeError>
+    {
+        let now = self.exchange_timestamp.load(Ordering::SeqCst);
+        let (expired_orders, _) = self.account_open_book.read().await.take_expired_orders(now);
+        self.release_removed_orders(expired_orders).await
+    }
+
+    /// 从 [`Instrument`] 的挂单中取出所有满足 `should_cancel` 的订单并撤销，释放冻结余额并发送事件。其余挂单保持原有顺序。
+    async fn cancel_open_orders_where(&mut self, instrument: &Instrument, should_cancel: impl Fn(&Order<Open>) -> bool) -> Result<Vec<Order<Cancelled>>, ExchangeError>
+    {
+        let removed_orders = {
+            let orders_guard = self.account_open_book.read().await;
+            let Ok(mut orders) = orders_guard.get_ins_orders_mut(instrument)
+            else {
+                return Ok(Vec::new());
+            };
+            let (cancelled_bids, bids): (Vec<_>, Vec<_>) = std::mem::take(&mut orders.bids).into_iter().partition(&should_cancel);
+            let (cancelled_asks, asks): (Vec<_>, Vec<_>) = std::mem::take(&mut orders.asks).into_iter().partition(&should_cancel);
+            orders.bids = bids;
+            orders.asks = asks;
+            cancelled_bids.into_iter().chain(cancelled_asks).collect::<Vec<_>>()
+        };
+
+        self.release_removed_orders(removed_orders).await
+    }
+
+    /// 为已经从挂单簿中移除的挂单释放冻结余额，发送 `Balance` 与 `OrdersCancelled` 事件，返回撤销后的订单。
+    async fn release_removed_orders(&mut self, removed_orders: Vec<Order<Open>>) -> Result<Vec<Order<Cancelled>>, ExchangeError>
+    {
+        if removed_orders.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut cancelled_orders = Vec::with_capacity(removed_orders.len());
+        for removed_order in removed_orders {
+            let balance_event = self.apply_cancel_order_changes(&removed_order).await?;
+            if let Err(err) = self.send_account_event(balance_event) {
+                warn!("Client offline - Failed to send AccountEvent::Balance: {:?}", err);
+            }
+            cancelled_orders.push(Order::from(removed_order));
+        }
+
+        let cancel_event = AccountEvent { exchange_timestamp: self.exchange_timestamp.load(Ordering::SeqCst),
+                                          exchange: Exchange::Hourglass,
+                                          kind: AccountEventKind::OrdersCancelled(cancelled_orders.clone()) };
+        if let Err(err) = self.send_account_event(cancel_event) {
+            warn!("Client offline - Failed to send AccountEvent::OrdersCancelled: {:?}", err);
+        }
+
+        Ok(cancelled_orders)
+    }
+
+    /// [PART 3] - [Miscellaneous]
+
+    pub(crate) fn get_exchange_ts(&self) -> Result<i64, ExchangeError>
+    {
+        // 直接访问 account 的 exchange_timestamp 字段
+        let exchange_ts = self.exchange_timestamp.load(Ordering::SeqCst);
+        Ok(exchange_ts)
+    }
+
+    /// 用于标记余额更新时间的时钟：回测时为交易所时间戳（毫秒），使同样的行情得到同样的余额事件；实时模式下为系统时间。
+    pub(crate) fn exchange_time(&self) -> DateTime<Utc>
+    {
+        match self.config.execution_mode {
+            | HourglassMode::Backtest => DateTime::from_timestamp_millis(self.exchange_timestamp.load(Ordering::SeqCst)).unwrap_or_default(),
+            | HourglassMode::Online => Utc::now(),
+        }
     }
 
     /// 查找匹配的订单，根据 `OrderId` 和 `ClientOrderId` 匹配。
+    ///
+    /// 挂单按撮合优先级排列（队尾最先成交），多个挂单同时匹配时从队尾查找，结果是确定的。
     fn find_matching_order(orders: &[Order<Open>], request: &Order<RequestCancel>) -> Result<usize, ExchangeError>
     {
         orders.par_iter()
-              .position_any(|order| Self::order_ids_check(order, request))
+              .position_last(|order| Self::order_ids_check(order, request))
               .ok_or_else(|| ExchangeError::OrderNotFound { client_order_id: request.cid.clone(),
                                                             order_id: request.state.id.clone() })
     }
@@ -744,6 +1546,18 @@ impl HourglassAccount
     }
 }
 
+/// 把持仓的最新价格更新为 `latest_prices` 中的价格并按持仓方向重算未实现盈亏，没有价格的金融工具保持不变。
+async fn mark_to_latest_prices<T>(positions: &RwLock<HashMap<Instrument, T>>, meta: fn(&mut T) -> &mut PositionMeta, latest_prices: &HashMap<Instrument, f64>)
+{
+    for (instrument, position) in positions.write().await.iter_mut() {
+        if let Some(price) = latest_prices.get(instrument) {
+            let meta = meta(position);
+            meta.current_symbol_price = *price;
+            meta.update_unrealised_pnl();
+        }
+    }
+}
+
 pub fn respond<Response>(response_tx: Sender<Response>, response: Response)
     where Response: Debug + Send + 'static
 {
@@ -757,11 +1571,50 @@ mod tests
     use crate::{
         common::{
             instrument::kind::InstrumentKind,
-            order::{identification::OrderId, states::request_open::RequestOpen},
+            order::{
+                identification::{machine_id::generate_machine_id, OrderId},
+                states::request_open::RequestOpen,
+            },
+        },
+        common::account_positions::{
+            exited_position::PositionExit,
+            leveraged_token::{LeveragedTokenPosition, MILLIS_PER_DAY},
+            perpetual::PerpetualPositionConfig,
+            PositionMarginMode,
         },
-        test_utils::create_test_account,
+        common::{
+            order::{identification::client_order_id::ClientOrderId, OrderRole},
+            trade::ClientTradeId,
+        },
+        hourglass::account::{
+            account_config::{ContractMultiplier, FeeBasis, FilterViolationHandling, InstrumentFilters, OrderToTradeLimit, StaleOrderPolicy, WithdrawalRule},
+            account_latency::{AccountLatency, FluctuationMode},
+        },
+        hourglass::open_orders_book::OpenOrdersBook,
+        test_utils::{create_test_account, create_test_account_configuration, create_test_account_orders, create_test_future_position_with_side, create_test_order_open, create_test_perpetual_position},
     };
 
+    #[tokio::test]
+    async fn test_configured_machine_id_is_used_by_account_and_orders()
+    {
+        let mut config = create_test_account_configuration();
+        config.machine_id = Some(42);
+        let account = HourglassAccount::initiate().account_event_tx(mpsc::unbounded_channel().0)
+                                                  .config(config)
+                                                  .orders(create_test_account_orders().await)
+                                                  .balances(DashMap::new())
+                                                  .positions(AccountPositions::init())
+                                                  .closed_positions(AccountExitedPositions::init())
+                                                  .build()
+                                                  .unwrap();
+
+        assert_eq!(account.machine_id, 42);
+        let mut orders = account.account_open_book.write().await;
+        assert_eq!(orders.machine_id, 42);
+        // 订单ID中 [machine_id:10 bits] 位于第 13~22 位
+        assert_eq!((orders.order_id(1234567).0 >> 13) & 0x3FF, 42);
+    }
+
     #[tokio::test]
     async fn test_validate_order_request_open()
     {
@@ -775,7 +1628,8 @@ mod tests
                             side: Side::Buy,
                             state: RequestOpen { price: 50000.0,
                                                  size: 1.0,
-                                                 reduce_only: false } };
+                                                 reduce_only: false,
+                                                 tag: None } };
 
         assert!(HourglassAccount::validate_order_request_open(&order).is_ok());
 
@@ -784,6 +1638,111 @@ mod tests
         assert!(HourglassAccount::validate_order_request_open(&invalid_order).is_err());
     }
 
+    #[tokio::test]
+    async fn test_validate_order_size_and_price_by_instruction()
+    {
+        let order_with = |instruction: OrderInstruction, price: f64, size: f64| Order { instruction,
+                                                                                        exchange: Exchange::Hourglass,
+                                                                                        instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+                                                                                        timestamp: 1625247600000,
+                                                                                        cid: Some(ClientOrderId("validCID123".into())),
+                                                                                        side: Side::Buy,
+                                                                                        state: RequestOpen { price,
+                                                                                                             size,
+                                                                                                             reduce_only: false,
+                                                                                                             tag: None } };
+
+        // 市价单不带价格是合法的，但数量仍需为有限正数
+        assert!(HourglassAccount::validate_order_size_and_price(&order_with(OrderInstruction::Market, 0.0, 1.0)).is_ok());
+        assert!(HourglassAccount::validate_order_size_and_price(&order_with(OrderInstruction::Market, f64::NAN, 1.0)).is_ok());
+        for size in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(HourglassAccount::validate_order_size_and_price(&order_with(OrderInstruction::Market, 0.0, size)), Err(ExchangeError::InvalidOrder(_))));
+        }
+
+        // 其他订单指令的价格与数量都必须为有限正数
+        for instruction in [OrderInstruction::Limit,
+                            OrderInstruction::PostOnlyLimit,
+                            OrderInstruction::ImmediateOrCancel,
+                            OrderInstruction::FillOrKill,
+                            OrderInstruction::GoodTilCancelled,
+                            OrderInstruction::GoodTilTime { expire_ts: 1625247600000 }]
+        {
+            assert!(HourglassAccount::validate_order_size_and_price(&order_with(instruction, 16000.0, 1.0)).is_ok());
+            for (price, size) in [(0.0, 1.0), (-16000.0, 1.0), (f64::NAN, 1.0), (f64::INFINITY, 1.0), (16000.0, 0.0), (16000.0, -1.0), (16000.0, f64::NAN), (0.0, 0.0)] {
+                assert!(matches!(HourglassAccount::validate_order_size_and_price(&order_with(instruction, price, size)), Err(ExchangeError::InvalidOrder(_))),
+                        "{} order with price {} and size {} should be rejected",
+                        instruction,
+                        price,
+                        size);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_open_orders_rejects_zero_size_and_zero_price()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+
+        let request = Order { instruction: OrderInstruction::Limit,
+                              exchange: Exchange::Hourglass,
+                              instrument: Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual)),
+                              timestamp: 1625247600000,
+                              cid: Some(ClientOrderId("validCID123".into())),
+                              side: Side::Buy,
+                              state: RequestOpen { price: 16000.0,
+                                                   size: 0.1,
+                                                   reduce_only: false,
+                                                   tag: None } };
+        let zero_size = Order { state: RequestOpen { size: 0.0, ..request.state.clone() },
+                                ..request.clone() };
+        let zero_price = Order { state: RequestOpen { price: 0.0, ..request.state.clone() },
+                                 ..request.clone() };
+
+        let (tx, rx) = oneshot::channel();
+        account.open_orders(vec![zero_size, zero_price, request], tx).await.unwrap();
+        let results = rx.await.unwrap();
+        assert!(matches!(results[0], Err(ExchangeError::InvalidOrder(_))));
+        assert!(matches!(results[1], Err(ExchangeError::InvalidOrder(_))));
+        assert!(results[2].is_ok());
+
+        // 被拒绝的订单不应冻结任何余额
+        let usdt = account.balances.get(&Token::from("USDT")).unwrap();
+        assert_eq!(usdt.total - usdt.available, 16000.0 * 0.1);
+    }
+
+    #[tokio::test]
+    async fn test_open_orders_rejects_crossing_post_only_without_resting_it()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+
+        // 最优卖价 16499：16499 的 PostOnly 买单会立即成交，16400 的可以挂单
+        let post_only = |cid: &str, price| Order { instruction: OrderInstruction::PostOnlyLimit,
+                                                   exchange: Exchange::Hourglass,
+                                                   instrument: Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual)),
+                                                   timestamp: 1234567,
+                                                   cid: Some(ClientOrderId(cid.into())),
+                                                   side: Side::Buy,
+                                                   state: RequestOpen { price,
+                                                                        size: 0.1,
+                                                                        reduce_only: false,
+                                                                        tag: None } };
+        let (tx, rx) = oneshot::channel();
+        account.open_orders(vec![post_only("crossing", 16499.0), post_only("resting", 16400.0)], tx).await.unwrap();
+        let results = rx.await.unwrap();
+        assert_eq!(results[0], Err(ExchangeError::PostOnlyWouldCross { price: 16499.0, best_opposing_price: 16499.0 }));
+        assert_eq!(results[1].as_ref().unwrap().state.order_role, OrderRole::Maker);
+
+        let resting = account.account_open_book.read().await.fetch_all();
+        assert_eq!(resting.len(), 1);
+        assert_eq!(resting[0].cid, Some(ClientOrderId("resting".into())));
+        let usdt = account.balances.get(&Token::from("USDT")).unwrap();
+        assert_eq!(usdt.total - usdt.available, 16400.0 * 0.1);
+    }
+
     #[tokio::test]
     async fn test_validate_order_request_cancel()
     {
@@ -898,4 +1857,1234 @@ mod tests
         assert_eq!(usdt_balance.total, 10_000.0);
         assert_eq!(btc_balance.total, usdt_amount / btc_price);
     }
+
+    #[tokio::test]
+    async fn test_order_to_trade_ratio_enforcement()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        account.config.order_to_trade_limit = Some(OrderToTradeLimit { window_ms: 60_000, max_ratio: 2.0 });
+
+        let order = Order { instruction: OrderInstruction::Limit,
+                            exchange: Exchange::Hourglass,
+                            instrument: Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual)),
+                            timestamp: 1625247600000,
+                            cid: Some(ClientOrderId("validCID123".into())),
+                            side: Side::Buy,
+                            state: RequestOpen { price: 16000.0,
+                                                 size: 0.1,
+                                                 reduce_only: false,
+                                                 tag: None } };
+
+        // 没有成交时，前三笔报单的比值依次为 0、1、2，均未超过上限
+        for _ in 0..3 {
+            assert!(account.atomic_open(order.clone()).await.is_ok());
+        }
+        assert_eq!(account.order_to_trade_ratio(), 3.0);
+
+        // 第四笔报单时比值为 3，超过上限被拒绝
+        assert_eq!(account.atomic_open(order.clone()).await.unwrap_err(), ExchangeError::OrderToTradeRatioExceeded(3.0));
+
+        // 窗口滑过之后旧消息不再计入，报单重新被接受
+        account.exchange_timestamp.fetch_add(60_001, Ordering::SeqCst);
+        assert_eq!(account.order_to_trade_ratio(), 0.0);
+        assert!(account.atomic_open(order).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_open_requests_do_not_count_as_submissions()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+
+        let order = |price: f64, size: f64| Order { instruction: OrderInstruction::Limit,
+                                                    exchange: Exchange::Hourglass,
+                                                    instrument: Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual)),
+                                                    timestamp: 1625247600000,
+                                                    cid: Some(ClientOrderId("validCID123".into())),
+                                                    side: Side::Buy,
+                                                    state: RequestOpen { price,
+                                                                         size,
+                                                                         reduce_only: false,
+                                                                         tag: None } };
+
+        // 校验失败与余额不足的请求都被拒绝，不计入报单数
+        assert!(account.atomic_open(order(-1.0, 0.1)).await.is_err());
+        assert!(account.atomic_open(order(16000.0, 1_000.0)).await.is_err());
+        assert_eq!(account.order_flow.submissions(), 0);
+
+        assert!(account.atomic_open(order(16000.0, 0.1)).await.is_ok());
+        assert_eq!(account.order_flow.submissions(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_order_to_trade_ratio_without_limit_only_tracks()
+    {
+        let mut account = create_test_account().await;
+        account.record_order_flow(OrderFlowMessage::Submission);
+        account.record_order_flow(OrderFlowMessage::Cancel);
+        account.record_order_flow(OrderFlowMessage::Fill);
+
+        assert_eq!(account.order_flow.submissions(), 1);
+        assert_eq!(account.order_flow.cancels(), 1);
+        assert_eq!(account.order_flow.fills(), 1);
+        assert_eq!(account.order_to_trade_ratio(), 2.0);
+    }
+
+    #[tokio::test]
+    async fn test_open_rejections_are_typed()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        account.config.instrument_filters = vec![InstrumentFilters { instrument: Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual)),
+                                                                     min_notional: 1000.0,
+                                                                     lot_size: 0.0,
+                                                                     tick_size: 0.0 }];
+
+        let request = |price: f64, size: f64| Order { instruction: OrderInstruction::Limit,
+                                                      exchange: Exchange::Hourglass,
+                                                      instrument: Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual)),
+                                                      timestamp: 1625247600000,
+                                                      cid: Some(ClientOrderId("validCID123".into())),
+                                                      side: Side::Buy,
+                                                      state: RequestOpen { price,
+                                                                           size,
+                                                                           reduce_only: false,
+                                                                           tag: None } };
+
+        let (tx, rx) = oneshot::channel();
+        account.open_orders(vec![request(16000.0, 0.05), request(15000.0, 0.1), request(16000.0, 0.1)], tx).await.unwrap();
+        let results = rx.await.unwrap();
+
+        assert_eq!(results[0], Err(ExchangeError::BelowMinNotional { min: 1000.0, got: 800.0 }));
+        // 允许的价格区间为 [16499 * 0.95, 16305 * 1.05]
+        match &results[1] {
+            | Err(ExchangeError::PriceOutOfBand { price, lower, upper }) => {
+                assert_eq!(*price, 15000.0);
+                assert!((lower - 16499.0 * 0.95).abs() < 1e-9);
+                assert!((upper - 16305.0 * 1.05).abs() < 1e-9);
+            }
+            | other => panic!("Expected PriceOutOfBand, got {:?}", other),
+        }
+        assert!(results[2].is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_instrument_filters_reject_dust_and_misaligned_orders()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        account.config.instrument_filters = vec![InstrumentFilters { instrument: instrument.clone(),
+                                                                     min_notional: 100.0,
+                                                                     lot_size: 0.001,
+                                                                     tick_size: 0.5 }];
+
+        let request = |price: f64, size: f64| Order { instruction: OrderInstruction::Limit,
+                                                      exchange: Exchange::Hourglass,
+                                                      instrument: instrument.clone(),
+                                                      timestamp: 1625247600000,
+                                                      cid: Some(ClientOrderId("validCID123".into())),
+                                                      side: Side::Buy,
+                                                      state: RequestOpen { price,
+                                                                           size,
+                                                                           reduce_only: false,
+                                                                           tag: None } };
+
+        let (tx, rx) = oneshot::channel();
+        account.open_orders(vec![request(16000.0, 0.005), request(16000.3, 0.01), request(16000.0, 0.0105), request(16000.5, 0.01)], tx).await.unwrap();
+        let results = rx.await.unwrap();
+
+        // 16000 * 0.005 = 80，低于 100 的最小名义价值
+        assert_eq!(results[0], Err(ExchangeError::BelowMinNotional { min: 100.0, got: 80.0 }));
+        assert_eq!(results[1], Err(ExchangeError::PriceNotAligned { price: 16000.3, tick_size: 0.5 }));
+        assert_eq!(results[2], Err(ExchangeError::SizeNotAligned { size: 0.0105, lot_size: 0.001 }));
+        assert!(results[3].is_ok());
+        assert_eq!(account.account_open_book.read().await.fetch_all().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_instrument_filters_round_misaligned_orders_when_configured()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        account.config.filter_violation = FilterViolationHandling::Round;
+        account.config.instrument_filters = vec![InstrumentFilters { instrument: instrument.clone(),
+                                                                     min_notional: 0.0,
+                                                                     lot_size: 0.01,
+                                                                     tick_size: 0.5 }];
+
+        let mut opened = Vec::new();
+        for (side, price) in [(Side::Buy, 16000.3), (Side::Sell, 16600.3)] {
+            let open = account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                                   exchange: Exchange::Hourglass,
+                                                   instrument: instrument.clone(),
+                                                   timestamp: 1625247600000,
+                                                   cid: Some(ClientOrderId("validCID123".into())),
+                                                   side,
+                                                   state: RequestOpen { price,
+                                                                        size: 0.0179,
+                                                                        reduce_only: false,
+                                                                        tag: None } })
+                              .await
+                              .unwrap();
+            opened.push((open.state.price, open.state.size));
+        }
+
+        // 数量向下取整；买单限价向下、卖单限价向上取整，都不会更激进
+        assert_eq!(opened, vec![(16000.0, 0.01), (16600.5, 0.01)]);
+        assert!(account.check_invariants().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_max_open_orders_per_instrument()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        account.config.max_open_orders_per_instrument = Some(2);
+
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let request = Order { instruction: OrderInstruction::Limit,
+                              exchange: Exchange::Hourglass,
+                              instrument: instrument.clone(),
+                              timestamp: 1625247600000,
+                              cid: Some(ClientOrderId("validCID123".into())),
+                              side: Side::Buy,
+                              state: RequestOpen { price: 16000.0,
+                                                   size: 0.1,
+                                                   reduce_only: false,
+                                                   tag: None } };
+
+        // 第三笔挂单超过上限被拒绝
+        let (tx, rx) = oneshot::channel();
+        account.open_orders(vec![request.clone(), request.clone(), request.clone()], tx).await.unwrap();
+        let results = rx.await.unwrap();
+        assert!(results[0].is_ok());
+        assert!(results[1].is_ok());
+        assert_eq!(results[2], Err(ExchangeError::MaxOpenOrdersExceeded(2)));
+
+        // 撤单之后释放出一个挂单名额
+        let opened = results[0].clone().unwrap();
+        account.atomic_cancel(Order { instruction: OrderInstruction::Cancel,
+                                      exchange: Exchange::Hourglass,
+                                      instrument: instrument.clone(),
+                                      timestamp: 1625247600000,
+                                      cid: opened.cid.clone(),
+                                      side: Side::Buy,
+                                      state: RequestCancel { id: Some(opened.state.id.clone()) } })
+               .await
+               .unwrap();
+
+        let (tx, rx) = oneshot::channel();
+        account.open_orders(vec![request.clone(), request], tx).await.unwrap();
+        let results = rx.await.unwrap();
+        assert!(results[0].is_ok());
+        assert_eq!(results[1], Err(ExchangeError::MaxOpenOrdersExceeded(2)));
+    }
+
+    #[tokio::test]
+    async fn test_leveraged_token_management_fee_erodes_balance_over_days()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        account.config.leveraged_token_daily_fee_rate = Some(0.01);
+
+        let instrument = Instrument::new("BTC3L", "USDT", InstrumentKind::CryptoLeveragedToken);
+        let mut meta = create_test_perpetual_position(instrument.clone()).meta;
+        meta.current_size = 100.0;
+        meta.current_symbol_price = 10.0;
+        account.positions
+               .margin_pos_long
+               .write()
+               .await
+               .insert(instrument.clone(), LeveragedTokenPosition { meta, last_fee_accrual_ts: None });
+
+        let initial_total = account.get_balance(&Token::from("USDT")).unwrap().total;
+
+        // 不足一个计提周期时不计提
+        account.exchange_timestamp.store(MANAGEMENT_FEE_TICK_MS - 1, Ordering::SeqCst);
+        assert_eq!(account.accrue_leveraged_token_management_fees().await, 0.0);
+
+        // 一个半周期只计提一个周期，剩下的半个周期留到下一次
+        account.exchange_timestamp.store(MANAGEMENT_FEE_TICK_MS * 3 / 2, Ordering::SeqCst);
+        let first = account.accrue_leveraged_token_management_fees().await;
+        let one_period = 1000.0 * (1.0 - 0.99_f64.powf(MANAGEMENT_FEE_TICK_MS as f64 / MILLIS_PER_DAY as f64));
+        assert!((first - one_period).abs() < 1e-9);
+        assert_eq!(account.positions.margin_pos_long.read().await.get(&instrument).unwrap().last_fee_accrual_ts, Some(MANAGEMENT_FEE_TICK_MS));
+        assert!(matches!(event_rx.try_recv().unwrap().kind, AccountEventKind::Balance(_)));
+
+        // 之后按天计提，每次只计提到最近的整周期，三天共计提 72 个周期
+        for day in 1..=3 {
+            account.exchange_timestamp.store(day * MILLIS_PER_DAY + MANAGEMENT_FEE_TICK_MS / 2, Ordering::SeqCst);
+            account.accrue_leveraged_token_management_fees().await;
+            assert!(matches!(event_rx.try_recv().unwrap().kind, AccountEventKind::Balance(_)));
+        }
+        assert_eq!(account.positions.margin_pos_long.read().await.get(&instrument).unwrap().last_fee_accrual_ts, Some(3 * MILLIS_PER_DAY));
+
+        // 每天按 1% 的管理费衰减：第一天分成 1 个与 23 个周期两次计提，之后两天各计提一整天，共扣除约 30 USDT
+        let rest_of_first_day = 1000.0 * (1.0 - 0.99_f64.powf(23.0 / 24.0));
+        let expected = one_period + rest_of_first_day + 20.0;
+        assert!((expected - 30.0).abs() < 0.01);
+        let balance = account.get_balance(&Token::from("USDT")).unwrap();
+        assert!((initial_total - balance.total - expected).abs() < 1e-6);
+        let position = account.positions.margin_pos_long.read().await.get(&instrument).cloned().unwrap();
+        assert!((position.meta.realised_pnl + expected).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_contract_multiplier_scales_fees_margin_and_pnl()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        account.config.contract_multipliers = vec![ContractMultiplier { instrument: instrument.clone(),
+                                                                        multiplier: 100.0 }];
+
+        // 开仓冻结：0.01 张 * 16000 * 100 = 16000 USDT，超过 10000 的可用余额
+        let request = Order { instruction: OrderInstruction::Limit,
+                              exchange: Exchange::Hourglass,
+                              instrument: instrument.clone(),
+                              timestamp: 1625247600000,
+                              cid: Some(ClientOrderId("validCID123".into())),
+                              side: Side::Buy,
+                              state: RequestOpen { price: 16000.0,
+                                                   size: 0.01,
+                                                   reduce_only: false,
+                                                   tag: None } };
+        let (tx, rx) = oneshot::channel();
+        account.open_orders(vec![request], tx).await.unwrap();
+        assert_eq!(rx.await.unwrap()[0],
+                   Err(ExchangeError::InsufficientBalance { token: Token::from("USDT"),
+                                                            required: 16000.0,
+                                                            available: 10000.0 }));
+
+        // 手续费：16000 * 0.01 * 0.001 (maker) * 100 = 16
+        let mut resting = create_test_order_open(Side::Buy, 16000.0, 0.01);
+        resting.timestamp = 0;
+        resting.state.order_role = OrderRole::Maker;
+        account.account_open_book.read().await.get_ins_orders_mut(&instrument).unwrap().add_order_open(resting);
+        let market_trade = MarketTrade { exchange: "binance-futures".to_string(),
+                                         symbol: "ETHUSDT".to_string(),
+                                         side: "sell".to_string(),
+                                         price: 15999.0,
+                                         timestamp: 1625247600000,
+                                         amount: 1.0 };
+        let fills = account.match_orders(&market_trade).await.unwrap();
+        assert_eq!(fills.len(), 1);
+        assert!((fills[0].fees - 16.0).abs() < 1e-9);
+
+        // 保证金：2 张 * 100 * 100 / 1 倍杠杆 = 20000
+        account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(),
+                                                                         PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                                                                                   leverage: 1.0,
+                                                                                                   position_direction_mode: PositionDirectionMode::Net });
+        let trade = ClientTrade { exchange: Exchange::Hourglass,
+                                  timestamp: 1625247600000,
+                                  trade_id: ClientTradeId(1),
+                                  order_id: Some(OrderId(1)),
+                                  cid: None,
+                                  instrument: instrument.clone(),
+                                  side: Side::Buy,
+                                  price: 100.0,
+                                  size: 2.0,
+                                  fees: 0.0,
+                                  tag: None };
+        let margin_before = account.account_margin.load(Ordering::SeqCst);
+        account.update_position_from_client_trade(trade).await.unwrap();
+        assert_eq!(account.account_margin.load(Ordering::SeqCst) - margin_before, 20000.0);
+
+        // 盈亏：(110 - 100) * 2 张 * 100 = 2000
+        let mut meta = account.positions.perpetual_pos_long.read().await.get(&instrument).unwrap().meta.clone();
+        assert_eq!(meta.contract_multiplier, 100.0);
+        meta.current_symbol_price = 110.0;
+        meta.update_unrealised_pnl();
+        assert_eq!(meta.unrealised_pnl, 2000.0);
+        assert_eq!(PositionExit::from_position_meta(&meta, None).realised_pnl, 2000.0);
+
+        // 名义敞口同样按乘数计算
+        let exposures = account.positions.net_exposure_by_base().await;
+        assert_eq!(exposures[&Token::from("ETH")], 2.0 * 100.0 * 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_finalize_run_marks_short_positions_by_side()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        let perpetual = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let future = Instrument::from(("ETH", "USDT", InstrumentKind::Future));
+
+        // 空仓均价 16000，回放结束时的最新成交价为 15000
+        let mut perpetual_short = create_test_perpetual_position(perpetual.clone());
+        perpetual_short.meta.side = Side::Sell;
+        perpetual_short.meta.current_avg_price = 16000.0;
+        account.positions.perpetual_pos_short.write().await.insert(perpetual.clone(), perpetual_short);
+        let mut future_short = create_test_future_position_with_side(future.clone(), Side::Sell);
+        future_short.meta.current_avg_price = 16000.0;
+        future_short.meta.current_size = 2.0;
+        account.positions.futures_pos_short.write().await.insert(future.clone(), future_short);
+        {
+            let mut order_books = account.single_level_order_book.lock().await;
+            order_books.get_mut(&perpetual).unwrap().latest_price = 15000.0;
+            order_books.insert(future.clone(),
+                               SingleLevelOrderBook { latest_bid: 14990.0,
+                                                      latest_ask: 15010.0,
+                                                      latest_price: 15000.0 });
+        }
+
+        account.finalize_run().await;
+
+        // 价格下跌时空仓的未实现盈亏为正
+        assert_eq!(account.positions.perpetual_pos_short.read().await[&perpetual].meta.unrealised_pnl, 1000.0);
+        assert_eq!(account.positions.futures_pos_short.read().await[&future].meta.unrealised_pnl, 2000.0);
+    }
+
+    #[tokio::test]
+    async fn test_net_mode_opposite_order_rejected_or_netted_against_position()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        for behavior in [NetModeOppositeBehavior::Reject, NetModeOppositeBehavior::Reduce] {
+            let mut account = create_test_account().await;
+            let (event_tx, _event_rx) = mpsc::unbounded_channel();
+            account.account_event_tx = event_tx;
+            account.config.net_mode_opposite_behavior = behavior;
+
+            // 已持有 1 张多仓
+            let mut position = create_test_perpetual_position(instrument.clone());
+            position.meta.current_avg_price = 16000.0;
+            position.pos_config.position_direction_mode = PositionDirectionMode::Net;
+            account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(), position.pos_config.clone());
+            account.positions.perpetual_pos_long.write().await.insert(instrument.clone(), position);
+
+            // 非 reduce_only 的卖单与多仓方向相反
+            let request = Order { instruction: OrderInstruction::Limit,
+                                  exchange: Exchange::Hourglass,
+                                  instrument: instrument.clone(),
+                                  timestamp: 1625247600000,
+                                  cid: Some(ClientOrderId("netOpposite".into())),
+                                  side: Side::Sell,
+                                  state: RequestOpen { price: 16400.0,
+                                                       size: 0.5,
+                                                       reduce_only: false,
+                                                       tag: None } };
+            let (tx, rx) = oneshot::channel();
+            account.open_orders(vec![request], tx).await.unwrap();
+            let result = rx.await.unwrap().remove(0);
+
+            match behavior {
+                | NetModeOppositeBehavior::Reject => {
+                    assert_eq!(result, Err(ExchangeError::InvalidDirection));
+                    assert!(account.account_open_book.read().await.fetch_all().is_empty());
+                }
+                | NetModeOppositeBehavior::Reduce => {
+                    assert!(result.is_ok());
+                    let market_trade = MarketTrade { exchange: "binance-futures".to_string(),
+                                                     symbol: "ETHUSDT".to_string(),
+                                                     side: "buy".to_string(),
+                                                     price: 16500.0,
+                                                     timestamp: 1625247600000 + 1_000,
+                                                     amount: 1.0 };
+                    let fills = account.match_orders(&market_trade).await.unwrap();
+                    assert_eq!(fills.len(), 1);
+
+                    // 成交与多仓轧差，只减少多仓，不产生空仓
+                    assert_eq!(account.positions.perpetual_pos_long.read().await.get(&instrument).unwrap().meta.current_size, 0.5);
+                    assert!(account.positions.perpetual_pos_short.read().await.get(&instrument).is_none());
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_competing_ioc_orders_in_one_batch_fill_in_submission_order()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let mut outcomes = Vec::new();
+        for _ in 0..3 {
+            let mut account = create_test_account().await;
+            let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+            account.account_event_tx = event_tx;
+
+            // 同一批次中两个同价位的 IOC 买单
+            let requests = ["iocFirst", "iocSecond"].map(|cid| Order { instruction: OrderInstruction::ImmediateOrCancel,
+                                                                         exchange: Exchange::Hourglass,
+                                                                         instrument: instrument.clone(),
+                                                                         timestamp: 1625247600000,
+                                                                         cid: Some(ClientOrderId(cid.into())),
+                                                                         side: Side::Buy,
+                                                                         state: RequestOpen { price: 16500.0,
+                                                                                              size: 0.25,
+                                                                                              reduce_only: false,
+                                                                                              tag: None } });
+            let (tx, rx) = oneshot::channel();
+            account.open_orders(requests.to_vec(), tx).await.unwrap();
+            assert!(rx.await.unwrap().iter().all(Result::is_ok));
+
+            // 外部主动卖单只提供 0.375 的流动性，不足以让两个 IOC 都完全成交
+            let market_trade = MarketTrade { exchange: "binance-futures".to_string(),
+                                             symbol: "ETHUSDT".to_string(),
+                                             side: "sell".to_string(),
+                                             price: 16400.0,
+                                             timestamp: 1625247600000 + 1_000,
+                                             amount: 0.375 };
+            let fills = account.match_orders(&market_trade).await.unwrap();
+
+            // 未成交的剩余部分在撮合后被撤销
+            assert!(account.account_open_book.read().await.fetch_all().is_empty());
+            let mut cancelled = Vec::new();
+            while let Ok(event) = event_rx.try_recv() {
+                if let AccountEventKind::OrdersCancelled(orders) = event.kind {
+                    cancelled.extend(orders.into_iter().map(|order| order.cid));
+                }
+            }
+            outcomes.push((fills.into_iter().map(|fill| (fill.cid, fill.size)).collect::<Vec<_>>(), cancelled));
+        }
+
+        let first = Some(ClientOrderId("iocFirst".into()));
+        let second = Some(ClientOrderId("iocSecond".into()));
+        assert_eq!(outcomes[0], (vec![(first, 0.25), (second.clone(), 0.125)], vec![second]));
+        assert!(outcomes.iter().all(|outcome| outcome == &outcomes[0]));
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_ioc_cancelled_after_first_matching_pass_without_aggressor_side()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let mut account = create_test_account().await;
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+
+        // 带延迟的 IOC 买单，开单时尚未到达交易所，先进入挂单簿
+        let request = Order { instruction: OrderInstruction::ImmediateOrCancel,
+                              exchange: Exchange::Hourglass,
+                              instrument: instrument.clone(),
+                              timestamp: 1625247600000,
+                              cid: Some(ClientOrderId("iocInFlight".into())),
+                              side: Side::Buy,
+                              state: RequestOpen { price: 16300.0,
+                                                   size: 0.25,
+                                                   reduce_only: false,
+                                                   tag: None } };
+        let (tx, rx) = oneshot::channel();
+        account.open_orders(vec![request], tx).await.unwrap();
+        assert!(rx.await.unwrap().iter().all(Result::is_ok));
+        assert_eq!(account.account_open_book.read().await.fetch_all().len(), 1);
+
+        // 到达后的第一笔外部成交没有主动方方向，也不与之交叉
+        let market_trade = MarketTrade { exchange: "binance-futures".to_string(),
+                                         symbol: "ETHUSDT".to_string(),
+                                         side: String::new(),
+                                         price: 16400.0,
+                                         timestamp: 1625247600000 + 1_000,
+                                         amount: 1.0 };
+        assert!(market_trade.aggressor_side().is_none());
+        assert!(account.match_orders(&market_trade).await.unwrap().is_empty());
+
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+        let mut cancelled = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            if let AccountEventKind::OrdersCancelled(orders) = event.kind {
+                cancelled.extend(orders.into_iter().map(|order| order.cid));
+            }
+        }
+        assert_eq!(cancelled, vec![Some(ClientOrderId("iocInFlight".into()))]);
+    }
+
+    #[tokio::test]
+    async fn test_fill_or_kill_never_partially_filled_by_market_trade()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let mut fills = Vec::new();
+        for amount in [0.2, 0.3] {
+            let mut account = create_test_account().await;
+            let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+            account.account_event_tx = event_tx;
+
+            account.atomic_open(Order { instruction: OrderInstruction::FillOrKill,
+                                        exchange: Exchange::Hourglass,
+                                        instrument: instrument.clone(),
+                                        timestamp: 1625247600000,
+                                        cid: Some(ClientOrderId("fok".into())),
+                                        side: Side::Buy,
+                                        state: RequestOpen { price: 16500.0,
+                                                             size: 0.25,
+                                                             reduce_only: false,
+                                                             tag: None } })
+                   .await
+                   .unwrap();
+            let market_trade = MarketTrade { exchange: "binance-futures".to_string(),
+                                             symbol: "ETHUSDT".to_string(),
+                                             side: "sell".to_string(),
+                                             price: 16400.0,
+                                             timestamp: 1625247600000 + 1_000,
+                                             amount };
+            let trades = account.match_orders(&market_trade).await.unwrap();
+
+            // 无论是否成交，FOK 都不会留在订单簿中
+            assert!(account.account_open_book.read().await.fetch_all().is_empty());
+            let cancelled = std::iter::from_fn(|| event_rx.try_recv().ok()).any(|event| matches!(event.kind, AccountEventKind::OrdersCancelled(_)));
+            fills.push((trades.into_iter().map(|trade| trade.size).collect::<Vec<_>>(), cancelled));
+        }
+
+        // 0.2 的流动性不足以成交 0.25，整笔撤销；0.3 时完全成交
+        assert_eq!(fills, vec![(vec![], true), (vec![0.25], false)]);
+    }
+
+    #[tokio::test]
+    async fn test_good_til_time_order_cancelled_once_trade_clock_passes_expiry()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        let gtt = |expire_ts| Order { instruction: OrderInstruction::GoodTilTime { expire_ts },
+                                      exchange: Exchange::Hourglass,
+                                      instrument: Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual)),
+                                      timestamp: 1234567,
+                                      cid: Some(ClientOrderId("gtt".into())),
+                                      side: Side::Buy,
+                                      state: RequestOpen { price: 16300.0,
+                                                           size: 0.1,
+                                                           reduce_only: false,
+                                                           tag: None } };
+        let trade_at = |timestamp| MarketTrade { exchange: "binance-futures".to_string(),
+                                                 symbol: "ETHUSDT".to_string(),
+                                                 side: "buy".to_string(),
+                                                 price: 16450.0,
+                                                 timestamp,
+                                                 amount: 1.0 };
+
+        // 提交时已经到期的 GTT 直接拒绝
+        assert!(matches!(account.atomic_open(gtt(1234567)).await, Err(ExchangeError::InvalidOrder(_))));
+
+        // 同一金融工具上的两笔 GTT 同时到期，一笔更晚到期
+        account.atomic_open(gtt(1240000)).await.unwrap();
+        account.atomic_open(gtt(1240000)).await.unwrap();
+        account.atomic_open(gtt(1250000)).await.unwrap();
+        account.handle_trade_data(&trade_at(1239999)).await.unwrap();
+        assert_eq!(account.account_open_book.read().await.fetch_all().len(), 3);
+
+        // 成交时间戳推进到到期时间后，到期的挂单全部被撤销并释放冻结额
+        while event_rx.try_recv().is_ok() {}
+        account.handle_trade_data(&trade_at(1240000)).await.unwrap();
+        let resting = account.account_open_book.read().await.fetch_all();
+        assert_eq!(resting.len(), 1);
+        assert_eq!(resting[0].instruction, OrderInstruction::GoodTilTime { expire_ts: 1250000 });
+        let cancelled: Vec<_> = std::iter::from_fn(|| event_rx.try_recv().ok()).filter_map(|event| match event.kind {
+                                                                                     | AccountEventKind::OrdersCancelled(orders) => Some(orders.len()),
+                                                                                     | _ => None,
+                                                                                 })
+                                                                                 .collect();
+        assert_eq!(cancelled, vec![2]);
+        assert!(account.check_invariants().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fee_basis_percent_notional_vs_per_contract()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let market_trade = MarketTrade { exchange: "binance-futures".to_string(),
+                                         symbol: "ETHUSDT".to_string(),
+                                         side: "sell".to_string(),
+                                         price: 15999.0,
+                                         timestamp: 1625247600000,
+                                         amount: 1.0 };
+
+        let mut fees = Vec::new();
+        for fee_basis in [FeeBasis::PercentNotional, FeeBasis::PerContract(0.75)] {
+            let mut account = create_test_account().await;
+            account.account_event_tx = mpsc::unbounded_channel().0;
+            account.config.fees_book.get_mut(&InstrumentKind::Perpetual).unwrap().fee_basis = fee_basis;
+
+            // 同一笔成交：0.5 张 maker 买单在 16000 成交
+            let mut resting = create_test_order_open(Side::Buy, 16000.0, 0.5);
+            resting.timestamp = 0;
+            resting.state.order_role = OrderRole::Maker;
+            account.account_open_book.read().await.get_ins_orders_mut(&instrument).unwrap().add_order_open(resting);
+            let fills = account.match_orders(&market_trade).await.unwrap();
+            assert_eq!(fills.len(), 1);
+            fees.push(fills[0].fees);
+        }
+
+        // 按比例：16000 * 0.5 * 0.001 = 8；按张：0.75 * 0.5 = 0.375，与价格无关
+        assert!((fees[0] - 8.0).abs() < 1e-9);
+        assert!((fees[1] - 0.375).abs() < 1e-9);
+
+        // 按张收取时不受合约乘数影响
+        assert_eq!(FeeBasis::PerContract(0.75).fee(8.0, 0.5, 100.0), 0.375);
+        assert_eq!(FeeBasis::PercentNotional.fee(8.0, 0.5, 100.0), 800.0);
+    }
+
+    #[tokio::test]
+    async fn test_delayed_cancel_races_incoming_fill()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let submitted_ts = 1625247600000;
+        // 撤单在提交后 100ms 生效，分别在生效前与恰好生效时到来一笔外部主动卖单
+        let mut outcomes = Vec::new();
+        for trade_delay in [99, 100] {
+            let mut account = create_test_account().await;
+            account.config.cancel_latency_ms = Some(100);
+            let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+            account.account_event_tx = event_tx;
+            account.exchange_timestamp.store(submitted_ts, Ordering::SeqCst);
+
+            let open = account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                                   exchange: Exchange::Hourglass,
+                                                   instrument: instrument.clone(),
+                                                   timestamp: submitted_ts,
+                                                   cid: Some(ClientOrderId("racing".into())),
+                                                   side: Side::Buy,
+                                                   state: RequestOpen { price: 16400.0,
+                                                                        size: 0.25,
+                                                                        reduce_only: false,
+                                                                        tag: None } })
+                              .await
+                              .unwrap();
+            account.atomic_cancel(Order { instruction: OrderInstruction::Limit,
+                                          exchange: Exchange::Hourglass,
+                                          instrument: instrument.clone(),
+                                          timestamp: submitted_ts,
+                                          cid: open.cid.clone(),
+                                          side: Side::Buy,
+                                          state: RequestCancel { id: Some(open.state.id.clone()) } })
+                   .await
+                   .unwrap();
+            // 撤单尚未生效，挂单仍在订单簿中
+            assert_eq!(account.account_open_book.read().await.fetch_all().len(), 1);
+
+            let market_trade = MarketTrade { exchange: "binance-futures".to_string(),
+                                             symbol: "ETHUSDT".to_string(),
+                                             side: "sell".to_string(),
+                                             price: 16300.0,
+                                             timestamp: submitted_ts + trade_delay,
+                                             amount: 1.0 };
+            account.handle_trade_data(&market_trade).await.unwrap();
+            // 随后的行情使所有撤单都已生效
+            account.process_due_cancels(submitted_ts + 1_000).await;
+            assert!(account.account_open_book.read().await.fetch_all().is_empty());
+
+            let (mut filled, mut cancelled) = (0.0, 0);
+            while let Ok(event) = event_rx.try_recv() {
+                match event.kind {
+                    | AccountEventKind::Trade(trade) => filled += trade.size,
+                    | AccountEventKind::OrdersCancelled(orders) => cancelled += orders.len(),
+                    | _ => {}
+                }
+            }
+            outcomes.push((filled, cancelled));
+        }
+
+        // 生效前到来的成交赢得竞速：发送成交而不是撤单；恰好生效时撤单先于成交
+        assert_eq!(outcomes, vec![(0.25, 0), (0.0, 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_after_partial_fill_releases_only_unfilled_remainder()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let mut account = create_test_account().await;
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        let available_before = account.get_balance(&Token::from("USDT")).unwrap().available;
+
+        let open = account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                               exchange: Exchange::Hourglass,
+                                               instrument: instrument.clone(),
+                                               timestamp: 1625247600000,
+                                               cid: Some(ClientOrderId("partial".into())),
+                                               side: Side::Buy,
+                                               state: RequestOpen { price: 16400.0,
+                                                                    size: 0.5,
+                                                                    reduce_only: false,
+                                                                    tag: None } })
+                          .await
+                          .unwrap();
+        account.handle_trade_data(&MarketTrade { exchange: "binance-futures".to_string(),
+                                                 symbol: "ETHUSDT".to_string(),
+                                                 side: "sell".to_string(),
+                                                 price: 16300.0,
+                                                 timestamp: 1625247600001,
+                                                 amount: 0.2 })
+               .await
+               .unwrap();
+        account.atomic_cancel(Order { instruction: OrderInstruction::Limit,
+                                      exchange: Exchange::Hourglass,
+                                      instrument: instrument.clone(),
+                                      timestamp: 1625247600002,
+                                      cid: open.cid.clone(),
+                                      side: Side::Buy,
+                                      state: RequestCancel { id: Some(open.state.id.clone()) } })
+               .await
+               .unwrap();
+
+        let (mut filled_cost, mut last_balance) = (0.0, None);
+        while let Ok(event) = event_rx.try_recv() {
+            match event.kind {
+                | AccountEventKind::Trade(trade) => filled_cost += trade.price * trade.size + trade.fees,
+                | AccountEventKind::Balance(token_balance) => last_balance = Some(token_balance.balance),
+                | _ => {}
+            }
+        }
+
+        // 撤单只释放未成交的 0.3，已成交部分的保证金与手续费不退回；撤单后发送的余额事件与账户余额一致
+        assert!(filled_cost > 0.0);
+        let available_after = account.get_balance(&Token::from("USDT")).unwrap().available;
+        assert!((available_after - (available_before - filled_cost)).abs() < 1e-9);
+        assert_eq!(last_balance.unwrap().available, available_after);
+        assert!(account.check_invariants().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_trades_and_positions_return_snapshots()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(),
+                                                                         PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                                                                                   leverage: 1.0,
+                                                                                                   position_direction_mode: PositionDirectionMode::Net });
+
+        let fill = |cid: &str, timestamp: i64| Order { instruction: OrderInstruction::Limit,
+                                                       exchange: Exchange::Hourglass,
+                                                       instrument: instrument.clone(),
+                                                       timestamp,
+                                                       cid: Some(ClientOrderId(cid.into())),
+                                                       side: Side::Buy,
+                                                       state: RequestOpen { price: 16400.0,
+                                                                            size: 0.1,
+                                                                            reduce_only: false,
+                                                                            tag: None } };
+        let sell_trade = |timestamp: i64| MarketTrade { exchange: "binance-futures".to_string(),
+                                                        symbol: "ETHUSDT".to_string(),
+                                                        side: "sell".to_string(),
+                                                        price: 16300.0,
+                                                        timestamp,
+                                                        amount: 0.1 };
+
+        account.atomic_open(fill("first", 1625247600000)).await.unwrap();
+        account.handle_trade_data(&sell_trade(1625247600001)).await.unwrap();
+        let positions = account.fetch_positions().await;
+        let trades = account.fetch_trades(&instrument);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].cid, Some(ClientOrderId("first".into())));
+        assert!(account.fetch_trades(&Instrument::from(("BTC", "USDT", InstrumentKind::Perpetual))).is_empty());
+
+        // 之后的成交追加到历史末尾，但不会改变已经取得的仓位快照
+        account.atomic_open(fill("second", 1625247600002)).await.unwrap();
+        account.handle_trade_data(&sell_trade(1625247600003)).await.unwrap();
+        let cids: Vec<_> = account.fetch_trades(&instrument).into_iter().map(|trade| trade.cid.unwrap().0).collect();
+        assert_eq!(cids, vec!["first", "second"]);
+        assert!((positions.perpetual_pos_long[&instrument].meta.current_size - 0.1).abs() < 1e-9);
+        assert!((account.fetch_positions().await.perpetual_pos_long[&instrument].meta.current_size - 0.2).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_option_order_and_fill_are_rejected_without_panicking()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        let option = Instrument::from(("ETH", "USDT", InstrumentKind::CryptoOption));
+        account.account_open_book.write().await.instrument_orders_map.insert(option.clone(), OpenOrdersBook::default());
+        account.single_level_order_book.lock().await.insert(option.clone(),
+                                                            SingleLevelOrderBook { latest_bid: 95.0,
+                                                                                   latest_ask: 105.0,
+                                                                                   latest_price: 100.0 });
+        let usdt_before = *account.get_balance(&Token::from("USDT")).unwrap();
+
+        let open = account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                               exchange: Exchange::Hourglass,
+                                               instrument: option.clone(),
+                                               timestamp: 1625247600000,
+                                               cid: Some(ClientOrderId("option".into())),
+                                               side: Side::Buy,
+                                               state: RequestOpen { price: 100.0,
+                                                                    size: 1.0,
+                                                                    reduce_only: false,
+                                                                    tag: None } })
+                          .await;
+        assert!(matches!(open, Err(ExchangeError::UnsupportedInstrumentKind)));
+
+        // 直接结算一笔期权成交，同样返回错误且不改变余额与仓位
+        let fill = ClientTrade { exchange: Exchange::Hourglass,
+                                 timestamp: 1625247600000,
+                                 trade_id: ClientTradeId(1),
+                                 order_id: Some(OrderId(1)),
+                                 cid: None,
+                                 instrument: option.clone(),
+                                 side: Side::Buy,
+                                 price: 100.0,
+                                 size: 1.0,
+                                 fees: 0.1,
+                                 tag: None };
+        assert!(matches!(account.process_trade(fill).await, Err(ExchangeError::UnsupportedInstrumentKind)));
+        assert_eq!(*account.get_balance(&Token::from("USDT")).unwrap(), usdt_before);
+        assert!(account.fetch_trades(&option).is_empty());
+        assert!(!std::iter::from_fn(|| event_rx.try_recv().ok()).any(|event| matches!(event.kind, AccountEventKind::Trade(_))));
+    }
+
+    #[tokio::test]
+    async fn test_funding_accumulates_separately_from_trading_pnl()
+    {
+        let mut account = create_test_account().await;
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let mut position = create_test_perpetual_position(instrument.clone());
+        position.meta.current_size = 2.0;
+        position.meta.realised_pnl = 5.0;
+        let fees_before = position.meta.current_fees_total;
+        account.positions.perpetual_pos_long.write().await.insert(instrument.clone(), position);
+
+        // 两个资金费周期：正费率时多头支付，负费率时多头收取
+        assert_eq!(account.settle_funding(&instrument, 0.001, 1000.0).await, -2.0);
+        assert_eq!(account.settle_funding(&instrument, -0.0005, 2000.0).await, 2.0);
+        assert_eq!(account.settle_funding(&instrument, 0.001, 1500.0).await, -3.0);
+
+        let long = account.positions.perpetual_pos_long.read().await;
+        let meta = &long.get(&instrument).unwrap().meta;
+        assert_eq!(meta.funding_pnl, -3.0);
+        // 资金费计入已实现盈亏与费用合计，交易盈亏不受影响
+        assert_eq!(meta.realised_pnl, 2.0);
+        assert!((meta.current_fees_total - (fees_before + 3.0)).abs() < 1e-9);
+        assert_eq!(meta.trading_pnl(), 5.0);
+        assert_eq!(account.balances.get(&Token::from("USDT")).unwrap().total, 10_000.0 - 3.0);
+
+        // 平仓记录中单独归因资金费
+        let exit = PositionExit::from_position_meta(meta, None);
+        assert_eq!(exit.funding_pnl, -3.0);
+    }
+
+    #[tokio::test]
+    async fn test_apply_funding_settles_once_per_crossed_interval()
+    {
+        const HOUR: i64 = 3_600_000;
+        let mut account = create_test_account().await;
+        account.config.funding_rate = 0.0001;
+        account.config.funding_interval_ms = Some(8 * HOUR);
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let mut long = create_test_perpetual_position(instrument.clone());
+        long.meta.current_size = 3.0;
+        long.meta.current_avg_price = 1000.0;
+        account.positions.perpetual_pos_long.write().await.insert(instrument.clone(), long);
+        let mut short = create_test_perpetual_position(instrument.clone());
+        short.meta.current_size = 1.0;
+        short.meta.current_avg_price = 1000.0;
+        account.positions.perpetual_pos_short.write().await.insert(instrument.clone(), short);
+
+        // 第一次只确定所在周期，同一周期内不结算
+        assert_eq!(account.apply_funding(HOUR).await, 0.0);
+        assert_eq!(account.apply_funding(7 * HOUR).await, 0.0);
+        // 跨过 8 点：多头支付 0.3，空头收取 0.1
+        assert!((account.apply_funding(9 * HOUR).await + 0.2).abs() < 1e-9);
+        assert_eq!(account.apply_funding(15 * HOUR).await, 0.0);
+        // 一次跨过 16 点、24 点、32 点三个结算时点
+        assert!((account.apply_funding(33 * HOUR).await + 0.6).abs() < 1e-9);
+
+        assert!((account.positions.perpetual_pos_long.read().await.get(&instrument).unwrap().meta.funding_pnl + 1.2).abs() < 1e-9);
+        assert!((account.positions.perpetual_pos_short.read().await.get(&instrument).unwrap().meta.funding_pnl - 0.4).abs() < 1e-9);
+        assert!((account.balances.get(&Token::from("USDT")).unwrap().total - (10_000.0 - 0.8)).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_withdrawal_below_minimum_rejected()
+    {
+        let mut account = create_test_account().await;
+        account.config.withdrawal_rules.push(WithdrawalRule { token: Token::from("USDT"),
+                                                              withdrawal_fee: 1.0,
+                                                              min_withdrawal: 10.0 });
+
+        let result = account.withdraw(Token::from("USDT"), 5.0);
+        assert!(matches!(result, Err(ExchangeError::BelowMinimumWithdrawal(_, 5.0, 10.0))));
+        assert_eq!(account.balances.get(&Token::from("USDT")).unwrap().total, 10000.0);
+    }
+
+    #[tokio::test]
+    async fn test_withdrawal_debits_fee_and_emits_it_with_balance()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.config.withdrawal_rules.push(WithdrawalRule { token: Token::from("USDT"),
+                                                              withdrawal_fee: 1.0,
+                                                              min_withdrawal: 10.0 });
+
+        let withdrawal = account.withdraw(Token::from("USDT"), 100.0).unwrap();
+        assert_eq!(withdrawal.fee, 1.0);
+        assert_eq!(withdrawal.balance.balance.total, 9899.0);
+        assert_eq!(withdrawal.balance.balance.available, 9899.0);
+
+        let event = account_event_rx.try_recv().unwrap();
+        assert_eq!(event.kind, AccountEventKind::Withdrawal(withdrawal));
+
+        // 余额不足以同时支付提现数量与手续费
+        assert!(matches!(account.withdraw(Token::from("USDT"), 9899.0), Err(ExchangeError::InsufficientBalance { .. })));
+        // 未配置规则的币种不收费
+        assert_eq!(account.withdraw(Token::from("ETH"), 1.0).unwrap().fee, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_dust_converts_small_balances_into_reference()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.deposit_coin(Token::from("BNB"), 0.01).unwrap();
+        account.deposit_coin(Token::from("DOGE"), 3.0).unwrap();
+        account.deposit_coin(Token::from("XYZ"), 0.5).unwrap();
+        // 有冻结余额的币种不清扫
+        account.balances.insert(Token::from("SOL"), Balance::new(0.02, 0.01));
+
+        let prices = HashMap::from([(Token::from("BNB"), 500.0), (Token::from("DOGE"), 0.1), (Token::from("ETH"), 16000.0), (Token::from("SOL"), 100.0)]);
+        let swept = account.sweep_dust(&Token::from("USDT"), &prices, 10.0).unwrap();
+
+        // BNB 价值 5、DOGE 价值 0.3 被折算；ETH 超过阈值，XYZ 没有价格
+        let swept_tokens: Vec<&str> = swept.iter().map(|balance| balance.token.as_ref()).collect();
+        assert_eq!(swept_tokens.len(), 3);
+        assert!(swept_tokens.contains(&"BNB") && swept_tokens.contains(&"DOGE"));
+        assert_eq!(swept.last().unwrap().token, Token::from("USDT"));
+        assert!((swept.last().unwrap().balance.total - 10005.3).abs() < 1e-9);
+        assert!(account.balances.get(&Token::from("BNB")).is_none());
+        assert!(account.balances.get(&Token::from("XYZ")).is_some());
+        assert!(account.balances.get(&Token::from("SOL")).is_some());
+        assert_eq!(account.balances.get(&Token::from("ETH")).unwrap().total, 10.0);
+
+        let event = account_event_rx.try_recv().unwrap();
+        assert_eq!(event.kind, AccountEventKind::Balances(swept));
+
+        // 再次清扫时没有可清扫的余额，不发送事件
+        assert!(account.sweep_dust(&Token::from("USDT"), &prices, 10.0).unwrap().is_empty());
+        assert!(account_event_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stale_orders_pruned_only_when_old_and_far_from_market()
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        account.config.stale_order_policy = Some(StaleOrderPolicy { max_age_ms: 60_000,
+                                                                    max_distance_pct: 3.0 });
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+
+        // 久且远、久但近、远但新
+        for (cid, price, timestamp) in [("old_far", 15700.0, 1_000), ("old_near", 16000.0, 1_000), ("new_far", 15700.0, 50_000)] {
+            account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                        exchange: Exchange::Hourglass,
+                                        instrument: instrument.clone(),
+                                        timestamp,
+                                        cid: Some(ClientOrderId(cid.into())),
+                                        side: Side::Buy,
+                                        state: RequestOpen { price,
+                                                             size: 0.1,
+                                                             reduce_only: false,
+                                                             tag: None } })
+                   .await
+                   .unwrap();
+        }
+        let available_before = account.balances.get(&Token::from("USDT")).unwrap().available;
+
+        // 主动买成交不会撮合买单，只触发清理
+        account.handle_trade_data(&MarketTrade { exchange: "binance-futures".to_string(),
+                                                 symbol: "ETHUSDT".to_string(),
+                                                 side: "buy".to_string(),
+                                                 price: 16300.0,
+                                                 timestamp: 70_000,
+                                                 amount: 1.0 })
+               .await
+               .unwrap();
+
+        let mut cancelled = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            if let AccountEventKind::OrdersCancelled(orders) = event.kind {
+                cancelled.extend(orders.into_iter().map(|order| order.cid.unwrap().0));
+            }
+        }
+        assert_eq!(cancelled, vec!["old_far".to_string()]);
+        assert_eq!(account.account_open_book.read().await.get_ins_orders_mut(&instrument).unwrap().num_orders(), 2);
+        assert!(account.balances.get(&Token::from("USDT")).unwrap().available > available_before);
+    }
+
+    fn reduce_only_request(side: Side, size: f64) -> Order<RequestOpen>
+    {
+        Order { instruction: OrderInstruction::Limit,
+                exchange: Exchange::Hourglass,
+                instrument: Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual)),
+                timestamp: 1625247600000,
+                cid: Some(ClientOrderId("reduceOnly".into())),
+                side,
+                state: RequestOpen { price: 16400.0,
+                                     size,
+                                     reduce_only: true,
+                                     tag: None } }
+    }
+
+    async fn open_single(account: &mut HourglassAccount, request: Order<RequestOpen>) -> Result<Order<Open>, ExchangeError>
+    {
+        let (tx, rx) = oneshot::channel();
+        account.open_orders(vec![request], tx).await.unwrap();
+        rx.await.unwrap().remove(0)
+    }
+
+    #[tokio::test]
+    async fn test_reduce_only_in_net_mode_clamped_to_position_or_rejected()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+
+        // 没有仓位时无可减少
+        assert!(matches!(open_single(&mut account, reduce_only_request(Side::Sell, 0.5)).await, Err(ExchangeError::ReduceOnlyRejected(_))));
+
+        // 持有 0.3 张多仓：卖单超出部分截断到仓位大小，买单会增加净敞口而被拒绝
+        let mut position = create_test_perpetual_position(instrument.clone());
+        position.meta.current_size = 0.3;
+        account.positions.perpetual_pos_long.write().await.insert(instrument.clone(), position);
+        let clamped = open_single(&mut account, reduce_only_request(Side::Sell, 0.5)).await.unwrap();
+        assert_eq!(clamped.state.size, 0.3);
+        assert!(clamped.state.reduce_only);
+        assert!(matches!(open_single(&mut account, reduce_only_request(Side::Buy, 0.5)).await, Err(ExchangeError::ReduceOnlyRejected(_))));
+    }
+
+    #[tokio::test]
+    async fn test_reduce_only_clamp_accounts_for_outstanding_reduce_only_orders()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+
+        // 持有 0.5 张多仓，先挂出 0.3 张 reduce_only 卖单
+        let mut position = create_test_perpetual_position(instrument.clone());
+        position.meta.current_size = 0.5;
+        account.positions.perpetual_pos_long.write().await.insert(instrument.clone(), position);
+        assert_eq!(open_single(&mut account, reduce_only_request(Side::Sell, 0.3)).await.unwrap().state.size, 0.3);
+
+        // 第二笔只能使用剩余的 0.2 张，之后仓位已被挂单占满
+        let second = open_single(&mut account, reduce_only_request(Side::Sell, 0.5)).await.unwrap();
+        assert!((second.state.size - 0.2).abs() < 1e-9);
+        assert!(matches!(open_single(&mut account, reduce_only_request(Side::Sell, 0.1)).await, Err(ExchangeError::ReduceOnlyRejected(_))));
+    }
+
+    #[tokio::test]
+    async fn test_reduce_only_in_long_short_mode_reduces_opposite_leg_only()
+    {
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        let mut account = create_test_account().await;
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        account.config.global_position_direction_mode = PositionDirectionMode::LongShort;
+
+        // 同时持有 1 张多仓与 0.2 张空仓
+        let long_position = create_test_perpetual_position(instrument.clone());
+        let mut short_position = create_test_perpetual_position(instrument.clone());
+        short_position.meta.side = Side::Sell;
+        short_position.meta.current_size = 0.2;
+        account.positions.perpetual_pos_long.write().await.insert(instrument.clone(), long_position);
+        account.positions.perpetual_pos_short.write().await.insert(instrument.clone(), short_position);
+
+        // 买单只减少空仓，多仓的存在不影响判断
+        let buy = open_single(&mut account, reduce_only_request(Side::Buy, 1.0)).await.unwrap();
+        assert_eq!(buy.state.size, 0.2);
+        let sell = open_single(&mut account, reduce_only_request(Side::Sell, 0.3)).await.unwrap();
+        assert_eq!(sell.state.size, 0.3);
+    }
+
+    #[tokio::test]
+    async fn test_order_cannot_fill_on_trade_before_latency_elapses()
+    {
+        let mut account = create_test_account().await;
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = event_tx;
+        let submitted_at = account.exchange_timestamp.load(Ordering::SeqCst);
+        let request = Order { instruction: OrderInstruction::Limit,
+                              exchange: Exchange::Hourglass,
+                              instrument: Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual)),
+                              timestamp: submitted_at,
+                              cid: Some(ClientOrderId("latency".into())),
+                              side: Side::Buy,
+                              state: RequestOpen { price: 16400.0,
+                                                   size: 0.1,
+                                                   reduce_only: false,
+                                                   tag: None } };
+
+        // 固定 50 毫秒的延迟：订单在提交时刻加上延迟后才到达交易所
+        account.account_open_book.write().await.latency_generator = AccountLatency::new(FluctuationMode::Constant, 50, 50, 0);
+        let open = open_single(&mut account, request).await.unwrap();
+        assert_eq!(open.timestamp, submitted_at + 50);
+
+        let market_trade = |timestamp: i64| MarketTrade { exchange: "binance-futures".to_string(),
+                                                          symbol: "ETHUSDT".to_string(),
+                                                          side: "sell".to_string(),
+                                                          price: 16300.0,
+                                                          timestamp,
+                                                          amount: 1.0 };
+        let filled = |event_rx: &mut mpsc::UnboundedReceiver<AccountEvent>| std::iter::from_fn(|| event_rx.try_recv().ok()).any(|event| matches!(event.kind, AccountEventKind::Trade(_)));
+
+        // 延迟尚未过去时的成交不会成交这笔订单
+        account.handle_trade_data(&market_trade(submitted_at + 5)).await.unwrap();
+        assert!(!filled(&mut event_rx));
+        assert_eq!(account.account_open_book.read().await.fetch_all().len(), 1);
+
+        account.handle_trade_data(&market_trade(open.timestamp)).await.unwrap();
+        assert!(filled(&mut event_rx));
+        assert!(account.account_open_book.read().await.fetch_all().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_same_seed_and_trades_reproduce_account_events()
+    {
+        async fn run(seed: u64) -> Vec<String>
+        {
+            let mut account = create_test_account().await;
+            let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+            account.account_event_tx = event_tx;
+            {
+                let mut orders = account.account_open_book.write().await;
+                orders.latency_generator = AccountLatency::new(FluctuationMode::Uniform { min: 0, max: 200 }, 200, 0, 0);
+                orders.reseed(seed);
+            }
+
+            for (index, (side, price)) in [(Side::Buy, 16400.0), (Side::Sell, 16450.0), (Side::Buy, 16350.0)].into_iter().enumerate() {
+                let request = Order { instruction: OrderInstruction::Limit,
+                                      exchange: Exchange::Hourglass,
+                                      instrument: Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual)),
+                                      timestamp: 1234567 + index as i64,
+                                      cid: Some(ClientOrderId(format!("seeded_{}", index))),
+                                      side,
+                                      state: RequestOpen { price,
+                                                           size: 0.1,
+                                                           reduce_only: false,
+                                                           tag: None } };
+                open_single(&mut account, request).await.unwrap();
+            }
+            for (timestamp, side, price) in [(1234700, "sell", 16300.0), (1234900, "buy", 16500.0)] {
+                account.handle_trade_data(&MarketTrade { exchange: "binance-futures".to_string(),
+                                                         symbol: "ETHUSDT".to_string(),
+                                                         side: side.to_string(),
+                                                         price,
+                                                         timestamp,
+                                                         amount: 1.0 })
+                       .await
+                       .unwrap();
+            }
+            std::iter::from_fn(|| event_rx.try_recv().ok()).map(|event| serde_json::to_string(&event).unwrap()).collect()
+        }
+
+        let events = run(7).await;
+        assert!(events.iter().any(|event| event.contains("Trade")));
+        assert_eq!(events, run(7).await);
+        assert_ne!(events, run(8).await);
+    }
 }
@@ -0,0 +1,96 @@
+use crate::{
+    common::datafeed::market_event::MarketEvent,
+    error::ExchangeError,
+    hourglass::{
+        clickhouse_api::{datatype::clickhouse_trade_data::MarketTrade, queries_operations::Row},
+        ws_trade::parse_base_and_quote,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+/// 聚合成交（例如 Binance 的 `aggTrade`）：同一笔主动单在同一价格上连续成交的多笔逐笔成交被合并为一行。
+///
+/// # 与逐笔成交的区别
+///
+/// 聚合后每行的 `amount` 是多笔逐笔成交之和，单行成交量的分布明显偏大、行数更少。撮合按每条外部成交触发，
+/// 同一条成交可以填满的挂单数量随之变大，因此用聚合成交回测时：
+///
+/// - 挂单更容易被一次性完全成交，部分成交的次数偏少；
+/// - 同价位多笔挂单按 FIFO 或 pro-rata 分配时，每次分配的总量更大，排队靠后的挂单更容易被轮到；
+/// - 基于成交笔数的统计（报单成交比、成交频率等）与逐笔数据不可直接比较。
+///
+/// 比较不同回测结果时应确认使用的是同一种数据源。逐笔数据见 [`MarketTrade`]，数据源的选择见
+/// [`DataSource`](crate::hourglass::DataSource)。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Row)]
+pub struct ClickhouseAggTrade
+{
+    pub exchange: String,
+    pub symbol: String,
+    pub side: String, // 主动方方向，口径与 `MarketTrade::side` 一致
+    pub price: f64,
+    pub timestamp: i64,
+    pub amount: f64,         // 被合并的逐笔成交的总成交量
+    pub agg_trade_id: u64,   // 聚合成交ID
+    pub first_trade_id: u64, // 被合并的第一笔逐笔成交ID
+    pub last_trade_id: u64,  // 被合并的最后一笔逐笔成交ID
+}
+
+impl ClickhouseAggTrade
+{
+    /// 被合并的逐笔成交笔数。
+    pub fn trade_count(&self) -> u64
+    {
+        self.last_trade_id.saturating_sub(self.first_trade_id) + 1
+    }
+
+    /// 按一条外部成交参与撮合：聚合后的总成交量作为这条成交的数量。价格或时间戳无效时返回错误，见 [`MarketTrade::validated`]。
+    pub fn to_market_trade(&self) -> Result<MarketTrade, ExchangeError>
+    {
+        MarketTrade { exchange: self.exchange.clone(),
+                      symbol: self.symbol.clone(),
+                      side: self.side.clone(),
+                      price: self.price,
+                      timestamp: self.timestamp,
+                      amount: self.amount }.validated()
+    }
+}
+
+impl MarketEvent<MarketTrade>
+{
+    /// 把聚合成交转换为行情事件，金融工具与时间戳的处理与 [`Self::from_swap_trade_clickhouse`] 一致。
+    pub fn from_agg_trade_clickhouse(trade: &ClickhouseAggTrade) -> Result<Self, ExchangeError>
+    {
+        let (base, quote) = parse_base_and_quote(&trade.symbol);
+        Ok(Self::from_swap_trade_clickhouse(trade.to_market_trade()?, base, quote))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::common::{instrument::kind::InstrumentKind, token::Token};
+
+    #[test]
+    fn test_agg_trade_converts_to_market_event()
+    {
+        let agg_trade = ClickhouseAggTrade { exchange: "binance-futures".to_string(),
+                                             symbol: "ETHUSDT".to_string(),
+                                             side: "sell".to_string(),
+                                             price: 16300.0,
+                                             timestamp: 1625247600000,
+                                             amount: 3.5,
+                                             agg_trade_id: 7,
+                                             first_trade_id: 100,
+                                             last_trade_id: 104 };
+        assert_eq!(agg_trade.trade_count(), 5);
+
+        let event = MarketEvent::from_agg_trade_clickhouse(&agg_trade).unwrap();
+        assert_eq!(event.instrument.base, Token::from("ETH"));
+        assert_eq!(event.instrument.quote, Token::from("USDT"));
+        assert_eq!(event.instrument.kind, InstrumentKind::Perpetual);
+        assert_eq!(event.exchange_ts, 1625247600000);
+        assert_eq!(event.kind.amount, 3.5);
+        assert_eq!(event.kind.side, "sell");
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{
+    common::{
+        datafeed::market_event::MarketEvent,
+        instrument::{kind::InstrumentKind, Instrument},
+        token::Token,
+    },
+    hourglass::clickhouse_api::queries_operations::Row,
+    Exchange,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// K 线的时间周期。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KlineInterval
+{
+    #[serde(rename = "1m")]
+    OneMinute,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+}
+
+impl KlineInterval
+{
+    /// 周期长度（毫秒）。
+    pub fn millis(&self) -> i64
+    {
+        match self {
+            | KlineInterval::OneMinute => 60_000,
+            | KlineInterval::FiveMinutes => 5 * 60_000,
+            | KlineInterval::OneHour => 60 * 60_000,
+        }
+    }
+
+    /// ClickHouse `toStartOfInterval` 使用的时间间隔表达式。
+    pub fn clickhouse_interval(&self) -> &'static str
+    {
+        match self {
+            | KlineInterval::OneMinute => "INTERVAL 1 MINUTE",
+            | KlineInterval::FiveMinutes => "INTERVAL 5 MINUTE",
+            | KlineInterval::OneHour => "INTERVAL 1 HOUR",
+        }
+    }
+}
+
+impl Display for KlineInterval
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f,
+               "{}",
+               match self {
+                   | KlineInterval::OneMinute => "1m",
+                   | KlineInterval::FiveMinutes => "5m",
+                   | KlineInterval::OneHour => "1h",
+               })
+    }
+}
+
+/// 由成交数据聚合得到的一根 K 线（OHLCV），查询见
+/// [`ClickHouseClient::cursor_klines`](crate::hourglass::clickhouse_api::queries_operations::ClickHouseClient::cursor_klines)。
+///
+/// `open_ts` 是该周期起点的毫秒时间戳（UTC）。K 线在周期结束后才完整，按 K 线回测时应在 `open_ts` 加上一个周期之后才使用其收盘价，
+/// 否则会引入未来数据。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Row)]
+pub struct ClickhouseKline
+{
+    pub open_ts: i64,
+    pub open: f64,   // 周期内第一笔成交的价格
+    pub high: f64,   // 周期内最高成交价
+    pub low: f64,    // 周期内最低成交价
+    pub close: f64,  // 周期内最后一笔成交的价格
+    pub volume: f64, // 周期内成交量之和
+}
+
+impl MarketEvent<ClickhouseKline>
+{
+    /// 把 K 线转换为行情事件，金融工具的处理与 [`MarketEvent::from_swap_trade_clickhouse`] 一致，时间戳取周期起点 `open_ts`。
+    pub fn from_kline_clickhouse(kline: ClickhouseKline, base: String, quote: String) -> Self
+    {
+        let instrument = Instrument { base: Token::from(base),
+                                      quote: Token::from(quote),
+                                      kind: InstrumentKind::Perpetual };
+
+        MarketEvent { exchange_ts: kline.open_ts,
+                      received_ts: kline.open_ts,
+                      exchange: Exchange::Hourglass,
+                      instrument,
+                      kind: kline }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_kline_converts_to_market_event()
+    {
+        let kline = ClickhouseKline { open_ts: 1625247600000,
+                                      open: 16300.0,
+                                      high: 16450.0,
+                                      low: 16250.0,
+                                      close: 16400.0,
+                                      volume: 12.5 };
+
+        let event = MarketEvent::from_kline_clickhouse(kline.clone(), "ETH".to_string(), "USDT".to_string());
+        assert_eq!(event.instrument, Instrument::new("ETH", "USDT", InstrumentKind::Perpetual));
+        assert_eq!(event.exchange_ts, 1625247600000);
+        assert_eq!(event.kind, kline);
+    }
+
+    #[test]
+    fn test_kline_interval_lengths()
+    {
+        assert_eq!(KlineInterval::OneMinute.millis(), 60_000);
+        assert_eq!(KlineInterval::FiveMinutes.millis(), 300_000);
+        assert_eq!(KlineInterval::OneHour.to_string(), "1h");
+        assert_eq!(serde_json::from_str::<KlineInterval>("\"5m\"").unwrap(), KlineInterval::FiveMinutes);
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{
+    error::ExchangeError,
+    hourglass::clickhouse_api::{datatype::clickhouse_trade_data::MarketTrade, queries_operations::Row},
+};
+use serde::{Deserialize, Serialize};
+
+/// 交易所公开推送的强平订单，例如 Binance 的 `forceOrder` 流。
+///
+/// # 表结构
+///
+/// 存储在 `{exchange}_{instrument}_liquidations` 库的同名表中，字段与 [`MarketTrade`] 一一对应，按 `timestamp` 排序，
+/// 建表语句见 [`ClickHouseClient::create_liquidations_table`](crate::hourglass::clickhouse_api::queries_operations::ClickHouseClient::create_liquidations_table)。
+///
+/// | 字段        | 类型      | 说明                                                         |
+/// |-------------|-----------|--------------------------------------------------------------|
+/// | `exchange`  | `String`  | 与成交数据相同的交易所标识，例如 `binance-futures`            |
+/// | `symbol`    | `String`  | 与成交数据相同的符号格式                                     |
+/// | `side`      | `String`  | 强平单的方向：多头被强平为 `sell`，空头被强平为 `buy`         |
+/// | `price`     | `Float64` | 强平单的成交均价                                             |
+/// | `timestamp` | `Int64`   | 毫秒时间戳                                                   |
+/// | `amount`    | `Float64` | 强平单的成交数量                                             |
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Row)]
+pub struct MarketLiquidation
+{
+    pub exchange: String,
+    pub symbol: String,
+    pub side: String, // 强平单方向，即这笔强平在行情流上的主动方方向
+    pub price: f64,
+    pub timestamp: i64,
+    pub amount: f64,
+}
+
+impl MarketLiquidation
+{
+    /// 把强平单转换为一笔由强平单主动成交的行情成交，注入行情流与撮合。价格或时间戳无效时返回错误，见 [`MarketTrade::validated`]。
+    pub fn to_market_trade(&self) -> Result<MarketTrade, ExchangeError>
+    {
+        MarketTrade { exchange: self.exchange.clone(),
+                      symbol: self.symbol.clone(),
+                      side: self.side.clone(),
+                      price: self.price,
+                      timestamp: self.timestamp,
+                      amount: self.amount }.validated()
+    }
+}
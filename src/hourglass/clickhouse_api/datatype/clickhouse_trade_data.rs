@@ -2,11 +2,15 @@ use crate::{
     common::{
         instrument::{kind::InstrumentKind, Instrument},
         stable_token::StableToken,
+        trade::ClientTrade,
+        Side,
     },
+    error::ExchangeError,
     hourglass::clickhouse_api::queries_operations::Row,
     Token,
 };
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize, Row)]
@@ -14,40 +18,93 @@ pub struct MarketTrade
 {
     pub exchange: String, // 注意：此字段及相关数据存储在数据库中，但截至2024年8月目前不适用。
     pub symbol: String,   // 注意：符号格式为 `base_quote` 代表永续合约，`base_quote_XXXX` 代表期货（取决于交易所的不同）。
-    pub side: String,
+    pub side: String, // 成交的主动方（taker）方向，如 `buy`、`SELL`，见 [`MarketTrade::aggressor_side`]。
     pub price: f64,
     pub timestamp: i64,
     pub amount: f64,
 }
 
+/// 由 `binance-futures` 这类交易所标识推断金融工具种类，成交与深度快照共用同一套规则。
+pub fn parse_instrument_kind(exchange: &str) -> InstrumentKind
+{
+    let parts: Vec<&str> = exchange.split('-').collect();
+
+    if parts.len() == 2 {
+        // 假设以 `perpetual` 结尾的为永续合约 FIXME 这个是非常不正确的临时处理方式。以后还是要用MarketEvent来包裹MarketTrade
+        if parts[1].to_lowercase().ends_with("futures") {
+            InstrumentKind::Perpetual
+        }
+        else {
+            InstrumentKind::Spot
+        }
+    }
+    else if parts.len() > 2 {
+        // 假设以 `futures` 结尾的为期货
+        if parts.last().unwrap().to_lowercase().ends_with("futures") {
+            InstrumentKind::Future
+        }
+        else {
+            InstrumentKind::Spot // 默认处理为现货，如果结尾不是 `future`
+        }
+    }
+    else {
+        InstrumentKind::Spot // 没有下划线，默认现货工具
+    }
+}
+
 /// 注意：当前适用于2024年8月。todo!() 需要更新。
 impl MarketTrade
 {
-    pub fn parse_kind(&self) -> InstrumentKind
+    /// 解析外部成交的主动方方向，不区分大小写。无法识别时返回 `None`。
+    ///
+    /// 主动买（`Buy`）会吃掉卖方挂单，主动卖（`Sell`）会吃掉买方挂单。
+    pub fn aggressor_side(&self) -> Option<Side>
     {
-        let parts: Vec<&str> = self.exchange.split('-').collect();
-
-        if parts.len() == 2 {
-            // 假设以 `perpetual` 结尾的为永续合约 FIXME 这个是非常不正确的临时处理方式。以后还是要用MarketEvent来包裹MarketTrade
-            if parts[1].to_lowercase().ends_with("futures") {
-                InstrumentKind::Perpetual
-            }
-            else {
-                InstrumentKind::Spot
-            }
-        }
-        else if parts.len() > 2 {
-            // 假设以 `futures` 结尾的为期货
-            if parts.last().unwrap().to_lowercase().ends_with("futures") {
-                InstrumentKind::Future
-            }
-            else {
-                InstrumentKind::Spot // 默认处理为现货，如果结尾不是 `future`
-            }
+        Side::from_str(self.side.trim()).ok()
+    }
+
+    /// 是否带有有效的成交量。`amount` 为 0、负数或非有限值时视为成交量未知，撮合方式见
+    /// [`UnsizedTradeHandling`](crate::hourglass::account::account_config::UnsizedTradeHandling)。
+    pub fn has_size(&self) -> bool
+    {
+        self.amount.is_finite() && self.amount > 0.0
+    }
+
+    /// 校验由其他数据格式转换而来的成交：价格必须是正的有限值，时间戳不能为负，否则返回 `ExchangeError::DataSourceError`。
+    /// 成交量不在此校验，缺失或无效的成交量按成交量未知处理，见 [`Self::has_size`]。
+    pub fn validated(self) -> Result<Self, ExchangeError>
+    {
+        if !self.price.is_finite() || self.price <= 0.0 {
+            return Err(ExchangeError::DataSourceError(format!("Invalid price {} for {} trade at {}", self.price, self.symbol, self.timestamp)));
         }
-        else {
-            InstrumentKind::Spot // 没有下划线，默认现货工具
+        if self.timestamp < 0 {
+            return Err(ExchangeError::DataSourceError(format!("Invalid timestamp {} for {} trade", self.timestamp, self.symbol)));
         }
+        Ok(self)
+    }
+
+    /// 把本账户的一笔成交转换为行情流上的成交记录，`aggressor_side` 为这笔成交的主动方方向。
+    ///
+    /// `exchange` 按 [`Self::parse_kind`] 的约定按金融工具种类生成（`hourglass-spot`、`hourglass-futures`、`hourglass-delivery-futures`），
+    /// `symbol` 为 `base` 与 `quote` 直接拼接，使转换结果可以按原有方式解析回同一个金融工具。
+    pub fn from_client_trade(trade: &ClientTrade, aggressor_side: Side) -> Self
+    {
+        let exchange = match trade.instrument.kind {
+            | InstrumentKind::Perpetual => "hourglass-futures",
+            | InstrumentKind::Future | InstrumentKind::CommodityFuture => "hourglass-delivery-futures",
+            | _ => "hourglass-spot",
+        };
+        MarketTrade { exchange: exchange.to_string(),
+                      symbol: format!("{}{}", trade.instrument.base, trade.instrument.quote),
+                      side: aggressor_side.to_string(),
+                      price: trade.price,
+                      timestamp: trade.timestamp,
+                      amount: trade.size }
+    }
+
+    pub fn parse_kind(&self) -> InstrumentKind
+    {
+        parse_instrument_kind(&self.exchange)
     }
 
     pub fn parse_instrument(&self) -> Option<Instrument>
@@ -190,4 +247,22 @@ mod tests
 
         assert!(trade.parse_instrument().is_none());
     }
+
+    #[test]
+    fn test_aggressor_side()
+    {
+        let trade_with_side = |side: &str| MarketTrade { exchange: "binance-futures".to_string(),
+                                                         symbol: "BTCUSDT".to_string(),
+                                                         side: side.to_string(),
+                                                         price: 10000.0,
+                                                         timestamp: 1625244000,
+                                                         amount: 1.0 };
+
+        assert_eq!(trade_with_side("buy").aggressor_side(), Some(Side::Buy));
+        assert_eq!(trade_with_side("BUY").aggressor_side(), Some(Side::Buy));
+        assert_eq!(trade_with_side("Sell").aggressor_side(), Some(Side::Sell));
+        assert_eq!(trade_with_side(" s ").aggressor_side(), Some(Side::Sell));
+        assert_eq!(trade_with_side("").aggressor_side(), None);
+        assert_eq!(trade_with_side("bid").aggressor_side(), None);
+    }
 }
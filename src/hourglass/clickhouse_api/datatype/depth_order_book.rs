@@ -0,0 +1,231 @@
+use crate::{common::Side, hourglass::clickhouse_api::datatype::order_book_25::OrderBook25};
+use serde::{Deserialize, Serialize};
+
+/// 深度订单簿中的一档价位。
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DepthLevel
+{
+    pub price: f64,
+    pub amount: f64,
+}
+
+/// 本地维护的多档（L2）深度订单簿，由回放的外部快照加载。
+///
+/// 本地市价单会按价位由优到劣吃掉其中的流动性，被吃掉的数量从本地簿中扣除，
+/// 直到下一次外部快照到来时整体覆盖恢复。
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DepthOrderBook
+{
+    pub timestamp: i64,
+    pub bids: Vec<DepthLevel>, // 买方档位，价格由高到低
+    pub asks: Vec<DepthLevel>, // 卖方档位，价格由低到高
+    #[serde(default)]
+    pub synthetic: bool, // 档位是否为围绕成交价铺设的合成流动性，而非真实的深度快照
+}
+
+/// 市价单吃掉深度后的结果。
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DepthSweep
+{
+    pub fills: Vec<DepthLevel>, // 每一档实际成交的价格与数量
+    pub filled_size: f64,
+}
+
+impl DepthSweep
+{
+    /// 按成交量加权的平均成交价，没有成交时为 `None`。
+    pub fn average_price(&self) -> Option<f64>
+    {
+        (self.filled_size > 0.0).then(|| self.fills.iter().map(|fill| fill.price * fill.amount).sum::<f64>() / self.filled_size)
+    }
+}
+
+impl DepthOrderBook
+{
+    /// 由 `(价格, 数量)` 档位构建，数量或价格非正的档位会被丢弃，并按各自方向由优到劣排序。
+    pub fn from_levels(timestamp: i64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> Self
+    {
+        let mut book = Self { timestamp, ..Default::default() };
+        book.replace_levels(timestamp, bids, asks);
+        book
+    }
+
+    /// 用外部快照整体覆盖本地档位，之前被本地成交吃掉的流动性随之恢复。
+    pub fn replace_levels(&mut self, timestamp: i64, bids: &[(f64, f64)], asks: &[(f64, f64)])
+    {
+        self.synthetic = false;
+        self.fill_levels(timestamp, bids, asks);
+    }
+
+    /// 用合成流动性覆盖本地档位，并标记为合成。
+    pub fn replace_with_synthetic_levels(&mut self, timestamp: i64, bids: &[(f64, f64)], asks: &[(f64, f64)])
+    {
+        self.synthetic = true;
+        self.fill_levels(timestamp, bids, asks);
+    }
+
+    fn fill_levels(&mut self, timestamp: i64, bids: &[(f64, f64)], asks: &[(f64, f64)])
+    {
+        let to_levels = |levels: &[(f64, f64)]| -> Vec<DepthLevel> {
+            levels.iter()
+                  .filter(|(price, amount)| *price > 0.0 && *amount > 0.0)
+                  .map(|&(price, amount)| DepthLevel { price, amount })
+                  .collect()
+        };
+        self.timestamp = timestamp;
+        self.bids = to_levels(bids);
+        self.bids.sort_by(|a, b| b.price.total_cmp(&a.price));
+        self.asks = to_levels(asks);
+        self.asks.sort_by(|a, b| a.price.total_cmp(&b.price));
+    }
+
+    /// 用 25 档快照刷新本地档位。
+    pub fn apply_snapshot(&mut self, snapshot: &OrderBook25)
+    {
+        self.replace_levels(snapshot.timestamp, &snapshot.bid_levels(), &snapshot.ask_levels());
+    }
+
+    pub fn best_bid(&self) -> Option<f64>
+    {
+        self.bids.first().map(|level| level.price)
+    }
+
+    pub fn best_ask(&self) -> Option<f64>
+    {
+        self.asks.first().map(|level| level.price)
+    }
+
+    /// 最优买价与最优卖价的中间价，任一方没有档位时返回 `None`。
+    pub fn mid_price(&self) -> Option<f64>
+    {
+        Some((self.best_bid()? + self.best_ask()?) / 2.0)
+    }
+
+    /// `side` 方向的主动单在价格不劣于 `limit_price` 的档位上可以成交的总数量，不扣除本地档位。`None` 表示不限价。
+    pub fn liquidity_within(&self, side: Side, limit_price: Option<f64>) -> f64
+    {
+        let levels = match side {
+            | Side::Buy => &self.asks,
+            | Side::Sell => &self.bids,
+        };
+        levels.iter()
+              .take_while(|level| match (side, limit_price) {
+                  | (Side::Buy, Some(limit_price)) => level.price <= limit_price,
+                  | (Side::Sell, Some(limit_price)) => level.price >= limit_price,
+                  | (_, None) => true,
+              })
+              .map(|level| level.amount)
+              .sum()
+    }
+
+    /// 以 `side` 方向的主动单吃掉最多 `size` 的流动性：买单吃卖方档位，卖单吃买方档位。
+    ///
+    /// 被吃掉的数量从本地档位中扣除，吃空的档位被移除。深度不足时只成交可用部分。
+    pub fn sweep(&mut self, side: Side, size: f64) -> DepthSweep
+    {
+        self.sweep_within(side, size, None)
+    }
+
+    /// 与 [`Self::sweep`] 相同，但只吃价格不劣于 `limit_price` 的档位：买单不高于限价，卖单不低于限价。`None` 表示不限价。
+    pub fn sweep_within(&mut self, side: Side, size: f64, limit_price: Option<f64>) -> DepthSweep
+    {
+        let levels = match side {
+            | Side::Buy => &mut self.asks,
+            | Side::Sell => &mut self.bids,
+        };
+
+        let mut sweep = DepthSweep::default();
+        let mut remaining = size;
+        while remaining > 0.0 {
+            let Some(level) = levels.first_mut()
+            else {
+                break;
+            };
+            let beyond_limit = match (side, limit_price) {
+                | (Side::Buy, Some(limit_price)) => level.price > limit_price,
+                | (Side::Sell, Some(limit_price)) => level.price < limit_price,
+                | (_, None) => false,
+            };
+            if beyond_limit {
+                break;
+            }
+            let amount = level.amount.min(remaining);
+            sweep.fills.push(DepthLevel { price: level.price, amount });
+            sweep.filled_size += amount;
+            remaining -= amount;
+            level.amount -= amount;
+            if level.amount <= 0.0 {
+                levels.remove(0);
+            }
+        }
+        sweep
+    }
+}
+
+impl From<&OrderBook25> for DepthOrderBook
+{
+    fn from(snapshot: &OrderBook25) -> Self
+    {
+        Self::from_levels(snapshot.timestamp, &snapshot.bid_levels(), &snapshot.ask_levels())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn book() -> DepthOrderBook
+    {
+        DepthOrderBook::from_levels(1, &[(99.0, 1.0), (100.0, 2.0), (0.0, 0.0)], &[(102.0, 3.0), (101.0, 1.0), (103.0, 0.0)])
+    }
+
+    #[test]
+    fn test_levels_are_sorted_and_empty_levels_dropped()
+    {
+        let book = book();
+        assert_eq!(book.best_bid(), Some(100.0));
+        assert_eq!(book.best_ask(), Some(101.0));
+        assert_eq!(book.bids.len(), 2);
+        assert_eq!(book.asks.len(), 2);
+    }
+
+    #[test]
+    fn test_sweep_walks_levels_and_depletes_book()
+    {
+        let mut book = book();
+        let sweep = book.sweep(Side::Buy, 2.0);
+        assert_eq!(sweep.fills, vec![DepthLevel { price: 101.0, amount: 1.0 }, DepthLevel { price: 102.0, amount: 1.0 }]);
+        assert_eq!(sweep.average_price(), Some(101.5));
+        assert_eq!(book.asks, vec![DepthLevel { price: 102.0, amount: 2.0 }]);
+
+        // 深度不足时只成交可用部分
+        let sweep = book.sweep(Side::Sell, 5.0);
+        assert_eq!(sweep.filled_size, 3.0);
+        assert!(book.bids.is_empty());
+        assert_eq!(book.sweep(Side::Sell, 1.0).average_price(), None);
+    }
+
+    #[test]
+    fn test_liquidity_within_stops_at_limit_price()
+    {
+        let book = DepthOrderBook::from_levels(1, &[(99.0, 1.0), (98.0, 2.0)], &[(101.0, 1.0), (102.0, 2.0), (103.0, 4.0)]);
+        assert_eq!(book.liquidity_within(Side::Buy, Some(102.0)), 3.0);
+        assert_eq!(book.liquidity_within(Side::Buy, None), 7.0);
+        assert_eq!(book.liquidity_within(Side::Sell, Some(99.5)), 0.0);
+        assert_eq!(book.liquidity_within(Side::Sell, Some(98.0)), 3.0);
+    }
+
+    #[test]
+    fn test_external_update_restores_consumed_liquidity()
+    {
+        let mut book = book();
+        book.sweep(Side::Buy, 4.0);
+        assert!(book.asks.is_empty());
+
+        book.replace_levels(2, &[(100.0, 2.0)], &[(101.0, 1.0), (102.0, 3.0)]);
+        assert_eq!(book.timestamp, 2);
+        assert_eq!(book.best_ask(), Some(101.0));
+        assert_eq!(book.asks.iter().map(|level| level.amount).sum::<f64>(), 4.0);
+    }
+}
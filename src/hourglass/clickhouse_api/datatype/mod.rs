@@ -1,3 +1,8 @@
+pub mod clickhouse_agg_trade_data;
+pub mod clickhouse_kline_data;
+pub mod clickhouse_liquidation_data;
 pub mod clickhouse_trade_data;
+pub mod depth_order_book;
 pub mod order_book_25;
 pub mod single_level_order_book;
+pub mod volume_profile;
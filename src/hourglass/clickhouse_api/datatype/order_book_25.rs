@@ -2,7 +2,7 @@ use crate::hourglass::clickhouse_api::queries_operations::Row;
 use serde::{Deserialize, Serialize};
 
 #[allow(dead_code)]
-#[derive(Debug, Serialize, Deserialize, Row)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Row)]
 pub struct OrderBook25
 {
     pub exchange: String,
@@ -110,3 +110,34 @@ pub struct OrderBook25
     pub bids_24_price: f64,
     pub bids_24_amount: f64,
 }
+
+impl OrderBook25
+{
+    /// 卖方 25 档 `(价格, 数量)`，由优到劣排列。
+    pub fn ask_levels(&self) -> [(f64, f64); 25]
+    {
+        [(self.asks_0_price, self.asks_0_amount), (self.asks_1_price, self.asks_1_amount), (self.asks_2_price, self.asks_2_amount),
+         (self.asks_3_price, self.asks_3_amount), (self.asks_4_price, self.asks_4_amount), (self.asks_5_price, self.asks_5_amount),
+         (self.asks_6_price, self.asks_6_amount), (self.asks_7_price, self.asks_7_amount), (self.asks_8_price, self.asks_8_amount),
+         (self.asks_9_price, self.asks_9_amount), (self.asks_10_price, self.asks_10_amount), (self.asks_11_price, self.asks_11_amount),
+         (self.asks_12_price, self.asks_12_amount), (self.asks_13_price, self.asks_13_amount), (self.asks_14_price, self.asks_14_amount),
+         (self.asks_15_price, self.asks_15_amount), (self.asks_16_price, self.asks_16_amount), (self.asks_17_price, self.asks_17_amount),
+         (self.asks_18_price, self.asks_18_amount), (self.asks_19_price, self.asks_19_amount), (self.asks_20_price, self.asks_20_amount),
+         (self.asks_21_price, self.asks_21_amount), (self.asks_22_price, self.asks_22_amount), (self.asks_23_price, self.asks_23_amount),
+         (self.asks_24_price, self.asks_24_amount)]
+    }
+
+    /// 买方 25 档 `(价格, 数量)`，由优到劣排列。
+    pub fn bid_levels(&self) -> [(f64, f64); 25]
+    {
+        [(self.bids_0_price, self.bids_0_amount), (self.bids_1_price, self.bids_1_amount), (self.bids_2_price, self.bids_2_amount),
+         (self.bids_3_price, self.bids_3_amount), (self.bids_4_price, self.bids_4_amount), (self.bids_5_price, self.bids_5_amount),
+         (self.bids_6_price, self.bids_6_amount), (self.bids_7_price, self.bids_7_amount), (self.bids_8_price, self.bids_8_amount),
+         (self.bids_9_price, self.bids_9_amount), (self.bids_10_price, self.bids_10_amount), (self.bids_11_price, self.bids_11_amount),
+         (self.bids_12_price, self.bids_12_amount), (self.bids_13_price, self.bids_13_amount), (self.bids_14_price, self.bids_14_amount),
+         (self.bids_15_price, self.bids_15_amount), (self.bids_16_price, self.bids_16_amount), (self.bids_17_price, self.bids_17_amount),
+         (self.bids_18_price, self.bids_18_amount), (self.bids_19_price, self.bids_19_amount), (self.bids_20_price, self.bids_20_amount),
+         (self.bids_21_price, self.bids_21_amount), (self.bids_22_price, self.bids_22_amount), (self.bids_23_price, self.bids_23_amount),
+         (self.bids_24_price, self.bids_24_amount)]
+    }
+}
@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 #[allow(dead_code)]
-#[derive(Debug, Serialize, Deserialize, Row)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Row)]
 pub struct SingleLevelOrderBook
 {
     pub latest_bid: f64,
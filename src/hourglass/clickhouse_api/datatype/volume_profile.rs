@@ -0,0 +1,151 @@
+use crate::hourglass::clickhouse_api::queries_operations::Row;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// 一天的毫秒数。
+pub const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// ClickHouse 按时间桶聚合成交量的查询结果行。
+#[derive(Clone, Debug, Serialize, Deserialize, Row)]
+pub struct VolumeBucket
+{
+    pub bucket: u32,
+    pub volume: f64,
+}
+
+/// 日内成交量分布，把一天按 `bucket_minutes` 切分为若干时间桶，每个桶的权重为该桶成交量占全天成交量的比例。
+///
+/// 供 VWAP 执行算法按历史成交量分布分配子订单数量，权重之和为 1。
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VolumeProfile
+{
+    pub bucket_minutes: u32,
+    pub weights: Vec<f64>,
+}
+
+impl VolumeProfile
+{
+    /// 一天中按 `bucket_minutes` 切分的时间桶数量，不能整除时最后一个桶较短。
+    pub fn bucket_count(bucket_minutes: u32) -> usize
+    {
+        let bucket_minutes = bucket_minutes.max(1) as usize;
+        (24 * 60usize).div_ceil(bucket_minutes)
+    }
+
+    /// 均匀分布，没有历史数据时使用。
+    pub fn uniform(bucket_minutes: u32) -> Self
+    {
+        let bucket_count = Self::bucket_count(bucket_minutes);
+        Self { bucket_minutes,
+               weights: vec![1.0 / bucket_count as f64; bucket_count] }
+    }
+
+    /// 由各时间桶的成交量构建归一化的分布。超出当天范围的桶会被忽略，总成交量为零时退化为均匀分布。
+    pub fn from_buckets(bucket_minutes: u32, buckets: &[VolumeBucket]) -> Self
+    {
+        let mut volumes = vec![0.0; Self::bucket_count(bucket_minutes)];
+        for bucket in buckets {
+            if let Some(volume) = volumes.get_mut(bucket.bucket as usize) {
+                *volume += bucket.volume.max(0.0);
+            }
+        }
+
+        let total: f64 = volumes.iter().sum();
+        if total <= 0.0 || !total.is_finite() {
+            return Self::uniform(bucket_minutes);
+        }
+
+        Self { bucket_minutes,
+               weights: volumes.into_iter().map(|volume| volume / total).collect() }
+    }
+
+    /// 时间戳（毫秒）所在的时间桶下标，按 UTC 日内时间计算。
+    pub fn bucket_index(&self, timestamp: i64) -> usize
+    {
+        let bucket_millis = self.bucket_minutes.max(1) as i64 * 60_000;
+        let index = (timestamp.rem_euclid(MILLIS_PER_DAY) / bucket_millis) as usize;
+        index.min(self.weights.len().saturating_sub(1))
+    }
+
+    /// 按分布把 `total_size` 分配到 `buckets` 范围内的各个时间桶，返回每个桶的子订单数量。
+    ///
+    /// 范围内的权重会重新归一化，使分配结果之和等于 `total_size`；范围内权重全为零时均匀分配。
+    pub fn allocate(&self, total_size: f64, buckets: Range<usize>) -> Vec<f64>
+    {
+        let buckets = buckets.start.min(self.weights.len())..buckets.end.min(self.weights.len());
+        if buckets.is_empty() {
+            return Vec::new();
+        }
+
+        let weights = &self.weights[buckets];
+        let total_weight: f64 = weights.iter().sum();
+        if total_weight <= 0.0 {
+            return vec![total_size / weights.len() as f64; weights.len()];
+        }
+
+        weights.iter().map(|weight| total_size * weight / total_weight).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_uniform_profile_sums_to_one()
+    {
+        let profile = VolumeProfile::uniform(60);
+        assert_eq!(profile.weights.len(), 24);
+        assert!((profile.weights.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+
+        // 不能整除时最后一个桶较短
+        assert_eq!(VolumeProfile::bucket_count(7), 206);
+    }
+
+    #[test]
+    fn test_from_buckets_normalizes_volumes()
+    {
+        let profile = VolumeProfile::from_buckets(360,
+                                                  &[VolumeBucket { bucket: 0, volume: 10.0 },
+                                                    VolumeBucket { bucket: 2, volume: 30.0 },
+                                                    VolumeBucket { bucket: 9, volume: 99.0 }]);
+        assert_eq!(profile.weights, vec![0.25, 0.0, 0.75, 0.0]);
+    }
+
+    #[test]
+    fn test_from_buckets_falls_back_to_uniform_without_volume()
+    {
+        assert_eq!(VolumeProfile::from_buckets(60, &[]), VolumeProfile::uniform(60));
+        assert_eq!(VolumeProfile::from_buckets(60, &[VolumeBucket { bucket: 3, volume: 0.0 }]), VolumeProfile::uniform(60));
+    }
+
+    #[test]
+    fn test_bucket_index_uses_time_of_day()
+    {
+        let profile = VolumeProfile::uniform(60);
+        let day_start = 1_700_006_400_000; // 2023-11-15 00:00:00 UTC
+        assert_eq!(profile.bucket_index(day_start), 0);
+        assert_eq!(profile.bucket_index(day_start + 90 * 60_000), 1);
+        assert_eq!(profile.bucket_index(day_start + MILLIS_PER_DAY - 1), 23);
+    }
+
+    #[test]
+    fn test_allocate_shapes_child_sizes_by_volume()
+    {
+        let profile = VolumeProfile { bucket_minutes: 360,
+                                      weights: vec![0.1, 0.2, 0.3, 0.4] };
+        assert_eq!(profile.allocate(10.0, 0..4), vec![1.0, 2.0, 3.0, 4.0]);
+
+        // 只在部分时间桶内执行时重新归一化
+        let sizes = profile.allocate(10.0, 2..4);
+        assert!((sizes[0] - 30.0 / 7.0).abs() < 1e-12);
+        assert!((sizes.iter().sum::<f64>() - 10.0).abs() < 1e-12);
+
+        // 范围内没有成交量时均匀分配，越界的范围被截断
+        let sparse = VolumeProfile { bucket_minutes: 360,
+                                     weights: vec![1.0, 0.0, 0.0, 0.0] };
+        assert_eq!(sparse.allocate(4.0, 1..10), vec![4.0 / 3.0; 3]);
+        assert!(sparse.allocate(4.0, 5..10).is_empty());
+    }
+}
@@ -8,20 +8,89 @@ pub use clickhouse::{
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 /// NOTE 目前表名的构建方式都以`Tardis API`的`Binance`数据为基础。可能并不适用于其他交易所。日后**必须**扩展。
 use rayon::prelude::*;
-use std::sync::{Arc, Mutex};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 use tokio::sync::RwLock;
 
 use crate::{
-    common::Side,
+    common::{datafeed::multi_symbol_feed::MultiSymbolFeed, Side},
     hourglass::{
-        clickhouse_api::{datatype::clickhouse_trade_data::MarketTrade, query_builder::ClickHouseQueryBuilder},
+        clickhouse_api::{
+            datatype::{
+                clickhouse_agg_trade_data::ClickhouseAggTrade,
+                clickhouse_kline_data::{ClickhouseKline, KlineInterval},
+                clickhouse_liquidation_data::MarketLiquidation,
+                clickhouse_trade_data::MarketTrade,
+                volume_profile::{VolumeBucket, VolumeProfile, MILLIS_PER_DAY},
+            },
+            query_builder::ClickHouseQueryBuilder,
+        },
         utils::chrono_operations::extract_date,
     },
 };
 
+/// 成交量分布缓存的键：(exchange, instrument, symbol, date, bucket_minutes)。
+type VolumeProfileKey = (String, String, String, String, u32);
+
+/// ClickHouse 连接池与读查询重试的配置。
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClickHouseConfig
+{
+    pub url: String,
+    pub user: String,
+    pub password: String,
+    pub pool_size: usize,        // 连接池中的客户端数量，查询按轮询方式分配，至少为 1
+    pub max_retries: u32,        // 读查询遇到连接中断或超时时的最大重试次数
+    pub initial_backoff_ms: u64, // 第一次重试前的等待时间，之后每次翻倍
+}
+
+impl Default for ClickHouseConfig
+{
+    fn default() -> Self
+    {
+        Self { url: "http://localhost:8123".to_string(),
+               user: "default".to_string(),
+               password: String::new(),
+               pool_size: 4,
+               max_retries: 3,
+               initial_backoff_ms: 100 }
+    }
+}
+
+impl ClickHouseConfig
+{
+    /// 第 `attempt` 次重试（从 0 开始）前的等待时间。
+    pub fn backoff(&self, attempt: u32) -> Duration
+    {
+        Duration::from_millis(self.initial_backoff_ms.saturating_mul(1 << attempt.min(16)))
+    }
+}
+
+/// 是否为可以重试的暂时性错误：连接被重置、网络中断或查询超时。
+pub fn is_transient_error(error: &Error) -> bool
+{
+    matches!(error, Error::Network(_) | Error::TimedOut)
+}
+
+/// ClickHouse 客户端，内部维护一个按轮询方式使用的连接池，配置见 [`ClickHouseConfig`]。
+///
+/// 一次性读取全部结果的查询（`retrieve_*`、`query_*` 等）遇到暂时性错误时自动重试；`cursor_*` 返回的游标按行流式读取，
+/// 中途出错时无法从断点继续，因此不重试，由调用方决定如何处理。
 pub struct ClickHouseClient
 {
-    pub client: Arc<RwLock<Client>>,
+    pub client: Arc<RwLock<Client>>, // 连接池中的第一个客户端，供需要直接执行查询的调用方使用
+    pub volume_profiles: Arc<RwLock<HashMap<VolumeProfileKey, VolumeProfile>>>, // 按金融工具与日期缓存的日内成交量分布
+    pool: Vec<Client>,
+    next_client: AtomicUsize,
+    config: ClickHouseConfig,
 }
 
 impl Default for ClickHouseClient
@@ -34,11 +103,67 @@ impl Default for ClickHouseClient
 
 impl ClickHouseClient
 {
+    /// 使用默认配置连接本地的 ClickHouse，见 [`ClickHouseConfig::default`]。
     pub fn new() -> Self
     {
-        let client = Client::default().with_url("http://localhost:8123").with_user("default").with_password("");
-        info!("Successfully connected to the ClickHouse server.");
-        Self { client: Arc::new(RwLock::new(client)) }
+        Self::new_with_config(ClickHouseConfig::default())
+    }
+
+    /// 按配置建立 `pool_size` 个客户端。各查询按轮询方式使用其中一个，读查询遇到暂时性错误时按指数退避重试。
+    pub fn new_with_config(config: ClickHouseConfig) -> Self
+    {
+        let pool: Vec<Client> = (0..config.pool_size.max(1)).map(|_| Client::default().with_url(&config.url).with_user(&config.user).with_password(&config.password))
+                                                            .collect();
+        info!("Successfully connected to the ClickHouse server with {} pooled clients.", pool.len());
+        Self { client: Arc::new(RwLock::new(pool[0].clone())),
+               volume_profiles: Arc::new(RwLock::new(HashMap::new())),
+               pool,
+               next_client: AtomicUsize::new(0),
+               config }
+    }
+
+    pub fn config(&self) -> &ClickHouseConfig
+    {
+        &self.config
+    }
+
+    /// 按轮询方式从连接池取出下一个客户端。
+    pub fn pooled_client(&self) -> &Client
+    {
+        &self.pool[self.next_client.fetch_add(1, Ordering::Relaxed) % self.pool.len()]
+    }
+
+    /// 执行一次读操作，遇到暂时性错误时换用连接池中的下一个客户端并按指数退避重试，其他错误直接返回。
+    async fn with_retry<T, F, Fut>(&self, mut operation: F) -> Result<T>
+        where F: FnMut(Client) -> Fut,
+              Fut: Future<Output = Result<T>>
+    {
+        let mut attempt = 0;
+        loop {
+            match operation(self.pooled_client().clone()).await {
+                | Err(error) if attempt < self.config.max_retries && is_transient_error(&error) => {
+                    let backoff = self.config.backoff(attempt);
+                    warn!("Transient ClickHouse error, retrying in {:?} ({}/{}): {:?}", backoff, attempt + 1, self.config.max_retries, error);
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                | result => return result,
+            }
+        }
+    }
+
+    /// 读取查询的全部结果，暂时性错误会重试。
+    pub async fn fetch_all_with_retry<T>(&self, query: &str) -> Result<Vec<T>>
+        where T: Row + DeserializeOwned
+    {
+        self.with_retry(|client| async move { client.query(query).fetch_all::<T>().await }).await
+    }
+
+    /// 读取查询的第一行结果，暂时性错误会重试。
+    pub async fn fetch_one_with_retry<T>(&self, query: &str) -> Result<T>
+        where T: Row + DeserializeOwned
+    {
+        self.with_retry(|client| async move { client.query(query).fetch_one::<T>().await }).await
     }
 }
 
@@ -118,7 +243,7 @@ impl ClickHouseClient
     {
         let table_names_query = format!("SHOW TABLES FROM {database}",);
         info!("Trying to retrieve table names within database : {:}", database);
-        self.client.read().await.query(&table_names_query).fetch_all::<String>().await.unwrap_or_else(|e| {
+        self.fetch_all_with_retry::<String>(&table_names_query).await.unwrap_or_else(|e| {
                                                                                           warn!("Error loading table names: {:?}", e);
 
                                                                                           vec![]
@@ -129,7 +254,7 @@ impl ClickHouseClient
     {
         let table_names_query = format!("SHOW TABLES FROM {database} LIKE '%union%'",);
         info!("Trying to retrieve table names within the database that contain 'union': {:?}", table_names_query);
-        self.client.read().await.query(&table_names_query).fetch_all::<String>().await.unwrap_or_else(|e| {
+        self.fetch_all_with_retry::<String>(&table_names_query).await.unwrap_or_else(|e| {
                                                                                           warn!("Error loading table names: {:?}", e);
 
                                                                                           vec![]
@@ -195,7 +320,7 @@ impl ClickHouseClient
         }
 
         // 执行创建新表的查询
-        self.client.read().await.query(&final_query).execute().await?;
+        self.pooled_client().query(&final_query).execute().await?;
 
         if report_progress {
             info!("Table {}.{} created successfully.", database, new_table_name);
@@ -204,6 +329,48 @@ impl ClickHouseClient
         Ok(())
     }
 
+    /// 从库中的表名里挑出某日各品种的逐笔成交表，排除已有的合并表，按表名排序。`date` 可以是 `2024-05-05` 或 `2024_05_05`。
+    pub fn union_source_tables(&self, table_names: &[String], date: &str) -> Vec<String>
+    {
+        let date = date.replace('-', "_");
+        let mut tables: Vec<String> = table_names.iter()
+                                                 .filter(|table_name| !table_name.contains("union") && extract_date(table_name).is_some_and(|table_date| table_date == date))
+                                                 .cloned()
+                                                 .collect();
+        tables.sort();
+        tables
+    }
+
+    /// 把某日各品种的逐笔成交表合并为 [`Self::construct_union_table_name`] 命名的合并表，供 `cursor_unioned_public_trades` 等按时间戳回放。
+    ///
+    /// 通过 [`Self::get_table_names`] 找到当天的各品种成交表，跳过没有数据的表，再按 [`Self::create_unioned_table`]
+    /// 以 `UNION ALL` 建表，合并表按 `timestamp` 排序。返回合并的品种表数量，当天没有任何非空的成交表时不建表并返回 0。
+    pub async fn build_unioned_trades_table(&self, exchange: &str, instrument: &str, date: &str) -> Result<usize, Error>
+    {
+        let database = self.construct_database_name(exchange, instrument, "trades");
+        let union_table = self.construct_union_table_name(exchange, instrument, "trades", date);
+        let candidates = self.union_source_tables(&self.get_table_names(&database).await, date);
+
+        let mut source_tables = Vec::with_capacity(candidates.len());
+        for (i, table_name) in candidates.iter().enumerate() {
+            let rows = self.fetch_one_with_retry::<u64>(&format!("SELECT count() FROM {}.{}", database, table_name)).await?;
+            if rows == 0 {
+                info!("Skipping empty table {}.{}", database, table_name);
+            }
+            else {
+                source_tables.push(table_name.clone());
+            }
+            info!("Checked {} / {} tables for {}", i + 1, candidates.len(), union_table);
+        }
+
+        if source_tables.is_empty() {
+            warn!("No non-empty trade tables found for {} in {}, skipping {}.", date, database, union_table);
+            return Ok(0);
+        }
+        self.create_unioned_table(&database, &union_table, &source_tables, true).await?;
+        Ok(source_tables.len())
+    }
+
     pub async fn retrieve_all_trades(&self, exchange: &str, instrument: &str, date: &str, base: &str, quote: &str) -> Result<Vec<MarketTrade>, Error>
     {
         let database_name = self.construct_database_name(exchange, instrument, "trades");
@@ -215,7 +382,7 @@ impl ClickHouseClient
                                                  .build();
 
         info!("Constructed query {}", query);
-        let trade_datas = self.client.read().await.query(&query).fetch_all::<MarketTrade>().await?;
+        let trade_datas = self.fetch_all_with_retry::<MarketTrade>(&query).await?;
         Ok(trade_datas)
     }
 
@@ -230,7 +397,7 @@ impl ClickHouseClient
                                                  .limit(1)
                                                  .build();
         info!("Constructed query :  {}", query);
-        let trade_data = self.client.read().await.query(&query).fetch_one::<MarketTrade>().await?;
+        let trade_data = self.fetch_one_with_retry::<MarketTrade>(&query).await?;
         Ok(trade_data)
     }
 
@@ -240,7 +407,7 @@ impl ClickHouseClient
         let database = self.construct_database_name(exchange, instrument, "trades");
         let query = format!("SELECT exchange, symbol, side, price, timestamp, amount FROM {}.{} ORDER BY timestamp", database, table_name);
         info!("Executing query: {}", query);
-        let trade_datas = self.client.read().await.query(&query).fetch_all::<MarketTrade>().await?;
+        let trade_datas = self.fetch_all_with_retry::<MarketTrade>(&query).await?;
         Ok(trade_datas)
     }
 
@@ -258,8 +425,8 @@ impl ClickHouseClient
 
         // info!("Constructed query {}", query);
 
-        // 获取 ClickHouse 客户端的只读引用
-        let client_ref = self.client.read().await;
+        // 从连接池取出一个客户端
+        let client_ref = self.pooled_client();
 
         // 执行查询并获取游标
         client_ref.query(&query).fetch::<MarketTrade>()
@@ -279,13 +446,51 @@ impl ClickHouseClient
 
         info!("Constructed query {}", query);
 
-        // 获取 ClickHouse 客户端的只读引用
-        let client_ref = self.client.read().await;
+        // 从连接池取出一个客户端
+        let client_ref = self.pooled_client();
 
         // 执行查询并获取游标
         client_ref.query(&query).fetch::<MarketTrade>()
     }
 
+    /// 按时间戳升序读取某日多个品种的成交，并按全局时间戳归并为一条行情流，归并与同时间戳的排序规则见 [`MultiSymbolFeed`]。
+    ///
+    /// `symbols` 为 (基础货币, 报价货币) 列表，每个品种各自打开一个游标。
+    pub async fn cursor_multi_symbol(&self, exchange: &str, instrument: &str, date: &str, symbols: Vec<(String, String)>) -> Result<MultiSymbolFeed>
+    {
+        let database_name = self.construct_database_name(exchange, instrument, "trades");
+        let client_ref = self.pooled_client();
+
+        let mut cursors = Vec::with_capacity(symbols.len());
+        for (base, quote) in symbols {
+            let table_name = self.construct_table_name(exchange, instrument, "trades", date, &base, &quote);
+            let query = ClickHouseQueryBuilder::new().select("exchange, symbol, side, price, timestamp, amount")
+                                                     .from(&database_name, &table_name)
+                                                     .order("timestamp", Some("ASC"))
+                                                     .build();
+            info!("Constructed query {}", query);
+            let cursor = client_ref.query(&query).fetch::<MarketTrade>()?;
+            cursors.push((base, quote, cursor));
+        }
+        Ok(MultiSymbolFeed::from_cursors(cursors))
+    }
+
+    /// 按时间戳升序读取某日的聚合成交，存储在 `agg_trades` 频道的合并表中，与逐笔成交的区别见 [`ClickhouseAggTrade`]。
+    pub async fn cursor_unioned_agg_trades(&self, exchange: &str, instrument: &str, date: &str) -> Result<RowCursor<ClickhouseAggTrade>>
+    {
+        let database_name = self.construct_database_name(exchange, instrument, "agg_trades");
+        let table_name = self.construct_union_table_name(exchange, instrument, "agg_trades", date);
+
+        let query = ClickHouseQueryBuilder::new().select("exchange, symbol, side, price, timestamp, amount, agg_trade_id, first_trade_id, last_trade_id")
+                                                 .from(&database_name, &table_name)
+                                                 .order("timestamp", Some("ASC"))
+                                                 .build();
+
+        info!("Constructed query {}", query);
+
+        self.pooled_client().query(&query).fetch::<ClickhouseAggTrade>()
+    }
+
     pub async fn cursor_unioned_public_trades_for_test(&self, exchange: &str, instrument: &str, date: &str) -> Result<RowCursor<MarketTrade>>
     {
         // 构造数据库名称和表名称
@@ -301,19 +506,92 @@ impl ClickHouseClient
 
         info!("Constructed query {}", query);
 
-        // 获取 ClickHouse 客户端的只读引用
-        let client_ref = self.client.read().await;
+        // 从连接池取出一个客户端
+        let client_ref = self.pooled_client();
 
         // 执行查询并获取游标
         client_ref.query(&query).fetch::<MarketTrade>()
     }
 
+    /// 把某日的逐笔成交按 `interval` 聚合为 K 线的查询语句，见 [`Self::cursor_klines`]。
+    pub fn kline_query(&self, exchange: &str, instrument: &str, date: &str, base: &str, quote: &str, interval: KlineInterval) -> String
+    {
+        let database_name = self.construct_database_name(exchange, instrument, "trades");
+        let table_name = self.construct_table_name(exchange, instrument, "trades", date, base, quote);
+        let open_ts = format!("toUnixTimestamp64Milli(toStartOfInterval(fromUnixTimestamp64Milli(timestamp, 'UTC'), {}))", interval.clickhouse_interval());
+        let query = ClickHouseQueryBuilder::new().select(&format!("{} AS open_ts, argMin(price, timestamp) AS open, max(price) AS high, min(price) AS low, argMax(price, timestamp) AS close, toFloat64(sum(amount)) AS volume",
+                                                                  open_ts))
+                                                 .from(&database_name, &table_name)
+                                                 .build();
+        format!("{} GROUP BY open_ts ORDER BY open_ts ASC", query)
+    }
+
+    /// 按周期起点升序读取某日的 K 线。
+    ///
+    /// K 线由 ClickHouse 在服务端用 `toStartOfInterval` 把逐笔成交表聚合得到，不需要在客户端遍历每一笔成交；
+    /// 没有成交的周期不会产生 K 线。
+    pub async fn cursor_klines(&self, exchange: &str, instrument: &str, date: &str, base: &str, quote: &str, interval: KlineInterval) -> Result<RowCursor<ClickhouseKline>>
+    {
+        let query = self.kline_query(exchange, instrument, date, base, quote, interval);
+        info!("Constructed query {}", query);
+
+        self.pooled_client().query(&query).fetch::<ClickhouseKline>()
+    }
+
+    /// 按时间戳升序查询 `[start, end)` 毫秒区间内的强平单，表结构见 [`MarketLiquidation`]。
+    ///
+    /// 返回的游标可以通过 [`LiquidationFeed::from_cursor`](crate::common::datafeed::simulated_event::LiquidationFeed::from_cursor) 与成交流合并回放。
+    pub async fn query_liquidations(&self, exchange: &str, instrument: &str, start: i64, end: i64) -> Result<RowCursor<MarketLiquidation>>
+    {
+        let database_name = self.construct_database_name(exchange, instrument, "liquidations");
+        let query = ClickHouseQueryBuilder::new().select("exchange, symbol, side, price, timestamp, amount")
+                                                 .from(&database_name, &database_name)
+                                                 .where_clause(&format!("timestamp >= {} AND timestamp < {}", start, end))
+                                                 .order("timestamp", Some("ASC"))
+                                                 .build();
+
+        info!("Constructed query {}", query);
+
+        self.pooled_client().query(&query).fetch::<MarketLiquidation>()
+    }
+
+    /// 查询某个金融工具在指定日期的日内成交量分布，用于 VWAP 执行算法按历史成交量分配子订单。
+    ///
+    /// 成交量按 UTC 日内时间以 `bucket_minutes` 分桶后归一化。结果按 (金融工具, 日期, 分桶) 缓存，
+    /// 同一组参数只查询一次；当天没有成交数据时退化为均匀分布。
+    pub async fn query_volume_profile(&self, exchange: &str, instrument: &str, date: &str, base: &str, quote: &str, bucket_minutes: u32) -> Result<VolumeProfile, Error>
+    {
+        let symbol = format!("{}{}", base.to_uppercase(), quote.to_uppercase());
+        let key = (exchange.to_string(), instrument.to_string(), symbol, date.to_string(), bucket_minutes);
+        if let Some(profile) = self.volume_profiles.read().await.get(&key) {
+            return Ok(profile.clone());
+        }
+
+        let database_name = self.construct_database_name(exchange, instrument, "trades");
+        let table_name = self.construct_table_name(exchange, instrument, "trades", date, base, quote);
+        let bucket_millis = bucket_minutes.max(1) as i64 * 60_000;
+        let query = ClickHouseQueryBuilder::new().select(&format!("toUInt32(intDiv(timestamp % {}, {})) AS bucket, toFloat64(sum(amount)) AS volume", MILLIS_PER_DAY, bucket_millis))
+                                                 .from(&database_name, &table_name)
+                                                 .build();
+        let query = format!("{} GROUP BY bucket ORDER BY bucket", query);
+        info!("Constructed query {}", query);
+
+        let buckets = self.fetch_all_with_retry::<VolumeBucket>(&query).await?;
+        if buckets.is_empty() {
+            warn!("No trades found in {}.{}, falling back to uniform volume profile.", database_name, table_name);
+        }
+
+        let profile = VolumeProfile::from_buckets(bucket_minutes, &buckets);
+        self.volume_profiles.write().await.insert(key, profile.clone());
+        Ok(profile)
+    }
+
     pub async fn optimize_table(&self, table_path: &str) -> Result<(), Error>
     {
         let optimize_query = format!("OPTIMIZE TABLE {}", table_path);
         info!("Sending optimize query for table: {}", table_path);
         // 执行优化查询
-        self.client.read().await.query(&optimize_query).execute().await?;
+        self.pooled_client().query(&optimize_query).execute().await?;
         info!("Table {} has been optimized.", table_path);
         Ok(())
     }
@@ -390,7 +668,7 @@ impl ClickHouseClient
         }
 
         // 执行插入数据的查询
-        self.client.read().await.query(&final_query).execute().await?;
+        self.pooled_client().query(&final_query).execute().await?;
 
         if report_progress {
             info!("Data inserted into {}.{} successfully.", database, target_table_name);
@@ -405,12 +683,81 @@ impl ClickHouseClient
         let create_db_query = format!("CREATE DATABASE IF NOT EXISTS {}", database);
 
         // 执行创建数据库的SQL查询
-        self.client.read().await.query(&create_db_query).execute().await?;
+        self.pooled_client().query(&create_db_query).execute().await?;
 
         info!("Database {} created successfully or already exists", database);
         Ok(())
     }
 
+    /// 存放逐笔成交的表的建表语句，字段与 [`MarketTrade`] 一一对应，按 `timestamp` 排序。
+    pub fn trades_table_schema(&self, database: &str, table: &str) -> String
+    {
+        format!(
+                "CREATE TABLE IF NOT EXISTS {}.{} ( \
+        exchange String, \
+        symbol String, \
+        side String, \
+        price Float64, \
+        timestamp Int64, \
+        amount Float64 \
+    )   ENGINE = MergeTree() \
+        PARTITION BY toYYYYMMDD(toDate(intDiv(timestamp, 1000))) \
+        ORDER BY (timestamp, symbol)",
+                database, table
+        )
+    }
+
+    /// 按 `batch_size` 分批把成交写入 `database.table`，每批一次 `INSERT`，返回写入的行数。
+    ///
+    /// `create_if_missing` 为 `true` 时先按 [`Self::trades_table_schema`] 创建库与表（已存在时不做改动），
+    /// 用于给测试库灌入数据或保存模拟器生成的成交流。某一批写入失败时返回该错误，此前的批次已经写入。
+    pub async fn insert_trades_batched(&self, database: &str, table: &str, trades: &[MarketTrade], batch_size: usize, create_if_missing: bool) -> Result<usize, Error>
+    {
+        if create_if_missing {
+            self.create_database_if_not_exists(database).await?;
+            self.pooled_client().query(&self.trades_table_schema(database, table)).execute().await?;
+        }
+
+        let table_path = format!("{}.{}", database, table);
+        let client_ref = self.pooled_client();
+        let mut written = 0;
+        for batch in trades.chunks(batch_size.max(1)) {
+            let mut insert = client_ref.insert::<MarketTrade>(&table_path)?;
+            for trade in batch {
+                insert.write(trade).await?;
+            }
+            insert.end().await?;
+            written += batch.len();
+            info!("Inserted {} / {} trades into {}", written, trades.len(), table_path);
+        }
+        Ok(written)
+    }
+
+    /// 创建存放强平单的库与表，字段见 [`MarketLiquidation`]。
+    pub async fn create_liquidations_table(&self, exchange: &str, instrument: &str) -> Result<(), Error>
+    {
+        let database = self.construct_database_name(exchange, instrument, "liquidations");
+        self.create_database_if_not_exists(&database).await?;
+
+        let create_table_query = format!(
+                                         "CREATE TABLE IF NOT EXISTS {}.{} ( \
+        exchange String, \
+        symbol String, \
+        side String, \
+        price Float64, \
+        timestamp Int64, \
+        amount Float64 \
+    )   ENGINE = ReplacingMergeTree() \
+        ORDER BY (symbol, timestamp, side, price, amount)",
+                                         database, database
+        );
+
+        self.pooled_client().query(&create_table_query).execute().await?;
+
+        info!("Table {}.{} created successfully", database, database);
+        Ok(())
+    }
+
     pub async fn create_users_table(&self, database: &str) -> Result<(), Error>
     {
         // 首先创建数据库（如果不存在）
@@ -432,7 +779,7 @@ impl ClickHouseClient
         );
 
         // 执行创建表的SQL查询
-        self.client.read().await.query(&create_table_query).execute().await?;
+        self.pooled_client().query(&create_table_query).execute().await?;
 
         info!("Table {}.user_info created successfully", database);
         Ok(())
@@ -451,6 +798,58 @@ mod tests
         ClickHouseClient::new()
     }
 
+    #[tokio::test]
+    async fn test_pool_size_and_exponential_backoff()
+    {
+        let client = ClickHouseClient::new_with_config(ClickHouseConfig { pool_size: 0, ..Default::default() });
+        assert_eq!(client.pool.len(), 1);
+        let client = ClickHouseClient::new_with_config(ClickHouseConfig { pool_size: 3, ..Default::default() });
+        assert_eq!(client.pool.len(), 3);
+        for expected in [0, 1, 2, 0] {
+            assert_eq!(client.next_client.load(Ordering::Relaxed) % 3, expected);
+            client.pooled_client();
+        }
+
+        let config = ClickHouseConfig::default();
+        assert_eq!(config.backoff(0), Duration::from_millis(100));
+        assert_eq!(config.backoff(3), Duration::from_millis(800));
+    }
+
+    #[tokio::test]
+    async fn test_only_transient_errors_are_retried()
+    {
+        let client = ClickHouseClient::new_with_config(ClickHouseConfig { max_retries: 3,
+                                                                          initial_backoff_ms: 1,
+                                                                          ..Default::default() });
+
+        let attempts = AtomicUsize::new(0);
+        let result = client.with_retry(|_| {
+                               let attempt = attempts.fetch_add(1, Ordering::Relaxed);
+                               async move { if attempt < 2 { Err(Error::TimedOut) } else { Ok(attempt) } }
+                           })
+                           .await;
+        assert_eq!(result.unwrap(), 2);
+
+        let attempts = AtomicUsize::new(0);
+        let result = client.with_retry(|_| {
+                               attempts.fetch_add(1, Ordering::Relaxed);
+                               async { Err::<(), _>(Error::RowNotFound) }
+                           })
+                           .await;
+        assert!(matches!(result, Err(Error::RowNotFound)));
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+
+        // 超过最大重试次数后返回最后一次的错误
+        let attempts = AtomicUsize::new(0);
+        let result = client.with_retry(|_| {
+                               attempts.fetch_add(1, Ordering::Relaxed);
+                               async { Err::<(), _>(Error::TimedOut) }
+                           })
+                           .await;
+        assert!(matches!(result, Err(Error::TimedOut)));
+        assert_eq!(attempts.load(Ordering::Relaxed), 4);
+    }
+
     #[tokio::test]
     async fn test_construct_table_name()
     {
@@ -458,4 +857,61 @@ mod tests
         let table_name = client.construct_table_name("binance", "futures", "trades", "2024_08_24", "BTC", "USDT");
         assert_eq!(table_name, "binance_futures_trades_2024_08_24_BTCUSDT");
     }
+
+    #[tokio::test]
+    async fn test_kline_query_aggregates_trades_by_interval()
+    {
+        let client = setup_clickhouse_client().await;
+        let query = client.kline_query("binance", "futures", "2024_08_24", "BTC", "USDT", KlineInterval::FiveMinutes);
+        assert!(query.contains("toStartOfInterval(fromUnixTimestamp64Milli(timestamp, 'UTC'), INTERVAL 5 MINUTE)"));
+        assert!(query.contains("FROM binance_futures_trades.binance_futures_trades_2024_08_24_BTCUSDT"));
+        assert!(query.ends_with("GROUP BY open_ts ORDER BY open_ts ASC"));
+    }
+
+    #[tokio::test]
+    async fn test_trades_table_schema_matches_market_trade_columns()
+    {
+        let client = setup_clickhouse_client().await;
+        let schema = client.trades_table_schema("synthetic", "trades");
+        assert!(schema.starts_with("CREATE TABLE IF NOT EXISTS synthetic.trades ("));
+        for column in ["exchange String", "symbol String", "side String", "price Float64", "timestamp Int64", "amount Float64"] {
+            assert!(schema.contains(column), "missing column {}", column);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_empty_trades_writes_nothing()
+    {
+        let client = setup_clickhouse_client().await;
+        assert_eq!(client.insert_trades_batched("synthetic", "trades", &[], 1_000, false).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_union_source_tables_exclude_other_dates_and_union_tables()
+    {
+        let client = setup_clickhouse_client().await;
+        let table_names = vec!["binance_futures_trades_2024_05_05_ETHUSDT".to_string(),
+                               "binance_futures_trades_union_2024_05_05".to_string(),
+                               "binance_futures_trades_2024_05_06_BTCUSDT".to_string(),
+                               "binance_futures_trades_2024_05_05_BTCUSDT".to_string()];
+
+        assert_eq!(client.union_source_tables(&table_names, "2024-05-05"),
+                   vec!["binance_futures_trades_2024_05_05_BTCUSDT".to_string(), "binance_futures_trades_2024_05_05_ETHUSDT".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_query_volume_profile_uses_cache()
+    {
+        let client = setup_clickhouse_client().await;
+        let cached = VolumeProfile { bucket_minutes: 720,
+                                     weights: vec![0.3, 0.7] };
+        client.volume_profiles
+              .write()
+              .await
+              .insert(("binance".to_string(), "futures".to_string(), "BTCUSDT".to_string(), "2024-08-24".to_string(), 720), cached.clone());
+
+        // 命中缓存时不会访问 ClickHouse
+        let profile = client.query_volume_profile("binance", "futures", "2024-08-24", "btc", "usdt", 720).await.unwrap();
+        assert_eq!(profile, cached);
+    }
 }
@@ -3,18 +3,21 @@ use async_trait::async_trait;
 use mpsc::UnboundedSender;
 use oneshot::Sender;
 use tokio::sync::{mpsc, mpsc::UnboundedReceiver, oneshot};
-use HourglassClientEvent::{CancelOrders, CancelOrdersAll, FetchOrdersOpen, FetchTokenBalances, OpenOrders};
+use HourglassClientEvent::{AmendOrders, CancelOrders, CancelOrdersAll, FetchOrdersOpen, FetchTokenBalances, OpenOrders};
 
 use crate::{
     common::{
-        account_positions::{AccountPositions, Position, PositionConfig},
+        account_positions::{positions_snapshot::AccountPositionsSnapshot, AccountPositions, Position, PositionConfig},
         balance::TokenBalance,
         instrument::Instrument,
         order::{
-            states::{cancelled::Cancelled, open::Open, request_cancel::RequestCancel},
+            identification::oco_group_id::OcoGroupId,
+            states::{cancelled::Cancelled, open::Open, request_amend::RequestAmend, request_cancel::RequestCancel},
+            stop_order::StopOrder,
             Order,
         },
         token::Token,
+        trade::ClientTrade,
     },
     hourglass::{clickhouse_api::datatype::clickhouse_trade_data::MarketTrade, config_request::ConfigurationRequest},
     network::login::{LoginRequest, LogoutRequest, RegisterRequest},
@@ -33,11 +36,15 @@ pub struct HourglassClient
 // 定义类型别名以简化复杂的类型
 pub type OpenOrderResults = Vec<Result<Order<Open>, ExchangeError>>;
 pub type CancelOrderResults = Vec<Result<Order<Cancelled>, ExchangeError>>;
+pub type AmendOrderResults = Vec<Result<Order<Open>, ExchangeError>>;
 pub type ConfigureInstrumentsResults = Vec<Result<PositionConfig, ExchangeError>>;
 pub type RequestOpenOrders = (Vec<Order<RequestOpen>>, Sender<OpenOrderResults>);
 pub type RequestCancelOrders = (Vec<Order<RequestCancel>>, Sender<CancelOrderResults>);
+pub type RequestAmendOrders = (Vec<Order<RequestAmend>>, Sender<AmendOrderResults>);
 pub type DepositResults = Result<Vec<TokenBalance>, ExchangeError>;
 pub type DepositRequest = (Vec<(Token, f64)>, Sender<DepositResults>);
+pub type OpenStopOrderResults = Vec<Result<StopOrder, ExchangeError>>;
+pub type CancelStopOrderResults = Vec<Result<StopOrder, ExchangeError>>;
 
 // 模拟交易所客户端可向模拟交易所发送的命令
 #[derive(Debug)]
@@ -50,11 +57,18 @@ pub enum HourglassClientEvent
     FetchLongPosition(Instrument, Sender<Result<Option<Position>, ExchangeError>>),
     FetchShortPosition(Instrument, Sender<Result<Option<Position>, ExchangeError>>),
     FetchAllPositions(Sender<Result<AccountPositions, ExchangeError>>),
+    FetchPositions(Sender<Result<AccountPositionsSnapshot, ExchangeError>>),
+    FetchTrades(Instrument, Sender<Result<Vec<ClientTrade>, ExchangeError>>),
     OpenOrders(RequestOpenOrders),
+    OpenStopOrders(Vec<StopOrder>, Sender<OpenStopOrderResults>),
+    CancelStopOrders(Vec<Order<RequestCancel>>, Sender<CancelStopOrderResults>),
+    OpenOco(Box<(Order<RequestOpen>, Order<RequestOpen>)>, Sender<Result<OcoGroupId, ExchangeError>>),
     CancelOrders(RequestCancelOrders),
     CancelOrdersAll(Sender<Result<Vec<Order<Cancelled>>, ExchangeError>>),
+    AmendOrders(RequestAmendOrders),
     ConfigureInstruments(Vec<ConfigurationRequest>, Sender<ConfigureInstrumentsResults>),
     LetItRoll, // Tell the system to send the next datafeed.
+    Shutdown,  // 处理完已排队的事件并收尾后退出事件循环
     Register(RegisterRequest),
     Login(LoginRequest),
     Logout(LogoutRequest),
@@ -109,6 +123,24 @@ impl ClientExecution for HourglassClient
         response_rx.await.expect("[HourglassClient] : Failed to receive FetchAllPositions response")
     }
 
+    async fn fetch_positions(&self) -> Result<AccountPositionsSnapshot, ExchangeError>
+    {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.client_event_tx
+            .send(HourglassClientEvent::FetchPositions(response_tx))
+            .expect("[HourglassClient] : Failed to send FetchPositions request");
+        response_rx.await.expect("[HourglassClient] : Failed to receive FetchPositions response")
+    }
+
+    async fn fetch_trades(&self, instrument: Instrument) -> Result<Vec<ClientTrade>, ExchangeError>
+    {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.client_event_tx
+            .send(HourglassClientEvent::FetchTrades(instrument, response_tx))
+            .expect("[HourglassClient] : Failed to send FetchTrades request");
+        response_rx.await.expect("[HourglassClient] : Failed to receive FetchTrades response")
+    }
+
     //  FetchLongPosition 的实现
     async fn fetch_long_position(&self, instrument: Instrument) -> Result<Option<Position>, ExchangeError>
     {
@@ -151,6 +183,17 @@ impl ClientExecution for HourglassClient
         response_rx.await.expect("Hourglass exchange is currently offline - Failed to receive CancelOrders response")
     }
 
+    async fn amend_orders(&self, amend_requests: Vec<Order<RequestAmend>>) -> Vec<Result<Order<Open>, ExchangeError>>
+    {
+        let (response_tx, response_rx) = oneshot::channel();
+        // 向模拟交易所发送修改订单的请求。
+        self.client_event_tx
+            .send(AmendOrders((amend_requests, response_tx)))
+            .expect("Hourglass exchange is currently offline - Failed to send AmendOrders request");
+        // 从模拟交易所接收修改订单的响应。
+        response_rx.await.expect("Hourglass exchange is currently offline - Failed to receive AmendOrders response")
+    }
+
     async fn cancel_orders_all(&self) -> Result<Vec<Order<Cancelled>>, ExchangeError>
     {
         // 创建一个 oneshot 通道以与模拟交易所通信。
@@ -191,6 +234,14 @@ impl ClientExecution for HourglassClient
 
 impl HourglassClient
 {
+    /// 请求交易所安全关闭，交易所会先处理完此前已发送的请求。
+    pub fn request_shutdown(&self) -> Result<(), ExchangeError>
+    {
+        self.client_event_tx
+            .send(HourglassClientEvent::Shutdown)
+            .map_err(|_| ExchangeError::Hourglass("Exchange is currently offline".into()))
+    }
+
     pub async fn listen_for_market_data(&mut self) -> Option<MarketTrade>
     {
         if let Some(market_event) = self.market_event_rx.recv().await {
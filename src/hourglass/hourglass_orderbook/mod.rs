@@ -5,7 +5,7 @@ use crate::common::order::{identification::OrderId, states::open::Open, Order};
 ///    - **部分成交 (Partial Fill)**: 目前的代码已经考虑了部分成交的情况，但你可以进一步优化部分成交的逻辑。例如，当一个订单被部分成交后，其剩余部分是否应该立即与下一个层级的订单继续撮合，或者应该优先处理其他等待中的订单。
 ///    - **优先级撮合**: 当有多个订单在同一价格层级时，可以实现基于时间戳的优先级撮合（即更早提交的订单优先成交），以更接近真实市场的逻辑。
 ///
-/// ### 2. **订单过期 (Order Expiration)**
+/// ### 2. **订单过期 (Order Expiration)** [DONE]
 ///   - **限时订单**: 增加订单过期时间的概念，某些订单可能只在一段时间内有效（如5分钟内有效），如果在此期间未成交则自动撤销。你可以在 Order 结构体中增加一个过期时间字段，并在 process_trades 方法中检查并处理过期订单。
 ///
 /// ### 3. **订单取消 (Order Cancellation)**
@@ -34,7 +34,10 @@ use crate::common::order::{identification::OrderId, states::open::Open, Order};
 use crate::common::Side;
 use rayon::{iter::IntoParallelRefIterator, prelude::IndexedParallelIterator};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PriceLevel
@@ -60,14 +63,15 @@ impl PriceLevel
         self.orders.pop_front() // 从队列头部移除并返回最早的订单
     }
 
-    fn remove_expired_orders(&mut self, expiration_times: &HashMap<OrderId, i64>, current_time: i64)
+    fn remove_orders(&mut self, order_ids: &HashSet<OrderId>, removed: &mut Vec<Order<Open>>)
     {
         self.orders.retain(|order| {
-                       if let Some(&expire_time) = expiration_times.get(&order.state.id) {
-                           expire_time > current_time // 保留未过期的订单
+                       if order_ids.contains(&order.state.id) {
+                           removed.push(order.clone());
+                           false
                        }
                        else {
-                           true // 如果订单没有指定过期时间，则保留
+                           true
                        }
                    });
     }
@@ -79,9 +83,15 @@ pub struct HourglassOrderBook
     pub bid_levels: Vec<PriceLevel>,                // 买单簿
     pub ask_levels: Vec<PriceLevel>,                // 卖单簿
     pub max_levels: usize,                          // 允许的最大层级数量
-    pub expiration_registry: HashMap<OrderId, i64>, // 订单ID与过期时间的映射
+    pub expiration_registry: HashMap<OrderId, i64>, // 订单ID与过期时间的映射，是过期时间的唯一可信来源
+    // 按 (过期时间, 订单ID) 排序的最小堆。撤单、成交或重设过期时间时不从堆中删除，
+    // 而是在弹出时与 `expiration_registry` 比对，不一致的条目直接丢弃（惰性删除）。
+    expiry_heap: BinaryHeap<Reverse<(i64, OrderId)>>,
 }
 
+/// 堆中失效条目超过有效条目的倍数时重建堆，避免长期撤单导致堆无限增长。
+const EXPIRY_HEAP_COMPACTION_RATIO: usize = 2;
+
 impl HourglassOrderBook
 {
     pub fn new(max_levels: usize) -> Self
@@ -89,12 +99,62 @@ impl HourglassOrderBook
         Self { bid_levels: Vec::new(),
                ask_levels: Vec::new(),
                max_levels,
-               expiration_registry: HashMap::new() }
+               expiration_registry: HashMap::new(),
+               expiry_heap: BinaryHeap::new() }
     }
 
+    /// 设置订单的过期时间，重复设置时以最后一次为准。订单在 `current_time >= expire_ts` 时过期。
     pub fn set_order_expiration(&mut self, order_id: OrderId, expire_ts: i64)
     {
-        self.expiration_registry.insert(order_id, expire_ts); // 设置订单的过期时间
+        self.expiration_registry.insert(order_id.clone(), expire_ts);
+        self.expiry_heap.push(Reverse((expire_ts, order_id)));
+        self.compact_expiry_heap();
+    }
+
+    /// 移除已过期的订单并返回它们。
+    ///
+    /// 只弹出堆顶过期时间不晚于 `current_time` 的条目，没有订单过期时开销为 O(1)；
+    /// 有订单过期时还需要遍历所有价格层级移除这些订单，开销与挂单总数相关。
+    /// 账户实际使用的挂单簿见 [`OpenOrdersBook::take_expired_orders`](crate::hourglass::open_orders_book::OpenOrdersBook::take_expired_orders)。
+    pub fn sweep_expired(&mut self, current_time: i64) -> Vec<Order<Open>>
+    {
+        let mut expired_ids = HashSet::new();
+        while let Some(Reverse((expire_ts, _))) = self.expiry_heap.peek() {
+            if *expire_ts > current_time {
+                break;
+            }
+            let Reverse((expire_ts, order_id)) = self.expiry_heap.pop().unwrap();
+            // 已撤单、已成交或过期时间被重设的订单在注册表中不存在或时间不一致，跳过
+            if self.expiration_registry.get(&order_id) == Some(&expire_ts) {
+                self.expiration_registry.remove(&order_id);
+                expired_ids.insert(order_id);
+            }
+        }
+
+        let mut expired = Vec::with_capacity(expired_ids.len());
+        if expired_ids.is_empty() {
+            return expired;
+        }
+        for levels in [&mut self.bid_levels, &mut self.ask_levels] {
+            for level in levels.iter_mut() {
+                level.remove_orders(&expired_ids, &mut expired);
+            }
+            levels.retain(|level| !level.orders.is_empty());
+        }
+        expired
+    }
+
+    /// 堆中的条目数（包含尚未被惰性删除的失效条目）。
+    pub fn pending_expiry_entries(&self) -> usize
+    {
+        self.expiry_heap.len()
+    }
+
+    fn compact_expiry_heap(&mut self)
+    {
+        if self.expiry_heap.len() > EXPIRY_HEAP_COMPACTION_RATIO * self.expiration_registry.len() + 64 {
+            self.expiry_heap = self.expiration_registry.iter().map(|(order_id, expire_ts)| Reverse((*expire_ts, order_id.clone()))).collect();
+        }
     }
 
     pub fn insert_order(&mut self, order: Order<Open>)
@@ -130,12 +190,7 @@ impl HourglassOrderBook
     pub fn process_trades(&mut self, current_time: i64)
     {
         // 清理过期的订单
-        for level in &mut self.bid_levels {
-            level.remove_expired_orders(&self.expiration_registry, current_time);
-        }
-        for level in &mut self.ask_levels {
-            level.remove_expired_orders(&self.expiration_registry, current_time);
-        }
+        self.sweep_expired(current_time);
 
         // 如果买单簿或卖单簿为空，则无法进行撮合
         if self.bid_levels.is_empty() || self.ask_levels.is_empty() {
@@ -158,14 +213,20 @@ impl HourglassOrderBook
                     buy_order.state.size -= executed_quantity;
                     sell_order.state.size -= executed_quantity;
 
-                    // 如果买单还有剩余数量，将其重新添加到买单簿的相应层级
+                    // 如果买单还有剩余数量，将其重新添加到买单簿的相应层级，否则注销其过期时间
                     if buy_order.state.size > 0.0 {
                         buy_level.add_order(buy_order);
                     }
-                    // 如果卖单还有剩余数量，将其重新添加到卖单簿的相应层级
+                    else {
+                        self.expiration_registry.remove(&buy_order.state.id);
+                    }
+                    // 如果卖单还有剩余数量，将其重新添加到卖单簿的相应层级，否则注销其过期时间
                     if sell_order.state.size > 0.0 {
                         sell_level.add_order(sell_order);
                     }
+                    else {
+                        self.expiration_registry.remove(&sell_order.state.id);
+                    }
                 }
                 else {
                     // 如果买单价格小于卖单价格，则无法成交，将订单重新放回原层级
@@ -195,6 +256,7 @@ impl HourglassOrderBook
     // 取消订单 NOTE 注意和Account模块的兼容性
     pub fn cancel_order(&mut self, order_id: OrderId) -> Option<Order<Open>>
     {
+        self.expiration_registry.remove(&order_id);
         for levels in [&mut self.bid_levels, &mut self.ask_levels].iter_mut() {
             for level in levels.iter_mut() {
                 if let Some(pos) = level.orders.par_iter().position_any(|order| order.state.id == order_id) {
@@ -205,3 +267,74 @@ impl HourglassOrderBook
         None
     }
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_utils::create_test_order_open;
+
+    fn order(id: u64, side: Side, price: f64) -> Order<Open>
+    {
+        let mut order = create_test_order_open(side, price, 1.0);
+        order.state.id = OrderId(id);
+        order
+    }
+
+    #[test]
+    fn test_sweep_removes_only_expired_orders()
+    {
+        let mut book = HourglassOrderBook::new(10);
+        book.insert_order(order(1, Side::Buy, 100.0));
+        book.insert_order(order(2, Side::Buy, 100.0));
+        book.insert_order(order(3, Side::Sell, 110.0));
+        book.set_order_expiration(OrderId(1), 1_000);
+        book.set_order_expiration(OrderId(3), 2_000);
+
+        assert!(book.sweep_expired(999).is_empty());
+
+        let expired = book.sweep_expired(1_000);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].state.id, OrderId(1));
+        assert_eq!(book.bid_levels[0].orders.len(), 1);
+
+        // 卖单层级清空后被移除
+        assert_eq!(book.sweep_expired(5_000)[0].state.id, OrderId(3));
+        assert!(book.ask_levels.is_empty());
+        assert!(book.expiration_registry.is_empty());
+    }
+
+    #[test]
+    fn test_cancel_and_reschedule_are_lazily_deleted()
+    {
+        let mut book = HourglassOrderBook::new(10);
+        book.insert_order(order(1, Side::Buy, 100.0));
+        book.insert_order(order(2, Side::Buy, 100.0));
+        book.set_order_expiration(OrderId(1), 1_000);
+        book.set_order_expiration(OrderId(2), 1_000);
+
+        // 撤单后过期条目仍留在堆中，但弹出时会被丢弃
+        book.cancel_order(OrderId(1));
+        // 延后过期时间，旧条目失效
+        book.set_order_expiration(OrderId(2), 3_000);
+        assert_eq!(book.pending_expiry_entries(), 3);
+
+        assert!(book.sweep_expired(1_000).is_empty());
+        assert_eq!(book.pending_expiry_entries(), 1);
+        assert_eq!(book.bid_levels[0].orders.len(), 1);
+        assert_eq!(book.sweep_expired(3_000)[0].state.id, OrderId(2));
+    }
+
+    #[test]
+    fn test_filled_orders_are_unregistered()
+    {
+        let mut book = HourglassOrderBook::new(10);
+        book.insert_order(order(1, Side::Buy, 100.0));
+        book.insert_order(order(2, Side::Sell, 100.0));
+        book.set_order_expiration(OrderId(1), 1_000);
+        book.process_trades(0);
+
+        assert!(book.expiration_registry.is_empty());
+        assert!(book.sweep_expired(1_000).is_empty());
+    }
+}
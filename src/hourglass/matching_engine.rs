@@ -0,0 +1,645 @@
+use crate::{
+    common::{
+        order::{identification::OrderId, order_instructions::OrderInstruction, states::open::Open, Order, OrderRole},
+        Side,
+    },
+    hourglass::{
+        account::account_config::{FillPricePolicy, MakerFillTrigger, MatchingAlgorithm, SlippageModel},
+        clickhouse_api::datatype::clickhouse_trade_data::MarketTrade,
+        open_orders_book::OpenOrdersBook,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+/// 按比例撮合时换算整手数与比较数量的容差，吸收浮点误差。
+pub const LOT_EPSILON: f64 = 1e-9;
+
+/// 撮合时使用的账户规则，由 `AccountConfig` 中对应的配置给出。
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MatchingRules
+{
+    pub fill_price_policy: FillPricePolicy, // 挂单的成交价规则
+    pub fill_trigger: MakerFillTrigger,     // 挂单被外部成交价触发的条件
+    pub slippage_model: SlippageModel,      // 主动成交的挂单的滑点模型
+}
+
+impl MatchingRules
+{
+    /// 挂单被外部成交撮合 `quantity` 时的成交价：先按 `fill_price_policy` 确定，
+    /// 以 Taker 身份挂出的订单（市价单与交叉的限价单）再按 `slippage_model` 向不利方向偏移。
+    /// 限价单的滑点不会越过限价：买单不高于 `order.state.price`，卖单不低于 `order.state.price`。
+    pub fn fill_price(&self, order: &Order<Open>, trade_price: f64, quantity: f64) -> f64
+    {
+        let fill_price = self.fill_price_policy.fill_price(order.state.price, trade_price);
+        if order.state.order_role == OrderRole::Maker {
+            return fill_price;
+        }
+        let slipped_price = self.slippage_model.slipped_price(order.side, fill_price, quantity);
+        match (order.instruction, order.side) {
+            | (OrderInstruction::Market, _) => slipped_price,
+            | (_, Side::Buy) => slipped_price.min(order.state.price),
+            | (_, Side::Sell) => slipped_price.max(order.state.price),
+        }
+    }
+}
+
+/// 撮合器对一笔挂单给出的一次成交。
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fill
+{
+    pub order: Order<Open>, // 已记录本次成交后的挂单
+    pub price: f64,         // 成交价
+    pub quantity: f64,      // 成交数量
+}
+
+/// 可插拔的撮合算法：决定一笔外部 [`MarketTrade`] 的成交量如何分配给账户的挂单。
+///
+/// 实现者从 `book` 中选出被触发的挂单，调用 `record_fill` 记录成交并移除完全成交的挂单，返回每次成交的 [`Fill`]；
+/// 手续费、成交ID、余额与仓位的更新由账户统一处理，撮合器无需关心。实现时应遵守以下约定：
+///
+/// - 主动买单只能成交卖方挂单，主动卖单只能成交买方挂单；
+/// - 挂单的 `timestamp` 晚于外部成交时间时，说明它尚未到达交易所，不能参与撮合；
+/// - 成交价由 `rules.fill_price` 给出，是否触发由 `rules.fill_trigger` 判断；
+/// - 所有成交数量之和不能超过外部成交量。
+///
+/// 内置实现为 [`FifoMatcher`] 与 [`ProRataMatcher`]，可通过 `AccountConfig.matching_algorithms` 按金融工具选择；
+/// 自定义撮合器通过 `AccountBuilder::matching_engine` 按金融工具注册。撮合器需实现 `Clone`，以便随账户一起复制。
+pub trait MatchingEngine: MatchingEngineClone + Debug + Send + Sync
+{
+    fn match_trade(&mut self, book: &mut OpenOrdersBook, trade: &MarketTrade, rules: &MatchingRules) -> Vec<Fill>;
+}
+
+/// 复制 `Box<dyn MatchingEngine>`，对所有实现了 `Clone` 的撮合器自动实现。
+pub trait MatchingEngineClone
+{
+    fn clone_box(&self) -> Box<dyn MatchingEngine>;
+}
+
+impl<T> MatchingEngineClone for T where T: MatchingEngine + Clone + 'static
+{
+    fn clone_box(&self) -> Box<dyn MatchingEngine>
+    {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn MatchingEngine>
+{
+    fn clone(&self) -> Self
+    {
+        self.clone_box()
+    }
+}
+
+/// 价格优先、时间优先撮合：按价位由优到劣，同价位先到的挂单先成交。
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FifoMatcher;
+
+impl MatchingEngine for FifoMatcher
+{
+    fn match_trade(&mut self, book: &mut OpenOrdersBook, trade: &MarketTrade, rules: &MatchingRules) -> Vec<Fill>
+    {
+        match book.determine_matching_side(trade, rules.fill_trigger) {
+            | Some(side) => book.fifo_fills(side, trade, rules),
+            | None => Vec::new(),
+        }
+    }
+}
+
+/// 按比例撮合时，整手取整后剩余数量（余量）的分配方式。
+///
+/// - `LargestRemainder`: 按比例份额的小数部分由大到小逐手分配，小数部分相同时先到的挂单优先，为默认值。
+/// - `TimePriority`: 按到达先后分配，先到的挂单分满后再分给下一笔。
+/// - `LargestOrder`: 按剩余数量由大到小逐手分配，数量相同时先到的挂单优先。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum LeftoverAllocation
+{
+    #[default]
+    LargestRemainder,
+    TimePriority,
+    LargestOrder,
+}
+
+/// 按比例撮合：价格优先，同一价位的所有挂单按剩余数量比例分配外部成交量，与到达先后无关。部分期权、利率市场采用这种方式。
+///
+/// 分配以 `lot_size` 为单位进行：每笔挂单先得到按比例份额向下取整的整手数，低于 `min_allocation` 的份额归零，
+/// 剩余的整手数再按 `leftover` 分配，因此各笔分配之和恰好等于外部成交量（按整手计）。
+/// 余量分给一笔尚未分到数量的挂单时，一次分配 `min_allocation` 对应的手数（不超过挂单剩余数量与余量），避免重新产生过小的成交。
+/// 每笔分配不超过挂单的剩余数量；外部成交量中不足一手的零头按到达先后分给该价位的挂单。
+///
+/// 外部成交量足以吃掉整个价位时，该价位的挂单全部成交，剩余的成交量继续分配给下一价位。
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ProRataMatcher
+{
+    pub lot_size: f64,                // 分配的最小单位，各笔分配均为其整数倍，默认 1e-8
+    pub min_allocation: f64,          // 按比例分配的最小数量，低于该值的份额归零并计入余量，默认 0.0 即不限制
+    pub leftover: LeftoverAllocation, // 余量的分配方式
+}
+
+impl Default for ProRataMatcher
+{
+    fn default() -> Self
+    {
+        Self { lot_size: 1e-8,
+               min_allocation: 0.0,
+               leftover: LeftoverAllocation::default() }
+    }
+}
+
+impl ProRataMatcher
+{
+    /// 按 `lot_size` 换算的整手数，向下取整，不会换算出超过 `quantity` 的手数。
+    fn lots(&self, quantity: f64) -> u64
+    {
+        (quantity / self.lot_size + LOT_EPSILON).floor().max(0.0) as u64
+    }
+
+    /// 把数量为 `quantity` 的外部成交按比例分配给剩余数量为 `sizes` 的挂单，`sizes` 按到达先后排列。
+    ///
+    /// 返回每笔挂单分到的数量，均为 `lot_size` 的整数倍，总手数恰好等于 `quantity` 与 `sizes` 总和中较小者的手数。
+    pub fn allocate(&self, quantity: f64, sizes: &[f64]) -> Vec<f64>
+    {
+        let sizes: Vec<u64> = sizes.iter().map(|size| self.lots(*size)).collect();
+        self.allocate_lots(self.lots(quantity), &sizes).into_iter().map(|lots| lots as f64 * self.lot_size).collect()
+    }
+
+    fn allocate_lots(&self, quantity: u64, sizes: &[u64]) -> Vec<u64>
+    {
+        let total: u64 = sizes.iter().sum();
+        if quantity >= total {
+            return sizes.to_vec();
+        }
+
+        // 按比例份额向下取整，余数用于最大余数法
+        let min_lots = self.lots(self.min_allocation);
+        let mut allocations: Vec<u64> = Vec::with_capacity(sizes.len());
+        let mut remainders: Vec<u128> = Vec::with_capacity(sizes.len());
+        for &size in sizes {
+            let share = quantity as u128 * size as u128;
+            let lots = (share / total as u128) as u64;
+            allocations.push(if lots < min_lots { 0 } else { lots });
+            remainders.push(share % total as u128);
+        }
+
+        let mut priority: Vec<usize> = (0..sizes.len()).collect();
+        match self.leftover {
+            | LeftoverAllocation::LargestRemainder => priority.sort_by(|a, b| remainders[*b].cmp(&remainders[*a])),
+            | LeftoverAllocation::LargestOrder => priority.sort_by(|a, b| sizes[*b].cmp(&sizes[*a])),
+            | LeftoverAllocation::TimePriority => {}
+        }
+
+        // 总挂单量大于外部成交量，余量总能分完
+        let mut leftover = quantity - allocations.iter().sum::<u64>();
+        while leftover > 0 {
+            for &index in &priority {
+                let capacity = sizes[index] - allocations[index];
+                if leftover == 0 || capacity == 0 {
+                    continue;
+                }
+                let lots = match (self.leftover, allocations[index]) {
+                    | (LeftoverAllocation::TimePriority, _) => capacity,
+                    | (_, 0) => min_lots.max(1).min(capacity),
+                    | _ => 1,
+                }.min(leftover);
+                allocations[index] += lots;
+                leftover -= lots;
+            }
+        }
+        allocations
+    }
+}
+
+impl MatchingEngine for ProRataMatcher
+{
+    fn match_trade(&mut self, book: &mut OpenOrdersBook, trade: &MarketTrade, rules: &MatchingRules) -> Vec<Fill>
+    {
+        // 主动卖单只会成交买方挂单，主动买单只会成交卖方挂单
+        let (side, orders) = match trade.aggressor_side() {
+            | Some(Side::Sell) => (Side::Buy, &mut book.bids),
+            | Some(Side::Buy) => (Side::Sell, &mut book.asks),
+            | None => return Vec::new(),
+        };
+
+        let mut remaining = trade.amount;
+        let mut fills = Vec::new();
+        // 分配不足以完全成交的 FOK 挂单，不再参与本笔成交的分配，之后由账户整笔撤销
+        let mut killed: Vec<OrderId> = Vec::new();
+        while remaining > LOT_EPSILON {
+            // 已到达交易所且被外部成交价触发的挂单中，价格最优的价位
+            let eligible = |order: &Order<Open>| {
+                order.timestamp <= trade.timestamp && order.state.remaining_quantity() > 0.0 && rules.fill_trigger.is_triggered(side, order.state.price, trade.price) && !killed.contains(&order.state.id)
+            };
+            let best_price = orders.iter().filter(|order| eligible(order)).map(|order| order.state.price).reduce(|best, price| match side {
+                                                                                                               | Side::Buy => best.max(price),
+                                                                                                               | Side::Sell => best.min(price),
+                                                                                                           });
+            let Some(best_price) = best_price
+            else {
+                break;
+            };
+
+            // 同价位的新订单排在已有订单之前，逆序即为到达先后
+            let level: Vec<usize> = (0..orders.len()).rev().filter(|&index| eligible(&orders[index]) && orders[index].state.price == best_price).collect();
+            // 冰山单只按显示部分参与分配
+            let visible: Vec<f64> = level.iter().map(|&index| orders[index].state.visible_quantity()).collect();
+            let level_quantity: f64 = visible.iter().sum();
+            let consumes_level = remaining + LOT_EPSILON >= level_quantity;
+
+            // 吃掉整个价位时按挂单的实际显示数量成交，否则按整手分配，每笔不超过挂单的剩余数量
+            let quantities: Vec<f64> = if consumes_level {
+                visible.clone()
+            }
+            else {
+                let sizes: Vec<u64> = visible.iter().map(|quantity| self.lots(*quantity)).collect();
+                let mut quantities: Vec<f64> = self.allocate_lots(self.lots(remaining), &sizes)
+                                                   .into_iter()
+                                                   .zip(&level)
+                                                   .map(|(lots, &index)| (lots as f64 * self.lot_size).min(orders[index].state.remaining_quantity()))
+                                                   .collect();
+                // 不足一手的零头按到达先后分配
+                let mut leftover = remaining - quantities.iter().sum::<f64>();
+                for (quantity, capacity) in quantities.iter_mut().zip(&visible) {
+                    let extra = leftover.min(capacity - *quantity).max(0.0);
+                    *quantity += extra;
+                    leftover -= extra;
+                }
+                quantities
+            };
+
+            // 有 FOK 挂单分不满时将其排除，并按剩余挂单重新分配该价位
+            let short_fill_or_kill: Vec<OrderId> = level.iter()
+                                                        .zip(quantities.iter().zip(&visible))
+                                                        .filter(|(&index, (quantity, capacity))| orders[index].instruction == OrderInstruction::FillOrKill && **quantity + LOT_EPSILON < **capacity)
+                                                        .map(|(&index, _)| orders[index].state.id.clone())
+                                                        .collect();
+            if !short_fill_or_kill.is_empty() {
+                killed.extend(short_fill_or_kill);
+                continue;
+            }
+
+            for (&index, quantity) in level.iter().zip(quantities) {
+                if quantity <= 0.0 {
+                    continue;
+                }
+                let fill_price = rules.fill_price(&orders[index], trade.price, quantity);
+                orders[index].state.record_fill(fill_price, quantity);
+                fills.push(Fill { order: orders[index].clone(),
+                                  price: fill_price,
+                                  quantity });
+            }
+
+            // 显示部分被吃完的冰山单从隐藏部分补充，按原有先后排到同价位队尾；`level` 为降序下标，可以依次移除
+            let replenished: Vec<usize> = level.into_iter().filter(|&index| orders[index].state.replenish()).collect();
+            let replenished: Vec<Order<Open>> = replenished.into_iter().map(|index| orders.remove(index)).collect();
+            for order in replenished {
+                let index = orders.partition_point(|resting| resting.state.price < order.state.price);
+                orders.insert(index, order);
+            }
+            if !consumes_level {
+                break;
+            }
+            remaining -= level_quantity;
+        }
+
+        orders.retain(|order| order.state.remaining_quantity() > 0.0);
+        fills
+    }
+}
+
+impl MatchingAlgorithm
+{
+    /// 用该内置算法撮合一笔外部成交。
+    pub fn match_trade(&self, book: &mut OpenOrdersBook, trade: &MarketTrade, rules: &MatchingRules) -> Vec<Fill>
+    {
+        match self {
+            | MatchingAlgorithm::Fifo => FifoMatcher.match_trade(book, trade, rules),
+            | MatchingAlgorithm::ProRata(mut matcher) => matcher.match_trade(book, trade, rules),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::{
+        common::{
+            event::AccountEventKind,
+            instrument::{kind::InstrumentKind, Instrument},
+            order::states::open::Iceberg,
+            trade::ClientTrade,
+        },
+        hourglass::account::{account_config::InstrumentMatchingAlgorithm, account_handlers::trade_handler::TradeHandler},
+        test_utils::{create_test_account, create_test_account_configuration, create_test_order_open},
+    };
+    use tokio::sync::mpsc;
+
+    fn sell_trade(price: f64, amount: f64) -> MarketTrade
+    {
+        MarketTrade { exchange: "binance-futures".to_string(),
+                      symbol: "ETHUSDT".to_string(),
+                      side: "sell".to_string(),
+                      price,
+                      timestamp: 1625247600000,
+                      amount }
+    }
+
+    fn book_with_bids(bids: &[(u64, f64, f64)]) -> OpenOrdersBook
+    {
+        let mut book = OpenOrdersBook::default();
+        for &(id, price, size) in bids {
+            let mut order = create_test_order_open(Side::Buy, price, size);
+            order.state.id = OrderId(id);
+            book.add_order_open(order);
+        }
+        book
+    }
+
+    #[test]
+    fn test_slippage_applies_only_to_taker_orders()
+    {
+        let mut book = book_with_bids(&[(1, 100.0, 1.0), (2, 100.0, 1.0)]);
+        book.bids.iter_mut().find(|order| order.state.id == OrderId(2)).unwrap().state.order_role = OrderRole::Maker;
+        let rules = MatchingRules { fill_price_policy: FillPricePolicy::TradePrice,
+                                    slippage_model: SlippageModel::FixedBps(50.0),
+                                    ..MatchingRules::default() };
+
+        let fills = FifoMatcher.match_trade(&mut book, &sell_trade(99.0, 2.0), &rules);
+        let prices: Vec<(u64, f64)> = fills.iter().map(|fill| (fill.order.state.id.0, fill.price)).collect();
+        // Taker 买单向上滑点，Maker 挂单按原成交价
+        assert_eq!(prices.len(), 2);
+        assert!(prices.iter().any(|&(id, price)| id == 1 && (price - 99.495).abs() < 1e-9));
+        assert!(prices.iter().any(|&(id, price)| id == 2 && price == 99.0));
+        assert!(fills.iter().all(|fill| fill.order.state.avg_fill_price() == fill.price));
+    }
+
+    #[test]
+    fn test_slippage_never_crosses_the_limit_price()
+    {
+        let rules = MatchingRules { slippage_model: SlippageModel::FixedBps(50.0),
+                                    ..MatchingRules::default() };
+        // 按限价成交时，向不利方向的滑点会越过限价，成交价被限制在限价上
+        let mut buy = create_test_order_open(Side::Buy, 100.0, 1.0);
+        buy.state.order_role = OrderRole::Taker;
+        assert_eq!(rules.fill_price(&buy, 99.0, 1.0), 100.0);
+        let mut sell = create_test_order_open(Side::Sell, 100.0, 1.0);
+        sell.state.order_role = OrderRole::Taker;
+        assert_eq!(rules.fill_price(&sell, 101.0, 1.0), 100.0);
+
+        // 离限价足够远时滑点照常生效
+        let rules = MatchingRules { fill_price_policy: FillPricePolicy::TradePrice, ..rules };
+        assert!((rules.fill_price(&buy, 99.0, 1.0) - 99.495).abs() < 1e-9);
+        assert!((rules.fill_price(&sell, 101.0, 1.0) - 100.495).abs() < 1e-9);
+
+        // 市价单没有限价，不受限制
+        buy.instruction = OrderInstruction::Market;
+        assert!((rules.fill_price(&buy, 100.0, 1.0) - 100.5).abs() < 1e-9);
+    }
+
+    fn filled_quantities(fills: &[Fill]) -> Vec<(u64, f64)>
+    {
+        let mut filled: Vec<(u64, f64)> = fills.iter().map(|fill| (fill.order.state.id.0, fill.quantity)).collect();
+        filled.sort_by_key(|(id, _)| *id);
+        filled
+    }
+
+    #[test]
+    fn test_fifo_matcher_fills_earliest_order_at_level_first()
+    {
+        // 1 先于 2 到达，同价位先到先成交
+        let mut book = book_with_bids(&[(1, 100.0, 1.0), (2, 100.0, 3.0)]);
+        let fills = FifoMatcher.match_trade(&mut book, &sell_trade(99.0, 2.0), &MatchingRules::default());
+        assert_eq!(filled_quantities(&fills), vec![(1, 1.0), (2, 1.0)]);
+        assert_eq!(book.bids.len(), 1);
+        assert_eq!(book.bids[0].state.remaining_quantity(), 2.0);
+    }
+
+    #[test]
+    fn test_pro_rata_matcher_splits_level_by_remaining_size()
+    {
+        let mut book = book_with_bids(&[(1, 100.0, 1.0), (2, 100.0, 3.0), (3, 99.5, 1.0)]);
+        let fills = ProRataMatcher::default().match_trade(&mut book, &sell_trade(99.0, 2.0), &MatchingRules::default());
+        // 最优价位 100.0 共 4.0，按 1:3 分配 2.0，次优价位不成交
+        assert_eq!(filled_quantities(&fills), vec![(1, 0.5), (2, 1.5)]);
+        assert!(fills.iter().all(|fill| fill.price == 100.0));
+        assert_eq!(book.num_orders(), 3);
+
+        // 成交量超过最优价位时，吃完该价位后继续分配给下一价位
+        let fills = ProRataMatcher::default().match_trade(&mut book, &sell_trade(99.0, 2.5), &MatchingRules::default());
+        assert_eq!(filled_quantities(&fills), vec![(1, 0.5), (2, 1.5), (3, 0.5)]);
+        assert_eq!(book.bids.len(), 1);
+        assert_eq!(book.bids[0].state.remaining_quantity(), 0.5);
+    }
+
+    #[test]
+    fn test_pro_rata_matcher_allocates_by_iceberg_display_size()
+    {
+        let mut book = book_with_bids(&[(1, 100.0, 4.0), (2, 100.0, 1.0)]);
+        book.bids.iter_mut().find(|order| order.state.id == OrderId(1)).unwrap().state.iceberg = Some(Iceberg::new(1.0, 4.0));
+        // 冰山单只按显示的 1.0 参与分配，与 2 各分一半
+        let fills = ProRataMatcher::default().match_trade(&mut book, &sell_trade(99.0, 1.0), &MatchingRules::default());
+        assert_eq!(filled_quantities(&fills), vec![(1, 0.5), (2, 0.5)]);
+
+        // 吃完整个价位后冰山单逐次补充显示部分，继续成交剩余的 2.0
+        let fills = ProRataMatcher::default().match_trade(&mut book, &sell_trade(99.0, 3.0), &MatchingRules::default());
+        assert_eq!(filled_quantities(&fills), vec![(1, 0.5), (1, 1.0), (1, 1.0), (2, 0.5)]);
+        assert_eq!(book.bids.len(), 1);
+        assert_eq!(book.bids[0].state.remaining_quantity(), 1.0);
+        assert_eq!(book.bids[0].state.visible_quantity(), 1.0);
+    }
+
+    #[test]
+    fn test_pro_rata_matcher_excludes_fill_or_kill_that_cannot_fill()
+    {
+        let mut book = book_with_bids(&[(1, 100.0, 1.0), (2, 100.0, 1.0)]);
+        book.bids.iter_mut().find(|order| order.state.id == OrderId(1)).unwrap().instruction = OrderInstruction::FillOrKill;
+        // FOK 按比例只能分到 0.5，不参与分配，整笔成交量分给 2
+        let fills = ProRataMatcher::default().match_trade(&mut book, &sell_trade(99.0, 1.0), &MatchingRules::default());
+        assert_eq!(filled_quantities(&fills), vec![(2, 1.0)]);
+        assert_eq!(book.bids.len(), 1);
+        assert_eq!(book.bids[0].state.filled_quantity, 0.0);
+    }
+
+    #[test]
+    fn test_pro_rata_matcher_ignores_orders_not_yet_arrived()
+    {
+        let mut book = book_with_bids(&[(1, 100.0, 1.0), (2, 100.0, 1.0)]);
+        book.bids.iter_mut().find(|order| order.state.id == OrderId(2)).unwrap().timestamp = 1625247600001;
+        let fills = ProRataMatcher::default().match_trade(&mut book, &sell_trade(99.0, 1.0), &MatchingRules::default());
+        assert_eq!(filled_quantities(&fills), vec![(1, 1.0)]);
+        assert_eq!(book.bids.len(), 1);
+    }
+
+    fn whole_lot_matcher(min_allocation: f64, leftover: LeftoverAllocation) -> ProRataMatcher
+    {
+        ProRataMatcher { lot_size: 1.0,
+                         min_allocation,
+                         leftover }
+    }
+
+    #[test]
+    fn test_pro_rata_leftover_rules_on_uneven_sizes()
+    {
+        // 7 手按 1:2:3:4 分配，按比例份额为 0.7、1.4、2.1、2.8，向下取整后剩余 2 手
+        let sizes = [1, 2, 3, 4];
+        for (leftover, expected) in [(LeftoverAllocation::LargestRemainder, vec![1, 1, 2, 3]),
+                                     (LeftoverAllocation::TimePriority, vec![1, 2, 2, 2]),
+                                     (LeftoverAllocation::LargestOrder, vec![0, 1, 3, 3])]
+        {
+            let allocations = whole_lot_matcher(0.0, leftover).allocate_lots(7, &sizes);
+            assert_eq!(allocations, expected, "{:?}", leftover);
+            assert_eq!(allocations.iter().sum::<u64>(), 7);
+        }
+    }
+
+    #[test]
+    fn test_pro_rata_minimum_allocation_moves_small_shares_into_leftover()
+    {
+        // 10 手按 1:1:8:10 分配，份额 0.5、0.5、4、5，不足 2 手的份额归零
+        let matcher = whole_lot_matcher(2.0, LeftoverAllocation::LargestOrder);
+        assert_eq!(matcher.allocate_lots(10, &[1, 1, 8, 10]), vec![0, 0, 4, 6]);
+
+        // 余量分给尚未分到数量的挂单时至少分配最小数量
+        let matcher = whole_lot_matcher(3.0, LeftoverAllocation::TimePriority);
+        assert_eq!(matcher.allocate_lots(6, &[4, 4, 4, 4]), vec![4, 2, 0, 0]);
+        let matcher = whole_lot_matcher(3.0, LeftoverAllocation::LargestRemainder);
+        assert_eq!(matcher.allocate_lots(6, &[4, 4, 4, 4]), vec![3, 3, 0, 0]);
+    }
+
+    #[test]
+    fn test_pro_rata_allocations_sum_to_aggressor_quantity()
+    {
+        let matcher = ProRataMatcher { lot_size: 0.001,
+                                       min_allocation: 0.0,
+                                       leftover: LeftoverAllocation::LargestRemainder };
+        let sizes = [0.013, 0.7, 1.109, 0.25, 3.333];
+        for quantity in [0.001, 0.017, 1.0, 2.345, 5.404] {
+            let allocations = matcher.allocate(quantity, &sizes);
+            let lots: Vec<u64> = allocations.iter().map(|allocation| (allocation / matcher.lot_size).round() as u64).collect();
+            // 每笔分配都是整手，且不超过挂单的剩余数量
+            for ((allocation, lot), size) in allocations.iter().zip(&lots).zip(&sizes) {
+                assert!((allocation - *lot as f64 * matcher.lot_size).abs() < 1e-12);
+                assert!(*lot <= (size / matcher.lot_size).round() as u64);
+            }
+            assert_eq!(lots.iter().sum::<u64>(), (quantity / matcher.lot_size).round() as u64);
+        }
+    }
+
+    #[test]
+    fn test_pro_rata_matcher_fills_with_configured_lots()
+    {
+        // 1 最先到达，余量按时间优先分配
+        let mut book = book_with_bids(&[(1, 100.0, 3.0), (2, 100.0, 5.0), (3, 100.0, 7.0)]);
+        let mut matcher = whole_lot_matcher(0.0, LeftoverAllocation::TimePriority);
+        let fills = matcher.match_trade(&mut book, &sell_trade(99.0, 8.0), &MatchingRules::default());
+        // 份额 1.6、2.66、3.73，取整后 1、2、3，剩余 2 手先分给 1
+        assert_eq!(filled_quantities(&fills), vec![(1, 3.0), (2, 2.0), (3, 3.0)]);
+        assert_eq!(fills.iter().map(|fill| fill.quantity).sum::<f64>(), 8.0);
+        assert_eq!(book.bids.len(), 2);
+    }
+
+    #[test]
+    fn test_pro_rata_matcher_gives_sub_lot_remainder_to_time_priority()
+    {
+        // 整手数向下取整：2.6 只算 2 手，4.6 只算 4 手，不会分配超过外部成交量的数量
+        let mut book = book_with_bids(&[(1, 100.0, 2.6), (2, 100.0, 5.0)]);
+        let mut matcher = whole_lot_matcher(0.0, LeftoverAllocation::LargestRemainder);
+        let fills = matcher.match_trade(&mut book, &sell_trade(99.0, 4.6), &MatchingRules::default());
+        // 份额 1.14、2.86 手，取整后 1、2，余下 1 手按最大余数给 2，不足一手的 0.6 按到达先后给 1
+        let filled = filled_quantities(&fills);
+        assert_eq!(filled.len(), 2);
+        assert_eq!(filled[0].0, 1);
+        assert!((filled[0].1 - 1.6).abs() < 1e-9);
+        assert_eq!(filled[1], (2, 3.0));
+        assert!((fills.iter().map(|fill| fill.quantity).sum::<f64>() - 4.6).abs() < 1e-9);
+        assert_eq!(book.bids.len(), 2);
+    }
+
+    /// 示例：只成交每个价位上数量最大的挂单。
+    #[derive(Clone, Debug)]
+    struct LargestOrderOnly;
+
+    impl MatchingEngine for LargestOrderOnly
+    {
+        fn match_trade(&mut self, book: &mut OpenOrdersBook, trade: &MarketTrade, rules: &MatchingRules) -> Vec<Fill>
+        {
+            let Some(side) = book.determine_matching_side(trade, rules.fill_trigger)
+            else {
+                return Vec::new();
+            };
+            let orders = match side {
+                | Side::Buy => &mut book.bids,
+                | Side::Sell => &mut book.asks,
+            };
+            let Some(order) = orders.iter_mut().max_by(|a, b| a.state.remaining_quantity().total_cmp(&b.state.remaining_quantity()))
+            else {
+                return Vec::new();
+            };
+            let quantity = order.state.remaining_quantity().min(trade.amount);
+            let price = rules.fill_price(order, trade.price, quantity);
+            order.state.record_fill(price, quantity);
+            let fill = Fill { order: order.clone(), price, quantity };
+            orders.retain(|order| order.state.remaining_quantity() > 0.0);
+            vec![fill]
+        }
+    }
+
+    async fn filled_sizes(algorithm: MatchingAlgorithm, custom: Option<Box<dyn MatchingEngine>>) -> Vec<f64>
+    {
+        let mut account = create_test_account().await;
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        account.account_event_tx = account_event_tx;
+        let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+        account.config.matching_algorithms.push(InstrumentMatchingAlgorithm { instrument: instrument.clone(), algorithm });
+        if let Some(engine) = custom {
+            account.matching_engines.insert(instrument.clone(), engine);
+        }
+
+        {
+            let orders_guard = account.account_open_book.read().await;
+            let mut instrument_orders = orders_guard.get_ins_orders_mut(&instrument).unwrap();
+            for (id, size) in [(1, 0.1), (2, 0.3)] {
+                let mut order = create_test_order_open(Side::Buy, 16400.0, size);
+                order.state.id = OrderId(id);
+                order.timestamp = 1234567;
+                instrument_orders.add_order_open(order);
+            }
+        }
+        account.handle_trade_data(&MarketTrade { exchange: "binance-futures".to_string(),
+                                                 symbol: "ETHUSDT".to_string(),
+                                                 side: "sell".to_string(),
+                                                 price: 16300.0,
+                                                 timestamp: 1234568,
+                                                 amount: 0.2 })
+               .await
+               .unwrap();
+
+        let mut trades: Vec<ClientTrade> = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            if let AccountEventKind::Trade(trade) = event.kind {
+                trades.push(trade);
+            }
+        }
+        trades.sort_by_key(|trade| trade.order_id.clone().unwrap().0);
+        trades.iter().map(|trade| trade.size).collect()
+    }
+
+    #[tokio::test]
+    async fn test_matching_algorithm_selected_per_instrument()
+    {
+        assert_eq!(filled_sizes(MatchingAlgorithm::Fifo, None).await, vec![0.1, 0.1]);
+        let pro_rata = filled_sizes(MatchingAlgorithm::ProRata(ProRataMatcher::default()), None).await;
+        assert_eq!(pro_rata.len(), 2);
+        assert!((pro_rata[0] - 0.05).abs() < 1e-12 && (pro_rata[1] - 0.15).abs() < 1e-12);
+        // 自定义撮合器优先于配置的内置算法
+        assert_eq!(filled_sizes(MatchingAlgorithm::Fifo, Some(Box::new(LargestOrderOnly))).await, vec![0.2]);
+    }
+
+    #[test]
+    fn test_unconfigured_instrument_defaults_to_fifo()
+    {
+        let config = create_test_account_configuration();
+        assert_eq!(config.matching_algorithm(&Instrument::new("ETH", "USDT", InstrumentKind::Perpetual)), MatchingAlgorithm::Fifo);
+    }
+}
@@ -1,18 +1,41 @@
 use crate::{
-    common::datafeed::market_event::MarketEvent,
+    common::datafeed::{
+        csv_trade_source::CsvTradeSource,
+        market_event::MarketEvent,
+        mock_data_source::MockDataSource,
+        parquet_trade_source::ParquetTradeSource,
+        price_jitter::{PriceJitter, PriceJitterConfig},
+        replay_clock::ReplayClock,
+        simulated_event::{DepthFeed, LiquidationFeed, SimulatedEvent},
+        timestamp_normalizer::TimestampNormalizer,
+        timestamp_sequencer::TimestampSequencer,
+    },
     error::ExchangeError,
     hourglass::{
-        account::account_handlers::{balance_handler::BalanceHandler, position_handler::PositionHandler, trade_handler::TradeHandler},
-        clickhouse_api::{datatype::clickhouse_trade_data::MarketTrade, queries_operations::ClickHouseClient},
+        account::{
+            account_handlers::{balance_handler::BalanceHandler, position_handler::PositionHandler, trade_handler::TradeHandler},
+            account_latency::AccountLatency,
+        },
+        clickhouse_api::{
+            datatype::{clickhouse_agg_trade_data::ClickhouseAggTrade, clickhouse_trade_data::MarketTrade},
+            queries_operations::ClickHouseClient,
+        },
         hourglass_client_local_mode::HourglassClientEvent,
+        progress::{ProgressCallback, ProgressPolicy, ProgressReport, ProgressReporter},
+        replay_checkpoint::{CheckpointPolicy, ReplayCheckpoint, ReplayCheckpointer},
+        run_summary::{RunSummary, ShutdownReason},
     },
-    hourglass_log::warn,
+    hourglass_log::{error, info, warn},
     network::{event::NetworkEvent, is_port_in_use},
 };
 use account::HourglassAccount;
 use clickhouse::query::RowCursor;
 use mpsc::UnboundedReceiver;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{atomic::Ordering, Arc},
+};
 use tokio::{
     sync::{mpsc, mpsc::UnboundedSender, Mutex},
     time::{self, Duration},
@@ -25,15 +48,28 @@ pub mod clickhouse_api;
 pub mod config_request;
 pub mod hourglass_client_local_mode;
 pub mod hourglass_orderbook;
+pub mod matching_engine;
 pub mod open_orders_book;
+pub mod progress;
+pub mod replay_checkpoint;
 pub mod risk_reserve;
+pub mod run_summary;
 pub mod utils;
 pub mod ws_trade;
 
+/// 交易所的行情数据源。
+///
+/// 回测可以选择逐笔成交（`Backtest`）或聚合成交（`BacktestAggregated`）。两者的成交量分布不同，
+/// 撮合结果不可直接比较，见 [`ClickhouseAggTrade`]。`Mock` 回放内存中的成交夹具，用于不依赖 ClickHouse 的集成测试。
+/// `Csv` 从 CSV 文件逐行回放成交，见 [`CsvTradeSource`]；`Parquet` 从 Parquet 文件按行组回放成交，见 [`ParquetTradeSource`]。
 pub enum DataSource
 {
     RealTime(UnboundedReceiver<MarketEvent<MarketTrade>>),
     Backtest(RowCursor<MarketTrade>),
+    BacktestAggregated(RowCursor<ClickhouseAggTrade>),
+    Mock(MockDataSource),
+    Csv(CsvTradeSource),
+    Parquet(ParquetTradeSource),
 }
 
 pub struct HourglassExchange
@@ -44,7 +80,16 @@ pub struct HourglassExchange
     pub account: Arc<Mutex<HourglassAccount>>,
     pub data_source: DataSource,
     pub clickhouse_client: ClickHouseClient,
-    pub active_sessions: Mutex<HashMap<String, Uuid>>, // 存储 session_token 和 username 的映射
+    pub active_sessions: Mutex<HashMap<String, Uuid>>,     // 存储 session_token 和 username 的映射
+    pub price_jitter: Option<PriceJitter>,                 // 回测时对市场成交价施加的随机扰动，默认关闭
+    pub sequencer: TimestampSequencer,                     // 保证同一时间戳内多品种成交的处理顺序确定
+    pub checkpointer: ReplayCheckpointer,                  // 回放检查点的自动写入与断点续跑
+    pub progress: Option<ProgressReporter>,                // 回测进度回调，默认关闭
+    pub liquidation_feed: Option<LiquidationFeed>,         // 与成交流合并回放的历史强平事件，默认关闭
+    pub depth_feed: Option<DepthFeed>,                     // 与成交流合并回放的历史深度快照，默认关闭
+    pub timestamp_normalizer: Option<TimestampNormalizer>, // 按数据流把成交时间戳统一为毫秒并校正时钟偏差，默认不转换
+    pub replay_clock: Option<ReplayClock>,                 // 按墙上时钟控制回放速度，默认不等待
+    pub network_latency: Option<AccountLatency>,           // 回放成交到达账户的网络延迟，默认为 0
 }
 
 impl HourglassExchange
@@ -59,114 +104,333 @@ impl HourglassExchange
         Arc::clone(&self.account)
     }
 
-    pub async fn start(mut self)
+    /// 运行事件循环，直到数据回放完毕、客户端请求关闭或超时无事件，随后执行收尾并返回运行摘要。
+    ///
+    /// 收尾见 [`shutdown`](Self::shutdown)。
+    pub async fn start(mut self) -> RunSummary
     {
         let timeout = 1;
         let mut processed_count = 0; // 记录已处理的数据条目数
+        let mut data_source_error = None;
 
-        loop {
+        let reason = loop {
             tokio::select! {
-                    // 监听客户端信号
-                     Some(event) = self.client_event_rx.recv() => {
-                match event {
-                    HourglassClientEvent::LetItRoll => {
-                        if let Some(row) = self.process_next_data().await {
-                            let mut account = self.account.lock().await;
-                            let _ = account.handle_trade_data(&row).await;
-                            processed_count += 1; // 每处理一个条目，计数器加1
-                        } else {
-                            // 如果没有更多数据
-                            if processed_count > 0 {
-                                warn!("No more data available. Processed {} entries", processed_count);
+                // 监听客户端信号
+                Some(event) = self.client_event_rx.recv() => {
+                    match event {
+                        HourglassClientEvent::LetItRoll => {
+                            let next_event = match self.process_next_data().await {
+                                Ok(next_event) => next_event,
+                                Err(err) => {
+                                    error!("Replay stopped after {} entries: {}", processed_count, err);
+                                    data_source_error = Some(err.to_string());
+                                    break ShutdownReason::DataSourceFailed;
+                                }
+                            };
+                            if let Some(event) = next_event {
+                                if let Some(clock) = &mut self.replay_clock {
+                                    clock.pace(event.timestamp()).await;
+                                }
+                                let mut account = self.account.lock().await;
+                                match &event {
+                                    // 配置了网络延迟时，挂单按成交到达账户的接收时间判断是否可见
+                                    | SimulatedEvent::MarketTrade(row) => {
+                                        let received_ts = self.network_latency.as_mut().map_or(row.timestamp, |latency| latency.receive_time(row.timestamp));
+                                        let _ = account.handle_received_trade_data(row, received_ts).await;
+                                    }
+                                    // 强平单按主动成交冲击挂单，不计入检查点的回放位置
+                                    | SimulatedEvent::ExternalLiquidation(liquidation) => match liquidation.to_market_trade() {
+                                        | Ok(trade) => {
+                                            let _ = account.handle_trade_data(&trade).await;
+                                        }
+                                        | Err(err) => warn!("Skipping invalid liquidation: {:?}", err),
+                                    },
+                                    // 深度快照覆盖本地深度，同时成交与新深度交叉的挂单
+                                    | SimulatedEvent::DepthSnapshot(snapshot) => {
+                                        match account.resolve_depth_instrument(snapshot) {
+                                            | Ok(instrument) => {
+                                                if let Err(err) = account.apply_depth_snapshot(&instrument, snapshot).await {
+                                                    warn!("Failed to apply depth snapshot for {}: {:?}", instrument, err);
+                                                }
+                                            }
+                                            | Err(err) => warn!("Failed to resolve instrument for depth snapshot {}: {:?}", snapshot.symbol, err),
+                                        }
+                                    }
+                                }
+                                Self::publish_own_fill_prints(&self.market_event_tx, &mut account);
+                                processed_count += 1; // 每处理一个条目，计数器加1
+                                if let SimulatedEvent::MarketTrade(row) = &event {
+                                    if self.checkpointer.advance(row.timestamp) {
+                                        self.checkpointer.write_checkpoint(&account).await;
+                                    }
+                                }
+                                if let Some(progress) = &mut self.progress {
+                                    progress.advance(processed_count, event.timestamp());
+                                }
                             } else {
-                                warn!("No data found.");
+                                // 如果没有更多数据
+                                if processed_count > 0 {
+                                    warn!("No more data available. Processed {} entries", processed_count);
+                                } else {
+                                    warn!("No data found.");
+                                }
+                                break ShutdownReason::DataExhausted; // 优雅退出循环
                             }
-                            break; // 优雅退出循环
-                        }
-                    },
-                            // 其他客户端事件处理
-                            HourglassClientEvent::FetchOrdersOpen(response_tx) => {
-                                self.account.lock().await.fetch_orders_open_and_respond(response_tx).await;
-                            },
-                            HourglassClientEvent::FetchTokenBalance(token, response_tx) => {
-                                self.account.lock().await.fetch_token_balance_and_respond(&token, response_tx).await;
-                            },
-                            HourglassClientEvent::FetchTokenBalances(response_tx) => {
-                                self.account.lock().await.fetch_token_balances_and_respond(response_tx).await;
-                            },
-                            HourglassClientEvent::OpenOrders((open_requests, response_tx)) => {
-                                self.account.lock().await.open_orders(open_requests, response_tx).await.expect("Failed to open.");
-                            },
-                            HourglassClientEvent::CancelOrders((cancel_requests, response_tx)) => {
-                                self.account.lock().await.cancel_orders(cancel_requests, response_tx).await;
-                            },
-                            HourglassClientEvent::CancelOrdersAll(response_tx) => {
-                                self.account.lock().await.cancel_orders_all(response_tx).await;
-                            },
-                            HourglassClientEvent::FetchAllPositions(response_tx) => {
-                                self.account.lock().await.fetch_positions_and_respond(response_tx).await;
-                            },
-                            HourglassClientEvent::FetchLongPosition(instrument, response_tx) => {
-                                self.account.lock().await.fetch_long_position_and_respond(&instrument, response_tx).await;
-                            },
-                            HourglassClientEvent::FetchShortPosition(instrument, response_tx) => {
-                                self.account.lock().await.fetch_short_position_and_respond(&instrument, response_tx).await;
-
-                            },
-                            HourglassClientEvent::DepositTokens(deposit_request) => {
-                                self.account.lock().await.deposit_multiple_coins_and_respond(deposit_request.0, deposit_request.1).await;
-                            },
-                            HourglassClientEvent::ConfigureInstruments(position_configs, response_tx) => {
-                                let _ = self.account.lock().await.preconfigure_positions(position_configs, response_tx).await;
-                            }
-
-                        HourglassClientEvent::Login(_) => {
-                            todo!()
-                        }
-
-                        HourglassClientEvent::Register(_) => {
-                            todo!()
-                        }
-
-                        HourglassClientEvent::Logout(_) => {
-                todo!()
-                        }
+                        },
+                        HourglassClientEvent::Shutdown => {
+                            info!("Shutdown requested after {} entries", processed_count);
+                            break ShutdownReason::Requested;
+                        },
+                        // 其他客户端事件处理
+                        event => self.handle_client_event(event).await,
+                    }
+                }
+                // 加入超时机制，防止一直挂起
+                _ = time::sleep(Duration::from_secs(timeout)) => {
+                    if processed_count > 0 {
+                        println!("No more data available.");
+                    } else {
+                        println!("No data found.");
                     }
+                    break ShutdownReason::Idle; // 超时后优雅退出循环
                 }
-                    // 加入超时机制，防止一直挂起
-            _ = time::sleep(Duration::from_secs(timeout)) => {
-                if processed_count > 0 {
-                    println!("No more data available.");
-                } else {
-                    println!("No data found.");
+            }
+        };
+
+        let mut summary = self.shutdown(reason, processed_count).await;
+        summary.data_source_error = data_source_error;
+        summary
+    }
+
+    /// 处理除 `LetItRoll` 与 `Shutdown` 之外的客户端事件。
+    async fn handle_client_event(&mut self, event: HourglassClientEvent)
+    {
+        match event {
+            | HourglassClientEvent::FetchOrdersOpen(response_tx) => {
+                self.account.lock().await.fetch_orders_open_and_respond(response_tx).await;
+            }
+            | HourglassClientEvent::FetchTokenBalance(token, response_tx) => {
+                self.account.lock().await.fetch_token_balance_and_respond(&token, response_tx).await;
+            }
+            | HourglassClientEvent::FetchTokenBalances(response_tx) => {
+                self.account.lock().await.fetch_token_balances_and_respond(response_tx).await;
+            }
+            | HourglassClientEvent::OpenOrders((open_requests, response_tx)) => {
+                let mut account = self.account.lock().await;
+                account.open_orders(open_requests, response_tx).await.expect("Failed to open.");
+                // 市价单可能立即吃掉本地深度而成交
+                Self::publish_own_fill_prints(&self.market_event_tx, &mut account);
+            }
+            | HourglassClientEvent::OpenStopOrders(stop_orders, response_tx) => {
+                if let Err(err) = self.account.lock().await.open_stop_orders(stop_orders, response_tx).await {
+                    warn!("{:?}", err);
                 }
-                break; // 超时后优雅退出循环
             }
+            | HourglassClientEvent::CancelStopOrders(cancel_requests, response_tx) => {
+                if let Err(err) = self.account.lock().await.cancel_stop_orders(cancel_requests, response_tx).await {
+                    warn!("{:?}", err);
                 }
+            }
+            | HourglassClientEvent::OpenOco(legs, response_tx) => {
+                let (leg_a, leg_b) = *legs;
+                let result = self.account.lock().await.submit_oco(leg_a, leg_b).await;
+                response_tx.send(result).unwrap_or(());
+            }
+            | HourglassClientEvent::CancelOrders((cancel_requests, response_tx)) => {
+                self.account.lock().await.cancel_orders(cancel_requests, response_tx).await;
+            }
+            | HourglassClientEvent::CancelOrdersAll(response_tx) => {
+                self.account.lock().await.cancel_orders_all(response_tx).await;
+            }
+            | HourglassClientEvent::AmendOrders((amend_requests, response_tx)) => {
+                self.account.lock().await.amend_orders(amend_requests, response_tx).await;
+            }
+            | HourglassClientEvent::FetchAllPositions(response_tx) => {
+                self.account.lock().await.fetch_positions_and_respond(response_tx).await;
+            }
+            | HourglassClientEvent::FetchPositions(response_tx) => {
+                let positions = self.account.lock().await.fetch_positions().await;
+                response_tx.send(Ok(positions)).unwrap_or(());
+            }
+            | HourglassClientEvent::FetchTrades(instrument, response_tx) => {
+                let trades = self.account.lock().await.fetch_trades(&instrument);
+                response_tx.send(Ok(trades)).unwrap_or(());
+            }
+            | HourglassClientEvent::FetchLongPosition(instrument, response_tx) => {
+                self.account.lock().await.fetch_long_position_and_respond(&instrument, response_tx).await;
+            }
+            | HourglassClientEvent::FetchShortPosition(instrument, response_tx) => {
+                self.account.lock().await.fetch_short_position_and_respond(&instrument, response_tx).await;
+            }
+            | HourglassClientEvent::DepositTokens(deposit_request) => {
+                self.account.lock().await.deposit_multiple_coins_and_respond(deposit_request.0, deposit_request.1).await;
+            }
+            | HourglassClientEvent::ConfigureInstruments(position_configs, response_tx) => {
+                let _ = self.account.lock().await.preconfigure_positions(position_configs, response_tx).await;
+            }
+            | HourglassClientEvent::LetItRoll | HourglassClientEvent::Shutdown => {}
+            | HourglassClientEvent::Login(_) => {
+                todo!()
+            }
+            | HourglassClientEvent::Register(_) => {
+                todo!()
+            }
+            | HourglassClientEvent::Logout(_) => {
+                todo!()
+            }
         }
     }
 
+    /// 把账户中等待推送的本账户成交记录推送到行情流，只在启用 `own_fills_on_tape` 时才会有记录。
+    ///
+    /// 这些记录只发给客户端，不会回流到撮合，前视偏差见 [`HourglassAccount::take_own_fill_prints`]。
+    fn publish_own_fill_prints(market_event_tx: &UnboundedSender<MarketTrade>, account: &mut HourglassAccount)
+    {
+        for print in account.take_own_fill_prints() {
+            if let Err(e) = market_event_tx.send(print) {
+                eprintln!("Failed to send own fill print to client: {:?}", e);
+            }
+        }
+    }
+
+    /// 安全关闭：
+    ///
+    /// 1. 处理完通道中已排队的客户端事件（不再推进行情，排队的 `LetItRoll` 会被忽略）；
+    /// 2. 执行账户收尾，见 [`HourglassAccount::finalize_run`]；
+    /// 3. 配置了 [`CheckpointPolicy`] 时写入最终检查点；
+    /// 4. 返回运行摘要。
+    async fn shutdown(&mut self, reason: ShutdownReason, processed_count: u64) -> RunSummary
+    {
+        let mut drained_events = 0;
+        while let Ok(event) = self.client_event_rx.try_recv() {
+            self.handle_client_event(event).await;
+            drained_events += 1;
+        }
+
+        let mut account = self.account.lock().await;
+        account.finalize_run().await;
+        self.checkpointer.write_checkpoint(&account).await;
+
+        let open_orders = account.account_open_book.read().await.fetch_all().len();
+        RunSummary { reason,
+                     processed_count,
+                     drained_events,
+                     final_timestamp: account.exchange_timestamp.load(Ordering::SeqCst),
+                     open_orders,
+                     balances: account.get_balances().await,
+                     data_source_error: None }
+    }
+
+    /// 从检查点文件恢复账户状态，并把数据流快进到检查点记录的位置。
+    ///
+    /// 需要在 [`start`](Self::start) 之前调用，数据源应与写入检查点时的回放一致。
+    pub async fn resume_from(&mut self, path: impl AsRef<Path>) -> Result<(), ExchangeError>
+    {
+        let checkpoint = ReplayCheckpoint::read_from(path.as_ref())?;
+        self.account.lock().await.restore_checkpoint(checkpoint.account).await;
+        self.checkpointer.resume_to(checkpoint.position);
+        info!("Resumed from checkpoint at timestamp {}, {} entries already processed",
+              checkpoint.position.last_timestamp,
+              checkpoint.position.processed_count);
+        Ok(())
+    }
+
     /// 处理下一条数据
-    async fn process_next_data(&mut self) -> Option<MarketTrade>
-    {
-        match &mut self.data_source {
-            | DataSource::Backtest(cursor) => {
-                // 这里 cursor 需要是 mutable 的
-                if let Ok(Some(row)) = cursor.next().await {
-                    // 发送市场数据给客户端
-                    if let Err(e) = self.market_event_tx.send(row.clone()) {
-                        eprintln!("Failed to send market data to client: {:?}", e);
-                    }
-                    Some(row)
+    ///
+    /// 同一时间戳内的成交会先经过 [`TimestampSequencer`] 排序，排序规则见其文档。
+    /// 配置了 [`LiquidationFeed`] 时，强平事件按时间戳与成交合并，先于同一时间戳的成交返回。
+    /// 配置了 [`DepthFeed`] 时，深度快照同样按时间戳合并，排在同一时间戳的强平事件之后、成交之前。
+    /// 文件数据源读取出错、聚合成交或强平事件的价格或时间戳无效时返回错误，由 [`start`](Self::start) 结束回放并记入运行摘要。
+    async fn process_next_data(&mut self) -> Result<Option<SimulatedEvent>, ExchangeError>
+    {
+        loop {
+            if let Some(timestamp) = self.sequencer.peek_timestamp() {
+                if let Some(liquidation) = self.next_liquidation(Some(timestamp)).await? {
+                    return Ok(Some(liquidation));
+                }
+                if let Some(snapshot) = self.next_depth_snapshot(Some(timestamp)).await {
+                    return Ok(Some(snapshot));
+                }
+            }
+
+            if let Some(row) = self.sequencer.pop() {
+                // 从检查点恢复时，跳过检查点之前已经处理过的数据
+                if self.checkpointer.should_skip(row.timestamp) {
+                    continue;
+                }
+                // 发送市场数据给客户端
+                if let Err(e) = self.market_event_tx.send(row.clone()) {
+                    eprintln!("Failed to send market data to client: {:?}", e);
                 }
-                else {
-                    None
+                return Ok(Some(SimulatedEvent::MarketTrade(row)));
+            }
+
+            // 这里 cursor 需要是 mutable 的
+            let next_row = match &mut self.data_source {
+                | DataSource::Backtest(cursor) => cursor.next().await.ok().flatten(),
+                // 聚合成交按一条外部成交参与撮合
+                | DataSource::BacktestAggregated(cursor) => cursor.next().await.ok().flatten().map(|agg_trade| agg_trade.to_market_trade()).transpose()?,
+                | DataSource::Mock(source) => source.next(),
+                | DataSource::Csv(source) => source.next().transpose()?,
+                | DataSource::Parquet(source) => source.next().transpose()?,
+                | _ => {
+                    println!("Unhandled data source type");
+                    return Ok(None);
                 }
+            };
+            if let Some(row) = next_row {
+                // 在排序之前把时间戳统一为毫秒，避免不同单位的数据源顺序错乱
+                let row = match &self.timestamp_normalizer {
+                    | Some(normalizer) => normalizer.apply(row),
+                    | None => row,
+                };
+                // 在进入撮合之前对价格施加扰动（若已启用）
+                let row = match &mut self.price_jitter {
+                    | Some(jitter) => jitter.apply(row),
+                    | None => row,
+                };
+                self.sequencer.push(row);
+            }
+            else if !self.sequencer.flush() {
+                // 成交已回放完毕，补齐剩余的强平事件与深度快照
+                if let Some(liquidation) = self.next_liquidation(None).await? {
+                    return Ok(Some(liquidation));
+                }
+                return Ok(self.next_depth_snapshot(None).await);
+            }
+        }
+    }
+
+    /// 取出下一条时间戳不晚于 `until` 的强平事件，并作为成交推送到行情流。强平事件的价格或时间戳无效时返回错误。
+    async fn next_liquidation(&mut self, until: Option<i64>) -> Result<Option<SimulatedEvent>, ExchangeError>
+    {
+        let Some(feed) = self.liquidation_feed.as_mut()
+        else {
+            return Ok(None);
+        };
+        while let Some(liquidation) = feed.pop_due(until).await {
+            if self.checkpointer.should_skip_liquidation(liquidation.timestamp) {
+                continue;
             }
-            | _ => {
-                println!("Unhandled data source type");
-                None
+            if let Err(e) = self.market_event_tx.send(liquidation.to_market_trade()?) {
+                eprintln!("Failed to send liquidation to client: {:?}", e);
+            }
+            return Ok(Some(SimulatedEvent::ExternalLiquidation(liquidation)));
+        }
+        Ok(None)
+    }
+
+    /// 取出下一条时间戳不晚于 `until` 的深度快照。
+    async fn next_depth_snapshot(&mut self, until: Option<i64>) -> Option<SimulatedEvent>
+    {
+        let feed = self.depth_feed.as_mut()?;
+        loop {
+            let snapshot = feed.pop_due(until).await?;
+            // 深度快照与强平事件一样先于同一时间戳的成交处理，跳过的规则相同
+            if self.checkpointer.should_skip_liquidation(snapshot.timestamp) {
+                continue;
             }
+            return Some(SimulatedEvent::DepthSnapshot(Box::new(snapshot)));
         }
     }
 
@@ -222,7 +486,15 @@ impl Default for ExchangeBuilder
         Self { event_hourglass_rx: Some(rx),
                account: None,
                market_event_tx: None,
-               data_source: None }
+               data_source: None,
+               price_jitter: None,
+               checkpoint_policy: None,
+               progress: None,
+               liquidation_feed: None,
+               depth_feed: None,
+               timestamp_normalizer: None,
+               replay_clock: None,
+               network_latency: None }
     }
 }
 pub struct ExchangeBuilder
@@ -231,6 +503,14 @@ pub struct ExchangeBuilder
     pub(crate) account: Option<Arc<Mutex<HourglassAccount>>>,
     pub(crate) market_event_tx: Option<UnboundedSender<MarketTrade>>,
     pub(crate) data_source: Option<DataSource>,
+    pub(crate) price_jitter: Option<PriceJitterConfig>,
+    pub(crate) checkpoint_policy: Option<CheckpointPolicy>,
+    pub(crate) progress: Option<(ProgressPolicy, ProgressCallback)>,
+    pub(crate) liquidation_feed: Option<LiquidationFeed>,
+    pub(crate) depth_feed: Option<DepthFeed>,
+    pub(crate) timestamp_normalizer: Option<TimestampNormalizer>,
+    pub(crate) replay_clock: Option<ReplayClock>,
+    pub(crate) network_latency: Option<AccountLatency>,
 }
 
 impl ExchangeBuilder
@@ -240,7 +520,15 @@ impl ExchangeBuilder
         Self { event_hourglass_rx: None,
                account: None,
                market_event_tx: None,
-               data_source: None }
+               data_source: None,
+               price_jitter: None,
+               checkpoint_policy: None,
+               progress: None,
+               liquidation_feed: None,
+               depth_feed: None,
+               timestamp_normalizer: None,
+               replay_clock: None,
+               network_latency: None }
     }
 
     pub fn event_hourglass_rx(self, value: UnboundedReceiver<HourglassClientEvent>) -> Self
@@ -264,6 +552,62 @@ impl ExchangeBuilder
         Self { account: Some(value), ..self }
     }
 
+    pub fn price_jitter(self, value: PriceJitterConfig) -> Self
+    {
+        Self { price_jitter: Some(value), ..self }
+    }
+
+    pub fn checkpoint_policy(self, value: CheckpointPolicy) -> Self
+    {
+        Self { checkpoint_policy: Some(value),
+               ..self }
+    }
+
+    /// 设置回测进度回调，按 [`ProgressPolicy`] 的节奏在事件循环中同步调用，回调中不应执行耗时操作。
+    pub fn progress<F>(self, policy: ProgressPolicy, callback: F) -> Self
+        where F: FnMut(&ProgressReport) + Send + 'static
+    {
+        Self { progress: Some((policy, Box::new(callback))),
+               ..self }
+    }
+
+    /// 设置与成交流按时间戳合并回放的历史强平事件，见 [`LiquidationFeed`]。
+    pub fn liquidation_feed(self, value: LiquidationFeed) -> Self
+    {
+        Self { liquidation_feed: Some(value),
+               ..self }
+    }
+
+    /// 设置与成交流按时间戳合并回放的历史深度快照，见 [`DepthFeed`]。
+    pub fn depth_feed(self, value: DepthFeed) -> Self
+    {
+        Self { depth_feed: Some(value), ..self }
+    }
+
+    /// 设置按数据流规范化成交时间戳的方式，见 [`TimestampNormalizer`]。同时配置了强平事件流时，强平事件的时间戳按同样的方式规范化。
+    pub fn timestamp_normalizer(self, value: TimestampNormalizer) -> Self
+    {
+        Self { timestamp_normalizer: Some(value),
+               ..self }
+    }
+
+    /// 设置按墙上时钟控制的回放速度，见 [`ReplayClock`]。未设置时尽可能快地回放。
+    pub fn replay_clock(self, value: ReplayClock) -> Self
+    {
+        Self { replay_clock: Some(value),
+               ..self }
+    }
+
+    /// 设置回放成交到达账户的网络延迟，通常由 [`AccountConfig::network_latency`](account::account_config::AccountConfig::network_latency) 创建。
+    ///
+    /// 每笔成交的接收时间为 `成交时间戳 + 延迟`，撮合时时间戳不晚于接收时间的挂单才参与成交，
+    /// 见 [`TradeHandler::handle_received_trade_data`]。未设置时接收时间等于成交时间戳。
+    pub fn network_latency(self, value: AccountLatency) -> Self
+    {
+        Self { network_latency: Some(value),
+               ..self }
+    }
+
     pub fn initiate(self) -> Result<HourglassExchange, ExchangeError>
     {
         Ok(HourglassExchange { client_event_rx: self.event_hourglass_rx.ok_or_else(|| ExchangeError::BuilderIncomplete("event_hourglass_rx".to_string()))?,
@@ -272,7 +616,20 @@ impl ExchangeBuilder
                                account: self.account.ok_or_else(|| ExchangeError::BuilderIncomplete("account".to_string()))?,
                                data_source: self.data_source.ok_or_else(|| ExchangeError::BuilderIncomplete("data_source".to_string()))?,
                                clickhouse_client: ClickHouseClient::new(),
-                               active_sessions: HashMap::new().into() })
+                               active_sessions: HashMap::new().into(),
+                               price_jitter: self.price_jitter.filter(|config| config.enabled).map(PriceJitter::new),
+                               sequencer: TimestampSequencer::default(),
+                               checkpointer: ReplayCheckpointer::new(self.checkpoint_policy),
+                               progress: self.progress.map(|(policy, callback)| ProgressReporter::new(policy, callback)),
+                               // 强平事件与成交流按同一套规范化后的时间戳合并
+                               liquidation_feed: match (self.liquidation_feed, &self.timestamp_normalizer) {
+                                   | (Some(feed), Some(normalizer)) => Some(feed.with_timestamp_normalizer(normalizer.clone())),
+                                   | (feed, _) => feed,
+                               },
+                               depth_feed: self.depth_feed,
+                               timestamp_normalizer: self.timestamp_normalizer,
+                               replay_clock: self.replay_clock,
+                               network_latency: self.network_latency })
     }
 }
 
@@ -280,7 +637,21 @@ impl ExchangeBuilder
 mod tests
 {
     use super::*;
-    use crate::{hourglass::clickhouse_api::queries_operations::ClickHouseClient, test_utils::create_test_account};
+    use crate::{
+        common::{
+            datafeed::timestamp_normalizer::{TimestampNormalization, TimestampUnit},
+            event::AccountEventKind,
+        },
+        common::{
+            account_positions::{perpetual::PerpetualPositionConfig, PositionDirectionMode, PositionMarginMode},
+            instrument::{kind::InstrumentKind, Instrument},
+            order::{identification::client_order_id::ClientOrderId, order_instructions::OrderInstruction, states::request_open::RequestOpen, Order},
+            Side,
+        },
+        hourglass::clickhouse_api::{datatype::order_book_25::OrderBook25, queries_operations::ClickHouseClient},
+        test_utils::create_test_account,
+        Exchange,
+    };
     use std::net::TcpListener;
     use tokio::sync::mpsc;
 
@@ -309,6 +680,37 @@ mod tests
         assert!(builder.account.is_some());
     }
 
+    #[tokio::test]
+    async fn builder_should_set_price_jitter()
+    {
+        let config = PriceJitterConfig { enabled: true, band_bps: 2.0, seed: 1 };
+        let builder = ExchangeBuilder::new().price_jitter(config.clone());
+        assert_eq!(builder.price_jitter, Some(config));
+    }
+
+    #[tokio::test]
+    async fn builder_should_set_progress()
+    {
+        let builder = ExchangeBuilder::new().progress(ProgressPolicy::default().every_events(1_000), |_: &ProgressReport| {});
+        assert_eq!(builder.progress.map(|(policy, _)| policy.every_events), Some(Some(1_000)));
+    }
+
+    #[tokio::test]
+    async fn builder_should_set_timestamp_normalizer()
+    {
+        let normalizer = TimestampNormalizer::default().stream("okex", TimestampNormalization::new(TimestampUnit::Microseconds, -15));
+        let builder = ExchangeBuilder::new().timestamp_normalizer(normalizer.clone());
+        assert_eq!(builder.timestamp_normalizer, Some(normalizer));
+    }
+
+    #[tokio::test]
+    async fn builder_should_set_replay_clock()
+    {
+        let builder = ExchangeBuilder::new().replay_clock(ReplayClock::new(10.0));
+        assert!(builder.replay_clock.is_some_and(|clock| clock.is_paced()));
+        assert!(ExchangeBuilder::new().replay_clock.is_none());
+    }
+
     #[tokio::test]
     async fn builder_should_return_error_if_event_hourglass_rx_is_missing()
     {
@@ -348,7 +750,16 @@ mod tests
                                            account,
                                            data_source: DataSource::Backtest(cursor),
                                            clickhouse_client: ClickHouseClient::new(),
-                                           active_sessions: HashMap::new().into() };
+                                           active_sessions: HashMap::new().into(),
+                                           price_jitter: None,
+                                           sequencer: TimestampSequencer::default(),
+                                           checkpointer: ReplayCheckpointer::default(),
+                                           progress: None,
+                                           liquidation_feed: None,
+                                           depth_feed: None,
+                                           timestamp_normalizer: None,
+                                           replay_clock: None,
+                                           network_latency: None };
         let address = "127.0.0.1:3030".parse().unwrap(); // Convert to a SocketAddr
         assert!(is_port_in_use(address));
         exchange.run_online().await;
@@ -358,4 +769,185 @@ mod tests
     {
         TcpListener::bind(address).is_err()
     }
+
+    #[tokio::test]
+    async fn start_should_drain_queued_events_and_return_summary_on_shutdown()
+    {
+        let (client_tx, client_rx) = mpsc::unbounded_channel();
+        let (market_tx, _market_rx) = mpsc::unbounded_channel();
+        let (_data_tx, data_rx) = mpsc::unbounded_channel();
+        let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+        let mut account = create_test_account().await;
+        account.account_event_tx = account_event_tx;
+        let exchange = ExchangeBuilder::new().event_hourglass_rx(client_rx)
+                                             .market_event_tx(market_tx)
+                                             .account(Arc::new(Mutex::new(account)))
+                                             .data_source(DataSource::RealTime(data_rx))
+                                             .initiate()
+                                             .unwrap();
+
+        // 关闭请求之后仍在排队的请求也会在退出前被处理
+        client_tx.send(HourglassClientEvent::Shutdown).unwrap();
+        let (balances_tx, balances_rx) = tokio::sync::oneshot::channel();
+        client_tx.send(HourglassClientEvent::FetchTokenBalances(balances_tx)).unwrap();
+
+        let summary = exchange.start().await;
+        assert_eq!(summary.reason, ShutdownReason::Requested);
+        assert_eq!(summary.drained_events, 1);
+        assert_eq!(summary.processed_count, 0);
+        assert_eq!(summary.open_orders, 0);
+        assert_eq!(summary.balances.len(), 2);
+        assert!(balances_rx.await.unwrap().is_ok());
+
+        // 收尾时发送最终的持仓与余额快照
+        let mut kinds = Vec::new();
+        while let Ok(event) = account_event_rx.try_recv() {
+            kinds.push(event.kind);
+        }
+        assert!(matches!(kinds.as_slice(), [AccountEventKind::Positions(_), AccountEventKind::Balances(_)]));
+    }
+
+    #[tokio::test]
+    async fn start_should_stop_with_data_source_error_on_invalid_csv_row()
+    {
+        let (client_tx, client_rx) = mpsc::unbounded_channel();
+        let (market_tx, _market_rx) = mpsc::unbounded_channel();
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        let mut account = create_test_account().await;
+        account.account_event_tx = account_event_tx;
+        let csv = "symbol,side,price,timestamp,amount\nETHUSDT,buy,16400,1234568,0.1\nETHUSDT,buy,abc,1234569,0.1\nETHUSDT,buy,16400,1234570,0.1\n";
+        let exchange = ExchangeBuilder::new().event_hourglass_rx(client_rx)
+                                             .market_event_tx(market_tx)
+                                             .account(Arc::new(Mutex::new(account)))
+                                             .data_source(DataSource::Csv(CsvTradeSource::from_reader(csv.as_bytes())))
+                                             .initiate()
+                                             .unwrap();
+        for _ in 0..3 {
+            client_tx.send(HourglassClientEvent::LetItRoll).unwrap();
+        }
+
+        // 损坏的行不会被当作数据结束：回放停止，错误记入运行摘要
+        let summary = exchange.start().await;
+        assert_eq!(summary.reason, ShutdownReason::DataSourceFailed);
+        assert!(summary.data_source_error.unwrap().contains("Invalid CSV trade row"));
+    }
+
+    #[tokio::test]
+    async fn start_should_fill_resting_orders_from_replayed_depth_snapshots()
+    {
+        let (client_tx, client_rx) = mpsc::unbounded_channel();
+        let (market_tx, _market_rx) = mpsc::unbounded_channel();
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        let mut account = create_test_account().await;
+        account.account_event_tx = account_event_tx;
+        let account = Arc::new(Mutex::new(account));
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        account.lock()
+               .await
+               .atomic_open(Order { instruction: OrderInstruction::Limit,
+                                    exchange: Exchange::Hourglass,
+                                    instrument: instrument.clone(),
+                                    timestamp: 1234567,
+                                    cid: Some(ClientOrderId("depth".into())),
+                                    side: Side::Buy,
+                                    state: RequestOpen { price: 16400.0,
+                                                         size: 0.1,
+                                                         reduce_only: false,
+                                                         tag: None } })
+               .await
+               .unwrap();
+
+        // 成交不触及买单，只有深度快照里低于限价的卖方档位能成交它
+        let trade = MarketTrade { exchange: "binance-futures".to_string(),
+                                  symbol: "ETHUSDT".to_string(),
+                                  side: "buy".to_string(),
+                                  price: 16450.0,
+                                  timestamp: 1234570,
+                                  amount: 1.0 };
+        let snapshot = OrderBook25 { exchange: "binance-futures".to_string(),
+                                     symbol: "ETHUSDT".to_string(),
+                                     timestamp: 1234569,
+                                     asks_0_price: 16390.0,
+                                     asks_0_amount: 1.0,
+                                     bids_0_price: 16300.0,
+                                     bids_0_amount: 1.0,
+                                     ..Default::default() };
+        let exchange = ExchangeBuilder::new().event_hourglass_rx(client_rx)
+                                             .market_event_tx(market_tx)
+                                             .account(account.clone())
+                                             .data_source(DataSource::Mock(MockDataSource::new(vec![trade])))
+                                             .depth_feed(DepthFeed::from_rows(vec![snapshot]))
+                                             .initiate()
+                                             .unwrap();
+        for _ in 0..3 {
+            client_tx.send(HourglassClientEvent::LetItRoll).unwrap();
+        }
+
+        let summary = exchange.start().await;
+        assert_eq!(summary.reason, ShutdownReason::DataExhausted);
+        assert_eq!(summary.processed_count, 2);
+        assert_eq!(summary.open_orders, 0);
+        let account = account.lock().await;
+        assert_eq!(account.depth_order_books.lock().await.get(&instrument).unwrap().best_ask(), Some(16390.0));
+    }
+
+    #[tokio::test]
+    async fn start_should_answer_fetch_trades_and_positions_with_capped_history()
+    {
+        let (client_tx, client_rx) = mpsc::unbounded_channel();
+        let (market_tx, _market_rx) = mpsc::unbounded_channel();
+        let (account_event_tx, _account_event_rx) = mpsc::unbounded_channel();
+        let mut account = create_test_account().await;
+        account.account_event_tx = account_event_tx;
+        account.config.trade_history_limit = Some(1);
+        let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
+        account.positions.perpetual_pos_long_config.write().await.insert(instrument.clone(),
+                                                                         PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
+                                                                                                   leverage: 1.0,
+                                                                                                   position_direction_mode: PositionDirectionMode::Net });
+        for cid in ["first", "second"] {
+            account.atomic_open(Order { instruction: OrderInstruction::Limit,
+                                        exchange: Exchange::Hourglass,
+                                        instrument: instrument.clone(),
+                                        timestamp: 1234567,
+                                        cid: Some(ClientOrderId(cid.into())),
+                                        side: Side::Buy,
+                                        state: RequestOpen { price: 16400.0,
+                                                             size: 0.1,
+                                                             reduce_only: false,
+                                                             tag: None } })
+                   .await
+                   .unwrap();
+        }
+
+        // 两笔外部卖单依次成交两个买单
+        let sell_trade = |timestamp: i64| MarketTrade { exchange: "binance-futures".to_string(),
+                                                        symbol: "ETHUSDT".to_string(),
+                                                        side: "sell".to_string(),
+                                                        price: 16300.0,
+                                                        timestamp,
+                                                        amount: 0.1 };
+        let exchange = ExchangeBuilder::new().event_hourglass_rx(client_rx)
+                                             .market_event_tx(market_tx)
+                                             .account(Arc::new(Mutex::new(account)))
+                                             .data_source(DataSource::Mock(MockDataSource::new(vec![sell_trade(1234568), sell_trade(1234569)])))
+                                             .initiate()
+                                             .unwrap();
+        client_tx.send(HourglassClientEvent::LetItRoll).unwrap();
+        client_tx.send(HourglassClientEvent::LetItRoll).unwrap();
+        let (trades_tx, trades_rx) = tokio::sync::oneshot::channel();
+        client_tx.send(HourglassClientEvent::FetchTrades(instrument.clone(), trades_tx)).unwrap();
+        let (positions_tx, positions_rx) = tokio::sync::oneshot::channel();
+        client_tx.send(HourglassClientEvent::FetchPositions(positions_tx)).unwrap();
+        client_tx.send(HourglassClientEvent::LetItRoll).unwrap();
+
+        let summary = exchange.start().await;
+        assert_eq!(summary.reason, ShutdownReason::DataExhausted);
+
+        // 每个金融工具只保留最近一笔成交，仓位快照包含两笔成交
+        let trades = trades_rx.await.unwrap().unwrap();
+        assert_eq!(trades.iter().map(|trade| trade.cid.clone()).collect::<Vec<_>>(), vec![Some(ClientOrderId("second".into()))]);
+        let positions = positions_rx.await.unwrap().unwrap();
+        assert!((positions.perpetual_pos_long[&instrument].meta.current_size - 0.2).abs() < 1e-9);
+    }
 }
@@ -2,29 +2,102 @@ use crate::{
     common::{
         friction::{Fees, InstrumentFees, OptionFees, PerpetualFees, SpotFees},
         instrument::kind::InstrumentKind,
-        order::{states::open::Open, Order},
+        order::{identification::OrderId, order_instructions::OrderInstruction, states::open::Open, Order, OrderRole},
         trade::ClientTrade,
         Side,
     },
     error::ExchangeError,
-    hourglass::clickhouse_api::datatype::clickhouse_trade_data::MarketTrade,
+    hourglass::{
+        account::account_config::{FillPricePolicy, MakerFillTrigger, SlippageModel},
+        clickhouse_api::datatype::clickhouse_trade_data::MarketTrade,
+        matching_engine::{Fill, MatchingRules}},
+    hourglass_log::warn,
     Exchange,
 };
-use rayon::prelude::ParallelSliceMut;
 use serde::{Deserialize, Serialize};
 use std::{
+    cmp::{Ordering as CmpOrdering, Reverse},
+    collections::BinaryHeap,
     fmt::Debug,
     sync::atomic::{AtomicI64, Ordering},
 };
 
 /// 客户端针对一个 [`Instrument`] 的 [`OpenOrdersBook`]。模拟客户端订单簿。
-#[derive(Clone, Eq, PartialEq, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct OpenOrdersBook
 {
     /// 在当前的代码设计中，batch_id 的递增仅在成功匹配订单并生成交易事件时发生
     // pub batch_id: i64,
     pub bids: Vec<Order<Open>>,
     pub asks: Vec<Order<Open>>,
+    // GTT 挂单按到期时间排序的最小堆，由挂单派生，不序列化，反序列化后用 `rebuild_expiry_index` 重建。
+    // 成交、撤单或改价时不从堆中删除，弹出时找不到对应挂单的条目直接丢弃（惰性删除）。
+    #[serde(skip)]
+    expiry_index: BinaryHeap<Reverse<ExpiryEntry>>,
+}
+
+/// 到期索引只是挂单的派生数据，比较挂单簿时只比较挂单本身。
+impl PartialEq for OpenOrdersBook
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.bids == other.bids && self.asks == other.asks
+    }
+}
+
+impl Eq for OpenOrdersBook {}
+
+/// 到期索引中失效条目超过挂单数量的倍数时重建索引，避免长期撤单导致堆无限增长。
+const EXPIRY_INDEX_COMPACTION_RATIO: usize = 2;
+
+/// 到期索引中的一项，按 (到期时间, 订单ID) 排序。记录登记时的方向与价格，用于在挂单簿中二分定位订单。
+#[derive(Clone, Debug)]
+struct ExpiryEntry
+{
+    expire_ts: i64,
+    id: OrderId,
+    side: Side,
+    price: f64,
+}
+
+impl ExpiryEntry
+{
+    fn of(order: &Order<Open>) -> Option<Self>
+    {
+        match order.instruction {
+            | OrderInstruction::GoodTilTime { expire_ts } => Some(Self { expire_ts,
+                                                                         id: order.state.id.clone(),
+                                                                         side: order.side,
+                                                                         price: order.state.price }),
+            | _ => None,
+        }
+    }
+}
+
+impl PartialEq for ExpiryEntry
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for ExpiryEntry {}
+
+impl PartialOrd for ExpiryEntry
+{
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering>
+    {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ExpiryEntry
+{
+    fn cmp(&self, other: &Self) -> CmpOrdering
+    {
+        (self.expire_ts, &self.id).cmp(&(other.expire_ts, &other.id))
+    }
 }
 
 /// 计算 [`Order<Open>`] 对应的 [`Fees`]
@@ -60,181 +133,490 @@ pub fn calculate_fees(order: &Order<Open>, trade_quantity: f64, fees_percent: f6
 /// 添加一个 [`Order<Open>`] 到买单或卖单中，取决于它的 [`Side`]。
 impl OpenOrdersBook
 {
+    ///
+    /// 挂单按价格升序排列，撮合时从队尾取出。同价位的挂单按到达顺序排队（先到先成交），
+    /// 因此新订单插入到同价位已有订单之前。同一批次内的订单按提交顺序依次到达，批次内的撮合优先级由此确定。
     pub fn add_order_open(&mut self, new_open_order: Order<Open>)
     {
-        match new_open_order.side {
-            | Side::Buy => {
-                // 添加 Order<Open> 到买单
-                self.bids.push(new_open_order);
-                self.bids.par_sort();
-            }
-            | Side::Sell => {
-                // 添加 Order<Open> 到卖单
-                self.asks.push(new_open_order);
-                self.asks.par_sort();
+        let orders = match new_open_order.side {
+            | Side::Buy => &self.bids,
+            | Side::Sell => &self.asks,
+        };
+        let index = orders.partition_point(|order| order.state.price < new_open_order.state.price);
+        self.insert_order_at(index, new_open_order);
+    }
+
+    /// 按订单的 `timestamp` 把 [`Order<Open>`] 插入到所在价位中，保留其原有的排队优先级。
+    ///
+    /// 同价位中时间晚于该订单的挂单排在它之前（后成交），时间相同或更早的挂单排在它之后（先成交）。
+    /// 用于不丢失优先级的改价，例如把价格改离盘口。
+    pub fn add_order_open_by_time(&mut self, open_order: Order<Open>)
+    {
+        let orders = match open_order.side {
+            | Side::Buy => &self.bids,
+            | Side::Sell => &self.asks,
+        };
+        let level_start = orders.partition_point(|order| order.state.price < open_order.state.price);
+        let newer = orders[level_start..].iter()
+                                         .take_while(|order| order.state.price == open_order.state.price && order.timestamp > open_order.timestamp)
+                                         .count();
+        self.insert_order_at(level_start + newer, open_order);
+    }
+
+    fn insert_order_at(&mut self, index: usize, open_order: Order<Open>)
+    {
+        if let Some(entry) = ExpiryEntry::of(&open_order) {
+            self.expiry_index.push(Reverse(entry));
+        }
+        match open_order.side {
+            | Side::Buy => self.bids.insert(index, open_order),
+            | Side::Sell => self.asks.insert(index, open_order),
+        }
+        if self.expiry_index.len() > EXPIRY_INDEX_COMPACTION_RATIO * self.num_orders() + 64 {
+            self.rebuild_expiry_index();
+        }
+    }
+
+    /// 按当前挂单重建 GTT 到期索引，丢弃所有失效条目。从检查点恢复挂单簿后必须调用。
+    pub fn rebuild_expiry_index(&mut self)
+    {
+        self.expiry_index = self.bids.iter().chain(self.asks.iter()).filter_map(ExpiryEntry::of).map(Reverse).collect();
+    }
+
+    /// 取出在交易所时间 `now` 已经到期的 GTT 挂单，同时返回查找过程中检查过的挂单数量。
+    ///
+    /// 只弹出到期时间不晚于 `now` 的索引条目，再按登记的方向与价格二分定位价位，只在该价位内查找订单，
+    /// 未到期的挂单与其他价位的挂单不会被访问。已成交、已撤销或已改价的订单留下的条目找不到对应挂单，直接丢弃。
+    pub fn take_expired_orders(&mut self, now: i64) -> (Vec<Order<Open>>, usize)
+    {
+        let mut expired = Vec::new();
+        let mut inspected = 0;
+        while self.expiry_index.peek().is_some_and(|Reverse(entry)| entry.expire_ts <= now) {
+            let Reverse(entry) = self.expiry_index.pop().expect("peeked entry exists");
+            let orders = match entry.side {
+                | Side::Buy => &mut self.bids,
+                | Side::Sell => &mut self.asks,
+            };
+            let level_start = orders.partition_point(|order| order.state.price < entry.price);
+            let position = orders[level_start..].iter()
+                                                .take_while(|order| order.state.price == entry.price)
+                                                .inspect(|_| inspected += 1)
+                                                .position(|order| order.state.id == entry.id && order.instruction.is_expired(now));
+            if let Some(offset) = position {
+                expired.push(orders.remove(level_start + offset));
             }
         }
+        (expired, inspected)
     }
 
     // 检查传入的 [`MarketTrade`] 与当前客户 [`Order<Open>`] 匹配的是买单还是卖单
-    pub fn determine_matching_side(&self, market_event: &MarketTrade) -> Option<Side>
+    pub fn determine_matching_side(&self, market_event: &MarketTrade, fill_trigger: MakerFillTrigger) -> Option<Side>
     {
-        match market_event.side.as_str() {
-            | "buy" => {
-                // 如果市场方向是买单，检查卖单的最佳报价
+        match market_event.aggressor_side() {
+            | Some(Side::Buy) => {
+                // 主动买单只会吃掉卖方挂单，检查卖单的最佳报价
                 if let Some(best_ask) = self.asks.last() {
-                    if market_event.price >= best_ask.state.price {
+                    if fill_trigger.is_triggered(Side::Sell, best_ask.state.price, market_event.price) {
                         return Some(Side::Sell);
                     }
                 }
             }
-            | "sell" => {
-                // 如果市场方向是卖单，检查买单的最佳报价
+            | Some(Side::Sell) => {
+                // 主动卖单只会吃掉买方挂单，检查买单的最佳报价
                 if let Some(best_bid) = self.bids.last() {
-                    if market_event.price <= best_bid.state.price {
+                    if fill_trigger.is_triggered(Side::Buy, best_bid.state.price, market_event.price) {
                         return Some(Side::Buy);
                     }
                 }
             }
-            | _ => {
-                println!("Input MarketTrade is likely to have mistaken 'side' info.")
+            | None => {
+                warn!("Input MarketTrade has unrecognised aggressor side '{}', skipping matching.", market_event.side)
             }
         }
         None
     }
 
-    pub fn match_bids(&mut self, market_trade: &MarketTrade, fees_percent: f64, counter: &AtomicI64) -> Vec<ClientTrade>
+    pub fn match_bids(&mut self, market_trade: &MarketTrade, commission: &dyn Fn(&ClientTrade, OrderRole) -> Result<f64, ExchangeError>, counter: &AtomicI64, fill_price_policy: FillPricePolicy, fill_trigger: MakerFillTrigger) -> Result<Vec<ClientTrade>, ExchangeError>
+    {
+        let fills = self.fifo_fills(Side::Buy, market_trade, &MatchingRules { fill_price_policy, fill_trigger, slippage_model: SlippageModel::None });
+        self.client_trades_from_fills(market_trade.timestamp, &fills, commission, counter)
+    }
+
+    pub fn match_asks(&mut self, market_trade: &MarketTrade, commission: &dyn Fn(&ClientTrade, OrderRole) -> Result<f64, ExchangeError>, counter: &AtomicI64, fill_price_policy: FillPricePolicy, fill_trigger: MakerFillTrigger) -> Result<Vec<ClientTrade>, ExchangeError>
     {
+        let fills = self.fifo_fills(Side::Sell, market_trade, &MatchingRules { fill_price_policy, fill_trigger, slippage_model: SlippageModel::None });
+        self.client_trades_from_fills(market_trade.timestamp, &fills, commission, counter)
+    }
+
+    /// 按价格优先、时间优先撮合 `side` 方向的挂单，返回每笔成交的 [`Fill`]，是 [`FifoMatcher`](crate::hourglass::matching_engine::FifoMatcher) 的撮合逻辑。
+    pub fn fifo_fills(&mut self, side: Side, market_trade: &MarketTrade, rules: &MatchingRules) -> Vec<Fill>
+    {
+        // 主动卖单只会成交买方挂单，主动买单只会成交卖方挂单
+        if market_trade.aggressor_side() != Some(side.toggle()) {
+            return Vec::new();
+        }
+
         let latest_trade_ts = market_trade.timestamp;
+        let orders = match side {
+            | Side::Buy => &mut self.bids,
+            | Side::Sell => &mut self.asks,
+        };
 
         // Track remaining liquidity for matching
         let mut remaining_liquidity = market_trade.amount;
 
-        // Collect trades generated by matching outstanding bid orders
-        let mut trades = Vec::new();
-
-        while let Some(mut best_bid) = self.bids.pop() {
-            let bid_timestamp = best_bid.timestamp;
+        // Collect fills generated by matching outstanding orders
+        let mut fills = Vec::new();
+        // 尚未到达交易所的挂单与无法被本笔成交完全成交的 FOK 挂单不参与本次撮合，撮合结束后按原顺序放回
+        let mut skipped = Vec::new();
 
-            // 如果传入的market_trade.timestamp比bid_timestamp小，则跳过该bid，但不报错
-            if latest_trade_ts < bid_timestamp {
-                self.bids.push(best_bid);
+        while let Some(mut best_order) = orders.pop() {
+            // 略过 timestamp 比传入的 market_trade.timestamp 大的挂单，但不报错
+            if latest_trade_ts < best_order.timestamp {
+                skipped.push(best_order);
                 continue;
             }
 
-            // If the best bid price is below the market trade price or liquidity is exhausted, exit loop
-            if best_bid.state.price < market_trade.price || remaining_liquidity <= 0.0 {
-                self.bids.push(best_bid);
+            // If the best order is not triggered by the market trade price or liquidity is exhausted, exit loop
+            if !rules.fill_trigger.is_triggered(side, best_order.state.price, market_trade.price) || remaining_liquidity <= 0.0 {
+                orders.push(best_order);
                 break;
             }
 
-            // Increment the atomic counter (this returns the old value)
-            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-
             // Get the remaining quantity of the order
-            let remaining_quantity = best_bid.state.remaining_quantity();
+            // 冰山单每次只有显示部分参与撮合
+            let remaining_quantity = best_order.state.remaining_quantity();
+            let trade_quantity = best_order.state.visible_quantity().min(remaining_liquidity);
+
+            // FOK 挂单不允许部分成交，也不消耗本笔成交的流动性，之后由账户整笔撤销
+            if best_order.instruction == OrderInstruction::FillOrKill && trade_quantity < remaining_quantity {
+                skipped.push(best_order);
+                continue;
+            }
+
+            // 按 FillPricePolicy 确定本次成交价，Taker 订单再叠加滑点
+            let fill_price = rules.fill_price(&best_order, market_trade.price, trade_quantity);
+            remaining_liquidity -= trade_quantity;
 
             // Determine if it's a full or partial fill
-            if remaining_quantity <= remaining_liquidity {
+            if trade_quantity >= remaining_quantity {
                 // Full fill
-                remaining_liquidity -= remaining_quantity;
-                trades.push(self.generate_client_trade_event(latest_trade_ts, &best_bid, remaining_quantity, fees_percent, counter).unwrap());
+                best_order.state.record_fill(fill_price, remaining_quantity);
+                fills.push(Fill { order: best_order,
+                                  price: fill_price,
+                                  quantity: remaining_quantity });
 
                 // If liquidity is exactly exhausted, exit loop
-                if remaining_liquidity == 0.0 {
+                if remaining_liquidity <= 0.0 {
                     break;
                 }
             }
             else {
                 // Partial fill
-                let trade_quantity = remaining_liquidity;
-                best_bid.state.filled_quantity += trade_quantity;
-                trades.push(self.generate_client_trade_event(latest_trade_ts, &best_bid, trade_quantity, fees_percent, counter).unwrap());
-                self.bids.push(best_bid); // Put the partially filled order back into the queue
+                best_order.state.record_fill(fill_price, trade_quantity);
+                fills.push(Fill { order: best_order.clone(),
+                                  price: fill_price,
+                                  quantity: trade_quantity });
+
+                // 冰山单的显示部分被吃完时从隐藏部分补充，排到同价位队尾后继续撮合
+                if best_order.state.replenish() {
+                    let index = orders.partition_point(|order| order.state.price < best_order.state.price);
+                    orders.insert(index, best_order);
+                    if remaining_liquidity <= 0.0 {
+                        break;
+                    }
+                    continue;
+                }
+                orders.push(best_order); // Put the partially filled order back into the queue
                 break;
             }
         }
 
-        trades
+        orders.extend(skipped.into_iter().rev());
+
+        fills
     }
 
-    pub fn match_asks(&mut self, market_trade: &MarketTrade, fees_percent: f64, counter: &AtomicI64) -> Vec<ClientTrade>
+    /// 为撮合产生的每笔 [`Fill`] 生成 [`ClientTrade`]，每笔成交递增一次 `counter` 作为成交ID。
+    pub fn client_trades_from_fills(&self, timestamp: i64, fills: &[Fill], commission: &dyn Fn(&ClientTrade, OrderRole) -> Result<f64, ExchangeError>, counter: &AtomicI64) -> Result<Vec<ClientTrade>, ExchangeError>
     {
-        let latest_trade_ts = market_trade.timestamp;
+        fills.iter()
+             .map(|fill| {
+                 counter.fetch_add(1, Ordering::SeqCst);
+                 self.generate_client_trade_event(timestamp, &fill.order, fill.price, fill.quantity, commission, counter)
+             })
+             .collect()
+    }
 
-        // Track remaining liquidity for matching
-        let mut remaining_liquidity = market_trade.amount;
+    /// 生成 [`ClientTrade`]，`fill_price` 由 [`FillPricePolicy`] 决定。
+    ///
+    /// 手续费由 `commission` 按实际成交与订单的 [`OrderRole`] 计算，账户传入的是其
+    /// [`CommissionProvider`](crate::hourglass::account::account_commission::CommissionProvider)，计费失败时返回其错误。
+    pub fn generate_client_trade_event(&self, timestamp: i64, order: &Order<Open>, fill_price: f64, trade_quantity: f64, commission: &dyn Fn(&ClientTrade, OrderRole) -> Result<f64, ExchangeError>, counter: &AtomicI64) -> Result<ClientTrade, ExchangeError>
+    {
+        // Fetch the current value from the AtomicI64
+        let trade_id = counter.load(Ordering::SeqCst); // Get the current value as an `i64`
+
+        let mut trade = ClientTrade { exchange: Exchange::Hourglass,
+                                      timestamp,
+                                      trade_id: trade_id.into(), // Use the fetched trade ID
+                                      order_id: Some(order.state.id.clone()),
+                                      cid: order.cid.clone(),
+                                      instrument: order.instrument.clone(),
+                                      side: order.side,
+                                      price: fill_price,
+                                      size: trade_quantity,
+                                      fees: 0.0,
+                                      tag: order.state.tag.clone() };
+        trade.fees = commission(&trade, order.state.order_role)?;
+        Ok(trade)
+    }
 
-        // Collect trades generated by matching outstanding sell orders
-        let mut trades = Vec::new();
+    /// 计算所有未成交买单和卖单的总数。
+    pub fn num_orders(&self) -> usize
+    {
+        self.bids.len() + self.asks.len()
+    }
+}
 
-        while let Some(mut best_ask) = self.asks.pop() {
-            let ask_timestamp = best_ask.timestamp;
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::test_utils::create_test_order_open;
 
-            // 略过 timestamp 比传入的 market_trade.timestamp 小的情况
-            if latest_trade_ts < ask_timestamp {
-                self.asks.push(best_ask);
-                continue;
-            }
+    fn fee(trade: &ClientTrade, _role: OrderRole) -> Result<f64, ExchangeError>
+    {
+        Ok(trade.price * trade.size * 0.001)
+    }
 
-            // If the best ask price is higher than the market trade price or liquidity is exhausted, exit loop
-            if best_ask.state.price > market_trade.price || remaining_liquidity <= 0.0 {
-                self.asks.push(best_ask);
-                break;
-            }
+    fn create_test_market_trade(side: Side, price: f64, amount: f64) -> MarketTrade
+    {
+        MarketTrade { exchange: "binance-futures".to_string(),
+                      symbol: "ETH_USDT".to_string(),
+                      side: side.to_string(),
+                      price,
+                      timestamp: 1625247600000,
+                      amount }
+    }
 
-            // Increment the atomic counter, but pass the counter reference to generate_client_trade_event
-            counter.fetch_add(1, Ordering::SeqCst);
+    fn match_single_bid(fill_price_policy: FillPricePolicy) -> ClientTrade
+    {
+        let mut book = OpenOrdersBook::default();
+        book.add_order_open(create_test_order_open(Side::Buy, 100.0, 1.0));
+        let counter = AtomicI64::new(0);
 
-            // Get the remaining quantity of the order
-            let remaining_quantity = best_ask.state.remaining_quantity();
+        let trades = book.match_bids(&create_test_market_trade(Side::Sell, 98.0, 1.0), &fee, &counter, fill_price_policy, MakerFillTrigger::OnTradeThrough).unwrap();
+        assert_eq!(trades.len(), 1);
+        trades[0].clone()
+    }
 
-            // Determine if it's a full or partial fill
-            if remaining_quantity <= remaining_liquidity {
-                // Fully fill
-                remaining_liquidity -= remaining_quantity;
-                trades.push(self.generate_client_trade_event(latest_trade_ts, &best_ask, remaining_quantity, fees_percent, counter).unwrap());
+    #[test]
+    fn test_fill_price_policy_resting_limit()
+    {
+        let trade = match_single_bid(FillPricePolicy::RestingLimit);
+        assert_eq!(trade.price, 100.0);
+        assert_eq!(trade.fees, 100.0 * 0.001);
+    }
 
-                // If liquidity is exactly exhausted, exit loop
-                if remaining_liquidity == 0.0 {
-                    break;
-                }
-            }
-            else {
-                // Partial fill
-                let trade_quantity = remaining_liquidity;
-                best_ask.state.filled_quantity += trade_quantity;
-                trades.push(self.generate_client_trade_event(latest_trade_ts, &best_ask, trade_quantity, fees_percent, counter).unwrap());
-                self.asks.push(best_ask); // Put the partially filled order back into the queue
-                break;
-            }
+    #[test]
+    fn test_fill_price_policy_trade_price()
+    {
+        let trade = match_single_bid(FillPricePolicy::TradePrice);
+        assert_eq!(trade.price, 98.0);
+        assert_eq!(trade.fees, 98.0 * 0.001);
+    }
+
+    #[test]
+    fn test_fill_price_policy_midpoint()
+    {
+        let trade = match_single_bid(FillPricePolicy::Midpoint);
+        assert_eq!(trade.price, 99.0);
+    }
+
+    #[test]
+    fn test_order_tag_flows_into_client_trade()
+    {
+        let mut book = OpenOrdersBook::default();
+        let mut order = create_test_order_open(Side::Buy, 100.0, 1.0);
+        order.state.tag = Some("mean_reversion".to_string());
+        book.add_order_open(order);
+        let counter = AtomicI64::new(0);
+
+        let trades = book.match_bids(&create_test_market_trade(Side::Sell, 98.0, 0.4), &fee, &counter, FillPricePolicy::RestingLimit, MakerFillTrigger::OnTradeThrough).unwrap();
+        assert_eq!(trades[0].tag.as_deref(), Some("mean_reversion"));
+        // 部分成交后留在挂单簿中的订单保留标签
+        assert_eq!(book.bids[0].state.tag.as_deref(), Some("mean_reversion"));
+    }
+
+    #[test]
+    fn test_fill_price_policy_on_asks()
+    {
+        let mut book = OpenOrdersBook::default();
+        book.add_order_open(create_test_order_open(Side::Sell, 100.0, 1.0));
+        let counter = AtomicI64::new(0);
+
+        let trades = book.match_asks(&create_test_market_trade(Side::Buy, 104.0, 1.0), &fee, &counter, FillPricePolicy::Midpoint, MakerFillTrigger::OnTradeThrough).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].price, 102.0);
+    }
+
+    #[test]
+    fn test_partial_fills_track_avg_fill_price()
+    {
+        let mut book = OpenOrdersBook::default();
+        book.add_order_open(create_test_order_open(Side::Buy, 100.0, 3.0));
+        let counter = AtomicI64::new(0);
+
+        let first = book.match_bids(&create_test_market_trade(Side::Sell, 98.0, 1.0), &fee, &counter, FillPricePolicy::TradePrice, MakerFillTrigger::OnTradeThrough).unwrap();
+        let second = book.match_bids(&create_test_market_trade(Side::Sell, 99.0, 1.0), &fee, &counter, FillPricePolicy::TradePrice, MakerFillTrigger::OnTradeThrough).unwrap();
+
+        let fills: Vec<ClientTrade> = first.into_iter().chain(second).collect();
+        let vwap = fills.iter().map(|t| t.price * t.size).sum::<f64>() / fills.iter().map(|t| t.size).sum::<f64>();
+
+        let resting = book.bids.last().unwrap();
+        assert_eq!(resting.state.filled_quantity, 2.0);
+        assert_eq!(resting.state.avg_fill_price(), vwap);
+        assert_eq!(resting.state.avg_fill_price(), 98.5);
+    }
+
+    #[test]
+    fn test_buy_aggressor_only_lifts_asks()
+    {
+        let mut book = OpenOrdersBook::default();
+        book.add_order_open(create_test_order_open(Side::Buy, 100.0, 1.0));
+        book.add_order_open(create_test_order_open(Side::Sell, 101.0, 1.0));
+        let counter = AtomicI64::new(0);
+
+        // 主动买单价格同时穿过了买单与卖单，但只能成交卖单
+        let market_trade = create_test_market_trade(Side::Buy, 101.5, 5.0);
+        assert_eq!(book.determine_matching_side(&market_trade, MakerFillTrigger::OnTradeThrough), Some(Side::Sell));
+        assert!(book.match_bids(&market_trade, &fee, &counter, FillPricePolicy::RestingLimit, MakerFillTrigger::OnTradeThrough).unwrap().is_empty());
+        let trades = book.match_asks(&market_trade, &fee, &counter, FillPricePolicy::RestingLimit, MakerFillTrigger::OnTradeThrough).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].side, Side::Sell);
+        assert_eq!(book.bids.len(), 1);
+        assert!(book.asks.is_empty());
+    }
+
+    #[test]
+    fn test_sell_aggressor_only_hits_bids()
+    {
+        let mut book = OpenOrdersBook::default();
+        book.add_order_open(create_test_order_open(Side::Buy, 100.0, 1.0));
+        book.add_order_open(create_test_order_open(Side::Sell, 99.0, 1.0));
+        let counter = AtomicI64::new(0);
+
+        // 大写的方向同样能被识别
+        let mut market_trade = create_test_market_trade(Side::Sell, 98.5, 5.0);
+        market_trade.side = "SELL".to_string();
+        assert_eq!(book.determine_matching_side(&market_trade, MakerFillTrigger::OnTradeThrough), Some(Side::Buy));
+        assert!(book.match_asks(&market_trade, &fee, &counter, FillPricePolicy::RestingLimit, MakerFillTrigger::OnTradeThrough).unwrap().is_empty());
+        let trades = book.match_bids(&market_trade, &fee, &counter, FillPricePolicy::RestingLimit, MakerFillTrigger::OnTradeThrough).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].side, Side::Buy);
+        assert!(book.bids.is_empty());
+        assert_eq!(book.asks.len(), 1);
+    }
+
+    #[test]
+    fn test_unrecognised_aggressor_side_matches_nothing()
+    {
+        let mut book = OpenOrdersBook::default();
+        book.add_order_open(create_test_order_open(Side::Buy, 100.0, 1.0));
+        book.add_order_open(create_test_order_open(Side::Sell, 101.0, 1.0));
+        let counter = AtomicI64::new(0);
+
+        let mut market_trade = create_test_market_trade(Side::Buy, 100.5, 5.0);
+        market_trade.side = "unknown".to_string();
+        assert_eq!(book.determine_matching_side(&market_trade, MakerFillTrigger::OnTradeThrough), None);
+        assert!(book.match_bids(&market_trade, &fee, &counter, FillPricePolicy::RestingLimit, MakerFillTrigger::OnTradeThrough).unwrap().is_empty());
+        assert!(book.match_asks(&market_trade, &fee, &counter, FillPricePolicy::RestingLimit, MakerFillTrigger::OnTradeThrough).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_maker_fill_trigger_at_exact_touch()
+    {
+        let counter = AtomicI64::new(0);
+
+        // 恰好在挂单价的成交：OnTouch 成交，OnTradeThrough 不成交
+        for (fill_trigger, expected_fills) in [(MakerFillTrigger::OnTouch, 1), (MakerFillTrigger::OnTradeThrough, 0)] {
+            let mut book = OpenOrdersBook::default();
+            book.add_order_open(create_test_order_open(Side::Buy, 100.0, 1.0));
+            book.add_order_open(create_test_order_open(Side::Sell, 101.0, 1.0));
+
+            let touch_bid = create_test_market_trade(Side::Sell, 100.0, 1.0);
+            assert_eq!(book.determine_matching_side(&touch_bid, fill_trigger).is_some(), expected_fills == 1);
+            assert_eq!(book.match_bids(&touch_bid, &fee, &counter, FillPricePolicy::RestingLimit, fill_trigger).unwrap().len(), expected_fills);
+
+            let touch_ask = create_test_market_trade(Side::Buy, 101.0, 1.0);
+            assert_eq!(book.determine_matching_side(&touch_ask, fill_trigger).is_some(), expected_fills == 1);
+            assert_eq!(book.match_asks(&touch_ask, &fee, &counter, FillPricePolicy::RestingLimit, fill_trigger).unwrap().len(), expected_fills);
         }
 
-        trades
+        // 穿价成交在两种模式下都会成交
+        for fill_trigger in [MakerFillTrigger::OnTouch, MakerFillTrigger::OnTradeThrough] {
+            let mut book = OpenOrdersBook::default();
+            book.add_order_open(create_test_order_open(Side::Buy, 100.0, 1.0));
+            book.add_order_open(create_test_order_open(Side::Sell, 101.0, 1.0));
+            assert_eq!(book.match_bids(&create_test_market_trade(Side::Sell, 99.99, 1.0), &fee, &counter, FillPricePolicy::RestingLimit, fill_trigger).unwrap().len(), 1);
+            assert_eq!(book.match_asks(&create_test_market_trade(Side::Buy, 101.01, 1.0), &fee, &counter, FillPricePolicy::RestingLimit, fill_trigger).unwrap().len(), 1);
+        }
     }
 
-    pub fn generate_client_trade_event(&self, timestamp: i64, order: &Order<Open>, trade_quantity: f64, fees_percent: f64, counter: &AtomicI64) -> Result<ClientTrade, ExchangeError>
+    #[test]
+    fn test_maker_fill_trigger_defaults_to_trade_through()
     {
-        let fee = trade_quantity * order.state.price * fees_percent;
+        assert_eq!(MakerFillTrigger::default(), MakerFillTrigger::OnTradeThrough);
+    }
 
-        // Fetch the current value from the AtomicI64
-        let trade_id = counter.load(Ordering::SeqCst); // Get the current value as an `i64`
+    #[test]
+    fn test_fill_price_policy_defaults_to_resting_limit()
+    {
+        assert_eq!(FillPricePolicy::default(), FillPricePolicy::RestingLimit);
+    }
 
-        Ok(ClientTrade { exchange: Exchange::Hourglass,
-                         timestamp,
-                         trade_id: trade_id.into(), // Use the fetched trade ID
-                         order_id: Some(order.state.id.clone()),
-                         cid: order.cid.clone(),
-                         instrument: order.instrument.clone(),
-                         side: order.side,
-                         price: order.state.price,
-                         size: trade_quantity,
-                         fees: fee })
+    #[test]
+    fn test_expiry_sweep_touches_only_expired_orders()
+    {
+        let resting = 20_000u64;
+        let mut book = OpenOrdersBook::default();
+        for id in 0..resting {
+            let mut order = create_test_order_open(Side::Buy, 100.0 + id as f64 * 0.01, 1.0);
+            order.state.id = OrderId(id);
+            // 只有 5 个挂单在 t=1000 到期，其余都在很久以后
+            let expire_ts = if id % 4_000 == 0 { 1_000 } else { 1_000_000 + id as i64 };
+            order.instruction = OrderInstruction::GoodTilTime { expire_ts };
+            book.add_order_open(order);
+        }
+        // 撤销其中一个即将到期的挂单，其索引条目留在堆中，弹出时被丢弃
+        book.bids.retain(|order| order.state.id != OrderId(4_000));
+
+        let (expired, inspected) = book.take_expired_orders(999);
+        assert!(expired.is_empty());
+        assert_eq!(inspected, 0);
+
+        let (expired, inspected) = book.take_expired_orders(1_000);
+        let mut expired_ids: Vec<u64> = expired.iter().map(|order| order.state.id.0).collect();
+        expired_ids.sort();
+        assert_eq!(expired_ids, vec![0, 8_000, 12_000, 16_000]);
+        // 每个到期挂单独占一个价位，只检查了这 4 个挂单
+        assert_eq!(inspected, 4);
+        assert_eq!(book.num_orders(), resting as usize - 5);
     }
 
-    /// 计算所有未成交买单和卖单的总数。
-    pub fn num_orders(&self) -> usize
+    #[test]
+    fn test_expiry_index_survives_serialization()
     {
-        self.bids.len() + self.asks.len()
+        let mut book = OpenOrdersBook::default();
+        let mut order = create_test_order_open(Side::Sell, 100.0, 1.0);
+        order.instruction = OrderInstruction::GoodTilTime { expire_ts: 1_000 };
+        book.add_order_open(order);
+
+        let mut restored: OpenOrdersBook = serde_json::from_str(&serde_json::to_string(&book).unwrap()).unwrap();
+        assert_eq!(restored, book);
+        restored.rebuild_expiry_index();
+        assert_eq!(restored.take_expired_orders(1_000).0.len(), 1);
+        assert!(restored.asks.is_empty());
     }
 }
@@ -0,0 +1,199 @@
+use chrono::{Duration as ChronoDuration, NaiveDate};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// 回测进度回调的触发条件，两个条件任一满足即回调。
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProgressPolicy
+{
+    pub every_events: Option<u64>,      // 每处理 N 条市场数据回调一次
+    pub every_percent: Option<f64>,     // 模拟时间每推进回放区间的 P% 回调一次，需要设置 `time_range`
+    pub time_range: Option<(i64, i64)>, // 回放区间的起止时间戳（毫秒），用于计算完成比例与预计剩余时间
+}
+
+impl ProgressPolicy
+{
+    /// 以按日回放的数据区间 `[start_date, end_date]`（UTC，含首尾两天）作为 `time_range`。
+    pub fn for_dates(start_date: NaiveDate, end_date: NaiveDate) -> Self
+    {
+        let start = start_date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp_millis();
+        let end = (end_date + ChronoDuration::days(1)).and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp_millis();
+        Self { time_range: Some((start, end)),
+               ..Default::default() }
+    }
+
+    pub fn every_events(self, value: u64) -> Self
+    {
+        Self { every_events: Some(value),
+               ..self }
+    }
+
+    pub fn every_percent(self, value: f64) -> Self
+    {
+        Self { every_percent: Some(value),
+               ..self }
+    }
+
+    /// 模拟时间在回放区间中的完成比例，限定在 `[0, 1]`；未设置区间时返回 `None`。
+    fn fraction(&self, timestamp: i64) -> Option<f64>
+    {
+        let (start, end) = self.time_range?;
+        if end <= start {
+            return None;
+        }
+        Some(((timestamp - start) as f64 / (end - start) as f64).clamp(0.0, 1.0))
+    }
+}
+
+/// 传给进度回调的回测进度。
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgressReport
+{
+    pub processed_count: u64,   // 本次运行已处理的市场数据条数
+    pub sim_timestamp: i64,     // 当前的模拟时间（最新处理数据的时间戳）
+    pub wall_elapsed: Duration, // 本次运行已耗费的真实时间
+    pub fraction: Option<f64>,  // 模拟时间在回放区间中的完成比例，未设置区间时为 `None`
+    pub eta: Option<Duration>,  // 按本次运行的推进速度估算的剩余真实时间，无法估算时为 `None`
+}
+
+/// 用户提供的进度回调。
+pub type ProgressCallback = Box<dyn FnMut(&ProgressReport) + Send>;
+
+/// 按 [`ProgressPolicy`] 决定何时调用进度回调，并估算预计完成时间。
+pub struct ProgressReporter
+{
+    policy: ProgressPolicy,
+    callback: ProgressCallback,
+    started: Option<(Instant, Option<f64>)>, // 本次运行开始处理的真实时间与当时的完成比例
+    last_report_count: u64,
+    last_report_fraction: f64,
+}
+
+impl fmt::Debug for ProgressReporter
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        f.debug_struct("ProgressReporter")
+         .field("policy", &self.policy)
+         .field("last_report_count", &self.last_report_count)
+         .field("last_report_fraction", &self.last_report_fraction)
+         .finish()
+    }
+}
+
+impl ProgressReporter
+{
+    pub fn new(policy: ProgressPolicy, callback: ProgressCallback) -> Self
+    {
+        Self { policy,
+               callback,
+               started: None,
+               last_report_count: 0,
+               last_report_fraction: 0.0 }
+    }
+
+    /// 记录一条已处理的数据，满足触发条件时调用进度回调。
+    ///
+    /// 预计剩余时间按本次运行开始以来的推进速度线性外推，因此从检查点恢复后不会把之前已完成的部分算作本次的速度。
+    pub fn advance(&mut self, processed_count: u64, timestamp: i64)
+    {
+        let fraction = self.policy.fraction(timestamp);
+        if self.started.is_none() {
+            // 从检查点恢复时从当前比例开始计算百分比间隔
+            self.started = Some((Instant::now(), fraction));
+            self.last_report_fraction = fraction.unwrap_or(0.0);
+        }
+        let (started_at, start_fraction) = self.started.unwrap();
+
+        let events_due = self.policy.every_events.is_some_and(|every| every > 0 && processed_count.saturating_sub(self.last_report_count) >= every);
+        let percent_due = match (self.policy.every_percent, fraction) {
+            | (Some(percent), Some(fraction)) => percent > 0.0 && (fraction - self.last_report_fraction) * 100.0 >= percent,
+            | _ => false,
+        };
+        if !events_due && !percent_due {
+            return;
+        }
+
+        let wall_elapsed = started_at.elapsed();
+        let eta = match (fraction, start_fraction) {
+            | (Some(fraction), Some(start_fraction)) if fraction > start_fraction => {
+                Some(wall_elapsed.mul_f64((1.0 - fraction) / (fraction - start_fraction)))
+            }
+            | _ => None,
+        };
+        self.last_report_count = processed_count;
+        if let Some(fraction) = fraction {
+            self.last_report_fraction = fraction;
+        }
+        (self.callback)(&ProgressReport { processed_count,
+                                          sim_timestamp: timestamp,
+                                          wall_elapsed,
+                                          fraction,
+                                          eta });
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn collecting_reporter(policy: ProgressPolicy) -> (ProgressReporter, Arc<Mutex<Vec<ProgressReport>>>)
+    {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        let reporter = ProgressReporter::new(policy, Box::new(move |report: &ProgressReport| sink.lock().unwrap().push(report.clone())));
+        (reporter, reports)
+    }
+
+    #[test]
+    fn test_for_dates_covers_whole_days()
+    {
+        let policy = ProgressPolicy::for_dates(NaiveDate::from_ymd_opt(2024, 5, 5).unwrap(), NaiveDate::from_ymd_opt(2024, 5, 6).unwrap());
+        assert_eq!(policy.time_range, Some((1_714_867_200_000, 1_715_040_000_000)));
+    }
+
+    #[test]
+    fn test_progress_reported_every_n_events()
+    {
+        let (mut reporter, reports) = collecting_reporter(ProgressPolicy::default().every_events(2));
+        for count in 1..=5 {
+            reporter.advance(count, count as i64);
+        }
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.iter().map(|report| report.processed_count).collect::<Vec<_>>(), vec![2, 4]);
+        // 未设置回放区间时无法估算完成比例
+        assert!(reports.iter().all(|report| report.fraction.is_none() && report.eta.is_none()));
+    }
+
+    #[test]
+    fn test_smaller_processed_count_does_not_underflow()
+    {
+        let (mut reporter, reports) = collecting_reporter(ProgressPolicy::default().every_events(2));
+        reporter.advance(4, 4);
+        // 计数小于上次回调时的计数（例如调用方重新计数）时不触发回调，也不会溢出
+        reporter.advance(1, 5);
+        reporter.advance(6, 6);
+        assert_eq!(reports.lock().unwrap().iter().map(|report| report.processed_count).collect::<Vec<_>>(), vec![4, 6]);
+    }
+
+    #[test]
+    fn test_progress_reported_every_percent_of_date_range_with_eta()
+    {
+        let policy = ProgressPolicy { every_events: None,
+                                      every_percent: Some(25.0),
+                                      time_range: Some((0, 1_000)) };
+        let (mut reporter, reports) = collecting_reporter(policy);
+        for (count, timestamp) in [(1, 0), (2, 100), (3, 250), (4, 400), (5, 500), (6, 1_000)] {
+            reporter.advance(count, timestamp);
+        }
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.iter().map(|report| report.sim_timestamp).collect::<Vec<_>>(), vec![250, 500, 1_000]);
+        assert_eq!(reports[0].fraction, Some(0.25));
+        assert!(reports[0].eta.is_some());
+        assert_eq!(reports[2].eta, Some(Duration::ZERO));
+    }
+}
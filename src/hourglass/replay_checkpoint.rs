@@ -0,0 +1,244 @@
+use crate::{
+    error::ExchangeError,
+    hourglass::account::{account_checkpoint::AccountCheckpoint, HourglassAccount},
+    hourglass_log::warn,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// 自动写入回放检查点的触发条件，两个条件任一满足即写入。
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckpointPolicy
+{
+    pub path: PathBuf,                   // 检查点文件路径，每次写入覆盖上一次的检查点
+    pub every_events: Option<u64>,       // 每处理 N 条市场数据写入一次
+    pub every_sim_minutes: Option<i64>,  // 每经过 T 分钟的模拟时间写入一次（时间戳单位为毫秒）
+}
+
+/// 数据流中的回放位置。
+///
+/// 同一时间戳内的成交顺序由 [`TimestampSequencer`](crate::common::datafeed::timestamp_sequencer::TimestampSequencer)
+/// 保证确定，因此 `last_timestamp` 加上该时间戳内已处理的条数即可唯一定位数据流位置。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct ReplayPosition
+{
+    pub processed_count: u64,             // 已处理的总条数
+    pub last_timestamp: i64,              // 最后处理的数据时间戳
+    pub processed_at_last_timestamp: u64, // `last_timestamp` 上已处理的条数
+}
+
+impl ReplayPosition
+{
+    pub fn advance(&mut self, timestamp: i64)
+    {
+        self.processed_count += 1;
+        if self.processed_count > 1 && timestamp == self.last_timestamp {
+            self.processed_at_last_timestamp += 1;
+        }
+        else {
+            self.last_timestamp = timestamp;
+            self.processed_at_last_timestamp = 1;
+        }
+    }
+}
+
+/// 写入磁盘的回放检查点：账户状态与数据流位置。
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ReplayCheckpoint
+{
+    pub position: ReplayPosition,
+    pub account: AccountCheckpoint,
+}
+
+impl ReplayCheckpoint
+{
+    /// 先写入临时文件再重命名，避免进程在写入过程中崩溃留下损坏的检查点。
+    pub fn write_to(&self, path: &Path) -> Result<(), ExchangeError>
+    {
+        let json = serde_json::to_string(self).map_err(|e| ExchangeError::CheckpointError(e.to_string()))?;
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json).map_err(|e| ExchangeError::CheckpointError(e.to_string()))?;
+        fs::rename(&tmp_path, path).map_err(|e| ExchangeError::CheckpointError(e.to_string()))
+    }
+
+    pub fn read_from(path: &Path) -> Result<Self, ExchangeError>
+    {
+        let json = fs::read_to_string(path).map_err(|e| ExchangeError::CheckpointError(e.to_string()))?;
+        serde_json::from_str(&json).map_err(|e| ExchangeError::CheckpointError(e.to_string()))
+    }
+}
+
+/// 跟踪回放位置，决定何时写入检查点，以及恢复后需要跳过哪些数据。
+#[derive(Debug, Default)]
+pub struct ReplayCheckpointer
+{
+    policy: Option<CheckpointPolicy>,
+    position: ReplayPosition,
+    resume_target: Option<ReplayPosition>,
+    resumed_at: Option<i64>,
+    skipped_at_target_timestamp: u64,
+    last_checkpoint_count: u64,
+    last_checkpoint_timestamp: Option<i64>,
+}
+
+impl ReplayCheckpointer
+{
+    pub fn new(policy: Option<CheckpointPolicy>) -> Self
+    {
+        Self { policy, ..Default::default() }
+    }
+
+    pub fn position(&self) -> ReplayPosition
+    {
+        self.position
+    }
+
+    /// 从检查点位置恢复：之后到达的、不晚于该位置的数据都会被跳过。
+    pub fn resume_to(&mut self, target: ReplayPosition)
+    {
+        self.position = target;
+        self.resume_target = Some(target);
+        self.resumed_at = Some(target.last_timestamp);
+        self.skipped_at_target_timestamp = 0;
+        self.last_checkpoint_count = target.processed_count;
+        self.last_checkpoint_timestamp = Some(target.last_timestamp);
+    }
+
+    /// 判断该时间戳的数据是否在检查点之前已经处理过。
+    pub fn should_skip(&mut self, timestamp: i64) -> bool
+    {
+        let Some(target) = self.resume_target
+        else {
+            return false;
+        };
+
+        if timestamp < target.last_timestamp {
+            return true;
+        }
+        if timestamp == target.last_timestamp && self.skipped_at_target_timestamp < target.processed_at_last_timestamp {
+            self.skipped_at_target_timestamp += 1;
+            return true;
+        }
+
+        // 已经越过检查点位置，之后不再需要跳过
+        self.resume_target = None;
+        false
+    }
+
+    /// 判断该时间戳的强平事件是否在检查点之前已经处理过。
+    ///
+    /// 强平事件先于同一时间戳的成交处理，因此不晚于检查点时间戳的强平事件都已处理。
+    pub fn should_skip_liquidation(&self, timestamp: i64) -> bool
+    {
+        self.resumed_at.is_some_and(|resumed_at| timestamp <= resumed_at)
+    }
+
+    /// 记录一条已处理的数据，返回是否应当写入检查点。
+    pub fn advance(&mut self, timestamp: i64) -> bool
+    {
+        self.position.advance(timestamp);
+
+        let Some(policy) = &self.policy
+        else {
+            return false;
+        };
+        let last_checkpoint_timestamp = *self.last_checkpoint_timestamp.get_or_insert(timestamp);
+
+        let events_due = policy.every_events.is_some_and(|every| every > 0 && self.position.processed_count - self.last_checkpoint_count >= every);
+        let time_due = policy.every_sim_minutes.is_some_and(|minutes| minutes > 0 && timestamp - last_checkpoint_timestamp >= minutes * 60_000);
+        events_due || time_due
+    }
+
+    /// 按 [`CheckpointPolicy`] 写入检查点，失败时仅记录警告，不中断回放。
+    pub async fn write_checkpoint(&mut self, account: &HourglassAccount)
+    {
+        let Some(policy) = &self.policy
+        else {
+            return;
+        };
+        let checkpoint = ReplayCheckpoint { position: self.position,
+                                            account: account.checkpoint().await };
+        match checkpoint.write_to(&policy.path) {
+            | Ok(()) => self.mark_written(),
+            | Err(e) => warn!("Failed to write replay checkpoint: {:?}", e),
+        }
+    }
+
+    /// 检查点写入成功后调用，重置触发计数。
+    pub fn mark_written(&mut self)
+    {
+        self.last_checkpoint_count = self.position.processed_count;
+        self.last_checkpoint_timestamp = Some(self.position.last_timestamp);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_due_every_n_events()
+    {
+        let mut checkpointer = ReplayCheckpointer::new(Some(CheckpointPolicy { path: PathBuf::from("unused"),
+                                                                               every_events: Some(2),
+                                                                               every_sim_minutes: None }));
+        assert!(!checkpointer.advance(1));
+        assert!(checkpointer.advance(2));
+        checkpointer.mark_written();
+        assert!(!checkpointer.advance(3));
+        assert!(checkpointer.advance(4));
+    }
+
+    #[test]
+    fn test_checkpoint_due_every_sim_minutes()
+    {
+        let mut checkpointer = ReplayCheckpointer::new(Some(CheckpointPolicy { path: PathBuf::from("unused"),
+                                                                               every_events: None,
+                                                                               every_sim_minutes: Some(1) }));
+        assert!(!checkpointer.advance(0));
+        assert!(!checkpointer.advance(59_999));
+        assert!(checkpointer.advance(60_000));
+        checkpointer.mark_written();
+        assert!(!checkpointer.advance(60_001));
+    }
+
+    #[test]
+    fn test_resume_skips_processed_rows_including_same_timestamp()
+    {
+        let mut original = ReplayCheckpointer::new(None);
+        for timestamp in [1, 2, 2] {
+            original.advance(timestamp);
+        }
+        let target = original.position();
+        assert_eq!(target,
+                   ReplayPosition { processed_count: 3,
+                                    last_timestamp: 2,
+                                    processed_at_last_timestamp: 2 });
+
+        let mut resumed = ReplayCheckpointer::new(None);
+        resumed.resume_to(target);
+        let replayed: Vec<bool> = [1, 2, 2, 2, 3].iter().map(|timestamp| resumed.should_skip(*timestamp)).collect();
+        assert_eq!(replayed, vec![true, true, true, false, false]);
+        // 强平事件先于同一时间戳的成交处理，检查点时间戳及之前的都已处理
+        assert!(resumed.should_skip_liquidation(2));
+        assert!(!resumed.should_skip_liquidation(3));
+    }
+
+    #[test]
+    fn test_checkpoint_file_round_trip()
+    {
+        let path = std::env::temp_dir().join(format!("hourglass_replay_checkpoint_{}.json", std::process::id()));
+        let checkpoint = ReplayCheckpoint { position: ReplayPosition { processed_count: 10,
+                                                                      last_timestamp: 42,
+                                                                      processed_at_last_timestamp: 3 },
+                                            account: AccountCheckpoint::default() };
+        checkpoint.write_to(&path).unwrap();
+        let loaded = ReplayCheckpoint::read_from(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(loaded.position, checkpoint.position);
+    }
+}
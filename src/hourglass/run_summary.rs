@@ -0,0 +1,26 @@
+use crate::common::balance::TokenBalance;
+use serde::{Deserialize, Serialize};
+
+/// 交易所事件循环结束的原因。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ShutdownReason
+{
+    DataExhausted,    // 数据源已回放完毕
+    Requested,        // 客户端发送了 `HourglassClientEvent::Shutdown`
+    Idle,             // 超时未收到任何客户端事件
+    DataSourceFailed, // 数据源读取出错，错误信息见 `RunSummary::data_source_error`
+}
+
+/// [`HourglassExchange::start`](super::HourglassExchange::start) 退出时返回的运行摘要。
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RunSummary
+{
+    pub reason: ShutdownReason,
+    pub processed_count: u64,        // 已处理的市场数据条数
+    pub drained_events: usize,       // 退出前处理掉的排队客户端事件数
+    pub final_timestamp: i64,        // 退出时的交易所时间戳
+    pub open_orders: usize,          // 退出时仍在挂单簿中的订单数
+    pub balances: Vec<TokenBalance>, // 退出时的各币种余额
+    #[serde(default)]
+    pub data_source_error: Option<String>, // 因数据源出错结束回放时的错误信息
+}
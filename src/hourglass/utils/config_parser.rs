@@ -53,7 +53,7 @@ mod tests
     use super::*;
     use crate::{
         common::{account_positions::PositionDirectionMode, instrument::kind::InstrumentKind},
-        hourglass::account::account_config::{CommissionLevel, CommissionRates, MarginMode},
+        hourglass::account::account_config::{CommissionLevel, CommissionRates, FeeBasis, MarginMode},
     };
     use std::{fs, io::Write};
     use tempfile::tempdir;
@@ -115,8 +115,14 @@ mod tests
         assert_eq!(config.commission_level, CommissionLevel::Lv2);
         assert_eq!(config.global_leverage_rate, 1.0);
         assert_eq!(config.lazy_account_positions, false);
-        assert_eq!(config.fees_book.get(&InstrumentKind::Spot).cloned(), Some(CommissionRates { maker_fees: 0.001, taker_fees: 0.002 }));
-        assert_eq!(config.fees_book.get(&InstrumentKind::Perpetual).cloned(), Some(CommissionRates { maker_fees: 0.0005, taker_fees: 0.001 }));
+        assert_eq!(config.fees_book.get(&InstrumentKind::Spot).cloned(),
+                   Some(CommissionRates { maker_fees: 0.001,
+                                          taker_fees: 0.002,
+                                          fee_basis: FeeBasis::PercentNotional }));
+        assert_eq!(config.fees_book.get(&InstrumentKind::Perpetual).cloned(),
+                   Some(CommissionRates { maker_fees: 0.0005,
+                                          taker_fees: 0.001,
+                                          fee_basis: FeeBasis::PercentNotional }));
     }
 
     /// 测试配置文件缺失的情况
@@ -1,6 +1,7 @@
 use crate::common::datafeed::market_event::MarketEvent;
 /// NOTE code below is to be merged later
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 use crate::{
     common::{
@@ -9,7 +10,9 @@ use crate::{
             Instrument,
         },
         token::Token,
+        Side,
     },
+    error::ExchangeError,
     hourglass::clickhouse_api::datatype::clickhouse_trade_data::MarketTrade,
     Exchange,
 };
@@ -26,7 +29,8 @@ pub struct WsTrade
     px: String,
     #[serde(alias = "timestamp")]
     ts: String,
-    amount: f64,
+    #[serde(default, alias = "sz", alias = "size")]
+    amount: Option<f64>, // 部分交易所的逐笔推送不带成交量，此时为 `None`
 }
 
 // NOTE 这是按照Okex交易所API数据类型构建的 WebsocketTrade 数据结构，回测选用。
@@ -35,7 +39,7 @@ impl MarketEvent<WsTrade>
     pub fn from_ws_trade(ws_trade: WsTrade, base: String, quote: String, instrument: InstrumentKind) -> Self
     {
         let exchange_time = ws_trade.ts.parse::<i64>().unwrap_or(0);
-        let received_time = ws_trade.ts.parse::<i64>().unwrap_or(0); // NOTE 注意这是不对的 应该加上一个标准化的随机延迟。
+        let received_time = exchange_time; // 默认不含网络延迟，需要时用 `with_network_latency` 加上模拟延迟
 
         let instrument = Instrument { base: Token::from(base),
                                       quote: Token::from(quote),
@@ -56,7 +60,7 @@ impl MarketEvent<MarketTrade>
     pub fn from_swap_trade_clickhouse(trade: MarketTrade, base: String, quote: String) -> Self
     {
         let exchange_time = trade.timestamp;
-        let received_time = trade.timestamp; // NOTE 注意这是不对的 应该加上一个标准化的随机延迟。
+        let received_time = trade.timestamp; // 默认不含网络延迟，需要时用 `with_network_latency` 加上模拟延迟
 
         let instrument = Instrument { base: Token::from(base),
                                       quote: Token::from(quote),
@@ -79,12 +83,20 @@ impl From<MarketTrade> for WsTrade
                   side: trade.side,
                   px: trade.price.to_string(),
                   ts: trade.timestamp.to_string(),
-                  amount: trade.amount }
+                  amount: Some(trade.amount) }
     }
 }
 
+/// 拆分交易对符号中的基础货币与报价货币。
+///
+/// 以 `-` 分隔的符号（如 OKX 的 `BTC-USDT`、`BTC-USDT-SWAP`）取前两段，其余后缀由调用方解释；
+/// 不带分隔的符号（如 `BTCUSDT`）按常见报价货币后缀拆分，无法识别时报价货币为空字符串。
 pub fn parse_base_and_quote(symbol: &str) -> (String, String)
 {
+    if let Some((base, rest)) = symbol.split_once('-') {
+        let quote = rest.split('-').next().unwrap_or_default();
+        return (base.to_string(), quote.to_string());
+    }
     // 定义一个包含常见报价货币的数组
     let quote_assets = ["USDT", "USTC", "USDC", "USD", "UST", "DAI", "FDUSD", "BTC", "ETH", "EURT"];
     // 遍历所有已知的报价货币
@@ -99,6 +111,32 @@ pub fn parse_base_and_quote(symbol: &str) -> (String, String)
     (symbol.to_string(), String::new())
 }
 
+/// 把 OKX v5 的 `instId` 解析为 [`Instrument`]，金融工具类别由后缀决定：
+///
+/// - `BTC-USDT`：现货；
+/// - `BTC-USDT-SWAP`：永续合约；
+/// - `BTC-USD-240329`：交割合约，后缀为交割日期；
+/// - `BTC-USD-240329-60000-C`：期权，后缀为交割日期、行权价与看涨/看跌。
+pub fn parse_okx_inst_id(inst_id: &str) -> Result<Instrument, ExchangeError>
+{
+    let invalid = || ExchangeError::InvalidInstrument(format!("Cannot parse OKX instId '{}'", inst_id));
+    let inst_id = inst_id.trim();
+    let (base, quote) = parse_base_and_quote(inst_id);
+    if base.is_empty() || quote.is_empty() {
+        return Err(invalid());
+    }
+
+    let is_expiry = |expiry: &str| !expiry.is_empty() && expiry.chars().all(|c| c.is_ascii_digit());
+    let kind = match inst_id.split('-').skip(2).collect::<Vec<_>>().as_slice() {
+        | [] => InstrumentKind::Spot,
+        | [suffix] if suffix.eq_ignore_ascii_case("SWAP") => InstrumentKind::Perpetual,
+        | [expiry] if is_expiry(expiry) => InstrumentKind::Future,
+        | [expiry, _strike, "C" | "P"] if is_expiry(expiry) => InstrumentKind::CryptoOption,
+        | _ => return Err(invalid()),
+    };
+    Ok(Instrument::new(base, quote, kind))
+}
+
 #[allow(dead_code)]
 impl WsTrade
 {
@@ -109,6 +147,75 @@ impl WsTrade
                   side: trade.side.clone(),
                   px: trade.price.to_string(),
                   ts: trade.timestamp.to_string(),
-                  amount: trade.amount }
+                  amount: Some(trade.amount) }
+    }
+
+    /// 转换为撮合使用的 [`MarketTrade`]。成交量缺失时 `amount` 记为 0，撮合器会按
+    /// [`UnsizedTradeHandling`](crate::hourglass::account::account_config::UnsizedTradeHandling) 处理这类成交。
+    /// 价格或时间戳无法解析或无效时返回 `ExchangeError::DataSourceError`，而不是按 0 参与撮合。
+    pub fn to_market_trade(&self, exchange: &str) -> Result<MarketTrade, ExchangeError>
+    {
+        let price = self.px.parse().map_err(|_| ExchangeError::DataSourceError(format!("Invalid price {:?} for {} trade", self.px, self.instId)))?;
+        let timestamp = self.ts.parse().map_err(|_| ExchangeError::DataSourceError(format!("Invalid timestamp {:?} for {} trade", self.ts, self.instId)))?;
+        MarketTrade { exchange: exchange.to_string(),
+                      symbol: self.instId.clone(),
+                      side: self.side.clone(),
+                      price,
+                      timestamp,
+                      amount: self.amount.unwrap_or(0.0) }.validated()
+    }
+
+    /// 按 OKX 的 `instId` 格式解析成交所属的金融工具，见 [`parse_okx_inst_id`]。
+    pub fn instrument(&self) -> Result<Instrument, ExchangeError>
+    {
+        parse_okx_inst_id(&self.instId)
+    }
+
+    /// 解析外部成交的主动方方向，口径与 [`MarketTrade::aggressor_side`] 一致。
+    pub fn aggressor_side(&self) -> Option<Side>
+    {
+        Side::from_str(self.side.trim()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn test_parse_base_and_quote_with_and_without_dashes()
+    {
+        assert_eq!(parse_base_and_quote("BTCUSDT"), ("BTC".to_string(), "USDT".to_string()));
+        assert_eq!(parse_base_and_quote("BTC-USDT"), ("BTC".to_string(), "USDT".to_string()));
+        assert_eq!(parse_base_and_quote("ETH-USD-SWAP"), ("ETH".to_string(), "USD".to_string()));
+    }
+
+    #[test]
+    fn test_okx_inst_id_normalized_by_suffix()
+    {
+        assert_eq!(parse_okx_inst_id("BTC-USDT").unwrap(), Instrument::new("BTC", "USDT", InstrumentKind::Spot));
+        assert_eq!(parse_okx_inst_id("BTC-USDT-SWAP").unwrap(), Instrument::new("BTC", "USDT", InstrumentKind::Perpetual));
+        assert_eq!(parse_okx_inst_id("BTC-USD-240329").unwrap(), Instrument::new("BTC", "USD", InstrumentKind::Future));
+        assert_eq!(parse_okx_inst_id("BTC-USD-240329-60000-C").unwrap(), Instrument::new("BTC", "USD", InstrumentKind::CryptoOption));
+        for inst_id in ["BTC", "BTC-USDT-PERP", "BTC-USD-240329-60000-X"] {
+            assert!(matches!(parse_okx_inst_id(inst_id), Err(ExchangeError::InvalidInstrument(_))));
+        }
+    }
+
+    #[test]
+    fn test_to_market_trade_rejects_unparsable_price_and_timestamp()
+    {
+        let trade = |px: &str, ts: &str| WsTrade { instId: "BTC-USDT-SWAP".to_string(),
+                                                  side: "buy".to_string(),
+                                                  px: px.to_string(),
+                                                  ts: ts.to_string(),
+                                                  amount: Some(1.0) };
+        let market_trade = trade("60000.5", "1700000000000").to_market_trade("okex").unwrap();
+        assert_eq!(market_trade.price, 60000.5);
+        assert_eq!(market_trade.timestamp, 1700000000000);
+        for (px, ts) in [("", "1700000000000"), ("abc", "1700000000000"), ("0", "1700000000000"), ("NaN", "1700000000000"), ("60000.5", ""), ("60000.5", "-1")] {
+            assert!(matches!(trade(px, ts).to_market_trade("okex"), Err(ExchangeError::DataSourceError(_))), "px={:?} ts={:?}", px, ts);
+        }
     }
 }
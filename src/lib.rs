@@ -1,14 +1,15 @@
 use crate::{
     common::{
-        account_positions::{AccountPositions, Position},
+        account_positions::{positions_snapshot::AccountPositionsSnapshot, AccountPositions, Position},
         balance::TokenBalance,
         event::AccountEvent,
         instrument::Instrument,
         order::{
-            states::{cancelled::Cancelled, request_cancel::RequestCancel, request_open::RequestOpen},
+            states::{cancelled::Cancelled, request_amend::RequestAmend, request_cancel::RequestCancel, request_open::RequestOpen},
             Order,
         },
         token::Token,
+        trade::ClientTrade,
     },
     error::ExchangeError,
 };
@@ -43,9 +44,11 @@ pub trait ClientExecution
     async fn fetch_long_position(&self, instrument: Instrument) -> Result<Option<Position>, ExchangeError>; // 补全 FetchShortPosition 的实现
     async fn fetch_short_position(&self, instrument: Instrument) -> Result<Option<Position>, ExchangeError>;
     // async fn fetch_balance(&self) -> Result<TokenBalance, ExchangeError>; // TODO
-    // async fn fetch_positions(&self) -> Result<AccountPositions, ExchangeError>;  // TODO
+    async fn fetch_positions(&self) -> Result<AccountPositionsSnapshot, ExchangeError>; // 仓位快照，之后的仓位变化不会反映到结果中
+    async fn fetch_trades(&self, instrument: Instrument) -> Result<Vec<ClientTrade>, ExchangeError>;
     async fn open_orders(&self, open_requests: Vec<Order<RequestOpen>>) -> Vec<Result<Order<Open>, ExchangeError>>;
     async fn cancel_orders(&self, cancel_requests: Vec<Order<RequestCancel>>) -> Vec<Result<Order<Cancelled>, ExchangeError>>;
+    async fn amend_orders(&self, amend_requests: Vec<Order<RequestAmend>>) -> Vec<Result<Order<Open>, ExchangeError>>;
     async fn cancel_orders_all(&self) -> Result<Vec<Order<Cancelled>>, ExchangeError>; // 实现 DepositTokens 的处理逻辑
     async fn deposit_tokens(&self, deposits: Vec<(Token, f64)>) -> Result<Vec<TokenBalance>, ExchangeError>;
     // 发送 LetItRoll 命令的函数
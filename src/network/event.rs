@@ -39,7 +39,8 @@
 ///                               side: Side::Buy,                                                       // 买卖方向
 ///                               state: RequestOpen { reduce_only: false, // 非减仓订单
 ///                                                    price: 50000.0,     // 下单价格
-///                                                    size: 1.0           /* 下单数量 */ } }];
+///                                                    size: 1.0,          // 下单数量
+///                                                    tag: None           /* 策略标签 */ } }];
 ///
 ///     // 序列化 orders 为 JSON 字符串
 ///     let payload = serde_json::to_string(&orders).expect("Failed to serialize orders");
@@ -72,9 +73,9 @@
 /// `NetworkEvent` 结构体旨在简化事件的创建和传递。使用 `NetworkEvent` 可以确保事件的数据格式统一，便于服务器端的解析和处理。
 ///
 /// 客户端在构建 `NetworkEvent` 时，需要确保提供的 `event_type` 是有效的，并且 `payload` 是与该事件类型匹配的有效数据。
-use crate::common::order::Order;
+use crate::common::{instrument::Instrument, order::Order};
 use crate::{
-    common::order::states::{request_cancel::RequestCancel, request_open::RequestOpen},
+    common::order::states::{request_amend::RequestAmend, request_cancel::RequestCancel, request_open::RequestOpen},
     hourglass::hourglass_client_local_mode::HourglassClientEvent,
 };
 use log::error;
@@ -104,6 +105,16 @@ impl NetworkEvent
                 let (response_tx, _response_rx) = oneshot::channel();
                 Ok(HourglassClientEvent::FetchOrdersOpen(response_tx))
             }
+            | "FetchPositions" => {
+                let (response_tx, _response_rx) = oneshot::channel();
+                Ok(HourglassClientEvent::FetchPositions(response_tx))
+            }
+            | "FetchTrades" => {
+                // 解析 payload 为 Instrument 类型
+                let instrument: Instrument = serde_json::from_str(&self.payload).map_err(|e| format!("Failed to parse FetchTrades payload: {}", e))?;
+                let (response_tx, _response_rx) = oneshot::channel();
+                Ok(HourglassClientEvent::FetchTrades(instrument, response_tx))
+            }
             | "FetchBalances" => {
                 let (response_tx, _response_rx) = oneshot::channel();
                 Ok(HourglassClientEvent::FetchTokenBalances(response_tx))
@@ -120,6 +131,12 @@ impl NetworkEvent
                 let (response_tx, _response_rx) = oneshot::channel();
                 Ok(HourglassClientEvent::CancelOrders((orders, response_tx)))
             }
+            | "AmendOrders" => {
+                // 解析 payload 为 Vec<Order<RequestAmend>> 类型
+                let orders: Vec<Order<RequestAmend>> = serde_json::from_str(&self.payload).map_err(|e| format!("Failed to parse AmendOrders payload: {}", e))?;
+                let (response_tx, _response_rx) = oneshot::channel();
+                Ok(HourglassClientEvent::AmendOrders((orders, response_tx)))
+            }
             | "CancelOrdersAll" => {
                 let (response_tx, _response_rx) = oneshot::channel();
                 Ok(HourglassClientEvent::CancelOrdersAll(response_tx))
@@ -163,7 +180,8 @@ mod tests
                                   side: Side::Buy,                                                       // 买卖方向
                                   state: RequestOpen { reduce_only: false, // 非减仓订单
                                                        price: 50000.0,     // 下单价格
-                                                       size: 1.0           /* 下单数量 */ } }];
+                                                       size: 1.0,          // 下单数量
+                                                       tag: None           /* 策略标签 */ } }];
 
         // 序列化 orders 为 JSON 字符串
         let payload = serde_json::to_string(&orders).expect("Failed to serialize orders");
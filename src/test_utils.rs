@@ -10,11 +10,12 @@ use crate::{
         },
         balance::Balance,
         instrument::{
+            alias::InstrumentAliasRegistry,
             kind::{InstrumentKind, InstrumentKind::Perpetual},
             Instrument,
         },
         order::{
-            identification::{client_order_id::ClientOrderId, machine_id::generate_machine_id, OrderId},
+            identification::{client_order_id::ClientOrderId, OrderId},
             order_instructions::OrderInstruction,
             states::{open::Open, request_open::RequestOpen},
             Order, OrderRole,
@@ -24,7 +25,8 @@ use crate::{
     },
     hourglass::{
         account::{
-            account_config::{AccountConfig, CommissionLevel, CommissionRates, HourglassMode, MarginMode},
+            account_commission::FeesBookCommission,
+            account_config::{AccountConfig, CommissionLevel, CommissionRates, CostBasisMethod, FeeBasis, FillPricePolicy, FilterViolationHandling, HourglassMode, InvariantCheckMode, MakerFillTrigger, MarginMode, NetModeOppositeBehavior, RoundingConfig, SlippageModel, SpreadLegRisk, UnsizedTradeHandling},
             account_latency::{AccountLatency, FluctuationMode},
             account_orders::AccountOrders,
             HourglassAccount,
@@ -34,11 +36,9 @@ use crate::{
     Exchange,
 };
 use dashmap::DashMap;
-use rand::Rng;
 use std::{
     collections::HashMap,
     sync::{atomic::AtomicI64, Arc},
-    time::{SystemTime, UNIX_EPOCH},
 };
 use tokio::sync::{Mutex, RwLock};
 use uuid::Uuid;
@@ -66,13 +66,44 @@ pub fn create_test_account_configuration() -> AccountConfig
                     execution_mode: HourglassMode::Backtest,
                     max_price_deviation: 0.05,
                     lazy_account_positions: false,
-                    liquidation_threshold: 0.9 }
+                    liquidation_threshold: 0.9,
+                    fill_price_policy: FillPricePolicy::RestingLimit,
+                    warmup_until_ts: None,
+                    order_to_trade_limit: None,
+                    max_open_orders_per_instrument: None,
+                    instrument_aliases: InstrumentAliasRegistry::default(),
+                    leveraged_token_daily_fee_rate: None,
+                    rounding: RoundingConfig::default(),
+                    invariant_check: InvariantCheckMode::Off,
+                    maker_fill_trigger: MakerFillTrigger::OnTradeThrough,
+                    contract_multipliers: Vec::new(),
+                    machine_id: None,
+                    seed: None,
+                    cost_basis_method: CostBasisMethod::Average,
+                    cancel_latency_ms: None,
+                    synthetic_depth: None,
+                    own_fills_on_tape: false,
+                    margin_update: None,
+                    matching_algorithms: Vec::new(),
+                    initial_margin_rates: Vec::new(),
+                    net_mode_opposite_behavior: NetModeOppositeBehavior::Reject,
+                    withdrawal_rules: Vec::new(),
+                    stale_order_policy: None,
+                    unsized_trade_handling: UnsizedTradeHandling::TouchOnly,
+                    spread_leg_risk: SpreadLegRisk::Reject,
+                    slippage_model: SlippageModel::None,
+                    funding_interval_ms: None,
+                    volume_tiers: Vec::new(),
+                    maintenance_margin_rate: None,
+                    instrument_filters: Vec::new(),
+                    filter_violation: FilterViolationHandling::Reject,
+                    trade_history_limit: None }
 }
 // 帮助函数，用于创建测试用的 AccountOrders 实例
 pub async fn create_test_account_orders() -> AccountOrders
 {
     let instruments = vec![Instrument::new("BTC", "USD", InstrumentKind::Spot)];
-    let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10);
+    let account_latency = AccountLatency::new(FluctuationMode::Sine, 100, 10, 0);
     AccountOrders::new(123124, instruments, account_latency).await
 }
 
@@ -91,29 +122,30 @@ pub fn create_test_order_open(side: Side, price: f64, size: f64) -> Order<Open>
                           price,
                           size,
                           filled_quantity: 0.0,         // 初始填充数量为0
-                          order_role: OrderRole::Taker  /* 假设订单角色为 Taker */ } }
+                          avg_fill_price: 0.0,
+                          reduce_only: false,
+                          order_role: OrderRole::Taker  /* 假设订单角色为 Taker */,
+                          tag: None,
+                          iceberg: None } }
 }
 
-// 帮助函数，用于创建测试用的订单
+// 帮助函数，用于创建测试用的订单，`cid` 由固定的时间戳与计数器生成，每次调用结果相同
 pub fn create_test_request_open(base: &str, quote: &str) -> Order<RequestOpen>
 {
-    let machine_id = generate_machine_id().unwrap();
-    let mut rng = rand::thread_rng();
-    let counter = rng.gen_range(0..10);
-    let now_ts = SystemTime::now().duration_since(UNIX_EPOCH).expect("时间出现倒退").as_millis() as u64;
-
-    let order_id = OrderId::new(now_ts, machine_id, counter);
+    let timestamp = 1625247600000;
+    let order_id = OrderId::new(timestamp as u64, 0, 0);
     Order { instruction: OrderInstruction::Market,
             exchange: Exchange::Hourglass,
             instrument: Instrument { base: Token::from(base),
                                      quote: Token::from(quote),
                                      kind: InstrumentKind::Spot },
-            timestamp: 1625247600000,
+            timestamp,
             cid: Some(ClientOrderId(format!("CID{}", order_id.0 % 1_000_000))),
             side: Side::Buy,
             state: RequestOpen { price: 50000.0,
                                  size: 1.0,
-                                 reduce_only: false } }
+                                 reduce_only: false,
+                                 tag: None } }
 }
 
 pub async fn create_test_account() -> HourglassAccount
@@ -123,7 +155,9 @@ pub async fn create_test_account() -> HourglassAccount
     balances.insert(Token::from("ETH"), Balance::new(10.0, 10.0));
     balances.insert(Token::from("USDT"), Balance::new(10_000.0, 10_000.0));
 
-    let commission_rates = CommissionRates { maker_fees: 0.001, taker_fees: 0.002 };
+    let commission_rates = CommissionRates { maker_fees: 0.001,
+                                             taker_fees: 0.002,
+                                             fee_basis: FeeBasis::PercentNotional };
 
     let mut account_config = AccountConfig { margin_mode: MarginMode::SingleCurrencyMargin,
                                              global_position_direction_mode: PositionDirectionMode::Net,
@@ -135,14 +169,45 @@ pub async fn create_test_account() -> HourglassAccount
                                              fees_book: HashMap::new(),
                                              execution_mode: HourglassMode::Backtest,
                                              lazy_account_positions: false,
-                                             liquidation_threshold: 0.9 };
+                                             liquidation_threshold: 0.9,
+                                             fill_price_policy: FillPricePolicy::RestingLimit,
+                                             warmup_until_ts: None,
+                                             order_to_trade_limit: None,
+                                             max_open_orders_per_instrument: None,
+                                             instrument_aliases: InstrumentAliasRegistry::default(),
+                                             leveraged_token_daily_fee_rate: None,
+                                             rounding: RoundingConfig::default(),
+                                             invariant_check: InvariantCheckMode::Off,
+                                             maker_fill_trigger: MakerFillTrigger::OnTradeThrough,
+                                             contract_multipliers: Vec::new(),
+                                             machine_id: Some(1),
+                                             seed: None,
+                                             cost_basis_method: CostBasisMethod::Average,
+                                             cancel_latency_ms: None,
+                                             synthetic_depth: None,
+                                             own_fills_on_tape: false,
+                                             margin_update: None,
+                                             matching_algorithms: Vec::new(),
+                                             initial_margin_rates: Vec::new(),
+                                             net_mode_opposite_behavior: NetModeOppositeBehavior::Reject,
+                                             withdrawal_rules: Vec::new(),
+                                             stale_order_policy: None,
+                                             unsized_trade_handling: UnsizedTradeHandling::TouchOnly,
+                                             spread_leg_risk: SpreadLegRisk::Reject,
+                                             slippage_model: SlippageModel::None,
+                                             funding_interval_ms: None,
+                                             volume_tiers: Vec::new(),
+                                             maintenance_margin_rate: None,
+                                             instrument_filters: Vec::new(),
+                                             filter_violation: FilterViolationHandling::Reject,
+                                             trade_history_limit: None };
 
     account_config.fees_book.insert(Perpetual, commission_rates);
 
     let positions = AccountPositions::init();
     let closed_positions = AccountExitedPositions::init();
 
-    let machine_id = generate_machine_id().unwrap();
+    let machine_id = account_config.resolve_machine_id().unwrap();
 
     let mut single_level_order_books = HashMap::new();
     single_level_order_books.insert(Instrument { base: Token::new("ETH".to_string()),
@@ -162,13 +227,19 @@ pub async fn create_test_account() -> HourglassAccount
                        balances,
                        positions,
                        exited_positions: closed_positions,
-                       account_open_book: Arc::new(RwLock::new(AccountOrders::new(machine_id, vec![Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual))], AccountLatency { fluctuation_mode:
-                                                                                                                                                                                       FluctuationMode::Sine,
-                                                                                                                                                                                   maximum: 300,
-                                                                                                                                                                                   minimum: 0,
-                                                                                                                                                                                   current_value: 0 }).await)),
+                       account_open_book: Arc::new(RwLock::new(AccountOrders::new(machine_id, vec![Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual))], AccountLatency::new(FluctuationMode::Sine, 300, 0, 0)).await)),
                        single_level_order_book: Arc::new(Mutex::new(single_level_order_books)),
-                       account_margin: Arc::new(0.0.into()) }
+                       account_margin: Arc::new(0.0.into()),
+                       order_flow: Default::default(),
+                       traded_volume: Default::default(),
+                       spot_cost_basis: DashMap::new(),
+                       depth_order_books: Arc::new(Mutex::new(HashMap::new())),
+                       own_fill_prints: Vec::new(),
+                       trade_history: HashMap::new(),
+                       last_margin_update: None,
+                       last_funding_ts: None,
+                       commission_provider: Arc::new(FeesBookCommission),
+                       matching_engines: HashMap::new() }
 }
 
 /// 创建一个测试用的 `PerpetualPosition` 实例。
@@ -188,7 +259,9 @@ pub fn create_test_perpetual_position(instrument: Instrument) -> PerpetualPositi
                                              current_symbol_price: 0.0,
                                              current_avg_price: 0.0,
                                              unrealised_pnl: 0.0,
-                                             realised_pnl: 0.0 },
+                                             realised_pnl: 0.0,
+                                             contract_multiplier: 1.0,
+                                             funding_pnl: 0.0 },
                         pos_config: PerpetualPositionConfig { pos_margin_mode: PositionMarginMode::Cross,
                                                               leverage: 1.0,
                                                               position_direction_mode: PositionDirectionMode::LongShort },
@@ -213,7 +286,9 @@ pub fn create_test_future_position_with_side(instrument: Instrument, side: Side)
                                           current_symbol_price: 0.0,
                                           current_avg_price: 0.0,
                                           unrealised_pnl: 0.0,
-                                          realised_pnl: 0.0 },
+                                          realised_pnl: 0.0,
+                                          contract_multiplier: 1.0,
+                                          funding_pnl: 0.0 },
                      pos_config: FuturePositionConfig { pos_margin_mode: PositionMarginMode::Cross,
                                                         leverage: 1.0,
                                                         position_direction_mode: PositionDirectionMode::LongShort },
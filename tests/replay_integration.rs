@@ -0,0 +1,123 @@
+//! 不依赖 ClickHouse 的端到端回放：加载固定的成交夹具 -> 撮合挂单 -> 检查成交、余额与仓位事件。
+//!
+//! 夹具 `tests/fixtures/ethusdt_replay.json` 共四笔 ETHUSDT 成交：
+//!
+//! 1. 16450 的主动买入，不触及任何挂单；
+//! 2. 16380 的主动卖出，穿过 16400 的买单，买单 0.3 全部成交，开多 0.3；
+//! 3. 16520 的主动买入，数量 0.1，穿过 16500 的卖单，成交 0.1 并减仓；
+//! 4. 16510 的主动买入，卖单剩余的 0.1 成交，再次减仓，最终持有多头 0.1。
+use hourglass::{
+    common::{
+        account_positions::{PositionDirectionMode, PositionMarginMode},
+        datafeed::mock_data_source::MockDataSource,
+        event::AccountEventKind,
+        instrument::{kind::InstrumentKind, Instrument},
+        order::{identification::client_order_id::ClientOrderId, order_instructions::OrderInstruction, states::request_open::RequestOpen, Order},
+        token::Token,
+        Side,
+    },
+    hourglass::{account::account_handlers::balance_handler::BalanceHandler, config_request::ConfigurationRequest, hourglass_client_local_mode::HourglassClientEvent, run_summary::ShutdownReason, DataSource, HourglassExchange},
+    test_utils::create_test_account,
+    Exchange,
+};
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+const FIXTURE: &str = include_str!("fixtures/ethusdt_replay.json");
+
+fn limit_order(cid: &str, side: Side, price: f64, size: f64) -> Order<RequestOpen>
+{
+    Order { instruction: OrderInstruction::Limit,
+            exchange: Exchange::Hourglass,
+            instrument: Instrument::new("ETH", "USDT", InstrumentKind::Perpetual),
+            timestamp: 1625247600000,
+            cid: Some(ClientOrderId(cid.into())),
+            side,
+            state: RequestOpen { reduce_only: false,
+                                 price,
+                                 size,
+                                 tag: None } }
+}
+
+#[tokio::test]
+async fn replay_fixture_end_to_end()
+{
+    let instrument = Instrument::new("ETH", "USDT", InstrumentKind::Perpetual);
+    let (account_event_tx, mut account_event_rx) = mpsc::unbounded_channel();
+    let (client_event_tx, client_event_rx) = mpsc::unbounded_channel();
+    let (market_event_tx, _market_event_rx) = mpsc::unbounded_channel();
+
+    let mut account = create_test_account().await;
+    account.account_event_tx = account_event_tx;
+    let account = Arc::new(Mutex::new(account));
+
+    let data_source = MockDataSource::from_json(FIXTURE).expect("Failed to parse trade fixture");
+    assert_eq!(data_source.len(), 4);
+    let exchange = HourglassExchange::builder().event_hourglass_rx(client_event_rx)
+                                               .account(Arc::clone(&account))
+                                               .market_event_tx(market_event_tx)
+                                               .data_source(DataSource::Mock(data_source))
+                                               .initiate()
+                                               .expect("Failed to build HourglassExchange");
+    let run = tokio::spawn(exchange.start());
+
+    // 多空两个方向都配置为单向持仓
+    let config_requests = [Side::Buy, Side::Sell].map(|side| ConfigurationRequest { exchange: Exchange::Hourglass,
+                                                                                    instrument: instrument.clone(),
+                                                                                    timestamp: 1625247600000,
+                                                                                    cid: None,
+                                                                                    leverage_rate: Some(1.0),
+                                                                                    side,
+                                                                                    position_margin_mode: Some(PositionMarginMode::Cross),
+                                                                                    position_direction_mode: Some(PositionDirectionMode::Net) });
+    let (response_tx, response_rx) = oneshot::channel();
+    client_event_tx.send(HourglassClientEvent::ConfigureInstruments(config_requests.to_vec(), response_tx)).unwrap();
+    assert!(response_rx.await.unwrap().iter().all(|result| result.is_ok()));
+
+    // 先挂出一笔买单与一笔卖单，再逐条回放夹具
+    let (response_tx, response_rx) = oneshot::channel();
+    client_event_tx.send(HourglassClientEvent::OpenOrders((vec![limit_order("bid", Side::Buy, 16400.0, 0.3), limit_order("ask", Side::Sell, 16500.0, 0.2)], response_tx)))
+                   .unwrap();
+    let open_results = response_rx.await.unwrap();
+    assert!(open_results.iter().all(|result| result.is_ok()), "{:?}", open_results);
+
+    for _ in 0..4 {
+        client_event_tx.send(HourglassClientEvent::LetItRoll).unwrap();
+    }
+    client_event_tx.send(HourglassClientEvent::Shutdown).unwrap();
+    let summary = run.await.unwrap();
+    assert_eq!(summary.reason, ShutdownReason::Requested);
+    assert_eq!(summary.processed_count, 4);
+    assert_eq!(summary.open_orders, 0);
+
+    // 成交：买单一次成交，卖单分两次成交，均按挂单价与 Maker 费率
+    let mut trades = Vec::new();
+    let mut realised_pnl = Vec::new();
+    while let Ok(event) = account_event_rx.try_recv() {
+        match event.kind {
+            | AccountEventKind::Trade(trade) => trades.push(trade),
+            | AccountEventKind::RealisedPnl(pnl) => realised_pnl.push(pnl),
+            | _ => {}
+        }
+    }
+    assert_eq!(trades.iter().map(|trade| (trade.side, trade.price, trade.size)).collect::<Vec<_>>(),
+               vec![(Side::Buy, 16400.0, 0.3), (Side::Sell, 16500.0, 0.1), (Side::Sell, 16500.0, 0.1)]);
+    let fees: f64 = trades.iter().map(|trade| trade.fees).sum();
+    assert!((fees - (16400.0 * 0.3 + 16500.0 * 0.2) * 0.001).abs() < 1e-9);
+
+    // 两次减仓各实现 (16500 - 16400) * 0.1，扣除各自的手续费
+    assert_eq!(realised_pnl.len(), 2);
+    assert!(realised_pnl.iter().all(|pnl| (pnl.amount - (10.0 - 1.65)).abs() < 1e-9));
+
+    // 余额：永续合约成交从报价货币中扣除手续费，两次减仓的实现盈亏计入报价货币
+    let account = account.lock().await;
+    let usdt = account.get_balance(&Token::from("USDT")).unwrap();
+    assert!((usdt.total - (10_000.0 - fees + 2.0 * 10.0)).abs() < 1e-9);
+
+    // 仓位：最终持有多头 0.1，均价为买单价
+    let long_positions = account.positions.perpetual_pos_long.read().await;
+    let position = long_positions.get(&instrument).expect("Expected a long position");
+    assert!((position.meta.current_size - 0.1).abs() < 1e-9);
+    assert_eq!(position.meta.current_avg_price, 16400.0);
+    assert!(account.positions.perpetual_pos_short.read().await.get(&instrument).is_none());
+}
@@ -25,6 +25,7 @@ use hourglass::{
     },
     hourglass::{
         account::{
+            account_commission::FeesBookCommission,
             account_latency::{AccountLatency, FluctuationMode},
             account_orders::AccountOrders,
             HourglassAccount,
@@ -55,10 +56,7 @@ pub async fn run_sample_exchange(event_account_tx: mpsc::UnboundedSender<Account
     let closed_positions = AccountExitedPositions::init();
 
     let instrument = Instrument::from(("ETH", "USDT", InstrumentKind::Perpetual));
-    let account_orders = AccountOrders::new(0, vec![instrument.clone()], AccountLatency { fluctuation_mode: FluctuationMode::Sine,
-                                                                                          maximum: 10,  // Example value
-                                                                                          minimum: -10, // Example value
-                                                                                          current_value: 0 }).await;
+    let account_orders = AccountOrders::new(0, vec![instrument.clone()], AccountLatency::new(FluctuationMode::Sine, 10, -10, 0)).await;
 
     // Create and insert a test order
     let test_order = Order { instruction: OrderInstruction::Limit,
@@ -71,7 +69,11 @@ pub async fn run_sample_exchange(event_account_tx: mpsc::UnboundedSender<Account
                                            price: 16499.0,
                                            size: 1.0,
                                            filled_quantity: 0.0,
-                                           order_role: OrderRole::Maker } };
+                                           avg_fill_price: 0.0,
+                                           reduce_only: false,
+                                           order_role: OrderRole::Maker,
+                                           tag: None,
+                                           iceberg: None } };
 
     // Directly modify the orders within the RwLock
     {
@@ -102,7 +104,17 @@ pub async fn run_sample_exchange(event_account_tx: mpsc::UnboundedSender<Account
                                                              positions,
                                                              exited_positions: closed_positions,
                                                              account_event_tx: event_account_tx,
-                                                             account_margin: Arc::new(Default::default()) }));
+                                                             account_margin: Arc::new(Default::default()),
+                                                             order_flow: Default::default(),
+                                                             traded_volume: Default::default(),
+                                                             spot_cost_basis: DashMap::new(),
+                                                             depth_order_books: Arc::new(Mutex::new(HashMap::new())),
+                                                             own_fill_prints: Vec::new(),
+                                                             trade_history: HashMap::new(),
+                                                             last_margin_update: None,
+                                                             last_funding_ts: None,
+                                                             commission_provider: Arc::new(FeesBookCommission),
+                                                             matching_engines: HashMap::new() }));
     let clickhouse_client = ClickHouseClient::new();
     let exchange = "binance";
     let instrument = "futures";
@@ -147,7 +159,8 @@ pub fn order_request_limit<I>(instrument: I, cid: ClientOrderId, side: Side, pri
             side,
             state: RequestOpen { reduce_only: false, // 假设创建的订单不是 reduce_only
                                  price,
-                                 size: quantity } }
+                                 size: quantity,
+                                 tag: None } }
 }
 
 /// 创建开放订单
@@ -164,7 +177,11 @@ pub fn open_order<I>(instrument: I, cid: ClientOrderId, id: OrderId, side: Side,
                           price,
                           size: quantity,
                           filled_quantity: filled,
-                          order_role: OrderRole::Maker } }
+                          avg_fill_price: if filled > 0.0 { price } else { 0.0 },
+                          reduce_only: false,
+                          order_role: OrderRole::Maker,
+                          tag: None,
+                          iceberg: None } }
 }
 
 /// 创建订单取消请求